@@ -0,0 +1,2 @@
+-- JSON array of progress snapshots recorded during the replay
+ALTER TABLE runs ADD COLUMN progress_timeline TEXT;
@@ -0,0 +1,2 @@
+-- JSON summary of the replay report (counts per rule, exit signal, duration)
+ALTER TABLE runs ADD COLUMN report_summary TEXT;
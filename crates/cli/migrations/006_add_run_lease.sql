@@ -0,0 +1,4 @@
+-- Claimed runs are leased to a worker so several replays can run in parallel; leases may be
+-- held by workers on other machines sharing the database
+ALTER TABLE runs ADD COLUMN claimed_by TEXT;
+ALTER TABLE runs ADD COLUMN lease_expires_at DATETIME;
@@ -0,0 +1,2 @@
+-- Runs with a higher priority are processed first
+ALTER TABLE runs ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,7 @@
+-- Whether each daemon instance last stopped cleanly
+CREATE TABLE daemon_state (
+    instance_id TEXT PRIMARY KEY NOT NULL,
+    started_at TEXT NOT NULL,
+    stopped_at TEXT,
+    clean_shutdown BOOLEAN NOT NULL DEFAULT FALSE
+);
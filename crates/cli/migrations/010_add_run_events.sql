@@ -0,0 +1,13 @@
+-- History of status changes; kept when runs are deleted
+CREATE TABLE run_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id TEXT NOT NULL,
+    old_status TEXT,
+    new_status TEXT NOT NULL,
+    reason TEXT,
+    -- Who made the change: daemon, admin, http_api
+    actor TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_run_events_run_id ON run_events(run_id);
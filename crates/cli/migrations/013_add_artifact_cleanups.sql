@@ -0,0 +1,7 @@
+-- Run artifacts removed by the retention janitor
+CREATE TABLE artifact_cleanups (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    cleaned_at TEXT NOT NULL,
+    runs_cleaned INTEGER NOT NULL,
+    bytes_reclaimed INTEGER NOT NULL
+);
@@ -0,0 +1,21 @@
+-- Notifier backends of the daemon; every status change is queued for each of them
+CREATE TABLE notifiers (
+    name TEXT PRIMARY KEY NOT NULL
+);
+
+-- Runs whose latest status a notifier still has to deliver
+CREATE TABLE notification_queue (
+    notifier TEXT NOT NULL,
+    run_id TEXT NOT NULL,
+    -- bumped when the run changes again, so a delivery in flight doesn't dequeue the change
+    seq INTEGER NOT NULL DEFAULT 0,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT NOT NULL,
+    last_error TEXT,
+    PRIMARY KEY (notifier, run_id)
+);
+
+-- runs the bot notifier had not delivered yet; dropped on startup if it isn't configured
+INSERT INTO notifiers (name) VALUES ('bot');
+INSERT INTO notification_queue (notifier, run_id, next_attempt_at)
+SELECT 'bot', run_id, updated_at FROM runs WHERE bot_notified = FALSE;
@@ -0,0 +1,10 @@
+-- SHA-256 of each save downloaded for a run, to find saves submitted more than once
+CREATE TABLE run_saves (
+    run_id TEXT NOT NULL REFERENCES runs(run_id) ON DELETE CASCADE,
+    -- 1-based; 1 for runs that aren't segmented
+    segment INTEGER NOT NULL,
+    sha256 TEXT NOT NULL,
+    recorded_at TEXT NOT NULL,
+    PRIMARY KEY (run_id, segment)
+);
+CREATE INDEX idx_run_saves_sha256 ON run_saves(sha256);
@@ -0,0 +1,6 @@
+-- Public copies of passed runs' saves and reports, e.g. Internet Archive items
+CREATE TABLE run_public_archives (
+    run_id TEXT PRIMARY KEY NOT NULL REFERENCES runs(run_id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    uploaded_at TEXT NOT NULL
+);
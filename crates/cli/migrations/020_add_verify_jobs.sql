@@ -0,0 +1,22 @@
+-- Verifications of saves submitted directly, through the HTTP API or the CLI, rather than
+-- linked from runs on speedrun.com
+CREATE TABLE verify_jobs (
+    job_id TEXT PRIMARY KEY NOT NULL,
+    -- upload or cli
+    source TEXT NOT NULL,
+    game_id TEXT NOT NULL,
+    category_id TEXT NOT NULL,
+    save_path TEXT NOT NULL,
+    status TEXT NOT NULL,
+    -- Instance ID of the daemon replaying the job
+    claimed_by TEXT,
+    submitted_at TEXT NOT NULL,
+    started_at TEXT,
+    finished_at TEXT,
+    report_summary TEXT,
+    -- JSON list of the replay's warning and error messages
+    messages TEXT,
+    error_message TEXT
+);
+
+CREATE INDEX idx_verify_jobs_status ON verify_jobs(status, submitted_at);
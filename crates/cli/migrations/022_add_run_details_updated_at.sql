@@ -0,0 +1,2 @@
+-- When the run's speedrun.com details were last fetched, so stale ones are fetched again
+ALTER TABLE runs ADD COLUMN details_updated_at TEXT;
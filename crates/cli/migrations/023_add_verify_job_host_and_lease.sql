@@ -0,0 +1,4 @@
+-- Machine whose output directory holds the job's save; only daemons on it replay the job
+ALTER TABLE verify_jobs ADD COLUMN host TEXT;
+-- Processing jobs whose lease expired are claimed again, like runs
+ALTER TABLE verify_jobs ADD COLUMN lease_expires_at TEXT;
@@ -0,0 +1,54 @@
+-- The schema of migrations/ 001-009 in one step. Later migrations are numbered as there.
+-- Timestamps are RFC 3339 text, as SQLite stores them, so both backends share their queries.
+
+CREATE TABLE runs (
+    run_id TEXT PRIMARY KEY NOT NULL,
+    game_id TEXT NOT NULL,
+    category_id TEXT NOT NULL,
+    submitted_date TEXT NOT NULL,
+    status TEXT NOT NULL,
+    error_message TEXT,
+    retry_count BIGINT NOT NULL DEFAULT 0,
+    next_retry_at TEXT,
+    error_class TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    bot_notified BOOLEAN NOT NULL DEFAULT FALSE,
+    -- JSON array of progress snapshots recorded during the replay
+    progress_timeline TEXT,
+    -- JSON summary of the replay report (counts per rule, exit signal, duration)
+    report_summary TEXT,
+    -- worker holding the run's lease, possibly on another machine
+    claimed_by TEXT,
+    lease_expires_at TEXT,
+    -- runs with a higher priority are processed first
+    priority BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE INDEX idx_runs_status ON runs(status);
+CREATE INDEX idx_runs_game_category ON runs(game_id, category_id);
+CREATE INDEX idx_runs_submitted_date ON runs(game_id, category_id, submitted_date DESC);
+CREATE INDEX idx_runs_retry ON runs(next_retry_at) WHERE next_retry_at IS NOT NULL;
+
+CREATE TABLE game_cache (
+    game_id TEXT PRIMARY KEY NOT NULL,
+    game_name TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE category_cache (
+    category_id TEXT PRIMARY KEY NOT NULL,
+    category_name TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_game_cache_updated_at ON game_cache(updated_at);
+CREATE INDEX idx_category_cache_updated_at ON category_cache(updated_at);
+
+-- Whether each daemon instance last stopped cleanly
+CREATE TABLE daemon_state (
+    instance_id TEXT PRIMARY KEY NOT NULL,
+    started_at TEXT NOT NULL,
+    stopped_at TEXT,
+    clean_shutdown BOOLEAN NOT NULL DEFAULT FALSE
+);
@@ -0,0 +1,14 @@
+-- Outcome of each run's latest replay, kept after the log files are cleaned up
+CREATE TABLE replay_results (
+    run_id TEXT PRIMARY KEY NOT NULL REFERENCES runs(run_id) ON DELETE CASCADE,
+    max_msg_level TEXT NOT NULL,
+    exit_tick BIGINT NOT NULL,
+    win_condition_completed BOOLEAN NOT NULL,
+    -- JSON object of finding counts per rule
+    rule_counts TEXT NOT NULL,
+    log_path TEXT,
+    report_path TEXT,
+    -- Last lines of the replay log
+    log_excerpt TEXT,
+    completed_at TEXT NOT NULL
+);
@@ -0,0 +1,6 @@
+-- Speedrun.com details shown alongside runs
+ALTER TABLE runs ADD COLUMN player_names TEXT;
+ALTER TABLE runs ADD COLUMN run_time_secs DOUBLE PRECISION;
+ALTER TABLE runs ADD COLUMN platform TEXT;
+-- JSON object of variable ID to value ID
+ALTER TABLE runs ADD COLUMN variable_values TEXT;
@@ -0,0 +1,7 @@
+-- Game and category IDs speedrun.com did not know, so they are not looked up again every time
+CREATE TABLE missing_name_cache (
+    kind TEXT NOT NULL,
+    id TEXT NOT NULL,
+    checked_at TEXT NOT NULL,
+    PRIMARY KEY (kind, id)
+);
@@ -0,0 +1,7 @@
+-- Run artifacts removed by the retention janitor
+CREATE TABLE artifact_cleanups (
+    id BIGSERIAL PRIMARY KEY,
+    cleaned_at TEXT NOT NULL,
+    runs_cleaned BIGINT NOT NULL,
+    bytes_reclaimed BIGINT NOT NULL
+);
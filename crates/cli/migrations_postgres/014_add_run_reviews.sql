@@ -0,0 +1,9 @@
+-- Reviewer decisions on runs that needed review; cleared when the run is verified again
+CREATE TABLE run_reviews (
+    run_id TEXT PRIMARY KEY NOT NULL REFERENCES runs(run_id) ON DELETE CASCADE,
+    -- accepted or rejected
+    decision TEXT NOT NULL,
+    reviewer TEXT NOT NULL,
+    note TEXT,
+    reviewed_at TEXT NOT NULL
+);
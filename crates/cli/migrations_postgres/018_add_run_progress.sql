@@ -0,0 +1,8 @@
+-- How far the replay of a run being processed has gotten
+CREATE TABLE run_progress (
+    run_id TEXT PRIMARY KEY NOT NULL REFERENCES runs(run_id) ON DELETE CASCADE,
+    tick BIGINT NOT NULL,
+    -- Estimated from the run's time on speedrun.com
+    percent DOUBLE PRECISION,
+    updated_at TEXT NOT NULL
+);
@@ -0,0 +1,10 @@
+-- How long each run's latest replay took, to estimate how long queued runs will take
+CREATE TABLE replay_throughput (
+    run_id TEXT PRIMARY KEY NOT NULL REFERENCES runs(run_id) ON DELETE CASCADE,
+    factorio_version TEXT NOT NULL,
+    -- Instance ID of the daemon that replayed the run
+    host TEXT NOT NULL,
+    ticks BIGINT NOT NULL,
+    duration_secs DOUBLE PRECISION NOT NULL,
+    recorded_at TEXT NOT NULL
+);
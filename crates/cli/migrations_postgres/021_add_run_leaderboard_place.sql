@@ -0,0 +1,2 @@
+-- Where the run's time placed on its speedrun.com leaderboard when its details were fetched
+ALTER TABLE runs ADD COLUMN leaderboard_place INTEGER;
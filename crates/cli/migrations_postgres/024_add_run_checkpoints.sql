@@ -0,0 +1,8 @@
+-- The latest autosave of a run's replay, kept when its rules enable checkpoints
+CREATE TABLE run_checkpoints (
+    run_id TEXT PRIMARY KEY NOT NULL REFERENCES runs(run_id) ON DELETE CASCADE,
+    save_path TEXT NOT NULL,
+    -- Latest tick the replay scripts had reported, if known
+    tick BIGINT,
+    created_at TEXT NOT NULL
+);
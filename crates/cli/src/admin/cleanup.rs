@@ -3,9 +3,7 @@ use clap::Args;
 
 use crate::daemon::database::connection::Database;
 use crate::daemon::speedrun_api::SpeedrunOps;
-use crate::query::common::{
-    RunDisplay, RunFilterArgs, format_runs_as_table, resolve_game_category,
-};
+use crate::query::common::{RunFilterArgs, format_runs_as_table, load_run_displays};
 
 #[derive(Args)]
 pub struct CleanupArgs {
@@ -36,16 +34,7 @@ pub async fn handle_cleanup(db: &Database, ops: &SpeedrunOps, args: CleanupArgs)
         return Ok(());
     }
 
-    let mut run_displays = Vec::new();
-    for run in &runs_to_delete {
-        let (game_name, category_name) =
-            resolve_game_category(ops, &run.game_id, &run.category_id).await;
-        run_displays.push(RunDisplay {
-            run,
-            game_name,
-            category_name,
-        });
-    }
+    let run_displays = load_run_displays(db, ops, &runs_to_delete).await?;
 
     println!(
         "Found {} run(s) matching the criteria:\n",
@@ -6,10 +6,16 @@ use crate::daemon::database::connection::Database;
 use crate::daemon::speedrun_api::{SpeedrunClient, SpeedrunOps};
 
 mod cleanup;
+mod priority;
 mod reset;
+mod reverify;
+mod review;
 
 pub use cleanup::CleanupArgs;
+pub use priority::PrioritizeArgs;
 pub use reset::{ResetArgs, ResetRunArgs};
+pub use reverify::ReverifyArgs;
+pub use review::ReviewArgs;
 
 #[derive(Args)]
 pub struct AdminArgs {
@@ -29,10 +35,16 @@ pub enum AdminSubcommand {
     Reset(ResetArgs),
     /// Delete runs matching criteria
     Cleanup(CleanupArgs),
+    /// Set a run's priority so it is processed ahead of the queue
+    Prioritize(PrioritizeArgs),
+    /// Verify a run again now, optionally with different rules
+    Reverify(ReverifyArgs),
+    /// Accept or reject a run that needs review
+    Review(ReviewArgs),
 }
 
 pub async fn handle_admin_command(args: AdminArgs) -> Result<()> {
-    let db = Database::new(&args.database).await?;
+    let db = Database::new(&args.database).await?.with_actor("admin");
     let speedrun_client = SpeedrunClient::new().context("Failed to create speedrun client")?;
     let speedrun_ops = SpeedrunOps::new(&speedrun_client).with_db(db.clone());
 
@@ -44,5 +56,12 @@ pub async fn handle_admin_command(args: AdminArgs) -> Result<()> {
         AdminSubcommand::Cleanup(cleanup_args) => {
             cleanup::handle_cleanup(&db, &speedrun_ops, cleanup_args).await
         }
+        AdminSubcommand::Prioritize(priority_args) => {
+            priority::handle_prioritize(&db, priority_args).await
+        }
+        AdminSubcommand::Reverify(reverify_args) => {
+            reverify::handle_reverify(&db, &speedrun_ops, reverify_args).await
+        }
+        AdminSubcommand::Review(review_args) => review::handle_review(&db, review_args).await,
     }
 }
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::daemon::database::connection::Database;
+
+#[derive(Args)]
+pub struct PrioritizeArgs {
+    /// Speedrun.com run ID
+    pub run_id: String,
+
+    /// Runs with higher priority are processed first. New runs get 0, so the default of 100
+    /// moves this run ahead of them
+    #[arg(long, default_value_t = 100, allow_negative_numbers = true)]
+    pub priority: i64,
+}
+
+pub async fn handle_prioritize(db: &Database, args: PrioritizeArgs) -> Result<()> {
+    if !db.set_run_priority(&args.run_id, args.priority).await? {
+        anyhow::bail!("Run not found: {}", args.run_id);
+    }
+
+    println!("Set priority of run {} to {}", args.run_id, args.priority);
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use clap::Args;
+use factorio_manager::expected_mods::ExpectedMods;
+use std::path::PathBuf;
+use tokio_util::sync::CancellationToken;
+use zip_downloader::throttle::DownloadThrottles;
+
+use crate::config::RunRules;
+use crate::daemon::config::SrcRunRules;
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::RunStatus;
+use crate::daemon::processor::{LEASE_DURATION, keep_lease};
+use crate::daemon::retry::RetryConfig;
+use crate::daemon::run_processing::{RunProcessor, download_and_run_replay};
+use crate::daemon::speedrun_api::SpeedrunOps;
+use crate::query::common::format_status;
+
+/// Holds the run's lease while it is reverified.
+const REVERIFY_WORKER: &str = "reverify";
+
+#[derive(Args)]
+pub struct ReverifyArgs {
+    /// Speedrun.com run ID
+    pub run_id: String,
+
+    /// Run rules (yaml) used instead of the run's category rules
+    #[arg(long)]
+    pub rules: Option<PathBuf>,
+
+    /// GAME rules (yaml)
+    #[arg(long, default_value = "./speedrun_rules.yaml")]
+    pub game_rules: PathBuf,
+
+    /// Factorio installations directory
+    #[arg(long, default_value = "./factorio_installs")]
+    pub install_dir: PathBuf,
+
+    /// Output directory; a save already downloaded to {output_dir}/{run_id}/ is reused
+    #[arg(long, default_value = "./src_runs")]
+    pub output_dir: PathBuf,
+}
+
+pub async fn handle_reverify(db: &Database, ops: &SpeedrunOps, args: ReverifyArgs) -> Result<()> {
+    let run = db
+        .get_run(&args.run_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Run not found: {}", args.run_id))?;
+
+    let src_rules = crate::load_src_rules(&args.game_rules).await?;
+    let rules_override = match &args.rules {
+        Some(path) => Some(crate::load_run_rules(path).await?),
+        None => None,
+    };
+    let (run_rules, expected_mods) = resolve_reverify_rules(
+        &src_rules,
+        rules_override.as_ref(),
+        &run.game_id,
+        &run.category_id,
+    )?;
+
+    std::fs::create_dir_all(&args.install_dir)?;
+
+    // results are attributed to "reverify" in the run history
+    let db = db.clone().with_actor("reverify");
+    // claimed like a daemon worker would, so daemons leave the run alone until it's done
+    if let Err(holder) = db
+        .claim_run(&run.run_id, REVERIFY_WORKER, Utc::now() + LEASE_DURATION)
+        .await?
+    {
+        anyhow::bail!(
+            "Run {} is being processed by {}; try again once it finishes",
+            run.run_id,
+            holder
+        );
+    }
+    db.clear_retry_fields(&run.run_id).await?;
+    let cancel = CancellationToken::new();
+    let lease = tokio::spawn(keep_lease(
+        db.clone(),
+        run.run_id.clone(),
+        REVERIFY_WORKER.to_string(),
+        cancel.clone(),
+    ));
+
+    let mut run_processor = RunProcessor::new(&ops.client, &DownloadThrottles::default())
+        .with_reused_saves()
+        .with_submitted_date(run.submitted_date);
+    let result = download_and_run_replay(
+        &mut run_processor,
+        &run.run_id,
+        run_rules,
+        expected_mods,
+        &args.install_dir,
+        &args.output_dir,
+        &cancel,
+    )
+    .await;
+    lease.abort();
+    let saved = !cancel.is_cancelled()
+        && db
+            .renew_lease(&run.run_id, REVERIFY_WORKER, Utc::now() + LEASE_DURATION)
+            .await?
+        && db
+            .process_replay_result(
+                &run.run_id,
+                Some(REVERIFY_WORKER),
+                result,
+                &RetryConfig::default(),
+            )
+            .await?;
+    db.release_lease(&run.run_id, REVERIFY_WORKER).await?;
+    if !saved {
+        anyhow::bail!(
+            "Run {} was taken over by a daemon during reverification; discarded this result",
+            run.run_id
+        );
+    }
+
+    let run = db
+        .get_run(&run.run_id)
+        .await?
+        .context("Run disappeared during reverification")?;
+    println!(
+        "Reverified run {}: {}",
+        run.run_id,
+        format_status(&run.status)
+    );
+    if run.status != RunStatus::Passed
+        && let Some(message) = &run.error_message
+    {
+        println!("{}", message);
+    }
+    Ok(())
+}
+
+/// The category's rules, or `rules_override` with the game's expected mods unless it sets its own.
+fn resolve_reverify_rules<'a>(
+    src_rules: &'a SrcRunRules,
+    rules_override: Option<&'a RunRules>,
+    game_id: &str,
+    category_id: &str,
+) -> Result<(&'a RunRules, &'a ExpectedMods)> {
+    let Some(rules) = rules_override else {
+        return src_rules.resolve_rules(game_id, category_id);
+    };
+    if let Some(expected_mods) = &rules.expected_mods_override {
+        return Ok((rules, expected_mods));
+    }
+    let game_config = src_rules.games.get(game_id).ok_or_else(|| {
+        anyhow::anyhow!(
+            "No configuration found for game={}; set expected_mods in the rules file",
+            game_id
+        )
+    })?;
+    Ok((rules, &game_config.expected_mods))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC_RULES: &str = r#"
+games:
+  game1:
+    expected_mods: [base]
+    categories:
+      cat1:
+        win_on_scenario_finished: true
+"#;
+
+    #[test]
+    fn test_override_uses_game_expected_mods() {
+        let src_rules: SrcRunRules = serde_yaml::from_str(SRC_RULES).unwrap();
+        let rules: RunRules = serde_yaml::from_str("blueprint_import: true").unwrap();
+
+        let (run_rules, expected_mods) =
+            resolve_reverify_rules(&src_rules, Some(&rules), "game1", "other").unwrap();
+        assert!(run_rules.replay_scripts.blueprint_import);
+        assert!(std::ptr::eq(
+            expected_mods,
+            &src_rules.games["game1"].expected_mods
+        ));
+
+        assert!(resolve_reverify_rules(&src_rules, Some(&rules), "game2", "cat1").is_err());
+        assert!(resolve_reverify_rules(&src_rules, None, "game1", "other").is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::{Result, bail};
+use clap::Args;
+
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::ReviewDecision;
+use crate::query::common::format_status;
+
+#[derive(Args)]
+pub struct ReviewArgs {
+    /// Speedrun.com run ID of a run that needs review
+    pub run_id: String,
+
+    #[command(flatten)]
+    pub decision: DecisionArgs,
+
+    /// Reason for the decision, kept with the run and sent with its notification
+    #[arg(long)]
+    pub note: Option<String>,
+
+    /// Who made the decision (defaults to $USER)
+    #[arg(long)]
+    pub reviewer: Option<String>,
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+pub struct DecisionArgs {
+    /// Accept the run; it passes
+    #[arg(long)]
+    pub accept: bool,
+
+    /// Reject the run; it fails
+    #[arg(long)]
+    pub reject: bool,
+}
+
+pub async fn handle_review(db: &Database, args: ReviewArgs) -> Result<()> {
+    let decision = if args.decision.accept {
+        ReviewDecision::Accepted
+    } else {
+        ReviewDecision::Rejected
+    };
+    let Some(reviewer) = args.reviewer.or_else(|| std::env::var("USER").ok()) else {
+        bail!("--reviewer is required when $USER is not set");
+    };
+
+    let reviewed = db
+        .record_review(&args.run_id, decision, &reviewer, args.note.as_deref())
+        .await?;
+    if !reviewed {
+        match db.get_run(&args.run_id).await? {
+            None => bail!("Run not found: {}", args.run_id),
+            Some(run) => bail!(
+                "Run {} does not need review; its status is {}",
+                args.run_id,
+                format_status(&run.status)
+            ),
+        }
+    }
+
+    println!(
+        "Run {} {} by {}: {}",
+        args.run_id,
+        decision.as_str(),
+        reviewer,
+        format_status(&decision.status())
+    );
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use comfy_table::{Cell, Table};
+use factorio_manager::expected_mods::ExpectedMods;
+use factorio_manager::factorio_install_dir::FactorioInstallDir;
+use futures::future::try_join_all;
+use log::info;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::RunRules;
+use crate::exit_code::{ConfigError, ExitCode, FailOn};
+use crate::output::{OutputFormat, print_json};
+use crate::run_replay::{ReplayOptions, ReplayReport, run_replay};
+
+#[derive(Args)]
+pub struct RunBatchArgs {
+    /// Directory containing the saves (.zip) to verify
+    dir: PathBuf,
+
+    /// RUN Rules (json/yaml)
+    run_rules: PathBuf,
+
+    /// Factorio installations directory (defaults to ./factorio_installs)
+    #[arg(long, default_value = "./factorio_installs")]
+    install_dir: PathBuf,
+
+    /// Output directory; defaults to the saves directory
+    /// Logs will be written to {output_dir}/{save_name}/output.log
+    #[arg(short, long)]
+    output_dir: Option<PathBuf>,
+
+    /// Number of saves verified in parallel. With more than one, each worker gets its own
+    /// Factorio installs under `install_dir/worker-N`.
+    #[arg(short, long, default_value_t = 1)]
+    jobs: usize,
+}
+
+#[derive(Serialize)]
+struct BatchOutput<'a> {
+    exit_code: ExitCode,
+    saves: &'a [SaveResult],
+}
+
+#[derive(Serialize)]
+struct SaveResult {
+    save: PathBuf,
+    log: PathBuf,
+    exit_code: ExitCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<ReplayReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub async fn cli_run_batch(
+    args: RunBatchArgs,
+    format: OutputFormat,
+    fail_on: Option<FailOn>,
+) -> Result<i32> {
+    let rules = crate::load_run_rules(&args.run_rules).await?;
+    let expected_mods = rules
+        .expected_mods_override
+        .as_ref()
+        .context(ConfigError("rules must list expected_mods".into()))?;
+    let output_dir = args.output_dir.as_ref().unwrap_or(&args.dir);
+    let saves = find_saves(&args.dir)?;
+    info!(
+        "Verifying {} save(s) from {}",
+        saves.len(),
+        args.dir.display()
+    );
+
+    let jobs = args.jobs.max(1);
+    let queue = Mutex::new(saves.into_iter());
+    let workers = (0..jobs).map(|index| {
+        let install_dir = if jobs > 1 {
+            args.install_dir.join(format!("worker-{}", index))
+        } else {
+            args.install_dir.clone()
+        };
+        let queue = &queue;
+        let rules = &rules;
+        async move {
+            let install_dir = crate::load_install_dir(&install_dir).await?;
+            let mut results = Vec::new();
+            loop {
+                let Some(save) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let result = verify_save(
+                    &install_dir,
+                    &save,
+                    rules,
+                    expected_mods,
+                    output_dir,
+                    fail_on,
+                );
+                results.push(result.await);
+            }
+            Ok::<_, anyhow::Error>(results)
+        }
+    });
+    let mut results: Vec<SaveResult> = try_join_all(workers).await?.into_iter().flatten().collect();
+    results.sort_by(|a, b| a.save.cmp(&b.save));
+
+    let exit_code = results
+        .iter()
+        .map(|result| result.exit_code)
+        .max()
+        .unwrap_or(ExitCode::Pass);
+    let table = format_summary(&results);
+    let summary_path = output_dir.join("summary.txt");
+    std::fs::write(&summary_path, format!("{table}\n"))
+        .with_context(|| format!("Failed to write {}", summary_path.display()))?;
+    if format.is_json() {
+        print_json(&BatchOutput {
+            exit_code,
+            saves: &results,
+        })?;
+    } else {
+        println!("{table}");
+    }
+    Ok(exit_code.code())
+}
+
+/// Saves in `dir`, leaving out saves with the replay script installed.
+pub fn find_saves(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut saves = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let name = name.to_lowercase();
+        if path.is_file() && name.ends_with(".zip") && !name.ends_with(".installed.zip") {
+            saves.push(path);
+        }
+    }
+    saves.sort();
+    Ok(saves)
+}
+
+async fn verify_save(
+    install_dir: &FactorioInstallDir,
+    save: &Path,
+    rules: &RunRules,
+    expected_mods: &ExpectedMods,
+    output_dir: &Path,
+    fail_on: Option<FailOn>,
+) -> SaveResult {
+    let save_name = save.file_stem().unwrap_or_default();
+    let log = output_dir.join(save_name).join("output.log");
+    info!("=== Verifying {} ===", save.display());
+    let result = async {
+        std::fs::create_dir_all(output_dir.join(save_name))?;
+        let mut save_file = crate::load_save(save).await?;
+        run_replay(
+            install_dir,
+            &mut save_file,
+            rules,
+            expected_mods,
+            &log,
+            ReplayOptions::default(),
+        )
+        .await
+        .map_err(anyhow::Error::from)
+    }
+    .await;
+    std::fs::remove_file(save.with_extension("installed.zip")).ok();
+
+    SaveResult {
+        save: save.to_path_buf(),
+        log,
+        exit_code: ExitCode::from_result(&result, fail_on),
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        report: result.ok(),
+    }
+}
+
+fn format_summary(results: &[SaveResult]) -> String {
+    let mut table = Table::new();
+    table.set_header(vec!["Save", "Result", "Final Tick", "Findings", "Notes"]);
+    for result in results {
+        let save = result
+            .save
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let label = match result.exit_code {
+            ExitCode::Pass => "Passed",
+            ExitCode::PassWithWarnings => "Warnings",
+            ExitCode::Fail => "Failed",
+            ExitCode::ConfigError | ExitCode::InfraError | ExitCode::Interrupted => "Error",
+        };
+        let (final_tick, findings, notes) = match (&result.report, &result.error) {
+            (Some(report), _) => (
+                report.final_tick.to_string(),
+                report.findings.len().to_string(),
+                report.messages.first().cloned().unwrap_or_default(),
+            ),
+            (None, error) => (
+                String::new(),
+                String::new(),
+                error.clone().unwrap_or_default(),
+            ),
+        };
+        table.add_row(vec![
+            Cell::new(save),
+            Cell::new(label),
+            Cell::new(final_tick),
+            Cell::new(findings),
+            Cell::new(notes),
+        ]);
+    }
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_saves() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.zip", "a.ZIP", "a.installed.zip", "notes.txt"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("c.zip")).unwrap();
+
+        let saves = find_saves(dir.path()).unwrap();
+        let names: Vec<_> = saves
+            .iter()
+            .map(|save| save.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["a.ZIP", "b.zip"]);
+    }
+}
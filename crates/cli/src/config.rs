@@ -1,12 +1,129 @@
-use factorio_manager::expected_mods::ExpectedMods;
-use replay_script::ReplayScripts;
+use factorio_manager::expected_mods::{ExpectedMods, ModPolicy};
+use factorio_manager::install_version::InstallVersionPolicy;
+use factorio_manager::process_manager::ResourceLimits;
+use factorio_manager::save_file::ScriptInjection;
+use replay_script::{MsgLevel, ReplayMsg, ReplayScripts};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use zip_downloader::SecurityOverrides;
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct RunRules {
     #[serde(rename = "expected_mods")]
     pub expected_mods_override: Option<ExpectedMods>,
+    #[serde(default)]
+    pub mod_policy: ModPolicy,
+    /// Allow a control.lua other than vanilla freeplay's, for scenario categories.
+    #[serde(default)]
+    pub allow_custom_control_lua: bool,
+    #[serde(default)]
+    pub script_injection: ScriptInjection,
+    /// Which Factorio version replays the saves: `exact`, `latest_patch` or a version to pin.
+    #[serde(default)]
+    pub install_version: InstallVersionPolicy,
+    /// Extra command line arguments for the Factorio replay process.
+    #[serde(default)]
+    pub factorio_args: Vec<String>,
+    /// Has Factorio autosave the replay every this many minutes, for very long runs. The
+    /// latest autosave is kept next to the log as `checkpoint.zip`, even if the replay fails.
+    #[serde(default)]
+    pub checkpoint_interval_minutes: Option<u32>,
+    #[serde(default)]
+    pub resource_limits: ResourceLimits,
+    /// Free disk space kept on top of the installed save while replaying, in MB, for
+    /// Factorio's log and temp files.
+    #[serde(default = "default_replay_disk_headroom_mb")]
+    pub replay_disk_headroom_mb: u64,
+    /// Download limits for the saves, e.g. a larger `max_file_size_mb` for TAS categories.
+    #[serde(default)]
+    pub security: SecurityOverrides,
+    /// Findings before this tick are left out of the report, to spot-check a later part
+    /// of a run. They are still in the log.
+    #[serde(default)]
+    pub verify_from_tick: u64,
+    /// Runs are submitted as several saves, one per segment. Every save linked in the run
+    /// description is verified in order.
+    #[serde(default)]
+    pub segmented: bool,
+    /// Verification plugins run after the replay, in order, by name.
+    #[serde(default)]
+    pub plugins: Vec<String>,
+    /// Findings below this level are left out of the report. They are still in the log, and
+    /// still decide whether the run passes.
+    #[serde(default)]
+    pub min_report_level: MsgLevel,
+    /// `min_report_level` of specific rules, e.g. `log_all_commands: Warn`.
+    #[serde(default)]
+    pub rule_report_levels: BTreeMap<String, MsgLevel>,
     #[serde(flatten)]
     pub replay_scripts: ReplayScripts,
 }
+
+fn default_replay_disk_headroom_mb() -> u64 {
+    256
+}
+
+impl RunRules {
+    /// These rules without the win condition, for replaying segments of a run that end
+    /// before it is won.
+    pub fn without_win_condition(&self) -> RunRules {
+        let mut rules = self.clone();
+        rules.replay_scripts.win_on_scenario_finished = false;
+        rules.replay_scripts.win_condition = None;
+        rules
+    }
+
+    /// Whether `msg`'s level is high enough for the report, for its rule.
+    pub fn is_reported(&self, msg: &ReplayMsg) -> bool {
+        let min_level = msg
+            .rule
+            .as_deref()
+            .and_then(|rule| self.rule_report_levels.get(rule))
+            .unwrap_or(&self.min_report_level);
+        msg.level >= *min_level || msg.level == MsgLevel::Critical
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(level: MsgLevel, rule: &str) -> ReplayMsg {
+        ReplayMsg {
+            time: 0,
+            level,
+            rule: Some(rule.to_string()),
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_is_reported() {
+        let rules: RunRules =
+            serde_yaml::from_str("rule_report_levels: { log_all_commands: Warn }").unwrap();
+        assert!(!rules.is_reported(&msg(MsgLevel::Debug, "no_map_editor")));
+        assert!(rules.is_reported(&msg(MsgLevel::Info, "no_map_editor")));
+        assert!(!rules.is_reported(&msg(MsgLevel::Info, "log_all_commands")));
+        assert!(rules.is_reported(&msg(MsgLevel::Warn, "log_all_commands")));
+
+        let rules: RunRules = serde_yaml::from_str("min_report_level: Debug").unwrap();
+        assert!(rules.is_reported(&msg(MsgLevel::Debug, "no_map_editor")));
+
+        let rules: RunRules = serde_yaml::from_str("min_report_level: Critical").unwrap();
+        assert!(!rules.is_reported(&msg(MsgLevel::Error, "no_map_editor")));
+        assert!(rules.is_reported(&msg(MsgLevel::Critical, "no_map_editor")));
+    }
+
+    #[test]
+    fn test_without_win_condition() {
+        let rules: RunRules =
+            serde_yaml::from_str("{ win_condition: { type: rocket_launched }, max_players: 2 }")
+                .unwrap();
+        assert!(rules.replay_scripts.has_win_condition());
+        let rules = rules.without_win_condition();
+        assert!(!rules.replay_scripts.has_win_condition());
+        assert_eq!(rules.replay_scripts.max_players, Some(2));
+    }
+}
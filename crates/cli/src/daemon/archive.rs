@@ -0,0 +1,266 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use log::info;
+use reqwest::StatusCode;
+use reqwest::header::CONTENT_LENGTH;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_util::io::ReaderStream;
+use zip_downloader::services::s3::S3Config;
+
+/// Long-term storage for verified saves and logs.
+#[async_trait]
+pub trait ArchiveStore: Send + Sync {
+    /// Stores the contents of `file` under `key`. Existing entries are never overwritten.
+    async fn put(&self, key: &str, file: &Path) -> Result<()>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ArchiveConfig {
+    Local {
+        path: PathBuf,
+    },
+    /// Credentials, region and endpoint come from the standard AWS environment variables.
+    S3 {
+        bucket: String,
+        #[serde(default)]
+        prefix: String,
+    },
+}
+
+impl ArchiveConfig {
+    pub fn build(&self) -> Arc<dyn ArchiveStore> {
+        match self {
+            Self::Local { path } => Arc::new(LocalDirStore::new(path.clone())),
+            Self::S3 { bucket, prefix } => Arc::new(S3Store::new(
+                S3Config::from_env(),
+                bucket.clone(),
+                prefix.clone(),
+            )),
+        }
+    }
+}
+
+/// Archive key prefix for a run, e.g. `{game}/{category}/{run_id}`.
+pub fn run_key(game_id: &str, category_id: &str, run_id: &str) -> String {
+    format!("{}/{}/{}", game_id, category_id, run_id)
+}
+
+/// A request body streaming the contents of `file`, and its length for `Content-Length`.
+pub async fn file_body(file: &Path) -> Result<(reqwest::Body, u64)> {
+    let file = tokio::fs::File::open(file)
+        .await
+        .with_context(|| format!("Failed to open {}", file.display()))?;
+    let len = file.metadata().await?.len();
+    Ok((reqwest::Body::wrap_stream(ReaderStream::new(file)), len))
+}
+
+/// Archives the save and replay log of a run under `prefix`.
+pub async fn archive_run(
+    store: &dyn ArchiveStore,
+    prefix: &str,
+    save_path: &Path,
+    log_path: &Path,
+) -> Result<()> {
+    store
+        .put(&format!("{}/save.zip", prefix), save_path)
+        .await?;
+    store
+        .put(&format!("{}/output.log", prefix), log_path)
+        .await?;
+    info!("Archived run to {}", prefix);
+    Ok(())
+}
+
+pub struct LocalDirStore {
+    root: PathBuf,
+}
+
+impl LocalDirStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+#[async_trait]
+impl ArchiveStore for LocalDirStore {
+    async fn put(&self, key: &str, file: &Path) -> Result<()> {
+        let dest = self.root.join(key);
+        if dest.exists() {
+            info!("{} is already archived", dest.display());
+            return Ok(());
+        }
+        let parent = dest.parent().unwrap_or(&self.root);
+        tokio::fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+
+        // copy under a temporary name so a partial copy is never mistaken for an archived file
+        let partial = dest.with_extension("partial");
+        tokio::fs::copy(file, &partial)
+            .await
+            .with_context(|| format!("Failed to copy {} to archive", file.display()))?;
+        tokio::fs::rename(&partial, &dest)
+            .await
+            .with_context(|| format!("Failed to move {} into place", dest.display()))?;
+        Ok(())
+    }
+}
+
+pub struct S3Store {
+    config: S3Config,
+    bucket: String,
+    prefix: String,
+    client: reqwest::Client,
+}
+
+impl S3Store {
+    pub fn new(config: S3Config, bucket: String, prefix: String) -> Self {
+        Self {
+            config,
+            bucket,
+            prefix,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn object_key(&self, key: &str) -> String {
+        match self.prefix.trim_end_matches('/') {
+            "" => key.to_string(),
+            prefix => format!("{}/{}", prefix, key),
+        }
+    }
+}
+
+#[async_trait]
+impl ArchiveStore for S3Store {
+    async fn put(&self, key: &str, file: &Path) -> Result<()> {
+        let object_key = self.object_key(key);
+        let (body, len) = file_body(file).await?;
+        let response = self
+            .config
+            .object_request(
+                &self.client,
+                reqwest::Method::PUT,
+                &self.bucket,
+                &object_key,
+            )?
+            // conditional write: fail instead of replacing an existing object
+            .header("if-none-match", "*")
+            .header(CONTENT_LENGTH, len)
+            .body(body)
+            .send()
+            .await
+            .context("Failed to send request to S3")?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::PRECONDITION_FAILED => {
+                info!("s3://{}/{} is already archived", self.bucket, object_key);
+                Ok(())
+            }
+            status => anyhow::bail!("S3 upload of {} failed with HTTP {}", object_key, status),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_bytes, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_archive_config_parsing() {
+        let local: ArchiveConfig = serde_yaml::from_str("type: local\npath: /srv/archive").unwrap();
+        assert_eq!(
+            local,
+            ArchiveConfig::Local {
+                path: PathBuf::from("/srv/archive")
+            }
+        );
+
+        let s3: ArchiveConfig = serde_yaml::from_str("type: s3\nbucket: saves").unwrap();
+        assert_eq!(
+            s3,
+            ArchiveConfig::S3 {
+                bucket: "saves".to_string(),
+                prefix: String::new()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_store_does_not_overwrite() {
+        let archive = tempfile::tempdir().unwrap();
+        let source = tempfile::tempdir().unwrap();
+        let first = source.path().join("first.zip");
+        let second = source.path().join("second.zip");
+        std::fs::write(&first, b"first").unwrap();
+        std::fs::write(&second, b"second").unwrap();
+
+        let store = LocalDirStore::new(archive.path().to_path_buf());
+        let key = format!("{}/save.zip", run_key("game", "cat", "run1"));
+        store.put(&key, &first).await.unwrap();
+        store.put(&key, &second).await.unwrap();
+
+        let archived = archive.path().join("game/cat/run1/save.zip");
+        assert_eq!(std::fs::read(archived).unwrap(), b"first");
+    }
+
+    #[tokio::test]
+    async fn test_s3_store_conditional_put() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/bucket/archive/game/cat/run1/save.zip"))
+            .and(header("if-none-match", "*"))
+            .and(header("content-length", "4"))
+            .and(body_bytes(b"save".to_vec()))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/bucket/archive/game/cat/run1/output.log"))
+            .respond_with(ResponseTemplate::new(412))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let source = tempfile::tempdir().unwrap();
+        let save = source.path().join("save.zip");
+        let log = source.path().join("output.log");
+        std::fs::write(&save, b"save").unwrap();
+        std::fs::write(&log, b"log").unwrap();
+
+        let config = S3Config {
+            endpoint: Some(server.uri()),
+            ..Default::default()
+        };
+        let store = S3Store::new(config, "bucket".to_string(), "archive/".to_string());
+        archive_run(&store, &run_key("game", "cat", "run1"), &save, &log)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_s3_store_reports_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&server)
+            .await;
+
+        let source = tempfile::NamedTempFile::new().unwrap();
+        let config = S3Config {
+            endpoint: Some(server.uri()),
+            ..Default::default()
+        };
+        let store = S3Store::new(config, "bucket".to_string(), String::new());
+        let err = store.put("key", source.path()).await.unwrap_err();
+        assert!(err.to_string().contains("403"));
+    }
+}
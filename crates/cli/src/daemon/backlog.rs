@@ -0,0 +1,198 @@
+//! Alerts the notifiers when runs wait in the queue longer than an SLA, so a backlog that
+//! stopped moving (e.g. a stuck worker or a download service that keeps failing) gets noticed.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::{RunFilter, RunStatus};
+use crate::daemon::notifier::Notifier;
+
+/// Overdue runs listed in an alert, oldest first.
+const MAX_LISTED_RUNS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BacklogAlertConfig {
+    /// Runs still discovered or erroring this many hours after they were discovered are
+    /// overdue.
+    pub sla_hours: u64,
+    #[serde(default = "default_backlog_interval_seconds")]
+    pub interval_seconds: u64,
+    /// While runs stay overdue, the alert is repeated after this many hours.
+    #[serde(default = "default_backlog_repeat_hours")]
+    pub repeat_hours: u64,
+}
+
+fn default_backlog_interval_seconds() -> u64 {
+    900
+}
+
+fn default_backlog_repeat_hours() -> u64 {
+    24
+}
+
+/// The runs waiting past the SLA.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BacklogSummary {
+    pub sla_hours: u64,
+    pub discovered: usize,
+    pub error: usize,
+    /// When the oldest overdue run was discovered.
+    pub oldest_created_at: DateTime<Utc>,
+    pub oldest_run_ids: Vec<String>,
+}
+
+impl BacklogSummary {
+    pub fn total(&self) -> usize {
+        self.discovered + self.error
+    }
+
+    /// One line for chat messages and mail subjects.
+    pub fn headline(&self) -> String {
+        format!(
+            "{} run(s) waiting over {}h for verification ({} discovered, {} erroring)",
+            self.total(),
+            self.sla_hours,
+            self.discovered,
+            self.error
+        )
+    }
+}
+
+/// The runs discovered more than `sla_hours` before `now` that are still discovered or
+/// erroring, or `None` if there are none.
+pub async fn find_backlog(
+    db: &Database,
+    sla_hours: u64,
+    now: DateTime<Utc>,
+) -> Result<Option<BacklogSummary>> {
+    let filter = RunFilter {
+        statuses: vec![RunStatus::Discovered, RunStatus::Error],
+        ..Default::default()
+    };
+    let cutoff = now - chrono::Duration::hours(sla_hours as i64);
+    let mut overdue: Vec<_> = db
+        .query_runs(filter)
+        .await?
+        .into_iter()
+        // errors that won't be retried are final, and wait for nobody
+        .filter(|run| !run.is_final() && run.created_at < cutoff)
+        .collect();
+    if overdue.is_empty() {
+        return Ok(None);
+    }
+    overdue.sort_by_key(|run| run.created_at);
+
+    let discovered = overdue
+        .iter()
+        .filter(|run| run.status == RunStatus::Discovered)
+        .count();
+    Ok(Some(BacklogSummary {
+        sla_hours,
+        discovered,
+        error: overdue.len() - discovered,
+        oldest_created_at: overdue[0].created_at,
+        oldest_run_ids: overdue
+            .iter()
+            .take(MAX_LISTED_RUNS)
+            .map(|run| run.run_id.clone())
+            .collect(),
+    }))
+}
+
+pub async fn run_backlog_alert_loop(
+    config: BacklogAlertConfig,
+    db: Database,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    token: CancellationToken,
+) -> Result<()> {
+    info!(
+        "Checking for runs waiting over {}h every {}s",
+        config.sla_hours, config.interval_seconds
+    );
+    let interval = Duration::from_secs(config.interval_seconds);
+    let repeat_after = chrono::Duration::hours(config.repeat_hours as i64);
+    let mut last_alert: Option<DateTime<Utc>> = None;
+
+    loop {
+        let now = Utc::now();
+        match find_backlog(&db, config.sla_hours, now).await {
+            Ok(Some(summary)) => {
+                if last_alert.is_none_or(|alerted_at| now - alerted_at >= repeat_after) {
+                    warn!("{}", summary.headline());
+                    send_alert(&notifiers, &summary).await;
+                    last_alert = Some(now);
+                }
+            }
+            Ok(None) => {
+                if last_alert.take().is_some() {
+                    info!("No runs waiting over {}h anymore", config.sla_hours);
+                }
+            }
+            Err(e) => warn!("Failed to check the run backlog: {:#}", e),
+        }
+
+        tokio::select! {
+            _ = token.cancelled() => return Ok(()),
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+/// Alerts are not queued or retried; a failed one is repeated with the next alert.
+async fn send_alert(notifiers: &[Arc<dyn Notifier>], summary: &BacklogSummary) {
+    for notifier in notifiers {
+        if let Err(e) = notifier.backlog_alert(summary).await {
+            warn!(
+                "Failed to send backlog alert to {} notifier: {:#}",
+                notifier.name(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::database::types::NewRun;
+
+    #[tokio::test]
+    async fn test_find_backlog() {
+        let db = Database::in_memory().await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        for run_id in ["discovered", "retrying", "given_up", "passed"] {
+            db.insert_run(NewRun::new(run_id, "game1", "cat1", submitted_date))
+                .await
+                .unwrap();
+        }
+        for run_id in ["retrying", "given_up"] {
+            db.mark_run_error(run_id, "timeout").await.unwrap();
+        }
+        db.schedule_retry("retrying", 1, "retryable", Utc::now())
+            .await
+            .unwrap();
+        db.mark_run_passed("passed").await.unwrap();
+
+        let now = Utc::now();
+        assert_eq!(find_backlog(&db, 24, now).await.unwrap(), None);
+
+        let summary = find_backlog(&db, 24, now + chrono::Duration::hours(25))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((summary.discovered, summary.error), (1, 1));
+        assert_eq!(summary.oldest_run_ids, ["discovered", "retrying"]);
+        assert_eq!(
+            summary.headline(),
+            "2 run(s) waiting over 24h for verification (1 discovered, 1 erroring)"
+        );
+    }
+}
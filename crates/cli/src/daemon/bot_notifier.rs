@@ -1,253 +1,156 @@
-use crate::daemon::database::{connection::Database, types::RunStatus};
-use log::{info, warn};
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::{Review, RunStatus};
+use crate::daemon::notifier::{Notification, Notifier, batch_failed};
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use log::info;
 use reqwest::Client;
 use std::time::Duration;
-use tokio::sync::mpsc;
-use tokio_util::sync::CancellationToken;
 
 use super::config::BotNotifierConfig;
 
-const MAX_NOTIFY_ATTEMPTS: usize = 5;
 pub const AUTH_TOKEN_ENV_VAR: &str = "RUNNER_STATUS_AUTH_TOKEN";
 
-#[derive(Clone)]
-pub struct BotNotifierHandle {
-    tx: mpsc::Sender<String>,
-}
-
-impl BotNotifierHandle {
-    pub fn new() -> (Self, mpsc::Receiver<String>) {
-        let (tx, rx) = mpsc::channel(64);
-        (Self { tx }, rx)
-    }
-
-    pub fn notify(&self, run_id: String) {
-        let _ = self.tx.try_send(run_id);
-    }
-}
-
-pub async fn run_bot_notifier_actor(
-    mut rx: mpsc::Receiver<String>,
+/// Reports run statuses to the Discord bot's API, and which runs are still in progress.
+pub struct BotNotifier {
     db: Database,
+    client: Client,
     config: BotNotifierConfig,
-    token: CancellationToken,
     auth_token: String,
-) -> Result<(), anyhow::Error> {
-    info!("Starting bot notifier");
-    let client = Client::new();
-
-    retry_unnotified(&db, &client, &config, &auth_token).await;
-    send_heartbeat(&db, &client, &config, &auth_token).await;
-
-    let mut poll_interval =
-        tokio::time::interval(Duration::from_secs(config.poll_interval_seconds));
-    poll_interval.tick().await;
-
-    loop {
-        tokio::select! {
-            Some(run_id) = rx.recv() => {
-                notify_run(&db, &client, &config, &auth_token, &run_id).await;
-            }
-            _ = poll_interval.tick() => {
-                retry_unnotified(&db, &client, &config, &auth_token).await;
-                send_heartbeat(&db, &client, &config, &auth_token).await;
-            }
-            _ = token.cancelled() => {
-                info!("Bot notifier shutting down");
-                return Ok(());
-            }
-        }
-    }
 }
 
-async fn notify_run(
-    db: &Database,
-    client: &Client,
-    config: &BotNotifierConfig,
-    auth_token: &str,
-    run_id: &str,
-) {
-    for _ in 0..MAX_NOTIFY_ATTEMPTS {
-        let Some(run) = db.get_run(run_id).await.ok().flatten() else {
-            return;
-        };
-        if run.bot_notified {
-            return;
-        }
-
-        let status = run_status_to_bot_status(&run.status);
-        if !post_status(
-            client,
+impl BotNotifier {
+    pub fn new(db: Database, config: BotNotifierConfig, auth_token: String) -> Self {
+        Self {
+            db,
+            client: Client::new(),
             config,
             auth_token,
-            run_id,
-            status,
-            run.error_message.as_deref(),
-        )
-        .await
-        {
-            return;
-        }
-
-        let updated = db
-            .set_bot_notified_if_status(run_id, true, &run.status)
-            .await
-            .unwrap_or(false);
-        if updated {
-            return;
         }
     }
-}
 
-async fn retry_unnotified(
-    db: &Database,
-    client: &Client,
-    config: &BotNotifierConfig,
-    auth_token: &str,
-) {
-    let runs = match db.get_unnotified_runs().await {
-        Ok(runs) => runs,
-        Err(e) => {
-            warn!("Failed to query unnotified runs: {}", e);
-            return;
+    /// Uses RUNNER_STATUS_AUTH_TOKEN if the config has no `auth_token`.
+    pub fn from_config(db: Database, config: BotNotifierConfig) -> Result<Self> {
+        let auth_token = match &config.auth_token {
+            Some(auth_token) => auth_token.expose().to_string(),
+            None => std::env::var(AUTH_TOKEN_ENV_VAR).context(
+                "auth_token or RUNNER_STATUS_AUTH_TOKEN env var is required for bot notifier",
+            )?,
+        };
+        Ok(Self::new(db, config, auth_token))
+    }
+
+    async fn post(&self, path: &str, body: &serde_json::Value) -> Result<()> {
+        let url = format!("{}{}", self.config.bot_url, path);
+        let resp = self
+            .client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", self.auth_token))
+            .json(body)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            bail!("HTTP {}", resp.status());
         }
-    };
-
-    if runs.is_empty() {
-        return;
+        Ok(())
     }
 
-    let entries: Vec<serde_json::Value> = runs
-        .iter()
-        .map(|run| {
-            serde_json::json!({
-                "runId": run.run_id,
-                "status": run_status_to_bot_status(&run.status),
-                "message": run.error_message,
-            })
-        })
-        .collect();
-
-    if !post_statuses_bulk(client, config, auth_token, &entries).await {
-        warn!("Bulk notification failed for {} runs", runs.len());
-        return;
+    /// Records the delivery in `bot_notified`, unless the run changed meanwhile.
+    async fn mark_notified(&self, notification: &Notification) -> Result<()> {
+        let run = &notification.run;
+        self.db
+            .set_bot_notified_if_status(&run.run_id, true, &run.status)
+            .await?;
+        Ok(())
     }
+}
 
-    for run in &runs {
-        let _ = db
-            .set_bot_notified_if_status(&run.run_id, true, &run.status)
-            .await;
+#[async_trait]
+impl Notifier for BotNotifier {
+    fn name(&self) -> &'static str {
+        "bot"
     }
 
-    info!("Bulk notified {} runs", runs.len());
-}
+    /// The bot only learns of verdicts from these, so they are never dropped.
+    fn max_attempts(&self) -> Option<u32> {
+        None
+    }
 
-async fn post_statuses_bulk(
-    client: &Client,
-    config: &BotNotifierConfig,
-    auth_token: &str,
-    entries: &[serde_json::Value],
-) -> bool {
-    let url = format!("{}/api/runs/status", config.bot_url);
-    let body = serde_json::json!({ "runs": entries });
-
-    let result = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", auth_token))
-        .json(&body)
-        .send()
-        .await;
-
-    match result {
-        Ok(resp) if resp.status().is_success() => true,
-        Ok(resp) => {
-            warn!("Bulk notification failed (HTTP {})", resp.status());
-            false
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let run = &notification.run;
+        let status = run_status_to_bot_status(&run.status);
+        let body = serde_json::json!({
+            "status": status,
+            "message": run.error_message,
+            "review": review_json(notification.review.as_ref()),
+        });
+        self.post(&format!("/api/runs/{}/status", run.run_id), &body)
+            .await?;
+        info!("Bot notified for run {} with status {}", run.run_id, status);
+        self.mark_notified(notification).await
+    }
+
+    async fn notify_batch(&self, notifications: &[Notification]) -> Vec<Result<()>> {
+        if let [notification] = notifications {
+            return vec![self.notify(notification).await];
         }
-        Err(e) => {
-            warn!("Bulk notification error: {}", e);
-            false
+        let entries: Vec<serde_json::Value> = notifications
+            .iter()
+            .map(|notification| {
+                serde_json::json!({
+                    "runId": notification.run.run_id,
+                    "status": run_status_to_bot_status(&notification.run.status),
+                    "message": notification.run.error_message,
+                    "review": review_json(notification.review.as_ref()),
+                })
+            })
+            .collect();
+        let body = serde_json::json!({ "runs": entries });
+        if let Err(e) = self.post("/api/runs/status", &body).await {
+            return batch_failed(notifications.len(), &e.context("Bulk notification failed"));
         }
-    }
-}
+        info!("Bulk notified {} runs", notifications.len());
 
-async fn send_heartbeat(
-    db: &Database,
-    client: &Client,
-    config: &BotNotifierConfig,
-    auth_token: &str,
-) {
-    let runs = match db.get_non_final_runs().await {
-        Ok(runs) => runs,
-        Err(e) => {
-            warn!("Failed to query non-final runs for heartbeat: {}", e);
-            return;
+        let mut results = Vec::with_capacity(notifications.len());
+        for notification in notifications {
+            results.push(self.mark_notified(notification).await);
         }
-    };
-
-    if runs.is_empty() {
-        return;
+        results
     }
 
-    let run_ids: Vec<&str> = runs.iter().map(|r| r.run_id.as_str()).collect();
-    let url = format!("{}/api/runs/heartbeat", config.bot_url);
-    let body = serde_json::json!({ "runIds": run_ids });
-
-    let result = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", auth_token))
-        .json(&body)
-        .send()
-        .await;
+    fn heartbeat_interval(&self) -> Option<Duration> {
+        Some(Duration::from_secs(self.config.poll_interval_seconds))
+    }
 
-    match result {
-        Ok(resp) if resp.status().is_success() => {
-            info!("Heartbeat sent for {} runs", run_ids.len());
-        }
-        Ok(resp) => {
-            warn!("Heartbeat failed (HTTP {})", resp.status());
-        }
-        Err(e) => {
-            warn!("Heartbeat error: {}", e);
+    /// Lists the runs still in progress, so the bot knows they haven't been forgotten.
+    async fn heartbeat(&self) -> Result<()> {
+        let runs = self
+            .db
+            .get_non_final_runs()
+            .await
+            .context("Failed to query non-final runs for heartbeat")?;
+        if runs.is_empty() {
+            return Ok(());
         }
+
+        let run_ids: Vec<&str> = runs.iter().map(|r| r.run_id.as_str()).collect();
+        let body = serde_json::json!({ "runIds": run_ids });
+        self.post("/api/runs/heartbeat", &body).await?;
+        info!("Heartbeat sent for {} runs", run_ids.len());
+        Ok(())
     }
 }
 
-async fn post_status(
-    client: &Client,
-    config: &BotNotifierConfig,
-    auth_token: &str,
-    run_id: &str,
-    status: &str,
-    message: Option<&str>,
-) -> bool {
-    let url = format!("{}/api/runs/{}/status", config.bot_url, run_id);
-    let body = serde_json::json!({ "status": status, "message": message });
-
-    let result = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", auth_token))
-        .json(&body)
-        .send()
-        .await;
-
-    match result {
-        Ok(resp) if resp.status().is_success() => {
-            info!("Bot notified for run {} with status {}", run_id, status);
-            true
-        }
-        Ok(resp) => {
-            warn!(
-                "Bot notification failed for run {} (HTTP {})",
-                run_id,
-                resp.status()
-            );
-            false
-        }
-        Err(e) => {
-            warn!("Bot notification error for run {}: {}", run_id, e);
-            false
-        }
+/// The reviewer's decision behind a passed or failed status, or null if there was no review.
+fn review_json(review: Option<&Review>) -> serde_json::Value {
+    match review {
+        Some(review) => serde_json::json!({
+            "decision": review.decision,
+            "reviewer": review.reviewer,
+            "note": review.note,
+            "reviewedAt": review.reviewed_at.to_rfc3339(),
+        }),
+        None => serde_json::Value::Null,
     }
 }
 
@@ -266,8 +169,8 @@ pub fn run_status_to_bot_status(status: &RunStatus) -> &'static str {
 mod tests {
     use super::*;
     use crate::daemon::config::BotNotifierConfig;
-    use crate::daemon::database::types::NewRun;
-    use wiremock::matchers::{header, method, path};
+    use crate::daemon::database::types::{NewRun, ReviewDecision};
+    use wiremock::matchers::{body_partial_json, header, method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     const TEST_TOKEN: &str = "test-token";
@@ -275,10 +178,27 @@ mod tests {
     fn make_config(bot_url: &str) -> BotNotifierConfig {
         BotNotifierConfig {
             bot_url: bot_url.to_string(),
+            auth_token: None,
             poll_interval_seconds: 1800,
         }
     }
 
+    fn make_notifier(db: &Database, bot_url: &str) -> BotNotifier {
+        BotNotifier::new(db.clone(), make_config(bot_url), TEST_TOKEN.to_string())
+    }
+
+    async fn notification(db: &Database, run_id: &str) -> Notification {
+        Notification::load(db, run_id).await.unwrap().unwrap()
+    }
+
+    async fn notifications(db: &Database, run_ids: &[&str]) -> Vec<Notification> {
+        let mut notifications = Vec::new();
+        for run_id in run_ids {
+            notifications.push(notification(db, run_id).await);
+        }
+        notifications
+    }
+
     async fn insert_test_run(db: &Database, run_id: &str) {
         let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
         let new_run = NewRun::new(run_id, "game1", "cat1", submitted_date);
@@ -298,9 +218,11 @@ mod tests {
         let db = Database::in_memory().await.unwrap();
         insert_test_run(&db, "run123").await;
 
-        let client = Client::new();
-        let config = make_config(&mock_server.uri());
-        notify_run(&db, &client, &config, TEST_TOKEN, "run123").await;
+        let notifier = make_notifier(&db, &mock_server.uri());
+        notifier
+            .notify(&notification(&db, "run123").await)
+            .await
+            .unwrap();
 
         mock_server.verify().await;
 
@@ -308,6 +230,38 @@ mod tests {
         assert!(run.bot_notified);
     }
 
+    #[tokio::test]
+    async fn test_notification_includes_review() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/runs/run123/status"))
+            .and(body_partial_json(serde_json::json!({
+                "status": "passed",
+                "review": { "decision": "accepted", "reviewer": "alice", "note": "fine" },
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let db = Database::in_memory().await.unwrap();
+        insert_test_run(&db, "run123").await;
+        db.mark_run_needs_review("run123", Some("used /editor"))
+            .await
+            .unwrap();
+        db.record_review("run123", ReviewDecision::Accepted, "alice", Some("fine"))
+            .await
+            .unwrap();
+
+        let notifier = make_notifier(&db, &mock_server.uri());
+        notifier
+            .notify(&notification(&db, "run123").await)
+            .await
+            .unwrap();
+
+        mock_server.verify().await;
+    }
+
     #[tokio::test]
     async fn test_auth_header_format() {
         let mock_server = MockServer::start().await;
@@ -322,9 +276,11 @@ mod tests {
         let db = Database::in_memory().await.unwrap();
         insert_test_run(&db, "run123").await;
 
-        let client = Client::new();
-        let config = make_config(&mock_server.uri());
-        notify_run(&db, &client, &config, TEST_TOKEN, "run123").await;
+        let notifier = make_notifier(&db, &mock_server.uri());
+        notifier
+            .notify(&notification(&db, "run123").await)
+            .await
+            .unwrap();
 
         mock_server.verify().await;
     }
@@ -341,9 +297,13 @@ mod tests {
         let db = Database::in_memory().await.unwrap();
         insert_test_run(&db, "run500").await;
 
-        let client = Client::new();
-        let config = make_config(&mock_server.uri());
-        notify_run(&db, &client, &config, TEST_TOKEN, "run500").await;
+        let notifier = make_notifier(&db, &mock_server.uri());
+        assert!(
+            notifier
+                .notify(&notification(&db, "run500").await)
+                .await
+                .is_err()
+        );
 
         let run = db.get_run("run500").await.unwrap().unwrap();
         assert!(!run.bot_notified);
@@ -354,16 +314,20 @@ mod tests {
         let db = Database::in_memory().await.unwrap();
         insert_test_run(&db, "run_unreachable").await;
 
-        let client = Client::new();
-        let config = make_config("http://127.0.0.1:19999");
-        notify_run(&db, &client, &config, TEST_TOKEN, "run_unreachable").await;
+        let notifier = make_notifier(&db, "http://127.0.0.1:19999");
+        assert!(
+            notifier
+                .notify(&notification(&db, "run_unreachable").await)
+                .await
+                .is_err()
+        );
 
         let run = db.get_run("run_unreachable").await.unwrap().unwrap();
         assert!(!run.bot_notified);
     }
 
     #[tokio::test]
-    async fn test_retry_unnotified_sends_bulk_request() {
+    async fn test_batch_sends_bulk_request() {
         let mock_server = MockServer::start().await;
         Mock::given(method("POST"))
             .and(path("/api/runs/status"))
@@ -374,20 +338,22 @@ mod tests {
             .await;
 
         let db = Database::in_memory().await.unwrap();
-        insert_test_run(&db, "run_retry").await;
+        insert_test_run(&db, "run_retry1").await;
+        insert_test_run(&db, "run_retry2").await;
 
-        let client = Client::new();
-        let config = make_config(&mock_server.uri());
-        retry_unnotified(&db, &client, &config, TEST_TOKEN).await;
+        let notifier = make_notifier(&db, &mock_server.uri());
+        let batch = notifications(&db, &["run_retry1", "run_retry2"]).await;
+        let results = notifier.notify_batch(&batch).await;
+        assert!(results.iter().all(Result::is_ok));
 
         mock_server.verify().await;
 
-        let run = db.get_run("run_retry").await.unwrap().unwrap();
+        let run = db.get_run("run_retry1").await.unwrap().unwrap();
         assert!(run.bot_notified);
     }
 
     #[tokio::test]
-    async fn test_retry_unnotified_bulk_with_multiple_runs() {
+    async fn test_batch_with_multiple_runs() {
         let mock_server = MockServer::start().await;
         Mock::given(method("POST"))
             .and(path("/api/runs/status"))
@@ -401,9 +367,9 @@ mod tests {
         insert_test_run(&db, "bulk_2").await;
         insert_test_run(&db, "bulk_3").await;
 
-        let client = Client::new();
-        let config = make_config(&mock_server.uri());
-        retry_unnotified(&db, &client, &config, TEST_TOKEN).await;
+        let notifier = make_notifier(&db, &mock_server.uri());
+        let batch = notifications(&db, &["bulk_1", "bulk_2", "bulk_3"]).await;
+        notifier.notify_batch(&batch).await;
 
         mock_server.verify().await;
 
@@ -414,7 +380,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_retry_unnotified_bulk_failure_leaves_unnotified() {
+    async fn test_batch_failure_leaves_unnotified() {
         let mock_server = MockServer::start().await;
         Mock::given(method("POST"))
             .and(path("/api/runs/status"))
@@ -423,98 +389,32 @@ mod tests {
             .await;
 
         let db = Database::in_memory().await.unwrap();
-        insert_test_run(&db, "bulk_fail").await;
+        insert_test_run(&db, "bulk_fail1").await;
+        insert_test_run(&db, "bulk_fail2").await;
 
-        let client = Client::new();
-        let config = make_config(&mock_server.uri());
-        retry_unnotified(&db, &client, &config, TEST_TOKEN).await;
+        let notifier = make_notifier(&db, &mock_server.uri());
+        let batch = notifications(&db, &["bulk_fail1", "bulk_fail2"]).await;
+        let results = notifier.notify_batch(&batch).await;
+        assert!(results.iter().all(Result::is_err));
 
-        let run = db.get_run("bulk_fail").await.unwrap().unwrap();
+        let run = db.get_run("bulk_fail1").await.unwrap().unwrap();
         assert!(!run.bot_notified);
     }
 
-    #[tokio::test]
-    async fn test_retry_unnotified_skips_when_no_runs() {
-        let mock_server = MockServer::start().await;
-        Mock::given(method("POST"))
-            .respond_with(ResponseTemplate::new(200))
-            .expect(0)
-            .mount(&mock_server)
-            .await;
-
-        let db = Database::in_memory().await.unwrap();
-
-        let client = Client::new();
-        let config = make_config(&mock_server.uri());
-        retry_unnotified(&db, &client, &config, TEST_TOKEN).await;
-
-        mock_server.verify().await;
-    }
-
     #[tokio::test]
     async fn test_bot_notified_flag_db_operations() {
         let db = Database::in_memory().await.unwrap();
         insert_test_run(&db, "run_flag").await;
 
-        let unnotified = db.get_unnotified_runs().await.unwrap();
-        assert_eq!(unnotified.len(), 1);
-        assert_eq!(unnotified[0].run_id, "run_flag");
+        let run = db.get_run("run_flag").await.unwrap().unwrap();
+        assert!(!run.bot_notified);
 
         db.set_bot_notified("run_flag", true).await.unwrap();
 
-        let unnotified = db.get_unnotified_runs().await.unwrap();
-        assert!(unnotified.is_empty());
-
         let run = db.get_run("run_flag").await.unwrap().unwrap();
         assert!(run.bot_notified);
     }
 
-    #[tokio::test]
-    async fn test_handle_notify_sends_to_channel() {
-        let (handle, mut rx) = BotNotifierHandle::new();
-        handle.notify("run123".to_string());
-        let received = rx.recv().await.unwrap();
-        assert_eq!(received, "run123");
-    }
-
-    #[tokio::test]
-    async fn test_notify_skips_already_notified_run() {
-        let mock_server = MockServer::start().await;
-        Mock::given(method("POST"))
-            .respond_with(ResponseTemplate::new(200))
-            .expect(0)
-            .mount(&mock_server)
-            .await;
-
-        let db = Database::in_memory().await.unwrap();
-        insert_test_run(&db, "run_already").await;
-        db.set_bot_notified("run_already", true).await.unwrap();
-
-        let client = Client::new();
-        let config = make_config(&mock_server.uri());
-        notify_run(&db, &client, &config, TEST_TOKEN, "run_already").await;
-
-        mock_server.verify().await;
-    }
-
-    #[tokio::test]
-    async fn test_notify_nonexistent_run_does_nothing() {
-        let mock_server = MockServer::start().await;
-        Mock::given(method("POST"))
-            .respond_with(ResponseTemplate::new(200))
-            .expect(0)
-            .mount(&mock_server)
-            .await;
-
-        let db = Database::in_memory().await.unwrap();
-
-        let client = Client::new();
-        let config = make_config(&mock_server.uri());
-        notify_run(&db, &client, &config, TEST_TOKEN, "nonexistent").await;
-
-        mock_server.verify().await;
-    }
-
     #[tokio::test]
     async fn test_set_bot_notified_if_status_matches() {
         let db = Database::in_memory().await.unwrap();
@@ -581,9 +481,8 @@ mod tests {
         insert_test_run(&db, "run_hb2").await;
         db.mark_run_passed("run_hb2").await.unwrap();
 
-        let client = Client::new();
-        let config = make_config(&mock_server.uri());
-        send_heartbeat(&db, &client, &config, TEST_TOKEN).await;
+        let notifier = make_notifier(&db, &mock_server.uri());
+        notifier.heartbeat().await.unwrap();
 
         mock_server.verify().await;
     }
@@ -599,9 +498,8 @@ mod tests {
 
         let db = Database::in_memory().await.unwrap();
 
-        let client = Client::new();
-        let config = make_config(&mock_server.uri());
-        send_heartbeat(&db, &client, &config, TEST_TOKEN).await;
+        let notifier = make_notifier(&db, &mock_server.uri());
+        notifier.heartbeat().await.unwrap();
 
         mock_server.verify().await;
     }
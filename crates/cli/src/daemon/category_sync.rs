@@ -0,0 +1,240 @@
+//! Checks the game rules against speedrun.com's categories, so that renamed, deleted and new
+//! categories are noticed instead of their runs silently going unverified.
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use super::config::SrcRunRules;
+use super::database::connection::Database;
+use super::database::types::NameKind;
+use super::speedrun_api::{ApiError, Category, CategoryType, SpeedrunClient};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum CategoryChange {
+    UnknownGame {
+        game_id: String,
+    },
+    /// A configured category speedrun.com no longer lists for its game.
+    Deleted {
+        game_id: String,
+        category_id: String,
+        last_name: Option<String>,
+    },
+    /// Renamed since its name was last cached.
+    Renamed {
+        game_id: String,
+        category_id: String,
+        old_name: String,
+        new_name: String,
+    },
+    /// A full-game category without rules, whose runs aren't verified.
+    New {
+        game_id: String,
+        category_id: String,
+        name: String,
+    },
+}
+
+impl fmt::Display for CategoryChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CategoryChange::UnknownGame { game_id } => {
+                write!(f, "game {game_id} does not exist on speedrun.com")
+            }
+            CategoryChange::Deleted {
+                game_id,
+                category_id,
+                last_name,
+            } => {
+                write!(f, "{game_id}/{category_id}")?;
+                if let Some(last_name) = last_name {
+                    write!(f, " ({last_name})")?;
+                }
+                write!(f, " no longer exists; remove it from the game rules")
+            }
+            CategoryChange::Renamed {
+                game_id,
+                category_id,
+                old_name,
+                new_name,
+            } => write!(
+                f,
+                "{game_id}/{category_id} was renamed from \"{old_name}\" to \"{new_name}\"; check its rules still apply"
+            ),
+            CategoryChange::New {
+                game_id,
+                category_id,
+                name,
+            } => write!(
+                f,
+                "{game_id}/{category_id} (\"{name}\") has no rules; add `{category_id}: # {name}` to the categories of {game_id} to verify its runs"
+            ),
+        }
+    }
+}
+
+/// Compares each configured game's categories to speedrun.com's, and caches their current
+/// names in `db`, which is also where renames are detected from.
+pub async fn check_categories(
+    client: &SpeedrunClient,
+    db: &Database,
+    src_rules: &SrcRunRules,
+) -> Result<Vec<CategoryChange>> {
+    let mut changes = Vec::new();
+    let games: BTreeMap<_, _> = src_rules.games.iter().collect();
+    for (game_id, game_config) in games {
+        let live = match client.get_game_categories(game_id).await {
+            Ok(live) => live,
+            Err(ApiError::NotFound(_)) => {
+                changes.push(CategoryChange::UnknownGame {
+                    game_id: game_id.clone(),
+                });
+                continue;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to list categories of {game_id}"));
+            }
+        };
+
+        let mut cached_names = HashMap::new();
+        for id in game_config.categories.keys() {
+            if let Some(name) = db
+                .get_cached_name(NameKind::Category, id)
+                .await?
+                .and_then(|cached| cached.name)
+            {
+                cached_names.insert(id.clone(), name);
+            }
+        }
+
+        let configured = game_config.categories.keys().map(String::as_str).collect();
+        changes.extend(diff_categories(game_id, configured, &live, &cached_names));
+
+        for category in &live {
+            db.cache_name(NameKind::Category, &category.id, &category.name)
+                .await?;
+        }
+    }
+    Ok(changes)
+}
+
+fn diff_categories(
+    game_id: &str,
+    mut configured: Vec<&str>,
+    live: &[Category],
+    cached_names: &HashMap<String, String>,
+) -> Vec<CategoryChange> {
+    configured.sort();
+    let live_by_id: HashMap<&str, &Category> = live
+        .iter()
+        .map(|category| (category.id.as_str(), category))
+        .collect();
+
+    let mut changes = Vec::new();
+    for &category_id in &configured {
+        let cached_name = cached_names.get(category_id);
+        match live_by_id.get(category_id) {
+            None => changes.push(CategoryChange::Deleted {
+                game_id: game_id.to_string(),
+                category_id: category_id.to_string(),
+                last_name: cached_name.cloned(),
+            }),
+            Some(category) => {
+                if let Some(old_name) = cached_name
+                    && *old_name != category.name
+                {
+                    changes.push(CategoryChange::Renamed {
+                        game_id: game_id.to_string(),
+                        category_id: category_id.to_string(),
+                        old_name: old_name.clone(),
+                        new_name: category.name.clone(),
+                    });
+                }
+            }
+        }
+    }
+    for category in live {
+        if category.category_type == CategoryType::PerGame
+            && !configured.contains(&category.id.as_str())
+        {
+            changes.push(CategoryChange::New {
+                game_id: game_id.to_string(),
+                category_id: category.id.clone(),
+                name: category.name.clone(),
+            });
+        }
+    }
+    changes
+}
+
+/// Logs [`check_categories`]'s findings, for the daemon to run on startup.
+pub async fn log_category_changes(client: SpeedrunClient, db: Database, src_rules: SrcRunRules) {
+    match check_categories(&client, &db, &src_rules).await {
+        Ok(changes) if changes.is_empty() => info!("Game rules match speedrun.com's categories"),
+        Ok(changes) => {
+            for change in changes {
+                warn!("Game rules out of date: {}", change);
+            }
+        }
+        Err(e) => warn!("Failed to check categories on speedrun.com: {:#}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(id: &str, name: &str, category_type: CategoryType) -> Category {
+        Category {
+            id: id.to_string(),
+            name: name.to_string(),
+            category_type,
+        }
+    }
+
+    #[test]
+    fn test_diff_categories() {
+        let live = [
+            category("any", "Any% NG", CategoryType::PerGame),
+            category("hundred", "100%", CategoryType::PerGame),
+            category("new", "Lazy Bastard", CategoryType::PerGame),
+            category("level", "Tutorial", CategoryType::PerLevel),
+        ];
+        let cached_names = HashMap::from([
+            ("any".to_string(), "Any%".to_string()),
+            ("hundred".to_string(), "100%".to_string()),
+            ("gone".to_string(), "Steelaxe%".to_string()),
+        ]);
+        let changes = diff_categories(
+            "game1",
+            vec!["hundred", "gone", "any"],
+            &live,
+            &cached_names,
+        );
+        assert_eq!(
+            changes,
+            vec![
+                CategoryChange::Renamed {
+                    game_id: "game1".to_string(),
+                    category_id: "any".to_string(),
+                    old_name: "Any%".to_string(),
+                    new_name: "Any% NG".to_string(),
+                },
+                CategoryChange::Deleted {
+                    game_id: "game1".to_string(),
+                    category_id: "gone".to_string(),
+                    last_name: Some("Steelaxe%".to_string()),
+                },
+                CategoryChange::New {
+                    game_id: "game1".to_string(),
+                    category_id: "new".to_string(),
+                    name: "Lazy Bastard".to_string(),
+                },
+            ]
+        );
+    }
+}
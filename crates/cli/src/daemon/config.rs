@@ -1,28 +1,127 @@
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
+use ed25519_dalek::SigningKey;
 use factorio_manager::expected_mods::ExpectedMods;
-use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::PathBuf};
+use factorio_manager::factorio_image::FactorioImage;
+use factorio_manager::factorio_install_dir::FactorioCredentials;
+use factorio_manager::process_manager::Sandbox;
+use replay_script::locale::RuleDescriptions;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use zip_downloader::throttle::ThrottleConfig;
 
 use crate::config::RunRules;
+use crate::daemon::archive::ArchiveConfig;
+use crate::daemon::backlog::BacklogAlertConfig;
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::RunStatus;
+use crate::daemon::factorio_log::FactorioLogConfig;
+use crate::daemon::hooks::HooksConfig;
+use crate::daemon::internet_archive::{InternetArchive, InternetArchiveConfig};
+use crate::daemon::janitor::RetentionConfig;
+use crate::daemon::liveness::LivenessConfig;
 use crate::daemon::retry::RetryConfig;
+use crate::daemon::scheduling::SchedulingPolicy;
+use crate::daemon::speedrun_api::SpeedrunClient;
+use crate::run_replay::plugin;
+use crate::signing;
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct PollingConfig {
     #[serde(default = "default_poll_interval_seconds")]
     pub poll_interval_seconds: u64,
     #[serde(default = "default_lookback_days")]
     pub lookback_days: u64,
+    /// Poll intervals in seconds keyed by "{game_id}/{category_id}" or "{game_id}", for
+    /// categories polled more or less often than `poll_interval_seconds`.
+    #[serde(default)]
+    pub intervals: HashMap<String, u64>,
+    /// A category whose poll fails is polled again after twice its interval, doubling per
+    /// failure up to this, with jitter.
+    #[serde(default = "default_max_backoff_seconds")]
+    pub max_backoff_seconds: u64,
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_seconds: default_poll_interval_seconds(),
+            lookback_days: default_lookback_days(),
+            intervals: HashMap::new(),
+            max_backoff_seconds: default_max_backoff_seconds(),
+        }
+    }
+}
+
+impl PollingConfig {
+    pub fn interval(&self, game_id: &str, category_id: &str) -> Duration {
+        let seconds = [format!("{}/{}", game_id, category_id), game_id.to_string()]
+            .iter()
+            .find_map(|key| self.intervals.get(key))
+            .copied()
+            .unwrap_or(self.poll_interval_seconds);
+        Duration::from_secs(seconds)
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        Duration::from_secs(self.max_backoff_seconds)
+    }
 }
 
 fn default_lookback_days() -> u64 {
     30
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+fn default_max_backoff_seconds() -> u64 {
+    6 * 3600
+}
+
+/// A credential from the config, kept out of logs and printed configs.
+#[derive(Clone, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str("***")
+    }
+}
+
+/// factorio.com account used to download Factorio.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FactorioAccountConfig {
+    pub username: String,
+    pub token: Secret,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct BotNotifierConfig {
     pub bot_url: String,
+    /// Defaults to RUNNER_STATUS_AUTH_TOKEN.
+    #[serde(default)]
+    pub auth_token: Option<Secret>,
     #[serde(default = "default_notifier_poll_interval_seconds")]
     pub poll_interval_seconds: u64,
 }
@@ -31,23 +130,320 @@ fn default_notifier_poll_interval_seconds() -> u64 {
     1800
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DiscordNotifierConfig {
+    /// Finished runs are collected and posted together at this interval
+    #[serde(default = "default_discord_batch_interval_seconds")]
+    pub batch_interval_seconds: u64,
+}
+
+fn default_discord_batch_interval_seconds() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub urls: Vec<String>,
+    /// Final statuses that trigger a notification
+    #[serde(default = "default_webhook_statuses")]
+    pub statuses: Vec<RunStatus>,
+}
+
+fn default_webhook_statuses() -> Vec<RunStatus> {
+    vec![
+        RunStatus::Passed,
+        RunStatus::NeedsReview,
+        RunStatus::Failed,
+        RunStatus::Error,
+    ]
+}
+
+/// Mails run results through a sendmail-compatible program.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct EmailNotifierConfig {
+    pub from: String,
+    pub to: Vec<String>,
+    /// Called as `sendmail -t -i` with the message on stdin
+    #[serde(default = "default_sendmail")]
+    pub sendmail: PathBuf,
+    /// Final statuses that trigger a mail
+    #[serde(default = "default_webhook_statuses")]
+    pub statuses: Vec<RunStatus>,
+}
+
+fn default_sendmail() -> PathBuf {
+    PathBuf::from("sendmail")
+}
+
+/// How requests identify themselves to speedrun.com.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpeedrunApiConfig {
+    /// speedrun.com asks for a User-Agent with contact details, e.g.
+    /// "factorio-replay-runner (contact: mods@example.com)".
+    pub user_agent: Option<String>,
+    /// Defaults to SPEEDRUN_API_KEY. Authenticated requests get higher rate limits.
+    pub api_key: Option<Secret>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HttpApiConfig {
+    /// Address to listen on, e.g. "127.0.0.1:8080"
+    pub bind: SocketAddr,
+    /// Largest save accepted by POST /verify, raised to the largest category
+    /// `security.max_file_size_mb`. Each category's own limit then applies.
+    #[serde(default = "default_max_upload_mb")]
+    pub max_upload_mb: u64,
+}
+
+fn default_max_upload_mb() -> u64 {
+    100
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct DaemonConfig {
+    /// Dotenv-style file of secrets (e.g. RUNNER_STATUS_AUTH_TOKEN,
+    /// GOOGLE_DRIVE_SERVICE_ACCOUNT_KEY), added to the environment on startup. Variables
+    /// already set take precedence.
+    #[serde(default)]
+    pub secrets_file: Option<PathBuf>,
     #[serde(default = "default_game_rules_file")]
     pub game_rules_file: PathBuf,
     #[serde(default = "default_install_dir")]
     pub install_dir: PathBuf,
+    /// Least recently used Factorio versions are removed once installs take more than this
+    /// many GB (per worker).
+    #[serde(default)]
+    pub install_quota_gb: Option<u64>,
+    /// Isolation for Factorio processes, which run Lua from untrusted saves.
+    #[serde(default)]
+    pub sandbox: Sandbox,
+    /// Run Factorio from container images instead of downloaded installs; replaces the
+    /// sandbox.
+    #[serde(default)]
+    pub factorio_image: Option<FactorioImage>,
+    /// Account for downloading Factorio on platforms without a headless build. Defaults to
+    /// FACTORIO_USERNAME and FACTORIO_TOKEN.
+    #[serde(default)]
+    pub factorio_account: Option<FactorioAccountConfig>,
     #[serde(default = "default_output_dir")]
     pub output_dir: PathBuf,
     #[serde(default = "default_database_path")]
     pub database_path: PathBuf,
+    /// Connection URL used instead of `database_path`, e.g. "sqlite:///srv/runs.db" or
+    /// "postgres://verifier@db.internal/runs"
+    #[serde(default)]
+    pub database_url: Option<String>,
     #[serde(default)]
     pub polling: PollingConfig,
     #[serde(default)]
+    pub speedrun_api: SpeedrunApiConfig,
+    #[serde(default)]
     pub retry: RetryConfig,
+    /// Number of replays verified in parallel. With more than one, each worker gets its own
+    /// Factorio installs under `install_dir/worker-N`.
+    #[serde(default = "default_max_concurrent_runs")]
+    pub max_concurrent_runs: usize,
+    /// On shutdown, how long in-flight runs may finish before they are aborted and requeued
+    #[serde(default = "default_shutdown_drain_seconds")]
+    pub shutdown_drain_seconds: u64,
+    /// Names this daemon in run leases when several share one database. Defaults to the host name.
+    #[serde(default)]
+    pub instance_id: Option<String>,
     #[serde(default)]
     pub bot_notifier: Option<BotNotifierConfig>,
+    /// Posts run results to a Discord webhook, set in DISCORD_WEBHOOK_URL.
+    #[serde(default)]
+    pub discord_notifier: Option<DiscordNotifierConfig>,
+    /// Scheduling policies keyed by "{game_id}/{category_id}", "{game_id}", or "default".
+    #[serde(default)]
+    pub scheduling: HashMap<String, SchedulingPolicy>,
+    /// Download limits keyed by service name (e.g. "google_drive"), or "default" for the rest.
+    #[serde(default)]
+    pub download_limits: HashMap<String, ThrottleConfig>,
+    /// Where verified saves and logs are copied to, keyed by `{game}/{category}/{run_id}`.
+    #[serde(default)]
+    pub archive: Option<ArchiveConfig>,
+    /// Uploads the saves and reports of passed runs to the Internet Archive, one item per run.
+    #[serde(default)]
+    pub internet_archive: Option<InternetArchiveConfig>,
+    /// Keeps Factorio's complete output per run under `{output_dir}/{run_id}/factorio.log.zst`.
+    #[serde(default)]
+    pub factorio_log: Option<FactorioLogConfig>,
+    /// Periodically deletes run artifacts under `output_dir`, which otherwise grow unboundedly.
+    #[serde(default)]
+    pub retention: Option<RetentionConfig>,
+    #[serde(default)]
+    pub http_api: Option<HttpApiConfig>,
+    #[serde(default)]
+    pub webhooks: Option<WebhookConfig>,
+    #[serde(default)]
+    pub email: Option<EmailNotifierConfig>,
+    /// PEM Ed25519 private key; each run's report.json is signed into report.json.sig, which
+    /// `verify-report` checks.
+    #[serde(default)]
+    pub report_signing_key: Option<PathBuf>,
+    /// A file kept fresh while the daemon is healthy, for external watchdogs.
+    #[serde(default)]
+    pub liveness: Option<LivenessConfig>,
+    /// Alerts the notifiers while runs wait in the queue longer than an SLA.
+    #[serde(default)]
+    pub backlog_alert: Option<BacklogAlertConfig>,
+    /// Commands run before download, after download and after replay of each run.
+    #[serde(default)]
+    pub hooks: Option<HooksConfig>,
+    /// Rule descriptions shown in reports instead of the English ones, in the format of
+    /// replay_script's `locale/en.cfg`.
+    #[serde(default)]
+    pub rule_locale: Option<PathBuf>,
+}
+
+impl DaemonConfig {
+    pub fn report_signing_key(&self) -> Result<Option<Arc<SigningKey>>> {
+        self.report_signing_key
+            .as_deref()
+            .map(|path| signing::load_signing_key(path).map(Arc::new))
+            .transpose()
+    }
+
+    pub fn internet_archive(&self) -> Option<Arc<InternetArchive>> {
+        self.internet_archive
+            .clone()
+            .map(|config| Arc::new(InternetArchive::new(config)))
+    }
+
+    pub fn speedrun_client(&self) -> Result<SpeedrunClient> {
+        let client = SpeedrunClient::with_identity(
+            self.speedrun_api.user_agent.as_deref(),
+            self.speedrun_api.api_key.as_ref().map(Secret::expose),
+        )?;
+        Ok(client)
+    }
+
+    pub fn factorio_credentials(&self) -> Option<FactorioCredentials> {
+        self.factorio_account
+            .as_ref()
+            .map(|account| FactorioCredentials {
+                username: account.username.clone(),
+                token: account.token.expose().to_string(),
+            })
+            .or_else(FactorioCredentials::from_env)
+    }
+
+    pub fn rule_descriptions(&self) -> Result<Arc<RuleDescriptions>> {
+        let Some(path) = &self.rule_locale else {
+            return Ok(Arc::default());
+        };
+        let locale = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read rule locale {}", path.display()))?;
+        let descriptions = RuleDescriptions::with_locale(&locale)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        Ok(Arc::new(descriptions))
+    }
+
+    /// Reads the config at `path`, after loading its `secrets_file`. `${NAME}` in string
+    /// values is replaced with environment variable `NAME`, and `$$` with `$`; unset
+    /// variables are an error.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut value: Value = serde_yaml::from_reader(File::open(path)?)?;
+        let mut missing = BTreeSet::new();
+        if let Some(secrets_file) = value.get_mut("secrets_file") {
+            interpolate_env(secrets_file, &mut missing);
+            if let Some(secrets_file) = secrets_file.as_str() {
+                dotenvy::from_path(secrets_file)
+                    .with_context(|| format!("Failed to load secrets file {}", secrets_file))?;
+            }
+        }
+        interpolate_env(&mut value, &mut missing);
+        if !missing.is_empty() {
+            let missing: Vec<_> = missing.into_iter().collect();
+            bail!(
+                "Config references unset environment variables: {}",
+                missing.join(", ")
+            );
+        }
+        Ok(serde_yaml::from_value(value)?)
+    }
+
+    pub async fn open_database(&self) -> Result<Database> {
+        Database::open_url_or_path(self.database_url.as_deref(), &self.database_path).await
+    }
+
+    pub fn install_quota_bytes(&self) -> Option<u64> {
+        self.install_quota_gb.map(|gb| gb * 1024 * 1024 * 1024)
+    }
+
+    pub fn instance_id(&self) -> String {
+        self.instance_id
+            .clone()
+            .or_else(hostname)
+            .unwrap_or_else(|| "daemon".to_string())
+    }
+}
+
+/// This machine's name, from HOSTNAME or /etc/hostname.
+pub fn hostname() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+fn interpolate_env(value: &mut Value, missing: &mut BTreeSet<String>) {
+    match value {
+        Value::String(s) => *s = interpolate_str(s, &|name| std::env::var(name).ok(), missing),
+        Value::Sequence(values) => {
+            for value in values {
+                interpolate_env(value, missing);
+            }
+        }
+        Value::Mapping(mapping) => {
+            for value in mapping.values_mut() {
+                interpolate_env(value, missing);
+            }
+        }
+        Value::Tagged(tagged) => interpolate_env(&mut tagged.value, missing),
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+fn interpolate_str(
+    s: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    missing: &mut BTreeSet<String>,
+) -> String {
+    let mut result = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(after) = after.strip_prefix('$') {
+            result.push('$');
+            rest = after;
+        } else if let Some(inner) = after.strip_prefix('{')
+            && let Some(end) = inner.find('}')
+        {
+            let name = &inner[..end];
+            match lookup(name) {
+                Some(value) => result.push_str(&value),
+                None => {
+                    missing.insert(name.to_string());
+                }
+            }
+            rest = &inner[end + 1..];
+        } else {
+            result.push('$');
+            rest = after;
+        }
+    }
+    result.push_str(rest);
+    result
 }
 
 fn default_game_rules_file() -> PathBuf {
@@ -66,28 +462,219 @@ fn default_poll_interval_seconds() -> u64 {
     3600
 }
 
+fn default_max_concurrent_runs() -> usize {
+    1
+}
+
+fn default_shutdown_drain_seconds() -> u64 {
+    60
+}
+
 fn default_database_path() -> PathBuf {
     PathBuf::from("run_verification.db")
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+/// Rules per speedrun.com game and category. Categories, and profiles, can `extends` one or
+/// more named `profiles`, which are merged in order and then overridden by the extending
+/// entry's own rules; this is resolved when the rules are loaded.
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(try_from = "RawSrcRunRules")]
 pub struct SrcRunRules {
     pub games: HashMap<String, GameConfig>,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct RawSrcRunRules {
+    #[serde(default)]
+    #[schemars(with = "BTreeMap<String, ExtendingRules>")]
+    profiles: BTreeMap<String, Mapping>,
+    games: HashMap<String, RawGameConfig>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(rename = "GameConfig")]
+struct RawGameConfig {
+    expected_mods: ExpectedMods,
+    #[schemars(with = "HashMap<String, ExtendingRules>")]
+    categories: HashMap<String, Value>,
+}
+
+// only describes categories and profiles as written, for the JSON Schema
+/// Rules of a category or profile, over the profiles it extends.
+#[derive(JsonSchema)]
+#[schemars(deny_unknown_fields)]
+#[allow(dead_code)]
+struct ExtendingRules {
+    /// Profiles whose rules these override, merged in order
+    extends: Option<Extends>,
+    #[serde(flatten)]
+    rules: RunRules,
+}
+
+#[derive(JsonSchema)]
+#[schemars(untagged)]
+#[allow(dead_code)]
+enum Extends {
+    Profile(String),
+    Profiles(Vec<String>),
+}
+
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct GameConfig {
     pub expected_mods: ExpectedMods,
     pub categories: HashMap<String, CategoryConfig>,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
 pub struct CategoryConfig {
     #[serde(flatten)]
     pub run_rules: RunRules,
 }
 
+const EXTENDS: &str = "extends";
+
+impl TryFrom<RawSrcRunRules> for SrcRunRules {
+    type Error = String;
+
+    fn try_from(raw: RawSrcRunRules) -> Result<Self, String> {
+        let mut profiles = Profiles {
+            raw: &raw.profiles,
+            resolved: BTreeMap::new(),
+        };
+        for name in raw.profiles.keys() {
+            let rules = profiles.resolve(name, &mut Vec::new())?;
+            serde_yaml::from_value::<CategoryConfig>(Value::Mapping(rules))
+                .map_err(|e| format!("profile {name}: {e}"))?;
+        }
+
+        let mut games = HashMap::new();
+        for (game_id, game) in raw.games {
+            let mut categories = HashMap::new();
+            for (category_id, value) in game.categories {
+                let at = format!("game {game_id} category {category_id}");
+                let value = match value {
+                    Value::Mapping(rules) => Value::Mapping(profiles.extend(&at, rules)?),
+                    value => value,
+                };
+                let category: CategoryConfig =
+                    serde_yaml::from_value(value).map_err(|e| format!("{at}: {e}"))?;
+                if let Some(name) = category
+                    .run_rules
+                    .plugins
+                    .iter()
+                    .find(|name| plugin::registry().get(name).is_none())
+                {
+                    return Err(format!("{at}: unknown verification plugin {name}"));
+                }
+                categories.insert(category_id, category);
+            }
+            let game = GameConfig {
+                expected_mods: game.expected_mods,
+                categories,
+            };
+            games.insert(game_id, game);
+        }
+        Ok(SrcRunRules { games })
+    }
+}
+
+struct Profiles<'a> {
+    raw: &'a BTreeMap<String, Mapping>,
+    resolved: BTreeMap<String, Mapping>,
+}
+
+impl Profiles<'_> {
+    fn resolve(&mut self, name: &str, chain: &mut Vec<String>) -> Result<Mapping, String> {
+        if let Some(rules) = self.resolved.get(name) {
+            return Ok(rules.clone());
+        }
+        if chain.iter().any(|extended| extended == name) {
+            chain.push(name.to_string());
+            return Err(format!(
+                "profiles extend each other: {}",
+                chain.join(" -> ")
+            ));
+        }
+        let rules = self
+            .raw
+            .get(name)
+            .ok_or_else(|| format!("unknown profile {name}"))?
+            .clone();
+        chain.push(name.to_string());
+        let rules = self.extend_in(&format!("profile {name}"), rules, chain)?;
+        chain.pop();
+        self.resolved.insert(name.to_string(), rules.clone());
+        Ok(rules)
+    }
+
+    /// `rules` merged over the profiles it extends.
+    fn extend(&mut self, at: &str, rules: Mapping) -> Result<Mapping, String> {
+        self.extend_in(at, rules, &mut Vec::new())
+    }
+
+    fn extend_in(
+        &mut self,
+        at: &str,
+        mut rules: Mapping,
+        chain: &mut Vec<String>,
+    ) -> Result<Mapping, String> {
+        let extends = match rules.remove(EXTENDS) {
+            None => return Ok(rules),
+            Some(Value::String(name)) => vec![name],
+            Some(value) => serde_yaml::from_value::<Vec<String>>(value)
+                .map_err(|_| format!("{at}: {EXTENDS} must be a profile name or a list of them"))?,
+        };
+        let mut base = Mapping::new();
+        let mut base_names: Vec<&str> = Vec::new();
+        for name in &extends {
+            let profile = self
+                .resolve(name, chain)
+                .map_err(|e| format!("{at}: {e}"))?;
+            if let Some(key) = conflicting_key(&base, &profile) {
+                return Err(format!(
+                    "{at}: profiles {} and {name} set {key} differently; set it in {at} instead",
+                    base_names.join(", ")
+                ));
+            }
+            merge(&mut base, profile);
+            base_names.push(name);
+        }
+        merge(&mut base, rules);
+        Ok(base)
+    }
+}
+
+/// Merges `overlay` into `base`, with nested mappings merged key by key.
+fn merge(base: &mut Mapping, overlay: Mapping) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Mapping(base)), Value::Mapping(value)) => merge(base, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// The first key, as a dotted path, that `a` and `b` both set to different values.
+fn conflicting_key(a: &Mapping, b: &Mapping) -> Option<String> {
+    b.iter().find_map(|(key, b_value)| {
+        let a_value = a.get(key)?;
+        let name = key
+            .as_str()
+            .map_or_else(|| format!("{key:?}"), str::to_string);
+        match (a_value, b_value) {
+            (Value::Mapping(a), Value::Mapping(b)) => {
+                conflicting_key(a, b).map(|nested| format!("{name}.{nested}"))
+            }
+            (a_value, b_value) => (a_value != b_value).then_some(name),
+        }
+    })
+}
+
 impl SrcRunRules {
     pub fn resolve_rules(
         &self,
@@ -112,3 +699,167 @@ impl SrcRunRules {
         Ok((run_rules, expected_mods))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate_str() {
+        let lookup = |name: &str| (name == "TOKEN").then(|| "abc".to_string());
+        let mut missing = BTreeSet::new();
+        assert_eq!(
+            interpolate_str("Bearer ${TOKEN}", &lookup, &mut missing),
+            "Bearer abc"
+        );
+        assert_eq!(
+            interpolate_str("$${TOKEN} costs $5", &lookup, &mut missing),
+            "${TOKEN} costs $5"
+        );
+        assert!(missing.is_empty());
+        assert_eq!(interpolate_str("${UNSET}", &lookup, &mut missing), "");
+        assert_eq!(missing, BTreeSet::from(["UNSET".to_string()]));
+    }
+
+    #[test]
+    fn test_load_with_secrets_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let secrets_path = dir.path().join("secrets.env");
+        std::fs::write(&secrets_path, "CONFIG_TEST_BOT_TOKEN=s3cret\n").unwrap();
+        let config_path = dir.path().join("daemon.yaml");
+        std::fs::write(
+            &config_path,
+            format!(
+                "secrets_file: {}\nbot_notifier:\n  bot_url: http://bot\n  auth_token: ${{CONFIG_TEST_BOT_TOKEN}}\n",
+                secrets_path.display()
+            ),
+        )
+        .unwrap();
+
+        let config = DaemonConfig::load(&config_path).unwrap();
+        let auth_token = config.bot_notifier.unwrap().auth_token.unwrap();
+        assert_eq!(auth_token.expose(), "s3cret");
+        assert_eq!(format!("{:?}", auth_token), "Secret(***)");
+
+        std::fs::write(&config_path, "instance_id: ${CONFIG_TEST_UNSET}\n").unwrap();
+        let err = DaemonConfig::load(&config_path).unwrap_err();
+        assert!(err.to_string().contains("CONFIG_TEST_UNSET"));
+    }
+
+    #[test]
+    fn test_rule_profiles() {
+        let src_rules: SrcRunRules = serde_yaml::from_str(
+            "
+profiles:
+  scenario:
+    win_on_scenario_finished: true
+    resource_limits:
+      max_memory_mb: 4096
+  multiplayer:
+    extends: scenario
+    max_players: 8
+games:
+  game1:
+    expected_mods: [base]
+    categories:
+      any:
+        extends: scenario
+        security:
+          max_file_size_mb: 2048
+      any_mp:
+        extends: multiplayer
+        resource_limits:
+          nice: 5
+      coop:
+        extends: [multiplayer]
+        max_players: null
+",
+        )
+        .unwrap();
+        let rules = |category| src_rules.resolve_rules("game1", category).unwrap().0;
+        assert!(rules("any").replay_scripts.win_on_scenario_finished);
+        assert_eq!(rules("any").replay_scripts.max_players, Some(1));
+        assert_eq!(rules("any").security.max_file_size_mb, Some(2048));
+        let any_mp = rules("any_mp");
+        assert!(any_mp.replay_scripts.win_on_scenario_finished);
+        assert_eq!(any_mp.replay_scripts.max_players, Some(8));
+        assert_eq!(any_mp.resource_limits.max_memory_mb, Some(4096));
+        assert_eq!(any_mp.resource_limits.nice, Some(5));
+        assert_eq!(any_mp.security.max_file_size_mb, None);
+        assert_eq!(rules("coop").replay_scripts.max_players, None);
+    }
+
+    #[test]
+    fn test_rule_profile_errors() {
+        let error = |yaml: &str| {
+            serde_yaml::from_str::<SrcRunRules>(yaml)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        let unknown = error(
+            "
+games:
+  game1:
+    expected_mods: [base]
+    categories:
+      any:
+        extends: strict
+",
+        );
+        assert!(unknown.contains("game game1 category any: unknown profile strict"));
+
+        let cycle = error(
+            "
+profiles:
+  a:
+    extends: b
+  b:
+    extends: a
+games: {}
+",
+        );
+        assert!(cycle.contains("profiles extend each other: a -> b -> a"));
+
+        let conflict = error(
+            "
+profiles:
+  solo:
+    max_players: 1
+  duo:
+    max_players: 2
+games:
+  game1:
+    expected_mods: [base]
+    categories:
+      any:
+        extends: [solo, duo]
+",
+        );
+        assert!(conflict.contains("profiles solo and duo set max_players differently"));
+
+        let invalid = error(
+            "
+profiles:
+  strict:
+    max_playerz: 1
+games: {}
+",
+        );
+        assert!(invalid.contains("profile strict: unknown field `max_playerz`"));
+
+        let plugin = error(
+            "
+games:
+  game1:
+    expected_mods: [base]
+    categories:
+      any:
+        plugins: [belt_weaving]
+",
+        );
+        assert!(
+            plugin.contains("game game1 category any: unknown verification plugin belt_weaving")
+        );
+    }
+}
@@ -1,44 +1,219 @@
-use anyhow::Result;
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use anyhow::{Result, bail};
+use log::info;
+use sqlx::any::AnyPoolOptions;
+use sqlx::migrate::Migrator;
+use sqlx::{Any, AnyPool, Transaction};
 use std::path::Path;
-use std::str::FromStr;
+
+/// Applied in order on open; add new numbered files to `migrations/` to evolve the schema,
+/// and the same change in Postgres' dialect to `migrations_postgres/`.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+/// Starts at the version of `MIGRATOR` Postgres support was added in.
+static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("./migrations_postgres");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    Sqlite,
+    Postgres,
+}
 
 #[derive(Clone)]
 pub struct Database {
-    pool: SqlitePool,
+    pool: AnyPool,
+    backend: Backend,
+    actor: &'static str,
+    /// Removed once the last handle is dropped.
+    #[cfg(test)]
+    test_schema: Option<std::sync::Arc<TestSchema>>,
 }
 
 impl Database {
     pub async fn new(path: impl AsRef<Path>) -> Result<Self> {
-        let path = path.as_ref();
-        let connection_string = format!("sqlite:{}", path.display());
-
-        let options = SqliteConnectOptions::from_str(&connection_string)?.create_if_missing(true);
-
-        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+        Self::connect(&format!("sqlite:{}", path.as_ref().display())).await
+    }
 
-        sqlx::migrate!("./migrations").run(&pool).await?;
+    /// Opens the database at `url` if given, otherwise the SQLite file at `path`.
+    pub async fn open_url_or_path(url: Option<&str>, path: &Path) -> Result<Self> {
+        match url {
+            Some(url) => Self::connect(url).await,
+            None => Self::new(path).await,
+        }
+    }
 
-        Ok(Self { pool })
+    /// Opens the database at a `sqlite:` or `postgres:` connection URL. SQLite databases are
+    /// created if missing.
+    pub async fn connect(url: &str) -> Result<Self> {
+        sqlx::any::install_default_drivers();
+        let db = if url.starts_with("sqlite:") {
+            // several daemons may share the database; wait for their writes instead of failing
+            let pool = AnyPoolOptions::new()
+                .after_connect(|conn, _| {
+                    Box::pin(async move {
+                        sqlx::query("PRAGMA busy_timeout = 30000")
+                            .execute(conn)
+                            .await?;
+                        Ok(())
+                    })
+                })
+                .connect(&create_if_missing(url))
+                .await?;
+            Self::open(pool, Backend::Sqlite).await?
+        } else if url.starts_with("postgres:") || url.starts_with("postgresql:") {
+            let pool = AnyPoolOptions::new().connect(url).await?;
+            Self::open(pool, Backend::Postgres).await?
+        } else {
+            bail!("Unsupported database URL: {}", url);
+        };
+        info!("Database schema at version {}", db.schema_version().await?);
+        Ok(db)
     }
 
+    /// A new empty database: in memory, or in a new schema of the Postgres database at
+    /// `TEST_POSTGRES_URL` if it is set, to run the tests against Postgres.
     #[cfg(test)]
     pub async fn in_memory() -> Result<Self> {
-        let pool = SqlitePoolOptions::new().connect("sqlite::memory:").await?;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static SEQ: AtomicUsize = AtomicUsize::new(0);
+
+        sqlx::any::install_default_drivers();
+        if let Ok(url) = std::env::var("TEST_POSTGRES_URL") {
+            let schema = format!(
+                "test_{}_{}",
+                std::process::id(),
+                SEQ.fetch_add(1, Ordering::Relaxed)
+            );
+            let pool = AnyPoolOptions::new().connect(&url).await?;
+            sqlx::query(&format!("CREATE SCHEMA {}", schema))
+                .execute(&pool)
+                .await?;
+            pool.close().await;
+            let search_path = format!("SET search_path TO {}", schema);
+            let pool = AnyPoolOptions::new()
+                .after_connect(move |conn, _| {
+                    let search_path = search_path.clone();
+                    Box::pin(async move {
+                        sqlx::query(&search_path).execute(conn).await?;
+                        Ok(())
+                    })
+                })
+                .connect(&url)
+                .await?;
+            let test_schema = TestSchema { url, name: schema };
+            let mut db = Self::open(pool, Backend::Postgres).await?;
+            db.test_schema = Some(std::sync::Arc::new(test_schema));
+            return Ok(db);
+        }
+
+        // named, so that the pool's connections share it
+        let url = format!(
+            "sqlite:file:test-db-{}?mode=memory",
+            SEQ.fetch_add(1, Ordering::Relaxed)
+        );
+        let pool = AnyPoolOptions::new().connect(&url).await?;
+        Self::open(pool, Backend::Sqlite).await
+    }
 
-        sqlx::migrate!("./migrations").run(&pool).await?;
+    async fn open(pool: AnyPool, backend: Backend) -> Result<Self> {
+        let migrator = match backend {
+            Backend::Sqlite => &MIGRATOR,
+            Backend::Postgres => &POSTGRES_MIGRATOR,
+        };
+        migrator.run(&pool).await?;
+        Ok(Self {
+            pool,
+            backend,
+            actor: "daemon",
+            #[cfg(test)]
+            test_schema: None,
+        })
+    }
 
-        Ok(Self { pool })
+    /// Names who is making changes through this handle in the run history.
+    pub fn with_actor(mut self, actor: &'static str) -> Self {
+        self.actor = actor;
+        self
     }
 
-    pub fn pool(&self) -> &SqlitePool {
+    pub fn pool(&self) -> &AnyPool {
         &self.pool
     }
+
+    pub fn actor(&self) -> &'static str {
+        self.actor
+    }
+
+    /// Starts a transaction that takes the write lock on runs up front, so no other daemon
+    /// changes them between its reads and writes.
+    pub async fn begin_write(&self) -> Result<Transaction<'static, Any>> {
+        match self.backend {
+            Backend::Sqlite => Ok(self.pool.begin_with("BEGIN IMMEDIATE").await?),
+            Backend::Postgres => {
+                let mut tx = self.pool.begin().await?;
+                sqlx::query("LOCK TABLE runs IN SHARE ROW EXCLUSIVE MODE")
+                    .execute(&mut *tx)
+                    .await?;
+                Ok(tx)
+            }
+        }
+    }
+
+    /// Version of the last applied migration.
+    pub async fn schema_version(&self) -> Result<i64> {
+        let version: Option<i64> =
+            sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(version.unwrap_or(0))
+    }
+}
+
+/// A Postgres schema made for one test's database, dropped with it.
+#[cfg(test)]
+struct TestSchema {
+    url: String,
+    name: String,
+}
+
+#[cfg(test)]
+impl Drop for TestSchema {
+    fn drop(&mut self) {
+        use sqlx::Connection;
+
+        let url = self.url.clone();
+        let drop_schema = format!("DROP SCHEMA IF EXISTS {} CASCADE", self.name);
+        // the test's runtime may be shutting down, so this runs on one of its own
+        let result = std::thread::spawn(move || -> Result<()> {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?
+                .block_on(async {
+                    let mut conn = sqlx::AnyConnection::connect(&url).await?;
+                    sqlx::query(&drop_schema).execute(&mut conn).await?;
+                    conn.close().await?;
+                    Ok(())
+                })
+        })
+        .join();
+        if let Ok(Err(e)) = result {
+            log::warn!("Failed to drop test schema {}: {e}", self.name);
+        }
+    }
+}
+
+/// Adds `mode=rwc` to a SQLite URL that doesn't set a mode.
+fn create_if_missing(url: &str) -> String {
+    if url.contains("mode=") {
+        return url.to_string();
+    }
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{}{}mode=rwc", url, separator)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+    use std::str::FromStr;
 
     #[tokio::test]
     async fn test_in_memory_database_creation() {
@@ -46,16 +221,120 @@ mod tests {
         assert!(!db.pool().is_closed());
     }
 
+    #[tokio::test]
+    async fn test_postgres_test_schema_dropped() {
+        let Ok(url) = std::env::var("TEST_POSTGRES_URL") else {
+            return;
+        };
+        let db = Database::in_memory().await.unwrap();
+        let name = db.test_schema.as_ref().unwrap().name.clone();
+        drop(db);
+
+        let pool = AnyPoolOptions::new().connect(&url).await.unwrap();
+        let schemas: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM information_schema.schemata WHERE schema_name = $1",
+        )
+        .bind(&name)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(schemas, 0);
+    }
+
     #[tokio::test]
     async fn test_migrations_create_tables() {
         let db = Database::in_memory().await.unwrap();
 
-        let result: (i32,) =
-            sqlx::query_as("SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='runs'")
-                .fetch_one(db.pool())
-                .await
-                .unwrap();
+        let runs: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM runs")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(runs, 0);
+        let latest = MIGRATOR.iter().map(|m| m.version).max().unwrap();
+        assert_eq!(db.schema_version().await.unwrap(), latest);
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_keeps_existing_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.db");
+
+        // a database created before the later migrations existed
+        let options = SqliteConnectOptions::from_str(&format!("sqlite:{}", path.display()))
+            .unwrap()
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .unwrap();
+        let mut old_migrator = sqlx::migrate!("./migrations");
+        old_migrator.migrations = old_migrator.migrations[..3].to_vec().into();
+        old_migrator.run(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO runs (run_id, game_id, category_id, submitted_date, status, \
+             retry_count, created_at, updated_at) \
+             VALUES ('run1', 'game1', 'cat1', '2024-01-01T00:00:00Z', 'passed', 0, \
+             '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool.close().await;
+
+        let db = Database::new(&path).await.unwrap();
+        let latest = MIGRATOR.iter().map(|m| m.version).max().unwrap();
+        assert_eq!(db.schema_version().await.unwrap(), latest);
+        let run = db.get_run("run1").await.unwrap().unwrap();
+        assert_eq!(run.game_id, "game1");
+    }
+
+    #[test]
+    fn test_postgres_migrations_match() {
+        let versions = |migrator: &Migrator| migrator.iter().map(|m| m.version).collect::<Vec<_>>();
+        let sqlite = versions(&MIGRATOR);
+        let postgres = versions(&POSTGRES_MIGRATOR);
+        assert_eq!(sqlite[sqlite.len() - postgres.len()..], postgres);
+    }
+
+    #[test]
+    fn test_create_if_missing() {
+        assert_eq!(
+            create_if_missing("sqlite:runs.db"),
+            "sqlite:runs.db?mode=rwc"
+        );
+        assert_eq!(
+            create_if_missing("sqlite:runs.db?cache=shared"),
+            "sqlite:runs.db?cache=shared&mode=rwc"
+        );
+        assert_eq!(
+            create_if_missing("sqlite:runs.db?mode=ro"),
+            "sqlite:runs.db?mode=ro"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_rejects_unsupported_urls() {
+        let err = Database::connect("mysql://localhost/runs")
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Unsupported database URL"));
+    }
+
+    #[tokio::test]
+    async fn test_open_url_or_path_prefers_url() {
+        let dir = tempfile::tempdir().unwrap();
+        let url_db = dir.path().join("url.db");
+        let path_db = dir.path().join("path.db");
+        let url = format!("sqlite:{}", url_db.display());
+
+        Database::open_url_or_path(Some(&url), &path_db)
+            .await
+            .unwrap();
+        assert!(url_db.exists());
+        assert!(!path_db.exists());
 
-        assert_eq!(result.0, 1);
+        Database::open_url_or_path(None, &path_db).await.unwrap();
+        assert!(path_db.exists());
     }
 }
@@ -1,112 +1,295 @@
 use super::connection::Database;
-use super::types::{NewRun, Run, RunFilter, RunStatus};
+use super::types::{
+    CachedName, Checkpoint, DuplicateSave, JobFilter, JobStatus, NameKind, NewJob, NewRun,
+    QueuedNotification, QueuedRun, ReplayResult, ReplayThroughput, Review, ReviewDecision, Run,
+    RunDetails, RunEvent, RunFilter, RunOrder, RunProgress, RunSelection, RunStatus, VerifyJob,
+};
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
 use log::{error, info, warn};
-use replay_script::MsgLevel;
+use replay_script::ProgressSnapshot;
+use sqlx::AnyConnection;
 use sqlx::Row;
+use sqlx::any::{Any, AnyArguments, AnyRow};
+use sqlx::query::Query;
+use std::collections::HashMap;
 
+use crate::daemon::janitor::CleanupStats;
 use crate::daemon::retry::{RetryConfig, calculate_next_retry, error_class_to_string};
 use crate::error::RunProcessingError;
-use crate::run_replay::ReplayReport;
+use crate::run_replay::{ReplayReport, ReportSummary, log_tail, report_json_path};
+
+const LOG_EXCERPT_LINES: usize = 50;
 
 impl Database {
     pub async fn insert_run(&self, new_run: NewRun) -> Result<()> {
-        let now = Utc::now();
+        let now = timestamp(Utc::now());
         let status = RunStatus::Discovered;
-        let retry_count: u32 = 0;
-        let bot_notified = false;
 
-        sqlx::query!(
+        sqlx::query(
             r#"
             INSERT INTO runs (
                 run_id, game_id, category_id, submitted_date,
                 status, error_message, retry_count, next_retry_at, error_class,
                 created_at, updated_at, bot_notified
-            ) VALUES (?, ?, ?, ?, ?, NULL, ?, NULL, NULL, ?, ?, ?)
+            ) VALUES ($1, $2, $3, $4, $5, NULL, 0, NULL, NULL, $6, $6, false)
             "#,
-            new_run.run_id,
-            new_run.game_id,
-            new_run.category_id,
-            new_run.submitted_date,
-            status,
-            retry_count,
-            now,
-            now,
-            bot_notified
         )
+        .bind(&new_run.run_id)
+        .bind(&new_run.game_id)
+        .bind(&new_run.category_id)
+        .bind(timestamp(new_run.submitted_date))
+        .bind(status)
+        .bind(now)
         .execute(self.pool())
         .await?;
 
+        let mut conn = self.pool().acquire().await?;
+        self.record_event(&mut conn, &new_run.run_id, None, status, None)
+            .await?;
+
+        if let Some(details) = &new_run.details {
+            self.store_run_details(&new_run.run_id, details).await?;
+        }
+
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub async fn set_bot_notified(&self, run_id: &str, notified: bool) -> Result<()> {
-        let now = Utc::now();
-
-        sqlx::query!(
-            r#"
-            UPDATE runs
-            SET bot_notified = ?, updated_at = ?
-            WHERE run_id = ?
-            "#,
-            notified,
-            now,
-            run_id
+    pub async fn store_run_details(&self, run_id: &str, details: &RunDetails) -> Result<()> {
+        sqlx::query(
+            "UPDATE runs SET player_names = $1, run_time_secs = $2, platform = $3, variable_values = $4,
+                 leaderboard_place = $5, details_updated_at = $6
+             WHERE run_id = $7",
         )
+        .bind(&details.player_names)
+        .bind(details.run_time_secs)
+        .bind(&details.platform)
+        .bind(serde_json::to_string(&details.variable_values)?)
+        .bind(details.leaderboard_place.map(i64::from))
+        .bind(timestamp(Utc::now()))
+        .bind(run_id)
         .execute(self.pool())
         .await?;
+        Ok(())
+    }
+
+    /// Stored speedrun.com details; `None` if the run is unknown or they were never fetched.
+    pub async fn get_run_details(&self, run_id: &str) -> Result<Option<RunDetails>> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM runs WHERE run_id = $1 AND variable_values IS NOT NULL",
+            RUN_DETAILS_COLUMNS
+        ))
+        .bind(run_id)
+        .fetch_optional(self.pool())
+        .await?;
+        row.as_ref().map(run_details_from_row).transpose()
+    }
 
+    /// Stored speedrun.com details, if they were fetched at or after `since`.
+    pub async fn get_run_details_since(
+        &self,
+        run_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<RunDetails>> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM runs
+             WHERE run_id = $1 AND variable_values IS NOT NULL AND details_updated_at >= $2",
+            RUN_DETAILS_COLUMNS
+        ))
+        .bind(run_id)
+        .bind(timestamp(since))
+        .fetch_optional(self.pool())
+        .await?;
+        row.as_ref().map(run_details_from_row).transpose()
+    }
+
+    /// Stored speedrun.com details of each of `run_ids` that has them, in one query.
+    pub async fn get_runs_details(&self, run_ids: &[&str]) -> Result<HashMap<String, RunDetails>> {
+        if run_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let params: Vec<_> = (1..=run_ids.len()).map(|i| format!("${}", i)).collect();
+        let query_str = format!(
+            "SELECT run_id, {} FROM runs WHERE run_id IN ({}) AND variable_values IS NOT NULL",
+            RUN_DETAILS_COLUMNS,
+            params.join(", ")
+        );
+        let mut query = sqlx::query(&query_str);
+        for run_id in run_ids {
+            query = query.bind(*run_id);
+        }
+        query
+            .fetch_all(self.pool())
+            .await?
+            .iter()
+            .map(|row| Ok((row.try_get("run_id")?, run_details_from_row(row)?)))
+            .collect()
+    }
+
+    async fn record_event(
+        &self,
+        conn: &mut AnyConnection,
+        run_id: &str,
+        old_status: Option<RunStatus>,
+        new_status: RunStatus,
+        reason: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO run_events (run_id, old_status, new_status, reason, actor, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(run_id)
+        .bind(old_status)
+        .bind(new_status)
+        .bind(reason)
+        .bind(self.actor())
+        .bind(timestamp(Utc::now()))
+        .execute(&mut *conn)
+        .await?;
+        self.queue_notifications(conn, run_id).await
+    }
+
+    /// Queues the run's latest status for every registered notifier.
+    async fn queue_notifications(&self, conn: &mut AnyConnection, run_id: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO notification_queue (notifier, run_id, next_attempt_at)
+             SELECT name, $1, $2 FROM notifiers WHERE true
+             ON CONFLICT(notifier, run_id) DO UPDATE SET
+                 seq = notification_queue.seq + 1, attempts = 0, next_attempt_at = excluded.next_attempt_at,
+                 last_error = NULL",
+        )
+        .bind(run_id)
+        .bind(timestamp(Utc::now()))
+        .execute(conn)
+        .await?;
         Ok(())
     }
 
-    pub async fn get_unnotified_runs(&self) -> Result<Vec<Run>> {
-        let runs = sqlx::query_as!(
-            Run,
-            r#"
-            SELECT run_id, game_id, category_id,
-                   submitted_date as "submitted_date: chrono::DateTime<Utc>",
-                   status as "status: RunStatus",
-                   error_message,
-                   retry_count as "retry_count: u32",
-                   next_retry_at as "next_retry_at: chrono::DateTime<Utc>",
-                   error_class,
-                   created_at as "created_at: chrono::DateTime<Utc>",
-                   updated_at as "updated_at: chrono::DateTime<Utc>",
-                   bot_notified as "bot_notified: bool"
-            FROM runs
-            WHERE bot_notified = false
-            "#
+    /// Adds notifiers status changes are queued for. Others stay registered with their
+    /// queues, since other daemons sharing the database may deliver them.
+    pub async fn register_notifiers(&self, names: &[&str]) -> Result<()> {
+        let mut tx = self.pool().begin().await?;
+        for name in names {
+            sqlx::query("INSERT INTO notifiers (name) VALUES ($1) ON CONFLICT DO NOTHING")
+                .bind(name)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Queued notifications of `notifier` due by `now`, oldest first.
+    pub async fn due_notifications(
+        &self,
+        notifier: &str,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<QueuedNotification>> {
+        let rows = sqlx::query(
+            "SELECT run_id, seq, attempts FROM notification_queue
+             WHERE notifier = $1 AND next_attempt_at <= $2
+             ORDER BY next_attempt_at LIMIT $3",
         )
+        .bind(notifier)
+        .bind(timestamp(now))
+        .bind(i64::from(limit))
         .fetch_all(self.pool())
         .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(QueuedNotification {
+                    run_id: row.try_get("run_id")?,
+                    seq: row.try_get("seq")?,
+                    attempts: row.try_get::<i64, _>("attempts")? as u32,
+                })
+            })
+            .collect()
+    }
 
-        Ok(runs)
+    /// Dequeues a delivered notification, unless the run changed again since it was read.
+    pub async fn complete_notification(
+        &self,
+        notifier: &str,
+        queued: &QueuedNotification,
+    ) -> Result<()> {
+        sqlx::query(
+            "DELETE FROM notification_queue WHERE notifier = $1 AND run_id = $2 AND seq = $3",
+        )
+        .bind(notifier)
+        .bind(&queued.run_id)
+        .bind(queued.seq)
+        .execute(self.pool())
+        .await?;
+        Ok(())
     }
 
-    pub async fn get_non_final_runs(&self) -> Result<Vec<Run>> {
-        let runs = sqlx::query_as!(
-            Run,
-            r#"
-            SELECT run_id, game_id, category_id,
-                   submitted_date as "submitted_date: chrono::DateTime<Utc>",
-                   status as "status: RunStatus",
-                   error_message,
-                   retry_count as "retry_count: u32",
-                   next_retry_at as "next_retry_at: chrono::DateTime<Utc>",
-                   error_class,
-                   created_at as "created_at: chrono::DateTime<Utc>",
-                   updated_at as "updated_at: chrono::DateTime<Utc>",
-                   bot_notified as "bot_notified: bool"
-            FROM runs
-            WHERE status IN ('discovered', 'processing')
-            "#
+    pub async fn retry_notification(
+        &self,
+        notifier: &str,
+        queued: &QueuedNotification,
+        next_attempt_at: DateTime<Utc>,
+        error: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE notification_queue
+             SET attempts = attempts + 1, next_attempt_at = $1, last_error = $2
+             WHERE notifier = $3 AND run_id = $4 AND seq = $5",
         )
+        .bind(timestamp(next_attempt_at))
+        .bind(error)
+        .bind(notifier)
+        .bind(&queued.run_id)
+        .bind(queued.seq)
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Status changes of a run, oldest first.
+    pub async fn get_run_events(&self, run_id: &str) -> Result<Vec<RunEvent>> {
+        let rows = sqlx::query(
+            "SELECT id, run_id, old_status, new_status, reason, actor, created_at
+             FROM run_events WHERE run_id = $1 ORDER BY id",
+        )
+        .bind(run_id)
         .fetch_all(self.pool())
         .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(RunEvent {
+                    id: row.try_get("id")?,
+                    run_id: row.try_get("run_id")?,
+                    old_status: row.try_get("old_status")?,
+                    new_status: row.try_get("new_status")?,
+                    reason: row.try_get("reason")?,
+                    actor: row.try_get("actor")?,
+                    created_at: get_timestamp(row, "created_at")?,
+                })
+            })
+            .collect()
+    }
 
-        Ok(runs)
+    #[allow(dead_code)]
+    pub async fn set_bot_notified(&self, run_id: &str, notified: bool) -> Result<()> {
+        sqlx::query("UPDATE runs SET bot_notified = $1, updated_at = $2 WHERE run_id = $3")
+            .bind(notified)
+            .bind(timestamp(Utc::now()))
+            .bind(run_id)
+            .execute(self.pool())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_non_final_runs(&self) -> Result<Vec<Run>> {
+        let query_str = format!(
+            "SELECT {} FROM runs WHERE status IN ('discovered', 'processing')",
+            RUN_COLUMNS
+        );
+        let rows = sqlx::query(&query_str).fetch_all(self.pool()).await?;
+
+        rows.iter().map(run_from_row).collect()
     }
 
     pub async fn set_bot_notified_if_status(
@@ -115,19 +298,13 @@ impl Database {
         notified: bool,
         expected_status: &RunStatus,
     ) -> Result<bool> {
-        let now = Utc::now();
-
-        let result = sqlx::query!(
-            r#"
-            UPDATE runs
-            SET bot_notified = ?, updated_at = ?
-            WHERE run_id = ? AND status = ?
-            "#,
-            notified,
-            now,
-            run_id,
-            expected_status
+        let result = sqlx::query(
+            "UPDATE runs SET bot_notified = $1, updated_at = $2 WHERE run_id = $3 AND status = $4",
         )
+        .bind(notified)
+        .bind(timestamp(Utc::now()))
+        .bind(run_id)
+        .bind(*expected_status)
         .execute(self.pool())
         .await?;
 
@@ -140,63 +317,193 @@ impl Database {
         status: RunStatus,
         error_message: Option<&str>,
     ) -> Result<()> {
-        let now = Utc::now();
+        self.set_run_status(run_id, None, status, error_message)
+            .await
+            .map(|_| ())
+    }
+
+    /// Like [`Self::update_run_status`], but only while `worker_id` holds the run's claim.
+    /// Returns false if another worker claimed it since.
+    pub async fn update_claimed_run_status(
+        &self,
+        run_id: &str,
+        worker_id: &str,
+        status: RunStatus,
+        error_message: Option<&str>,
+    ) -> Result<bool> {
+        self.set_run_status(run_id, Some(worker_id), status, error_message)
+            .await
+    }
 
-        sqlx::query!(
+    async fn set_run_status(
+        &self,
+        run_id: &str,
+        claimed_by: Option<&str>,
+        status: RunStatus,
+        error_message: Option<&str>,
+    ) -> Result<bool> {
+        let mut tx = self.begin_write().await?;
+
+        let old: Option<(RunStatus, Option<String>)> =
+            sqlx::query_as("SELECT status, claimed_by FROM runs WHERE run_id = $1")
+                .bind(run_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+        if let Some(worker_id) = claimed_by
+            && old.as_ref().and_then(|(_, owner)| owner.as_deref()) != Some(worker_id)
+        {
+            return Ok(false);
+        }
+        let old_status = old.map(|(status, _)| status);
+        sqlx::query(
             r#"
             UPDATE runs
-            SET status = ?, error_message = ?, bot_notified = false, updated_at = ?
-            WHERE run_id = ?
+            SET status = $1, error_message = $2, bot_notified = false, updated_at = $3
+            WHERE run_id = $4
             "#,
-            status,
-            error_message,
-            now,
-            run_id
         )
-        .execute(self.pool())
+        .bind(status)
+        .bind(error_message)
+        .bind(timestamp(Utc::now()))
+        .bind(run_id)
+        .execute(&mut *tx)
         .await?;
+        // a run reset for verifying again no longer has its review
+        if status == RunStatus::Discovered {
+            sqlx::query("DELETE FROM run_reviews WHERE run_id = $1")
+                .bind(run_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        if old_status.is_some() {
+            self.record_event(&mut tx, run_id, old_status, status, error_message)
+                .await?;
+        }
 
-        Ok(())
+        tx.commit().await?;
+        Ok(true)
     }
 
+    /// Verifying the run again supersedes its review.
     pub async fn mark_run_processing(&self, run_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM run_reviews WHERE run_id = $1")
+            .bind(run_id)
+            .execute(self.pool())
+            .await?;
         self.update_run_status(run_id, RunStatus::Processing, None)
             .await
     }
 
+    /// Records a reviewer's decision on a run that needs review, which then passes or fails
+    /// and is notified again. Returns false if the run doesn't need review.
+    pub async fn record_review(
+        &self,
+        run_id: &str,
+        decision: ReviewDecision,
+        reviewer: &str,
+        note: Option<&str>,
+    ) -> Result<bool> {
+        let now = timestamp(Utc::now());
+        let mut tx = self.begin_write().await?;
+
+        let status = decision.status();
+        let updated = sqlx::query(
+            "UPDATE runs SET status = $1, bot_notified = false, updated_at = $2
+             WHERE run_id = $3 AND status = $4",
+        )
+        .bind(status)
+        .bind(&now)
+        .bind(run_id)
+        .bind(RunStatus::NeedsReview)
+        .execute(&mut *tx)
+        .await?;
+        if updated.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query(
+            "INSERT INTO run_reviews (run_id, decision, reviewer, note, reviewed_at)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT(run_id) DO UPDATE SET
+                 decision = excluded.decision, reviewer = excluded.reviewer,
+                 note = excluded.note, reviewed_at = excluded.reviewed_at",
+        )
+        .bind(run_id)
+        .bind(decision)
+        .bind(reviewer)
+        .bind(note)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        let mut reason = format!("{} by {}", decision.as_str(), reviewer);
+        if let Some(note) = note {
+            reason = format!("{}: {}", reason, note);
+        }
+        self.record_event(
+            &mut tx,
+            run_id,
+            Some(RunStatus::NeedsReview),
+            status,
+            Some(&reason),
+        )
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn get_review(&self, run_id: &str) -> Result<Option<Review>> {
+        let row = sqlx::query(
+            "SELECT decision, reviewer, note, reviewed_at FROM run_reviews WHERE run_id = $1",
+        )
+        .bind(run_id)
+        .fetch_optional(self.pool())
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        Ok(Some(Review {
+            decision: row.try_get("decision")?,
+            reviewer: row.try_get("reviewer")?,
+            note: row.try_get("note")?,
+            reviewed_at: get_timestamp(&row, "reviewed_at")?,
+        }))
+    }
+
+    #[cfg(test)]
     pub async fn mark_run_passed(&self, run_id: &str) -> Result<()> {
         self.update_run_status(run_id, RunStatus::Passed, None)
             .await
     }
 
+    #[cfg(test)]
     pub async fn mark_run_needs_review(&self, run_id: &str, message: Option<&str>) -> Result<()> {
         self.update_run_status(run_id, RunStatus::NeedsReview, message)
             .await
     }
 
+    #[cfg(test)]
     pub async fn mark_run_failed(&self, run_id: &str, message: Option<&str>) -> Result<()> {
         self.update_run_status(run_id, RunStatus::Failed, message)
             .await
     }
 
+    #[cfg(test)]
     pub async fn mark_run_error(&self, run_id: &str, error_message: &str) -> Result<()> {
         self.update_run_status(run_id, RunStatus::Error, Some(error_message))
             .await
     }
 
     pub async fn mark_run_permanently_failed(&self, run_id: &str, error_class: &str) -> Result<()> {
-        let now = Utc::now();
-
-        sqlx::query!(
+        sqlx::query(
             r#"
             UPDATE runs
-            SET next_retry_at = NULL, error_class = ?, updated_at = ?
-            WHERE run_id = ?
+            SET next_retry_at = NULL, error_class = $1, updated_at = $2
+            WHERE run_id = $3
             "#,
-            error_class,
-            now,
-            run_id
         )
+        .bind(error_class)
+        .bind(timestamp(Utc::now()))
+        .bind(run_id)
         .execute(self.pool())
         .await?;
 
@@ -210,20 +517,18 @@ impl Database {
         error_class: &str,
         next_retry_at: DateTime<Utc>,
     ) -> Result<()> {
-        let now = Utc::now();
-
-        sqlx::query!(
+        sqlx::query(
             r#"
             UPDATE runs
-            SET retry_count = ?, error_class = ?, next_retry_at = ?, updated_at = ?
-            WHERE run_id = ?
+            SET retry_count = $1, error_class = $2, next_retry_at = $3, updated_at = $4
+            WHERE run_id = $5
             "#,
-            retry_count,
-            error_class,
-            next_retry_at,
-            now,
-            run_id
         )
+        .bind(i64::from(retry_count))
+        .bind(error_class)
+        .bind(timestamp(next_retry_at))
+        .bind(timestamp(Utc::now()))
+        .bind(run_id)
         .execute(self.pool())
         .await?;
 
@@ -231,108 +536,506 @@ impl Database {
     }
 
     pub async fn clear_retry_fields(&self, run_id: &str) -> Result<()> {
-        let now = Utc::now();
-        let retry_count: u32 = 0;
-
-        sqlx::query!(
+        sqlx::query(
             r#"
             UPDATE runs
-            SET retry_count = ?, next_retry_at = NULL, error_class = NULL, updated_at = ?
-            WHERE run_id = ?
+            SET retry_count = 0, next_retry_at = NULL, error_class = NULL, updated_at = $1
+            WHERE run_id = $2
             "#,
-            retry_count,
-            now,
-            run_id
         )
+        .bind(timestamp(Utc::now()))
+        .bind(run_id)
         .execute(self.pool())
         .await?;
 
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub async fn get_run(&self, run_id: &str) -> Result<Option<Run>> {
-        let run = sqlx::query_as!(
-            Run,
-            r#"
-            SELECT run_id, game_id, category_id,
-                   submitted_date as "submitted_date: chrono::DateTime<Utc>",
-                   status as "status: RunStatus",
-                   error_message,
-                   retry_count as "retry_count: u32",
-                   next_retry_at as "next_retry_at: chrono::DateTime<Utc>",
-                   error_class,
-                   created_at as "created_at: chrono::DateTime<Utc>",
-                   updated_at as "updated_at: chrono::DateTime<Utc>",
-                   bot_notified as "bot_notified: bool"
-            FROM runs
-            WHERE run_id = ?
-            "#,
-            run_id
+    /// Stores the progress timeline and report summary of a finished replay.
+    pub async fn store_report_details(&self, run_id: &str, report: &ReplayReport) -> Result<()> {
+        let timeline = serde_json::to_string(&report.timeline)?;
+        let summary = serde_json::to_string(&report.summary())?;
+        sqlx::query(
+            "UPDATE runs SET progress_timeline = $1, report_summary = $2 WHERE run_id = $3",
+        )
+        .bind(timeline)
+        .bind(summary)
+        .bind(run_id)
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Records the outcome of a finished replay, replacing any earlier result for the run.
+    pub async fn store_replay_result(&self, run_id: &str, report: &ReplayReport) -> Result<()> {
+        let log_path = report.log_path.as_deref();
+        let report_path = log_path.map(report_json_path);
+        let log_excerpt = log_path.and_then(|path| log_tail(path, LOG_EXCERPT_LINES));
+        sqlx::query(
+            "INSERT INTO replay_results (
+                run_id, max_msg_level, exit_tick, win_condition_completed, rule_counts,
+                log_path, report_path, log_excerpt, completed_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT(run_id) DO UPDATE SET
+                max_msg_level = excluded.max_msg_level, exit_tick = excluded.exit_tick,
+                win_condition_completed = excluded.win_condition_completed,
+                rule_counts = excluded.rule_counts, log_path = excluded.log_path,
+                report_path = excluded.report_path, log_excerpt = excluded.log_excerpt,
+                completed_at = excluded.completed_at",
+        )
+        .bind(run_id)
+        .bind(report.max_msg_level.to_string())
+        .bind(report.final_tick as i64)
+        .bind(!report.win_condition_not_completed)
+        .bind(serde_json::to_string(&report.rule_counts)?)
+        .bind(log_path.map(|path| path.display().to_string()))
+        .bind(report_path.map(|path| path.display().to_string()))
+        .bind(log_excerpt)
+        .bind(timestamp(Utc::now()))
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_replay_result(&self, run_id: &str) -> Result<Option<ReplayResult>> {
+        let row = sqlx::query(
+            "SELECT run_id, max_msg_level, exit_tick,
+                    CAST(win_condition_completed AS INTEGER) AS win_condition_completed,
+                    rule_counts, log_path, report_path, log_excerpt, completed_at
+             FROM replay_results WHERE run_id = $1",
+        )
+        .bind(run_id)
+        .fetch_optional(self.pool())
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let max_msg_level: String = row.try_get("max_msg_level")?;
+        let rule_counts: String = row.try_get("rule_counts")?;
+        Ok(Some(ReplayResult {
+            run_id: row.try_get("run_id")?,
+            max_msg_level: max_msg_level.parse()?,
+            exit_tick: row.try_get::<i64, _>("exit_tick")? as u64,
+            win_condition_completed: row.try_get::<i64, _>("win_condition_completed")? != 0,
+            rule_counts: serde_json::from_str(&rule_counts)?,
+            log_path: row.try_get("log_path")?,
+            report_path: row.try_get("report_path")?,
+            log_excerpt: row.try_get("log_excerpt")?,
+            completed_at: get_timestamp(&row, "completed_at")?,
+        }))
+    }
+
+    pub async fn get_report_summary(&self, run_id: &str) -> Result<Option<ReportSummary>> {
+        let summary: Option<String> =
+            sqlx::query_scalar("SELECT report_summary FROM runs WHERE run_id = $1")
+                .bind(run_id)
+                .fetch_optional(self.pool())
+                .await?
+                .flatten();
+        summary
+            .map(|summary| serde_json::from_str(&summary))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// Replaces the hashes of the run's saves, in segment order.
+    pub async fn store_save_hashes(&self, run_id: &str, hashes: &[String]) -> Result<()> {
+        let mut tx = self.pool().begin().await?;
+        sqlx::query("DELETE FROM run_saves WHERE run_id = $1")
+            .bind(run_id)
+            .execute(&mut *tx)
+            .await?;
+        let now = timestamp(Utc::now());
+        for (segment, sha256) in (1..).zip(hashes) {
+            sqlx::query(
+                "INSERT INTO run_saves (run_id, segment, sha256, recorded_at) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(run_id)
+            .bind(segment)
+            .bind(sha256)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Runs sharing a save with a different run, grouped by hash and oldest first. With
+    /// `run_id`, only the runs sharing a save with that run, including itself.
+    pub async fn find_duplicate_saves(&self, run_id: Option<&str>) -> Result<Vec<DuplicateSave>> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT s.sha256, r.run_id, r.game_id, r.category_id, r.player_names,
+                    r.submitted_date, r.status
+             FROM run_saves s JOIN runs r ON r.run_id = s.run_id
+             WHERE s.sha256 IN (
+                 SELECT sha256 FROM run_saves GROUP BY sha256 HAVING COUNT(DISTINCT run_id) > 1
+             )
+             AND ($1 IS NULL OR s.sha256 IN (SELECT sha256 FROM run_saves WHERE run_id = $1))
+             ORDER BY s.sha256, r.submitted_date, r.run_id",
+        )
+        .bind(run_id)
+        .fetch_all(self.pool())
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(DuplicateSave {
+                    sha256: row.try_get("sha256")?,
+                    run_id: row.try_get("run_id")?,
+                    game_id: row.try_get("game_id")?,
+                    category_id: row.try_get("category_id")?,
+                    player_names: row.try_get("player_names")?,
+                    submitted_date: get_timestamp(row, "submitted_date")?,
+                    status: row.try_get("status")?,
+                })
+            })
+            .collect()
+    }
+
+    /// Records where the run was published, replacing an earlier upload.
+    pub async fn store_public_archive_url(&self, run_id: &str, url: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO run_public_archives (run_id, url, uploaded_at) VALUES ($1, $2, $3)
+             ON CONFLICT(run_id) DO UPDATE SET url = excluded.url, uploaded_at = excluded.uploaded_at",
+        )
+        .bind(run_id)
+        .bind(url)
+        .bind(timestamp(Utc::now()))
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_public_archive_url(&self, run_id: &str) -> Result<Option<String>> {
+        let url = sqlx::query_scalar("SELECT url FROM run_public_archives WHERE run_id = $1")
+            .bind(run_id)
+            .fetch_optional(self.pool())
+            .await?;
+        Ok(url)
+    }
+
+    /// Records the latest tick replayed for a run, replacing its earlier progress.
+    pub async fn record_run_progress(
+        &self,
+        run_id: &str,
+        tick: u64,
+        percent: Option<f64>,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO run_progress (run_id, tick, percent, updated_at) VALUES ($1, $2, $3, $4)
+             ON CONFLICT(run_id) DO UPDATE SET
+                 tick = excluded.tick, percent = excluded.percent, updated_at = excluded.updated_at",
+        )
+        .bind(run_id)
+        .bind(tick as i64)
+        .bind(percent)
+        .bind(timestamp(Utc::now()))
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Records the checkpoint kept of a run's replay, replacing its earlier one.
+    pub async fn record_checkpoint(
+        &self,
+        run_id: &str,
+        save_path: &str,
+        tick: Option<u64>,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO run_checkpoints (run_id, save_path, tick, created_at) VALUES ($1, $2, $3, $4)
+             ON CONFLICT(run_id) DO UPDATE SET
+                 save_path = excluded.save_path, tick = excluded.tick, created_at = excluded.created_at",
+        )
+        .bind(run_id)
+        .bind(save_path)
+        .bind(tick.map(|tick| tick as i64))
+        .bind(timestamp(Utc::now()))
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_checkpoint(&self, run_id: &str) -> Result<Option<Checkpoint>> {
+        let row = sqlx::query(
+            "SELECT save_path, tick, created_at FROM run_checkpoints WHERE run_id = $1",
         )
+        .bind(run_id)
         .fetch_optional(self.pool())
         .await?;
+        row.map(|row| {
+            Ok(Checkpoint {
+                save_path: row.try_get("save_path")?,
+                tick: row
+                    .try_get::<Option<i64>, _>("tick")?
+                    .map(|tick| tick as u64),
+                created_at: get_timestamp(&row, "created_at")?,
+            })
+        })
+        .transpose()
+    }
+
+    /// Replay progress of the runs currently being processed.
+    pub async fn processing_progress(&self) -> Result<Vec<RunProgress>> {
+        let rows = sqlx::query(
+            "SELECT p.run_id, p.tick, p.percent, p.updated_at
+             FROM run_progress p JOIN runs r ON r.run_id = p.run_id
+             WHERE r.status = $1
+             ORDER BY p.run_id",
+        )
+        .bind(RunStatus::Processing)
+        .fetch_all(self.pool())
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(RunProgress {
+                    run_id: row.try_get("run_id")?,
+                    tick: row.try_get::<i64, _>("tick")? as u64,
+                    percent: row.try_get("percent")?,
+                    updated_at: get_timestamp(row, "updated_at")?,
+                })
+            })
+            .collect()
+    }
+
+    /// Records how long the replay of a run took on `host`, by the Factorio version it ran on,
+    /// replacing its earlier replays. Replays without that version or ticks say nothing about
+    /// the speed and are skipped.
+    pub async fn record_replay_throughput(
+        &self,
+        run_id: &str,
+        host: &str,
+        report: &ReplayReport,
+    ) -> Result<()> {
+        let Some(version) = report.install_version else {
+            return Ok(());
+        };
+        if report.final_tick == 0 || report.duration_secs <= 0.0 {
+            return Ok(());
+        }
+        sqlx::query(
+            "INSERT INTO replay_throughput (run_id, factorio_version, host, ticks, duration_secs, recorded_at)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT(run_id) DO UPDATE SET
+                 factorio_version = excluded.factorio_version, host = excluded.host,
+                 ticks = excluded.ticks, duration_secs = excluded.duration_secs,
+                 recorded_at = excluded.recorded_at",
+        )
+        .bind(run_id)
+        .bind(version.to_string())
+        .bind(host)
+        .bind(report.final_tick as i64)
+        .bind(report.duration_secs)
+        .bind(timestamp(Utc::now()))
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Recorded replay speed, by Factorio version and host.
+    pub async fn replay_throughput(&self) -> Result<Vec<ReplayThroughput>> {
+        let rows = sqlx::query(
+            "SELECT factorio_version, host, COUNT(*) AS replays,
+                 CAST(SUM(ticks) AS BIGINT) AS ticks, SUM(duration_secs) AS duration_secs
+             FROM replay_throughput
+             GROUP BY factorio_version, host
+             ORDER BY factorio_version, host",
+        )
+        .fetch_all(self.pool())
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(ReplayThroughput {
+                    factorio_version: row.try_get("factorio_version")?,
+                    host: row.try_get("host")?,
+                    replays: row.try_get::<i64, _>("replays")? as u64,
+                    ticks: row.try_get::<i64, _>("ticks")? as u64,
+                    duration_secs: row.try_get("duration_secs")?,
+                })
+            })
+            .collect()
+    }
+
+    /// Runs being processed, then those waiting to be and those scheduled for a retry, in the
+    /// order the processor picks them.
+    pub async fn queued_runs(&self) -> Result<Vec<QueuedRun>> {
+        let rows = sqlx::query(
+            "SELECT r.run_id, r.run_time_secs, t.factorio_version,
+                 CASE WHEN r.status = $1 THEN p.tick END AS tick
+             FROM runs r
+             LEFT JOIN run_progress p ON p.run_id = r.run_id
+             LEFT JOIN replay_throughput t ON t.run_id = r.run_id
+             WHERE r.status = $1 OR r.status = $2
+                 OR (r.status = $3 AND r.next_retry_at IS NOT NULL)
+             ORDER BY
+                 CASE WHEN r.status = $1 THEN 0 ELSE 1 END,
+                 r.priority DESC,
+                 CASE WHEN r.status = $2 THEN 0 ELSE 1 END,
+                 r.submitted_date ASC",
+        )
+        .bind(RunStatus::Processing)
+        .bind(RunStatus::Discovered)
+        .bind(RunStatus::Error)
+        .fetch_all(self.pool())
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(QueuedRun {
+                    run_id: row.try_get("run_id")?,
+                    run_time_secs: row.try_get("run_time_secs")?,
+                    replayed_ticks: row.try_get::<Option<i64>, _>("tick")?.unwrap_or(0) as u64,
+                    factorio_version: row.try_get("factorio_version")?,
+                })
+            })
+            .collect()
+    }
+
+    pub async fn record_artifact_cleanup(&self, stats: &CleanupStats) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO artifact_cleanups (cleaned_at, runs_cleaned, bytes_reclaimed)
+             VALUES ($1, $2, $3)",
+        )
+        .bind(timestamp(Utc::now()))
+        .bind(stats.runs_cleaned as i64)
+        .bind(stats.bytes_reclaimed as i64)
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Total bytes of run artifacts removed by the janitor.
+    pub async fn total_reclaimed_bytes(&self) -> Result<u64> {
+        let total: i64 = sqlx::query_scalar(
+            "SELECT CAST(COALESCE(SUM(bytes_reclaimed), 0) AS BIGINT) FROM artifact_cleanups",
+        )
+        .fetch_one(self.pool())
+        .await?;
+        Ok(total as u64)
+    }
 
-        Ok(run)
+    #[allow(dead_code)]
+    pub async fn get_progress_timeline(&self, run_id: &str) -> Result<Vec<ProgressSnapshot>> {
+        let timeline: Option<String> =
+            sqlx::query_scalar("SELECT progress_timeline FROM runs WHERE run_id = $1")
+                .bind(run_id)
+                .fetch_optional(self.pool())
+                .await?
+                .flatten();
+        match timeline {
+            Some(timeline) => Ok(serde_json::from_str(&timeline)?),
+            None => Ok(Vec::new()),
+        }
     }
 
-    pub async fn count_runs_by_status(&self) -> Result<std::collections::HashMap<RunStatus, i64>> {
-        let rows = sqlx::query!(
+    pub async fn get_run(&self, run_id: &str) -> Result<Option<Run>> {
+        let query_str = format!("SELECT {} FROM runs WHERE run_id = $1", RUN_COLUMNS);
+        let row = sqlx::query(&query_str)
+            .bind(run_id)
+            .fetch_optional(self.pool())
+            .await?;
+
+        row.as_ref().map(run_from_row).transpose()
+    }
+
+    pub async fn count_runs_by_status(&self) -> Result<HashMap<RunStatus, i64>> {
+        let rows = sqlx::query(
             r#"
-            SELECT status as "status: RunStatus", COUNT(*) as "count: i64"
+            SELECT status, COUNT(*) AS count
             FROM runs
             GROUP BY status
-            "#
+            "#,
         )
         .fetch_all(self.pool())
         .await?;
 
-        Ok(rows.into_iter().map(|r| (r.status, r.count)).collect())
+        rows.iter()
+            .map(|row| Ok((row.try_get("status")?, row.try_get("count")?)))
+            .collect()
     }
 
     pub async fn query_runs(&self, filter: RunFilter) -> Result<Vec<Run>> {
-        let mut query_parts = vec!["SELECT run_id, game_id, category_id, submitted_date, status, error_message, retry_count, next_retry_at, error_class, created_at, updated_at, bot_notified FROM runs WHERE 1=1".to_string()];
+        let mut query_parts = vec![format!("SELECT {} FROM runs WHERE 1=1", RUN_COLUMNS)];
         let mut conditions = Vec::new();
-
-        if filter.status.is_some() {
-            conditions.push("status = ?");
+        let search_terms: Vec<&str> = filter
+            .search
+            .as_deref()
+            .map(|search| search.split_whitespace().collect())
+            .unwrap_or_default();
+
+        let mut params = 0;
+        let mut param = || {
+            params += 1;
+            format!("${}", params)
+        };
+        if !filter.statuses.is_empty() {
+            let statuses: Vec<_> = filter.statuses.iter().map(|_| param()).collect();
+            conditions.push(format!("status IN ({})", statuses.join(", ")));
         }
         if filter.game_id.is_some() {
-            conditions.push("game_id = ?");
+            conditions.push(format!("game_id = {}", param()));
         }
         if filter.category_id.is_some() {
-            conditions.push("category_id = ?");
+            conditions.push(format!("category_id = {}", param()));
         }
         if filter.since_date.is_some() {
-            conditions.push("submitted_date >= ?");
+            conditions.push(format!("submitted_date >= {}", param()));
         }
         if filter.before_date.is_some() {
-            conditions.push("submitted_date < ?");
+            conditions.push(format!("submitted_date < {}", param()));
         }
         if filter.error_class.is_some() {
-            conditions.push("error_class = ?");
+            conditions.push(format!("error_class = {}", param()));
         }
+        // LIKE is case-sensitive on Postgres, unlike on SQLite
         if filter.error_reason.is_some() {
-            conditions.push("error_message LIKE ?");
+            conditions.push(format!("LOWER(error_message) LIKE LOWER({})", param()));
+        }
+        if filter.min_retries.is_some() {
+            conditions.push(format!("retry_count >= {}", param()));
+        }
+        if filter.max_retries.is_some() {
+            conditions.push(format!("retry_count <= {}", param()));
+        }
+        if filter.bot_notified.is_some() {
+            conditions.push(format!("bot_notified = {}", param()));
+        }
+        for _ in &search_terms {
+            conditions.push(format!(
+                "LOWER(error_message) LIKE LOWER({}) ESCAPE '\\'",
+                param()
+            ));
+        }
+        if filter.after.is_some() {
+            let (date, date_again, run_id) = (param(), param(), param());
+            conditions.push(format!(
+                "(submitted_date < {} OR (submitted_date = {} AND run_id > {}))",
+                date, date_again, run_id
+            ));
         }
 
         for condition in conditions {
             query_parts.push(format!("AND {}", condition));
         }
 
-        query_parts.push("ORDER BY submitted_date DESC".to_string());
+        query_parts.push(
+            match filter.order {
+                RunOrder::Submitted => "ORDER BY submitted_date DESC, run_id",
+                RunOrder::Updated => "ORDER BY updated_at DESC, run_id",
+            }
+            .to_string(),
+        );
         if filter.limit.is_some() {
-            query_parts.push("LIMIT ?".to_string());
+            query_parts.push(format!("LIMIT {}", param()));
         }
         if filter.offset > 0 {
-            query_parts.push("OFFSET ?".to_string());
+            query_parts.push(format!("OFFSET {}", param()));
         }
 
         let query_str = query_parts.join(" ");
         let mut query = sqlx::query(&query_str);
 
-        if let Some(status) = filter.status {
-            query = query.bind(status);
+        for status in &filter.statuses {
+            query = query.bind(*status);
         }
         if let Some(game_id) = filter.game_id {
             query = query.bind(game_id);
@@ -341,144 +1044,365 @@ impl Database {
             query = query.bind(category_id);
         }
         if let Some(since_date) = filter.since_date {
-            query = query.bind(since_date);
+            query = query.bind(timestamp(since_date));
         }
         if let Some(before_date) = filter.before_date {
-            query = query.bind(before_date);
+            query = query.bind(timestamp(before_date));
         }
         if let Some(error_class) = filter.error_class {
             query = query.bind(error_class);
         }
-        if let Some(error_reason) = filter.error_reason {
+        if let Some(error_reason) = &filter.error_reason {
             query = query.bind(format!("%{}%", error_reason));
         }
+        if let Some(min_retries) = filter.min_retries {
+            query = query.bind(i64::from(min_retries));
+        }
+        if let Some(max_retries) = filter.max_retries {
+            query = query.bind(i64::from(max_retries));
+        }
+        if let Some(bot_notified) = filter.bot_notified {
+            query = query.bind(bot_notified);
+        }
+        for term in &search_terms {
+            query = query.bind(format!("%{}%", escape_like(term)));
+        }
+        if let Some((submitted_date, run_id)) = filter.after {
+            query = query
+                .bind(timestamp(submitted_date))
+                .bind(timestamp(submitted_date))
+                .bind(run_id);
+        }
         if let Some(limit) = filter.limit {
-            query = query.bind(limit);
+            query = query.bind(i64::from(limit));
         }
         if filter.offset > 0 {
-            query = query.bind(filter.offset);
+            query = query.bind(i64::from(filter.offset));
         }
 
         let rows = query.fetch_all(self.pool()).await?;
 
-        rows.iter()
-            .map(|r| {
-                Ok::<_, sqlx::Error>(Run {
-                    run_id: r.try_get("run_id")?,
-                    game_id: r.try_get("game_id")?,
-                    category_id: r.try_get("category_id")?,
-                    submitted_date: r.try_get("submitted_date")?,
-                    status: r.try_get("status")?,
-                    error_message: r.try_get("error_message")?,
-                    retry_count: r.try_get("retry_count")?,
-                    next_retry_at: r.try_get("next_retry_at")?,
-                    error_class: r.try_get("error_class")?,
-                    created_at: r.try_get("created_at")?,
-                    updated_at: r.try_get("updated_at")?,
-                    bot_notified: r.try_get("bot_notified")?,
-                })
-            })
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(Into::into)
+        rows.iter().map(run_from_row).collect()
     }
 
-    pub async fn get_next_run_to_process(
+    /// Atomically picks the next run to process and marks it as processing by `worker_id`.
+    /// Processing runs are only picked once their lease has expired.
+    pub async fn claim_next_run(
         &self,
-        allowed_game_categories: &[(String, String)],
+        selection: &RunSelection,
+        worker_id: &str,
+        lease_expires_at: DateTime<Utc>,
     ) -> Result<Option<Run>> {
-        if allowed_game_categories.is_empty() {
+        if selection.is_empty() {
             return Ok(None);
         }
 
-        let now = Utc::now();
-        let processing_status = RunStatus::Processing;
-        let discovered_status = RunStatus::Discovered;
-        let error_status = RunStatus::Error;
-        let conditions = allowed_game_categories
-            .iter()
-            .map(|_| "(game_id = ? AND category_id = ?)")
-            .collect::<Vec<_>>()
-            .join(" OR ");
+        // the write lock is taken up front, so no other daemon can claim the same run
+        let mut tx = self.begin_write().await?;
+
+        let query_str = format!(
+            "SELECT run_id, status FROM runs {}",
+            next_run_filter(selection)
+        );
+        let candidate = bind_next_run_filter(sqlx::query(&query_str), selection)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(candidate) = candidate else {
+            return Ok(None);
+        };
+        let run_id: String = candidate.try_get("run_id")?;
+        let old_status: RunStatus = candidate.try_get("status")?;
 
         let query_str = format!(
             r#"
-            SELECT run_id, game_id, category_id, submitted_date, status,
-                   error_message, retry_count, next_retry_at, error_class,
-                   created_at, updated_at, bot_notified
-            FROM runs
-            WHERE (
-                (status = ? AND ({}))
-                OR (status = ? AND next_retry_at IS NOT NULL AND next_retry_at <= ? AND ({}))
-                OR (status = ? AND ({}))
-            )
-            ORDER BY
-                CASE
-                    WHEN status = ? THEN 0
-                    WHEN status = ? THEN 1
-                    WHEN status = ? THEN 2
-                END,
-                submitted_date ASC
-            LIMIT 1
+            UPDATE runs
+            SET status = $1, claimed_by = $2, lease_expires_at = $3, bot_notified = false, updated_at = $4
+            WHERE run_id = $5
+            RETURNING {}
             "#,
-            conditions, conditions, conditions
+            RUN_COLUMNS
         );
+        let row = sqlx::query(&query_str)
+            .bind(RunStatus::Processing)
+            .bind(worker_id)
+            .bind(timestamp(lease_expires_at))
+            .bind(timestamp(Utc::now()))
+            .bind(&run_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        let run = run_from_row(&row)?;
+        // progress and review of an earlier attempt no longer apply
+        for table in ["run_progress", "run_reviews"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE run_id = $1", table))
+                .bind(&run_id)
+                .execute(&mut *tx)
+                .await?;
+        }
 
-        let mut query = sqlx::query(&query_str).bind(processing_status);
+        let reason = format!("claimed by {}", worker_id);
+        self.record_event(
+            &mut tx,
+            &run_id,
+            Some(old_status),
+            RunStatus::Processing,
+            Some(&reason),
+        )
+        .await?;
 
-        for (game_id, cat_id) in allowed_game_categories {
-            query = query.bind(game_id).bind(cat_id);
-        }
+        tx.commit().await?;
+        Ok(Some(run))
+    }
 
-        query = query.bind(error_status).bind(now);
+    /// Claims a specific run for `worker_id` like [`Self::claim_next_run`], whatever its status,
+    /// unless a worker holds an unexpired lease on it. Returns the holder in that case.
+    pub async fn claim_run(
+        &self,
+        run_id: &str,
+        worker_id: &str,
+        lease_expires_at: DateTime<Utc>,
+    ) -> Result<Result<Run, String>> {
+        let mut tx = self.begin_write().await?;
+
+        let now = timestamp(Utc::now());
+        let old_status: RunStatus = sqlx::query_scalar("SELECT status FROM runs WHERE run_id = $1")
+            .bind(run_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Run not found: {}", run_id))?;
+        let holder: Option<String> = sqlx::query_scalar(
+            "SELECT claimed_by FROM runs WHERE run_id = $1 AND status = $2 AND lease_expires_at > $3",
+        )
+        .bind(run_id)
+        .bind(RunStatus::Processing)
+        .bind(&now)
+        .fetch_optional(&mut *tx)
+        .await?
+        .flatten();
+        if let Some(holder) = holder {
+            return Ok(Err(holder));
+        }
 
-        for (game_id, cat_id) in allowed_game_categories {
-            query = query.bind(game_id).bind(cat_id);
+        let query_str = format!(
+            r#"
+            UPDATE runs
+            SET status = $1, claimed_by = $2, lease_expires_at = $3, bot_notified = false, updated_at = $4
+            WHERE run_id = $5
+            RETURNING {}
+            "#,
+            RUN_COLUMNS
+        );
+        let row = sqlx::query(&query_str)
+            .bind(RunStatus::Processing)
+            .bind(worker_id)
+            .bind(timestamp(lease_expires_at))
+            .bind(&now)
+            .bind(run_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        let run = run_from_row(&row)?;
+        // verifying the run again supersedes its progress and review
+        for table in ["run_progress", "run_reviews"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE run_id = $1", table))
+                .bind(run_id)
+                .execute(&mut *tx)
+                .await?;
         }
 
-        query = query.bind(discovered_status);
+        let reason = format!("claimed by {}", worker_id);
+        self.record_event(
+            &mut tx,
+            run_id,
+            Some(old_status),
+            RunStatus::Processing,
+            Some(&reason),
+        )
+        .await?;
+
+        tx.commit().await?;
+        Ok(Ok(run))
+    }
+
+    /// Extends the lease of a run still held by `worker_id`. Returns false if it was lost.
+    pub async fn renew_lease(
+        &self,
+        run_id: &str,
+        worker_id: &str,
+        lease_expires_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE runs SET lease_expires_at = $1 WHERE run_id = $2 AND claimed_by = $3 AND status = $4",
+        )
+        .bind(timestamp(lease_expires_at))
+        .bind(run_id)
+        .bind(worker_id)
+        .bind(RunStatus::Processing)
+        .execute(self.pool())
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
 
-        for (game_id, cat_id) in allowed_game_categories {
-            query = query.bind(game_id).bind(cat_id);
+    /// Resets a run to be verified again, dropping any stale claim. Returns the holder's
+    /// worker id instead if the run is being processed under a live lease.
+    pub async fn reset_run(&self, run_id: &str, clear_retries: bool) -> Result<Result<(), String>> {
+        let mut tx = self.begin_write().await?;
+
+        let now = timestamp(Utc::now());
+        let old_status: RunStatus = sqlx::query_scalar("SELECT status FROM runs WHERE run_id = $1")
+            .bind(run_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Run not found: {}", run_id))?;
+        let holder: Option<String> = sqlx::query_scalar(
+            "SELECT claimed_by FROM runs WHERE run_id = $1 AND status = $2 AND lease_expires_at > $3",
+        )
+        .bind(run_id)
+        .bind(RunStatus::Processing)
+        .bind(&now)
+        .fetch_optional(&mut *tx)
+        .await?
+        .flatten();
+        if let Some(holder) = holder {
+            return Ok(Err(holder));
         }
 
-        query = query
-            .bind(processing_status)
-            .bind(error_status)
-            .bind(discovered_status);
+        sqlx::query(
+            r#"
+            UPDATE runs
+            SET status = $1, error_message = NULL, claimed_by = NULL, lease_expires_at = NULL,
+                bot_notified = false, updated_at = $2
+            WHERE run_id = $3
+            "#,
+        )
+        .bind(RunStatus::Discovered)
+        .bind(&now)
+        .bind(run_id)
+        .execute(&mut *tx)
+        .await?;
+        if clear_retries {
+            sqlx::query(
+                "UPDATE runs SET retry_count = 0, next_retry_at = NULL, error_class = NULL WHERE run_id = $1",
+            )
+            .bind(run_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query("DELETE FROM run_reviews WHERE run_id = $1")
+            .bind(run_id)
+            .execute(&mut *tx)
+            .await?;
+        self.record_event(
+            &mut tx,
+            run_id,
+            Some(old_status),
+            RunStatus::Discovered,
+            None,
+        )
+        .await?;
 
-        let row = query.fetch_optional(self.pool()).await?;
+        tx.commit().await?;
+        Ok(Ok(()))
+    }
 
-        row.map(|r| {
-            Ok::<_, sqlx::Error>(Run {
-                run_id: r.try_get("run_id")?,
-                game_id: r.try_get("game_id")?,
-                category_id: r.try_get("category_id")?,
-                submitted_date: r.try_get("submitted_date")?,
-                status: r.try_get("status")?,
-                error_message: r.try_get("error_message")?,
-                retry_count: r.try_get("retry_count")?,
-                next_retry_at: r.try_get("next_retry_at")?,
-                error_class: r.try_get("error_class")?,
-                created_at: r.try_get("created_at")?,
-                updated_at: r.try_get("updated_at")?,
-                bot_notified: r.try_get("bot_notified")?,
-            })
-        })
-        .transpose()
-        .map_err(Into::into)
+    pub async fn release_lease(&self, run_id: &str, worker_id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE runs SET claimed_by = NULL, lease_expires_at = NULL WHERE run_id = $1 AND claimed_by = $2",
+        )
+        .bind(run_id)
+        .bind(worker_id)
+        .execute(self.pool())
+        .await?;
+        Ok(())
     }
 
-    pub async fn get_earliest_submitted_date(&self) -> Result<Option<DateTime<Utc>>> {
-        let result = sqlx::query!(
+    /// Records that `instance_id` started. Returns whether its previous run shut down
+    /// cleanly, or None on its first start.
+    pub async fn record_daemon_start(&self, instance_id: &str) -> Result<Option<bool>> {
+        let previous: Option<i64> = sqlx::query_scalar(
+            "SELECT CAST(clean_shutdown AS INTEGER) FROM daemon_state WHERE instance_id = $1",
+        )
+        .bind(instance_id)
+        .fetch_optional(self.pool())
+        .await?;
+        sqlx::query(
             r#"
-            SELECT MIN(submitted_date) as "earliest: chrono::DateTime<Utc>"
-            FROM runs
+            INSERT INTO daemon_state (instance_id, started_at, stopped_at, clean_shutdown)
+            VALUES ($1, $2, NULL, false)
+            ON CONFLICT(instance_id) DO UPDATE SET
+                started_at = excluded.started_at, stopped_at = NULL, clean_shutdown = false
             "#,
         )
+        .bind(instance_id)
+        .bind(timestamp(Utc::now()))
+        .execute(self.pool())
+        .await?;
+        Ok(previous.map(|clean_shutdown| clean_shutdown != 0))
+    }
+
+    pub async fn record_clean_shutdown(&self, instance_id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE daemon_state SET stopped_at = $1, clean_shutdown = true WHERE instance_id = $2",
+        )
+        .bind(timestamp(Utc::now()))
+        .bind(instance_id)
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Number of discovered runs waiting to be processed for a game/category.
+    pub async fn count_queued_runs(&self, game_id: &str, category_id: &str) -> Result<usize> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM runs WHERE game_id = $1 AND category_id = $2 AND status = $3",
+        )
+        .bind(game_id)
+        .bind(category_id)
+        .bind(RunStatus::Discovered)
         .fetch_one(self.pool())
         .await?;
+        Ok(count as usize)
+    }
+
+    /// Returns false if the run does not exist.
+    pub async fn set_run_priority(&self, run_id: &str, priority: i64) -> Result<bool> {
+        let result =
+            sqlx::query("UPDATE runs SET priority = $1, updated_at = $2 WHERE run_id = $3")
+                .bind(priority)
+                .bind(timestamp(Utc::now()))
+                .bind(run_id)
+                .execute(self.pool())
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
 
-        Ok(result.earliest)
+    /// Releases leases left behind by crashed workers: those held by `own_workers` (this
+    /// instance, restarting) and expired ones from any instance. The runs stay in processing
+    /// state and are picked up again first. Returns the recovered run ids.
+    pub async fn recover_orphaned_leases(&self, own_workers: &[String]) -> Result<Vec<String>> {
+        let query_str = format!(
+            r#"
+            UPDATE runs
+            SET claimed_by = NULL, lease_expires_at = NULL
+            WHERE status = $1 AND claimed_by IS NOT NULL
+              AND (lease_expires_at <= $2 OR claimed_by IN ({}))
+            RETURNING run_id
+            "#,
+            placeholders(3, own_workers.len())
+        );
+        let mut query = sqlx::query_scalar(&query_str)
+            .bind(RunStatus::Processing)
+            .bind(timestamp(Utc::now()));
+        for worker in own_workers {
+            query = query.bind(worker);
+        }
+        Ok(query.fetch_all(self.pool()).await?)
+    }
+
+    pub async fn get_earliest_submitted_date(&self) -> Result<Option<DateTime<Utc>>> {
+        let earliest: Option<String> = sqlx::query_scalar("SELECT MIN(submitted_date) FROM runs")
+            .fetch_one(self.pool())
+            .await?;
+
+        earliest.as_deref().map(parse_timestamp).transpose()
     }
 
     pub async fn get_latest_submitted_date(
@@ -486,19 +1410,15 @@ impl Database {
         game_id: &str,
         category_id: &str,
     ) -> Result<Option<DateTime<Utc>>> {
-        let result = sqlx::query!(
-            r#"
-            SELECT MAX(submitted_date) as "latest: chrono::DateTime<Utc>"
-            FROM runs
-            WHERE game_id = ? AND category_id = ?
-            "#,
-            game_id,
-            category_id
+        let latest: Option<String> = sqlx::query_scalar(
+            "SELECT MAX(submitted_date) FROM runs WHERE game_id = $1 AND category_id = $2",
         )
+        .bind(game_id)
+        .bind(category_id)
         .fetch_one(self.pool())
         .await?;
 
-        Ok(result.latest)
+        latest.as_deref().map(parse_timestamp).transpose()
     }
 
     pub async fn delete_runs(&self, run_ids: &[String]) -> Result<u64> {
@@ -506,8 +1426,10 @@ impl Database {
             return Ok(0);
         }
 
-        let placeholders = run_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-        let query_str = format!("DELETE FROM runs WHERE run_id IN ({})", placeholders);
+        let query_str = format!(
+            "DELETE FROM runs WHERE run_id IN ({})",
+            placeholders(1, run_ids.len())
+        );
 
         let mut query = sqlx::query(&query_str);
         for run_id in run_ids {
@@ -518,15 +1440,21 @@ impl Database {
         Ok(result.rows_affected())
     }
 
+    /// Records the result of verifying a run. With `claimed_by`, the status only changes while
+    /// that worker holds the run's claim, and false is returned if it lost it; the worker
+    /// renews its lease first, so the report can't be stored after another worker took over.
     pub async fn process_replay_result(
         &self,
         run_id: &str,
+        claimed_by: Option<&str>,
         result: Result<ReplayReport, RunProcessingError>,
         retry_config: &RetryConfig,
-    ) -> Result<()> {
+    ) -> Result<bool> {
         match result {
             Ok(report) => {
                 self.clear_retry_fields(run_id).await?;
+                self.store_report_details(run_id, &report).await?;
+                self.store_replay_result(run_id, &report).await?;
 
                 let message = if report.messages.is_empty() {
                     None
@@ -534,30 +1462,33 @@ impl Database {
                     Some(report.messages.join("; "))
                 };
 
-                if report.win_condition_not_completed {
-                    warn!("Run {} failed: win condition never met", run_id);
-                    self.mark_run_failed(run_id, message.as_deref()).await?;
-                    return Ok(());
+                let status = RunStatus::for_report(&report);
+                let outcome = match status {
+                    RunStatus::Passed => "passed verification",
+                    RunStatus::NeedsReview => "passed with warnings (needs review)",
+                    _ if report.win_condition_not_completed => "failed: win condition never met",
+                    _ => "failed verification",
+                };
+                let message = message.filter(|_| status != RunStatus::Passed);
+                if !self
+                    .set_run_status(run_id, claimed_by, status, message.as_deref())
+                    .await?
+                {
+                    return Ok(false);
                 }
-
-                match report.max_msg_level {
-                    MsgLevel::Info => {
-                        self.mark_run_passed(run_id).await?;
-                        info!("Run {} passed verification", run_id);
-                    }
-                    MsgLevel::Warn => {
-                        self.mark_run_needs_review(run_id, message.as_deref())
-                            .await?;
-                        warn!("Run {} passed with warnings (needs review)", run_id);
-                    }
-                    MsgLevel::Error => {
-                        self.mark_run_failed(run_id, message.as_deref()).await?;
-                        warn!("Run {} failed verification", run_id);
-                    }
+                if status == RunStatus::Passed {
+                    info!("Run {} {}", run_id, outcome);
+                } else {
+                    warn!("Run {} {}", run_id, outcome);
                 }
             }
             Err(e) => {
-                self.mark_run_error(run_id, &e.message).await?;
+                if !self
+                    .set_run_status(run_id, claimed_by, RunStatus::Error, Some(&e.message))
+                    .await?
+                {
+                    return Ok(false);
+                }
 
                 let run = self.get_run(run_id).await?.ok_or_else(|| {
                     anyhow::anyhow!("Run {} not found after marking error", run_id)
@@ -596,75 +1527,441 @@ impl Database {
                 }
             }
         }
+        Ok(true)
+    }
+
+    pub async fn cache_game_name(&self, game_id: &str, game_name: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO game_cache (game_id, game_name, updated_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT(game_id) DO UPDATE SET
+                game_name = excluded.game_name,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(game_id)
+        .bind(game_name)
+        .bind(timestamp(Utc::now()))
+        .execute(self.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// The cached name of a game or category, or a remembered failed lookup.
+    pub async fn get_cached_name(&self, kind: NameKind, id: &str) -> Result<Option<CachedName>> {
+        let query = match kind {
+            NameKind::Game => "SELECT game_name, updated_at FROM game_cache WHERE game_id = $1",
+            NameKind::Category => {
+                "SELECT category_name, updated_at FROM category_cache WHERE category_id = $1"
+            }
+        };
+        let found: Option<(String, String)> = sqlx::query_as(query)
+            .bind(id)
+            .fetch_optional(self.pool())
+            .await?;
+        if let Some((name, updated_at)) = found {
+            return Ok(Some(CachedName {
+                name: Some(name),
+                updated_at: parse_timestamp(&updated_at)?,
+            }));
+        }
+
+        let checked_at: Option<String> = sqlx::query_scalar(
+            "SELECT checked_at FROM missing_name_cache WHERE kind = $1 AND id = $2",
+        )
+        .bind(kind.as_str())
+        .bind(id)
+        .fetch_optional(self.pool())
+        .await?;
+        checked_at
+            .map(|checked_at| {
+                Ok(CachedName {
+                    name: None,
+                    updated_at: parse_timestamp(&checked_at)?,
+                })
+            })
+            .transpose()
+    }
+
+    pub async fn cache_name(&self, kind: NameKind, id: &str, name: &str) -> Result<()> {
+        match kind {
+            NameKind::Game => self.cache_game_name(id, name).await?,
+            NameKind::Category => self.cache_category_name(id, name).await?,
+        }
+        sqlx::query("DELETE FROM missing_name_cache WHERE kind = $1 AND id = $2")
+            .bind(kind.as_str())
+            .bind(id)
+            .execute(self.pool())
+            .await?;
         Ok(())
     }
 
-    pub async fn get_cached_game_name(&self, game_id: &str) -> Result<Option<String>> {
-        let result = sqlx::query!(
-            r#"SELECT game_name FROM game_cache WHERE game_id = ?"#,
-            game_id
+    /// Remembers that speedrun.com has no game or category with this ID.
+    pub async fn cache_missing_name(&self, kind: NameKind, id: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO missing_name_cache (kind, id, checked_at) VALUES ($1, $2, $3)
+             ON CONFLICT(kind, id) DO UPDATE SET checked_at = excluded.checked_at",
         )
-        .fetch_optional(self.pool())
+        .bind(kind.as_str())
+        .bind(id)
+        .bind(timestamp(Utc::now()))
+        .execute(self.pool())
         .await?;
-
-        Ok(result.map(|r| r.game_name))
+        Ok(())
     }
 
-    pub async fn cache_game_name(&self, game_id: &str, game_name: &str) -> Result<()> {
-        let now = Utc::now();
-        sqlx::query!(
+    pub async fn cache_category_name(&self, category_id: &str, category_name: &str) -> Result<()> {
+        sqlx::query(
             r#"
-            INSERT INTO game_cache (game_id, game_name, updated_at)
-            VALUES (?, ?, ?)
-            ON CONFLICT(game_id) DO UPDATE SET
-                game_name = excluded.game_name,
+            INSERT INTO category_cache (category_id, category_name, updated_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT(category_id) DO UPDATE SET
+                category_name = excluded.category_name,
                 updated_at = excluded.updated_at
             "#,
-            game_id,
-            game_name,
-            now
         )
+        .bind(category_id)
+        .bind(category_name)
+        .bind(timestamp(Utc::now()))
         .execute(self.pool())
         .await?;
 
         Ok(())
     }
 
-    pub async fn get_cached_category_name(&self, category_id: &str) -> Result<Option<String>> {
-        let result = sqlx::query!(
-            r#"SELECT category_name FROM category_cache WHERE category_id = ?"#,
-            category_id
+    pub async fn insert_job(&self, job: &NewJob) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO verify_jobs (job_id, source, game_id, category_id, save_path, host, status, submitted_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
         )
-        .fetch_optional(self.pool())
+        .bind(&job.job_id)
+        .bind(job.source)
+        .bind(&job.game_id)
+        .bind(&job.category_id)
+        .bind(&job.save_path)
+        .bind(&job.host)
+        .bind(JobStatus::Queued)
+        .bind(timestamp(Utc::now()))
+        .execute(self.pool())
         .await?;
+        Ok(())
+    }
 
-        Ok(result.map(|r| r.category_name))
+    pub async fn get_job(&self, job_id: &str) -> Result<Option<VerifyJob>> {
+        let query_str = format!("SELECT {} FROM verify_jobs WHERE job_id = $1", JOB_COLUMNS);
+        sqlx::query(&query_str)
+            .bind(job_id)
+            .fetch_optional(self.pool())
+            .await?
+            .as_ref()
+            .map(job_from_row)
+            .transpose()
     }
 
-    pub async fn cache_category_name(&self, category_id: &str, category_name: &str) -> Result<()> {
-        let now = Utc::now();
-        sqlx::query!(
-            r#"
-            INSERT INTO category_cache (category_id, category_name, updated_at)
-            VALUES (?, ?, ?)
-            ON CONFLICT(category_id) DO UPDATE SET
-                category_name = excluded.category_name,
-                updated_at = excluded.updated_at
-            "#,
-            category_id,
-            category_name,
-            now
+    /// Jobs matching `filter`, most recently submitted first.
+    pub async fn query_jobs(&self, filter: &JobFilter) -> Result<Vec<VerifyJob>> {
+        let mut query_str = format!("SELECT {} FROM verify_jobs", JOB_COLUMNS);
+        if !filter.statuses.is_empty() {
+            query_str.push_str(&format!(
+                " WHERE status IN ({})",
+                placeholders(1, filter.statuses.len())
+            ));
+        }
+        query_str.push_str(" ORDER BY submitted_at DESC, job_id");
+        if filter.limit.is_some() {
+            query_str.push_str(&format!(" LIMIT ${}", filter.statuses.len() + 1));
+        }
+        let mut query = sqlx::query(&query_str);
+        for status in &filter.statuses {
+            query = query.bind(*status);
+        }
+        if let Some(limit) = filter.limit {
+            query = query.bind(i64::from(limit));
+        }
+        let rows = query.fetch_all(self.pool()).await?;
+        rows.iter().map(job_from_row).collect()
+    }
+
+    /// Claims the job queued longest ago whose save is on `host`, for the daemon
+    /// `instance_id`. Processing jobs are claimed again once their lease has expired.
+    pub async fn claim_next_job(
+        &self,
+        instance_id: &str,
+        host: &str,
+        lease_expires_at: DateTime<Utc>,
+    ) -> Result<Option<VerifyJob>> {
+        let now = timestamp(Utc::now());
+        let mut tx = self.begin_write().await?;
+        // jobs from before hosts were recorded may be replayed anywhere
+        let job_id: Option<String> = sqlx::query_scalar(
+            "SELECT job_id FROM verify_jobs
+             WHERE (status = $1 OR (status = $2 AND (lease_expires_at IS NULL OR lease_expires_at < $3)))
+               AND (host IS NULL OR host = $4)
+             ORDER BY submitted_at, job_id LIMIT 1",
         )
-        .execute(self.pool())
+        .bind(JobStatus::Queued)
+        .bind(JobStatus::Processing)
+        .bind(&now)
+        .bind(host)
+        .fetch_optional(&mut *tx)
         .await?;
+        let Some(job_id) = job_id else {
+            return Ok(None);
+        };
+        let query_str = format!(
+            "UPDATE verify_jobs SET status = $1, claimed_by = $2, started_at = $3, lease_expires_at = $4
+             WHERE job_id = $5
+             RETURNING {}",
+            JOB_COLUMNS
+        );
+        let row = sqlx::query(&query_str)
+            .bind(JobStatus::Processing)
+            .bind(instance_id)
+            .bind(&now)
+            .bind(timestamp(lease_expires_at))
+            .bind(&job_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        job_from_row(&row).map(Some)
+    }
 
+    /// Stores the result of replaying a job's save.
+    pub async fn finish_job(
+        &self,
+        job_id: &str,
+        result: Result<&ReplayReport, &str>,
+    ) -> Result<()> {
+        let (status, summary, messages, error) = match result {
+            Ok(report) => (
+                JobStatus::for_report(report),
+                Some(serde_json::to_string(&report.summary())?),
+                Some(serde_json::to_string(&report.messages)?),
+                None,
+            ),
+            Err(error) => (JobStatus::Error, None, None, Some(error)),
+        };
+        sqlx::query(
+            "UPDATE verify_jobs
+             SET status = $1, report_summary = $2, messages = $3, error_message = $4, finished_at = $5
+             WHERE job_id = $6",
+        )
+        .bind(status)
+        .bind(summary)
+        .bind(messages)
+        .bind(error)
+        .bind(timestamp(Utc::now()))
+        .bind(job_id)
+        .execute(self.pool())
+        .await?;
         Ok(())
     }
+
+    /// Extends `instance_id`'s lease on a job it is replaying. Returns false if the job was
+    /// claimed by another daemon since.
+    pub async fn renew_job_lease(
+        &self,
+        job_id: &str,
+        instance_id: &str,
+        lease_expires_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE verify_jobs SET lease_expires_at = $1
+             WHERE job_id = $2 AND claimed_by = $3 AND status = $4",
+        )
+        .bind(timestamp(lease_expires_at))
+        .bind(job_id)
+        .bind(instance_id)
+        .bind(JobStatus::Processing)
+        .execute(self.pool())
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Queues the jobs `instance_id` was replaying again, after it stopped without finishing
+    /// them, rather than waiting for their leases to expire. Returns their IDs.
+    pub async fn requeue_jobs(&self, instance_id: &str) -> Result<Vec<String>> {
+        let job_ids = sqlx::query_scalar(
+            "UPDATE verify_jobs SET status = $1, claimed_by = NULL, started_at = NULL, lease_expires_at = NULL
+             WHERE status = $2 AND claimed_by = $3
+             RETURNING job_id",
+        )
+        .bind(JobStatus::Queued)
+        .bind(JobStatus::Processing)
+        .bind(instance_id)
+        .fetch_all(self.pool())
+        .await?;
+        Ok(job_ids)
+    }
+}
+
+// SQLite has no boolean type, so booleans are read as integers on both backends
+const RUN_COLUMNS: &str = "run_id, game_id, category_id, submitted_date, status, \
+    error_message, retry_count, next_retry_at, error_class, created_at, updated_at, \
+    CAST(bot_notified AS INTEGER) AS bot_notified";
+
+const RUN_DETAILS_COLUMNS: &str =
+    "player_names, run_time_secs, platform, variable_values, leaderboard_place";
+
+const JOB_COLUMNS: &str = "job_id, source, game_id, category_id, save_path, status, \
+    submitted_at, started_at, finished_at, report_summary, messages, error_message";
+
+fn job_from_row(row: &AnyRow) -> Result<VerifyJob> {
+    let summary: Option<String> = row.try_get("report_summary")?;
+    let messages: Option<String> = row.try_get("messages")?;
+    Ok(VerifyJob {
+        job_id: row.try_get("job_id")?,
+        source: row.try_get("source")?,
+        game_id: row.try_get("game_id")?,
+        category_id: row.try_get("category_id")?,
+        save_path: row.try_get("save_path")?,
+        status: row.try_get("status")?,
+        submitted_at: get_timestamp(row, "submitted_at")?,
+        started_at: get_optional_timestamp(row, "started_at")?,
+        finished_at: get_optional_timestamp(row, "finished_at")?,
+        summary: summary.as_deref().map(serde_json::from_str).transpose()?,
+        messages: messages
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()?
+            .unwrap_or_default(),
+        error: row.try_get("error_message")?,
+    })
+}
+
+/// Escapes LIKE wildcards so the term matches literally (with `ESCAPE '\'`).
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Placeholders of `count` parameters from `$first`. No parameters give `NULL`, as Postgres
+/// doesn't allow `IN ()`; nothing is `IN (NULL)` either.
+fn placeholders(first: usize, count: usize) -> String {
+    if count == 0 {
+        return "NULL".to_string();
+    }
+    (first..first + count)
+        .map(|param| format!("${}", param))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Timestamps are stored as RFC 3339 text, the format sqlx used for SQLite before Postgres
+/// was supported, which sorts in time order.
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, false)
+}
+
+fn parse_timestamp(text: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(text)?.with_timezone(&Utc))
+}
+
+fn get_timestamp(row: &AnyRow, column: &str) -> Result<DateTime<Utc>> {
+    parse_timestamp(&row.try_get::<String, _>(column)?)
+}
+
+fn get_optional_timestamp(row: &AnyRow, column: &str) -> Result<Option<DateTime<Utc>>> {
+    row.try_get::<Option<String>, _>(column)?
+        .as_deref()
+        .map(parse_timestamp)
+        .transpose()
+}
+
+/// `WHERE ... ORDER BY ... LIMIT 1` selecting the next run to process.
+/// Parameters are bound by [`bind_next_run_filter`].
+fn next_run_filter(selection: &RunSelection) -> String {
+    // the statuses and the current time come first
+    let mut param = 4;
+    let mut categories = |game_categories: &[(String, String)]| {
+        if game_categories.is_empty() {
+            return "false".to_string();
+        }
+        game_categories
+            .iter()
+            .map(|_| {
+                param += 2;
+                format!("(game_id = ${} AND category_id = ${})", param - 1, param)
+            })
+            .collect::<Vec<_>>()
+            .join(" OR ")
+    };
+    let auto = categories(&selection.auto);
+    let manual_only = categories(&selection.manual_only);
+    let conditions = format!("({}) OR (priority > 0 AND ({}))", auto, manual_only);
+
+    format!(
+        r#"
+        WHERE (
+            (status = $1 AND (lease_expires_at IS NULL OR lease_expires_at <= $4) AND ({}))
+            OR (status = $2 AND next_retry_at IS NOT NULL AND next_retry_at <= $4 AND ({}))
+            OR (status = $3 AND ({}))
+        )
+        ORDER BY
+            priority DESC,
+            CASE
+                WHEN status = $1 THEN 0
+                WHEN status = $2 THEN 1
+                WHEN status = $3 THEN 2
+            END,
+            submitted_date ASC
+        LIMIT 1
+        "#,
+        conditions, conditions, conditions
+    )
+}
+
+fn bind_next_run_filter<'q>(
+    mut query: Query<'q, Any, AnyArguments<'q>>,
+    selection: &'q RunSelection,
+) -> Query<'q, Any, AnyArguments<'q>> {
+    query = query
+        .bind(RunStatus::Processing)
+        .bind(RunStatus::Error)
+        .bind(RunStatus::Discovered)
+        .bind(timestamp(Utc::now()));
+    for (game_id, cat_id) in selection.auto.iter().chain(&selection.manual_only) {
+        query = query.bind(game_id).bind(cat_id);
+    }
+    query
+}
+
+fn run_details_from_row(row: &AnyRow) -> Result<RunDetails> {
+    let variable_values: String = row.try_get("variable_values")?;
+    let leaderboard_place: Option<i64> = row.try_get("leaderboard_place")?;
+    Ok(RunDetails {
+        player_names: row.try_get("player_names")?,
+        run_time_secs: row.try_get("run_time_secs")?,
+        platform: row.try_get("platform")?,
+        variable_values: serde_json::from_str(&variable_values)?,
+        leaderboard_place: leaderboard_place.map(|place| place as u32),
+    })
+}
+
+fn run_from_row(r: &AnyRow) -> Result<Run> {
+    Ok(Run {
+        run_id: r.try_get("run_id")?,
+        game_id: r.try_get("game_id")?,
+        category_id: r.try_get("category_id")?,
+        submitted_date: get_timestamp(r, "submitted_date")?,
+        status: r.try_get("status")?,
+        error_message: r.try_get("error_message")?,
+        retry_count: r.try_get::<i64, _>("retry_count")? as u32,
+        next_retry_at: get_optional_timestamp(r, "next_retry_at")?,
+        error_class: r.try_get("error_class")?,
+        created_at: get_timestamp(r, "created_at")?,
+        updated_at: get_timestamp(r, "updated_at")?,
+        bot_notified: r.try_get::<i64, _>("bot_notified")? != 0,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use factorio_manager::factorio_install_dir::VersionStr;
 
     #[tokio::test]
     async fn test_insert_and_get_run() {
@@ -685,6 +1982,81 @@ mod tests {
         assert_eq!(run.error_class, None);
     }
 
+    #[tokio::test]
+    async fn test_run_details_round_trip() {
+        let db = Database::in_memory().await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new("run1", "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+        assert_eq!(db.get_run_details("run1").await.unwrap(), None);
+
+        let details = RunDetails {
+            player_names: Some("alice, bob".to_string()),
+            run_time_secs: Some(5400.5),
+            platform: Some("PC".to_string()),
+            variable_values: [("var1".to_string(), "val1".to_string())].into(),
+            leaderboard_place: Some(3),
+        };
+        db.insert_run(
+            NewRun::new("run2", "game1", "cat1", submitted_date).with_details(details.clone()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            db.get_run_details("run2").await.unwrap(),
+            Some(details.clone())
+        );
+
+        let all = db
+            .get_runs_details(&["run1", "run2", "unknown"])
+            .await
+            .unwrap();
+        assert_eq!(all, HashMap::from([("run2".to_string(), details)]));
+        assert!(db.get_runs_details(&[]).await.unwrap().is_empty());
+
+        let hour_ago = Utc::now() - chrono::TimeDelta::hours(1);
+        assert!(
+            db.get_run_details_since("run2", hour_ago)
+                .await
+                .unwrap()
+                .is_some()
+        );
+        let in_an_hour = Utc::now() + chrono::TimeDelta::hours(1);
+        assert_eq!(
+            db.get_run_details_since("run2", in_an_hour).await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_name_cache_remembers_missing_ids() {
+        let db = Database::in_memory().await.unwrap();
+        assert_eq!(
+            db.get_cached_name(NameKind::Game, "game1").await.unwrap(),
+            None
+        );
+
+        db.cache_missing_name(NameKind::Game, "game1")
+            .await
+            .unwrap();
+        let cached = db.get_cached_name(NameKind::Game, "game1").await.unwrap();
+        assert_eq!(cached.unwrap().name, None);
+        // a category with the same ID is unaffected
+        assert_eq!(
+            db.get_cached_name(NameKind::Category, "game1")
+                .await
+                .unwrap(),
+            None
+        );
+
+        db.cache_name(NameKind::Game, "game1", "Factorio")
+            .await
+            .unwrap();
+        let cached = db.get_cached_name(NameKind::Game, "game1").await.unwrap();
+        assert_eq!(cached.unwrap().name.as_deref(), Some("Factorio"));
+    }
+
     #[tokio::test]
     async fn test_update_run_status() {
         let db = Database::in_memory().await.unwrap();
@@ -702,8 +2074,25 @@ mod tests {
         assert_eq!(run.status, RunStatus::Passed);
     }
 
+    /// The run [`Database::claim_next_run`] would pick next, without claiming it.
+    async fn peek_next_run(db: &Database, allowed: &[(String, String)]) -> Result<Option<Run>> {
+        let selection = RunSelection {
+            auto: allowed.to_vec(),
+            ..Default::default()
+        };
+        let query_str = format!(
+            "SELECT {} FROM runs {}",
+            RUN_COLUMNS,
+            next_run_filter(&selection)
+        );
+        let row = bind_next_run_filter(sqlx::query(&query_str), &selection)
+            .fetch_optional(db.pool())
+            .await?;
+        row.as_ref().map(run_from_row).transpose()
+    }
+
     #[tokio::test]
-    async fn test_get_next_run_to_process() {
+    async fn test_peek_next_run() {
         let db = Database::in_memory().await.unwrap();
 
         db.insert_run(NewRun::new(
@@ -732,11 +2121,11 @@ mod tests {
         .unwrap();
 
         let allowed = vec![("game_id_1".to_string(), "cat_id_1".to_string())];
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap().unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
         assert_eq!(next_run.run_id, "run2");
 
         let filtered_out = vec![("game_id_1".to_string(), "cat_id_2".to_string())];
-        let no_run = db.get_next_run_to_process(&filtered_out).await.unwrap();
+        let no_run = peek_next_run(&db, &filtered_out).await.unwrap();
         assert!(no_run.is_none());
     }
 
@@ -938,7 +2327,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_get_next_run_to_process_with_retry_eligible() {
+    async fn test_peek_next_run_with_retry_eligible() {
         let db = Database::in_memory().await.unwrap();
 
         let old_date = "2024-01-01T00:00:00Z".parse().unwrap();
@@ -959,13 +2348,13 @@ mod tests {
             .unwrap();
 
         let allowed = vec![("game1".to_string(), "cat1".to_string())];
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap().unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
 
         assert_eq!(next_run.run_id, "run_old");
     }
 
     #[tokio::test]
-    async fn test_get_next_run_to_process_retry_not_yet_ready() {
+    async fn test_peek_next_run_retry_not_yet_ready() {
         let db = Database::in_memory().await.unwrap();
 
         let old_date = "2024-01-01T00:00:00Z".parse().unwrap();
@@ -986,13 +2375,13 @@ mod tests {
             .unwrap();
 
         let allowed = vec![("game1".to_string(), "cat1".to_string())];
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap().unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
 
         assert_eq!(next_run.run_id, "run_new");
     }
 
     #[tokio::test]
-    async fn test_get_next_run_to_process_ordering() {
+    async fn test_peek_next_run_ordering() {
         let db = Database::in_memory().await.unwrap();
 
         db.insert_run(NewRun::new(
@@ -1029,13 +2418,264 @@ mod tests {
             .unwrap();
 
         let allowed = vec![("game1".to_string(), "cat1".to_string())];
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap().unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
+
+        assert_eq!(next_run.run_id, "run_2024_01_01");
+    }
+
+    #[tokio::test]
+    async fn test_claim_next_run_is_exclusive() {
+        let db = Database::in_memory().await.unwrap();
+        for (run_id, date) in [
+            ("run1", "2024-01-01T00:00:00Z"),
+            ("run2", "2024-01-02T00:00:00Z"),
+        ] {
+            db.insert_run(NewRun::new(run_id, "game1", "cat1", date.parse().unwrap()))
+                .await
+                .unwrap();
+        }
+
+        let selection = RunSelection {
+            auto: vec![("game1".to_string(), "cat1".to_string())],
+            ..Default::default()
+        };
+        let lease = Utc::now() + chrono::Duration::minutes(10);
+        let first = db
+            .claim_next_run(&selection, "w0", lease)
+            .await
+            .unwrap()
+            .unwrap();
+        let second = db
+            .claim_next_run(&selection, "w1", lease)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.run_id, "run1");
+        assert_eq!(first.status, RunStatus::Processing);
+        assert_eq!(second.run_id, "run2");
+        assert!(
+            db.claim_next_run(&selection, "w2", lease)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        assert!(db.renew_lease("run1", "w0", lease).await.unwrap());
+        assert!(!db.renew_lease("run1", "w1", lease).await.unwrap());
+
+        // a released or expired lease makes a processing run claimable again
+        db.release_lease("run1", "w0").await.unwrap();
+        let reclaimed = db
+            .claim_next_run(&selection, "w2", lease)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reclaimed.run_id, "run1");
+
+        let expired = Utc::now() - chrono::Duration::minutes(1);
+        assert!(db.renew_lease("run2", "w1", expired).await.unwrap());
+        let reclaimed = db
+            .claim_next_run(&selection, "w3", lease)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reclaimed.run_id, "run2");
+        assert!(!db.renew_lease("run2", "w1", lease).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_claim_run_respects_leases() {
+        let db = Database::in_memory().await.unwrap();
+        db.insert_run(NewRun::new(
+            "run1",
+            "game1",
+            "cat1",
+            "2024-01-01T00:00:00Z".parse().unwrap(),
+        ))
+        .await
+        .unwrap();
+        db.mark_run_failed("run1", Some("used editor"))
+            .await
+            .unwrap();
+
+        let lease = Utc::now() + chrono::Duration::minutes(10);
+        let run = db
+            .claim_run("run1", "reverify", lease)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(run.status, RunStatus::Processing);
+        assert!(db.renew_lease("run1", "reverify", lease).await.unwrap());
+
+        let held = db.claim_run("run1", "host/0", lease).await.unwrap();
+        assert_eq!(held.unwrap_err(), "reverify");
+        let selection = RunSelection {
+            auto: vec![("game1".to_string(), "cat1".to_string())],
+            ..Default::default()
+        };
+        assert!(
+            db.claim_next_run(&selection, "host/0", lease)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let expired = Utc::now() - chrono::Duration::minutes(1);
+        assert!(db.renew_lease("run1", "reverify", expired).await.unwrap());
+        assert!(db.claim_run("run1", "host/0", lease).await.unwrap().is_ok());
+        assert!(db.claim_run("missing", "host/0", lease).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reset_run_respects_leases() {
+        let db = Database::in_memory().await.unwrap();
+        db.insert_run(NewRun::new(
+            "run1",
+            "game1",
+            "cat1",
+            "2024-01-01T00:00:00Z".parse().unwrap(),
+        ))
+        .await
+        .unwrap();
+        let lease = Utc::now() + chrono::Duration::minutes(10);
+        db.claim_run("run1", "host/0", lease)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let held = db.reset_run("run1", true).await.unwrap();
+        assert_eq!(held.unwrap_err(), "host/0");
+        let run = db.get_run("run1").await.unwrap().unwrap();
+        assert_eq!(run.status, RunStatus::Processing);
+
+        let expired = Utc::now() - chrono::Duration::minutes(1);
+        assert!(db.renew_lease("run1", "host/0", expired).await.unwrap());
+        assert!(db.reset_run("run1", true).await.unwrap().is_ok());
+        let run = db.get_run("run1").await.unwrap().unwrap();
+        assert_eq!(run.status, RunStatus::Discovered);
+        // the stale claim is gone, so the old worker can't write a verdict over the reset
+        assert!(!db.renew_lease("run1", "host/0", lease).await.unwrap());
+        assert!(
+            !db.update_claimed_run_status("run1", "host/0", RunStatus::Passed, None)
+                .await
+                .unwrap()
+        );
+        assert!(db.reset_run("missing", true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_claim_manual_only_requires_priority() {
+        let db = Database::in_memory().await.unwrap();
+        db.insert_run(NewRun::new(
+            "manual",
+            "game1",
+            "cat1",
+            "2024-01-01T00:00:00Z".parse().unwrap(),
+        ))
+        .await
+        .unwrap();
+
+        let selection = RunSelection {
+            manual_only: vec![("game1".to_string(), "cat1".to_string())],
+            ..Default::default()
+        };
+        let lease = Utc::now() + chrono::Duration::minutes(10);
+        let run = db.claim_next_run(&selection, "w0", lease).await.unwrap();
+        assert!(run.is_none());
+
+        db.set_run_priority("manual", 1).await.unwrap();
+        let run = db.claim_next_run(&selection, "w0", lease).await.unwrap();
+        assert_eq!(run.unwrap().run_id, "manual");
+    }
+
+    #[tokio::test]
+    async fn test_daemon_shutdown_marker() {
+        let db = Database::in_memory().await.unwrap();
+        assert_eq!(db.record_daemon_start("host").await.unwrap(), None);
+        assert_eq!(db.record_daemon_start("host").await.unwrap(), Some(false));
+        db.record_clean_shutdown("host").await.unwrap();
+        assert_eq!(db.record_daemon_start("host").await.unwrap(), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_recover_orphaned_leases() {
+        let db = Database::in_memory().await.unwrap();
+        for run_id in ["own", "expired", "other"] {
+            db.insert_run(NewRun::new(
+                run_id,
+                "game1",
+                "cat1",
+                "2024-01-01T00:00:00Z".parse().unwrap(),
+            ))
+            .await
+            .unwrap();
+        }
+
+        let selection = RunSelection {
+            auto: vec![("game1".to_string(), "cat1".to_string())],
+            ..Default::default()
+        };
+        let lease = Utc::now() + chrono::Duration::minutes(10);
+        let mut claimed = HashMap::new();
+        for worker in ["host-a/0", "host-b/0", "host-b/1"] {
+            let run = db
+                .claim_next_run(&selection, worker, lease)
+                .await
+                .unwrap()
+                .unwrap();
+            claimed.insert(worker, run.run_id);
+        }
+        let expired = Utc::now() - chrono::Duration::minutes(1);
+        db.renew_lease(&claimed["host-b/0"], "host-b/0", expired)
+            .await
+            .unwrap();
+
+        let mut recovered = db
+            .recover_orphaned_leases(&["host-a/0".to_string()])
+            .await
+            .unwrap();
+        recovered.sort();
+        let mut expected = vec![claimed["host-a/0"].clone(), claimed["host-b/0"].clone()];
+        expected.sort();
+        assert_eq!(recovered, expected);
+
+        // the live lease of another instance is kept
+        assert!(
+            db.renew_lease(&claimed["host-b/1"], "host-b/1", lease)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !db.renew_lease(&claimed["host-a/0"], "host-a/0", lease)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_peek_next_run_respects_priority() {
+        let db = Database::in_memory().await.unwrap();
+        for (run_id, date) in [
+            ("old", "2024-01-01T00:00:00Z"),
+            ("new", "2024-01-05T00:00:00Z"),
+        ] {
+            db.insert_run(NewRun::new(run_id, "game1", "cat1", date.parse().unwrap()))
+                .await
+                .unwrap();
+        }
+        let allowed = vec![("game1".to_string(), "cat1".to_string())];
 
-        assert_eq!(next_run.run_id, "run_2024_01_01");
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
+        assert_eq!(next_run.run_id, "old");
+
+        assert!(db.set_run_priority("new", 10).await.unwrap());
+        assert!(!db.set_run_priority("missing", 10).await.unwrap());
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
+        assert_eq!(next_run.run_id, "new");
     }
 
     #[tokio::test]
-    async fn test_get_next_run_to_process_prioritizes_processing_runs() {
+    async fn test_peek_next_run_prioritizes_processing_runs() {
         let db = Database::in_memory().await.unwrap();
 
         db.insert_run(NewRun::new(
@@ -1074,20 +2714,20 @@ mod tests {
             .unwrap();
 
         let allowed = vec![("game1".to_string(), "cat1".to_string())];
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap().unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
 
         assert_eq!(next_run.run_id, "run_processing_new");
         assert_eq!(next_run.status, RunStatus::Processing);
 
         db.mark_run_passed("run_processing_new").await.unwrap();
 
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap().unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
         assert_eq!(next_run.run_id, "run_error_ready");
         assert_eq!(next_run.status, RunStatus::Error);
 
         db.mark_run_passed("run_error_ready").await.unwrap();
 
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap().unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
         assert_eq!(next_run.run_id, "run_discovered_old");
         assert_eq!(next_run.status, RunStatus::Discovered);
     }
@@ -1109,7 +2749,7 @@ mod tests {
         };
         let config = RetryConfig::default();
 
-        db.process_replay_result("run_retry_result", Err(error), &config)
+        db.process_replay_result("run_retry_result", None, Err(error), &config)
             .await
             .unwrap();
 
@@ -1137,7 +2777,7 @@ mod tests {
         };
         let config = RetryConfig::default();
 
-        db.process_replay_result("run_final", Err(error), &config)
+        db.process_replay_result("run_final", None, Err(error), &config)
             .await
             .unwrap();
 
@@ -1151,7 +2791,7 @@ mod tests {
     #[tokio::test]
     async fn test_process_replay_result_success_clears_retry() {
         use crate::daemon::retry::RetryConfig;
-        use replay_script::MsgLevel;
+        use replay_script::{MsgLevel, ProgressSnapshot};
 
         let db = Database::in_memory().await.unwrap();
 
@@ -1167,14 +2807,24 @@ mod tests {
             .await
             .unwrap();
 
+        let timeline = vec![ProgressSnapshot {
+            tick: 18000,
+            science_produced: 10,
+            rockets_launched: 0,
+            players: 1,
+        }];
         let report = ReplayReport {
             max_msg_level: MsgLevel::Info,
             win_condition_not_completed: false,
             messages: vec![],
+            timeline: timeline.clone(),
+            rule_counts: [("log_time".to_string(), 2)].into(),
+            final_tick: 18000,
+            ..Default::default()
         };
         let config = RetryConfig::default();
 
-        db.process_replay_result("run_success_clear", Ok(report), &config)
+        db.process_replay_result("run_success_clear", None, Ok(report), &config)
             .await
             .unwrap();
 
@@ -1183,6 +2833,341 @@ mod tests {
         assert_eq!(run.retry_count, 0);
         assert_eq!(run.next_retry_at, None);
         assert_eq!(run.error_class, None);
+        assert_eq!(
+            db.get_progress_timeline("run_success_clear").await.unwrap(),
+            timeline
+        );
+        let summary = db
+            .get_report_summary("run_success_clear")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(summary.rule_counts.get("log_time"), Some(&2));
+        assert_eq!(summary.final_tick, 18000);
+
+        let result = db
+            .get_replay_result("run_success_clear")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.max_msg_level, MsgLevel::Info);
+        assert_eq!(result.exit_tick, 18000);
+        assert!(result.win_condition_completed);
+        assert_eq!(result.rule_counts.get("log_time"), Some(&2));
+        assert_eq!(result.log_excerpt, None);
+    }
+
+    #[tokio::test]
+    async fn test_status_changes_are_recorded() {
+        let db = Database::in_memory().await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new("run1", "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+        let selection = RunSelection {
+            auto: vec![("game1".to_string(), "cat1".to_string())],
+            ..Default::default()
+        };
+        let lease_expires_at = Utc::now() + chrono::Duration::minutes(10);
+        db.claim_next_run(&selection, "host/0", lease_expires_at)
+            .await
+            .unwrap()
+            .unwrap();
+        db.mark_run_failed("run1", Some("used map editor"))
+            .await
+            .unwrap();
+        let admin = db.clone().with_actor("admin");
+        admin
+            .update_run_status("run1", RunStatus::Discovered, None)
+            .await
+            .unwrap();
+
+        let events = db.get_run_events("run1").await.unwrap();
+        let transitions: Vec<_> = events
+            .iter()
+            .map(|e| (e.old_status, e.new_status, e.actor.as_str()))
+            .collect();
+        assert_eq!(
+            transitions,
+            vec![
+                (None, RunStatus::Discovered, "daemon"),
+                (Some(RunStatus::Discovered), RunStatus::Processing, "daemon"),
+                (Some(RunStatus::Processing), RunStatus::Failed, "daemon"),
+                (Some(RunStatus::Failed), RunStatus::Discovered, "admin"),
+            ]
+        );
+        assert_eq!(events[1].reason.as_deref(), Some("claimed by host/0"));
+        assert_eq!(events[2].reason.as_deref(), Some("used map editor"));
+
+        db.update_run_status("missing", RunStatus::Passed, None)
+            .await
+            .unwrap();
+        assert!(db.get_run_events("missing").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_public_archive_url() {
+        let db = Database::in_memory().await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new("run1", "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+        assert_eq!(db.get_public_archive_url("run1").await.unwrap(), None);
+
+        db.store_public_archive_url("run1", "https://archive.org/details/old")
+            .await
+            .unwrap();
+        db.store_public_archive_url("run1", "https://archive.org/details/new")
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_public_archive_url("run1").await.unwrap().as_deref(),
+            Some("https://archive.org/details/new")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_progress() {
+        let db = Database::in_memory().await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        for (run_id, category_id) in [("run1", "cat1"), ("run2", "cat2")] {
+            db.insert_run(NewRun::new(run_id, "game1", category_id, submitted_date))
+                .await
+                .unwrap();
+        }
+        let selection = RunSelection {
+            auto: vec![("game1".to_string(), "cat1".to_string())],
+            ..Default::default()
+        };
+        let lease = Utc::now() + chrono::Duration::minutes(10);
+        db.claim_next_run(&selection, "w0", lease)
+            .await
+            .unwrap()
+            .unwrap();
+
+        db.record_run_progress("run1", 600, Some(10.0))
+            .await
+            .unwrap();
+        db.record_run_progress("run1", 1200, Some(20.0))
+            .await
+            .unwrap();
+        // only runs being processed are reported
+        db.record_run_progress("run2", 60, None).await.unwrap();
+        let progress = db.processing_progress().await.unwrap();
+        assert_eq!(progress.len(), 1);
+        assert_eq!(progress[0].run_id, "run1");
+        assert_eq!(progress[0].tick, 1200);
+        assert_eq!(progress[0].percent, Some(20.0));
+
+        // claiming the run again starts over
+        db.release_lease("run1", "w0").await.unwrap();
+        db.claim_next_run(&selection, "w1", lease)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(db.processing_progress().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_checkpoints() {
+        let db = Database::in_memory().await.unwrap();
+        db.insert_run(NewRun::new(
+            "run1",
+            "game1",
+            "cat1",
+            "2024-01-01T00:00:00Z".parse().unwrap(),
+        ))
+        .await
+        .unwrap();
+        assert!(db.get_checkpoint("run1").await.unwrap().is_none());
+
+        db.record_checkpoint("run1", "runs/run1/checkpoint.zip", None)
+            .await
+            .unwrap();
+        db.record_checkpoint("run1", "runs/run1/checkpoint.zip", Some(216000))
+            .await
+            .unwrap();
+        let checkpoint = db.get_checkpoint("run1").await.unwrap().unwrap();
+        assert_eq!(checkpoint.save_path, "runs/run1/checkpoint.zip");
+        assert_eq!(checkpoint.tick, Some(216000));
+
+        db.delete_runs(&["run1".to_string()]).await.unwrap();
+        assert!(db.get_checkpoint("run1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_replay_throughput_and_queue() {
+        let db = Database::in_memory().await.unwrap();
+        for (run_id, date) in [
+            ("run1", "2024-01-01T00:00:00Z"),
+            ("run2", "2024-01-02T00:00:00Z"),
+            ("run3", "2024-01-03T00:00:00Z"),
+        ] {
+            db.insert_run(NewRun::new(run_id, "game1", "cat1", date.parse().unwrap()))
+                .await
+                .unwrap();
+        }
+        db.set_run_priority("run3", 1).await.unwrap();
+        let queued: Vec<_> = db
+            .queued_runs()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|run| run.run_id)
+            .collect();
+        assert_eq!(queued, ["run3", "run1", "run2"]);
+
+        let report = |version, final_tick, duration_secs| ReplayReport {
+            // the save's version doesn't matter, only the one it ran on
+            factorio_version: Some(VersionStr::new(2, 0, 60)),
+            install_version: version,
+            final_tick,
+            duration_secs,
+            ..Default::default()
+        };
+        let version = Some(VersionStr::new(2, 0, 65));
+        db.record_replay_throughput("run1", "host1", &report(version, 6000, 20.0))
+            .await
+            .unwrap();
+        // a later replay of the same run replaces the earlier one
+        db.record_replay_throughput("run1", "host1", &report(version, 6000, 10.0))
+            .await
+            .unwrap();
+        db.record_replay_throughput("run2", "host1", &report(version, 3000, 5.0))
+            .await
+            .unwrap();
+        db.record_replay_throughput("run3", "host1", &report(None, 3000, 5.0))
+            .await
+            .unwrap();
+
+        let throughput = db.replay_throughput().await.unwrap();
+        assert_eq!(throughput.len(), 1);
+        assert_eq!(throughput[0].factorio_version, "2.0.65");
+        assert_eq!(throughput[0].replays, 2);
+        assert_eq!(throughput[0].ticks, 9000);
+        assert_eq!(throughput[0].ticks_per_sec(), 600.0);
+
+        // running and retrying runs are queued too, running ones first
+        db.update_run_status("run2", RunStatus::Processing, None)
+            .await
+            .unwrap();
+        db.record_run_progress("run2", 1200, None).await.unwrap();
+        db.update_run_status("run1", RunStatus::Error, Some("timeout"))
+            .await
+            .unwrap();
+        db.schedule_retry("run1", 1, "retryable", Utc::now())
+            .await
+            .unwrap();
+        let queued = db.queued_runs().await.unwrap();
+        let order: Vec<_> = queued.iter().map(|run| run.run_id.as_str()).collect();
+        assert_eq!(order, ["run2", "run3", "run1"]);
+        assert_eq!(queued[0].replayed_ticks, 1200);
+        assert_eq!(queued[0].factorio_version.as_deref(), Some("2.0.65"));
+        assert_eq!(queued[1].factorio_version, None);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_saves() {
+        let db = Database::in_memory().await.unwrap();
+        for (run_id, category_id, date) in [
+            ("run1", "cat1", "2024-01-01T00:00:00Z"),
+            ("run2", "cat2", "2024-02-01T00:00:00Z"),
+            ("run3", "cat1", "2024-03-01T00:00:00Z"),
+        ] {
+            db.insert_run(NewRun::new(
+                run_id,
+                "game1",
+                category_id,
+                date.parse().unwrap(),
+            ))
+            .await
+            .unwrap();
+        }
+        let hashes = |hashes: &[&str]| hashes.iter().map(|h| h.to_string()).collect::<Vec<_>>();
+        db.store_save_hashes("run1", &hashes(&["aaa", "bbb"]))
+            .await
+            .unwrap();
+        db.store_save_hashes("run3", &hashes(&["ccc"]))
+            .await
+            .unwrap();
+        assert!(db.find_duplicate_saves(None).await.unwrap().is_empty());
+
+        // re-verifying a run replaces its hashes instead of matching itself
+        db.store_save_hashes("run1", &hashes(&["aaa", "bbb"]))
+            .await
+            .unwrap();
+        db.store_save_hashes("run2", &hashes(&["bbb"]))
+            .await
+            .unwrap();
+        let duplicates = db.find_duplicate_saves(None).await.unwrap();
+        let run_ids: Vec<_> = duplicates.iter().map(|d| d.run_id.as_str()).collect();
+        assert_eq!(run_ids, ["run1", "run2"]);
+        assert!(duplicates.iter().all(|d| d.sha256 == "bbb"));
+        assert_eq!(duplicates[1].category_id, "cat2");
+        assert_eq!(duplicates[1].status, RunStatus::Discovered);
+
+        assert_eq!(
+            db.find_duplicate_saves(Some("run2")).await.unwrap(),
+            duplicates
+        );
+        assert!(
+            db.find_duplicate_saves(Some("run3"))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_artifact_cleanups() {
+        let db = Database::in_memory().await.unwrap();
+        assert_eq!(db.total_reclaimed_bytes().await.unwrap(), 0);
+        for bytes_reclaimed in [100, 250] {
+            let stats = CleanupStats {
+                runs_cleaned: 1,
+                jobs_cleaned: 0,
+                bytes_reclaimed,
+            };
+            db.record_artifact_cleanup(&stats).await.unwrap();
+        }
+        assert_eq!(db.total_reclaimed_bytes().await.unwrap(), 350);
+    }
+
+    #[tokio::test]
+    async fn test_store_replay_result_keeps_log_excerpt() {
+        use replay_script::MsgLevel;
+
+        let db = Database::in_memory().await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new("run1", "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("output.log");
+        let log: Vec<String> = (1..=60).map(|i| format!("line {}", i)).collect();
+        std::fs::write(&log_path, log.join("\n")).unwrap();
+        let report = ReplayReport {
+            max_msg_level: MsgLevel::Warn,
+            win_condition_not_completed: true,
+            log_path: Some(log_path.clone()),
+            ..Default::default()
+        };
+        db.store_replay_result("run1", &report).await.unwrap();
+
+        let result = db.get_replay_result("run1").await.unwrap().unwrap();
+        assert_eq!(result.max_msg_level, MsgLevel::Warn);
+        assert!(!result.win_condition_completed);
+        let excerpt = result.log_excerpt.unwrap();
+        assert!(excerpt.starts_with("line 11\n"));
+        assert!(excerpt.ends_with("line 60"));
+        assert_eq!(
+            result.report_path,
+            Some(dir.path().join("report.json").display().to_string())
+        );
+
+        db.delete_runs(&["run1".to_string()]).await.unwrap();
+        assert_eq!(db.get_replay_result("run1").await.unwrap(), None);
     }
 
     #[tokio::test]
@@ -1203,7 +3188,7 @@ mod tests {
         };
         let config = RetryConfig::default();
 
-        db.process_replay_result("run_e2e", Err(error), &config)
+        db.process_replay_result("run_e2e", None, Err(error), &config)
             .await
             .unwrap();
 
@@ -1214,7 +3199,7 @@ mod tests {
         assert_eq!(run.error_class, Some("retryable".to_string()));
 
         let allowed = vec![("game1".to_string(), "cat1".to_string())];
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap();
         assert!(next_run.is_none());
 
         let past_time = Utc::now() - chrono::Duration::hours(1);
@@ -1222,15 +3207,16 @@ mod tests {
             .await
             .unwrap();
 
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap().unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
         assert_eq!(next_run.run_id, "run_e2e");
 
         let report = ReplayReport {
             max_msg_level: MsgLevel::Info,
             win_condition_not_completed: false,
             messages: vec![],
+            ..Default::default()
         };
-        db.process_replay_result("run_e2e", Ok(report), &config)
+        db.process_replay_result("run_e2e", None, Ok(report), &config)
             .await
             .unwrap();
 
@@ -1264,7 +3250,7 @@ mod tests {
                 message: format!("Failure attempt {}", attempt + 1),
             };
 
-            db.process_replay_result("run_max_attempts", Err(error), &config)
+            db.process_replay_result("run_max_attempts", None, Err(error), &config)
                 .await
                 .unwrap();
 
@@ -1285,7 +3271,7 @@ mod tests {
         assert_eq!(run.next_retry_at, None);
 
         let allowed = vec![("game1".to_string(), "cat1".to_string())];
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap();
         assert!(next_run.is_none());
     }
 
@@ -1310,7 +3296,7 @@ mod tests {
         };
         let config = RetryConfig::default();
 
-        db.process_replay_result("run_rate_limited", Err(error), &config)
+        db.process_replay_result("run_rate_limited", None, Err(error), &config)
             .await
             .unwrap();
 
@@ -1390,7 +3376,7 @@ mod tests {
         db.mark_run_passed("run1").await.unwrap();
 
         let filter = RunFilter {
-            status: Some(RunStatus::Passed),
+            statuses: vec![RunStatus::Passed],
             limit: Some(10),
             ..Default::default()
         };
@@ -1399,6 +3385,105 @@ mod tests {
         assert_eq!(runs[0].run_id, "run1");
     }
 
+    #[tokio::test]
+    async fn test_query_runs_with_advanced_filters() {
+        let db = Database::in_memory().await.unwrap();
+        for (i, run_id) in ["run1", "run2", "run3", "run4"].iter().enumerate() {
+            let submitted_date = format!("2024-01-0{}T00:00:00Z", i + 1).parse().unwrap();
+            db.insert_run(NewRun::new(*run_id, "game1", "cat1", submitted_date))
+                .await
+                .unwrap();
+        }
+        db.mark_run_failed("run1", Some("Used the Map Editor"))
+            .await
+            .unwrap();
+        db.mark_run_needs_review("run2", Some("map changed; 100% done"))
+            .await
+            .unwrap();
+        db.mark_run_error("run3", "editor crashed").await.unwrap();
+        db.schedule_retry("run3", 2, "retryable", Utc::now())
+            .await
+            .unwrap();
+        db.set_bot_notified("run4", true).await.unwrap();
+
+        let run_ids = |runs: Vec<Run>| -> Vec<String> {
+            let mut ids: Vec<String> = runs.into_iter().map(|r| r.run_id).collect();
+            ids.sort();
+            ids
+        };
+        let query = |filter: RunFilter| {
+            let db = db.clone();
+            async move { run_ids(db.query_runs(filter).await.unwrap()) }
+        };
+
+        assert_eq!(
+            query(RunFilter {
+                statuses: vec![RunStatus::Failed, RunStatus::NeedsReview],
+                ..Default::default()
+            })
+            .await,
+            ["run1", "run2"]
+        );
+        assert_eq!(
+            query(RunFilter {
+                search: Some("map EDITOR".to_string()),
+                ..Default::default()
+            })
+            .await,
+            ["run1"]
+        );
+        assert_eq!(
+            query(RunFilter {
+                search: Some("100%".to_string()),
+                ..Default::default()
+            })
+            .await,
+            ["run2"]
+        );
+        assert_eq!(
+            query(RunFilter {
+                search: Some("0%".to_string()),
+                ..Default::default()
+            })
+            .await,
+            ["run2"]
+        );
+        assert!(
+            query(RunFilter {
+                search: Some("1_0".to_string()),
+                ..Default::default()
+            })
+            .await
+            .is_empty()
+        );
+        assert_eq!(
+            query(RunFilter {
+                min_retries: Some(1),
+                max_retries: Some(2),
+                ..Default::default()
+            })
+            .await,
+            ["run3"]
+        );
+        assert_eq!(
+            query(RunFilter {
+                bot_notified: Some(true),
+                ..Default::default()
+            })
+            .await,
+            ["run4"]
+        );
+        assert_eq!(
+            query(RunFilter {
+                since_date: Some("2024-01-02T00:00:00Z".parse().unwrap()),
+                before_date: Some("2024-01-04T00:00:00Z".parse().unwrap()),
+                ..Default::default()
+            })
+            .await,
+            ["run2", "run3"]
+        );
+    }
+
     #[tokio::test]
     async fn test_query_runs_with_game_category_filter() {
         let db = Database::in_memory().await.unwrap();
@@ -1463,6 +3548,43 @@ mod tests {
         assert_eq!(runs.len(), 2);
         assert_eq!(runs[0].run_id, "run4");
         assert_eq!(runs[1].run_id, "run3");
+
+        let filter = RunFilter {
+            after: Some((runs[1].submitted_date, runs[1].run_id.clone())),
+            ..Default::default()
+        };
+        let rest: Vec<_> = db
+            .query_runs(filter)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|run| run.run_id)
+            .collect();
+        assert_eq!(rest, ["run2", "run1"]);
+    }
+
+    #[tokio::test]
+    async fn test_query_runs_after_breaks_date_ties_by_run_id() {
+        let db = Database::in_memory().await.unwrap();
+        for run_id in ["b", "a", "c"] {
+            db.insert_run(NewRun::new(
+                run_id,
+                "game1",
+                "cat1",
+                "2024-01-01T00:00:00Z".parse().unwrap(),
+            ))
+            .await
+            .unwrap();
+        }
+
+        let date = "2024-01-01T00:00:00Z".parse().unwrap();
+        let filter = RunFilter {
+            after: Some((date, "a".to_string())),
+            ..Default::default()
+        };
+        let runs = db.query_runs(filter).await.unwrap();
+        let run_ids: Vec<_> = runs.iter().map(|run| run.run_id.as_str()).collect();
+        assert_eq!(run_ids, ["b", "c"]);
     }
 
     #[tokio::test]
@@ -1505,7 +3627,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_get_next_run_to_process_category_filtering() {
+    async fn test_peek_next_run_category_filtering() {
         let db = Database::in_memory().await.unwrap();
 
         db.insert_run(NewRun::new(
@@ -1537,27 +3659,23 @@ mod tests {
             ("game1".to_string(), "cat1".to_string()),
             ("game2".to_string(), "cat1".to_string()),
         ];
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap().unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
         assert_eq!(next_run.run_id, "run_cat1");
 
         db.mark_run_processing("run_cat1").await.unwrap();
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap().unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
         assert_eq!(next_run.run_id, "run_cat1");
 
         db.mark_run_passed("run_cat1").await.unwrap();
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap().unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap().unwrap();
         assert_eq!(next_run.run_id, "run_game2_cat1");
 
         db.mark_run_passed("run_game2_cat1").await.unwrap();
-        let next_run = db.get_next_run_to_process(&allowed).await.unwrap();
+        let next_run = peek_next_run(&db, &allowed).await.unwrap();
         assert!(next_run.is_none());
 
         let only_cat2 = vec![("game1".to_string(), "cat2".to_string())];
-        let next_run = db
-            .get_next_run_to_process(&only_cat2)
-            .await
-            .unwrap()
-            .unwrap();
+        let next_run = peek_next_run(&db, &only_cat2).await.unwrap().unwrap();
         assert_eq!(next_run.run_id, "run_cat2");
     }
 
@@ -1912,6 +4030,86 @@ mod tests {
         assert!(run3.is_some());
     }
 
+    #[tokio::test]
+    async fn test_record_review() {
+        let db = Database::in_memory().await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new("run1", "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+        assert!(
+            !db.record_review("run1", ReviewDecision::Accepted, "alice", None)
+                .await
+                .unwrap()
+        );
+
+        db.mark_run_needs_review("run1", Some("used /editor"))
+            .await
+            .unwrap();
+        db.set_bot_notified("run1", true).await.unwrap();
+        assert!(
+            db.record_review(
+                "run1",
+                ReviewDecision::Rejected,
+                "alice",
+                Some("editor used mid-run")
+            )
+            .await
+            .unwrap()
+        );
+
+        let run = db.get_run("run1").await.unwrap().unwrap();
+        assert_eq!(run.status, RunStatus::Failed);
+        assert_eq!(run.error_message.as_deref(), Some("used /editor"));
+        assert!(!run.bot_notified);
+        let review = db.get_review("run1").await.unwrap().unwrap();
+        assert_eq!(review.decision, ReviewDecision::Rejected);
+        assert_eq!(review.reviewer, "alice");
+        assert_eq!(review.note.as_deref(), Some("editor used mid-run"));
+        let event = db.get_run_events("run1").await.unwrap().pop().unwrap();
+        assert_eq!(
+            event.reason.as_deref(),
+            Some("rejected by alice: editor used mid-run")
+        );
+
+        db.mark_run_processing("run1").await.unwrap();
+        assert_eq!(db.get_review("run1").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_reset_and_claim_clear_review() {
+        let db = Database::in_memory().await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new("run1", "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+        db.mark_run_needs_review("run1", None).await.unwrap();
+        db.record_review("run1", ReviewDecision::Accepted, "alice", None)
+            .await
+            .unwrap();
+
+        db.update_run_status("run1", RunStatus::Discovered, None)
+            .await
+            .unwrap();
+        assert_eq!(db.get_review("run1").await.unwrap(), None);
+
+        sqlx::query(
+            "INSERT INTO run_reviews (run_id, decision, reviewer, reviewed_at) \
+             VALUES ('run1', 'accepted', 'alice', '2024-01-02T00:00:00Z')",
+        )
+        .execute(db.pool())
+        .await
+        .unwrap();
+        let selection = RunSelection {
+            auto: vec![("game1".to_string(), "cat1".to_string())],
+            ..Default::default()
+        };
+        let lease = Utc::now() + chrono::Duration::minutes(10);
+        let claimed = db.claim_next_run(&selection, "w0", lease).await.unwrap();
+        assert_eq!(claimed.unwrap().run_id, "run1");
+        assert_eq!(db.get_review("run1").await.unwrap(), None);
+    }
+
     #[tokio::test]
     async fn test_delete_runs_empty() {
         let db = Database::in_memory().await.unwrap();
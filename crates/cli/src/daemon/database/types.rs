@@ -1,8 +1,43 @@
 use chrono::{DateTime, Utc};
+use replay_script::MsgLevel;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sqlx::Database;
+use sqlx::any::{Any, AnyTypeInfo, AnyValueRef};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use std::collections::BTreeMap;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
-#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+use crate::run_replay::{ReplayReport, ReportSummary};
+
+/// Stores the enums as their snake_case names, which are also their serde names.
+macro_rules! text_type {
+    ($($ty:ty),*) => {$(
+        impl sqlx::Type<Any> for $ty {
+            fn type_info() -> AnyTypeInfo {
+                <str as sqlx::Type<Any>>::type_info()
+            }
+        }
+
+        impl<'q> sqlx::Encode<'q, Any> for $ty {
+            fn encode_by_ref(&self, buf: &mut <Any as Database>::ArgumentBuffer<'q>) -> Result<IsNull, BoxDynError> {
+                <&str as sqlx::Encode<'q, Any>>::encode(self.as_str(), buf)
+            }
+        }
+
+        impl<'r> sqlx::Decode<'r, Any> for $ty {
+            fn decode(value: AnyValueRef<'r>) -> Result<Self, BoxDynError> {
+                let name = <String as sqlx::Decode<'r, Any>>::decode(value)?;
+                Ok(serde_json::from_value(serde_json::Value::String(name))?)
+            }
+        }
+    )*};
+}
+
+text_type!(RunStatus, ReviewDecision, JobStatus, JobSource);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub enum RunStatus {
     Discovered,
     Processing,
@@ -12,20 +47,77 @@ pub enum RunStatus {
     Error,
 }
 
+impl RunStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RunStatus::Discovered => "discovered",
+            RunStatus::Processing => "processing",
+            RunStatus::Passed => "passed",
+            RunStatus::NeedsReview => "needs_review",
+            RunStatus::Failed => "failed",
+            RunStatus::Error => "error",
+        }
+    }
+
+    /// The status of a run whose replay produced `report`.
+    pub fn for_report(report: &ReplayReport) -> Self {
+        if report.win_condition_not_completed {
+            return RunStatus::Failed;
+        }
+        match report.verdict_level() {
+            MsgLevel::Debug | MsgLevel::Info => RunStatus::Passed,
+            MsgLevel::Warn => RunStatus::NeedsReview,
+            MsgLevel::Error | MsgLevel::Critical => RunStatus::Failed,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct RunFilter {
-    pub status: Option<RunStatus>,
+    /// Matches any of these; empty matches all
+    pub statuses: Vec<RunStatus>,
     pub game_id: Option<String>,
     pub category_id: Option<String>,
     pub since_date: Option<DateTime<Utc>>,
     pub before_date: Option<DateTime<Utc>>,
     pub error_class: Option<String>,
     pub error_reason: Option<String>,
+    pub min_retries: Option<u32>,
+    pub max_retries: Option<u32>,
+    pub bot_notified: Option<bool>,
+    /// Whitespace-separated words that must all appear in the error message (case-insensitive)
+    pub search: Option<String>,
+    pub order: RunOrder,
+    /// Only runs after this `(submitted_date, run_id)` in [`RunOrder::Submitted`], to page
+    /// through results without an offset.
+    pub after: Option<(DateTime<Utc>, String)>,
     pub limit: Option<u32>,
     pub offset: u32,
 }
 
-#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+/// Sort order of query results; newest first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RunOrder {
+    #[default]
+    Submitted,
+    Updated,
+}
+
+/// Game/categories the processor picks runs from.
+#[derive(Debug, Clone, Default)]
+pub struct RunSelection {
+    pub auto: Vec<(String, String)>,
+    /// Runs from these are only picked once given a priority above 0.
+    pub manual_only: Vec<(String, String)>,
+}
+
+impl RunSelection {
+    pub fn is_empty(&self) -> bool {
+        self.auto.is_empty() && self.manual_only.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 #[allow(dead_code)]
 pub struct Run {
     pub run_id: String,
@@ -42,12 +134,185 @@ pub struct Run {
     pub bot_notified: bool,
 }
 
+impl Run {
+    /// Whether the run has a verdict; errors scheduled for retry are not final yet.
+    pub fn is_final(&self) -> bool {
+        match self.status {
+            RunStatus::Discovered | RunStatus::Processing => false,
+            RunStatus::Error => self.next_retry_at.is_none(),
+            RunStatus::Passed | RunStatus::NeedsReview | RunStatus::Failed => true,
+        }
+    }
+}
+
+/// A recorded status change of a run.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunEvent {
+    pub id: i64,
+    pub run_id: String,
+    pub old_status: Option<RunStatus>,
+    pub new_status: RunStatus,
+    pub reason: Option<String>,
+    pub actor: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A reviewer's decision on a run that needed review.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewDecision {
+    Accepted,
+    Rejected,
+}
+
+impl ReviewDecision {
+    /// The status the run gets.
+    pub fn status(self) -> RunStatus {
+        match self {
+            ReviewDecision::Accepted => RunStatus::Passed,
+            ReviewDecision::Rejected => RunStatus::Failed,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReviewDecision::Accepted => "accepted",
+            ReviewDecision::Rejected => "rejected",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Review {
+    pub decision: ReviewDecision,
+    pub reviewer: String,
+    pub note: Option<String>,
+    pub reviewed_at: DateTime<Utc>,
+}
+
+/// Outcome details of a run's latest replay.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReplayResult {
+    pub run_id: String,
+    pub max_msg_level: MsgLevel,
+    pub exit_tick: u64,
+    pub win_condition_completed: bool,
+    pub rule_counts: BTreeMap<String, usize>,
+    pub log_path: Option<String>,
+    pub report_path: Option<String>,
+    pub log_excerpt: Option<String>,
+    pub completed_at: DateTime<Utc>,
+}
+
+/// How far the replay of a run has gotten.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunProgress {
+    pub run_id: String,
+    /// Latest tick the replay scripts reported.
+    pub tick: u64,
+    /// Estimated from the run's time on speedrun.com, if known.
+    pub percent: Option<f64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The latest autosave of a run's replay, kept as a checkpoint.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Checkpoint {
+    pub save_path: String,
+    /// Latest tick the replay scripts had reported, if known.
+    pub tick: Option<u64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Replay speed of the runs of one Factorio version replayed by one daemon instance.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReplayThroughput {
+    pub factorio_version: String,
+    pub host: String,
+    pub replays: u64,
+    pub ticks: u64,
+    pub duration_secs: f64,
+}
+
+impl ReplayThroughput {
+    pub fn ticks_per_sec(&self) -> f64 {
+        self.ticks as f64 / self.duration_secs
+    }
+}
+
+/// A run being processed or waiting to be, with its time on speedrun.com.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueuedRun {
+    pub run_id: String,
+    pub run_time_secs: Option<f64>,
+    /// Ticks replayed so far, if the run is being processed.
+    pub replayed_ticks: u64,
+    /// The Factorio version an earlier replay of the run ran on, if any.
+    pub factorio_version: Option<String>,
+}
+
+/// A run whose save was also downloaded for another run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicateSave {
+    pub sha256: String,
+    pub run_id: String,
+    pub game_id: String,
+    pub category_id: String,
+    pub player_names: Option<String>,
+    pub submitted_date: DateTime<Utc>,
+    pub status: RunStatus,
+}
+
+/// A run whose latest status a notifier still has to deliver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedNotification {
+    pub run_id: String,
+    pub seq: i64,
+    pub attempts: u32,
+}
+
+/// Kind of speedrun.com ID whose name is cached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NameKind {
+    Game,
+    Category,
+}
+
+impl NameKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NameKind::Game => "game",
+            NameKind::Category => "category",
+        }
+    }
+}
+
+/// A cached name lookup; `name` is `None` if speedrun.com did not know the ID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedName {
+    pub name: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Speedrun.com details of a run, shown alongside it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RunDetails {
+    pub player_names: Option<String>,
+    pub run_time_secs: Option<f64>,
+    pub platform: Option<String>,
+    /// Variable ID to value ID
+    pub variable_values: BTreeMap<String, String>,
+    /// Where the run's time placed on its leaderboard when the details were fetched.
+    pub leaderboard_place: Option<u32>,
+}
+
 #[derive(Debug, Clone)]
 pub struct NewRun {
     pub run_id: String,
     pub game_id: String,
     pub category_id: String,
     pub submitted_date: DateTime<Utc>,
+    pub details: Option<RunDetails>,
 }
 
 impl NewRun {
@@ -62,6 +327,103 @@ impl NewRun {
             game_id: game_id.into(),
             category_id: category_id.into(),
             submitted_date,
+            details: None,
         }
     }
+
+    pub fn with_details(mut self, details: RunDetails) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+/// Status of a [`VerifyJob`]. Finished jobs get the status a run with the same report would.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Processing,
+    Passed,
+    NeedsReview,
+    Failed,
+    Error,
+}
+
+impl JobStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Processing => "processing",
+            JobStatus::Passed => "passed",
+            JobStatus::NeedsReview => "needs_review",
+            JobStatus::Failed => "failed",
+            JobStatus::Error => "error",
+        }
+    }
+
+    pub fn for_report(report: &ReplayReport) -> Self {
+        match RunStatus::for_report(report) {
+            RunStatus::Passed => JobStatus::Passed,
+            RunStatus::NeedsReview => JobStatus::NeedsReview,
+            _ => JobStatus::Failed,
+        }
+    }
+}
+
+/// Where a [`VerifyJob`] was submitted from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobSource {
+    /// POST /verify of the HTTP API
+    Upload,
+    /// `cli jobs submit`
+    Cli,
+}
+
+impl JobSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobSource::Upload => "upload",
+            JobSource::Cli => "cli",
+        }
+    }
+}
+
+/// A verification of a save submitted directly rather than linked from a run on
+/// speedrun.com, with the rules of a configured game and category.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VerifyJob {
+    pub job_id: String,
+    pub source: JobSource,
+    pub game_id: String,
+    pub category_id: String,
+    /// Where the daemon replays the save from; deleted once it is done.
+    #[serde(skip)]
+    pub save_path: String,
+    pub status: JobStatus,
+    pub submitted_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub summary: Option<ReportSummary>,
+    /// Warning and error messages of the replay.
+    pub messages: Vec<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewJob {
+    pub job_id: String,
+    pub source: JobSource,
+    pub game_id: String,
+    pub category_id: String,
+    pub save_path: String,
+    /// Machine the save is on, whose daemons replay the job.
+    pub host: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JobFilter {
+    /// Matches any of these; empty matches all
+    pub statuses: Vec<JobStatus>,
+    pub limit: Option<u32>,
 }
@@ -0,0 +1,349 @@
+use crate::daemon::backlog::BacklogSummary;
+use crate::daemon::database::types::{Run, RunStatus};
+use crate::daemon::notifier::{Notification, Notifier, batch_failed};
+use crate::daemon::speedrun_api::SpeedrunOps;
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use log::{info, warn};
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+use std::time::Duration;
+
+use super::config::DiscordNotifierConfig;
+
+pub const WEBHOOK_URL_ENV_VAR: &str = "DISCORD_WEBHOOK_URL";
+
+/// Discord accepts at most 10 embeds per message.
+const MAX_EMBEDS_PER_MESSAGE: usize = 10;
+const MAX_DESCRIPTION_CHARS: usize = 1024;
+const MAX_WARNINGS: usize = 5;
+const MAX_SEND_ATTEMPTS: usize = 3;
+
+/// Posts finished runs to a Discord webhook, collecting status changes into fewer messages.
+pub struct DiscordNotifier {
+    speedrun_ops: SpeedrunOps,
+    client: Client,
+    config: DiscordNotifierConfig,
+    webhook_url: String,
+}
+
+impl DiscordNotifier {
+    pub fn new(
+        speedrun_ops: SpeedrunOps,
+        config: DiscordNotifierConfig,
+        webhook_url: String,
+    ) -> Self {
+        Self {
+            speedrun_ops,
+            client: Client::new(),
+            config,
+            webhook_url,
+        }
+    }
+
+    /// Posts to the webhook in DISCORD_WEBHOOK_URL.
+    pub fn from_env(speedrun_ops: SpeedrunOps, config: DiscordNotifierConfig) -> Result<Self> {
+        let webhook_url = std::env::var(WEBHOOK_URL_ENV_VAR)
+            .context("DISCORD_WEBHOOK_URL env var is required for Discord notifier")?;
+        Ok(Self::new(speedrun_ops, config, webhook_url))
+    }
+
+    async fn embed(&self, notification: &Notification) -> serde_json::Value {
+        let run = &notification.run;
+        let game_category = self
+            .speedrun_ops
+            .format_game_category(&run.game_id, &run.category_id)
+            .await;
+        build_embed(notification, &game_category)
+    }
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    fn accepts(&self, run: &Run) -> bool {
+        run.is_final()
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let embed = self.embed(notification).await;
+        post_embeds(&self.client, &self.webhook_url, &[embed]).await
+    }
+
+    async fn notify_batch(&self, notifications: &[Notification]) -> Vec<Result<()>> {
+        let mut results = Vec::with_capacity(notifications.len());
+        for chunk in notifications.chunks(MAX_EMBEDS_PER_MESSAGE) {
+            let mut embeds = Vec::with_capacity(chunk.len());
+            for notification in chunk {
+                embeds.push(self.embed(notification).await);
+            }
+            match post_embeds(&self.client, &self.webhook_url, &embeds).await {
+                Ok(()) => {
+                    info!("Posted {} run(s) to Discord", chunk.len());
+                    results.extend(chunk.iter().map(|_| Ok(())));
+                }
+                Err(e) => results.extend(batch_failed(chunk.len(), &e)),
+            }
+        }
+        results
+    }
+
+    fn batch_delay(&self) -> Duration {
+        Duration::from_secs(self.config.batch_interval_seconds)
+    }
+
+    async fn backlog_alert(&self, summary: &BacklogSummary) -> Result<()> {
+        let embed = build_backlog_embed(summary);
+        post_embeds(&self.client, &self.webhook_url, &[embed]).await
+    }
+}
+
+fn status_label_and_color(status: &RunStatus) -> (&'static str, u32) {
+    match status {
+        RunStatus::Passed => ("Passed", 0x2ecc71),
+        RunStatus::NeedsReview => ("Needs review", 0xf1c40f),
+        RunStatus::Failed => ("Failed", 0xe74c3c),
+        RunStatus::Error => ("Error", 0x95a5a6),
+        RunStatus::Discovered | RunStatus::Processing => ("Pending", 0x3498db),
+    }
+}
+
+/// The first few findings, one per line, cut to fit the embed description.
+fn format_warnings(message: &str) -> String {
+    let mut lines: Vec<String> = message
+        .split("; ")
+        .take(MAX_WARNINGS)
+        .map(|warning| format!("• {}", warning))
+        .collect();
+    let total = message.split("; ").count();
+    if total > MAX_WARNINGS {
+        lines.push(format!("…and {} more", total - MAX_WARNINGS));
+    }
+    let text = lines.join("\n");
+    if text.chars().count() > MAX_DESCRIPTION_CHARS {
+        let truncated: String = text.chars().take(MAX_DESCRIPTION_CHARS - 1).collect();
+        format!("{}…", truncated)
+    } else {
+        text
+    }
+}
+
+fn build_embed(notification: &Notification, game_category: &str) -> serde_json::Value {
+    let run = &notification.run;
+    let (label, color) = status_label_and_color(&run.status);
+    let mut embed = json!({
+        "title": game_category,
+        "url": format!("https://speedrun.com/runs/{}", run.run_id),
+        "color": color,
+        "fields": [
+            { "name": "Status", "value": label, "inline": true },
+            { "name": "Run", "value": run.run_id, "inline": true },
+        ],
+        "timestamp": run.updated_at.to_rfc3339(),
+    });
+    if let Some(message) = run.error_message.as_deref().filter(|m| !m.is_empty()) {
+        embed["description"] = json!(format_warnings(message));
+    }
+    if let Some(url) = &notification.public_archive_url {
+        embed["fields"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "name": "Archive", "value": url }));
+    }
+    embed
+}
+
+fn build_backlog_embed(summary: &BacklogSummary) -> serde_json::Value {
+    let mut lines: Vec<String> = summary
+        .oldest_run_ids
+        .iter()
+        .map(|run_id| format!("• https://speedrun.com/runs/{}", run_id))
+        .collect();
+    if summary.total() > lines.len() {
+        lines.push(format!("…and {} more", summary.total() - lines.len()));
+    }
+    json!({
+        "title": summary.headline(),
+        "color": 0xe67e22,
+        "description": lines.join("\n"),
+        "fields": [
+            {
+                "name": "Oldest waiting since",
+                "value": summary.oldest_created_at.format("%Y-%m-%d %H:%M UTC").to_string(),
+            },
+        ],
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+async fn post_embeds(
+    client: &Client,
+    webhook_url: &str,
+    embeds: &[serde_json::Value],
+) -> Result<()> {
+    let body = json!({ "embeds": embeds });
+
+    for _ in 0..MAX_SEND_ATTEMPTS {
+        let resp = client.post(webhook_url).json(&body).send().await?;
+        match resp.status() {
+            status if status.is_success() => return Ok(()),
+            StatusCode::TOO_MANY_REQUESTS => {
+                let retry_after = resp
+                    .json::<serde_json::Value>()
+                    .await
+                    .ok()
+                    .and_then(|body| body["retry_after"].as_f64())
+                    .unwrap_or(1.0);
+                warn!("Discord rate limited, retrying in {:.1}s", retry_after);
+                tokio::time::sleep(Duration::from_secs_f64(retry_after)).await;
+            }
+            status => bail!("HTTP {}", status),
+        }
+    }
+    bail!("still rate limited after {} attempts", MAX_SEND_ATTEMPTS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::database::connection::Database;
+    use crate::daemon::database::types::NewRun;
+    use crate::daemon::speedrun_api::SpeedrunClient;
+    use itertools::Itertools;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn insert_test_run(db: &Database, run_id: &str) {
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        let new_run = NewRun::new(run_id, "game1", "cat1", submitted_date);
+        db.insert_run(new_run).await.unwrap();
+    }
+
+    #[test]
+    fn test_format_warnings_limits_count() {
+        let message = (1..=7).map(|i| format!("warning {}", i)).join("; ");
+        let formatted = format_warnings(&message);
+        assert!(formatted.starts_with("• warning 1\n"));
+        assert!(formatted.contains("• warning 5\n…and 2 more"));
+        assert!(!formatted.contains("warning 6"));
+
+        let long = "x".repeat(2000);
+        assert_eq!(
+            format_warnings(&long).chars().count(),
+            MAX_DESCRIPTION_CHARS
+        );
+    }
+
+    #[tokio::test]
+    async fn test_build_embed() {
+        let db = Database::in_memory().await.unwrap();
+        insert_test_run(&db, "run1").await;
+        db.mark_run_needs_review("run1", Some("used /editor; crafted infinity-chest"))
+            .await
+            .unwrap();
+        let notification = Notification::load(&db, "run1").await.unwrap().unwrap();
+
+        let embed = build_embed(&notification, "Factorio / Any%");
+        assert_eq!(embed["title"], "Factorio / Any%");
+        assert_eq!(embed["url"], "https://speedrun.com/runs/run1");
+        assert_eq!(embed["color"], 0xf1c40f);
+        assert_eq!(embed["fields"][0]["value"], "Needs review");
+        assert_eq!(
+            embed["description"],
+            "• used /editor\n• crafted infinity-chest"
+        );
+        assert_eq!(embed["fields"].as_array().unwrap().len(), 2);
+
+        db.store_public_archive_url("run1", "https://archive.org/details/run1")
+            .await
+            .unwrap();
+        let notification = Notification::load(&db, "run1").await.unwrap().unwrap();
+        let embed = build_embed(&notification, "Factorio / Any%");
+        assert_eq!(embed["fields"][2]["name"], "Archive");
+        assert_eq!(
+            embed["fields"][2]["value"],
+            "https://archive.org/details/run1"
+        );
+    }
+
+    #[test]
+    fn test_build_backlog_embed() {
+        let summary = BacklogSummary {
+            sla_hours: 24,
+            discovered: 11,
+            error: 1,
+            oldest_created_at: "2024-01-01T12:30:00Z".parse().unwrap(),
+            oldest_run_ids: (0..10).map(|i| format!("run{}", i)).collect(),
+        };
+        let embed = build_backlog_embed(&summary);
+        assert_eq!(
+            embed["title"],
+            "12 run(s) waiting over 24h for verification (11 discovered, 1 erroring)"
+        );
+        let description = embed["description"].as_str().unwrap();
+        assert!(description.starts_with("• https://speedrun.com/runs/run0\n"));
+        assert!(description.ends_with("• https://speedrun.com/runs/run9\n…and 2 more"));
+        assert_eq!(embed["fields"][0]["value"], "2024-01-01 12:30 UTC");
+    }
+
+    #[tokio::test]
+    async fn test_notify_batch_chunks() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/webhook"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let db = Database::in_memory().await.unwrap();
+        db.cache_game_name("game1", "Factorio").await.unwrap();
+        db.cache_category_name("cat1", "Any%").await.unwrap();
+        let mut notifications = Vec::new();
+        for i in 0..12 {
+            let run_id = format!("run{}", i);
+            insert_test_run(&db, &run_id).await;
+            db.mark_run_passed(&run_id).await.unwrap();
+            notifications.push(Notification::load(&db, &run_id).await.unwrap().unwrap());
+        }
+
+        let speedrun_ops = SpeedrunOps::new(&SpeedrunClient::new().unwrap()).with_db(db.clone());
+        let webhook_url = format!("{}/webhook", mock_server.uri());
+        let notifier = DiscordNotifier::new(
+            speedrun_ops,
+            DiscordNotifierConfig {
+                batch_interval_seconds: 30,
+            },
+            webhook_url,
+        );
+        let results = notifier.notify_batch(&notifications).await;
+        assert_eq!(results.len(), 12);
+        assert!(results.iter().all(Result::is_ok));
+
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_post_embeds_retries_after_rate_limit() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).set_body_json(json!({ "retry_after": 0.01 })))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "embeds": [{ "title": "t" }] })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = Client::new();
+        post_embeds(&client, &mock_server.uri(), &[json!({ "title": "t" })])
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::Result;
+use chrono::Utc;
+use log::{info, warn};
+use zip_downloader::throttle::DownloadThrottles;
+
+use super::config::{PollingConfig, SrcRunRules};
+use super::run_processing::RunProcessor;
+use super::speedrun_api::{Run, RunsQuery, SpeedrunClient, SpeedrunOps};
+
+/// Polls speedrun.com and logs what each run in the lookback window would be verified
+/// with, without touching the database, downloading saves or starting Factorio.
+pub async fn dry_run_poll(
+    src_rules: &SrcRunRules,
+    ops: &SpeedrunOps,
+    config: &PollingConfig,
+) -> Result<()> {
+    let cutoff_date = Utc::now() - chrono::Duration::days(config.lookback_days as i64);
+    info!(
+        "Dry run: listing runs submitted since {}",
+        cutoff_date.format("%Y-%m-%d")
+    );
+
+    let mut processor = RunProcessor::new(&ops.client, &DownloadThrottles::default());
+    for (game_id, game_config) in &src_rules.games {
+        for category_id in game_config.categories.keys() {
+            let game_category = ops.format_game_category(game_id, category_id).await;
+            if let Err(e) = src_rules.resolve_rules(game_id, category_id) {
+                warn!("{}: {:#}", game_category, e);
+                continue;
+            }
+            let query = RunsQuery::new()
+                .game(game_id)
+                .category(category_id)
+                .orderby("submitted")
+                .direction("asc");
+            let runs = match ops.client.stream_runs(&query).await {
+                Ok(runs) => runs,
+                Err(e) => {
+                    warn!("Failed to poll {}: {:#}", game_category, e);
+                    continue;
+                }
+            };
+            let runs: Vec<Run> = runs
+                .into_iter()
+                .filter(|run| {
+                    run.get_submitted_date()
+                        .is_ok_and(|submitted| submitted > cutoff_date)
+                })
+                .collect();
+            info!("{}: {} run(s)", game_category, runs.len());
+            for run in &runs {
+                info!("{}", describe_run(&mut processor, src_rules, run).await);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Logs what verifying a single run would do.
+pub async fn dry_run_single(
+    src_rules: &SrcRunRules,
+    client: &SpeedrunClient,
+    run_id: &str,
+) -> Result<()> {
+    let run = client.get_run(run_id).await?;
+    let mut processor = RunProcessor::new(client, &DownloadThrottles::default());
+    info!("{}", describe_run(&mut processor, src_rules, &run).await);
+    Ok(())
+}
+
+async fn describe_run(
+    processor: &mut RunProcessor<'_>,
+    src_rules: &SrcRunRules,
+    run: &Run,
+) -> String {
+    let submitted = run
+        .get_submitted_date()
+        .map(|date| date.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+    let mut lines = vec![format!("Run {} (submitted {})", run.id, submitted)];
+
+    let description = processor.run_description(run).await;
+    let links = processor.detect_save_links(&description);
+    if links.is_empty() {
+        lines.push("  no save link found; would fail to download".to_string());
+    }
+    for link in links {
+        lines.push(format!("  would download: {}", link));
+    }
+
+    match src_rules.resolve_rules(&run.game, &run.category) {
+        Ok((run_rules, expected_mods)) => {
+            let mut mods: Vec<&str> = expected_mods.iter().map(String::as_str).collect();
+            mods.sort();
+            lines.push(format!("  expected mods: {}", mods.join(", ")));
+            lines.push(format!(
+                "  replay scripts: {}",
+                serde_json::to_string(&run_rules.replay_scripts).unwrap_or_default()
+            ));
+        }
+        Err(e) => lines.push(format!("  would not run: {:#}", e)),
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC_RULES: &str = r#"
+games:
+  game1:
+    expected_mods: [base, quality]
+    categories:
+      cat1:
+        win_on_scenario_finished: true
+"#;
+
+    fn test_run(category: &str, comment: &str) -> Run {
+        serde_json::from_value(serde_json::json!({
+            "id": "run1",
+            "game": "game1",
+            "category": category,
+            "comment": comment,
+            "submitted": "2024-01-01T00:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_describe_run() {
+        let src_rules: SrcRunRules = serde_yaml::from_str(SRC_RULES).unwrap();
+        let client = SpeedrunClient::new().unwrap();
+        let mut processor = RunProcessor::new(&client, &DownloadThrottles::default());
+
+        let run = test_run(
+            "cat1",
+            "save: https://www.dropbox.com/s/abc123/run.zip?dl=0",
+        );
+        let description = describe_run(&mut processor, &src_rules, &run).await;
+        assert!(description.contains("Run run1 (submitted 2024-01-01 00:00)"));
+        assert!(description.contains("would download: "));
+        assert!(description.contains("expected mods: base, quality"));
+        assert!(description.contains("\"win_on_scenario_finished\":true"));
+
+        let mut run = test_run("cat1", "no link here");
+        run.splits = serde_json::from_value(serde_json::json!({
+            "rel": "splits.io",
+            "uri": "https://www.dropbox.com/s/abc123/run.zip?dl=0",
+        }))
+        .unwrap();
+        let description = describe_run(&mut processor, &src_rules, &run).await;
+        assert!(description.contains("would download: "));
+
+        let run = test_run("cat2", "no link here");
+        let description = describe_run(&mut processor, &src_rules, &run).await;
+        assert!(description.contains("no save link found"));
+        assert!(description.contains("would not run: No configuration found for category=cat2"));
+    }
+}
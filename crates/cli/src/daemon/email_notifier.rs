@@ -0,0 +1,217 @@
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use log::info;
+use std::fmt::Write as _;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::daemon::backlog::BacklogSummary;
+use crate::daemon::bot_notifier::run_status_to_bot_status;
+use crate::daemon::config::EmailNotifierConfig;
+use crate::daemon::database::types::Run;
+use crate::daemon::notifier::{Notification, Notifier};
+use crate::daemon::speedrun_api::SpeedrunOps;
+
+/// Mails finished runs with the configured statuses, one message per run.
+pub struct EmailNotifier {
+    speedrun_ops: SpeedrunOps,
+    config: EmailNotifierConfig,
+}
+
+impl EmailNotifier {
+    pub fn new(speedrun_ops: SpeedrunOps, config: EmailNotifierConfig) -> Self {
+        Self {
+            speedrun_ops,
+            config,
+        }
+    }
+
+    async fn message(&self, notification: &Notification) -> String {
+        let run = &notification.run;
+        let game_category = self
+            .speedrun_ops
+            .format_game_category(&run.game_id, &run.category_id)
+            .await;
+        let mut message = self.headers(&format!(
+            "[{}] {} run {}",
+            run_status_to_bot_status(&run.status),
+            game_category,
+            run.run_id
+        ));
+        write_body(&mut message, notification);
+        message
+    }
+
+    fn headers(&self, subject: &str) -> String {
+        let mut message = String::new();
+        writeln!(message, "From: {}", header_value(&self.config.from)).unwrap();
+        writeln!(message, "To: {}", header_value(&self.config.to.join(", "))).unwrap();
+        writeln!(message, "Subject: {}", header_value(subject)).unwrap();
+        writeln!(message, "Content-Type: text/plain; charset=utf-8").unwrap();
+        writeln!(message).unwrap();
+        message
+    }
+
+    async fn send(&self, message: &str) -> Result<()> {
+        let mut child = Command::new(&self.config.sendmail)
+            .args(["-t", "-i"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", self.config.sendmail.display()))?;
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(message.as_bytes()).await?;
+        drop(stdin);
+
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            bail!(
+                "{} exited with {}: {}",
+                self.config.sendmail.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    fn accepts(&self, run: &Run) -> bool {
+        run.is_final() && self.config.statuses.contains(&run.status)
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let message = self.message(notification).await;
+        self.send(&message).await?;
+        info!("Mailed run {}", notification.run.run_id);
+        Ok(())
+    }
+
+    async fn backlog_alert(&self, summary: &BacklogSummary) -> Result<()> {
+        let mut message = self.headers(&format!("[backlog] {}", summary.headline()));
+        writeln!(
+            message,
+            "Oldest waiting since: {}",
+            summary.oldest_created_at.format("%Y-%m-%d %H:%M UTC")
+        )
+        .unwrap();
+        writeln!(message).unwrap();
+        for run_id in &summary.oldest_run_ids {
+            writeln!(message, "- https://speedrun.com/runs/{}", run_id).unwrap();
+        }
+        self.send(&message).await?;
+        info!("Mailed backlog alert");
+        Ok(())
+    }
+}
+
+/// Keeps names from speedrun.com from adding headers.
+fn header_value(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+fn write_body(message: &mut String, notification: &Notification) {
+    let run = &notification.run;
+    writeln!(message, "Run: https://speedrun.com/runs/{}", run.run_id).unwrap();
+    writeln!(message, "Status: {}", run_status_to_bot_status(&run.status)).unwrap();
+    if let Some(report) = &notification.report {
+        writeln!(
+            message,
+            "Replay: {} finding(s), {} ticks",
+            report.finding_count, report.final_tick
+        )
+        .unwrap();
+    }
+    if let Some(url) = &notification.public_archive_url {
+        writeln!(message, "Archive: {}", url).unwrap();
+    }
+    if let Some(review) = &notification.review {
+        write!(
+            message,
+            "Review: {} by {}",
+            review.decision.as_str(),
+            review.reviewer
+        )
+        .unwrap();
+        if let Some(note) = &review.note {
+            write!(message, ": {}", note).unwrap();
+        }
+        writeln!(message).unwrap();
+    }
+    if let Some(error_message) = run.error_message.as_deref().filter(|m| !m.is_empty()) {
+        writeln!(message).unwrap();
+        for finding in error_message.split("; ") {
+            writeln!(message, "- {}", finding).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::database::connection::Database;
+    use crate::daemon::database::types::{NewRun, RunStatus};
+    use crate::daemon::speedrun_api::SpeedrunClient;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_notify_pipes_message_to_sendmail() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let sent = dir.path().join("sent.eml");
+        let sendmail = dir.path().join("sendmail");
+        std::fs::write(
+            &sendmail,
+            format!(
+                "#!/bin/sh\necho \"$@\" > {0}.args\ncat > {0}\n",
+                sent.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&sendmail, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let db = Database::in_memory().await.unwrap();
+        db.cache_game_name("game1", "Factorio").await.unwrap();
+        db.cache_category_name("cat1", "Any%").await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new("run1", "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+        db.mark_run_failed("run1", Some("used /editor; crafted infinity-chest"))
+            .await
+            .unwrap();
+        let notification = Notification::load(&db, "run1").await.unwrap().unwrap();
+
+        let speedrun_ops = SpeedrunOps::new(&SpeedrunClient::new().unwrap()).with_db(db.clone());
+        let notifier = EmailNotifier::new(
+            speedrun_ops,
+            EmailNotifierConfig {
+                from: "runner@example.com".to_string(),
+                to: vec!["mods@example.com".to_string(), "a@example.com".to_string()],
+                sendmail,
+                statuses: vec![RunStatus::Failed],
+            },
+        );
+        assert!(notifier.accepts(&notification.run));
+        notifier.notify(&notification).await.unwrap();
+
+        let message = std::fs::read_to_string(&sent).unwrap();
+        assert!(message.starts_with(
+            "From: runner@example.com\nTo: mods@example.com, a@example.com\n\
+             Subject: [failed] Factorio / Any% run run1\n"
+        ));
+        assert!(message.contains("\n\nRun: https://speedrun.com/runs/run1\n"));
+        assert!(message.ends_with("- used /editor\n- crafted infinity-chest\n"));
+        let args = std::fs::read_to_string(dir.path().join("sent.eml.args")).unwrap();
+        assert_eq!(args.trim(), "-t -i");
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use log::{info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Name of the complete, zstd-compressed Factorio output kept in each run's working dir.
+pub const FACTORIO_LOG_FILE: &str = "factorio.log.zst";
+
+/// Keeps Factorio's complete output for each run, for debugging failed replays.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct FactorioLogConfig {
+    /// Logs older than this many days are deleted.
+    pub retention_days: Option<u64>,
+    /// Once logs take more than this many MB together, the oldest are deleted.
+    pub max_total_mb: Option<u64>,
+}
+
+impl FactorioLogConfig {
+    /// Deletes logs under `output_dir` past the retention policy.
+    pub fn rotate(&self, output_dir: &Path) -> Result<()> {
+        let mut logs = find_logs(output_dir)?;
+        logs.sort_by_key(|log| log.modified);
+
+        let cutoff = self
+            .retention_days
+            .and_then(|days| SystemTime::now().checked_sub(Duration::from_secs(days * 24 * 3600)));
+        let mut total: u64 = logs.iter().map(|log| log.size).sum();
+        let max_total = self.max_total_mb.map(|mb| mb * 1024 * 1024);
+
+        for log in logs {
+            let expired = cutoff.is_some_and(|cutoff| log.modified < cutoff);
+            let over_quota = max_total.is_some_and(|max_total| total > max_total);
+            if !expired && !over_quota {
+                break;
+            }
+            match std::fs::remove_file(&log.path) {
+                Ok(()) => {
+                    info!("Removed old Factorio log {}", log.path.display());
+                    total -= log.size;
+                }
+                Err(e) => warn!("Failed to remove {}: {}", log.path.display(), e),
+            }
+        }
+        Ok(())
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+}
+
+fn find_logs(output_dir: &Path) -> Result<Vec<LogFile>> {
+    let mut logs = Vec::new();
+    for entry in std::fs::read_dir(output_dir)? {
+        let path = entry?.path().join(FACTORIO_LOG_FILE);
+        if let Ok(metadata) = path.metadata() {
+            logs.push(LogFile {
+                path,
+                modified: metadata.modified()?,
+                size: metadata.len(),
+            });
+        }
+    }
+    Ok(logs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    fn write_log(output_dir: &Path, run_id: &str, size: usize, age_days: u64) -> PathBuf {
+        let run_dir = output_dir.join(run_id);
+        std::fs::create_dir_all(&run_dir).unwrap();
+        let path = run_dir.join(FACTORIO_LOG_FILE);
+        std::fs::write(&path, vec![0; size]).unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(age_days * 24 * 3600);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_rotate_by_age() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let old = write_log(output_dir.path(), "old", 10, 40);
+        let recent = write_log(output_dir.path(), "recent", 10, 1);
+        std::fs::create_dir(output_dir.path().join("no_log")).unwrap();
+
+        let config = FactorioLogConfig {
+            retention_days: Some(30),
+            max_total_mb: None,
+        };
+        config.rotate(output_dir.path()).unwrap();
+        assert!(!old.exists());
+        assert!(recent.exists());
+    }
+
+    #[test]
+    fn test_rotate_by_size() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let mb = 1024 * 1024;
+        let oldest = write_log(output_dir.path(), "a", mb, 3);
+        let older = write_log(output_dir.path(), "b", mb, 2);
+        let newest = write_log(output_dir.path(), "c", mb, 1);
+
+        let config = FactorioLogConfig {
+            retention_days: None,
+            max_total_mb: Some(2),
+        };
+        config.rotate(output_dir.path()).unwrap();
+        assert!(!oldest.exists());
+        assert!(older.exists());
+        assert!(newest.exists());
+    }
+}
@@ -0,0 +1,381 @@
+//! Operator commands run at fixed points of processing a run, e.g. external anti-cheat
+//! checks. Each hook gets the run's details as JSON on stdin, and the main ones in
+//! environment variables:
+//!   HOOK_STAGE, RUN_ID, RUN_GAME_ID, RUN_CATEGORY_ID, RUN_WORKING_DIR, RUN_REPORT_PATH
+//! A hook fails if it exits unsuccessfully or times out; its `on_failure` policy decides
+//! what that means for the run.
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use replay_script::MsgLevel;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+
+use crate::daemon::database::types::Run;
+use crate::error::{ErrorClass, RunProcessingError};
+use crate::run_replay::ReplayReport;
+
+/// How much of a failed hook's stderr is kept in its failure message.
+const MAX_OUTPUT_CHARS: usize = 500;
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Before the save is downloaded
+    #[serde(default)]
+    pub before_download: Vec<HookConfig>,
+    /// Before the replay, with the downloaded saves
+    #[serde(default)]
+    pub after_download: Vec<HookConfig>,
+    /// After a replay that finished, with its report
+    #[serde(default)]
+    pub after_replay: Vec<HookConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HookConfig {
+    /// Program and arguments; not run through a shell
+    pub command: Vec<String>,
+    #[serde(default = "default_hook_timeout_seconds")]
+    pub timeout_seconds: u64,
+    #[serde(default)]
+    pub on_failure: HookFailurePolicy,
+}
+
+fn default_hook_timeout_seconds() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HookFailurePolicy {
+    /// Only log the failure
+    Ignore,
+    /// The run needs review, with the hook's output in its messages
+    #[default]
+    Review,
+    /// The run errors and is retried later
+    Retry,
+    /// The run errors without being retried
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookStage {
+    BeforeDownload,
+    AfterDownload,
+    AfterReplay,
+}
+
+impl HookStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookStage::BeforeDownload => "before_download",
+            HookStage::AfterDownload => "after_download",
+            HookStage::AfterReplay => "after_replay",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct HookRun {
+    run_id: String,
+    game_id: String,
+    category_id: String,
+    submitted_date: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+struct HookPayload<'a> {
+    stage: HookStage,
+    #[serde(flatten)]
+    run: &'a HookRun,
+    working_dir: &'a Path,
+    save_paths: &'a [PathBuf],
+    report_path: Option<&'a Path>,
+}
+
+/// The configured hooks, as run for one run.
+#[derive(Debug, Clone)]
+pub struct RunHooks {
+    config: Arc<HooksConfig>,
+    run: HookRun,
+    /// Failures of hooks with the `review` policy, added to the run's report
+    review_messages: Vec<String>,
+}
+
+impl RunHooks {
+    pub fn new(config: Arc<HooksConfig>, run: &Run) -> Self {
+        Self {
+            config,
+            run: HookRun {
+                run_id: run.run_id.clone(),
+                game_id: run.game_id.clone(),
+                category_id: run.category_id.clone(),
+                submitted_date: run.submitted_date,
+            },
+            review_messages: Vec::new(),
+        }
+    }
+
+    /// Runs the stage's hooks in order, stopping at the first failure that fails the run.
+    pub async fn run(
+        &mut self,
+        stage: HookStage,
+        working_dir: &Path,
+        save_paths: &[PathBuf],
+        report_path: Option<&Path>,
+        cancel: &CancellationToken,
+    ) -> Result<(), RunProcessingError> {
+        let hooks = match stage {
+            HookStage::BeforeDownload => &self.config.before_download,
+            HookStage::AfterDownload => &self.config.after_download,
+            HookStage::AfterReplay => &self.config.after_replay,
+        };
+        let payload = HookPayload {
+            stage,
+            run: &self.run,
+            working_dir,
+            save_paths,
+            report_path,
+        };
+        let input = serde_json::to_vec(&payload)
+            .map_err(|e| RunProcessingError::from_error(ErrorClass::Retryable, &e))?;
+
+        for hook in hooks {
+            let result = tokio::select! {
+                result = run_hook(hook, &payload, &input) => result,
+                _ = cancel.cancelled() => {
+                    return Err(RunProcessingError::from_error(
+                        ErrorClass::Retryable,
+                        &"Hook interrupted by shutdown",
+                    ));
+                }
+            };
+            let Err(e) = result else {
+                continue;
+            };
+            let message = format!("{} hook {}: {:#}", stage.as_str(), hook_name(hook), e);
+            match hook.on_failure {
+                HookFailurePolicy::Ignore => warn!("{}", message),
+                HookFailurePolicy::Review => {
+                    warn!("{}", message);
+                    self.review_messages.push(message);
+                }
+                HookFailurePolicy::Retry => {
+                    return Err(RunProcessingError::from_error(
+                        ErrorClass::Retryable,
+                        &message,
+                    ));
+                }
+                HookFailurePolicy::Fail => {
+                    return Err(RunProcessingError::from_error(ErrorClass::Final, &message));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the failures of `review` hooks so far to `report`, which then needs review at
+    /// least. Returns whether there were any.
+    pub fn flag_report(&mut self, report: &mut ReplayReport) -> bool {
+        if self.review_messages.is_empty() {
+            return false;
+        }
+        report.max_msg_level = report.max_msg_level.max(MsgLevel::Warn);
+        report.messages.append(&mut self.review_messages);
+        true
+    }
+}
+
+fn hook_name(hook: &HookConfig) -> &str {
+    hook.command.first().map_or("<empty>", String::as_str)
+}
+
+async fn run_hook(hook: &HookConfig, payload: &HookPayload<'_>, input: &[u8]) -> Result<()> {
+    let Some((program, args)) = hook.command.split_first() else {
+        bail!("no command configured");
+    };
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .current_dir(payload.working_dir)
+        .env("HOOK_STAGE", payload.stage.as_str())
+        .env("RUN_ID", &payload.run.run_id)
+        .env("RUN_GAME_ID", &payload.run.game_id)
+        .env("RUN_CATEGORY_ID", &payload.run.category_id)
+        .env("RUN_WORKING_DIR", payload.working_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(report_path) = payload.report_path {
+        command.env("RUN_REPORT_PATH", report_path);
+    }
+
+    info!("Running {} hook {}", payload.stage.as_str(), program);
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to run {}", program))?;
+    let mut stdin = child.stdin.take().unwrap();
+    let output = tokio::time::timeout(Duration::from_secs(hook.timeout_seconds), async {
+        // hooks needn't read their input
+        stdin.write_all(input).await.ok();
+        drop(stdin);
+        child.wait_with_output().await
+    })
+    .await
+    .map_err(|_| anyhow::anyhow!("timed out after {}s", hook.timeout_seconds))??;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stderr = stderr.trim();
+        let start = stderr
+            .char_indices()
+            .rev()
+            .nth(MAX_OUTPUT_CHARS - 1)
+            .map_or(0, |(i, _)| i);
+        bail!("exited with {}: {}", output.status, &stderr[start..]);
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::daemon::database::connection::Database;
+    use crate::daemon::database::types::NewRun;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn write_script(dir: &Path, name: &str, body: &str) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn hook(command: String, on_failure: HookFailurePolicy) -> HookConfig {
+        HookConfig {
+            command: vec![command],
+            timeout_seconds: 5,
+            on_failure,
+        }
+    }
+
+    async fn run_hooks(config: HooksConfig) -> RunHooks {
+        let db = Database::in_memory().await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new("run1", "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+        let run = db.get_run("run1").await.unwrap().unwrap();
+        RunHooks::new(Arc::new(config), &run)
+    }
+
+    #[tokio::test]
+    async fn test_hook_gets_run_details() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(
+            dir.path(),
+            "hook.sh",
+            "cat > input.json; echo \"$HOOK_STAGE $RUN_ID $RUN_GAME_ID $RUN_REPORT_PATH\" > env.txt",
+        );
+        let mut hooks = run_hooks(HooksConfig {
+            after_replay: vec![hook(script, HookFailurePolicy::Fail)],
+            ..Default::default()
+        })
+        .await;
+        let report_path = dir.path().join("output.json");
+        hooks
+            .run(
+                HookStage::AfterReplay,
+                dir.path(),
+                &[dir.path().join("run1_save.zip")],
+                Some(&report_path),
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+
+        let env = std::fs::read_to_string(dir.path().join("env.txt")).unwrap();
+        assert_eq!(
+            env.trim(),
+            format!("after_replay run1 game1 {}", report_path.display())
+        );
+        let input: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.path().join("input.json")).unwrap()).unwrap();
+        assert_eq!(input["stage"], "after_replay");
+        assert_eq!(input["category_id"], "cat1");
+        assert_eq!(input["submitted_date"], "2024-01-01T00:00:00Z");
+        assert!(
+            input["save_paths"][0]
+                .as_str()
+                .unwrap()
+                .ends_with("run1_save.zip")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failure_policies() {
+        let dir = tempfile::tempdir().unwrap();
+        let failing = write_script(dir.path(), "fail.sh", "echo 'used /editor' >&2; exit 3");
+        let slow = write_script(dir.path(), "slow.sh", "sleep 10");
+        let stage = HookStage::AfterDownload;
+        let cancel = CancellationToken::new();
+
+        let mut hooks = run_hooks(HooksConfig {
+            after_download: vec![
+                hook(failing.clone(), HookFailurePolicy::Ignore),
+                hook(failing.clone(), HookFailurePolicy::Review),
+            ],
+            ..Default::default()
+        })
+        .await;
+        hooks
+            .run(stage, dir.path(), &[], None, &cancel)
+            .await
+            .unwrap();
+        let mut report = ReplayReport::default();
+        assert!(hooks.flag_report(&mut report));
+        assert_eq!(report.max_msg_level, MsgLevel::Warn);
+        assert_eq!(report.messages.len(), 1);
+        assert!(report.messages[0].starts_with("after_download hook "));
+        assert!(report.messages[0].ends_with("used /editor"));
+        assert!(!hooks.flag_report(&mut report));
+
+        let mut hooks = run_hooks(HooksConfig {
+            after_download: vec![hook(failing, HookFailurePolicy::Fail)],
+            ..Default::default()
+        })
+        .await;
+        let err = hooks
+            .run(stage, dir.path(), &[], None, &cancel)
+            .await
+            .unwrap_err();
+        assert_eq!(err.class, ErrorClass::Final);
+
+        let mut slow_hook = hook(slow, HookFailurePolicy::Retry);
+        slow_hook.timeout_seconds = 0;
+        let mut hooks = run_hooks(HooksConfig {
+            after_download: vec![slow_hook],
+            ..Default::default()
+        })
+        .await;
+        let err = hooks
+            .run(stage, dir.path(), &[], None, &cancel)
+            .await
+            .unwrap_err();
+        assert_eq!(err.class, ErrorClass::Retryable);
+        assert!(err.message.contains("timed out"));
+    }
+}
@@ -0,0 +1,578 @@
+use anyhow::{Context, Result};
+use axum::extract::{DefaultBodyLimit, Multipart, Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::Utc;
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+use crate::daemon::config::HttpApiConfig;
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::{JobSource, RunFilter};
+use crate::daemon::liveness::Liveness;
+use crate::daemon::verify_jobs::VerifyJobs;
+use crate::query::common::{format_status, parse_status};
+
+/// Bearer token required for POST endpoints. Without it, they are disabled.
+pub const AUTH_TOKEN_ENV_VAR: &str = "HTTP_API_AUTH_TOKEN";
+
+const DEFAULT_LIST_LIMIT: u32 = 100;
+
+#[derive(Clone)]
+struct ApiState {
+    db: Database,
+    work_notify: Arc<Notify>,
+    auth_token: Option<String>,
+    liveness: Liveness,
+    verify_jobs: Option<VerifyJobs>,
+    max_upload_bytes: usize,
+}
+
+enum ApiError {
+    NotFound(String),
+    BadRequest(String),
+    Conflict(String),
+    Unauthorized,
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError::Internal(e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            ApiError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()),
+            ApiError::Internal(e) => {
+                error!("HTTP API error: {:#}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal error".to_string(),
+                )
+            }
+        };
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct ListRunsQuery {
+    status: Option<String>,
+    game_id: Option<String>,
+    category_id: Option<String>,
+    limit: Option<u32>,
+    #[serde(default)]
+    offset: u32,
+}
+
+fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/runs", get(list_runs))
+        .route("/runs/:run_id", get(get_run))
+        .route("/runs/:run_id/reprocess", post(reprocess_run))
+        .route(
+            "/verify",
+            post(submit_verification).layer(DefaultBodyLimit::max(state.max_upload_bytes)),
+        )
+        .route("/verify/:job_id", get(get_verification))
+        .with_state(state)
+}
+
+fn authorize(state: &ApiState, headers: &HeaderMap) -> Result<(), ApiError> {
+    let authorized = state.auth_token.as_deref().is_some_and(|token| {
+        headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            == Some(token)
+    });
+    if authorized {
+        Ok(())
+    } else {
+        Err(ApiError::Unauthorized)
+    }
+}
+
+async fn health(State(state): State<ApiState>) -> Result<Json<serde_json::Value>, ApiError> {
+    let counts = state.db.count_runs_by_status().await?;
+    let counts: serde_json::Map<_, _> = counts
+        .into_iter()
+        .map(|(status, count)| (format_status(&status), count.into()))
+        .collect();
+    let progress = state.db.processing_progress().await?;
+    Ok(Json(json!({
+        "status": "ok",
+        "runs": counts,
+        "processing": progress,
+        "daemon": state.liveness.snapshot(Utc::now()),
+    })))
+}
+
+async fn list_runs(
+    State(state): State<ApiState>,
+    Query(query): Query<ListRunsQuery>,
+) -> Result<Response, ApiError> {
+    let status = query
+        .status
+        .as_deref()
+        .map(parse_status)
+        .transpose()
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let filter = RunFilter {
+        statuses: status.into_iter().collect(),
+        game_id: query.game_id,
+        category_id: query.category_id,
+        limit: Some(query.limit.unwrap_or(DEFAULT_LIST_LIMIT)),
+        offset: query.offset,
+        ..Default::default()
+    };
+    let runs = state.db.query_runs(filter).await?;
+    Ok(Json(runs).into_response())
+}
+
+async fn get_run(
+    State(state): State<ApiState>,
+    Path(run_id): Path<String>,
+) -> Result<Response, ApiError> {
+    let run = state
+        .db
+        .get_run(&run_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Run not found: {}", run_id)))?;
+    Ok(Json(run).into_response())
+}
+
+async fn reprocess_run(
+    State(state): State<ApiState>,
+    Path(run_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    authorize(&state, &headers)?;
+
+    state
+        .db
+        .get_run(&run_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Run not found: {}", run_id)))?;
+    if let Err(holder) = state.db.reset_run(&run_id, true).await? {
+        return Err(ApiError::Conflict(format!(
+            "Run {} is being processed by {}",
+            run_id, holder
+        )));
+    }
+    state.work_notify.notify_one();
+
+    info!("Run {} queued for reprocessing via HTTP API", run_id);
+    Ok(Json(json!({ "run_id": run_id, "status": "discovered" })).into_response())
+}
+
+fn verify_jobs(state: &ApiState) -> Result<&VerifyJobs, ApiError> {
+    state
+        .verify_jobs
+        .as_ref()
+        .ok_or_else(|| ApiError::NotFound("Save verification is not enabled".to_string()))
+}
+
+/// Queues a verification of an uploaded save, not tied to a run on speedrun.com. Takes a
+/// multipart form with the `save` file and the `game_id` and `category_id` whose rules to
+/// verify it with.
+async fn submit_verification(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Result<Response, ApiError> {
+    authorize(&state, &headers)?;
+    let jobs = verify_jobs(&state)?;
+    let (job_id, save_path) = jobs.create_job()?;
+    let result = receive_upload(multipart, &save_path).await;
+    let (game_id, category_id) = match result {
+        Ok(fields) => fields,
+        Err(e) => {
+            jobs.discard_job(&job_id);
+            return Err(e);
+        }
+    };
+    jobs.check_save(&job_id, &game_id, &category_id)
+        .map_err(|e| ApiError::BadRequest(format!("{:#}", e)))?;
+    let job = jobs
+        .submit(&job_id, JobSource::Upload, &game_id, &category_id)
+        .await?;
+
+    info!(
+        "Verification job {} queued via HTTP API for game {} category {}",
+        job_id, game_id, category_id
+    );
+    Ok((StatusCode::ACCEPTED, Json(job)).into_response())
+}
+
+/// Writes the `save` field of the form to `save_path`, returning the game and category IDs.
+async fn receive_upload(
+    mut multipart: Multipart,
+    save_path: &std::path::Path,
+) -> Result<(String, String), ApiError> {
+    let bad_request = |e: &dyn std::fmt::Display| ApiError::BadRequest(e.to_string());
+    let mut game_id = None;
+    let mut category_id = None;
+    let mut has_save = false;
+    while let Some(mut field) = multipart.next_field().await.map_err(|e| bad_request(&e))? {
+        match field.name() {
+            Some("game_id") => game_id = Some(field.text().await.map_err(|e| bad_request(&e))?),
+            Some("category_id") => {
+                category_id = Some(field.text().await.map_err(|e| bad_request(&e))?)
+            }
+            Some("save") => {
+                let mut file = tokio::fs::File::create(save_path)
+                    .await
+                    .context("Failed to create upload file")?;
+                while let Some(chunk) = field.chunk().await.map_err(|e| bad_request(&e))? {
+                    file.write_all(&chunk)
+                        .await
+                        .context("Failed to write upload")?;
+                }
+                file.flush().await.context("Failed to write upload")?;
+                has_save = true;
+            }
+            _ => {}
+        }
+    }
+    let missing = |name: &str| ApiError::BadRequest(format!("Missing form field: {}", name));
+    if !has_save {
+        return Err(missing("save"));
+    }
+    Ok((
+        game_id.ok_or_else(|| missing("game_id"))?,
+        category_id.ok_or_else(|| missing("category_id"))?,
+    ))
+}
+
+/// Authorized like submitting, as job IDs are only as hard to guess as `fastrand`'s.
+async fn get_verification(
+    State(state): State<ApiState>,
+    Path(job_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    authorize(&state, &headers)?;
+    let job = verify_jobs(&state)?
+        .get(&job_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Verification job not found: {}", job_id)))?;
+    Ok(Json(job).into_response())
+}
+
+async fn serve(listener: TcpListener, state: ApiState, token: CancellationToken) -> Result<()> {
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(token.cancelled_owned())
+        .await
+        .context("HTTP API server failed")
+}
+
+pub async fn run_http_api(
+    config: HttpApiConfig,
+    db: Database,
+    work_notify: Arc<Notify>,
+    liveness: Liveness,
+    verify_jobs: Option<VerifyJobs>,
+    token: CancellationToken,
+) -> Result<()> {
+    let listener = TcpListener::bind(config.bind)
+        .await
+        .with_context(|| format!("Failed to bind HTTP API to {}", config.bind))?;
+    info!("HTTP API listening on {}", config.bind);
+
+    // categories may accept saves larger than max_upload_mb; their own limits are checked
+    let max_upload_bytes = verify_jobs
+        .as_ref()
+        .map_or(0, VerifyJobs::max_save_size)
+        .max(config.max_upload_mb.saturating_mul(1024 * 1024));
+    let state = ApiState {
+        db: db.with_actor("http_api"),
+        work_notify,
+        auth_token: std::env::var(AUTH_TOKEN_ENV_VAR).ok(),
+        liveness,
+        verify_jobs,
+        max_upload_bytes: usize::try_from(max_upload_bytes).unwrap_or(usize::MAX),
+    };
+    serve(listener, state, token).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::database::types::{NewRun, RunSelection, RunStatus};
+
+    async fn start_server(db: Database, auth_token: Option<&str>) -> (String, CancellationToken) {
+        start_server_with_jobs(db, auth_token, None).await
+    }
+
+    async fn start_server_with_jobs(
+        db: Database,
+        auth_token: Option<&str>,
+        verify_jobs: Option<VerifyJobs>,
+    ) -> (String, CancellationToken) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let token = CancellationToken::new();
+        let state = ApiState {
+            db,
+            work_notify: Arc::new(Notify::new()),
+            auth_token: auth_token.map(str::to_string),
+            liveness: Liveness::default(),
+            verify_jobs,
+            max_upload_bytes: 1024 * 1024,
+        };
+        tokio::spawn(serve(listener, state, token.clone()));
+        (base_url, token)
+    }
+
+    async fn db_with_run(run_id: &str) -> Database {
+        let db = Database::in_memory().await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new(run_id, "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_health_and_runs() {
+        let db = db_with_run("run1").await;
+        let (base_url, token) = start_server(db, None).await;
+        let client = reqwest::Client::new();
+
+        let health: serde_json::Value = client
+            .get(format!("{}/health", base_url))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(health["status"], "ok");
+        assert_eq!(health["runs"]["discovered"], 1);
+        assert_eq!(health["processing"], json!([]));
+        assert_eq!(health["daemon"]["last_poll_at"], serde_json::Value::Null);
+        assert!(health["daemon"]["uptime_seconds"].is_i64());
+
+        let runs: serde_json::Value = client
+            .get(format!("{}/runs?status=discovered", base_url))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(runs[0]["run_id"], "run1");
+
+        let response = client
+            .get(format!("{}/runs?status=bogus", base_url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let run: serde_json::Value = client
+            .get(format!("{}/runs/run1", base_url))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(run["game_id"], "game1");
+
+        let response = client
+            .get(format!("{}/runs/missing", base_url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_health_shows_progress() {
+        let db = db_with_run("run1").await;
+        let selection = RunSelection {
+            auto: vec![("game1".to_string(), "cat1".to_string())],
+            ..Default::default()
+        };
+        let lease = Utc::now() + chrono::Duration::minutes(10);
+        db.claim_next_run(&selection, "w0", lease).await.unwrap();
+        db.record_run_progress("run1", 3600, Some(50.0))
+            .await
+            .unwrap();
+        let (base_url, token) = start_server(db, None).await;
+
+        let health: serde_json::Value = reqwest::get(format!("{}/health", base_url))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(health["processing"][0]["run_id"], "run1");
+        assert_eq!(health["processing"][0]["tick"], 3600);
+        assert_eq!(health["processing"][0]["percent"], 50.0);
+
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_reprocess_requires_token() {
+        let db = db_with_run("run1").await;
+        db.mark_run_failed("run1", Some("bad")).await.unwrap();
+        let (base_url, token) = start_server(db.clone(), Some("secret")).await;
+        let client = reqwest::Client::new();
+        let url = format!("{}/runs/run1/reprocess", base_url);
+
+        let response = client.post(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .post(&url)
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let run = db.get_run("run1").await.unwrap().unwrap();
+        assert_eq!(run.status, RunStatus::Discovered);
+
+        let lease = chrono::Utc::now() + chrono::Duration::minutes(10);
+        db.claim_run("run1", "host/0", lease)
+            .await
+            .unwrap()
+            .unwrap();
+        let response = client
+            .post(&url)
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let run = db.get_run("run1").await.unwrap().unwrap();
+        assert_eq!(run.status, RunStatus::Processing);
+
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_verify_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let src_rules = serde_yaml::from_str(
+            "games:\n  game1:\n    expected_mods: []\n    categories:\n      cat1: {}\n",
+        )
+        .unwrap();
+        let db = Database::in_memory().await.unwrap();
+        let jobs = VerifyJobs::new(
+            db.clone(),
+            src_rules,
+            dir.path().to_path_buf(),
+            "host".to_string(),
+        );
+        let (base_url, token) =
+            start_server_with_jobs(db.clone(), Some("secret"), Some(jobs)).await;
+        let client = reqwest::Client::new();
+        let url = format!("{}/verify", base_url);
+        let save = std::fs::read(test_utils::fixtures_dir().join("EMPTY_SAVE.zip")).unwrap();
+        let form = |category_id: &str| {
+            reqwest::multipart::Form::new()
+                .text("game_id", "game1")
+                .text("category_id", category_id.to_string())
+                .part(
+                    "save",
+                    reqwest::multipart::Part::bytes(save.clone()).file_name("my_run.zip"),
+                )
+        };
+
+        let response = client
+            .post(&url)
+            .multipart(form("cat1"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .post(&url)
+            .bearer_auth("secret")
+            .multipart(form("missing"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = client
+            .post(&url)
+            .bearer_auth("secret")
+            .multipart(reqwest::multipart::Form::new().text("game_id", "game1"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = client
+            .post(&url)
+            .bearer_auth("secret")
+            .multipart(form("cat1"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let job: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(job["status"], "queued");
+        let job_id = job["job_id"].as_str().unwrap().to_string();
+        assert_eq!(
+            db.claim_next_job("daemon", "host", chrono::Utc::now())
+                .await
+                .unwrap()
+                .unwrap()
+                .job_id,
+            job_id
+        );
+
+        let response = client
+            .get(format!("{}/verify/{}", base_url, job_id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let polled: serde_json::Value = client
+            .get(format!("{}/verify/{}", base_url, job_id))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(polled["job_id"], job_id.as_str());
+        assert_eq!(polled["category_id"], "cat1");
+
+        let response = client
+            .get(format!("{}/verify/missing", base_url))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        token.cancel();
+    }
+}
@@ -0,0 +1,185 @@
+//! Uploads the saves and reports of passed runs to the Internet Archive, for long-term public
+//! archival. Each run gets its own item, created by its first upload through the archive's
+//! S3-like API.
+
+use anyhow::{Context, Result, bail};
+use log::info;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::daemon::archive::file_body;
+use crate::daemon::config::Secret;
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct InternetArchiveConfig {
+    /// S3 keys of the uploading account, from https://archive.org/account/s3.php
+    pub access_key: Secret,
+    pub secret_key: Secret,
+    /// Items are named this followed by the run ID.
+    #[serde(default = "default_item_prefix")]
+    pub item_prefix: String,
+    #[serde(default = "default_collection")]
+    pub collection: String,
+    #[serde(default = "default_endpoint")]
+    pub endpoint: String,
+}
+
+fn default_item_prefix() -> String {
+    "factorio-speedrun-".to_string()
+}
+
+fn default_collection() -> String {
+    "open_source_software".to_string()
+}
+
+fn default_endpoint() -> String {
+    "https://s3.us.archive.org".to_string()
+}
+
+pub struct InternetArchive {
+    config: InternetArchiveConfig,
+    client: reqwest::Client,
+}
+
+impl InternetArchive {
+    pub fn new(config: InternetArchiveConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn identifier(&self, run_id: &str) -> String {
+        format!("{}{}", self.config.item_prefix, run_id)
+    }
+
+    /// The public page of the run's item.
+    pub fn item_url(&self, run_id: &str) -> String {
+        format!("https://archive.org/details/{}", self.identifier(run_id))
+    }
+
+    /// Uploads `files`, as `(name in the item, path)`, to the run's item, and returns the
+    /// item's URL. Files already in the item are replaced.
+    pub async fn upload_run(&self, run_id: &str, files: &[(String, PathBuf)]) -> Result<String> {
+        let identifier = self.identifier(run_id);
+        for (name, path) in files {
+            self.put(run_id, &identifier, name, path).await?;
+        }
+        info!(
+            "Uploaded run {} to Internet Archive item {}",
+            run_id, identifier
+        );
+        Ok(self.item_url(run_id))
+    }
+
+    async fn put(&self, run_id: &str, identifier: &str, name: &str, path: &Path) -> Result<()> {
+        let (body, len) = file_body(path).await?;
+        let url = format!(
+            "{}/{}/{}",
+            self.config.endpoint.trim_end_matches('/'),
+            identifier,
+            name
+        );
+        let response = self
+            .client
+            .put(&url)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!(
+                    "LOW {}:{}",
+                    self.config.access_key.expose(),
+                    self.config.secret_key.expose()
+                ),
+            )
+            // the item's metadata is only used when the first upload creates it
+            .header("x-archive-auto-make-bucket", "1")
+            .header("x-archive-meta-mediatype", "software")
+            .header("x-archive-meta-collection", &self.config.collection)
+            .header(
+                "x-archive-meta-title",
+                format!("Factorio speedrun {}", run_id),
+            )
+            .header(
+                "x-archive-meta-source",
+                format!("https://speedrun.com/runs/{}", run_id),
+            )
+            // saves and reports have nothing to derive
+            .header("x-archive-queue-derive", "0")
+            .header(reqwest::header::CONTENT_LENGTH, len)
+            .body(body)
+            .send()
+            .await
+            .context("Failed to send request to the Internet Archive")?;
+        if !response.status().is_success() {
+            bail!(
+                "Internet Archive upload of {}/{} failed with HTTP {}",
+                identifier,
+                name,
+                response.status()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config(endpoint: &str) -> InternetArchiveConfig {
+        let mut config: InternetArchiveConfig =
+            serde_yaml::from_str("access_key: access\nsecret_key: secret").unwrap();
+        config.endpoint = endpoint.to_string();
+        config
+    }
+
+    #[tokio::test]
+    async fn test_upload_run() {
+        let server = MockServer::start().await;
+        for name in ["save.zip", "report.json"] {
+            Mock::given(method("PUT"))
+                .and(path(format!("/factorio-speedrun-run1/{}", name)))
+                .and(header("authorization", "LOW access:secret"))
+                .and(header("x-archive-auto-make-bucket", "1"))
+                .and(header("x-archive-meta-collection", "open_source_software"))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let source = tempfile::tempdir().unwrap();
+        let files: Vec<(String, PathBuf)> = ["save.zip", "report.json"]
+            .into_iter()
+            .map(|name| {
+                let path = source.path().join(name);
+                std::fs::write(&path, name).unwrap();
+                (name.to_string(), path)
+            })
+            .collect();
+        let archive = InternetArchive::new(config(&server.uri()));
+        let url = archive.upload_run("run1", &files).await.unwrap();
+        assert_eq!(url, "https://archive.org/details/factorio-speedrun-run1");
+    }
+
+    #[tokio::test]
+    async fn test_upload_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&server)
+            .await;
+
+        let source = tempfile::NamedTempFile::new().unwrap();
+        let archive = InternetArchive::new(config(&server.uri()));
+        let err = archive
+            .upload_run("run1", &[("save.zip".to_string(), source.path().into())])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("HTTP 403"), "{}", err);
+    }
+}
@@ -0,0 +1,306 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::RunStatus;
+use crate::daemon::verify_jobs::JOBS_DIR;
+
+/// Deletes run working directories (saves, logs and reports) under the output dir, and
+/// those of verification jobs. Log excerpts and results stay in the database.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Artifacts of runs last updated, and of verification jobs finished, more than this many
+    /// days ago are deleted.
+    pub max_age_days: Option<u64>,
+    /// Artifacts of passed runs are deleted regardless of age.
+    pub delete_passed: bool,
+    /// Artifacts of runs with these statuses are never deleted.
+    pub keep_statuses: Vec<RunStatus>,
+    pub interval_seconds: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_age_days: None,
+            delete_passed: false,
+            keep_statuses: vec![RunStatus::Failed, RunStatus::NeedsReview],
+            interval_seconds: 3600,
+        }
+    }
+}
+
+impl RetentionConfig {
+    /// Whether a run's artifacts should be deleted. Unknown runs are judged by age only.
+    fn should_delete(
+        &self,
+        status: Option<RunStatus>,
+        updated_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        match status {
+            // queued or in flight, and may reuse their saves
+            Some(RunStatus::Discovered | RunStatus::Processing) => return false,
+            Some(status) if self.keep_statuses.contains(&status) => return false,
+            Some(RunStatus::Passed) if self.delete_passed => return true,
+            _ => {}
+        }
+        self.is_expired(updated_at, now)
+    }
+
+    fn is_expired(&self, time: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        self.max_age_days
+            .is_some_and(|days| now - time > chrono::Duration::days(days as i64))
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CleanupStats {
+    pub runs_cleaned: u64,
+    /// Not recorded in the database, which only counts runs.
+    pub jobs_cleaned: u64,
+    pub bytes_reclaimed: u64,
+}
+
+pub async fn run_janitor_loop(
+    db: Database,
+    output_dir: &Path,
+    config: RetentionConfig,
+    token: CancellationToken,
+) -> Result<()> {
+    let interval = Duration::from_secs(config.interval_seconds);
+    info!(
+        "Starting artifact janitor (interval: {}s)",
+        config.interval_seconds
+    );
+
+    loop {
+        match clean_artifacts(&db, output_dir, &config).await {
+            Ok(stats) if stats.runs_cleaned + stats.jobs_cleaned > 0 => {
+                info!(
+                    "Removed artifacts of {} run(s) and {} verification job(s), reclaiming {} MB",
+                    stats.runs_cleaned,
+                    stats.jobs_cleaned,
+                    stats.bytes_reclaimed / (1024 * 1024)
+                );
+                if let Err(e) = db.record_artifact_cleanup(&stats).await {
+                    warn!("Failed to record artifact cleanup: {:#}", e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => error!("Artifact cleanup failed: {:#}", e),
+        }
+
+        tokio::select! {
+            _ = token.cancelled() => {
+                info!("Janitor shutting down");
+                return Ok(());
+            }
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+/// Deletes the working directories under `output_dir` past the retention policy.
+pub async fn clean_artifacts(
+    db: &Database,
+    output_dir: &Path,
+    config: &RetentionConfig,
+) -> Result<CleanupStats> {
+    let now = Utc::now();
+    let mut stats = CleanupStats::default();
+    for entry in std::fs::read_dir(output_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let run_id = entry.file_name().to_string_lossy().into_owned();
+        if run_id == JOBS_DIR {
+            clean_job_artifacts(db, &entry.path(), config, now, &mut stats).await?;
+            continue;
+        }
+        let (status, updated_at) = match db.get_run(&run_id).await? {
+            Some(run) => (Some(run.status), run.updated_at),
+            None => (None, entry.metadata()?.modified()?.into()),
+        };
+        if !config.should_delete(status, updated_at, now) {
+            continue;
+        }
+        let path = entry.path();
+        let size = dir_size(&path);
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => {
+                info!("Removed artifacts of run {}", run_id);
+                stats.runs_cleaned += 1;
+                stats.bytes_reclaimed += size;
+            }
+            Err(e) => warn!("Failed to remove {}: {}", path.display(), e),
+        }
+    }
+    Ok(stats)
+}
+
+/// Deletes the directories of verification jobs finished longer ago than `max_age_days`.
+/// Those of jobs never submitted, whose upload failed, expire from when they were created.
+async fn clean_job_artifacts(
+    db: &Database,
+    jobs_dir: &Path,
+    config: &RetentionConfig,
+    now: DateTime<Utc>,
+    stats: &mut CleanupStats,
+) -> Result<()> {
+    for entry in std::fs::read_dir(jobs_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let job_id = entry.file_name().to_string_lossy().into_owned();
+        let expires_from = match db.get_job(&job_id).await? {
+            Some(job) => job.finished_at,
+            None => Some(entry.metadata()?.modified()?.into()),
+        };
+        if !expires_from.is_some_and(|time| config.is_expired(time, now)) {
+            continue;
+        }
+        let path = entry.path();
+        let size = dir_size(&path);
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => {
+                info!("Removed artifacts of verification job {}", job_id);
+                stats.jobs_cleaned += 1;
+                stats.bytes_reclaimed += size;
+            }
+            Err(e) => warn!("Failed to remove {}: {}", path.display(), e),
+        }
+    }
+    Ok(())
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::database::types::{JobSource, NewJob, NewRun};
+
+    async fn insert_run(db: &Database, output_dir: &Path, run_id: &str, status: RunStatus) {
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new(run_id, "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+        db.update_run_status(run_id, status, None).await.unwrap();
+        let run_dir = output_dir.join(run_id);
+        std::fs::create_dir_all(&run_dir).unwrap();
+        std::fs::write(run_dir.join("output.log"), vec![0; 100]).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_clean_artifacts() {
+        let db = Database::in_memory().await.unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let output_dir = output_dir.path();
+        insert_run(&db, output_dir, "passed", RunStatus::Passed).await;
+        insert_run(&db, output_dir, "failed", RunStatus::Failed).await;
+        insert_run(&db, output_dir, "queued", RunStatus::Discovered).await;
+
+        let config = RetentionConfig {
+            delete_passed: true,
+            ..Default::default()
+        };
+        let stats = clean_artifacts(&db, output_dir, &config).await.unwrap();
+        assert_eq!(
+            stats,
+            CleanupStats {
+                runs_cleaned: 1,
+                jobs_cleaned: 0,
+                bytes_reclaimed: 100,
+            }
+        );
+        assert!(!output_dir.join("passed").exists());
+        assert!(output_dir.join("failed").exists());
+        assert!(output_dir.join("queued").exists());
+    }
+
+    #[tokio::test]
+    async fn test_clean_job_artifacts() {
+        let db = Database::in_memory().await.unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let jobs_dir = output_dir.path().join(JOBS_DIR);
+        for job_id in ["finished", "queued", "not_submitted"] {
+            std::fs::create_dir_all(jobs_dir.join(job_id)).unwrap();
+            std::fs::write(jobs_dir.join(job_id).join("output.log"), vec![0; 100]).unwrap();
+            if job_id == "not_submitted" {
+                continue;
+            }
+            let job = NewJob {
+                job_id: job_id.to_string(),
+                source: JobSource::Cli,
+                game_id: "game1".to_string(),
+                category_id: "cat1".to_string(),
+                save_path: String::new(),
+                host: "host".to_string(),
+            };
+            db.insert_job(&job).await.unwrap();
+        }
+        db.finish_job("finished", Err("replay crashed"))
+            .await
+            .unwrap();
+
+        // the jobs dir isn't a run's
+        let config = RetentionConfig {
+            max_age_days: Some(1),
+            ..Default::default()
+        };
+        let stats = clean_artifacts(&db, output_dir.path(), &config)
+            .await
+            .unwrap();
+        assert_eq!(stats, CleanupStats::default());
+
+        let mut stats = CleanupStats::default();
+        let later = Utc::now() + chrono::Duration::days(2);
+        clean_job_artifacts(&db, &jobs_dir, &config, later, &mut stats)
+            .await
+            .unwrap();
+        assert_eq!(stats.jobs_cleaned, 2);
+        assert_eq!(stats.bytes_reclaimed, 200);
+        assert!(!jobs_dir.join("finished").exists());
+        assert!(jobs_dir.join("queued").exists());
+        assert!(!jobs_dir.join("not_submitted").exists());
+    }
+
+    #[test]
+    fn test_should_delete_by_age() {
+        let config = RetentionConfig {
+            max_age_days: Some(30),
+            ..Default::default()
+        };
+        let now = Utc::now();
+        let old = now - chrono::Duration::days(40);
+        let recent = now - chrono::Duration::days(1);
+        assert!(config.should_delete(Some(RunStatus::Passed), old, now));
+        assert!(!config.should_delete(Some(RunStatus::Passed), recent, now));
+        assert!(config.should_delete(Some(RunStatus::Error), old, now));
+        assert!(config.should_delete(None, old, now));
+        assert!(!config.should_delete(Some(RunStatus::Failed), old, now));
+        assert!(!config.should_delete(Some(RunStatus::NeedsReview), old, now));
+        assert!(!config.should_delete(Some(RunStatus::Processing), old, now));
+    }
+}
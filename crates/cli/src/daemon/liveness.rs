@@ -0,0 +1,175 @@
+//! Tracks when the daemon last polled and processed runs, for the status API and a liveness
+//! file that watchdogs (e.g. systemd path units or monit) can check the age of.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LivenessConfig {
+    /// Rewritten with the daemon's [`LivenessSnapshot`] every `interval_seconds`.
+    pub file: PathBuf,
+    #[serde(default = "default_liveness_interval_seconds")]
+    pub interval_seconds: u64,
+    /// Stop updating the file once no poll has succeeded for this long, so a wedged poller
+    /// shows up as a stale file.
+    #[serde(default)]
+    pub max_poll_age_seconds: Option<u64>,
+}
+
+fn default_liveness_interval_seconds() -> u64 {
+    30
+}
+
+/// Shared between the poller, the processor and the status API.
+#[derive(Debug, Clone)]
+pub struct Liveness {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    started_at: DateTime<Utc>,
+    last_poll_at: Mutex<Option<DateTime<Utc>>>,
+    last_processed_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LivenessSnapshot {
+    pub started_at: DateTime<Utc>,
+    pub uptime_seconds: i64,
+    pub last_poll_at: Option<DateTime<Utc>>,
+    pub last_processed_at: Option<DateTime<Utc>>,
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self::new(Utc::now())
+    }
+}
+
+impl Liveness {
+    pub fn new(started_at: DateTime<Utc>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                started_at,
+                last_poll_at: Mutex::new(None),
+                last_processed_at: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Call after polling a category succeeded.
+    pub fn record_poll(&self) {
+        *self.inner.last_poll_at.lock().unwrap() = Some(Utc::now());
+    }
+
+    /// Call after a run finished processing.
+    pub fn record_processed(&self) {
+        *self.inner.last_processed_at.lock().unwrap() = Some(Utc::now());
+    }
+
+    pub fn snapshot(&self, now: DateTime<Utc>) -> LivenessSnapshot {
+        LivenessSnapshot {
+            started_at: self.inner.started_at,
+            uptime_seconds: (now - self.inner.started_at).num_seconds(),
+            last_poll_at: *self.inner.last_poll_at.lock().unwrap(),
+            last_processed_at: *self.inner.last_processed_at.lock().unwrap(),
+        }
+    }
+}
+
+impl LivenessSnapshot {
+    /// Whether the last poll, or the start if nothing was polled yet, is older than `max_age`.
+    fn poll_is_stale(&self, max_age: Duration, now: DateTime<Utc>) -> bool {
+        let last_poll = self.last_poll_at.unwrap_or(self.started_at);
+        (now - last_poll).to_std().is_ok_and(|age| age > max_age)
+    }
+}
+
+pub async fn run_liveness_loop(
+    config: LivenessConfig,
+    liveness: Liveness,
+    token: CancellationToken,
+) -> Result<()> {
+    info!(
+        "Writing liveness file {} every {}s",
+        config.file.display(),
+        config.interval_seconds
+    );
+    let interval = Duration::from_secs(config.interval_seconds);
+    let mut was_stale = false;
+
+    loop {
+        let now = Utc::now();
+        let snapshot = liveness.snapshot(now);
+        let stale = config
+            .max_poll_age_seconds
+            .is_some_and(|max_age| snapshot.poll_is_stale(Duration::from_secs(max_age), now));
+        if stale && !was_stale {
+            warn!("No successful poll recently; no longer updating the liveness file");
+        } else if !stale && let Err(e) = write_liveness_file(&config.file, &snapshot) {
+            warn!("{:#}", e);
+        }
+        was_stale = stale;
+
+        tokio::select! {
+            _ = token.cancelled() => return Ok(()),
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+/// Replaces the file atomically, so monitors never read it half-written.
+fn write_liveness_file(path: &Path, snapshot: &LivenessSnapshot) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_vec(snapshot)?)
+        .and_then(|()| std::fs::rename(&tmp, path))
+        .with_context(|| format!("Failed to write liveness file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_and_staleness() {
+        let started_at: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        let liveness = Liveness::new(started_at);
+        let now = started_at + chrono::Duration::minutes(10);
+
+        let snapshot = liveness.snapshot(now);
+        assert_eq!(snapshot.uptime_seconds, 600);
+        assert_eq!(snapshot.last_poll_at, None);
+        assert!(snapshot.poll_is_stale(Duration::from_secs(300), now));
+        assert!(!snapshot.poll_is_stale(Duration::from_secs(900), now));
+
+        liveness.record_poll();
+        liveness.record_processed();
+        let snapshot = liveness.snapshot(Utc::now());
+        assert!(snapshot.last_poll_at.is_some());
+        assert!(snapshot.last_processed_at.is_some());
+        assert!(!snapshot.poll_is_stale(Duration::from_secs(300), Utc::now()));
+    }
+
+    #[test]
+    fn test_write_liveness_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("alive.json");
+        let snapshot = Liveness::default().snapshot(Utc::now());
+        write_liveness_file(&path, &snapshot).unwrap();
+
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["last_poll_at"], serde_json::Value::Null);
+        assert!(written["uptime_seconds"].is_i64());
+        assert!(!dir.path().join("alive.tmp").exists());
+    }
+}
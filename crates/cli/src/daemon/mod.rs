@@ -1,24 +1,47 @@
 use anyhow::{Context, Result};
-use log::info;
+use factorio_manager::factorio_install_dir::FactorioInstallDir;
+use log::{info, warn};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Notify;
 use tokio_util::sync::CancellationToken;
+use zip_downloader::throttle::DownloadThrottles;
 
+pub mod archive;
+pub mod backlog;
 pub mod bot_notifier;
+pub mod category_sync;
 pub mod config;
 pub mod database;
+pub mod discord_notifier;
+pub mod dry_run;
+pub mod email_notifier;
+pub mod factorio_log;
+pub mod hooks;
+pub mod http_api;
+pub mod internet_archive;
+pub mod janitor;
+pub mod liveness;
+pub mod notifier;
 pub mod poller;
 pub mod processor;
+pub mod progress;
 pub mod retry;
+pub mod run_links;
 pub mod run_processing;
+pub mod scheduling;
+pub mod shutdown;
 pub mod speedrun_api;
+pub mod verify_jobs;
+pub mod webhook;
 
-pub use bot_notifier::BotNotifierHandle;
 pub use config::{DaemonConfig, SrcRunRules};
+pub use notifier::{NotificationDispatcher, Notifier};
 pub use poller::{poll_speedrun_com, poll_speedrun_com_loop};
 pub use processor::{ProcessResult, find_run_to_process, process_runs_loop};
-pub use run_processing::{RunProcessingContext, download_and_run_replay};
-pub use speedrun_api::{SpeedrunClient, SpeedrunOps};
+pub use run_processing::{RunProcessingContext, RunProcessor, download_and_run_replay};
+pub use speedrun_api::SpeedrunOps;
 
 pub async fn run_daemon(
     config: DaemonConfig,
@@ -28,66 +51,246 @@ pub async fn run_daemon(
     info!("Starting daemon with config: {:?}", config);
     info!("Monitoring {} game(s)", src_rules.games.len());
 
-    let db = database::connection::Database::new(&config.database_path)
+    let db = config
+        .open_database()
         .await
         .context("Failed to initialize database")?;
 
-    let client = SpeedrunClient::new()?;
+    let client = config.speedrun_client()?;
     let speedrun_ops = SpeedrunOps::new(&client).with_db(db.clone());
 
     std::fs::create_dir_all(&config.install_dir)?;
     std::fs::create_dir_all(&config.output_dir)?;
 
+    // a corrupted install would otherwise fail replays and be blamed on the runs
+    let mut repaired = 0;
+    for install_dir in install_dirs(&config.install_dir)? {
+        repaired += FactorioInstallDir::new(&install_dir)?
+            .with_image(config.factorio_image.clone())
+            .with_credentials(config.factorio_credentials())
+            .verify_and_repair()
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to verify Factorio installations in {}",
+                    install_dir.display()
+                )
+            })?
+            .len();
+    }
+    if repaired > 0 {
+        info!("Repaired {} Factorio installation(s)", repaired);
+    }
+
+    let report_signing_key = config
+        .report_signing_key()
+        .context("Failed to load report signing key")?;
+    let rule_descriptions = config.rule_descriptions()?;
+    let internet_archive = config.internet_archive();
+
+    let instance_id = config.instance_id();
+    if db.record_daemon_start(&instance_id).await? == Some(false) {
+        warn!(
+            "Instance {} did not shut down cleanly last time; recovering its runs",
+            instance_id
+        );
+    }
+
+    let shutdown = shutdown::ShutdownCoordinator::new(
+        token,
+        Duration::from_secs(config.shutdown_drain_seconds),
+    );
+    // notifiers outlive the processor so they can report runs finished while draining
+    let notifier_token = CancellationToken::new();
+
     let work_notify = Arc::new(Notify::new());
+    let liveness = liveness::Liveness::default();
 
-    let bot_notifier = if let Some(cfg) = &config.bot_notifier {
-        let auth_token = std::env::var(bot_notifier::AUTH_TOKEN_ENV_VAR)
-            .context("RUNNER_STATUS_AUTH_TOKEN env var is required for bot notifier")?;
-        let (handle, rx) = BotNotifierHandle::new();
-        let join_handle = tokio::spawn(bot_notifier::run_bot_notifier_actor(
-            rx,
+    let notifiers = configured_notifiers(&config, &db, &speedrun_ops)?;
+    let backlog_alert = config.backlog_alert.clone().map(|cfg| {
+        tokio::spawn(backlog::run_backlog_alert_loop(
+            cfg,
             db.clone(),
-            cfg.clone(),
-            token.clone(),
-            auth_token,
-        ));
-        Some((handle, join_handle))
-    } else {
-        None
-    };
+            notifiers.clone(),
+            shutdown.drain_token(),
+        ))
+    });
+    let (notifications, notifier_workers) =
+        NotificationDispatcher::start(&db, notifiers, notifier_token.clone())
+            .await
+            .context("Failed to start notifiers")?;
 
-    info!("Daemon started successfully");
+    let liveness_file = config.liveness.clone().map(|cfg| {
+        tokio::spawn(liveness::run_liveness_loop(
+            cfg,
+            liveness.clone(),
+            shutdown.drain_token(),
+        ))
+    });
 
-    let bot_notifier_handle = bot_notifier.as_ref().map(|(h, _)| h.clone());
+    let janitor = config.retention.clone().map(|cfg| {
+        let db = db.clone();
+        let output_dir = config.output_dir.clone();
+        let token = shutdown.drain_token();
+        tokio::spawn(async move { janitor::run_janitor_loop(db, &output_dir, cfg, token).await })
+    });
 
+    tokio::spawn(category_sync::log_category_changes(
+        client.clone(),
+        db.clone(),
+        src_rules.clone(),
+    ));
+
+    info!("Daemon started successfully");
+
+    let factorio_credentials = config.factorio_credentials();
     let ctx = RunProcessingContext {
-        db,
+        db: db.clone(),
         speedrun_ops,
         src_rules,
+        install_quota_bytes: config.install_quota_bytes(),
+        sandbox: config.sandbox,
+        factorio_image: config.factorio_image,
+        factorio_credentials,
         install_dir: config.install_dir,
         output_dir: config.output_dir,
         retry_config: config.retry,
-        bot_notifier: bot_notifier_handle,
+        scheduling: scheduling::Scheduling::new(&config.scheduling),
+        notifications,
+        liveness,
+        download_throttles: DownloadThrottles::new(&config.download_limits),
+        shutdown: shutdown.abort_token(),
+        archive: config.archive.as_ref().map(|archive| archive.build()),
+        internet_archive,
+        factorio_log: config.factorio_log.clone(),
+        report_signing_key,
+        hooks: config.hooks.map(Arc::new),
+        rule_descriptions,
     };
 
+    let verify_jobs = verify_jobs::VerifyJobs::new(
+        db.clone(),
+        ctx.src_rules.clone(),
+        ctx.output_dir.join(verify_jobs::JOBS_DIR),
+        config::hostname().unwrap_or_else(|| instance_id.clone()),
+    );
+    let verify_jobs_worker = verify_jobs::run_verify_jobs_loop(
+        ctx.clone(),
+        verify_jobs.clone(),
+        &instance_id,
+        shutdown.drain_token(),
+    );
+    let http_api = config.http_api.clone().map(|cfg| {
+        tokio::spawn(http_api::run_http_api(
+            cfg,
+            db.clone(),
+            work_notify.clone(),
+            ctx.liveness.clone(),
+            Some(verify_jobs),
+            shutdown.drain_token(),
+        ))
+    });
+
     let poller = poll_speedrun_com_loop(
         ctx.clone(),
         config.polling,
         work_notify.clone(),
-        token.clone(),
+        shutdown.drain_token(),
+    );
+    let processor = process_runs_loop(
+        ctx,
+        &instance_id,
+        config.max_concurrent_runs,
+        work_notify.clone(),
+        shutdown.drain_token(),
     );
-    let processor = process_runs_loop(ctx, work_notify.clone(), token);
 
-    let (poller_result, processor_result) = tokio::join!(poller, processor);
+    let (poller_result, processor_result, verify_jobs_result) =
+        tokio::join!(poller, processor, verify_jobs_worker);
+    shutdown.finish();
+    notifier_token.cancel();
 
-    if let Some((_, join_handle)) = bot_notifier {
-        if let Ok(Err(e)) = join_handle.await {
-            log::error!("Bot notifier exited with error: {:#}", e);
-        }
+    for join_handle in notifier_workers {
+        let _ = join_handle.await;
     }
 
-    poller_result.and(processor_result)?;
+    if let Some(join_handle) = http_api
+        && let Ok(Err(e)) = join_handle.await
+    {
+        log::error!("HTTP API exited with error: {:#}", e);
+    }
+
+    if let Some(join_handle) = janitor
+        && let Ok(Err(e)) = join_handle.await
+    {
+        log::error!("Janitor exited with error: {:#}", e);
+    }
 
+    if let Some(join_handle) = liveness_file
+        && let Ok(Err(e)) = join_handle.await
+    {
+        log::error!("Liveness file writer exited with error: {:#}", e);
+    }
+
+    if let Some(join_handle) = backlog_alert
+        && let Ok(Err(e)) = join_handle.await
+    {
+        log::error!("Backlog alerts exited with error: {:#}", e);
+    }
+
+    poller_result
+        .and(processor_result)
+        .and(verify_jobs_result)?;
+
+    db.record_clean_shutdown(&instance_id).await?;
     info!("Daemon shutting down");
     Ok(())
 }
+
+/// The notifiers enabled in `config`.
+/// The install dirs replays use: the configured one, each worker's, and verification jobs'.
+fn install_dirs(install_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = vec![install_dir.to_path_buf()];
+    for entry in std::fs::read_dir(install_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if entry.file_type()?.is_dir()
+            && (name.starts_with("worker-") || name == verify_jobs::JOBS_DIR)
+        {
+            dirs.push(entry.path());
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+fn configured_notifiers(
+    config: &DaemonConfig,
+    db: &database::connection::Database,
+    speedrun_ops: &SpeedrunOps,
+) -> Result<Vec<Arc<dyn Notifier>>> {
+    let mut notifiers: Vec<Arc<dyn Notifier>> = Vec::new();
+    if let Some(cfg) = &config.bot_notifier {
+        notifiers.push(Arc::new(bot_notifier::BotNotifier::from_config(
+            db.clone(),
+            cfg.clone(),
+        )?));
+    }
+    if let Some(cfg) = &config.discord_notifier {
+        notifiers.push(Arc::new(discord_notifier::DiscordNotifier::from_env(
+            speedrun_ops.clone(),
+            cfg.clone(),
+        )?));
+    }
+    if let Some(cfg) = &config.webhooks {
+        notifiers.push(Arc::new(webhook::WebhookNotifier::from_env(cfg.clone())));
+    }
+    if let Some(cfg) = &config.email {
+        notifiers.push(Arc::new(email_notifier::EmailNotifier::new(
+            speedrun_ops.clone(),
+            cfg.clone(),
+        )));
+    }
+    Ok(notifiers)
+}
@@ -0,0 +1,458 @@
+//! Delivers run status changes to the configured notifiers. Every status change is queued in
+//! the database for each registered notifier, and each notifier works through its own queue,
+//! so a backend that is down only delays its own notifications, which are retried with
+//! backoff, across restarts too.
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::Utc;
+use log::{info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::daemon::backlog::BacklogSummary;
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::{QueuedNotification, Review, Run};
+use crate::run_replay::ReportSummary;
+
+const BATCH_SIZE: u32 = 50;
+/// How often queues are checked for retries that became due.
+const RETRY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+const DEFAULT_MAX_ATTEMPTS: u32 = 10;
+
+/// A run's current status, with its report and review if it has them.
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub run: Run,
+    pub report: Option<ReportSummary>,
+    pub review: Option<Review>,
+    /// Where the run's save and report were published, if it passed.
+    pub public_archive_url: Option<String>,
+}
+
+impl Notification {
+    pub async fn load(db: &Database, run_id: &str) -> Result<Option<Self>> {
+        let Some(run) = db.get_run(run_id).await? else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            report: db.get_report_summary(run_id).await?,
+            review: db.get_review(run_id).await?,
+            public_archive_url: db.get_public_archive_url(run_id).await?,
+            run,
+        }))
+    }
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Names the notifier's queue in the database.
+    fn name(&self) -> &'static str;
+
+    /// Whether to notify of `run`'s current status; other notifications are dropped.
+    fn accepts(&self, _run: &Run) -> bool {
+        true
+    }
+
+    /// Attempts at a notification before it is dropped, or `None` to retry it until it is
+    /// delivered.
+    fn max_attempts(&self) -> Option<u32> {
+        Some(DEFAULT_MAX_ATTEMPTS)
+    }
+
+    /// Fails if the notification should be retried.
+    async fn notify(&self, notification: &Notification) -> Result<()>;
+
+    /// Delivers several notifications, returning one result per notification.
+    async fn notify_batch(&self, notifications: &[Notification]) -> Vec<Result<()>> {
+        let mut results = Vec::with_capacity(notifications.len());
+        for notification in notifications {
+            results.push(self.notify(notification).await);
+        }
+        results
+    }
+
+    /// How long to wait for more status changes before delivering them together.
+    fn batch_delay(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn heartbeat_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Sent every [`Self::heartbeat_interval`], starting on startup.
+    async fn heartbeat(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Sent while runs wait in the queue past the backlog SLA.
+    async fn backlog_alert(&self, _summary: &BacklogSummary) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Wakes the notifier workers when runs change, so they don't wait for the next retry check.
+#[derive(Clone, Default)]
+pub struct NotificationDispatcher {
+    wakers: Arc<Vec<Arc<Notify>>>,
+}
+
+impl NotificationDispatcher {
+    /// Registers `notifiers` as the ones status changes are queued for, and spawns a worker
+    /// for each, which delivers what's left in its queue once `token` is cancelled and exits.
+    pub async fn start(
+        db: &Database,
+        notifiers: Vec<Arc<dyn Notifier>>,
+        token: CancellationToken,
+    ) -> Result<(Self, Vec<JoinHandle<()>>)> {
+        let names: Vec<&str> = notifiers.iter().map(|notifier| notifier.name()).collect();
+        db.register_notifiers(&names).await?;
+
+        let mut wakers = Vec::new();
+        let mut workers = Vec::new();
+        for notifier in notifiers {
+            let wake = Arc::new(Notify::new());
+            wakers.push(wake.clone());
+            workers.push(tokio::spawn(run_notifier_worker(
+                db.clone(),
+                notifier,
+                wake,
+                token.clone(),
+            )));
+        }
+        Ok((
+            Self {
+                wakers: Arc::new(wakers),
+            },
+            workers,
+        ))
+    }
+
+    /// Call after changing runs' statuses.
+    pub fn wake(&self) {
+        for wake in self.wakers.iter() {
+            wake.notify_one();
+        }
+    }
+}
+
+async fn run_notifier_worker(
+    db: Database,
+    notifier: Arc<dyn Notifier>,
+    wake: Arc<Notify>,
+    token: CancellationToken,
+) {
+    let name = notifier.name();
+    info!("Starting {} notifier", name);
+    let mut heartbeat = notifier.heartbeat_interval().map(|period| {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        interval
+    });
+
+    loop {
+        deliver_due(&db, notifier.as_ref()).await;
+
+        tokio::select! {
+            _ = wake.notified() => {
+                tokio::select! {
+                    _ = tokio::time::sleep(notifier.batch_delay()) => {}
+                    _ = token.cancelled() => {}
+                }
+            }
+            _ = tokio::time::sleep(RETRY_CHECK_INTERVAL) => {}
+            _ = async {
+                match &mut heartbeat {
+                    Some(interval) => interval.tick().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                if let Err(e) = notifier.heartbeat().await {
+                    warn!("{} notifier heartbeat failed: {:#}", name, e);
+                }
+            }
+            _ = token.cancelled() => {
+                deliver_due(&db, notifier.as_ref()).await;
+                info!("{} notifier shutting down", name);
+                return;
+            }
+        }
+    }
+}
+
+/// Delivers the notifier's due notifications, rescheduling those that fail.
+async fn deliver_due(db: &Database, notifier: &dyn Notifier) {
+    loop {
+        match deliver_batch(db, notifier).await {
+            Ok(true) => return,
+            Ok(false) => {}
+            Err(e) => {
+                warn!(
+                    "Failed to deliver {} notifications: {:#}",
+                    notifier.name(),
+                    e
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Returns whether the queue had no more due notifications.
+async fn deliver_batch(db: &Database, notifier: &dyn Notifier) -> Result<bool> {
+    let name = notifier.name();
+    let queued = db.due_notifications(name, Utc::now(), BATCH_SIZE).await?;
+    let drained = queued.len() < BATCH_SIZE as usize;
+
+    let mut sending = Vec::new();
+    let mut notifications = Vec::new();
+    for entry in queued {
+        match Notification::load(db, &entry.run_id).await? {
+            Some(notification) if notifier.accepts(&notification.run) => {
+                sending.push(entry);
+                notifications.push(notification);
+            }
+            _ => db.complete_notification(name, &entry).await?,
+        }
+    }
+    if notifications.is_empty() {
+        return Ok(drained);
+    }
+
+    let results = notifier.notify_batch(&notifications).await;
+    for (entry, result) in sending.iter().zip(results) {
+        match result {
+            Ok(()) => db.complete_notification(name, entry).await?,
+            Err(e) => reschedule(db, notifier, entry, &e).await?,
+        }
+    }
+    Ok(drained)
+}
+
+async fn reschedule(
+    db: &Database,
+    notifier: &dyn Notifier,
+    entry: &QueuedNotification,
+    error: &anyhow::Error,
+) -> Result<()> {
+    let name = notifier.name();
+    let attempts = entry.attempts + 1;
+    if notifier
+        .max_attempts()
+        .is_some_and(|max_attempts| attempts >= max_attempts)
+    {
+        warn!(
+            "Giving up on {} notification for run {} after {} attempts: {:#}",
+            name, entry.run_id, attempts, error
+        );
+        return db.complete_notification(name, entry).await;
+    }
+    let delay = retry_delay(entry.attempts);
+    warn!(
+        "{} notification for run {} failed, retrying in {}s: {:#}",
+        name,
+        entry.run_id,
+        delay.as_secs(),
+        error
+    );
+    let next_attempt_at = Utc::now() + chrono::Duration::from_std(delay)?;
+    db.retry_notification(name, entry, next_attempt_at, &format!("{:#}", error))
+        .await
+}
+
+fn retry_delay(attempts: u32) -> Duration {
+    INITIAL_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(attempts))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// Fails every notification of a batch delivered at once with `error`.
+pub fn batch_failed(len: usize, error: &anyhow::Error) -> Vec<Result<()>> {
+    (0..len).map(|_| Err(anyhow!("{:#}", error))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::database::types::{NewRun, RunStatus};
+    use std::sync::Mutex;
+
+    /// Records delivered run ids, failing runs in `failing`.
+    #[derive(Default)]
+    struct RecordingNotifier {
+        delivered: Mutex<Vec<String>>,
+        failing: Vec<&'static str>,
+        retry_forever: bool,
+    }
+
+    #[async_trait]
+    impl Notifier for RecordingNotifier {
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+
+        fn accepts(&self, run: &Run) -> bool {
+            run.is_final()
+        }
+
+        fn max_attempts(&self) -> Option<u32> {
+            (!self.retry_forever).then_some(DEFAULT_MAX_ATTEMPTS)
+        }
+
+        async fn notify(&self, notification: &Notification) -> Result<()> {
+            if self.failing.contains(&notification.run.run_id.as_str()) {
+                return Err(anyhow!("unreachable"));
+            }
+            self.delivered
+                .lock()
+                .unwrap()
+                .push(notification.run.run_id.clone());
+            Ok(())
+        }
+    }
+
+    async fn insert_test_run(db: &Database, run_id: &str) {
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        let new_run = NewRun::new(run_id, "game1", "cat1", submitted_date);
+        db.insert_run(new_run).await.unwrap();
+    }
+
+    async fn queued(db: &Database) -> Vec<QueuedNotification> {
+        db.due_notifications("recording", Utc::now() + chrono::Duration::days(1), 100)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(0), Duration::from_secs(30));
+        assert_eq!(retry_delay(3), Duration::from_secs(240));
+        assert_eq!(retry_delay(20), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_status_changes_are_queued_per_notifier() {
+        let db = Database::in_memory().await.unwrap();
+        insert_test_run(&db, "before").await;
+        db.register_notifiers(&["recording", "other"])
+            .await
+            .unwrap();
+        insert_test_run(&db, "run1").await;
+        let other = db
+            .due_notifications("other", Utc::now(), 100)
+            .await
+            .unwrap();
+        assert_eq!(other.len(), 1);
+
+        // another daemon may still deliver a notifier this one doesn't know
+        db.register_notifiers(&["recording"]).await.unwrap();
+        let entries = queued(&db).await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].run_id.as_str(), entries[0].seq), ("run1", 0));
+        assert_eq!(
+            db.due_notifications("other", Utc::now(), 100)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // a change while a delivery is in flight stays queued
+        db.mark_run_passed("run1").await.unwrap();
+        db.complete_notification("recording", &entries[0])
+            .await
+            .unwrap();
+        let entries = queued(&db).await;
+        assert_eq!(entries[0].seq, 1);
+        db.complete_notification("recording", &entries[0])
+            .await
+            .unwrap();
+        assert!(queued(&db).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_deliver_due() {
+        let db = Database::in_memory().await.unwrap();
+        db.register_notifiers(&["recording"]).await.unwrap();
+        for run_id in ["passed", "failing", "pending"] {
+            insert_test_run(&db, run_id).await;
+        }
+        db.mark_run_passed("passed").await.unwrap();
+        db.mark_run_failed("failing", Some("bad")).await.unwrap();
+
+        let notifier = RecordingNotifier {
+            failing: vec!["failing"],
+            ..Default::default()
+        };
+        deliver_due(&db, &notifier).await;
+
+        assert_eq!(*notifier.delivered.lock().unwrap(), vec!["passed"]);
+        // unfinished runs are dropped, failed deliveries wait for their retry
+        let entries = queued(&db).await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            (entries[0].run_id.as_str(), entries[0].attempts),
+            ("failing", 1)
+        );
+        assert!(
+            db.due_notifications("recording", Utc::now(), 100)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let db = Database::in_memory().await.unwrap();
+        db.register_notifiers(&["recording"]).await.unwrap();
+        insert_test_run(&db, "failing").await;
+        db.update_run_status("failing", RunStatus::Failed, None)
+            .await
+            .unwrap();
+
+        let notifier = RecordingNotifier {
+            failing: vec!["failing"],
+            ..Default::default()
+        };
+        for _ in 1..DEFAULT_MAX_ATTEMPTS {
+            let entry = queued(&db).await.remove(0);
+            db.retry_notification("recording", &entry, Utc::now(), "unreachable")
+                .await
+                .unwrap();
+        }
+        deliver_due(&db, &notifier).await;
+        assert!(queued(&db).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retries_forever_without_max_attempts() {
+        let db = Database::in_memory().await.unwrap();
+        db.register_notifiers(&["recording"]).await.unwrap();
+        insert_test_run(&db, "failing").await;
+        db.update_run_status("failing", RunStatus::Failed, None)
+            .await
+            .unwrap();
+
+        let notifier = RecordingNotifier {
+            failing: vec!["failing"],
+            retry_forever: true,
+            ..Default::default()
+        };
+        for _ in 1..DEFAULT_MAX_ATTEMPTS {
+            let entry = queued(&db).await.remove(0);
+            db.retry_notification("recording", &entry, Utc::now(), "unreachable")
+                .await
+                .unwrap();
+        }
+        deliver_due(&db, &notifier).await;
+        let entries = queued(&db).await;
+        assert_eq!(entries[0].attempts, DEFAULT_MAX_ATTEMPTS);
+    }
+}
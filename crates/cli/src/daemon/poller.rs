@@ -1,15 +1,19 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use log::{error, info};
+use log::{error, info, warn};
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Notify;
+use tokio::time::Instant;
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 use crate::daemon::SpeedrunOps;
 use crate::daemon::database::types::NewRun;
 use crate::daemon::speedrun_api::{ApiError, RunsQuery};
 
-use super::config::PollingConfig;
+use super::config::{PollingConfig, SrcRunRules};
 use super::run_processing::RunProcessingContext;
 
 pub async fn poll_speedrun_com_loop(
@@ -18,63 +22,194 @@ pub async fn poll_speedrun_com_loop(
     work_notify: Arc<Notify>,
     token: CancellationToken,
 ) -> Result<()> {
-    let poll_interval = std::time::Duration::from_secs(config.poll_interval_seconds);
-
     info!(
-        "Starting speedrun.com poller (interval: {}s)",
-        config.poll_interval_seconds
+        "Starting speedrun.com poller (interval: {}s, {} override(s))",
+        config.poll_interval_seconds,
+        config.intervals.len()
     );
 
+    let mut schedule = PollSchedule::new(&ctx.src_rules, Instant::now());
     loop {
+        let next_poll = schedule.next_poll();
         tokio::select! {
             _ = token.cancelled() => {
                 info!("Poller shutting down");
                 return Ok(());
             }
-            result = poll_speedrun_com(&ctx, &config, &work_notify) => {
-                if let Err(e) = result {
-                    error!("Speedrun.com poll iteration failed: {:#}", e);
+            _ = sleep_until_or_forever(next_poll) => {}
+        }
+
+        for (game_id, category_id) in schedule.due(Instant::now()) {
+            let result = tokio::select! {
+                _ = token.cancelled() => {
+                    info!("Poller shutting down");
+                    return Ok(());
                 }
-            }
+                result = poll_one(&ctx, &config, &game_id, &category_id, &work_notify) => result,
+            };
+            schedule.record(
+                &game_id,
+                &category_id,
+                result.is_ok(),
+                &config,
+                Instant::now(),
+            );
         }
+    }
+}
 
-        tokio::select! {
-            _ = token.cancelled() => {
-                info!("Poller shutting down");
-                return Ok(());
-            }
-            _ = tokio::time::sleep(poll_interval) => {}
+async fn sleep_until_or_forever(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// When each category is next polled. Categories are polled at their configured interval,
+/// backing off while their polls fail.
+struct PollSchedule {
+    categories: BTreeMap<(String, String), CategorySchedule>,
+}
+
+struct CategorySchedule {
+    next_poll: Instant,
+    failures: u32,
+}
+
+impl PollSchedule {
+    fn new(src_rules: &SrcRunRules, now: Instant) -> Self {
+        let categories = src_rules
+            .games
+            .iter()
+            .flat_map(|(game_id, game_config)| {
+                game_config.categories.keys().map(|category_id| {
+                    let schedule = CategorySchedule {
+                        next_poll: now,
+                        failures: 0,
+                    };
+                    ((game_id.clone(), category_id.clone()), schedule)
+                })
+            })
+            .collect();
+        Self { categories }
+    }
+
+    fn next_poll(&self) -> Option<Instant> {
+        self.categories.values().map(|c| c.next_poll).min()
+    }
+
+    fn due(&self, now: Instant) -> Vec<(String, String)> {
+        self.categories
+            .iter()
+            .filter(|(_, schedule)| schedule.next_poll <= now)
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    fn record(
+        &mut self,
+        game_id: &str,
+        category_id: &str,
+        succeeded: bool,
+        config: &PollingConfig,
+        now: Instant,
+    ) {
+        let Some(schedule) = self
+            .categories
+            .get_mut(&(game_id.to_string(), category_id.to_string()))
+        else {
+            return;
+        };
+        schedule.failures = if succeeded {
+            0
+        } else {
+            schedule.failures.saturating_add(1)
+        };
+        let interval = config.interval(game_id, category_id);
+        let delay = next_poll_delay(
+            interval,
+            schedule.failures,
+            config.max_backoff(),
+            fastrand::f64(),
+        );
+        if schedule.failures > 0 {
+            warn!(
+                "Polling game={}, category={} failed {} time(s) in a row; retrying in {}s",
+                game_id,
+                category_id,
+                schedule.failures,
+                delay.as_secs()
+            );
         }
+        schedule.next_poll = now + delay;
     }
 }
 
+/// The interval, or after `failures` consecutive failures, the interval doubled per failure
+/// up to `max_backoff`, scaled by half to all of it by `jitter` (0 to 1) so that categories
+/// failing together spread out.
+fn next_poll_delay(
+    interval: Duration,
+    failures: u32,
+    max_backoff: Duration,
+    jitter: f64,
+) -> Duration {
+    if failures == 0 {
+        return interval;
+    }
+    let backoff = interval
+        .saturating_mul(2u32.saturating_pow(failures))
+        .min(max_backoff.max(interval));
+    backoff.mul_f64(0.5 + jitter / 2.0).max(interval)
+}
+
+/// Polls every category once.
 pub async fn poll_speedrun_com(
     ctx: &RunProcessingContext,
     config: &PollingConfig,
     work_notify: &Notify,
 ) -> Result<()> {
-    let cutoff_date = ctx
-        .db
-        .get_earliest_submitted_date()
-        .await?
-        .unwrap_or_else(|| Utc::now() - chrono::Duration::days(config.lookback_days as i64));
-
     for (game_id, game_config) in &ctx.src_rules.games {
         for category_id in game_config.categories.keys() {
-            if let Err(e) = poll_category(ctx, game_id, category_id, cutoff_date, work_notify).await
-            {
-                let game_category = ctx
-                    .speedrun_ops
-                    .format_game_category(game_id, category_id)
-                    .await;
-                error!("Failed to poll {}: {:#}", game_category, e);
-            }
+            let _ = poll_one(ctx, config, game_id, category_id, work_notify).await;
         }
     }
 
     Ok(())
 }
 
+/// Polls a category, logging any error.
+async fn poll_one(
+    ctx: &RunProcessingContext,
+    config: &PollingConfig,
+    game_id: &str,
+    category_id: &str,
+    work_notify: &Notify,
+) -> Result<()> {
+    let span = tracing::info_span!("poll", game = %game_id, category = %category_id);
+    let result = async {
+        let cutoff_date = ctx
+            .db
+            .get_earliest_submitted_date()
+            .await?
+            .unwrap_or_else(|| Utc::now() - chrono::Duration::days(config.lookback_days as i64));
+        poll_category(ctx, game_id, category_id, cutoff_date, work_notify).await
+    }
+    .instrument(span)
+    .await;
+    if result.is_ok() {
+        ctx.liveness.record_poll();
+    }
+    if let Err(e) = &result {
+        let game_category = ctx
+            .speedrun_ops
+            .format_game_category(game_id, category_id)
+            .await;
+        error!("Failed to poll {}: {:#}", game_category, e);
+    }
+    result
+}
+
 async fn poll_game_category(
     speedrun_ops: &SpeedrunOps,
     game_id: &str,
@@ -99,7 +234,8 @@ async fn poll_game_category(
         .game(game_id)
         .category(category_id)
         .orderby("submitted")
-        .direction("asc");
+        .direction("asc")
+        .embed("players,platform");
 
     let runs = speedrun_ops.client.stream_runs(&query).await?;
 
@@ -107,8 +243,10 @@ async fn poll_game_category(
         .into_iter()
         .filter_map(|run| {
             let submitted_date = run.get_submitted_date().ok()?;
-            (submitted_date > *cutoff_date)
-                .then(|| NewRun::new(run.id, game_id, category_id, submitted_date))
+            (submitted_date > *cutoff_date).then(|| {
+                NewRun::new(&run.id, game_id, category_id, submitted_date)
+                    .with_details(run.details())
+            })
         })
         .collect();
 
@@ -129,7 +267,23 @@ async fn poll_category(
         .await?
         .unwrap_or(cutoff_date);
 
-    let new_runs = poll_game_category(
+    let policy = ctx.scheduling.policy(game_id, category_id);
+    let capacity = match policy.max_queue_depth {
+        Some(max_depth) => {
+            let queued = ctx.db.count_queued_runs(game_id, category_id).await?;
+            max_depth.saturating_sub(queued)
+        }
+        None => usize::MAX,
+    };
+    if capacity == 0 {
+        info!(
+            "Queue for game={}, category={} is full; not polling",
+            game_id, category_id
+        );
+        return Ok(());
+    }
+
+    let mut new_runs = poll_game_category(
         &ctx.speedrun_ops,
         game_id,
         category_id,
@@ -137,19 +291,14 @@ async fn poll_category(
     )
     .await
     .context("Failed to poll game category from API")?;
+    // the rest are picked up by a later poll, as runs are found by submission date
+    new_runs.truncate(capacity);
 
     let discovered_count = new_runs.len();
 
     for new_run in &new_runs {
-        match ctx.db.insert_run(new_run.clone()).await {
-            Ok(()) => {
-                if let Some(notifier) = &ctx.bot_notifier {
-                    notifier.notify(new_run.run_id.clone());
-                }
-            }
-            Err(e) => {
-                error!("Failed to insert run into database: {:#}", e);
-            }
+        if let Err(e) = ctx.db.insert_run(new_run.clone()).await {
+            error!("Failed to insert run into database: {:#}", e);
         }
     }
 
@@ -163,6 +312,7 @@ async fn poll_category(
             discovered_count, game_category
         );
         work_notify.notify_one();
+        ctx.notifications.wake();
     }
 
     Ok(())
@@ -171,17 +321,19 @@ async fn poll_category(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::daemon::config::SrcRunRules;
     use crate::daemon::database::connection::Database;
+    use crate::daemon::database::types::RunFilter;
+    use crate::daemon::liveness::Liveness;
+    use crate::daemon::notifier::NotificationDispatcher;
     use crate::daemon::retry::RetryConfig;
-    use crate::daemon::speedrun_api::{SpeedrunClient, SpeedrunOps};
+    use crate::daemon::speedrun_api::{RUNS_PAGE_SIZE, SpeedrunOps};
+    use crate::speedrun_mock::{SpeedrunMock, run_json};
     use std::collections::HashMap;
     use std::path::PathBuf;
 
-    async fn create_test_ctx() -> RunProcessingContext {
+    async fn create_test_ctx(mock: &SpeedrunMock) -> RunProcessingContext {
         let db = Database::in_memory().await.unwrap();
-        let client = SpeedrunClient::new().unwrap();
-        let speedrun_ops = SpeedrunOps::new(&client);
+        let speedrun_ops = SpeedrunOps::new(&mock.client());
         let src_rules = SrcRunRules {
             games: HashMap::new(),
         };
@@ -190,23 +342,161 @@ mod tests {
             speedrun_ops,
             src_rules,
             install_dir: PathBuf::from("./factorio_installs"),
+            install_quota_bytes: None,
+            sandbox: Default::default(),
+            factorio_image: None,
+            factorio_credentials: None,
             output_dir: PathBuf::from("./daemon_runs"),
             retry_config: RetryConfig::default(),
-            bot_notifier: None,
+            notifications: NotificationDispatcher::default(),
+            liveness: Liveness::default(),
+            scheduling: Default::default(),
+            download_throttles: Default::default(),
+            shutdown: CancellationToken::new(),
+            archive: None,
+            internet_archive: None,
+            factorio_log: None,
+            report_signing_key: None,
+            hooks: None,
+            rule_descriptions: Default::default(),
         }
     }
 
+    /// `count` runs of game1/cat1, submitted a minute apart starting at `first_submitted`.
+    fn runs(count: usize, first_submitted: DateTime<Utc>) -> Vec<serde_json::Value> {
+        (0..count)
+            .map(|i| {
+                let submitted = first_submitted + chrono::Duration::minutes(i as i64);
+                run_json(
+                    &format!("run{}", i),
+                    "game1",
+                    "cat1",
+                    &submitted.to_rfc3339(),
+                )
+            })
+            .collect()
+    }
+
+    async fn stored_run_count(ctx: &RunProcessingContext) -> usize {
+        ctx.db.query_runs(RunFilter::default()).await.unwrap().len()
+    }
+
     #[tokio::test]
     async fn test_poll_with_no_game_configs() {
-        let ctx = create_test_ctx().await;
-        let config = PollingConfig {
-            poll_interval_seconds: 3600,
-            lookback_days: 30,
-        };
+        let mock = SpeedrunMock::start().await;
+        let ctx = create_test_ctx(&mock).await;
+        let config = PollingConfig::default();
         let work_notify = Notify::new();
 
         let result = poll_speedrun_com(&ctx, &config, &work_notify).await;
 
         assert!(result.is_ok());
+        assert!(mock.requests("/runs").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_poll_category_across_pages() {
+        let mock = SpeedrunMock::start().await;
+        mock.game("game1", "Factorio").await;
+        mock.category("cat1", "Any%").await;
+        let first_submitted: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        mock.runs("game1", "cat1", runs(RUNS_PAGE_SIZE + 1, first_submitted))
+            .await;
+        let ctx = create_test_ctx(&mock).await;
+        let work_notify = Notify::new();
+
+        // the first run was submitted at the cutoff, so isn't new
+        poll_category(&ctx, "game1", "cat1", first_submitted, &work_notify)
+            .await
+            .unwrap();
+        assert_eq!(stored_run_count(&ctx).await, RUNS_PAGE_SIZE);
+        assert!(ctx.db.get_run("run0").await.unwrap().is_none());
+        let last = ctx.db.get_run_details("run200").await.unwrap().unwrap();
+        assert_eq!(last.player_names.as_deref(), Some("runner"));
+
+        // later polls start after the latest stored run
+        poll_category(&ctx, "game1", "cat1", first_submitted, &work_notify)
+            .await
+            .unwrap();
+        assert_eq!(stored_run_count(&ctx).await, RUNS_PAGE_SIZE);
+        assert_eq!(mock.requests("/runs").await.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_poll_fails_while_rate_limited() {
+        let mock = SpeedrunMock::start().await;
+        mock.runs(
+            "game1",
+            "cat1",
+            runs(3, Utc::now() - chrono::Duration::hours(1)),
+        )
+        .await;
+        mock.rate_limit("/runs", 4).await;
+        let ctx = create_test_ctx(&mock).await;
+        let config = PollingConfig::default();
+        let work_notify = Notify::new();
+
+        let result = poll_one(&ctx, &config, "game1", "cat1", &work_notify).await;
+        assert!(format!("{:#}", result.unwrap_err()).contains("rate limited"));
+        assert_eq!(ctx.liveness.snapshot(Utc::now()).last_poll_at, None);
+        assert_eq!(stored_run_count(&ctx).await, 0);
+
+        poll_one(&ctx, &config, "game1", "cat1", &work_notify)
+            .await
+            .unwrap();
+        assert!(ctx.liveness.snapshot(Utc::now()).last_poll_at.is_some());
+        assert_eq!(stored_run_count(&ctx).await, 3);
+    }
+
+    #[test]
+    fn test_next_poll_delay() {
+        let minute = Duration::from_secs(60);
+        let hour = Duration::from_secs(3600);
+        assert_eq!(next_poll_delay(minute, 0, hour, 0.5), minute);
+        assert_eq!(next_poll_delay(minute, 1, hour, 1.0), minute * 2);
+        assert_eq!(next_poll_delay(minute, 3, hour, 1.0), minute * 8);
+        assert_eq!(next_poll_delay(minute, 3, hour, 0.0), minute * 4);
+        assert_eq!(next_poll_delay(minute, 30, hour, 1.0), hour);
+        // never sooner than the interval
+        assert_eq!(next_poll_delay(hour, 2, hour, 0.0), hour);
+    }
+
+    #[test]
+    fn test_poll_schedule() {
+        let src_rules: SrcRunRules = serde_yaml::from_str(
+            "
+games:
+  game1:
+    expected_mods: [base]
+    categories:
+      busy: {}
+      quiet: {}
+",
+        )
+        .unwrap();
+        let config: PollingConfig = serde_yaml::from_str(
+            "
+poll_interval_seconds: 3600
+intervals:
+  game1/busy: 60
+",
+        )
+        .unwrap();
+        let now = Instant::now();
+        let mut schedule = PollSchedule::new(&src_rules, now);
+        assert_eq!(schedule.due(now).len(), 2);
+
+        schedule.record("game1", "busy", true, &config, now);
+        schedule.record("game1", "quiet", true, &config, now);
+        assert_eq!(schedule.next_poll(), Some(now + Duration::from_secs(60)));
+        let later = now + Duration::from_secs(60);
+        assert_eq!(
+            schedule.due(later),
+            [("game1".to_string(), "busy".to_string())]
+        );
+
+        schedule.record("game1", "busy", false, &config, later);
+        let backoff = schedule.next_poll().unwrap() - later;
+        assert!(backoff >= Duration::from_secs(60) && backoff <= Duration::from_secs(120));
     }
 }
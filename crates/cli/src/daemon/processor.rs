@@ -1,11 +1,21 @@
 use anyhow::{Context, Result};
-use log::{error, info};
+use chrono::Utc;
+use log::{error, info, warn};
+use std::collections::BTreeSet;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Notify;
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
-use super::database::types::Run;
-use super::run_processing::{RunProcessingContext, download_and_run_replay};
+use super::database::connection::Database;
+use super::database::types::{Run, RunSelection, RunStatus};
+use super::hooks::RunHooks;
+use super::progress::ProgressRecorder;
+use super::run_processing::{RunProcessingContext, RunProcessor, download_and_run_replay};
+use super::speedrun_api::format_run_time;
+use crate::error::RunProcessingError;
+use crate::run_replay::ReplayReport;
 
 #[derive(Debug)]
 pub enum ProcessResult {
@@ -13,21 +23,77 @@ pub enum ProcessResult {
     NoWork,
 }
 
+/// How long a claimed run stays reserved for a worker; renewed while it is being processed.
+pub(crate) const LEASE_DURATION: Duration = Duration::from_secs(600);
+
+/// Identifies a worker in the run leases; stable across restarts of the same instance.
+pub fn worker_id(instance_id: &str, index: usize) -> String {
+    format!("{}/{}", instance_id, index)
+}
+
+/// The instance ID a [`worker_id`] was made from.
+fn worker_instance(worker_id: &str) -> &str {
+    worker_id
+        .rsplit_once('/')
+        .map_or(worker_id, |(instance_id, _)| instance_id)
+}
+
 pub async fn process_runs_loop(
     ctx: RunProcessingContext,
+    instance_id: &str,
+    max_concurrent_runs: usize,
     work_notify: Arc<Notify>,
     token: CancellationToken,
 ) -> Result<()> {
-    info!("Starting run processor");
+    let max_concurrent_runs = max_concurrent_runs.max(1);
+    info!(
+        "Starting run processor {} with {} worker(s)",
+        instance_id, max_concurrent_runs
+    );
 
-    loop {
-        let result = tokio::select! {
-            _ = token.cancelled() => {
-                info!("Processor shutting down");
-                return Ok(());
+    let own_workers: Vec<String> = (0..max_concurrent_runs)
+        .map(|index| worker_id(instance_id, index))
+        .collect();
+    let recovered = ctx.db.recover_orphaned_leases(&own_workers).await?;
+    if !recovered.is_empty() {
+        warn!(
+            "Recovered {} orphaned run lease(s): {}",
+            recovered.len(),
+            recovered.join(", ")
+        );
+    }
+
+    let workers = own_workers
+        .into_iter()
+        .enumerate()
+        .map(|(index, worker_id)| {
+            let mut ctx = ctx.clone();
+            if max_concurrent_runs > 1 {
+                // a Factorio install can only run one instance at a time
+                ctx.install_dir = ctx.install_dir.join(format!("worker-{}", index));
             }
-            result = find_run_to_process(&ctx) => result,
-        };
+            worker_loop(ctx, worker_id, work_notify.clone(), token.clone())
+        });
+    futures::future::try_join_all(workers).await?;
+    Ok(())
+}
+
+async fn worker_loop(
+    ctx: RunProcessingContext,
+    worker_id: String,
+    work_notify: Arc<Notify>,
+    token: CancellationToken,
+) -> Result<()> {
+    std::fs::create_dir_all(&ctx.install_dir)?;
+
+    loop {
+        if token.is_cancelled() {
+            info!("Processor shutting down");
+            return Ok(());
+        }
+        // not raced against the token: an in-flight run finishes, or is aborted by
+        // `ctx.shutdown`, before the worker stops
+        let result = find_run_to_process(&ctx, &worker_id, &work_notify).await;
 
         match result {
             Ok(ProcessResult::Processed) => {
@@ -52,51 +118,120 @@ pub async fn process_runs_loop(
     }
 }
 
-pub async fn find_run_to_process(ctx: &RunProcessingContext) -> Result<ProcessResult> {
-    let allowed_game_categories: Vec<(String, String)> = ctx
-        .src_rules
-        .games
-        .iter()
-        .flat_map(|(game_id, config)| {
-            config
-                .categories
-                .keys()
-                .map(|cat_id| (game_id.clone(), cat_id.clone()))
-        })
-        .collect();
+pub async fn find_run_to_process(
+    ctx: &RunProcessingContext,
+    worker_id: &str,
+    work_notify: &Notify,
+) -> Result<ProcessResult> {
+    let now = Utc::now();
+    let mut selection = RunSelection::default();
+    for (game_id, config) in &ctx.src_rules.games {
+        for category_id in config.categories.keys() {
+            let policy = ctx.scheduling.policy(game_id, category_id);
+            if policy.is_quiet(now) {
+                continue;
+            }
+            let game_category = (game_id.clone(), category_id.clone());
+            if policy.auto_process {
+                selection.auto.push(game_category);
+            } else {
+                selection.manual_only.push(game_category);
+            }
+        }
+    }
 
+    let lease_expires_at = now + LEASE_DURATION;
     let Some(run) = ctx
         .db
-        .get_next_run_to_process(&allowed_game_categories)
+        .claim_next_run(&selection, worker_id, lease_expires_at)
         .await?
     else {
         return Ok(ProcessResult::NoWork);
     };
-    process_run(ctx, run).await?;
+    // there may be more work; wake another idle worker to look for it
+    work_notify.notify_one();
+
+    let run_id = run.run_id.clone();
+    let span = tracing::info_span!(
+        "run",
+        run_id = %run.run_id,
+        game = %run.game_id,
+        category = %run.category_id,
+        worker = worker_id
+    );
+    // cancelled on shutdown, or when another worker takes over the run
+    let cancel = ctx.shutdown.child_token();
+    let lease = tokio::spawn(
+        keep_lease(
+            ctx.db.clone(),
+            run_id.clone(),
+            worker_id.to_string(),
+            cancel.clone(),
+        )
+        .instrument(span.clone()),
+    );
+    let result = process_run(ctx, run, worker_id, &cancel)
+        .instrument(span.clone())
+        .await;
+    lease.abort();
+    ctx.liveness.record_processed();
+    ctx.db
+        .release_lease(&run_id, worker_id)
+        .instrument(span)
+        .await?;
+
+    result?;
     Ok(ProcessResult::Processed)
 }
 
-async fn process_run(ctx: &RunProcessingContext, run: Run) -> Result<()> {
+/// Renews the lease on a run while it is processed, and cancels `lease_lost` if another
+/// worker took the run over, so its replay stops instead of racing the new owner.
+pub(crate) async fn keep_lease(
+    db: Database,
+    run_id: String,
+    worker_id: String,
+    lease_lost: CancellationToken,
+) {
+    loop {
+        tokio::time::sleep(LEASE_DURATION / 3).await;
+        match db
+            .renew_lease(&run_id, &worker_id, Utc::now() + LEASE_DURATION)
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                warn!("Worker {} lost the lease on run {}", worker_id, run_id);
+                lease_lost.cancel();
+                return;
+            }
+            Err(e) => warn!("Failed to renew lease on run {}: {:#}", run_id, e),
+        }
+    }
+}
+
+async fn process_run(
+    ctx: &RunProcessingContext,
+    run: Run,
+    worker_id: &str,
+    cancel: &CancellationToken,
+) -> Result<()> {
     let (run_rules, expected_mods) = ctx
         .src_rules
         .resolve_rules(&run.game_id, &run.category_id)
         .context("Failed to resolve rules for run")?;
 
-    ctx.db
-        .mark_run_processing(&run.run_id)
-        .await
-        .context("Failed to mark run as processing")?;
-
-    if let Some(notifier) = &ctx.bot_notifier {
-        notifier.notify(run.run_id.clone());
-    }
+    ctx.notifications.wake();
 
     let game_category = ctx
         .speedrun_ops
         .format_game_category(&run.game_id, &run.category_id)
         .await;
 
-    let src_run = ctx.speedrun_ops.client.get_run(&run.run_id).await.ok();
+    let details = ctx
+        .speedrun_ops
+        .get_run_details(&run.run_id)
+        .await
+        .unwrap_or_default();
 
     let header = if run.retry_count > 0 {
         format!(
@@ -111,49 +246,160 @@ async fn process_run(ctx: &RunProcessingContext, run: Run) -> Result<()> {
         "{}\nGame: {}\nPlayers: {}\nTime: {}\nSubmitted: {}",
         header,
         game_category,
-        src_run
-            .as_ref()
-            .and_then(|r| r.format_players())
-            .unwrap_or_else(|| "unknown".to_string()),
-        src_run
-            .as_ref()
-            .and_then(|r| r.format_time())
-            .unwrap_or_else(|| "unknown".to_string()),
-        src_run
-            .as_ref()
-            .and_then(|r| r.submitted.clone())
+        details.player_names.as_deref().unwrap_or("unknown"),
+        details
+            .run_time_secs
+            .map(format_run_time)
             .unwrap_or_else(|| "unknown".to_string()),
+        run.submitted_date.format("%Y-%m-%d %H:%M:%S UTC"),
     );
 
+    let mut run_processor = RunProcessor::new(&ctx.speedrun_ops.client, &ctx.download_throttles)
+        .with_archive(ctx.archive.clone())
+        .with_internet_archive(ctx.internet_archive.clone())
+        .with_submitted_date(run.submitted_date)
+        .with_install_quota(ctx.install_quota_bytes)
+        .with_sandbox(ctx.sandbox.clone())
+        .with_factorio_image(ctx.factorio_image.clone())
+        .with_factorio_credentials(ctx.factorio_credentials.clone())
+        .with_factorio_log(ctx.factorio_log.clone())
+        .with_signing_key(ctx.report_signing_key.clone())
+        .with_rule_descriptions(ctx.rule_descriptions.clone())
+        .with_progress(ProgressRecorder::new(
+            ctx.db.clone(),
+            &run.run_id,
+            details.run_time_secs,
+        ))
+        .with_hooks(
+            ctx.hooks
+                .as_ref()
+                .map(|hooks| RunHooks::new(hooks.clone(), &run)),
+        );
     let result = download_and_run_replay(
-        &ctx.speedrun_ops.client,
+        &mut run_processor,
         &run.run_id,
         run_rules,
         expected_mods,
         &ctx.install_dir,
         &ctx.output_dir,
+        cancel,
     )
     .await;
 
-    info!("Saving replay result");
-    ctx.db
-        .process_replay_result(&run.run_id, result, &ctx.retry_config)
-        .await?;
+    if let Some(url) = run_processor.public_archive_url()
+        && let Err(e) = ctx.db.store_public_archive_url(&run.run_id, url).await
+    {
+        warn!("Failed to store archive URL of run {}: {:#}", run.run_id, e);
+    }
+    if let Some((path, tick)) = run_processor.checkpoint()
+        && let Err(e) = ctx
+            .db
+            .record_checkpoint(&run.run_id, &path.to_string_lossy(), tick)
+            .await
+    {
+        warn!("Failed to record checkpoint of run {}: {:#}", run.run_id, e);
+    }
+    if !run_processor.save_hashes().is_empty() {
+        record_save_hashes(ctx, &run.run_id, run_processor.save_hashes()).await;
+    }
+    if let Ok(report) = &result
+        && let Err(e) = ctx
+            .db
+            .record_replay_throughput(&run.run_id, worker_instance(worker_id), report)
+            .await
+    {
+        warn!(
+            "Failed to record replay time of run {}: {:#}",
+            run.run_id, e
+        );
+    }
+
+    save_run_result(ctx, &run.run_id, worker_id, cancel, result).await
+}
 
-    if let Some(notifier) = &ctx.bot_notifier {
-        notifier.notify(run.run_id.clone());
+/// Stores the result of processing a run, unless the worker lost its claim on the run to
+/// another worker, whose result then counts instead.
+async fn save_run_result(
+    ctx: &RunProcessingContext,
+    run_id: &str,
+    worker_id: &str,
+    cancel: &CancellationToken,
+    result: Result<ReplayReport, RunProcessingError>,
+) -> Result<()> {
+    if result.is_err() && ctx.shutdown.is_cancelled() {
+        info!(
+            "Run {} interrupted by shutdown; resetting to discovered",
+            run_id
+        );
+        ctx.db
+            .update_claimed_run_status(run_id, worker_id, RunStatus::Discovered, None)
+            .await?;
+        ctx.notifications.wake();
+        return Ok(());
     }
+    // renewing the lease keeps other workers from taking the run over while the result is saved
+    let still_claimed = !cancel.is_cancelled()
+        && ctx
+            .db
+            .renew_lease(run_id, worker_id, Utc::now() + LEASE_DURATION)
+            .await?;
+    let saved = still_claimed && {
+        info!("Saving replay result");
+        ctx.db
+            .process_replay_result(run_id, Some(worker_id), result, &ctx.retry_config)
+            .await?
+    };
+    if !saved {
+        warn!(
+            "Run {} was taken over by another worker; discarding this result",
+            run_id
+        );
+        return Ok(());
+    }
+
+    ctx.notifications.wake();
 
-    info!("Run {} finished successfully", run.run_id);
+    info!("Run {} finished successfully", run_id);
     Ok(())
 }
 
+/// Stores the hashes of the run's saves, and warns if another run has the same save.
+/// Failures are logged only.
+async fn record_save_hashes(ctx: &RunProcessingContext, run_id: &str, hashes: &[String]) {
+    if let Err(e) = ctx.db.store_save_hashes(run_id, hashes).await {
+        warn!("Failed to store save hashes of run {}: {:#}", run_id, e);
+        return;
+    }
+    match ctx.db.find_duplicate_saves(Some(run_id)).await {
+        Ok(duplicates) => {
+            // a segmented run can share several saves with the same run
+            let others: BTreeSet<_> = duplicates
+                .iter()
+                .map(|duplicate| duplicate.run_id.as_str())
+                .filter(|other| *other != run_id)
+                .collect();
+            if !others.is_empty() {
+                warn!(
+                    "Run {} has the same save as run(s) {}",
+                    run_id,
+                    others.into_iter().collect::<Vec<_>>().join(", ")
+                );
+            }
+        }
+        Err(e) => warn!(
+            "Failed to look up duplicate saves of run {}: {:#}",
+            run_id, e
+        ),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::daemon::config::SrcRunRules;
-    use crate::daemon::database::connection::Database;
-    use crate::daemon::database::types::NewRun;
+    use crate::daemon::database::types::{NewRun, RunStatus};
+    use crate::daemon::liveness::Liveness;
+    use crate::daemon::notifier::NotificationDispatcher;
     use crate::daemon::retry::RetryConfig;
     use crate::daemon::speedrun_api::{SpeedrunClient, SpeedrunOps};
     use std::collections::HashMap;
@@ -171,17 +417,37 @@ mod tests {
             speedrun_ops,
             src_rules,
             install_dir: PathBuf::from("/tmp/test"),
+            install_quota_bytes: None,
+            sandbox: Default::default(),
+            factorio_image: None,
+            factorio_credentials: None,
             output_dir: PathBuf::from("/tmp/test_output"),
             retry_config: RetryConfig::default(),
-            bot_notifier: None,
+            notifications: NotificationDispatcher::default(),
+            liveness: Liveness::default(),
+            scheduling: Default::default(),
+            download_throttles: Default::default(),
+            shutdown: CancellationToken::new(),
+            archive: None,
+            internet_archive: None,
+            factorio_log: None,
+            report_signing_key: None,
+            hooks: None,
+            rule_descriptions: Default::default(),
         }
     }
 
+    #[test]
+    fn test_worker_instance() {
+        assert_eq!(worker_instance(&worker_id("host", 0)), "host");
+        assert_eq!(worker_instance(&worker_id("rack/host", 3)), "rack/host");
+    }
+
     #[tokio::test]
     async fn test_poll_runs_no_discovered_runs() {
         let ctx = create_test_ctx().await;
 
-        let result = find_run_to_process(&ctx).await;
+        let result = find_run_to_process(&ctx, &worker_id("test", 0), &Notify::new()).await;
 
         assert!(result.is_ok());
         assert!(matches!(result.unwrap(), ProcessResult::NoWork));
@@ -198,7 +464,7 @@ mod tests {
         );
         ctx.db.insert_run(new_run).await.unwrap();
 
-        let result = find_run_to_process(&ctx).await;
+        let result = find_run_to_process(&ctx, &worker_id("test", 0), &Notify::new()).await;
 
         assert!(result.is_ok());
         assert!(matches!(result.unwrap(), ProcessResult::NoWork));
@@ -228,4 +494,104 @@ mod tests {
         let run_with_retries = ctx.db.get_run("run_logging").await.unwrap().unwrap();
         assert_eq!(run_with_retries.retry_count, 2);
     }
+
+    #[tokio::test]
+    async fn test_lost_lease_discards_result() {
+        let ctx = create_test_ctx().await;
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        let new_run = NewRun::new("run_lost", "game1", "cat1", submitted_date);
+        ctx.db.insert_run(new_run).await.unwrap();
+
+        let selection = RunSelection {
+            auto: vec![("game1".to_string(), "cat1".to_string())],
+            ..Default::default()
+        };
+        let expired = Utc::now() - chrono::Duration::minutes(1);
+        ctx.db
+            .claim_next_run(&selection, "host-a/0", expired)
+            .await
+            .unwrap()
+            .unwrap();
+        // the first worker's lease expired, so another one takes the run over
+        let lease = Utc::now() + LEASE_DURATION;
+        ctx.db
+            .claim_next_run(&selection, "host-b/0", lease)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let failed = ReplayReport {
+            max_msg_level: replay_script::MsgLevel::Error,
+            ..Default::default()
+        };
+        save_run_result(
+            &ctx,
+            "run_lost",
+            "host-a/0",
+            &CancellationToken::new(),
+            Ok(failed),
+        )
+        .await
+        .unwrap();
+        let run = ctx.db.get_run("run_lost").await.unwrap().unwrap();
+        assert_eq!(run.status, RunStatus::Processing);
+        assert!(
+            ctx.db
+                .get_replay_result("run_lost")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            ctx.db
+                .renew_lease("run_lost", "host-b/0", lease)
+                .await
+                .unwrap()
+        );
+
+        save_run_result(
+            &ctx,
+            "run_lost",
+            "host-b/0",
+            &CancellationToken::new(),
+            Ok(ReplayReport::default()),
+        )
+        .await
+        .unwrap();
+        let run = ctx.db.get_run("run_lost").await.unwrap().unwrap();
+        assert_eq!(run.status, RunStatus::Passed);
+    }
+
+    #[tokio::test]
+    async fn test_claimed_run_status() {
+        let ctx = create_test_ctx().await;
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        let new_run = NewRun::new("run_claimed", "game1", "cat1", submitted_date);
+        ctx.db.insert_run(new_run).await.unwrap();
+        let selection = RunSelection {
+            auto: vec![("game1".to_string(), "cat1".to_string())],
+            ..Default::default()
+        };
+        let lease = Utc::now() + LEASE_DURATION;
+        ctx.db
+            .claim_next_run(&selection, "host-a/0", lease)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let update = |worker_id: &'static str| {
+            ctx.db
+                .update_claimed_run_status("run_claimed", worker_id, RunStatus::Discovered, None)
+        };
+        assert!(!update("host-b/0").await.unwrap());
+        assert_eq!(
+            ctx.db.get_run("run_claimed").await.unwrap().unwrap().status,
+            RunStatus::Processing
+        );
+        assert!(update("host-a/0").await.unwrap());
+        assert_eq!(
+            ctx.db.get_run("run_claimed").await.unwrap().unwrap().status,
+            RunStatus::Discovered
+        );
+    }
 }
@@ -0,0 +1,88 @@
+//! Records how far the replay of a run being processed has gotten, so the status API can
+//! show the progress of long replays.
+
+use log::warn;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::daemon::database::connection::Database;
+
+/// Factorio runs at 60 ticks per second.
+pub const TICKS_PER_SECOND: f64 = 60.0;
+
+/// How often the latest tick is written to the database at most.
+const RECORD_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct ProgressRecorder {
+    db: Database,
+    run_id: String,
+    /// Ticks the replay should take, from the run's time on speedrun.com.
+    expected_ticks: Option<f64>,
+}
+
+impl ProgressRecorder {
+    pub fn new(db: Database, run_id: impl Into<String>, run_time_secs: Option<f64>) -> Self {
+        Self {
+            db,
+            run_id: run_id.into(),
+            expected_ticks: run_time_secs
+                .filter(|secs| *secs > 0.0)
+                .map(|secs| secs * TICKS_PER_SECOND),
+        }
+    }
+
+    /// Estimated share of the replay done by `tick`, in percent. Runs rarely end exactly at
+    /// their timed length, so this stays at 100 past it.
+    pub fn percent(&self, tick: u64) -> Option<f64> {
+        self.expected_ticks
+            .map(|expected| (tick as f64 / expected * 100.0).min(100.0))
+    }
+
+    /// Starts recording the ticks sent on the returned sender, until it is dropped.
+    pub fn start(&self) -> watch::Sender<u64> {
+        let (sender, receiver) = watch::channel(0);
+        tokio::spawn(self.clone().record(receiver));
+        sender
+    }
+
+    async fn record(self, mut ticks: watch::Receiver<u64>) {
+        loop {
+            let tick = *ticks.borrow_and_update();
+            if let Err(e) = self
+                .db
+                .record_run_progress(&self.run_id, tick, self.percent(tick))
+                .await
+            {
+                warn!("Failed to record progress of run {}: {:#}", self.run_id, e);
+            }
+            if ticks.changed().await.is_err() {
+                break;
+            }
+            tokio::time::sleep(RECORD_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_percent() {
+        let db = Database::in_memory().await.unwrap();
+        let recorder = ProgressRecorder::new(db.clone(), "run1", Some(100.0));
+        assert_eq!(recorder.percent(0), Some(0.0));
+        assert_eq!(recorder.percent(1500), Some(25.0));
+        assert_eq!(recorder.percent(9000), Some(100.0));
+
+        assert_eq!(
+            ProgressRecorder::new(db.clone(), "run1", None).percent(60),
+            None
+        );
+        assert_eq!(
+            ProgressRecorder::new(db, "run1", Some(0.0)).percent(60),
+            None
+        );
+    }
+}
@@ -1,10 +1,11 @@
 use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 use crate::error::ErrorClass;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct RetryConfig {
     #[serde(default = "default_max_attempts")]
     pub max_attempts: u32,
@@ -62,7 +63,7 @@ pub fn calculate_next_retry(
     config: &RetryConfig,
 ) -> Option<DateTime<Utc>> {
     match error_class {
-        ErrorClass::Final => None,
+        ErrorClass::Final | ErrorClass::AuthRequired => None,
         ErrorClass::RateLimited {
             retry_after: Some(retry_after),
         } => {
@@ -92,6 +93,7 @@ pub fn error_class_to_string(error_class: &ErrorClass) -> &'static str {
         ErrorClass::Final => "final",
         ErrorClass::Retryable => "retryable",
         ErrorClass::RateLimited { .. } => "rate_limited",
+        ErrorClass::AuthRequired => "auth_required",
     }
 }
 
@@ -115,6 +117,13 @@ mod tests {
         assert_eq!(result, None);
     }
 
+    #[test]
+    fn test_auth_required_returns_none() {
+        let config = RetryConfig::default();
+        let result = calculate_next_retry(0, &ErrorClass::AuthRequired, &config);
+        assert_eq!(result, None);
+    }
+
     #[test]
     fn test_max_attempts_exceeded() {
         let config = RetryConfig::default();
@@ -217,6 +226,10 @@ mod tests {
             }),
             "rate_limited"
         );
+        assert_eq!(
+            error_class_to_string(&ErrorClass::AuthRequired),
+            "auth_required"
+        );
     }
 
     #[test]
@@ -0,0 +1,384 @@
+//! Where save links are looked for in a run. Runners don't always put the link in the
+//! comment: when it has none, the run's video field, splits link and the descriptions of
+//! its videos are searched too. Shortened links (bit.ly, tinyurl, ...) are resolved to the
+//! link they redirect to, without requesting that link itself.
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::{info, warn};
+use regex::Regex;
+use reqwest::{Method, Url};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crate::daemon::speedrun_api::Run;
+
+pub const SHORTENER_HOSTS: &[&str] = &[
+    "bit.ly",
+    "tinyurl.com",
+    "t.co",
+    "is.gd",
+    "goo.gl",
+    "rb.gy",
+    "shorturl.at",
+];
+
+/// Sites whose video pages are fetched for their descriptions.
+pub const VIDEO_HOSTS: &[&str] = &[
+    "youtube.com",
+    "www.youtube.com",
+    "m.youtube.com",
+    "youtu.be",
+];
+
+/// Redirects followed per link, including those between shorteners.
+const MAX_REDIRECTS: usize = 5;
+/// Video pages larger than this are cut off.
+const MAX_PAGE_BYTES: usize = 2 * 1024 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+static URL_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"https?://[^\s<>"'()\[\]]+"#).unwrap());
+/// YouTube embeds the full description in the page's player data.
+static SHORT_DESCRIPTION_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""shortDescription":("(?:[^"\\]|\\.)*")"#).unwrap());
+static META_DESCRIPTION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<meta\s+(?:name|property)="(?:og:)?description"\s+content="([^"]*)""#).unwrap()
+});
+
+pub struct LinkSources {
+    client: reqwest::Client,
+    shortener_hosts: Vec<String>,
+    video_hosts: Vec<String>,
+}
+
+impl Default for LinkSources {
+    fn default() -> Self {
+        Self::new(SHORTENER_HOSTS, VIDEO_HOSTS)
+    }
+}
+
+enum Followed {
+    /// The response of the last URL on the followed hosts
+    Response(reqwest::Response),
+    /// The first URL redirected to off the followed hosts
+    Left(Url),
+}
+
+impl LinkSources {
+    pub fn new(shortener_hosts: &[&str], video_hosts: &[&str]) -> Self {
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent("factorio-replay-runner")
+            .build()
+            .expect("HTTP client config is valid");
+        Self {
+            client,
+            shortener_hosts: shortener_hosts.iter().map(|s| s.to_string()).collect(),
+            video_hosts: video_hosts.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// The text to look for save links in, one source per line, stopping at the first
+    /// source for which `has_links` is true: the comment, then the video field and splits
+    /// link, then the video descriptions.
+    pub async fn run_description(
+        &self,
+        run: &Run,
+        mut has_links: impl FnMut(&str) -> bool,
+    ) -> String {
+        let mut description = self
+            .with_resolved_links(run.comment.as_deref().unwrap_or_default())
+            .await;
+        if has_links(&description) {
+            return description;
+        }
+
+        let mut fields: Vec<&str> = Vec::new();
+        if let Some(videos) = &run.videos {
+            fields.extend(videos.text.as_deref());
+            fields.extend(videos.links.iter().flatten().map(|link| link.uri.as_str()));
+        }
+        fields.extend(run.splits.as_ref().map(|splits| splits.uri.as_str()));
+        push_line(
+            &mut description,
+            &self.with_resolved_links(&fields.join("\n")).await,
+        );
+        if has_links(&description) {
+            return description;
+        }
+
+        for link in run
+            .videos
+            .iter()
+            .flat_map(|videos| videos.links.iter().flatten())
+        {
+            match self.video_description(&link.uri).await {
+                Ok(Some(text)) => {
+                    info!("Searching the description of video {}", link.uri);
+                    push_line(&mut description, &self.with_resolved_links(&text).await);
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Failed to fetch description of video {}: {:#}", link.uri, e),
+            }
+        }
+        description
+    }
+
+    /// `text`, followed by the links its shortened links redirect to.
+    pub async fn with_resolved_links(&self, text: &str) -> String {
+        let mut resolved = text.to_string();
+        for found in URL_REGEX.find_iter(text) {
+            // sentence punctuation after a link isn't part of it
+            let link = found
+                .as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?']);
+            let Ok(url) = Url::parse(link) else {
+                continue;
+            };
+            if !self.is_shortener(&url) {
+                continue;
+            }
+            match self.resolve_short_link(url.clone()).await {
+                Ok(target) => {
+                    info!("Resolved {} to {}", url, target);
+                    push_line(&mut resolved, target.as_str());
+                }
+                Err(e) => warn!("Failed to resolve shortened link {}: {:#}", url, e),
+            }
+        }
+        resolved
+    }
+
+    fn is_shortener(&self, url: &Url) -> bool {
+        url.host_str()
+            .is_some_and(|host| self.shortener_hosts.iter().any(|s| s == host))
+    }
+
+    fn is_video(&self, url: &Url) -> bool {
+        url.host_str()
+            .is_some_and(|host| self.video_hosts.iter().any(|s| s == host))
+    }
+
+    async fn resolve_short_link(&self, url: Url) -> Result<Url> {
+        match self
+            .follow(Method::HEAD, url, |url| self.is_shortener(url))
+            .await?
+        {
+            Followed::Left(target) => Ok(target),
+            Followed::Response(response) => bail!("not redirected ({})", response.status()),
+        }
+    }
+
+    /// The description of the video page at `uri`, or `None` if it's not on a video site
+    /// or has no description.
+    async fn video_description(&self, uri: &str) -> Result<Option<String>> {
+        let Ok(url) = Url::parse(uri) else {
+            return Ok(None);
+        };
+        if !self.is_video(&url) {
+            return Ok(None);
+        }
+        let mut response = match self
+            .follow(Method::GET, url, |url| self.is_video(url))
+            .await?
+        {
+            Followed::Response(response) => response.error_for_status()?,
+            Followed::Left(target) => bail!("redirected off the video site to {}", target),
+        };
+        let mut page = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            page.extend_from_slice(&chunk);
+            if page.len() >= MAX_PAGE_BYTES {
+                page.truncate(MAX_PAGE_BYTES);
+                break;
+            }
+        }
+        Ok(page_description(&String::from_utf8_lossy(&page)))
+    }
+
+    /// Requests `url`, following redirects while `follow_to` accepts their target.
+    async fn follow(
+        &self,
+        method: Method,
+        mut url: Url,
+        follow_to: impl Fn(&Url) -> bool,
+    ) -> Result<Followed> {
+        for _ in 0..=MAX_REDIRECTS {
+            let response = self
+                .client
+                .request(method.clone(), url.clone())
+                .send()
+                .await
+                .with_context(|| format!("Failed to request {}", url))?;
+            if !response.status().is_redirection() {
+                return Ok(Followed::Response(response));
+            }
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or_else(|| anyhow!("redirect without a location"))?;
+            let target = url
+                .join(location)
+                .with_context(|| format!("Invalid redirect to {}", location))?;
+            if !matches!(target.scheme(), "http" | "https") {
+                bail!("redirected to unsupported URL {}", target);
+            }
+            if !follow_to(&target) {
+                return Ok(Followed::Left(target));
+            }
+            url = target;
+        }
+        bail!("more than {} redirects", MAX_REDIRECTS)
+    }
+}
+
+fn push_line(text: &mut String, line: &str) {
+    if line.is_empty() {
+        return;
+    }
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    text.push_str(line);
+}
+
+fn page_description(page: &str) -> Option<String> {
+    if let Some(captures) = SHORT_DESCRIPTION_REGEX.captures(page)
+        && let Ok(description) = serde_json::from_str::<String>(&captures[1])
+    {
+        return Some(description);
+    }
+    let captures = META_DESCRIPTION_REGEX.captures(page)?;
+    Some(
+        captures[1]
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const SAVE_LINK: &str = "https://drive.google.com/file/d/abc/view";
+
+    fn has_save_link(text: &str) -> bool {
+        text.contains("drive.google.com")
+    }
+
+    async fn redirect(server: &MockServer, from: &str, to: &str) {
+        Mock::given(path(from))
+            .respond_with(ResponseTemplate::new(301).insert_header("Location", to))
+            .mount(server)
+            .await;
+    }
+
+    /// Link sources treating the mock server's host as both a shortener and a video site.
+    fn link_sources(server: &MockServer) -> LinkSources {
+        let host = Url::parse(&server.uri())
+            .unwrap()
+            .host_str()
+            .unwrap()
+            .to_string();
+        LinkSources::new(&[&host], &[&host])
+    }
+
+    fn run(comment: Option<&str>, videos: serde_json::Value) -> Run {
+        serde_json::from_value(json!({
+            "id": "run1",
+            "game": "game1",
+            "category": "cat1",
+            "comment": comment,
+            "videos": videos,
+            "splits": null,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resolve_short_links() {
+        let server = MockServer::start().await;
+        redirect(&server, "/abc", "/def").await;
+        redirect(&server, "/def", SAVE_LINK).await;
+        redirect(&server, "/loop", "/loop").await;
+        let sources = link_sources(&server);
+
+        let text = format!("save: {}/abc, loop: {}/loop", server.uri(), server.uri());
+        let resolved = sources.with_resolved_links(&text).await;
+        assert_eq!(resolved, format!("{}\n{}", text, SAVE_LINK));
+
+        let loops = server
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|request| request.url.path() == "/loop")
+            .count();
+        assert_eq!(loops, MAX_REDIRECTS + 1);
+        assert_eq!(
+            sources
+                .with_resolved_links("https://example.com/save.zip")
+                .await,
+            "https://example.com/save.zip"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_description_sources() {
+        let server = MockServer::start().await;
+        let page = format!(
+            r#"<meta name="description" content="Cut off"><script>{{"shortDescription":"Save:\n{}"}}</script>"#,
+            SAVE_LINK
+        );
+        Mock::given(method("GET"))
+            .and(path("/watch"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(page))
+            .mount(&server)
+            .await;
+        let sources = link_sources(&server);
+        let video = format!("{}/watch", server.uri());
+
+        let in_comment = run(Some(SAVE_LINK), json!({ "links": [{ "uri": video }] }));
+        assert_eq!(
+            sources.run_description(&in_comment, has_save_link).await,
+            SAVE_LINK
+        );
+
+        let in_video_text = run(None, json!({ "text": SAVE_LINK, "links": null }));
+        assert_eq!(
+            sources.run_description(&in_video_text, has_save_link).await,
+            SAVE_LINK
+        );
+
+        let in_video_page = run(Some("gg"), json!({ "links": [{ "uri": video }] }));
+        assert_eq!(
+            sources.run_description(&in_video_page, has_save_link).await,
+            format!("gg\n{}\nSave:\n{}", video, SAVE_LINK)
+        );
+
+        let elsewhere = LinkSources::default();
+        assert_eq!(
+            elsewhere
+                .run_description(&in_video_page, has_save_link)
+                .await,
+            format!("gg\n{}", video)
+        );
+    }
+
+    #[test]
+    fn test_page_description() {
+        assert_eq!(
+            page_description(r#"<meta property="og:description" content="Save &amp; splits">"#),
+            Some("Save & splits".to_string())
+        );
+        assert_eq!(page_description("<html></html>"), None);
+    }
+}
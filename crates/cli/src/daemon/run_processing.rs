@@ -1,25 +1,53 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::SigningKey;
 use factorio_manager::error::FactorioError;
 use factorio_manager::expected_mods::ExpectedMods;
-use factorio_manager::factorio_install_dir::{FactorioInstallDir, VersionStr};
+use factorio_manager::factorio_image::FactorioImage;
+use factorio_manager::factorio_install_dir::{FactorioCredentials, FactorioInstallDir, VersionStr};
+use factorio_manager::process_manager::Sandbox;
 use factorio_manager::save_file::{SaveFile, WrittenSaveFile};
-use log::info;
+use log::{info, warn};
+use replay_script::locale::RuleDescriptions;
 use std::fs::File;
 use std::path::{Path, PathBuf};
-use zip_downloader::FileDownloader;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use zip_downloader::naming::sanitize_file_name;
 use zip_downloader::services::dropbox::DropboxService;
 use zip_downloader::services::gdrive::GoogleDriveService;
+use zip_downloader::services::mega::MegaService;
+use zip_downloader::services::onedrive::OneDriveService;
+use zip_downloader::services::s3::S3Service;
 use zip_downloader::services::speedrun::SpeedrunService;
+use zip_downloader::throttle::DownloadThrottles;
+use zip_downloader::{
+    DetectedLink, DownloadedFile, FileDownloader, FileNameTemplate, SecurityConfig,
+    SecurityOverrides,
+};
 
 use crate::config::RunRules;
-use crate::daemon::bot_notifier::BotNotifierHandle;
+use crate::daemon::archive::{self, ArchiveStore};
 use crate::daemon::config::SrcRunRules;
 use crate::daemon::database::connection::Database;
+use crate::daemon::factorio_log::{FACTORIO_LOG_FILE, FactorioLogConfig};
+use crate::daemon::hooks::{HookStage, HooksConfig, RunHooks};
+use crate::daemon::internet_archive::InternetArchive;
+use crate::daemon::liveness::Liveness;
+use crate::daemon::notifier::NotificationDispatcher;
+use crate::daemon::progress::ProgressRecorder;
 use crate::daemon::retry::RetryConfig;
-use crate::daemon::speedrun_api::{ApiError, SpeedrunClient, SpeedrunOps};
+use crate::daemon::run_links::LinkSources;
+use crate::daemon::scheduling::Scheduling;
+use crate::daemon::speedrun_api::{ApiError, Run, SpeedrunClient, SpeedrunOps};
 use crate::error::ErrorClass;
 use crate::error::RunProcessingError;
-use crate::run_replay::{ReplayReport, run_replay};
+use crate::run_replay::report::{self, ReportContext};
+use crate::run_replay::{
+    ReplayOptions, ReplayReport, checkpoint_path, report_json_path, run_replay,
+};
+use crate::signing;
 
 const MIN_FACTORIO_VERSION: VersionStr = VersionStr::new(2, 0, 65);
 
@@ -29,50 +57,257 @@ pub struct RunProcessingContext {
     pub speedrun_ops: SpeedrunOps,
     pub src_rules: SrcRunRules,
     pub install_dir: PathBuf,
+    pub install_quota_bytes: Option<u64>,
+    pub sandbox: Sandbox,
+    pub factorio_image: Option<FactorioImage>,
+    pub factorio_credentials: Option<FactorioCredentials>,
     pub output_dir: PathBuf,
     pub retry_config: RetryConfig,
-    pub bot_notifier: Option<BotNotifierHandle>,
+    pub scheduling: Scheduling,
+    pub notifications: NotificationDispatcher,
+    pub liveness: Liveness,
+    pub download_throttles: DownloadThrottles,
+    /// Cancelled once the shutdown drain times out; aborts in-flight downloads and replays.
+    pub shutdown: CancellationToken,
+    pub archive: Option<Arc<dyn ArchiveStore>>,
+    pub internet_archive: Option<Arc<InternetArchive>>,
+    pub factorio_log: Option<FactorioLogConfig>,
+    pub report_signing_key: Option<Arc<SigningKey>>,
+    pub hooks: Option<Arc<HooksConfig>>,
+    pub rule_descriptions: Arc<RuleDescriptions>,
 }
 
 pub struct RunProcessor<'a> {
     downloader: FileDownloader,
     client: &'a SpeedrunClient,
+    link_sources: LinkSources,
+    archive: Option<Arc<dyn ArchiveStore>>,
+    archive_prefix: Option<String>,
+    internet_archive: Option<Arc<InternetArchive>>,
+    public_archive_url: Option<String>,
+    checkpoint: Option<(PathBuf, Option<u64>)>,
+    save_link: Option<String>,
+    save_hashes: Vec<String>,
+    reuse_saves: bool,
+    submitted_date: Option<DateTime<Utc>>,
+    install_quota_bytes: Option<u64>,
+    sandbox: Sandbox,
+    factorio_image: Option<FactorioImage>,
+    factorio_credentials: Option<FactorioCredentials>,
+    factorio_log: Option<FactorioLogConfig>,
+    signing_key: Option<Arc<SigningKey>>,
+    hooks: Option<RunHooks>,
+    rule_descriptions: Arc<RuleDescriptions>,
+    progress: Option<ProgressRecorder>,
 }
 
 impl<'a> RunProcessor<'a> {
-    pub fn new(client: &'a SpeedrunClient) -> Result<Self> {
+    pub fn new(client: &'a SpeedrunClient, throttles: &DownloadThrottles) -> Self {
         let downloader = FileDownloader::builder()
-            .add_service(GoogleDriveService::new())
-            .add_service(DropboxService::new())
-            .add_service(SpeedrunService::new())
+            .add_service(throttles.wrap(GoogleDriveService::from_env()))
+            .add_service(throttles.wrap(DropboxService::new()))
+            .add_service(throttles.wrap(OneDriveService::new()))
+            .add_service(throttles.wrap(MegaService::new()))
+            .add_service(throttles.wrap(SpeedrunService::from_env()))
+            .add_service(throttles.wrap(S3Service::from_env()))
+            .with_security_config(default_security_config())
             .build();
 
-        Ok(Self { downloader, client })
+        Self {
+            downloader,
+            client,
+            link_sources: LinkSources::default(),
+            archive: None,
+            archive_prefix: None,
+            internet_archive: None,
+            public_archive_url: None,
+            checkpoint: None,
+            save_link: None,
+            save_hashes: Vec::new(),
+            reuse_saves: false,
+            submitted_date: None,
+            install_quota_bytes: None,
+            sandbox: Sandbox::None,
+            factorio_image: None,
+            factorio_credentials: None,
+            factorio_log: None,
+            signing_key: None,
+            hooks: None,
+            rule_descriptions: Arc::default(),
+            progress: None,
+        }
+    }
+
+    pub fn with_archive(mut self, archive: Option<Arc<dyn ArchiveStore>>) -> Self {
+        self.archive = archive;
+        self
+    }
+
+    /// Uploads the saves and reports of passed runs to the Internet Archive.
+    pub fn with_internet_archive(mut self, internet_archive: Option<Arc<InternetArchive>>) -> Self {
+        self.internet_archive = internet_archive;
+        self
+    }
+
+    /// Uses a save already downloaded into the run's working directory instead of
+    /// downloading it again, and keeps the save after the replay.
+    pub fn with_reused_saves(mut self) -> Self {
+        self.reuse_saves = true;
+        self
+    }
+
+    /// Save files modified after this date are flagged in the report.
+    pub fn with_submitted_date(mut self, submitted_date: DateTime<Utc>) -> Self {
+        self.submitted_date = Some(submitted_date);
+        self
+    }
+
+    pub fn with_install_quota(mut self, quota_bytes: Option<u64>) -> Self {
+        self.install_quota_bytes = quota_bytes;
+        self
+    }
+
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    pub fn with_factorio_image(mut self, factorio_image: Option<FactorioImage>) -> Self {
+        self.factorio_image = factorio_image;
+        self
+    }
+
+    pub fn with_factorio_credentials(
+        mut self,
+        factorio_credentials: Option<FactorioCredentials>,
+    ) -> Self {
+        self.factorio_credentials = factorio_credentials;
+        self
+    }
+
+    pub fn with_factorio_log(mut self, factorio_log: Option<FactorioLogConfig>) -> Self {
+        self.factorio_log = factorio_log;
+        self
+    }
+
+    /// Signs each run's report.json into report.json.sig.
+    pub fn with_signing_key(mut self, signing_key: Option<Arc<SigningKey>>) -> Self {
+        self.signing_key = signing_key;
+        self
+    }
+
+    pub fn with_hooks(mut self, hooks: Option<RunHooks>) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Describes rules in the reports; defaults to English.
+    pub fn with_rule_descriptions(mut self, rule_descriptions: Arc<RuleDescriptions>) -> Self {
+        self.rule_descriptions = rule_descriptions;
+        self
+    }
+
+    /// Records how far each replay has gotten, for the status API.
+    pub fn with_progress(mut self, progress: ProgressRecorder) -> Self {
+        self.progress = Some(progress);
+        self
     }
 
-    async fn fetch_run_description(&self, run_id: &str) -> Result<String, ApiError> {
+    /// Applies a category's download limits to the following downloads.
+    pub fn set_security_overrides(&mut self, overrides: &SecurityOverrides) {
+        self.downloader
+            .set_security_config(default_security_config().with_overrides(overrides));
+    }
+
+    /// Save links the downloader would try for a run description, in order.
+    pub fn detect_save_links(&mut self, description: &str) -> Vec<String> {
+        self.detect_links(description)
+            .into_iter()
+            .map(|link| link.link)
+            .collect()
+    }
+
+    /// Like [`Self::detect_save_links`], with the service handling each link.
+    pub fn detect_links(&mut self, description: &str) -> Vec<DetectedLink> {
+        self.downloader.detect_all_links(description)
+    }
+
+    /// The run's comment, or if it has no save links, also the other places runners put
+    /// them; see [`LinkSources::run_description`].
+    pub async fn fetch_run_description(&mut self, run_id: &str) -> Result<String, ApiError> {
         info!("Fetching run description");
         let run = self.client.get_run(run_id).await?;
+        self.archive_prefix = Some(archive::run_key(&run.game, &run.category, run_id));
 
-        let description = run.comment.as_ref().ok_or_else(|| {
-            ApiError::MissingField(format!("Comment with link needed for run {}", run_id))
-        })?;
+        let description = self.run_description(&run).await;
+        if description.trim().is_empty() {
+            return Err(ApiError::MissingField(format!(
+                "Comment or video with link needed for run {}",
+                run_id
+            )));
+        }
 
-        Ok(description.to_string())
+        Ok(description)
     }
 
-    async fn download_save(
+    /// Where the run's save links are looked for; see [`LinkSources::run_description`].
+    pub async fn run_description(&mut self, run: &Run) -> String {
+        let downloader = &mut self.downloader;
+        self.link_sources
+            .run_description(run, |text| !downloader.detect_all_links(text).is_empty())
+            .await
+    }
+
+    /// Downloads the first working save link in `description`, after the downloader's
+    /// security checks.
+    pub async fn download_file(
         &mut self,
         description: &str,
         working_dir: &Path,
-    ) -> Result<WrittenSaveFile, RunProcessingError> {
+        cancel: &CancellationToken,
+    ) -> Result<DownloadedFile, RunProcessingError> {
         info!("Downloading save file");
         let save_file_info = self
             .downloader
-            .download_zip(description, working_dir)
+            .download_zip(description, working_dir, cancel)
             .await?;
+        info!(
+            "Downloaded {} from {}",
+            save_file_info.original_name, save_file_info.link
+        );
+        self.save_link = Some(save_file_info.link.clone());
+        self.save_hashes = vec![save_file_info.sha256.clone()];
+        Ok(save_file_info)
+    }
+
+    /// The Internet Archive item the run was uploaded to, if it passed.
+    pub fn public_archive_url(&self) -> Option<&str> {
+        self.public_archive_url.as_deref()
+    }
+
+    /// The checkpoint kept of the replay, and the latest tick the scripts had reported.
+    pub fn checkpoint(&self) -> Option<(&Path, Option<u64>)> {
+        self.checkpoint
+            .as_ref()
+            .map(|(path, tick)| (path.as_path(), *tick))
+    }
+
+    /// SHA-256 of each save downloaded for the run, in segment order. Empty if the saves
+    /// were reused rather than downloaded.
+    pub fn save_hashes(&self) -> &[String] {
+        &self.save_hashes
+    }
 
-        let save_path = working_dir.join(save_file_info.name);
+    async fn download_save(
+        &mut self,
+        description: &str,
+        working_dir: &Path,
+        cancel: &CancellationToken,
+    ) -> Result<WrittenSaveFile, RunProcessingError> {
+        let save_path = self
+            .download_file(description, working_dir, cancel)
+            .await?
+            .path;
         let file = File::open(&save_path).map_err(|e| {
             RunProcessingError::from(factorio_manager::error::FactorioError::IoError(e))
         })?;
@@ -85,50 +320,502 @@ impl<'a> RunProcessor<'a> {
         &mut self,
         run_id: &str,
         working_dir: &Path,
+        cancel: &CancellationToken,
     ) -> Result<WrittenSaveFile, RunProcessingError> {
+        if self.reuse_saves
+            && let Some(save_file) = find_cached_save(run_id, working_dir)
+        {
+            info!("Using cached save {}", save_file.0.display());
+            return Ok(save_file);
+        }
+        let description = self.fetch_run_description(run_id).await?;
+        self.downloader
+            .set_file_name_template(save_file_name_template(run_id));
+        self.download_save(&description, working_dir, cancel).await
+    }
+
+    /// Downloads every save linked in the run description, for segmented runs. Segments
+    /// are always downloaded again, even with reused saves.
+    pub async fn download_segment_saves(
+        &mut self,
+        run_id: &str,
+        working_dir: &Path,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<WrittenSaveFile>, RunProcessingError> {
         let description = self.fetch_run_description(run_id).await?;
-        self.download_save(&description, working_dir).await
+        self.downloader
+            .set_file_name_template(save_file_name_template(run_id));
+        info!("Downloading segment saves");
+        let files = self
+            .downloader
+            .download_all_zips(&description, working_dir, cancel)
+            .await?;
+        self.save_link = files.first().map(|file| file.link.clone());
+        self.save_hashes = files.iter().map(|file| file.sha256.clone()).collect();
+        files
+            .into_iter()
+            .map(|file| {
+                let save_file =
+                    SaveFile::new(File::open(&file.path).map_err(FactorioError::from)?)?;
+                Ok(WrittenSaveFile(file.path, save_file))
+            })
+            .collect()
+    }
+
+    fn factorio_install_dir(
+        &self,
+        install_dir: &Path,
+    ) -> Result<FactorioInstallDir, FactorioError> {
+        FactorioInstallDir::new_or_create(install_dir).map(|dir| {
+            dir.with_quota(self.install_quota_bytes)
+                .with_sandbox(self.sandbox.clone())
+                .with_image(self.factorio_image.clone())
+                .with_credentials(self.factorio_credentials.clone())
+        })
+    }
+
+    async fn run_hooks(
+        &mut self,
+        stage: HookStage,
+        working_dir: &Path,
+        save_paths: &[PathBuf],
+        cancel: &CancellationToken,
+    ) -> Result<(), RunProcessingError> {
+        match &mut self.hooks {
+            Some(hooks) => {
+                hooks
+                    .run(stage, working_dir, save_paths, None, cancel)
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Runs the after-replay hooks, and adds the failures of `review` hooks of every stage
+    /// to the report.
+    async fn run_after_replay_hooks(
+        &mut self,
+        mut report: ReplayReport,
+        working_dir: &Path,
+        save_paths: &[PathBuf],
+        cancel: &CancellationToken,
+    ) -> Result<ReplayReport, RunProcessingError> {
+        let Some(hooks) = &mut self.hooks else {
+            return Ok(report);
+        };
+        let report_path = report.log_path.as_deref().map(report_json_path);
+        hooks
+            .run(
+                HookStage::AfterReplay,
+                working_dir,
+                save_paths,
+                report_path.as_deref(),
+                cancel,
+            )
+            .await?;
+        if hooks.flag_report(&mut report)
+            && let Some(report_path) = &report_path
+            && let Err(e) = report.write_json(report_path)
+        {
+            warn!("Failed to write replay report: {e}");
+        }
+        Ok(report)
+    }
+
+    /// Writes the moderator reports next to the replay log. Failures are logged only.
+    pub fn write_reports(&self, run_id: &str, report: &ReplayReport, working_dir: &Path) {
+        let title = format!("Run {}", run_id);
+        let ctx = ReportContext {
+            title: &title,
+            save_link: self.save_link.as_deref(),
+            // segments link their own logs
+            log_link: report.segments.is_empty().then_some("output.log"),
+            descriptions: &self.rule_descriptions,
+        };
+        let files = [
+            (
+                report::REPORT_MARKDOWN_FILE,
+                report::render_markdown(report, &ctx),
+            ),
+            (report::REPORT_HTML_FILE, report::render_html(report, &ctx)),
+        ];
+        for (file_name, contents) in files {
+            let path = working_dir.join(file_name);
+            if let Err(e) = std::fs::write(&path, contents) {
+                warn!("Failed to write {}: {}", path.display(), e);
+            }
+        }
+        if let Some(key) = &self.signing_key
+            && let Some(log_path) = &report.log_path
+            && let Err(e) =
+                signing::sign_report(key, &report_json_path(log_path), run_id, &self.save_hashes)
+        {
+            warn!("Failed to sign report of run {}: {:#}", run_id, e);
+        }
+    }
+
+    /// Copies the save and replay log to the archive, if one is configured.
+    /// Failures are logged rather than failing the run.
+    pub async fn archive_artifacts(&self, save_path: &Path) {
+        if let Some(prefix) = &self.archive_prefix {
+            let log_path = save_path.with_file_name("output.log");
+            self.archive_to(prefix, save_path, &log_path).await;
+        }
+    }
+
+    /// Archives one segment of a segmented run under `{prefix}/segment{number}`.
+    async fn archive_segment(&self, number: usize, save_path: &Path, log_path: &Path) {
+        if let Some(prefix) = &self.archive_prefix {
+            let prefix = format!("{prefix}/segment{number}");
+            self.archive_to(&prefix, save_path, log_path).await;
+        }
+    }
+
+    async fn archive_to(&self, prefix: &str, save_path: &Path, log_path: &Path) {
+        let Some(store) = &self.archive else {
+            return;
+        };
+        if let Err(e) = archive::archive_run(store.as_ref(), prefix, save_path, log_path).await {
+            warn!("Failed to archive {}: {:#}", prefix, e);
+        }
+    }
+
+    /// Uploads the saves and reports of a passed run to the Internet Archive, if configured.
+    /// Failures are logged rather than failing the run.
+    async fn publish(
+        &mut self,
+        run_id: &str,
+        report: &ReplayReport,
+        save_paths: &[PathBuf],
+        working_dir: &Path,
+    ) {
+        let Some(internet_archive) = &self.internet_archive else {
+            return;
+        };
+        if !report.passed() {
+            return;
+        }
+        let mut files: Vec<(String, PathBuf)> = match save_paths {
+            [save_path] => vec![("save.zip".to_string(), save_path.clone())],
+            _ => (1..)
+                .zip(save_paths)
+                .map(|(number, path)| (format!("segment{number}.zip"), path.clone()))
+                .collect(),
+        };
+        if let Some(log_path) = &report.log_path {
+            files.push(("report.json".to_string(), report_json_path(log_path)));
+        }
+        files.push((
+            report::REPORT_HTML_FILE.to_string(),
+            working_dir.join(report::REPORT_HTML_FILE),
+        ));
+        files.retain(|(_, path)| path.exists());
+        match internet_archive.upload_run(run_id, &files).await {
+            Ok(url) => self.public_archive_url = Some(url),
+            Err(e) => warn!(
+                "Failed to upload run {} to the Internet Archive: {:#}",
+                run_id, e
+            ),
+        }
+    }
+}
+
+/// Security checks for downloaded saves, before a category's overrides.
+pub fn default_security_config() -> SecurityConfig {
+    SecurityConfig {
+        require_factorio_save: true,
+        ..Default::default()
     }
 }
 
 pub async fn download_and_run_replay(
-    client: &SpeedrunClient,
+    processor: &mut RunProcessor<'_>,
     run_id: &str,
     run_rules: &RunRules,
     expected_mods: &ExpectedMods,
     install_dir: &Path,
     output_dir: &Path,
+    cancel: &CancellationToken,
 ) -> Result<ReplayReport, RunProcessingError> {
-    let working_dir = output_dir.join(run_id);
+    processor.set_security_overrides(&run_rules.security);
+    let working_dir = output_dir.join(sanitize_file_name(run_id));
     std::fs::create_dir_all(&working_dir)
         .map_err(|e| RunProcessingError::from_error(ErrorClass::Retryable, &e))?;
+    processor
+        .run_hooks(HookStage::BeforeDownload, &working_dir, &[], cancel)
+        .await?;
 
-    let mut processor = RunProcessor::new(client)
-        .map_err(|e| RunProcessingError::from_error(ErrorClass::Retryable, &e))?;
-    let mut save_file = processor.download_run_save(run_id, &working_dir).await?;
+    if run_rules.segmented {
+        return run_segments(
+            processor,
+            run_id,
+            run_rules,
+            expected_mods,
+            &processor.factorio_install_dir(install_dir)?,
+            &working_dir,
+            cancel,
+        )
+        .await;
+    }
+
+    let mut save_file = processor
+        .download_run_save(run_id, &working_dir, cancel)
+        .instrument(tracing::info_span!("download"))
+        .await?;
+    let save_paths = [save_file.0.clone()];
+    if let Err(e) = processor
+        .run_hooks(HookStage::AfterDownload, &working_dir, &save_paths, cancel)
+        .await
+    {
+        if !processor.reuse_saves {
+            cleanup_save_files(&save_file.0);
+        }
+        return Err(e);
+    }
+
+    // dropping the replay terminates Factorio
+    let install_dir = processor.factorio_install_dir(install_dir)?;
+    let log_path = working_dir.join("output.log");
+    let factorio_log_path = processor
+        .factorio_log
+        .is_some()
+        .then(|| working_dir.join(FACTORIO_LOG_FILE));
+    let progress = processor.progress.as_ref().map(ProgressRecorder::start);
+    let options = ReplayOptions {
+        submitted_date: processor.submitted_date,
+        full_log_path: factorio_log_path.as_deref(),
+        progress: progress.as_ref(),
+    };
+    let result = tokio::select! {
+        result = run_replay_with_save(&mut save_file, run_rules, expected_mods, &install_dir, &log_path, options)
+            .instrument(tracing::info_span!("replay")) => result,
+        _ = cancel.cancelled() => Err(RunProcessingError::from_error(
+            ErrorClass::Retryable,
+            &"Replay interrupted by shutdown",
+        )),
+    };
+    let result = match result {
+        Ok(report) => {
+            processor
+                .run_after_replay_hooks(report, &working_dir, &save_paths, cancel)
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    let checkpoint = checkpoint_path(&log_path);
+    if run_rules.checkpoint_interval_minutes.is_some() && checkpoint.exists() {
+        let tick = progress.as_ref().map(|progress| *progress.borrow());
+        processor.checkpoint = Some((checkpoint, tick));
+    }
+    if let Ok(report) = &result {
+        processor.write_reports(run_id, report, &working_dir);
+        processor.archive_artifacts(&save_file.0).await;
+        processor
+            .publish(run_id, report, &save_paths, &working_dir)
+            .await;
+    }
+    if !processor.reuse_saves {
+        cleanup_save_files(&save_file.0);
+    }
+    if let Some(factorio_log) = &processor.factorio_log
+        && let Err(e) = factorio_log.rotate(output_dir)
+    {
+        warn!("Failed to rotate Factorio logs: {e:#}");
+    }
+    result
+}
+
+/// Verifies a save that was uploaded rather than downloaded from a run's links, writing the
+/// logs next to it. The save is deleted afterwards.
+pub async fn run_uploaded_replay(
+    processor: &RunProcessor<'_>,
+    save_path: &Path,
+    run_rules: &RunRules,
+    expected_mods: &ExpectedMods,
+    install_dir: &Path,
+    cancel: &CancellationToken,
+) -> Result<ReplayReport, RunProcessingError> {
+    let working_dir = save_path.parent().unwrap_or(Path::new("."));
+    let install_dir = processor.factorio_install_dir(install_dir)?;
+    let log_path = working_dir.join("output.log");
+    let result = async {
+        let file = File::open(save_path)
+            .map_err(|e| RunProcessingError::from_error(ErrorClass::Retryable, &e))?;
+        let mut save_file = WrittenSaveFile(save_path.to_path_buf(), SaveFile::new(file)?);
+        tokio::select! {
+            result = run_replay_with_save(&mut save_file, run_rules, expected_mods, &install_dir, &log_path, ReplayOptions::default()) => result,
+            _ = cancel.cancelled() => Err(RunProcessingError::from_error(
+                ErrorClass::Retryable,
+                &"Replay interrupted by shutdown",
+            )),
+        }
+    }
+    .instrument(tracing::info_span!("replay"))
+    .await;
+    cleanup_save_files(save_path);
+    result
+}
+
+/// Verifies each save of a segmented run in order, and combines their reports. Stops at
+/// the first segment that can't be verified.
+async fn run_segments(
+    processor: &mut RunProcessor<'_>,
+    run_id: &str,
+    run_rules: &RunRules,
+    expected_mods: &ExpectedMods,
+    install_dir: &FactorioInstallDir,
+    working_dir: &Path,
+    cancel: &CancellationToken,
+) -> Result<ReplayReport, RunProcessingError> {
+    let mut saves = processor
+        .download_segment_saves(run_id, working_dir, cancel)
+        .instrument(tracing::info_span!("download"))
+        .await?;
+    let save_paths: Vec<PathBuf> = saves.iter().map(|save| save.0.clone()).collect();
+    if let Err(e) = processor
+        .run_hooks(HookStage::AfterDownload, working_dir, &save_paths, cancel)
+        .await
+    {
+        save_paths.iter().for_each(|path| cleanup_save_files(path));
+        return Err(e);
+    }
+    let log_paths: Vec<PathBuf> = (1..=saves.len())
+        .map(|number| working_dir.join(format!("output.segment{number}.log")))
+        .collect();
 
-    let result = run_replay_with_save(&mut save_file, run_rules, expected_mods, install_dir).await;
-    cleanup_save_files(&save_file.0);
+    let result = replay_segments(
+        processor,
+        &mut saves,
+        &log_paths,
+        run_rules,
+        expected_mods,
+        install_dir,
+        cancel,
+    )
+    .await;
+    let result = match result {
+        Err(error) => Err(error),
+        Ok(report) => {
+            if let Some(log_path) = &report.log_path
+                && let Err(e) = report.write_json(&report_json_path(log_path))
+            {
+                warn!("Failed to write combined replay report: {e}");
+            }
+            processor
+                .run_after_replay_hooks(report, working_dir, &save_paths, cancel)
+                .await
+        }
+    };
+    if let Ok(report) = &result {
+        processor.write_reports(run_id, report, working_dir);
+        for (i, (save_file, log_path)) in saves.iter().zip(&log_paths).enumerate() {
+            processor
+                .archive_segment(i + 1, &save_file.0, log_path)
+                .await;
+        }
+        processor
+            .publish(run_id, report, &save_paths, working_dir)
+            .await;
+    }
+    save_paths.iter().for_each(|path| cleanup_save_files(path));
     result
 }
 
+/// Replays the saves of a segmented run in order, logging each to its `log_paths` entry, and
+/// combines their reports. Only the last segment is checked for the win condition, as the
+/// others end before the run is won. Stops at the first segment that can't be verified.
+pub(crate) async fn replay_segments(
+    processor: &RunProcessor<'_>,
+    saves: &mut [WrittenSaveFile],
+    log_paths: &[PathBuf],
+    run_rules: &RunRules,
+    expected_mods: &ExpectedMods,
+    install_dir: &FactorioInstallDir,
+    cancel: &CancellationToken,
+) -> Result<ReplayReport, RunProcessingError> {
+    let segment_count = saves.len();
+    let earlier_rules = run_rules.without_win_condition();
+    let mut reports = Vec::new();
+    let progress = processor.progress.as_ref().map(ProgressRecorder::start);
+    for (i, (save_file, log_path)) in saves.iter_mut().zip(log_paths).enumerate() {
+        info!("=== Segment {}/{} ===", i + 1, segment_count);
+        let rules = if i + 1 == segment_count {
+            run_rules
+        } else {
+            &earlier_rules
+        };
+        let options = ReplayOptions {
+            submitted_date: processor.submitted_date,
+            progress: progress.as_ref(),
+            ..Default::default()
+        };
+        let result = tokio::select! {
+            result = run_replay_with_save(save_file, rules, expected_mods, install_dir, log_path, options)
+                .instrument(tracing::info_span!("replay", segment = i + 1)) => result,
+            _ = cancel.cancelled() => Err(RunProcessingError::from_error(
+                ErrorClass::Retryable,
+                &"Replay interrupted by shutdown",
+            )),
+        };
+        let report = result.map_err(|e| RunProcessingError {
+            class: e.class,
+            message: format!("Segment {}: {}", i + 1, e.message),
+        })?;
+        reports.push(report);
+    }
+    Ok(ReplayReport::from_segments(reports))
+}
+
+/// Names downloaded saves `{run_id}_{name}`, so [`find_cached_save`] can find them again.
+fn save_file_name_template(run_id: &str) -> FileNameTemplate {
+    FileNameTemplate::new("{run_id}_{name}").var("run_id", run_id)
+}
+
+/// A previously downloaded save of the run in `working_dir` that still opens as a save file.
+fn find_cached_save(run_id: &str, working_dir: &Path) -> Option<WrittenSaveFile> {
+    let prefix = format!("{}_", sanitize_file_name(run_id));
+    std::fs::read_dir(working_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.starts_with(&prefix)
+                        && name.ends_with(".zip")
+                        && !name.ends_with(".installed.zip")
+                })
+        })
+        .find_map(|path| {
+            let save_file = SaveFile::new(File::open(&path).ok()?).ok()?;
+            Some(WrittenSaveFile(path, save_file))
+        })
+}
+
 async fn run_replay_with_save(
     save_file: &mut WrittenSaveFile,
     run_rules: &RunRules,
     expected_mods: &ExpectedMods,
-    install_dir: &Path,
+    install_dir: &FactorioInstallDir,
+    log_path: &Path,
+    options: ReplayOptions<'_>,
 ) -> Result<ReplayReport, RunProcessingError> {
     let version = save_file.1.get_factorio_version()?;
     if version < MIN_FACTORIO_VERSION {
         return Err(FactorioError::VersionTooOld { version }.into());
     }
 
-    let install_dir = FactorioInstallDir::new_or_create(install_dir)?;
-    let log_path = save_file.0.with_file_name("output.log");
-
-    run_replay(&install_dir, save_file, run_rules, expected_mods, &log_path)
-        .await
-        .map_err(RunProcessingError::from)
+    run_replay(
+        install_dir,
+        save_file,
+        run_rules,
+        expected_mods,
+        log_path,
+        options,
+    )
+    .await
+    .map_err(RunProcessingError::from)
 }
 
 fn cleanup_save_files(save_path: &Path) {
@@ -0,0 +1,136 @@
+use chrono::{DateTime, Timelike, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct QuietHours {
+    /// First quiet hour (UTC, 0-23)
+    pub start: u32,
+    /// First hour after the quiet period; may be before `start` to wrap past midnight
+    pub end: u32,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        let hour = time.hour();
+        if self.start <= self.end {
+            self.start <= hour && hour < self.end
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SchedulingPolicy {
+    /// The poller stops adding runs while this many are waiting to be processed
+    #[serde(default)]
+    pub max_queue_depth: Option<usize>,
+    /// No new runs are started during these hours
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    /// If false, runs are only queued, and processed once given a priority above 0
+    #[serde(default = "default_auto_process")]
+    pub auto_process: bool,
+}
+
+fn default_auto_process() -> bool {
+    true
+}
+
+impl Default for SchedulingPolicy {
+    fn default() -> Self {
+        Self {
+            max_queue_depth: None,
+            quiet_hours: None,
+            auto_process: default_auto_process(),
+        }
+    }
+}
+
+impl SchedulingPolicy {
+    pub fn is_quiet(&self, time: DateTime<Utc>) -> bool {
+        self.quiet_hours
+            .is_some_and(|quiet_hours| quiet_hours.contains(time))
+    }
+}
+
+/// Scheduling policies keyed by "{game_id}/{category_id}", "{game_id}", or "default".
+#[derive(Debug, Clone, Default)]
+pub struct Scheduling {
+    policies: HashMap<String, SchedulingPolicy>,
+}
+
+impl Scheduling {
+    pub fn new(policies: &HashMap<String, SchedulingPolicy>) -> Self {
+        Self {
+            policies: policies.clone(),
+        }
+    }
+
+    pub fn policy(&self, game_id: &str, category_id: &str) -> SchedulingPolicy {
+        [
+            format!("{}/{}", game_id, category_id),
+            game_id.to_string(),
+            "default".to_string(),
+        ]
+        .iter()
+        .find_map(|key| self.policies.get(key))
+        .cloned()
+        .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_hour(hour: u32) -> DateTime<Utc> {
+        format!("2024-01-01T{:02}:30:00Z", hour).parse().unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let daytime = QuietHours { start: 9, end: 17 };
+        assert!(daytime.contains(at_hour(9)));
+        assert!(daytime.contains(at_hour(16)));
+        assert!(!daytime.contains(at_hour(17)));
+        assert!(!daytime.contains(at_hour(3)));
+
+        let overnight = QuietHours { start: 22, end: 6 };
+        assert!(overnight.contains(at_hour(23)));
+        assert!(overnight.contains(at_hour(0)));
+        assert!(overnight.contains(at_hour(5)));
+        assert!(!overnight.contains(at_hour(6)));
+        assert!(!overnight.contains(at_hour(12)));
+    }
+
+    #[test]
+    fn test_policy_lookup() {
+        let policies: HashMap<String, SchedulingPolicy> = serde_yaml::from_str(
+            r#"
+default:
+  max_queue_depth: 50
+game1:
+  auto_process: false
+game1/cat1:
+  quiet_hours: { start: 22, end: 6 }
+"#,
+        )
+        .unwrap();
+        let scheduling = Scheduling::new(&policies);
+
+        let category = scheduling.policy("game1", "cat1");
+        assert_eq!(category.quiet_hours, Some(QuietHours { start: 22, end: 6 }));
+        assert!(category.auto_process);
+        assert!(!scheduling.policy("game1", "cat2").auto_process);
+        assert_eq!(scheduling.policy("game2", "cat1").max_queue_depth, Some(50));
+        assert_eq!(
+            Scheduling::default().policy("game1", "cat1"),
+            SchedulingPolicy::default()
+        );
+    }
+}
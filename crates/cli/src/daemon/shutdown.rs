@@ -0,0 +1,76 @@
+use log::{info, warn};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Two-phase daemon shutdown. Cancelling the drain token stops polling and picking up new
+/// runs; in-flight runs may finish until `drain_timeout` passes, after which the abort token
+/// is cancelled and they are interrupted.
+#[derive(Clone)]
+pub struct ShutdownCoordinator {
+    drain: CancellationToken,
+    abort: CancellationToken,
+}
+
+impl ShutdownCoordinator {
+    pub fn new(drain: CancellationToken, drain_timeout: Duration) -> Self {
+        let abort = CancellationToken::new();
+        tokio::spawn({
+            let drain = drain.clone();
+            let abort = abort.clone();
+            async move {
+                tokio::select! {
+                    _ = drain.cancelled() => {}
+                    _ = abort.cancelled() => return,
+                }
+                info!(
+                    "Draining: waiting up to {}s for in-flight runs",
+                    drain_timeout.as_secs()
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(drain_timeout) => {
+                        warn!("Drain timeout reached, aborting in-flight runs");
+                        abort.cancel();
+                    }
+                    _ = abort.cancelled() => {}
+                }
+            }
+        });
+        Self { drain, abort }
+    }
+
+    /// Cancelled when the daemon should stop taking new work.
+    pub fn drain_token(&self) -> CancellationToken {
+        self.drain.clone()
+    }
+
+    /// Cancelled when in-flight work should be interrupted.
+    pub fn abort_token(&self) -> CancellationToken {
+        self.abort.clone()
+    }
+
+    /// Called once all work has stopped.
+    pub fn finish(&self) {
+        self.abort.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_abort_after_drain_timeout() {
+        let drain = CancellationToken::new();
+        let coordinator = ShutdownCoordinator::new(drain.clone(), Duration::from_secs(60));
+        let abort = coordinator.abort_token();
+
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        assert!(!abort.is_cancelled());
+
+        drain.cancel();
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert!(!abort.is_cancelled());
+        tokio::time::sleep(Duration::from_secs(31)).await;
+        assert!(abort.is_cancelled());
+    }
+}
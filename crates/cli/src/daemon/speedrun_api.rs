@@ -2,15 +2,33 @@ use anyhow::{Context, anyhow};
 use chrono::{DateTime, Utc};
 use reqwest::Client;
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
 use thiserror::Error;
 
 use super::database::connection::Database;
+use super::database::types::{CachedName, NameKind, RunDetails};
 
 const API_BASE: &str = "https://www.speedrun.com/api/v1";
+const DEFAULT_USER_AGENT: &str = "factorio-replay-runner";
+/// API key used when none is configured.
+pub const API_KEY_ENV_VAR: &str = "SPEEDRUN_API_KEY";
+const API_KEY_HEADER: &str = "X-API-Key";
+/// Runs requested per page when listing runs, speedrun.com's maximum.
+pub const RUNS_PAGE_SIZE: usize = 200;
+/// Rate limited requests are retried this many times before failing.
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+/// Wait before retrying a rate limited request without a Retry-After header.
+const DEFAULT_RATE_LIMIT_DELAY: Duration = Duration::from_secs(5);
+const MAX_RATE_LIMIT_DELAY: Duration = Duration::from_secs(60);
+
+/// Cached game and category names older than this are looked up again.
+pub const NAME_CACHE_TTL: chrono::TimeDelta = chrono::TimeDelta::days(30);
+/// How long an ID speedrun.com did not know is remembered as missing.
+pub const MISSING_NAME_TTL: chrono::TimeDelta = chrono::TimeDelta::days(1);
 
 #[derive(Debug, Error)]
 pub enum ApiError {
@@ -30,27 +48,85 @@ pub enum ApiError {
 #[derive(Clone)]
 pub struct SpeedrunClient {
     client: Client,
+    base_url: String,
 }
 
 impl SpeedrunClient {
     pub fn new() -> Result<Self, ApiError> {
+        Self::with_identity(None, None)
+    }
+
+    /// Identifies requests with `user_agent` and authenticates them with `api_key`, which
+    /// defaults to SPEEDRUN_API_KEY. Authenticated requests get higher rate limits.
+    pub fn with_identity(
+        user_agent: Option<&str>,
+        api_key: Option<&str>,
+    ) -> Result<Self, ApiError> {
+        let api_key = api_key
+            .map(str::to_string)
+            .or_else(|| std::env::var(API_KEY_ENV_VAR).ok());
+        let mut headers = reqwest::header::HeaderMap::new();
+        if let Some(api_key) = api_key {
+            let mut value = reqwest::header::HeaderValue::from_str(&api_key)
+                .context("Invalid speedrun.com API key")
+                .map_err(ApiError::NetworkError)?;
+            value.set_sensitive(true);
+            headers.insert(API_KEY_HEADER, value);
+        }
         let client = Client::builder()
-            .user_agent("factorio-replay-runner")
+            .user_agent(user_agent.unwrap_or(DEFAULT_USER_AGENT))
+            .default_headers(headers)
             .build()
             .context("Failed to create HTTP client")
             .map_err(ApiError::NetworkError)?;
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            base_url: API_BASE.to_string(),
+        })
+    }
+
+    /// Sends requests to `base_url` instead of speedrun.com.
+    #[cfg(test)]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Sends a GET request, waiting out rate limits up to [`MAX_RATE_LIMIT_RETRIES`] times.
+    async fn get(&self, url: &str) -> Result<reqwest::Response, ApiError> {
+        let mut retries = 0;
+        loop {
+            let response = self
+                .client
+                .get(url)
+                .send()
+                .await
+                .context("Failed to send request")
+                .map_err(ApiError::NetworkError)?;
+            if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+            if retries == MAX_RATE_LIMIT_RETRIES {
+                return Err(ApiError::NetworkError(anyhow!(
+                    "Still rate limited after {} retries",
+                    retries
+                )));
+            }
+            let delay = retry_after(&response)
+                .unwrap_or(DEFAULT_RATE_LIMIT_DELAY)
+                .min(MAX_RATE_LIMIT_DELAY);
+            log::warn!(
+                "Rate limited by speedrun.com, retrying in {}s",
+                delay.as_secs()
+            );
+            tokio::time::sleep(delay).await;
+            retries += 1;
+        }
     }
 
     pub async fn get_run(&self, run_id: &str) -> Result<Run, ApiError> {
-        let url = format!("{}/runs/{}?embed=players", API_BASE, run_id);
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .context("Failed to send request")
-            .map_err(ApiError::NetworkError)?;
+        let url = format!("{}/runs/{}?embed=players,platform", self.base_url, run_id);
+        let response = self.get(&url).await?;
 
         if !response.status().is_success() {
             return Err(ApiError::NotFound(anyhow::anyhow!(
@@ -69,7 +145,7 @@ impl SpeedrunClient {
     }
 
     pub async fn list_runs(&self, query: &RunsQuery) -> Result<Vec<Run>, ApiError> {
-        let mut url = format!("{}/runs", API_BASE);
+        let mut url = format!("{}/runs", self.base_url);
         let mut params = vec![];
 
         if let Some(game) = &query.game {
@@ -90,19 +166,16 @@ impl SpeedrunClient {
         if let Some(max) = query.max {
             params.push(format!("max={}", max));
         }
+        if let Some(embed) = &query.embed {
+            params.push(format!("embed={}", embed));
+        }
 
         if !params.is_empty() {
             url.push('?');
             url.push_str(&params.join("&"));
         }
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .context("Failed to send request")
-            .map_err(ApiError::NetworkError)?;
+        let response = self.get(&url).await?;
 
         if !response.status().is_success() {
             return Err(ApiError::NotFound(anyhow::anyhow!(
@@ -123,7 +196,7 @@ impl SpeedrunClient {
     pub async fn stream_runs(&self, query: &RunsQuery) -> Result<Vec<Run>, ApiError> {
         let mut all_runs = Vec::new();
         let mut offset = 0;
-        let page_size = 200;
+        let page_size = RUNS_PAGE_SIZE;
 
         loop {
             let mut page_query = query.clone();
@@ -145,17 +218,13 @@ impl SpeedrunClient {
     }
 
     pub async fn get_game(&self, game_id: &str) -> Result<Game, ApiError> {
-        let url = format!("{}/games/{}", API_BASE, game_id);
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .context("Failed to send request")
-            .map_err(ApiError::NetworkError)?;
+        let url = format!("{}/games/{}", self.base_url, game_id);
+        let response = self.get(&url).await?;
 
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ApiError::NotFound(anyhow!("Unknown game {}", game_id)));
+        }
         if !response.status().is_success() {
-            // anyhow::bail!("API request failed: {}", response.status());
             return Err(ApiError::NetworkError(anyhow!(
                 "API request failed: {}",
                 response.status()
@@ -172,15 +241,15 @@ impl SpeedrunClient {
     }
 
     pub async fn get_category(&self, category_id: &str) -> Result<Category, ApiError> {
-        let url = format!("{}/categories/{}", API_BASE, category_id);
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .context("Failed to send request")
-            .map_err(ApiError::NetworkError)?;
+        let url = format!("{}/categories/{}", self.base_url, category_id);
+        let response = self.get(&url).await?;
 
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ApiError::NotFound(anyhow!(
+                "Unknown category {}",
+                category_id
+            )));
+        }
         if !response.status().is_success() {
             return Err(ApiError::NetworkError(anyhow!(
                 "API request failed: {}",
@@ -196,6 +265,85 @@ impl SpeedrunClient {
 
         Ok(wrapper.data)
     }
+
+    /// The leaderboard of a category, narrowed to the given variable values.
+    pub async fn get_leaderboard(
+        &self,
+        game_id: &str,
+        category_id: &str,
+        variable_values: &BTreeMap<String, String>,
+    ) -> Result<Leaderboard, ApiError> {
+        let mut url = format!(
+            "{}/leaderboards/{}/category/{}",
+            self.base_url, game_id, category_id
+        );
+        let params: Vec<String> = variable_values
+            .iter()
+            .map(|(variable, value)| format!("var-{}={}", variable, value))
+            .collect();
+        if !params.is_empty() {
+            url.push('?');
+            url.push_str(&params.join("&"));
+        }
+        let response = self.get(&url).await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ApiError::NotFound(anyhow!(
+                "No leaderboard for {} / {}",
+                game_id,
+                category_id
+            )));
+        }
+        if !response.status().is_success() {
+            return Err(ApiError::NetworkError(anyhow!(
+                "API request failed: {}",
+                response.status()
+            )));
+        }
+
+        let wrapper: LeaderboardResponse = response
+            .json()
+            .await
+            .context("Failed to parse leaderboard response")
+            .map_err(ApiError::ParseError)?;
+
+        Ok(wrapper.data)
+    }
+
+    pub async fn get_game_categories(&self, game_id: &str) -> Result<Vec<Category>, ApiError> {
+        let url = format!("{}/games/{}/categories", self.base_url, game_id);
+        let response = self.get(&url).await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ApiError::NotFound(anyhow!("Unknown game {}", game_id)));
+        }
+        if !response.status().is_success() {
+            return Err(ApiError::NetworkError(anyhow!(
+                "API request failed: {}",
+                response.status()
+            )));
+        }
+
+        let wrapper: CategoriesResponse = response
+            .json()
+            .await
+            .context("Failed to parse categories response")
+            .map_err(ApiError::ParseError)?;
+
+        Ok(wrapper.data)
+    }
+}
+
+/// The delay of a Retry-After header given in seconds.
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let seconds = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
 }
 
 #[derive(Debug, Clone)]
@@ -206,6 +354,7 @@ pub struct RunsQuery {
     pub direction: Option<String>,
     pub offset: Option<usize>,
     pub max: Option<usize>,
+    pub embed: Option<String>,
 }
 
 impl RunsQuery {
@@ -217,6 +366,7 @@ impl RunsQuery {
             direction: None,
             offset: None,
             max: None,
+            embed: None,
         }
     }
 
@@ -239,6 +389,12 @@ impl RunsQuery {
         self.direction = Some(direction.into());
         self
     }
+
+    /// Comma-separated resources embedded in each run, e.g. "players,platform"
+    pub fn embed(mut self, embed: impl Into<String>) -> Self {
+        self.embed = Some(embed.into());
+        self
+    }
 }
 
 impl Default for RunsQuery {
@@ -267,6 +423,48 @@ struct CategoryResponse {
     data: Category,
 }
 
+#[derive(Debug, Deserialize)]
+struct CategoriesResponse {
+    data: Vec<Category>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LeaderboardResponse {
+    data: Leaderboard,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Leaderboard {
+    pub runs: Vec<LeaderboardEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardEntry {
+    pub place: u32,
+    pub run: LeaderboardRun,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardRun {
+    pub id: String,
+    pub times: RunTimes,
+}
+
+impl Leaderboard {
+    /// The run's place if it is on the leaderboard, otherwise the place its time would take.
+    pub fn place_of(&self, run_id: &str, time_secs: f64) -> u32 {
+        if let Some(entry) = self.runs.iter().find(|entry| entry.run.id == run_id) {
+            return entry.place;
+        }
+        let faster = self
+            .runs
+            .iter()
+            .filter(|entry| entry.run.times.primary_t < time_secs)
+            .count();
+        faster as u32 + 1
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RunTimes {
     pub primary_t: f64,
@@ -290,6 +488,19 @@ pub enum Players {
     Simple(Vec<RunPlayer>),
 }
 
+/// The run's platform; only its ID unless embedded. Runs without one embed an empty list.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Platform {
+    Embedded { data: PlatformData },
+    Other(serde::de::IgnoredAny),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlatformData {
+    pub name: String,
+}
+
 impl Players {
     pub fn as_slice(&self) -> &[RunPlayer] {
         match self {
@@ -308,6 +519,32 @@ pub struct Run {
     pub submitted: Option<String>,
     pub times: Option<RunTimes>,
     pub players: Option<Players>,
+    #[serde(default)]
+    pub platform: Option<Platform>,
+    #[serde(default)]
+    pub values: BTreeMap<String, String>,
+    #[serde(default)]
+    pub videos: Option<RunVideos>,
+    #[serde(default)]
+    pub splits: Option<RunSplits>,
+}
+
+/// The run's video field: free text, and the links found in it.
+#[derive(Debug, Deserialize)]
+pub struct RunVideos {
+    pub text: Option<String>,
+    pub links: Option<Vec<RunLink>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RunLink {
+    pub uri: String,
+}
+
+/// The run's splits.io link.
+#[derive(Debug, Deserialize)]
+pub struct RunSplits {
+    pub uri: String,
 }
 
 impl Run {
@@ -319,14 +556,6 @@ impl Run {
         parse_datetime(submitted_str)
     }
 
-    pub fn format_time(&self) -> Option<String> {
-        let secs = self.times.as_ref()?.primary_t;
-        let hours = (secs / 3600.0) as u32;
-        let mins = ((secs % 3600.0) / 60.0) as u32;
-        let secs = (secs % 60.0) as u32;
-        Some(format!("{}:{:02}:{:02}", hours, mins, secs))
-    }
-
     pub fn format_players(&self) -> Option<String> {
         let players = self.players.as_ref()?.as_slice();
         let names: Vec<&str> = players
@@ -340,6 +569,19 @@ impl Run {
             .collect();
         (!names.is_empty()).then(|| names.join(", "))
     }
+
+    pub fn details(&self) -> RunDetails {
+        RunDetails {
+            player_names: self.format_players(),
+            run_time_secs: self.times.as_ref().map(|times| times.primary_t),
+            platform: match &self.platform {
+                Some(Platform::Embedded { data }) => Some(data.name.clone()),
+                _ => None,
+            },
+            variable_values: self.values.clone(),
+            leaderboard_place: None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -354,7 +596,26 @@ pub struct GameNames {
 
 #[derive(Debug, Deserialize)]
 pub struct Category {
+    pub id: String,
     pub name: String,
+    #[serde(rename = "type")]
+    pub category_type: CategoryType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CategoryType {
+    PerGame,
+    /// Individual level runs, which aren't verified.
+    PerLevel,
+}
+
+/// Formats a run time in seconds as `h:mm:ss`.
+pub fn format_run_time(secs: f64) -> String {
+    let hours = (secs / 3600.0) as u32;
+    let mins = ((secs % 3600.0) / 60.0) as u32;
+    let secs = (secs % 60.0) as u32;
+    format!("{}:{:02}:{:02}", hours, mins, secs)
 }
 
 pub fn parse_datetime(s: &str) -> Result<DateTime<Utc>, ApiError> {
@@ -364,10 +625,19 @@ pub fn parse_datetime(s: &str) -> Result<DateTime<Utc>, ApiError> {
         .map_err(ApiError::ParseError)
 }
 
+/// A cached value and when it was fetched or read from the database.
+type Cached<T> = (T, DateTime<Utc>);
+type MemoryCache<K, V> = Arc<RwLock<HashMap<K, Cached<V>>>>;
+
+/// Whether an entry of a cache in memory is younger than [`NAME_CACHE_TTL`].
+fn is_fresh<T>(cached: &Cached<T>) -> bool {
+    Utc::now() - cached.1 < NAME_CACHE_TTL
+}
+
 #[derive(Clone)]
 pub struct SpeedrunOps {
-    games: Arc<RwLock<HashMap<String, String>>>,
-    categories: Arc<RwLock<HashMap<String, String>>>,
+    names: MemoryCache<(NameKind, String), String>,
+    run_details: MemoryCache<String, RunDetails>,
     pub client: SpeedrunClient,
     db: Option<Database>,
 }
@@ -375,8 +645,8 @@ pub struct SpeedrunOps {
 impl SpeedrunOps {
     pub fn new(client: &SpeedrunClient) -> Self {
         Self {
-            games: Arc::new(RwLock::new(HashMap::new())),
-            categories: Arc::new(RwLock::new(HashMap::new())),
+            names: Arc::new(RwLock::new(HashMap::new())),
+            run_details: Arc::new(RwLock::new(HashMap::new())),
             client: client.clone(),
             db: None,
         }
@@ -388,59 +658,143 @@ impl SpeedrunOps {
     }
 
     pub async fn get_game_name(&self, game_id: &str) -> Result<String, ApiError> {
+        self.get_name(NameKind::Game, game_id).await
+    }
+
+    pub async fn get_category_name(&self, category_id: &str) -> Result<String, ApiError> {
+        self.get_name(NameKind::Category, category_id).await
+    }
+
+    /// Looks a name up in memory, then the database, then speedrun.com. Cached names are
+    /// refreshed after [`NAME_CACHE_TTL`] but still used if speedrun.com can't be reached,
+    /// and unknown IDs are not looked up again for [`MISSING_NAME_TTL`].
+    async fn get_name(&self, kind: NameKind, id: &str) -> Result<String, ApiError> {
+        let key = (kind, id.to_string());
+        if let Some(cached) = self.names.read().await.get(&key)
+            && is_fresh(cached)
         {
-            let games = self.games.read().await;
-            if let Some(name) = games.get(game_id) {
-                return Ok(name.clone());
-            }
+            return Ok(cached.0.clone());
         }
 
-        if let Some(db) = &self.db
-            && let Ok(Some(name)) = db.get_cached_game_name(game_id).await
-        {
-            let mut games = self.games.write().await;
-            games.insert(game_id.to_string(), name.clone());
-            return Ok(name);
+        let cached = match &self.db {
+            Some(db) => db.get_cached_name(kind, id).await.unwrap_or_else(|e| {
+                log::warn!("Failed to read cached {} name: {:#}", kind.as_str(), e);
+                None
+            }),
+            None => None,
+        };
+        let now = Utc::now();
+        match &cached {
+            Some(CachedName {
+                name: Some(name),
+                updated_at,
+            }) if now - *updated_at < NAME_CACHE_TTL => {
+                self.names
+                    .write()
+                    .await
+                    .insert(key, (name.clone(), *updated_at));
+                return Ok(name.clone());
+            }
+            Some(CachedName {
+                name: None,
+                updated_at,
+            }) if now - *updated_at < MISSING_NAME_TTL => {
+                return Err(ApiError::NotFound(anyhow!(
+                    "Unknown {} {} (cached)",
+                    kind.as_str(),
+                    id
+                )));
+            }
+            _ => {}
         }
 
-        let game = self.client.get_game(game_id).await?;
-        let name = game.names.international;
+        let fetched = match kind {
+            NameKind::Game => self
+                .client
+                .get_game(id)
+                .await
+                .map(|game| game.names.international),
+            NameKind::Category => self
+                .client
+                .get_category(id)
+                .await
+                .map(|category| category.name),
+        };
+        let name = match fetched {
+            Ok(name) => name,
+            Err(e @ ApiError::NotFound(_)) => {
+                if let Some(db) = &self.db {
+                    let _ = db.cache_missing_name(kind, id).await;
+                }
+                return Err(e);
+            }
+            Err(e) => {
+                // kept in memory as of now, so speedrun.com isn't asked again on every lookup
+                let stale = cached.and_then(|cached| cached.name).ok_or(e)?;
+                self.names.write().await.insert(key, (stale.clone(), now));
+                return Ok(stale);
+            }
+        };
 
         if let Some(db) = &self.db {
-            let _ = db.cache_game_name(game_id, &name).await;
+            let _ = db.cache_name(kind, id, &name).await;
         }
-        let mut games = self.games.write().await;
-        games.insert(game_id.to_string(), name.clone());
+        self.names.write().await.insert(key, (name.clone(), now));
 
         Ok(name)
     }
 
-    pub async fn get_category_name(&self, category_id: &str) -> Result<String, ApiError> {
+    /// A run's players, time, platform, variables and leaderboard place: the ones stored
+    /// with the run if they include its place and are younger than [`NAME_CACHE_TTL`],
+    /// otherwise fetched and stored.
+    pub async fn get_run_details(&self, run_id: &str) -> Result<RunDetails, ApiError> {
+        if let Some(cached) = self.run_details.read().await.get(run_id)
+            && is_fresh(cached)
         {
-            let categories = self.categories.read().await;
-            if let Some(name) = categories.get(category_id) {
-                return Ok(name.clone());
-            }
+            return Ok(cached.0.clone());
         }
 
-        if let Some(db) = &self.db
-            && let Ok(Some(name)) = db.get_cached_category_name(category_id).await
-        {
-            let mut categories = self.categories.write().await;
-            categories.insert(category_id.to_string(), name.clone());
-            return Ok(name);
+        let now = Utc::now();
+        let stored = match &self.db {
+            Some(db) => db
+                .get_run_details_since(run_id, now - NAME_CACHE_TTL)
+                .await
+                .unwrap_or_else(|e| {
+                    log::warn!("Failed to read stored details of run {}: {:#}", run_id, e);
+                    None
+                }),
+            None => None,
+        };
+        if let Some(details) = stored.filter(|details| details.leaderboard_place.is_some()) {
+            let mut run_details = self.run_details.write().await;
+            run_details.insert(run_id.to_string(), (details.clone(), now));
+            return Ok(details);
         }
 
-        let category = self.client.get_category(category_id).await?;
-        let name = category.name;
+        let run = self.client.get_run(run_id).await?;
+        let mut details = run.details();
+        if let Some(time_secs) = details.run_time_secs {
+            match self
+                .client
+                .get_leaderboard(&run.game, &run.category, &run.values)
+                .await
+            {
+                Ok(leaderboard) => {
+                    details.leaderboard_place = Some(leaderboard.place_of(run_id, time_secs));
+                }
+                Err(e) => log::warn!("Failed to get leaderboard of run {}: {}", run_id, e),
+            }
+        }
 
-        if let Some(db) = &self.db {
-            let _ = db.cache_category_name(category_id, &name).await;
+        if let Some(db) = &self.db
+            && let Err(e) = db.store_run_details(run_id, &details).await
+        {
+            log::warn!("Failed to store details of run {}: {:#}", run_id, e);
         }
-        let mut categories = self.categories.write().await;
-        categories.insert(category_id.to_string(), name.clone());
+        let mut run_details = self.run_details.write().await;
+        run_details.insert(run_id.to_string(), (details.clone(), now));
 
-        Ok(name)
+        Ok(details)
     }
 
     pub async fn format_game_category(&self, game_id: &str, category_id: &str) -> String {
@@ -456,3 +810,282 @@ impl SpeedrunOps {
         format!("{} / {}", game_name, category_name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::database::types::NewRun;
+    use crate::speedrun_mock::{SpeedrunMock, run_json};
+
+    fn runs(count: usize) -> Vec<serde_json::Value> {
+        (0..count)
+            .map(|i| {
+                run_json(
+                    &format!("run{}", i),
+                    "game1",
+                    "cat1",
+                    "2024-01-01T00:00:00Z",
+                )
+            })
+            .collect()
+    }
+
+    fn category_query() -> RunsQuery {
+        RunsQuery::new().game("game1").category("cat1")
+    }
+
+    #[test]
+    fn test_run_details_from_embedded_run() {
+        let run: Run = serde_json::from_value(serde_json::json!({
+            "id": "run1",
+            "game": "game1",
+            "category": "cat1",
+            "times": { "primary_t": 3723.5 },
+            "players": { "data": [
+                { "names": { "international": "alice" } },
+                { "name": "bob" }
+            ] },
+            "platform": { "data": { "id": "pc", "name": "PC" } },
+            "values": { "var1": "val1" }
+        }))
+        .unwrap();
+
+        let details = run.details();
+        assert_eq!(details.player_names.as_deref(), Some("alice, bob"));
+        assert_eq!(details.run_time_secs, Some(3723.5));
+        assert_eq!(details.platform.as_deref(), Some("PC"));
+        assert_eq!(details.variable_values["var1"], "val1");
+        assert_eq!(format_run_time(3723.5), "1:02:03");
+    }
+
+    #[tokio::test]
+    async fn test_names_served_from_database_cache() {
+        let db = Database::in_memory().await.unwrap();
+        db.cache_name(NameKind::Game, "game1", "Factorio")
+            .await
+            .unwrap();
+        db.cache_missing_name(NameKind::Category, "gone")
+            .await
+            .unwrap();
+        let mock = SpeedrunMock::start().await;
+        let ops = SpeedrunOps::new(&mock.client()).with_db(db);
+
+        assert_eq!(ops.get_game_name("game1").await.unwrap(), "Factorio");
+        assert!(matches!(
+            ops.get_category_name("gone").await,
+            Err(ApiError::NotFound(_))
+        ));
+        assert!(mock.requests("/games/game1").await.is_empty());
+        assert!(mock.requests("/categories/gone").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_names_fetched_and_cached() {
+        let db = Database::in_memory().await.unwrap();
+        let mock = SpeedrunMock::start().await;
+        mock.game("game1", "Factorio").await;
+        mock.category("cat1", "Any%").await;
+        let ops = SpeedrunOps::new(&mock.client()).with_db(db.clone());
+
+        assert_eq!(
+            ops.format_game_category("game1", "cat1").await,
+            "Factorio / Any%"
+        );
+        assert_eq!(
+            ops.format_game_category("game1", "cat1").await,
+            "Factorio / Any%"
+        );
+        assert_eq!(mock.requests("/games/game1").await.len(), 1);
+        let cached = db
+            .get_cached_name(NameKind::Category, "cat1")
+            .await
+            .unwrap();
+        assert_eq!(cached.unwrap().name.as_deref(), Some("Any%"));
+
+        // unknown to speedrun.com, which answers 404
+        assert!(matches!(
+            ops.get_game_name("gone").await,
+            Err(ApiError::NotFound(_))
+        ));
+        let cached = db.get_cached_name(NameKind::Game, "gone").await.unwrap();
+        assert_eq!(cached.unwrap().name, None);
+    }
+
+    #[tokio::test]
+    async fn test_names_in_memory_expire() {
+        let mock = SpeedrunMock::start().await;
+        mock.game("game1", "Factorio").await;
+        let ops = SpeedrunOps::new(&mock.client());
+        let expired = Utc::now() - NAME_CACHE_TTL - chrono::TimeDelta::hours(1);
+        ops.names.write().await.insert(
+            (NameKind::Game, "game1".to_string()),
+            ("Old name".to_string(), expired),
+        );
+
+        assert_eq!(ops.get_game_name("game1").await.unwrap(), "Factorio");
+        assert_eq!(ops.get_game_name("game1").await.unwrap(), "Factorio");
+        assert_eq!(mock.requests("/games/game1").await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_identity_headers() {
+        let mock = SpeedrunMock::start().await;
+        mock.game("game1", "Factorio").await;
+
+        let client = SpeedrunClient::with_identity(
+            Some("factorio-replay-runner (contact: mods@example.com)"),
+            Some("key123"),
+        )
+        .unwrap()
+        .with_base_url(mock.uri());
+        client.get_game("game1").await.unwrap();
+        let request = &mock.requests("/games/game1").await[0];
+        assert_eq!(
+            request.headers["user-agent"],
+            "factorio-replay-runner (contact: mods@example.com)"
+        );
+        assert_eq!(request.headers["x-api-key"], "key123");
+
+        assert!(SpeedrunClient::with_identity(None, Some("bad\nkey")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_run_details() {
+        let mock = SpeedrunMock::start().await;
+        mock.run(run_json("run1", "game1", "cat1", "2024-01-01T00:00:00Z"))
+            .await;
+        let ops = SpeedrunOps::new(&mock.client());
+
+        let details = ops.get_run_details("run1").await.unwrap();
+        assert_eq!(details.player_names.as_deref(), Some("runner"));
+        assert_eq!(details.platform.as_deref(), Some("PC"));
+        ops.get_run_details("run1").await.unwrap();
+        let requests = mock.requests("/runs/run1").await;
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].url.query(), Some("embed=players,platform"));
+
+        assert!(matches!(
+            ops.client.get_run("missing").await,
+            Err(ApiError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_details_leaderboard_place() {
+        let db = Database::in_memory().await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new("run1", "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+        let mock = SpeedrunMock::start().await;
+        let mut run = run_json("run1", "game1", "cat1", "2024-01-01T00:00:00Z");
+        run["values"] = serde_json::json!({ "var1": "val1" });
+        mock.run(run).await;
+        mock.leaderboard("game1", "cat1", &[("fast", 3000.0), ("slow", 4000.0)])
+            .await;
+
+        let ops = SpeedrunOps::new(&mock.client()).with_db(db.clone());
+        let details = ops.get_run_details("run1").await.unwrap();
+        // not on the leaderboard yet, so placed by its time of 3600s
+        assert_eq!(details.leaderboard_place, Some(2));
+        let requests = mock.requests("/leaderboards/game1/category/cat1").await;
+        assert_eq!(requests[0].url.query(), Some("var-var1=val1"));
+
+        // a fresh process uses the stored details
+        let ops = SpeedrunOps::new(&mock.client()).with_db(db.clone());
+        assert_eq!(ops.get_run_details("run1").await.unwrap(), details);
+        assert_eq!(mock.requests("/runs/run1").await.len(), 1);
+    }
+
+    #[test]
+    fn test_leaderboard_place_of_listed_run() {
+        let leaderboard: Leaderboard = serde_json::from_value(serde_json::json!({
+            "runs": [
+                { "place": 1, "run": { "id": "a", "times": { "primary_t": 10.0 } } },
+                { "place": 1, "run": { "id": "b", "times": { "primary_t": 10.0 } } },
+                { "place": 3, "run": { "id": "c", "times": { "primary_t": 20.0 } } },
+            ]
+        }))
+        .unwrap();
+        assert_eq!(leaderboard.place_of("b", 10.0), 1);
+        assert_eq!(leaderboard.place_of("new", 5.0), 1);
+        assert_eq!(leaderboard.place_of("new", 15.0), 3);
+        assert_eq!(leaderboard.place_of("new", 25.0), 4);
+    }
+
+    #[tokio::test]
+    async fn test_stream_runs_pagination_boundaries() {
+        for (count, pages) in [
+            (0, 1),
+            (RUNS_PAGE_SIZE - 1, 1),
+            (RUNS_PAGE_SIZE, 2),
+            (RUNS_PAGE_SIZE + 1, 2),
+        ] {
+            let mock = SpeedrunMock::start().await;
+            mock.runs("game1", "cat1", runs(count)).await;
+
+            let streamed = mock.client().stream_runs(&category_query()).await.unwrap();
+            assert_eq!(streamed.len(), count);
+            if let Some(last) = streamed.last() {
+                assert_eq!(last.id, format!("run{}", count - 1));
+            }
+            assert_eq!(mock.requests("/runs").await.len(), pages, "{} runs", count);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_requests_are_retried() {
+        let mock = SpeedrunMock::start().await;
+        mock.runs("game1", "cat1", runs(RUNS_PAGE_SIZE + 1)).await;
+        mock.rate_limit("/runs", MAX_RATE_LIMIT_RETRIES as u64)
+            .await;
+
+        let streamed = mock.client().stream_runs(&category_query()).await.unwrap();
+        assert_eq!(streamed.len(), RUNS_PAGE_SIZE + 1);
+        let offsets: Vec<_> = mock
+            .requests("/runs")
+            .await
+            .iter()
+            .map(|request| {
+                request
+                    .url
+                    .query_pairs()
+                    .find(|(key, _)| key == "offset")
+                    .unwrap()
+                    .1
+                    .into_owned()
+            })
+            .collect();
+        assert_eq!(offsets, ["0", "0", "0", "0", "200"]);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_gives_up() {
+        let mock = SpeedrunMock::start().await;
+        mock.game_categories("game1", &[("cat1", "Any%")]).await;
+        mock.rate_limit("/games/game1/categories", MAX_RATE_LIMIT_RETRIES as u64 + 1)
+            .await;
+
+        let client = mock.client();
+        let err = client.get_game_categories("game1").await.unwrap_err();
+        assert!(matches!(err, ApiError::NetworkError(_)));
+        assert!(err.to_string().contains("rate limited"));
+
+        let categories = client.get_game_categories("game1").await.unwrap();
+        assert_eq!(categories[0].name, "Any%");
+        assert_eq!(categories[0].category_type, CategoryType::PerGame);
+    }
+
+    #[test]
+    fn test_run_without_platform() {
+        let run: Run = serde_json::from_value(serde_json::json!({
+            "id": "run1",
+            "game": "game1",
+            "category": "cat1",
+            "platform": { "data": [] }
+        }))
+        .unwrap();
+
+        assert_eq!(run.details().platform, None);
+    }
+}
@@ -0,0 +1,432 @@
+//! Verifications of saves submitted directly, so runners can check a save before
+//! submitting it to speedrun.com. Jobs come from the HTTP API's POST /verify and from
+//! `cli jobs submit`, and are kept in their own table, apart from the runs.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use log::{error, info, warn};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use zip_downloader::security;
+
+use crate::daemon::config::SrcRunRules;
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::{JobSource, NewJob, VerifyJob};
+use crate::daemon::processor::LEASE_DURATION;
+use crate::daemon::run_processing::{
+    RunProcessingContext, RunProcessor, default_security_config, run_uploaded_replay,
+};
+use crate::run_replay::ReplayReport;
+
+/// Where the daemon keeps submitted saves, under its output directory.
+pub const JOBS_DIR: &str = "verify-jobs";
+
+const SAVE_FILE_NAME: &str = "save.zip";
+
+/// How often the queue is checked for jobs submitted by other processes, like the CLI.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Submits verification jobs, keeping their saves until they are replayed.
+#[derive(Clone)]
+pub struct VerifyJobs {
+    db: Database,
+    src_rules: SrcRunRules,
+    dir: PathBuf,
+    /// The machine `dir` is on; only daemons on it can replay the jobs.
+    host: String,
+    work_notify: Arc<Notify>,
+}
+
+impl VerifyJobs {
+    /// Keeps submitted saves and their logs in `dir`, on the machine `host`.
+    pub fn new(db: Database, src_rules: SrcRunRules, dir: PathBuf, host: String) -> Self {
+        Self {
+            db,
+            src_rules,
+            dir,
+            host,
+            work_notify: Arc::default(),
+        }
+    }
+
+    /// A new job ID, and where its save is to be put before it is submitted.
+    pub fn create_job(&self) -> Result<(String, PathBuf)> {
+        let job_id = format!("{:016x}", fastrand::u64(..));
+        let job_dir = self.dir.join(&job_id);
+        std::fs::create_dir_all(&job_dir)
+            .with_context(|| format!("Failed to create {}", job_dir.display()))?;
+        Ok((job_id, job_dir.join(SAVE_FILE_NAME)))
+    }
+
+    /// Removes the save of a job that wasn't submitted.
+    pub fn discard_job(&self, job_id: &str) {
+        let _ = std::fs::remove_dir_all(self.dir.join(job_id));
+    }
+
+    /// Checks that the game and category have rules and that the save of `job_id` passes
+    /// their download checks. Errors are the submitter's to fix; the save is then removed.
+    pub fn check_save(&self, job_id: &str, game_id: &str, category_id: &str) -> Result<()> {
+        let result = self.check_save_inner(job_id, game_id, category_id);
+        if result.is_err() {
+            self.discard_job(job_id);
+        }
+        result
+    }
+
+    fn check_save_inner(&self, job_id: &str, game_id: &str, category_id: &str) -> Result<()> {
+        let (run_rules, _) = self.src_rules.resolve_rules(game_id, category_id)?;
+        let security_config = default_security_config().with_overrides(&run_rules.security);
+        let mut file = File::open(self.save_path(job_id)).context("No save uploaded")?;
+        let size = file.metadata()?.len();
+        anyhow::ensure!(
+            size <= security_config.max_file_size,
+            "Save is too large: {} bytes, at most {} allowed",
+            size,
+            security_config.max_file_size
+        );
+        security::validate_zip(&mut file, &security_config)?;
+        security::validate_factorio_save(&mut file)
+    }
+
+    /// Queues a job whose save passed [`Self::check_save`].
+    pub async fn submit(
+        &self,
+        job_id: &str,
+        source: JobSource,
+        game_id: &str,
+        category_id: &str,
+    ) -> Result<VerifyJob> {
+        let job = NewJob {
+            job_id: job_id.to_string(),
+            source,
+            game_id: game_id.to_string(),
+            category_id: category_id.to_string(),
+            save_path: self.save_path(job_id).to_string_lossy().into_owned(),
+            host: self.host.clone(),
+        };
+        self.db.insert_job(&job).await?;
+        self.work_notify.notify_one();
+        self.db
+            .get_job(job_id)
+            .await?
+            .context("Job not found after submitting it")
+    }
+
+    /// The largest save the rules of any category accept, in bytes.
+    pub fn max_save_size(&self) -> u64 {
+        self.src_rules
+            .games
+            .values()
+            .flat_map(|game| game.categories.values())
+            .map(|category| {
+                default_security_config()
+                    .with_overrides(&category.run_rules.security)
+                    .max_file_size
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub async fn get(&self, job_id: &str) -> Result<Option<VerifyJob>> {
+        self.db.get_job(job_id).await
+    }
+
+    fn save_path(&self, job_id: &str) -> PathBuf {
+        self.dir.join(job_id).join(SAVE_FILE_NAME)
+    }
+}
+
+/// Replays the queued jobs whose saves are on this machine one at a time, with their own
+/// Factorio installs so they don't wait for the run workers. Stops once `token` is cancelled.
+pub async fn run_verify_jobs_loop(
+    ctx: RunProcessingContext,
+    jobs: VerifyJobs,
+    instance_id: &str,
+    token: CancellationToken,
+) -> Result<()> {
+    let requeued = ctx.db.requeue_jobs(instance_id).await?;
+    if !requeued.is_empty() {
+        warn!(
+            "Queued {} interrupted verification job(s) again: {}",
+            requeued.len(),
+            requeued.join(", ")
+        );
+    }
+
+    let install_dir = ctx.install_dir.join(JOBS_DIR);
+    while !token.is_cancelled() {
+        let claimed = ctx
+            .db
+            .claim_next_job(instance_id, &jobs.host, Utc::now() + LEASE_DURATION)
+            .await;
+        let job = match claimed {
+            Ok(Some(job)) => job,
+            Ok(None) => {
+                tokio::select! {
+                    _ = token.cancelled() => {}
+                    _ = jobs.work_notify.notified() => {}
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                }
+                continue;
+            }
+            Err(e) => {
+                error!("Failed to claim a verification job: {:#}", e);
+                tokio::time::sleep(POLL_INTERVAL).await;
+                continue;
+            }
+        };
+        let span = tracing::info_span!(
+            "verify_job",
+            job_id = %job.job_id,
+            game = %job.game_id,
+            category = %job.category_id
+        );
+        // cancelled on shutdown, or when another daemon takes over the job
+        let cancel = ctx.shutdown.child_token();
+        let lease = tokio::spawn(
+            keep_job_lease(
+                ctx.db.clone(),
+                job.job_id.clone(),
+                instance_id.to_string(),
+                cancel.clone(),
+            )
+            .instrument(span.clone()),
+        );
+        let result = verify(&ctx, &job, &install_dir, &cancel)
+            .instrument(span)
+            .await;
+        lease.abort();
+        if result.is_err() && ctx.shutdown.is_cancelled() {
+            // queued again when the daemon starts
+            info!("Verification job {} interrupted by shutdown", job.job_id);
+            break;
+        }
+        if cancel.is_cancelled() && !ctx.shutdown.is_cancelled() {
+            // finished by the daemon that took it over
+            continue;
+        }
+        let result = result.map_err(|e| format!("{:#}", e));
+        match &result {
+            Ok(_) => info!("Verification job {} finished", job.job_id),
+            Err(e) => warn!("Verification job {} failed: {}", job.job_id, e),
+        }
+        ctx.db
+            .finish_job(&job.job_id, result.as_ref().map_err(String::as_str))
+            .await?;
+    }
+    info!("Verification job processor shutting down");
+    Ok(())
+}
+
+/// Renews the lease on a job while it is replayed, and cancels `lease_lost` if another
+/// daemon took the job over.
+async fn keep_job_lease(
+    db: Database,
+    job_id: String,
+    instance_id: String,
+    lease_lost: CancellationToken,
+) {
+    loop {
+        tokio::time::sleep(LEASE_DURATION / 3).await;
+        match db
+            .renew_job_lease(&job_id, &instance_id, Utc::now() + LEASE_DURATION)
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                warn!("Lost the lease on verification job {}", job_id);
+                lease_lost.cancel();
+                return;
+            }
+            Err(e) => warn!(
+                "Failed to renew lease on verification job {}: {:#}",
+                job_id, e
+            ),
+        }
+    }
+}
+
+async fn verify(
+    ctx: &RunProcessingContext,
+    job: &VerifyJob,
+    install_dir: &Path,
+    cancel: &CancellationToken,
+) -> Result<ReplayReport> {
+    let (run_rules, expected_mods) = ctx
+        .src_rules
+        .resolve_rules(&job.game_id, &job.category_id)?;
+    info!(
+        "=== Processing verification job {} ===\nGame: {}\nCategory: {}",
+        job.job_id, job.game_id, job.category_id
+    );
+
+    let processor = RunProcessor::new(&ctx.speedrun_ops.client, &ctx.download_throttles)
+        .with_install_quota(ctx.install_quota_bytes)
+        .with_sandbox(ctx.sandbox.clone())
+        .with_factorio_image(ctx.factorio_image.clone())
+        .with_factorio_credentials(ctx.factorio_credentials.clone());
+    run_uploaded_replay(
+        &processor,
+        Path::new(&job.save_path),
+        run_rules,
+        expected_mods,
+        install_dir,
+        cancel,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!(e.message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::database::types::{JobFilter, JobStatus};
+
+    fn src_rules() -> SrcRunRules {
+        serde_yaml::from_str(
+            "
+games:
+  game1:
+    expected_mods: []
+    categories:
+      cat1: {}
+",
+        )
+        .unwrap()
+    }
+
+    fn put_save(jobs: &VerifyJobs, fixture: &str) -> String {
+        let (job_id, save_path) = jobs.create_job().unwrap();
+        std::fs::copy(test_utils::fixtures_dir().join(fixture), save_path).unwrap();
+        job_id
+    }
+
+    #[tokio::test]
+    async fn test_submit_and_claim() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::in_memory().await.unwrap();
+        let jobs = VerifyJobs::new(
+            db.clone(),
+            src_rules(),
+            dir.path().to_path_buf(),
+            "host".to_string(),
+        );
+        let lease = || Utc::now() + LEASE_DURATION;
+
+        let job_id = put_save(&jobs, "EMPTY_SAVE.zip");
+        let err = jobs.check_save(&job_id, "game1", "missing").unwrap_err();
+        assert!(err.to_string().contains("category=missing"));
+        assert!(!dir.path().join(&job_id).exists());
+
+        let job_id = put_save(&jobs, "TEST.txt");
+        assert!(jobs.check_save(&job_id, "game1", "cat1").is_err());
+
+        let job_id = put_save(&jobs, "EMPTY_SAVE.zip");
+        jobs.check_save(&job_id, "game1", "cat1").unwrap();
+        let job = jobs
+            .submit(&job_id, JobSource::Upload, "game1", "cat1")
+            .await
+            .unwrap();
+        assert_eq!(job.status, JobStatus::Queued);
+        assert_eq!(job.source, JobSource::Upload);
+        assert!(Path::new(&job.save_path).exists());
+
+        // the save is only on its host
+        assert!(
+            db.claim_next_job("daemon", "other_host", lease())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let claimed = db
+            .claim_next_job("daemon", "host", lease())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(claimed.job_id, job_id);
+        assert_eq!(claimed.status, JobStatus::Processing);
+        assert!(
+            db.claim_next_job("daemon", "host", lease())
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // a restarted daemon replays its interrupted jobs again
+        assert!(db.requeue_jobs("other").await.unwrap().is_empty());
+        assert_eq!(
+            db.requeue_jobs("daemon").await.unwrap(),
+            vec![job_id.clone()]
+        );
+        db.claim_next_job("daemon", "host", Utc::now() - LEASE_DURATION)
+            .await
+            .unwrap()
+            .unwrap();
+        // and other daemons on the host take over jobs whose lease expired
+        let taken = db
+            .claim_next_job("other", "host", lease())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(taken.job_id, job_id);
+        assert!(
+            !db.renew_job_lease(&job_id, "daemon", lease())
+                .await
+                .unwrap()
+        );
+        assert!(db.renew_job_lease(&job_id, "other", lease()).await.unwrap());
+
+        db.finish_job(&job_id, Err("replay crashed")).await.unwrap();
+        let job = jobs.get(&job_id).await.unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Error);
+        assert_eq!(job.error.as_deref(), Some("replay crashed"));
+        assert!(job.finished_at.is_some());
+
+        let passed_id = put_save(&jobs, "EMPTY_SAVE.zip");
+        jobs.submit(&passed_id, JobSource::Cli, "game1", "cat1")
+            .await
+            .unwrap();
+        db.finish_job(&passed_id, Ok(&ReplayReport::default()))
+            .await
+            .unwrap();
+        let passed = db
+            .query_jobs(&JobFilter {
+                statuses: vec![JobStatus::Passed],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(passed.len(), 1);
+        assert_eq!(passed[0].source, JobSource::Cli);
+        assert_eq!(passed[0].summary.as_ref().unwrap().finding_count, 0);
+        assert_eq!(db.query_jobs(&JobFilter::default()).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_max_save_size() {
+        let src_rules = serde_yaml::from_str(
+            "
+games:
+  game1:
+    expected_mods: []
+    categories:
+      cat1: {}
+      tas: { security: { max_file_size_mb: 2048 } }
+",
+        )
+        .unwrap();
+        let db = Database::in_memory().await.unwrap();
+        let jobs = VerifyJobs::new(db.clone(), src_rules, PathBuf::new(), String::new());
+        assert_eq!(jobs.max_save_size(), 2048 * 1024 * 1024);
+        let jobs = VerifyJobs::new(db, self::src_rules(), PathBuf::new(), String::new());
+        assert_eq!(
+            jobs.max_save_size(),
+            default_security_config().max_file_size
+        );
+    }
+}
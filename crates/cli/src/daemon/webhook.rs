@@ -0,0 +1,290 @@
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use log::info;
+use reqwest::Client;
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::daemon::backlog::BacklogSummary;
+use crate::daemon::config::WebhookConfig;
+use crate::daemon::database::types::{Review, Run, RunStatus};
+use crate::daemon::notifier::{Notification, Notifier};
+use crate::run_replay::ReportSummary;
+
+/// When set, payloads are signed with HMAC-SHA256 and the hex digest is sent in
+/// [`SIGNATURE_HEADER`] as `sha256=<digest>`.
+pub const SECRET_ENV_VAR: &str = "WEBHOOK_SIGNING_SECRET";
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    event: &'static str,
+    run_id: &'a str,
+    game_id: &'a str,
+    category_id: &'a str,
+    status: RunStatus,
+    message: Option<&'a str>,
+    report: Option<&'a ReportSummary>,
+    review: Option<&'a Review>,
+    archive_url: Option<&'a str>,
+    timestamp: String,
+}
+
+#[derive(Debug, Serialize)]
+struct BacklogPayload<'a> {
+    event: &'static str,
+    #[serde(flatten)]
+    backlog: &'a BacklogSummary,
+    timestamp: String,
+}
+
+/// Posts a JSON payload to the configured URLs when a run reaches a final status.
+pub struct WebhookNotifier {
+    client: Client,
+    config: WebhookConfig,
+    secret: Option<String>,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: WebhookConfig, secret: Option<String>) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+            secret,
+        }
+    }
+
+    pub fn from_env(config: WebhookConfig) -> Self {
+        Self::new(config, std::env::var(SECRET_ENV_VAR).ok())
+    }
+
+    async fn post(&self, url: &str, body: &[u8], signature: Option<&str>) -> Result<()> {
+        let mut request = self
+            .client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(body.to_vec());
+        if let Some(signature) = signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+        let resp = request.send().await?;
+        if !resp.status().is_success() {
+            bail!("HTTP {}", resp.status());
+        }
+        Ok(())
+    }
+
+    /// Posts `payload` to every URL, failing if any of them failed; on retry all are posted
+    /// to again.
+    async fn post_all(&self, payload: &impl Serialize, what: &str) -> Result<()> {
+        let body = serde_json::to_vec(payload)?;
+        let signature = self.secret.as_deref().map(|secret| sign(secret, &body));
+
+        let mut failed = Vec::new();
+        for url in &self.config.urls {
+            match self.post(url, &body, signature.as_deref()).await {
+                Ok(()) => info!("Webhook {} notified for {}", url, what),
+                Err(e) => failed.push(format!("{}: {:#}", url, e)),
+            }
+        }
+        if !failed.is_empty() {
+            bail!("webhooks failed: {}", failed.join(", "));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn accepts(&self, run: &Run) -> bool {
+        run.is_final() && self.config.statuses.contains(&run.status)
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let run = &notification.run;
+        let payload = WebhookPayload {
+            event: "run_completed",
+            run_id: &run.run_id,
+            game_id: &run.game_id,
+            category_id: &run.category_id,
+            status: run.status,
+            message: run.error_message.as_deref(),
+            report: notification.report.as_ref(),
+            review: notification.review.as_ref(),
+            archive_url: notification.public_archive_url.as_deref(),
+            timestamp: Utc::now().to_rfc3339(),
+        };
+        self.post_all(&payload, &format!("run {}", run.run_id))
+            .await
+    }
+
+    async fn backlog_alert(&self, summary: &BacklogSummary) -> Result<()> {
+        let payload = BacklogPayload {
+            event: "backlog_alert",
+            backlog: summary,
+            timestamp: Utc::now().to_rfc3339(),
+        };
+        self.post_all(&payload, "the backlog").await
+    }
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::database::connection::Database;
+    use crate::daemon::database::types::NewRun;
+    use wiremock::matchers::{body_partial_json, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn failed_run() -> Notification {
+        let db = Database::in_memory().await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new("run1", "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+        db.mark_run_failed("run1", Some("used map editor"))
+            .await
+            .unwrap();
+        Notification::load(&db, "run1").await.unwrap().unwrap()
+    }
+
+    fn config(urls: Vec<String>, statuses: Vec<RunStatus>) -> WebhookConfig {
+        WebhookConfig { urls, statuses }
+    }
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_accepts_filters_statuses() {
+        let mut run = failed_run().await.run;
+        let notifier = WebhookNotifier::new(config(vec![], vec![RunStatus::Failed]), None);
+        assert!(notifier.accepts(&run));
+
+        run.status = RunStatus::Passed;
+        assert!(!notifier.accepts(&run));
+
+        let notifier = WebhookNotifier::new(config(vec![], vec![RunStatus::Error]), None);
+        run.status = RunStatus::Error;
+        assert!(notifier.accepts(&run));
+        run.next_retry_at = Some(Utc::now());
+        assert!(!notifier.accepts(&run));
+    }
+
+    #[tokio::test]
+    async fn test_notify_posts_signed_payload_to_all_urls() {
+        let server = MockServer::start().await;
+        for hook in ["/hook1", "/hook2"] {
+            Mock::given(method("POST"))
+                .and(path(hook))
+                .and(header_exists(SIGNATURE_HEADER))
+                .and(body_partial_json(serde_json::json!({
+                    "event": "run_completed",
+                    "run_id": "run1",
+                    "status": "failed",
+                    "message": "used map editor",
+                })))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let urls = vec![
+            format!("{}/hook1", server.uri()),
+            format!("{}/hook2", server.uri()),
+        ];
+        let notifier = WebhookNotifier::new(
+            config(urls, vec![RunStatus::Failed]),
+            Some("secret".to_string()),
+        );
+        notifier.notify(&failed_run().await).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_backlog_alert() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "event": "backlog_alert",
+                "sla_hours": 24,
+                "discovered": 2,
+                "oldest_run_ids": ["run1", "run2"],
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let notifier = WebhookNotifier::new(config(vec![server.uri()], vec![]), None);
+        let summary = BacklogSummary {
+            sla_hours: 24,
+            discovered: 2,
+            error: 0,
+            oldest_created_at: Utc::now(),
+            oldest_run_ids: vec!["run1".to_string(), "run2".to_string()],
+        };
+        notifier.backlog_alert(&summary).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_without_secret() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("Content-Type", "application/json"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let notifier = WebhookNotifier::new(config(vec![server.uri()], vec![]), None);
+        notifier.notify(&failed_run().await).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert!(!requests[0].headers.contains_key(SIGNATURE_HEADER));
+    }
+
+    #[tokio::test]
+    async fn test_fails_if_any_url_fails() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/ok"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/down"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let urls = vec![
+            format!("{}/ok", server.uri()),
+            format!("{}/down", server.uri()),
+        ];
+        let notifier = WebhookNotifier::new(config(urls, vec![]), None);
+        let err = notifier.notify(&failed_run().await).await.unwrap_err();
+        assert!(err.to_string().contains("/down: HTTP 503"));
+    }
+}
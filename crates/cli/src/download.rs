@@ -0,0 +1,138 @@
+use anyhow::Result;
+use clap::Args;
+use factorio_manager::save_file::SaveFile;
+use serde::Serialize;
+use std::fs::File;
+use std::path::PathBuf;
+use tokio_util::sync::CancellationToken;
+use zip_downloader::throttle::DownloadThrottles;
+
+use crate::daemon::RunProcessor;
+use crate::daemon::speedrun_api::SpeedrunClient;
+use crate::output::{OutputFormat, print_json};
+
+#[derive(Args)]
+pub struct DownloadArgs {
+    /// Share link, text containing links, or a speedrun.com run id
+    input: String,
+
+    /// Directory to download into
+    #[arg(short, long, default_value = ".")]
+    output: PathBuf,
+}
+
+#[derive(Serialize)]
+struct DownloadOutput {
+    links: Vec<LinkOutput>,
+    file: FileOutput,
+    save: SaveOutput,
+}
+
+#[derive(Serialize)]
+struct LinkOutput {
+    service: String,
+    link: String,
+}
+
+#[derive(Serialize)]
+struct FileOutput {
+    path: PathBuf,
+    original_name: String,
+    link: String,
+    size: u64,
+    sha256: String,
+}
+
+#[derive(Serialize)]
+struct SaveOutput {
+    name: String,
+    factorio_version: String,
+    mods: Vec<String>,
+}
+
+/// Speedrun.com run ids are 8 lowercase letters and digits.
+fn is_run_id(input: &str) -> bool {
+    input.len() == 8
+        && input
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+}
+
+pub async fn handle_download(
+    args: DownloadArgs,
+    format: OutputFormat,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let client = SpeedrunClient::new()?;
+    let mut processor = RunProcessor::new(&client, &DownloadThrottles::default());
+    let description = if is_run_id(&args.input) {
+        processor.fetch_run_description(&args.input).await?
+    } else {
+        args.input
+    };
+
+    let links: Vec<LinkOutput> = processor
+        .detect_links(&description)
+        .into_iter()
+        .map(|link| LinkOutput {
+            service: link.service,
+            link: link.link,
+        })
+        .collect();
+    if !format.is_json() {
+        println!("Detected links:");
+        for link in &links {
+            println!("  [{}] {}", link.service, link.link);
+        }
+    }
+
+    std::fs::create_dir_all(&args.output)?;
+    let file = processor
+        .download_file(&description, &args.output, cancel)
+        .await?;
+    let mut save_file = SaveFile::new(File::open(&file.path)?)?;
+    let save = SaveOutput {
+        name: save_file.save_name().to_string(),
+        factorio_version: save_file.get_factorio_version()?.to_string(),
+        mods: save_file
+            .get_mods()?
+            .into_iter()
+            .map(|save_mod| format!("{} {}", save_mod.name, save_mod.version))
+            .collect(),
+    };
+    let file = FileOutput {
+        path: file.path,
+        original_name: file.original_name,
+        link: file.link,
+        size: file.size,
+        sha256: file.sha256,
+    };
+
+    if format.is_json() {
+        return print_json(&DownloadOutput { links, file, save });
+    }
+    println!();
+    println!("Downloaded:      {}", file.path.display());
+    println!("Original name:   {}", file.original_name);
+    println!("From:            {}", file.link);
+    println!("Size:            {} bytes", file.size);
+    println!("SHA-256:         {}", file.sha256);
+    println!();
+    println!("Save name:       {}", save.name);
+    println!("Factorio:        {}", save.factorio_version);
+    println!("Mods:            {}", save.mods.join(", "));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_run_id() {
+        assert!(is_run_id("zngelo7m"));
+        assert!(!is_run_id("https://drive.google.com/file/d/abc/view"));
+        assert!(!is_run_id("ZNGELO7M"));
+        assert!(!is_run_id("zngelo7"));
+    }
+}
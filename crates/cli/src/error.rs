@@ -11,6 +11,20 @@ pub enum ErrorClass {
     Final,
     Retryable,
     RateLimited { retry_after: Option<Duration> },
+    AuthRequired,
+}
+
+impl From<zip_downloader::ErrorClass> for ErrorClass {
+    fn from(class: zip_downloader::ErrorClass) -> Self {
+        match class {
+            zip_downloader::ErrorClass::Final => ErrorClass::Final,
+            zip_downloader::ErrorClass::Retryable => ErrorClass::Retryable,
+            zip_downloader::ErrorClass::RateLimited { retry_after } => {
+                ErrorClass::RateLimited { retry_after }
+            }
+            zip_downloader::ErrorClass::AuthRequired => ErrorClass::AuthRequired,
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -31,17 +45,7 @@ impl RunProcessingError {
 
 impl From<DownloadError> for RunProcessingError {
     fn from(e: DownloadError) -> Self {
-        let class = match &e {
-            DownloadError::NoLinkFound => ErrorClass::Final,
-            DownloadError::SecurityViolation(_) => ErrorClass::Final,
-            DownloadError::FileNotAccessible(_) => ErrorClass::Final,
-            DownloadError::ServiceError(_) => ErrorClass::Retryable,
-            &DownloadError::RateLimited { retry_after, .. } => {
-                ErrorClass::RateLimited { retry_after }
-            }
-            DownloadError::IoError(_) => ErrorClass::Retryable,
-        };
-        RunProcessingError::from_error(class, &e)
+        RunProcessingError::from_error(e.class().into(), &e)
     }
 }
 
@@ -53,9 +57,13 @@ impl From<FactorioError> for RunProcessingError {
             FactorioError::VersionTooOld { .. } => ErrorClass::Final,
             FactorioError::ModMismatch { .. } => ErrorClass::Final,
             FactorioError::ScriptInjectionFailed(_) => ErrorClass::Final,
+            FactorioError::UnsupportedScripts { .. } => ErrorClass::Final,
             FactorioError::FactorioDownloadFailed { .. } => ErrorClass::Retryable,
+            FactorioError::ImageUnavailable { .. } => ErrorClass::Retryable,
+            FactorioError::VersionListUnavailable(_) => ErrorClass::Retryable,
             FactorioError::ExtractionFailed(_) => ErrorClass::Retryable,
             FactorioError::InstallationNotFound(_) => ErrorClass::Retryable,
+            FactorioError::SaveNewerThanInstall { .. } => ErrorClass::Final,
             FactorioError::InstallDirError(_) => ErrorClass::Retryable,
             FactorioError::ProcessSpawnFailed(_) => ErrorClass::Retryable,
             FactorioError::ProcessExitedUnsuccessfully { detail, .. } => {
@@ -67,6 +75,7 @@ impl From<FactorioError> for RunProcessingError {
             }
             FactorioError::ModInfoReadFailed(_) => ErrorClass::Retryable,
             FactorioError::ReplayTimeout => ErrorClass::Final,
+            FactorioError::InsufficientDiskSpace(_) => ErrorClass::Retryable,
             FactorioError::IoError(_) => ErrorClass::Retryable,
         };
         RunProcessingError::from_error(class, &e)
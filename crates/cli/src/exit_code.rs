@@ -0,0 +1,166 @@
+use clap::ValueEnum;
+use replay_script::MsgLevel;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+use crate::run_replay::ReplayReport;
+
+/// Exit codes of the commands that verify runs. Other commands exit with [`ExitCode::Pass`]
+/// on success and [`ExitCode::ConfigError`] or [`ExitCode::InfraError`] on failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExitCode {
+    Pass = 0,
+    PassWithWarnings = 1,
+    Fail = 2,
+    /// Invalid arguments, rules or config; nothing was verified.
+    ConfigError = 3,
+    /// The run couldn't be verified, e.g. the download or Factorio failed.
+    InfraError = 20,
+    Interrupted = 130,
+}
+
+/// Collapses warnings into pass or fail, for wrappers that only look at success.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FailOn {
+    /// Warnings fail the run
+    Warn,
+    /// Only errors fail the run; warnings pass
+    Error,
+}
+
+/// Marks an error as caused by the user's arguments, rules or config.
+#[derive(Debug)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for ExitCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(self.code())
+    }
+}
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// Judges the report by the same rule as [`ReplayReport::passed`]: an unmet win
+    /// condition fails, and a partial verification counts as a warning.
+    pub fn from_report(report: &ReplayReport, fail_on: Option<FailOn>) -> Self {
+        if report.win_condition_not_completed {
+            return ExitCode::Fail;
+        }
+        match (report.verdict_level(), fail_on) {
+            (MsgLevel::Debug | MsgLevel::Info, _) => ExitCode::Pass,
+            (MsgLevel::Warn, None) => ExitCode::PassWithWarnings,
+            (MsgLevel::Warn, Some(FailOn::Warn)) => ExitCode::Fail,
+            (MsgLevel::Warn, Some(FailOn::Error)) => ExitCode::Pass,
+            (MsgLevel::Error | MsgLevel::Critical, _) => ExitCode::Fail,
+        }
+    }
+
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        if error.downcast_ref::<ConfigError>().is_some() {
+            ExitCode::ConfigError
+        } else {
+            ExitCode::InfraError
+        }
+    }
+
+    pub fn from_result(result: &anyhow::Result<ReplayReport>, fail_on: Option<FailOn>) -> Self {
+        match result {
+            Ok(report) => Self::from_report(report, fail_on),
+            Err(e) => Self::from_error(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    fn report(max_msg_level: MsgLevel) -> ReplayReport {
+        ReplayReport {
+            max_msg_level,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fail_on() {
+        let warn = report(MsgLevel::Warn);
+        assert_eq!(
+            ExitCode::from_report(&warn, None),
+            ExitCode::PassWithWarnings
+        );
+        assert_eq!(
+            ExitCode::from_report(&warn, Some(FailOn::Warn)),
+            ExitCode::Fail
+        );
+        assert_eq!(
+            ExitCode::from_report(&warn, Some(FailOn::Error)),
+            ExitCode::Pass
+        );
+        let error = report(MsgLevel::Error);
+        assert_eq!(
+            ExitCode::from_report(&error, Some(FailOn::Error)),
+            ExitCode::Fail
+        );
+        assert_eq!(
+            ExitCode::from_report(&report(MsgLevel::Critical), None),
+            ExitCode::Fail
+        );
+        assert_eq!(
+            ExitCode::from_report(&report(MsgLevel::Debug), Some(FailOn::Warn)),
+            ExitCode::Pass
+        );
+    }
+
+    #[test]
+    fn test_win_condition_not_completed() {
+        let report = ReplayReport {
+            win_condition_not_completed: true,
+            ..Default::default()
+        };
+        assert_eq!(ExitCode::from_report(&report, None), ExitCode::Fail);
+        assert_eq!(
+            ExitCode::from_report(&report, Some(FailOn::Error)),
+            ExitCode::Fail
+        );
+    }
+
+    #[test]
+    fn test_partial_verification() {
+        let report = ReplayReport {
+            partial_verification: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ExitCode::from_report(&report, None),
+            ExitCode::PassWithWarnings
+        );
+        assert_eq!(
+            ExitCode::from_report(&report, Some(FailOn::Warn)),
+            ExitCode::Fail
+        );
+    }
+
+    #[test]
+    fn test_from_error() {
+        let config_error = std::fs::read("missing.yaml")
+            .context(ConfigError("failed to load rules".into()))
+            .unwrap_err()
+            .context("while starting");
+        assert_eq!(ExitCode::from_error(&config_error), ExitCode::ConfigError);
+        assert_eq!(
+            ExitCode::from_error(&anyhow::anyhow!("download failed")),
+            ExitCode::InfraError
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use comfy_table::{Cell, Table};
+use log::info;
+use std::path::PathBuf;
+
+use crate::batch::find_saves;
+use crate::daemon::config::hostname;
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::{JobFilter, JobSource, JobStatus, VerifyJob};
+use crate::daemon::verify_jobs::{JOBS_DIR, VerifyJobs};
+use crate::output::{OutputFormat, print_json};
+
+#[derive(Args)]
+pub struct JobsArgs {
+    #[command(subcommand)]
+    pub subcommand: JobsSubcommand,
+
+    /// SQLite database file path
+    #[arg(long, default_value = "run_verification.db")]
+    pub database: PathBuf,
+
+    /// Connection URL used instead of --database, e.g.
+    /// "postgres://verifier@db.internal/runs"
+    #[arg(long)]
+    pub database_url: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum JobsSubcommand {
+    /// Queue saves for the daemon to verify, apart from speedrun.com runs
+    Submit(SubmitArgs),
+    /// List verification jobs, most recent first
+    List(ListJobsArgs),
+    /// Show a verification job and its result
+    Show(ShowJobArgs),
+}
+
+#[derive(Args)]
+pub struct SubmitArgs {
+    /// Saves (.zip) to verify, or directories of saves
+    #[arg(required = true)]
+    saves: Vec<PathBuf>,
+
+    /// Speedrun.com game ID whose rules to verify with
+    #[arg(long)]
+    game_id: String,
+
+    /// Speedrun.com category ID whose rules to verify with
+    #[arg(long)]
+    category_id: String,
+
+    /// GAME rules (yaml), as used by the daemon
+    #[arg(long, default_value = "./speedrun_rules.yaml")]
+    game_rules: PathBuf,
+
+    /// The daemon's output directory; saves are copied to its verify-jobs/
+    #[arg(long, default_value = "./src_runs")]
+    output_dir: PathBuf,
+
+    /// Machine the output directory is on, whose daemons replay the saves. Defaults to
+    /// this one
+    #[arg(long)]
+    host: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ListJobsArgs {
+    /// Only jobs with this status
+    #[arg(long)]
+    status: Option<JobStatus>,
+
+    #[arg(long, default_value_t = 50)]
+    limit: u32,
+}
+
+#[derive(Args)]
+pub struct ShowJobArgs {
+    pub job_id: String,
+}
+
+pub async fn handle_jobs_command(args: JobsArgs, format: OutputFormat) -> Result<()> {
+    let db = Database::open_url_or_path(args.database_url.as_deref(), &args.database).await?;
+    match args.subcommand {
+        JobsSubcommand::Submit(submit_args) => handle_submit(db, submit_args, format).await,
+        JobsSubcommand::List(list_args) => {
+            let filter = JobFilter {
+                statuses: list_args.status.into_iter().collect(),
+                limit: Some(list_args.limit),
+            };
+            let jobs = db.query_jobs(&filter).await?;
+            if format.is_json() {
+                return print_json(&jobs);
+            }
+            println!("{}", format_jobs_as_table(&jobs));
+            Ok(())
+        }
+        JobsSubcommand::Show(show_args) => {
+            let job = db
+                .get_job(&show_args.job_id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Job not found: {}", show_args.job_id))?;
+            if format.is_json() {
+                return print_json(&job);
+            }
+            print_job(&job);
+            Ok(())
+        }
+    }
+}
+
+async fn handle_submit(db: Database, args: SubmitArgs, format: OutputFormat) -> Result<()> {
+    let src_rules = crate::load_src_rules(&args.game_rules).await?;
+    // the daemon may run from another directory
+    let jobs_dir = std::path::absolute(args.output_dir.join(JOBS_DIR))?;
+    let host = args
+        .host
+        .or_else(hostname)
+        .context("This machine's name is unknown; pass --host")?;
+    let jobs = VerifyJobs::new(db, src_rules, jobs_dir, host);
+
+    let mut saves = Vec::new();
+    for path in args.saves {
+        if path.is_dir() {
+            saves.extend(find_saves(&path)?);
+        } else {
+            saves.push(path);
+        }
+    }
+
+    let mut submitted = Vec::new();
+    for save in saves {
+        let (job_id, save_path) = jobs.create_job()?;
+        if let Err(e) = std::fs::copy(&save, &save_path) {
+            jobs.discard_job(&job_id);
+            return Err(e).with_context(|| format!("Failed to copy {}", save.display()));
+        }
+        jobs.check_save(&job_id, &args.game_id, &args.category_id)
+            .with_context(|| format!("Can't verify {}", save.display()))?;
+        let job = jobs
+            .submit(&job_id, JobSource::Cli, &args.game_id, &args.category_id)
+            .await?;
+        info!("Queued {} as job {}", save.display(), job.job_id);
+        submitted.push(job);
+    }
+
+    if format.is_json() {
+        return print_json(&submitted);
+    }
+    println!("{}", format_jobs_as_table(&submitted));
+    Ok(())
+}
+
+fn format_jobs_as_table(jobs: &[VerifyJob]) -> String {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Job ID",
+        "Source",
+        "Game/Category",
+        "Submitted",
+        "Status",
+        "Findings",
+    ]);
+    for job in jobs {
+        let findings = job
+            .summary
+            .as_ref()
+            .map(|summary| summary.finding_count.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        table.add_row(vec![
+            Cell::new(&job.job_id),
+            Cell::new(job.source.as_str()),
+            Cell::new(format!("{} / {}", job.game_id, job.category_id)),
+            Cell::new(job.submitted_at.format("%Y-%m-%d %H:%M").to_string()),
+            Cell::new(job.status.as_str()),
+            Cell::new(findings),
+        ]);
+    }
+    table.to_string()
+}
+
+fn print_job(job: &VerifyJob) {
+    println!("Job Details");
+    println!("===========");
+    println!();
+    println!("Job ID:          {}", job.job_id);
+    println!("Source:          {}", job.source.as_str());
+    println!("Game:            {}", job.game_id);
+    println!("Category:        {}", job.category_id);
+    println!("Save:            {}", job.save_path);
+    println!(
+        "Submitted:       {}",
+        job.submitted_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    if let Some(started_at) = job.started_at {
+        println!(
+            "Started:         {}",
+            started_at.format("%Y-%m-%d %H:%M:%S UTC")
+        );
+    }
+    if let Some(finished_at) = job.finished_at {
+        println!(
+            "Finished:        {}",
+            finished_at.format("%Y-%m-%d %H:%M:%S UTC")
+        );
+    }
+    println!("Status:          {}", job.status.as_str());
+    if let Some(error) = &job.error {
+        println!("Error:           {}", error);
+    }
+    if let Some(summary) = &job.summary {
+        println!("Final Tick:      {}", summary.final_tick);
+        println!("Findings:        {}", summary.finding_count);
+        if let Some(exit_message) = &summary.exit_message {
+            println!("Exit:            {}", exit_message);
+        }
+    }
+    if !job.messages.is_empty() {
+        println!();
+        println!("Messages");
+        println!("--------");
+        for message in &job.messages {
+            println!("{}", message);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use std::io::Write;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of enclosing spans (e.g. run_id)
+    Json,
+}
+
+/// Flushes exported traces when dropped.
+pub struct LoggingGuard {
+    #[cfg(feature = "otlp")]
+    tracer_provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for LoggingGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = &self.tracer_provider
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush traces: {e}");
+        }
+    }
+}
+
+/// Logs to stderr at the level set in RUST_LOG (default info). Messages from the `log`
+/// crate are included. With the `otlp` feature, spans are also exported to
+/// OTEL_EXPORTER_OTLP_ENDPOINT when set.
+pub fn init_logging(format: LogFormat) -> Result<LoggingGuard> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer(format, std::io::stderr));
+
+    #[cfg(feature = "otlp")]
+    {
+        let tracer_provider = otlp_tracer_provider()?;
+        let otel_layer = tracer_provider.as_ref().map(|provider| {
+            use opentelemetry::trace::TracerProvider as _;
+            tracing_opentelemetry::layer().with_tracer(provider.tracer("factorio-replay-runner"))
+        });
+        registry.with(otel_layer).try_init()?;
+        Ok(LoggingGuard { tracer_provider })
+    }
+    #[cfg(not(feature = "otlp"))]
+    {
+        registry.try_init()?;
+        Ok(LoggingGuard {})
+    }
+}
+
+fn fmt_layer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w, Writer: Write> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer
+            .json()
+            .with_current_span(false)
+            .with_span_list(true)
+            .boxed(),
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_tracer_provider() -> Result<Option<opentelemetry_sdk::trace::SdkTracerProvider>> {
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_none() {
+        return Ok(None);
+    }
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()?;
+    let resource = opentelemetry_sdk::Resource::builder()
+        .with_service_name("factorio-replay-runner")
+        .build();
+    Ok(Some(
+        opentelemetry_sdk::trace::SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource)
+            .build(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'w> MakeWriter<'w> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'w self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_json_includes_run_span() {
+        let buffer = Buffer::default();
+        let subscriber =
+            tracing_subscriber::registry().with(fmt_layer(LogFormat::Json, buffer.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("run", run_id = "abc123", game = "factorio");
+            span.in_scope(|| tracing::info!("Downloading save"));
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["fields"]["message"], "Downloading save");
+        assert_eq!(line["spans"][0]["name"], "run");
+        assert_eq!(line["spans"][0]["run_id"], "abc123");
+    }
+}
@@ -1,29 +1,45 @@
 use anyhow::{Context, Result};
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use config::RunRules;
 use factorio_manager::{
     factorio_install_dir::FactorioInstallDir,
     save_file::{SaveFile, WrittenSaveFile},
 };
 use log::info;
-use run_replay::{ReplayReport, run_replay};
+use output::{ErrorOutput, OutputFormat, print_json};
+use run_replay::{ReplayOptions, ReplayReport, run_replay};
+use serde::Serialize;
 use std::{
     fs::File,
     path::{Path, PathBuf},
     sync::Arc,
 };
 use tokio::signal;
-use tokio::signal::unix::SignalKind;
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use zip_downloader::throttle::DownloadThrottles;
 
-use crate::daemon::{RunProcessingContext, SrcRunRules, download_and_run_replay};
+use crate::daemon::{RunProcessingContext, RunProcessor, SrcRunRules, download_and_run_replay};
+use crate::exit_code::{ConfigError, ExitCode, FailOn};
 
 mod admin;
+mod batch;
 mod config;
 mod daemon;
+mod download;
 mod error;
+mod exit_code;
+mod jobs;
+mod logging;
+mod output;
 mod query;
+mod report;
 mod run_replay;
+mod script;
+mod signing;
+mod test_rules;
+mod tui;
+mod validate;
 
 #[derive(Parser)]
 #[command(name = "factorio-replay-cli")]
@@ -31,20 +47,52 @@ mod run_replay;
 struct CliArgs {
     #[command(subcommand)]
     command: Commands,
+
+    /// Output format for command results; csv and jsonl are only for `query export`
+    #[arg(long, global = true, value_enum, default_value_t)]
+    format: OutputFormat,
+
+    /// Format of log messages, written to stderr
+    #[arg(long, global = true, value_enum, default_value_t)]
+    log_format: logging::LogFormat,
+
+    /// Exit with pass or fail only: whether warnings fail the run. By default warnings
+    /// exit with their own code
+    #[arg(long, global = true, value_enum)]
+    fail_on: Option<FailOn>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Run a replay from a local save file
     Run(RunReplayOnFileArgs),
+    /// Run replays of every save in a local directory
+    RunBatch(batch::RunBatchArgs),
+    /// Replay a corpus of fixture saves and check their verdicts against a manifest
+    TestRules(test_rules::TestRulesArgs),
     /// Run a replay fetched from speedrun.com
     RunSrc(RunReplayFromSrcArgs),
+    /// Download a save without running it, to debug share links
+    Download(download::DownloadArgs),
     /// Start the daemon to poll and process speedrun.com runs
     Daemon(DaemonArgs),
     /// Query the database for run information
     Query(query::QueryArgs),
     /// Administrative database operations
     Admin(admin::AdminArgs),
+    /// Verify saves apart from speedrun.com runs, and look up their results
+    Jobs(jobs::JobsArgs),
+    /// Compare the replay messages of verifications
+    Report(report::ReportArgs),
+    /// Interactive dashboard of the verification queue
+    Tui(tui::TuiArgs),
+    /// Check configuration files
+    Config(validate::ConfigArgs),
+    /// Inspect the generated replay script
+    Script(script::ScriptArgs),
+    /// Check that a report.json was signed by the runner and not modified since, and show the
+    /// run and saves it was signed for
+    VerifyReport(signing::VerifyReportArgs),
 }
 
 #[derive(Args)]
@@ -89,6 +137,10 @@ struct RunReplayFromSrcArgs {
     /// SQLite database for tracking run status
     #[arg(long, default_value = "run_verification.db")]
     database: PathBuf,
+
+    /// Only log what would be downloaded and verified; no database writes or replays
+    #[arg(long)]
+    dry_run: bool,
 }
 
 #[derive(Args)]
@@ -96,42 +148,138 @@ struct DaemonArgs {
     /// Daemon configuration (yaml)
     #[arg(short, long, default_value = "./daemon.yaml")]
     config: PathBuf,
+
+    /// Poll once and log what would be downloaded and verified; no database writes or replays
+    #[arg(long)]
+    dry_run: bool,
+}
+
+impl CliArgs {
+    /// Rejects the formats only `query export` writes on other commands.
+    fn check_format(self) -> Result<Self, clap::Error> {
+        let is_export = matches!(
+            &self.command,
+            Commands::Query(query::QueryArgs {
+                subcommand: query::QuerySubcommand::Export(_),
+                ..
+            })
+        );
+        if self.format.is_export_only() && !is_export {
+            return Err(Self::command().error(
+                clap::error::ErrorKind::InvalidValue,
+                "--format csv and jsonl are only supported by `query export`",
+            ));
+        }
+        Ok(self)
+    }
 }
+
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
-    init_logger();
+    let args = CliArgs::try_parse()
+        .and_then(CliArgs::check_format)
+        .unwrap_or_else(|e| {
+            let code = if e.use_stderr() {
+                ExitCode::ConfigError.code()
+            } else {
+                ExitCode::Pass.code()
+            };
+            e.print().ok();
+            std::process::exit(code);
+        });
+    let _logging = logging::init_logging(args.log_format)?;
 
     let token = setup_signal_handler()?;
-    let args = CliArgs::parse();
+    let format = args.format;
+
+    let result = run_command(args.command, format, args.fail_on, token).await;
+    if let Err(e) = &result {
+        if format.is_json() {
+            print_json(&ErrorOutput::new(e))?;
+        } else {
+            eprintln!("Error: {:?}", e);
+        }
+        std::process::exit(ExitCode::from_error(e).code());
+    }
+    Ok(())
+}
 
-    match args.command {
+async fn run_command(
+    command: Commands,
+    format: OutputFormat,
+    fail_on: Option<FailOn>,
+    token: CancellationToken,
+) -> Result<()> {
+    match command {
         Commands::Run(sub_args) => {
             let exit_code = tokio::select! {
-                result = cli_run_file(sub_args) => result?,
-                _ = token.cancelled() => { log::info!("Interrupted"); 130 }
+                result = cli_run_file(sub_args, format, fail_on) => result?,
+                _ = token.cancelled() => { log::info!("Interrupted"); ExitCode::Interrupted.code() }
+            };
+            std::process::exit(exit_code);
+        }
+        Commands::RunBatch(sub_args) => {
+            let exit_code = tokio::select! {
+                result = batch::cli_run_batch(sub_args, format, fail_on) => result?,
+                _ = token.cancelled() => { log::info!("Interrupted"); ExitCode::Interrupted.code() }
+            };
+            std::process::exit(exit_code);
+        }
+        Commands::TestRules(sub_args) => {
+            let exit_code = tokio::select! {
+                result = test_rules::cli_test_rules(sub_args, format) => result?,
+                _ = token.cancelled() => { log::info!("Interrupted"); ExitCode::Interrupted.code() }
             };
             std::process::exit(exit_code);
         }
         Commands::RunSrc(sub_args) => {
             let exit_code = tokio::select! {
-                result = cli_run_src(sub_args) => result?,
-                _ = token.cancelled() => { log::info!("Interrupted"); 130 }
+                result = cli_run_src(sub_args, format, fail_on) => result?,
+                _ = token.cancelled() => { log::info!("Interrupted"); ExitCode::Interrupted.code() }
             };
             std::process::exit(exit_code);
         }
+        Commands::Download(sub_args) => {
+            tokio::select! {
+                result = download::handle_download(sub_args, format, &token) => result?,
+                _ = token.cancelled() => log::info!("Interrupted"),
+            }
+            Ok(())
+        }
         Commands::Daemon(sub_args) => {
             cli_daemon(sub_args, token).await?;
             Ok(())
         }
         Commands::Query(sub_args) => {
-            query::handle_query_command(sub_args).await?;
+            query::handle_query_command(sub_args, format).await?;
             Ok(())
         }
         Commands::Admin(sub_args) => {
             admin::handle_admin_command(sub_args).await?;
             Ok(())
         }
+        Commands::Jobs(sub_args) => {
+            jobs::handle_jobs_command(sub_args, format).await?;
+            Ok(())
+        }
+        Commands::Report(sub_args) => {
+            report::handle_report_command(sub_args, format).await?;
+            Ok(())
+        }
+        Commands::Tui(sub_args) => {
+            tui::handle_tui_command(sub_args, token).await?;
+            Ok(())
+        }
+        Commands::Config(sub_args) => {
+            validate::handle_config_command(sub_args, format).await?;
+            Ok(())
+        }
+        Commands::Script(sub_args) => {
+            script::handle_script_command(sub_args).await?;
+            Ok(())
+        }
+        Commands::VerifyReport(sub_args) => signing::handle_verify_report(sub_args),
     }
 }
 
@@ -139,26 +287,41 @@ fn setup_signal_handler() -> Result<CancellationToken> {
     let token = CancellationToken::new();
     let cloned = token.clone();
     tokio::spawn(async move {
-        let mut sigint = signal::unix::signal(SignalKind::interrupt())?;
-        let mut sigterm = signal::unix::signal(SignalKind::terminate())?;
-        tokio::select! {
-            _ = sigint.recv() => log::info!("Received SIGINT, shutting down..."),
-            _ = sigterm.recv() => log::info!("Received SIGTERM, shutting down..."),
-        }
+        shutdown_signal().await?;
         cloned.cancel();
         Ok::<(), std::io::Error>(())
     });
     Ok(token)
 }
 
-fn init_logger() {
-    env_logger::builder()
-        .filter_level(log::LevelFilter::Info)
-        .parse_default_env()
-        .init();
+#[cfg(unix)]
+async fn shutdown_signal() -> std::io::Result<()> {
+    use signal::unix::SignalKind;
+    let mut sigint = signal::unix::signal(SignalKind::interrupt())?;
+    let mut sigterm = signal::unix::signal(SignalKind::terminate())?;
+    tokio::select! {
+        _ = sigint.recv() => log::info!("Received SIGINT, shutting down..."),
+        _ = sigterm.recv() => log::info!("Received SIGTERM, shutting down..."),
+    }
+    Ok(())
 }
 
-async fn cli_run_file(args: RunReplayOnFileArgs) -> Result<i32> {
+#[cfg(windows)]
+async fn shutdown_signal() -> std::io::Result<()> {
+    let mut ctrl_c = signal::windows::ctrl_c()?;
+    let mut ctrl_close = signal::windows::ctrl_close()?;
+    tokio::select! {
+        _ = ctrl_c.recv() => log::info!("Received Ctrl+C, shutting down..."),
+        _ = ctrl_close.recv() => log::info!("Console closed, shutting down..."),
+    }
+    Ok(())
+}
+
+async fn cli_run_file(
+    args: RunReplayOnFileArgs,
+    format: OutputFormat,
+    fail_on: Option<FailOn>,
+) -> Result<i32> {
     let RunReplayOnFileArgs {
         save,
         run_rules,
@@ -168,7 +331,7 @@ async fn cli_run_file(args: RunReplayOnFileArgs) -> Result<i32> {
     let output_path = output.unwrap_or_else(|| save.with_extension("log"));
 
     let result = run_file(&save, &run_rules, &install_dir, &output_path).await;
-    Ok(result_to_exit_code(&result))
+    print_run_result(&result, format, fail_on)
 }
 
 async fn run_file(
@@ -180,35 +343,53 @@ async fn run_file(
     let install_dir = load_install_dir(install_dir).await?;
     let mut save_file = load_save(save).await?;
     let rules = load_run_rules(rules).await?;
+    let expected_mods = rules
+        .expected_mods_override
+        .as_ref()
+        .context(ConfigError("rules must list expected_mods".into()))?;
     run_replay(
         &install_dir,
         &mut save_file,
         &rules,
-        rules
-            .expected_mods_override
-            .as_ref()
-            .expect("Expected mods is required for basic rules"),
+        expected_mods,
         output,
+        ReplayOptions::default(),
     )
     .await
     .map_err(anyhow::Error::from)
 }
 
-async fn cli_run_src(args: RunReplayFromSrcArgs) -> Result<i32> {
+async fn cli_run_src(
+    args: RunReplayFromSrcArgs,
+    format: OutputFormat,
+    fail_on: Option<FailOn>,
+) -> Result<i32> {
     let RunReplayFromSrcArgs {
         run_id,
         game_rules,
         install_dir,
         output_dir,
         database,
+        dry_run,
     } = args;
 
+    if dry_run {
+        cli_dry_run(run_id.as_deref(), &game_rules).await?;
+        return Ok(ExitCode::Pass.code());
+    }
+
     match run_id {
         Some(run_id) => {
             let result = run_src(&run_id, &game_rules, &install_dir, &output_dir, &database).await;
-            Ok(result_to_exit_code(&result))
+            print_run_result(&result, format, fail_on)
+        }
+        None => {
+            let processed = run_src_once(&game_rules, &install_dir, &output_dir, &database).await?;
+            if format.is_json() {
+                print_json(&serde_json::json!({ "processed": processed }))?;
+            }
+            Ok(ExitCode::Pass.code())
         }
-        None => run_src_once(&game_rules, &install_dir, &output_dir, &database).await,
     }
 }
 
@@ -222,7 +403,7 @@ async fn run_src(
     let src_rules = load_src_rules(game_rules).await?;
     let db = daemon::database::connection::Database::new(database).await?;
     let client = daemon::speedrun_api::SpeedrunClient::new()?;
-    let speedrun_ops = daemon::speedrun_api::SpeedrunOps::new(&client);
+    let speedrun_ops = daemon::speedrun_api::SpeedrunOps::new(&client).with_db(db.clone());
 
     info!("Fetching run data (https://speedrun.com/runs/{})", run_id);
     let run = client.get_run(run_id).await?;
@@ -233,15 +414,28 @@ async fn run_src(
         .await;
     info!("Game: {}", game_category);
 
-    let (run_rules, expected_mods) = src_rules.resolve_rules(&run.game, &run.category)?;
+    let (run_rules, expected_mods) = src_rules
+        .resolve_rules(&run.game, &run.category)
+        .context(ConfigError(format!("no rules for {}", game_category)))?;
+    let details = run.details();
     let run_id = run.id;
+    let span = tracing::info_span!(
+        "run",
+        run_id = %run_id,
+        game = %run.game,
+        category = %run.category
+    );
 
     let new_run =
         daemon::database::types::NewRun::new(&run_id, run.game, run.category, submitted_date);
     db.insert_run(new_run)
         .await
         .or_else(|e| {
-            if e.to_string().contains("UNIQUE constraint failed") {
+            let exists = e
+                .downcast_ref::<sqlx::Error>()
+                .and_then(|e| e.as_database_error())
+                .is_some_and(|e| e.is_unique_violation());
+            if exists {
                 info!("Run already exists in database");
                 Ok(())
             } else {
@@ -249,22 +443,28 @@ async fn run_src(
             }
         })
         .context("Failed to insert run into database")?;
+    db.store_run_details(&run_id, &details).await?;
 
     db.mark_run_processing(&run_id).await?;
 
+    let mut run_processor = RunProcessor::new(&client, &DownloadThrottles::default())
+        .with_submitted_date(submitted_date);
     let result = download_and_run_replay(
-        &client,
+        &mut run_processor,
         &run_id,
         run_rules,
         expected_mods,
         install_dir,
         output_dir,
+        &CancellationToken::new(),
     )
+    .instrument(span.clone())
     .await;
 
     let report = result.as_ref().ok().cloned();
     let retry_config = daemon::retry::RetryConfig::default();
-    db.process_replay_result(&run_id, result, &retry_config)
+    db.process_replay_result(&run_id, None, result, &retry_config)
+        .instrument(span)
         .await?;
 
     report.ok_or_else(|| anyhow::anyhow!("Failed to process replay"))
@@ -275,14 +475,14 @@ async fn run_src_once(
     install_dir: &Path,
     output_dir: &Path,
     database: &Path,
-) -> Result<i32> {
+) -> Result<bool> {
     let daemon_config = load_daemon_config(&PathBuf::from("./daemon.yaml"))
         .await
         .context("Failed to load daemon config")?;
     let src_rules = load_src_rules(game_rules).await?;
     let db = daemon::database::connection::Database::new(database).await?;
-    let client = daemon::speedrun_api::SpeedrunClient::new()?;
-    let speedrun_ops = daemon::speedrun_api::SpeedrunOps::new(&client);
+    let client = daemon_config.speedrun_client()?;
+    let speedrun_ops = daemon::speedrun_api::SpeedrunOps::new(&client).with_db(db.clone());
 
     std::fs::create_dir_all(install_dir)?;
     std::fs::create_dir_all(output_dir)?;
@@ -292,9 +492,26 @@ async fn run_src_once(
         speedrun_ops,
         src_rules,
         install_dir: install_dir.to_path_buf(),
+        install_quota_bytes: daemon_config.install_quota_bytes(),
+        sandbox: daemon_config.sandbox.clone(),
+        factorio_image: daemon_config.factorio_image.clone(),
+        factorio_credentials: daemon_config.factorio_credentials(),
         output_dir: output_dir.to_path_buf(),
         retry_config: daemon_config.retry.clone(),
-        bot_notifier: None,
+        notifications: daemon::notifier::NotificationDispatcher::default(),
+        liveness: daemon::liveness::Liveness::default(),
+        scheduling: daemon::scheduling::Scheduling::new(&daemon_config.scheduling),
+        download_throttles: DownloadThrottles::new(&daemon_config.download_limits),
+        shutdown: CancellationToken::new(),
+        archive: daemon_config
+            .archive
+            .as_ref()
+            .map(|archive| archive.build()),
+        internet_archive: daemon_config.internet_archive(),
+        factorio_log: daemon_config.factorio_log.clone(),
+        report_signing_key: daemon_config.report_signing_key()?,
+        hooks: daemon_config.hooks.clone().map(Arc::new),
+        rule_descriptions: daemon_config.rule_descriptions()?,
     };
 
     info!("Polling speedrun.com for new runs");
@@ -302,24 +519,52 @@ async fn run_src_once(
     daemon::poll_speedrun_com(&ctx, &daemon_config.polling, &work_notify).await?;
 
     info!("Processing one run from queue");
-    match daemon::find_run_to_process(&ctx).await? {
+    match daemon::find_run_to_process(
+        &ctx,
+        &daemon::processor::worker_id(&daemon_config.instance_id(), 0),
+        &work_notify,
+    )
+    .await?
+    {
         daemon::ProcessResult::Processed => {
             info!("Successfully processed one run");
-            Ok(0)
+            Ok(true)
         }
         daemon::ProcessResult::NoWork => {
             info!("No runs available to process");
-            Ok(0)
+            Ok(false)
+        }
+    }
+}
+
+async fn cli_dry_run(run_id: Option<&str>, game_rules: &Path) -> Result<()> {
+    let src_rules = load_src_rules(game_rules).await?;
+    let client = daemon::speedrun_api::SpeedrunClient::new()?;
+    match run_id {
+        Some(run_id) => daemon::dry_run::dry_run_single(&src_rules, &client, run_id).await,
+        None => {
+            let daemon_config = load_daemon_config(&PathBuf::from("./daemon.yaml"))
+                .await
+                .context("Failed to load daemon config")?;
+            let speedrun_ops = daemon::speedrun_api::SpeedrunOps::new(&client);
+            daemon::dry_run::dry_run_poll(&src_rules, &speedrun_ops, &daemon_config.polling).await
         }
     }
 }
 
 async fn cli_daemon(args: DaemonArgs, token: CancellationToken) -> Result<i32> {
-    let DaemonArgs { config } = args;
+    let DaemonArgs { config, dry_run } = args;
 
     let daemon_config = load_daemon_config(&config).await?;
     let src_rules = load_src_rules(&daemon_config.game_rules_file).await?;
 
+    if dry_run {
+        let client = daemon_config.speedrun_client()?;
+        let speedrun_ops = daemon::speedrun_api::SpeedrunOps::new(&client);
+        daemon::dry_run::dry_run_poll(&src_rules, &speedrun_ops, &daemon_config.polling).await?;
+        return Ok(0);
+    }
+
     daemon::run_daemon(daemon_config, src_rules, token).await?;
     Ok(0)
 }
@@ -337,23 +582,50 @@ async fn load_save(path: &Path) -> Result<WrittenSaveFile> {
 }
 
 async fn load_run_rules(path: &Path) -> Result<RunRules> {
-    serde_yaml::from_reader(File::open(path)?).with_context(|| "failed to load rules")
+    let file = File::open(path).map_err(anyhow::Error::from);
+    file.and_then(|file| Ok(serde_yaml::from_reader(file)?))
+        .with_context(|| ConfigError(format!("failed to load rules {}", path.display())))
 }
 
 async fn load_src_rules(path: &Path) -> Result<SrcRunRules> {
-    serde_yaml::from_reader(File::open(path)?).with_context(|| "failed to load src rules")
+    let file = File::open(path).map_err(anyhow::Error::from);
+    file.and_then(|file| Ok(serde_yaml::from_reader(file)?))
+        .with_context(|| ConfigError(format!("failed to load src rules {}", path.display())))
 }
 
 async fn load_daemon_config(path: &Path) -> Result<daemon::DaemonConfig> {
-    serde_yaml::from_reader(File::open(path)?).with_context(|| "failed to load daemon config")
+    daemon::DaemonConfig::load(path)
+        .with_context(|| ConfigError(format!("failed to load daemon config {}", path.display())))
+}
+
+#[derive(Serialize)]
+struct RunOutput<'a> {
+    exit_code: ExitCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<&'a ReplayReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
 }
 
-fn result_to_exit_code<T>(result: &Result<ReplayReport, T>) -> i32 {
-    match result {
-        Ok(report) => report.to_exit_code(),
-        Err(_) => 20,
+fn print_run_result(
+    result: &Result<ReplayReport>,
+    format: OutputFormat,
+    fail_on: Option<FailOn>,
+) -> Result<i32> {
+    let exit_code = ExitCode::from_result(result, fail_on);
+    if format.is_json() {
+        print_json(&RunOutput {
+            exit_code,
+            report: result.as_ref().ok(),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        })?;
     }
+    Ok(exit_code.code())
 }
 
+#[cfg(test)]
+mod script_harness;
+#[cfg(test)]
+mod speedrun_mock;
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,44 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// How command results are written to stdout. Logs always go to stderr.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    /// CSV rows; only for `query export`
+    Csv,
+    /// JSON lines; only for `query export`
+    Jsonl,
+}
+
+impl OutputFormat {
+    pub fn is_json(self) -> bool {
+        self == OutputFormat::Json
+    }
+
+    /// Formats only `query export` writes.
+    pub fn is_export_only(self) -> bool {
+        matches!(self, OutputFormat::Csv | OutputFormat::Jsonl)
+    }
+}
+
+pub fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub struct ErrorOutput {
+    pub error: String,
+}
+
+impl ErrorOutput {
+    pub fn new(error: &anyhow::Error) -> Self {
+        Self {
+            error: format!("{:#}", error),
+        }
+    }
+}
@@ -2,15 +2,19 @@ use anyhow::{Context, Result};
 use chrono::Utc;
 use clap::Args;
 use comfy_table::{Cell, Table};
+use serde::Serialize;
 
-use crate::daemon::database::types::{Run, RunFilter, RunStatus};
-use crate::daemon::speedrun_api::SpeedrunOps;
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::{Run, RunDetails, RunFilter, RunStatus};
+use crate::daemon::speedrun_api::{SpeedrunOps, format_run_time};
+use crate::output::{OutputFormat, print_json};
 
 #[derive(Args, Clone, Default)]
 pub(crate) struct RunFilterArgs {
-    /// Filter by run status (discovered, processing, passed, needs_review, failed, error)
-    #[arg(long)]
-    pub status: Option<String>,
+    /// Filter by run status (discovered, processing, passed, needs_review, failed, error).
+    /// Comma-separated to match any of several.
+    #[arg(long, value_delimiter = ',')]
+    pub status: Vec<String>,
 
     /// Filter by speedrun.com game ID
     #[arg(long)]
@@ -28,6 +32,14 @@ pub(crate) struct RunFilterArgs {
     #[arg(long)]
     pub older_than: Option<String>,
 
+    /// Only show runs submitted on or after this date (e.g., 2024-01-31)
+    #[arg(long, conflicts_with = "newer_than")]
+    pub after: Option<String>,
+
+    /// Only show runs submitted before this date (e.g., 2024-01-31)
+    #[arg(long, conflicts_with = "older_than")]
+    pub before: Option<String>,
+
     /// Filter by error class (final, retryable, rate_limited)
     #[arg(long)]
     pub error_class: Option<String>,
@@ -36,6 +48,22 @@ pub(crate) struct RunFilterArgs {
     #[arg(long)]
     pub error_reason: Option<String>,
 
+    /// Search error messages for all of these words (case-insensitive)
+    #[arg(long)]
+    pub search: Option<String>,
+
+    /// Only show runs retried at least this many times
+    #[arg(long)]
+    pub min_retries: Option<u32>,
+
+    /// Only show runs retried at most this many times
+    #[arg(long)]
+    pub max_retries: Option<u32>,
+
+    /// Filter by whether the bot has been notified (true/false)
+    #[arg(long)]
+    pub bot_notified: Option<bool>,
+
     /// Maximum number of runs to display
     #[arg(long)]
     pub limit: Option<u32>,
@@ -47,85 +75,118 @@ pub(crate) struct RunFilterArgs {
 
 impl RunFilterArgs {
     pub fn to_filter(&self) -> Result<RunFilter> {
-        let status = self
+        let statuses = self
             .status
-            .as_ref()
+            .iter()
             .map(|s| parse_status(s))
-            .transpose()
+            .collect::<Result<Vec<_>>>()
             .context("Invalid status value")?;
 
-        let since_date = self
-            .newer_than
-            .as_ref()
-            .map(|s| parse_relative_duration(s))
-            .transpose()?;
+        let since_date = match (&self.newer_than, &self.after) {
+            (Some(duration), _) => Some(parse_relative_duration(duration)?),
+            (None, Some(date)) => Some(parse_date(date)?),
+            (None, None) => None,
+        };
 
-        let before_date = self
-            .older_than
-            .as_ref()
-            .map(|s| parse_relative_duration(s))
-            .transpose()?;
+        let before_date = match (&self.older_than, &self.before) {
+            (Some(duration), _) => Some(parse_relative_duration(duration)?),
+            (None, Some(date)) => Some(parse_date(date)?),
+            (None, None) => None,
+        };
 
         Ok(RunFilter {
-            status,
+            statuses,
             game_id: self.game_id.clone(),
             category_id: self.category_id.clone(),
             since_date,
             before_date,
             error_class: self.error_class.clone(),
             error_reason: self.error_reason.clone(),
+            min_retries: self.min_retries,
+            max_retries: self.max_retries,
+            bot_notified: self.bot_notified,
+            search: self.search.clone(),
             limit: self.limit,
             offset: self.offset,
+            ..Default::default()
         })
     }
 
     pub fn with_status(mut self, status: &str) -> Self {
-        self.status = Some(status.to_string());
+        self.status = vec![status.to_string()];
         self
     }
 
     pub fn has_any_filter(&self) -> bool {
-        self.status.is_some()
+        !self.status.is_empty()
             || self.game_id.is_some()
             || self.category_id.is_some()
             || self.newer_than.is_some()
             || self.older_than.is_some()
+            || self.after.is_some()
+            || self.before.is_some()
             || self.error_class.is_some()
             || self.error_reason.is_some()
+            || self.search.is_some()
+            || self.min_retries.is_some()
+            || self.max_retries.is_some()
+            || self.bot_notified.is_some()
     }
 }
 
 pub(crate) async fn query_and_display_runs(
-    db: &crate::daemon::database::connection::Database,
+    db: &Database,
     ops: &SpeedrunOps,
     filter: RunFilter,
+    format: OutputFormat,
 ) -> Result<()> {
     let runs = db.query_runs(filter).await?;
 
-    if runs.is_empty() {
+    if runs.is_empty() && !format.is_json() {
         println!("No runs found matching the criteria");
         return Ok(());
     }
 
+    let run_displays = load_run_displays(db, ops, &runs).await?;
+
+    if format.is_json() {
+        print_json(&run_displays)?;
+    } else {
+        println!("{}", format_runs_as_table(&run_displays));
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub(crate) struct RunDisplay<'a> {
+    #[serde(flatten)]
+    pub run: &'a Run,
+    pub game_name: String,
+    pub category_name: String,
+    #[serde(flatten)]
+    pub details: Option<RunDetails>,
+}
+
+/// Resolves game/category names and loads the stored speedrun.com details of each run.
+pub(crate) async fn load_run_displays<'a>(
+    db: &Database,
+    ops: &SpeedrunOps,
+    runs: &'a [Run],
+) -> Result<Vec<RunDisplay<'a>>> {
+    let run_ids: Vec<&str> = runs.iter().map(|run| run.run_id.as_str()).collect();
+    let mut details = db.get_runs_details(&run_ids).await?;
     let mut run_displays = Vec::new();
-    for run in &runs {
+    for run in runs {
         let (game_name, category_name) =
             resolve_game_category(ops, &run.game_id, &run.category_id).await;
         run_displays.push(RunDisplay {
             run,
             game_name,
             category_name,
+            details: details.remove(&run.run_id),
         });
     }
-
-    println!("{}", format_runs_as_table(&run_displays));
-    Ok(())
-}
-
-pub(crate) struct RunDisplay<'a> {
-    pub run: &'a Run,
-    pub game_name: String,
-    pub category_name: String,
+    Ok(run_displays)
 }
 
 pub(crate) fn format_runs_as_table(runs: &[RunDisplay]) -> String {
@@ -133,6 +194,9 @@ pub(crate) fn format_runs_as_table(runs: &[RunDisplay]) -> String {
     table.set_header(vec![
         "Run ID",
         "Game/Category",
+        "Runner",
+        "Time",
+        "Place",
         "Submitted",
         "Status",
         "Retries",
@@ -143,6 +207,19 @@ pub(crate) fn format_runs_as_table(runs: &[RunDisplay]) -> String {
     for run_display in runs {
         let run = run_display.run;
         let game_category = format!("{} / {}", run_display.game_name, run_display.category_name);
+        let details = run_display.details.as_ref();
+        let runner = details
+            .and_then(|details| details.player_names.as_deref())
+            .map(|names| truncate_str(names, 20))
+            .unwrap_or_else(|| "-".to_string());
+        let run_time = details
+            .and_then(|details| details.run_time_secs)
+            .map(format_run_time)
+            .unwrap_or_else(|| "-".to_string());
+        let place = details
+            .and_then(|details| details.leaderboard_place)
+            .map(|place| format!("#{}", place))
+            .unwrap_or_else(|| "-".to_string());
         let submitted = run.submitted_date.format("%Y-%m-%d %H:%M").to_string();
         let status = format_status(&run.status);
         let retries = if run.retry_count > 0 {
@@ -160,6 +237,9 @@ pub(crate) fn format_runs_as_table(runs: &[RunDisplay]) -> String {
         table.add_row(vec![
             Cell::new(&run.run_id[..8.min(run.run_id.len())]),
             Cell::new(game_category),
+            Cell::new(runner),
+            Cell::new(run_time),
+            Cell::new(place),
             Cell::new(submitted),
             Cell::new(status),
             Cell::new(retries),
@@ -225,10 +305,37 @@ pub(crate) fn parse_relative_duration(duration_str: &str) -> Result<chrono::Date
     Ok(Utc::now() - chrono_duration)
 }
 
+/// Parses an RFC 3339 timestamp or a date, taken as midnight UTC.
+pub(crate) fn parse_date(date_str: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(date_str) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+    let date = chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
+        .context("Invalid date format. Examples: 2024-01-31, 2024-01-31T12:00:00Z")?;
+    Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(
+            parse_date("2024-01-31").unwrap(),
+            "2024-01-31T00:00:00Z"
+                .parse::<chrono::DateTime<Utc>>()
+                .unwrap()
+        );
+        assert_eq!(
+            parse_date("2024-01-31T12:30:00+02:00").unwrap(),
+            "2024-01-31T10:30:00Z"
+                .parse::<chrono::DateTime<Utc>>()
+                .unwrap()
+        );
+        assert!(parse_date("31/01/2024").is_err());
+    }
+
     #[test]
     fn test_parse_relative_duration_days() {
         let result = parse_relative_duration("30d");
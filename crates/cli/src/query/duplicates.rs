@@ -0,0 +1,88 @@
+use anyhow::Result;
+use clap::Args;
+use comfy_table::{Cell, Table};
+use serde::Serialize;
+
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::DuplicateSave;
+use crate::daemon::speedrun_api::SpeedrunOps;
+use crate::output::{OutputFormat, print_json};
+
+use super::common::{format_status, resolve_game_category};
+
+#[derive(Args)]
+pub struct DuplicatesArgs {
+    /// Only show runs sharing a save with this run
+    #[arg(long)]
+    pub run_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DuplicateDisplay {
+    #[serde(flatten)]
+    duplicate: DuplicateSave,
+    game_name: String,
+    category_name: String,
+}
+
+pub async fn handle_duplicates(
+    db: &Database,
+    ops: &SpeedrunOps,
+    args: DuplicatesArgs,
+    format: OutputFormat,
+) -> Result<()> {
+    let duplicates = db.find_duplicate_saves(args.run_id.as_deref()).await?;
+
+    if duplicates.is_empty() && !format.is_json() {
+        println!("No runs share a save with another run");
+        return Ok(());
+    }
+
+    let mut displays = Vec::new();
+    for duplicate in duplicates {
+        let (game_name, category_name) =
+            resolve_game_category(ops, &duplicate.game_id, &duplicate.category_id).await;
+        displays.push(DuplicateDisplay {
+            duplicate,
+            game_name,
+            category_name,
+        });
+    }
+
+    if format.is_json() {
+        return print_json(&displays);
+    }
+    println!("{}", format_duplicates_as_table(&displays));
+    Ok(())
+}
+
+fn format_duplicates_as_table(displays: &[DuplicateDisplay]) -> String {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Save SHA-256",
+        "Run ID",
+        "Game/Category",
+        "Runner",
+        "Submitted",
+        "Status",
+    ]);
+
+    for display in displays {
+        let duplicate = &display.duplicate;
+        table.add_row(vec![
+            Cell::new(&duplicate.sha256[..12.min(duplicate.sha256.len())]),
+            Cell::new(&duplicate.run_id),
+            Cell::new(format!("{} / {}", display.game_name, display.category_name)),
+            Cell::new(duplicate.player_names.as_deref().unwrap_or("-")),
+            Cell::new(
+                duplicate
+                    .submitted_date
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+            ),
+            Cell::new(format_status(&duplicate.status)),
+        ]);
+    }
+
+    table.to_string()
+}
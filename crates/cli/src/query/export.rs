@@ -0,0 +1,287 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::{Run, RunFilter, RunOrder};
+use crate::daemon::speedrun_api::SpeedrunOps;
+use crate::output::OutputFormat;
+
+use super::common::{RunFilterArgs, format_status, resolve_game_category};
+
+/// Runs are read from the database in pages of this size.
+const EXPORT_PAGE_SIZE: u32 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ExportFormat {
+    Csv,
+    Jsonl,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub filter: RunFilterArgs,
+
+    /// File to write; defaults to stdout
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+}
+
+impl ExportArgs {
+    /// JSON lines with `--format jsonl` or `json`, CSV with `--format csv`, otherwise CSV
+    /// unless the output file is `.jsonl`.
+    fn export_format(&self, format: OutputFormat) -> ExportFormat {
+        match format {
+            OutputFormat::Json | OutputFormat::Jsonl => ExportFormat::Jsonl,
+            OutputFormat::Csv => ExportFormat::Csv,
+            OutputFormat::Text => match self.output.as_deref().and_then(Path::extension) {
+                Some(ext) if ext == "jsonl" => ExportFormat::Jsonl,
+                _ => ExportFormat::Csv,
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExportRow<'a> {
+    run_id: &'a str,
+    game_id: &'a str,
+    game_name: &'a str,
+    category_id: &'a str,
+    category_name: &'a str,
+    submitted_date: DateTime<Utc>,
+    status: String,
+    error_class: Option<&'a str>,
+    error_message: Option<&'a str>,
+    retry_count: u32,
+    bot_notified: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+pub async fn handle_export(
+    db: &Database,
+    ops: &SpeedrunOps,
+    args: ExportArgs,
+    format: OutputFormat,
+) -> Result<()> {
+    let filter = args.filter.to_filter()?;
+    let format = args.export_format(format);
+    let count = match &args.output {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            let count = write_export(
+                db,
+                ops,
+                filter,
+                format,
+                BufWriter::new(file),
+                EXPORT_PAGE_SIZE,
+            )
+            .await?;
+            eprintln!("Exported {} run(s) to {}", count, path.display());
+            count
+        }
+        None => {
+            let stdout = std::io::stdout().lock();
+            write_export(db, ops, filter, format, stdout, EXPORT_PAGE_SIZE).await?
+        }
+    };
+    log::info!("Exported {} run(s)", count);
+    Ok(())
+}
+
+/// Writes all runs matching `filter`, a page at a time. Returns the number of runs written.
+async fn write_export(
+    db: &Database,
+    ops: &SpeedrunOps,
+    filter: RunFilter,
+    format: ExportFormat,
+    writer: impl Write,
+    page_size: u32,
+) -> Result<usize> {
+    let mut writer = match format {
+        ExportFormat::Csv => RowWriter::Csv(Box::new(csv::Writer::from_writer(writer))),
+        ExportFormat::Jsonl => RowWriter::Jsonl(writer),
+    };
+
+    let mut names: HashMap<(String, String), (String, String)> = HashMap::new();
+    let mut remaining = filter.limit;
+    // the offset only skips to the first page; later pages continue after the last run
+    let mut offset = filter.offset;
+    let mut after = None;
+    let mut count = 0;
+
+    loop {
+        let page_limit = remaining.map_or(page_size, |remaining| remaining.min(page_size));
+        if page_limit == 0 {
+            break;
+        }
+        let page = db
+            .query_runs(RunFilter {
+                order: RunOrder::Submitted,
+                after: after.clone(),
+                limit: Some(page_limit),
+                offset,
+                ..filter.clone()
+            })
+            .await?;
+
+        for run in &page {
+            let key = (run.game_id.clone(), run.category_id.clone());
+            if !names.contains_key(&key) {
+                let resolved = resolve_game_category(ops, &run.game_id, &run.category_id).await;
+                names.insert(key.clone(), resolved);
+            }
+            let (game_name, category_name) = &names[&key];
+            writer.write_row(&export_row(run, game_name, category_name))?;
+        }
+
+        count += page.len();
+        offset = 0;
+        after = page
+            .last()
+            .map(|run| (run.submitted_date, run.run_id.clone()));
+        remaining = remaining.map(|remaining| remaining - page.len() as u32);
+        if (page.len() as u32) < page_limit {
+            break;
+        }
+    }
+
+    writer.flush()?;
+    Ok(count)
+}
+
+enum RowWriter<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Jsonl(W),
+}
+
+impl<W: Write> RowWriter<W> {
+    fn write_row(&mut self, row: &ExportRow) -> Result<()> {
+        match self {
+            RowWriter::Csv(writer) => writer.serialize(row)?,
+            RowWriter::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, row)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            RowWriter::Csv(writer) => writer.flush(),
+            RowWriter::Jsonl(writer) => writer.flush(),
+        }
+    }
+}
+
+fn export_row<'a>(run: &'a Run, game_name: &'a str, category_name: &'a str) -> ExportRow<'a> {
+    ExportRow {
+        run_id: &run.run_id,
+        game_id: &run.game_id,
+        game_name,
+        category_id: &run.category_id,
+        category_name,
+        submitted_date: run.submitted_date,
+        status: format_status(&run.status),
+        error_class: run.error_class.as_deref(),
+        error_message: run.error_message.as_deref(),
+        retry_count: run.retry_count,
+        bot_notified: run.bot_notified,
+        created_at: run.created_at,
+        updated_at: run.updated_at,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::database::types::NewRun;
+    use crate::daemon::speedrun_api::SpeedrunClient;
+
+    async fn setup() -> (Database, SpeedrunOps) {
+        let db = Database::in_memory().await.unwrap();
+        db.cache_game_name("game1", "Factorio").await.unwrap();
+        db.cache_category_name("cat1", "Any%").await.unwrap();
+        for i in 1..=5 {
+            let submitted_date = format!("2024-01-0{}T00:00:00Z", i).parse().unwrap();
+            db.insert_run(NewRun::new(
+                format!("run{}", i),
+                "game1",
+                "cat1",
+                submitted_date,
+            ))
+            .await
+            .unwrap();
+        }
+        db.mark_run_failed("run3", Some("used editor, twice"))
+            .await
+            .unwrap();
+        let ops = SpeedrunOps::new(&SpeedrunClient::new().unwrap()).with_db(db.clone());
+        (db, ops)
+    }
+
+    #[tokio::test]
+    async fn test_export_csv_in_pages() {
+        let (db, ops) = setup().await;
+        let mut out = Vec::new();
+        let count = write_export(
+            &db,
+            &ops,
+            RunFilter::default(),
+            ExportFormat::Csv,
+            &mut out,
+            2,
+        )
+        .await
+        .unwrap();
+        assert_eq!(count, 5);
+
+        let mut reader = csv::Reader::from_reader(out.as_slice());
+        let headers = reader.headers().unwrap().clone();
+        assert_eq!(&headers[0], "run_id");
+        assert_eq!(&headers[2], "game_name");
+        let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        let run_ids: Vec<&str> = rows.iter().map(|row| &row[0]).collect();
+        assert_eq!(run_ids, ["run5", "run4", "run3", "run2", "run1"]);
+        assert_eq!(&rows[2][4], "Any%");
+        assert_eq!(&rows[2][6], "failed");
+        assert_eq!(&rows[2][8], "used editor, twice");
+    }
+
+    #[tokio::test]
+    async fn test_export_jsonl_respects_limit() {
+        let (db, ops) = setup().await;
+        let filter = RunFilter {
+            limit: Some(3),
+            offset: 1,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let count = write_export(&db, &ops, filter, ExportFormat::Jsonl, &mut out, 2)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let run_ids: Vec<&str> = lines
+            .iter()
+            .map(|l| l["run_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(run_ids, ["run4", "run3", "run2"]);
+        assert_eq!(lines[0]["game_name"], "Factorio");
+    }
+}
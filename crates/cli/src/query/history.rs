@@ -0,0 +1,52 @@
+use anyhow::Result;
+use clap::Args;
+use comfy_table::{Cell, Table};
+
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::RunEvent;
+use crate::output::{OutputFormat, print_json};
+
+use super::common::format_status;
+
+#[derive(Args)]
+pub struct HistoryArgs {
+    /// Speedrun.com run ID
+    pub run_id: String,
+}
+
+pub async fn handle_history(db: &Database, args: HistoryArgs, format: OutputFormat) -> Result<()> {
+    let events = db.get_run_events(&args.run_id).await?;
+    if events.is_empty() && db.get_run(&args.run_id).await?.is_none() {
+        anyhow::bail!("Run not found: {}", args.run_id);
+    }
+
+    if format.is_json() {
+        return print_json(&events);
+    }
+
+    println!("{}", format_events_as_table(&events));
+    Ok(())
+}
+
+fn format_events_as_table(events: &[RunEvent]) -> String {
+    let mut table = Table::new();
+    table.set_header(vec!["Time", "From", "To", "Actor", "Reason"]);
+
+    for event in events {
+        table.add_row(vec![
+            Cell::new(event.created_at.format("%Y-%m-%d %H:%M:%S").to_string()),
+            Cell::new(
+                event
+                    .old_status
+                    .as_ref()
+                    .map(format_status)
+                    .unwrap_or_else(|| "-".to_string()),
+            ),
+            Cell::new(format_status(&event.new_status)),
+            Cell::new(&event.actor),
+            Cell::new(event.reason.as_deref().unwrap_or("-")),
+        ]);
+    }
+
+    table.to_string()
+}
@@ -3,6 +3,7 @@ use clap::Args;
 
 use crate::daemon::database::connection::Database;
 use crate::daemon::speedrun_api::SpeedrunOps;
+use crate::output::OutputFormat;
 
 use super::common::{RunFilterArgs, query_and_display_runs};
 
@@ -12,7 +13,12 @@ pub struct ListArgs {
     pub filter: RunFilterArgs,
 }
 
-pub async fn handle_list(db: &Database, ops: &SpeedrunOps, args: ListArgs) -> Result<()> {
+pub async fn handle_list(
+    db: &Database,
+    ops: &SpeedrunOps,
+    args: ListArgs,
+    format: OutputFormat,
+) -> Result<()> {
     let filter = args.filter.to_filter()?;
-    query_and_display_runs(db, ops, filter).await
+    query_and_display_runs(db, ops, filter, format).await
 }
@@ -4,15 +4,22 @@ use std::path::PathBuf;
 
 use crate::daemon::database::connection::Database;
 use crate::daemon::speedrun_api::{SpeedrunClient, SpeedrunOps};
+use crate::output::OutputFormat;
 
 pub mod common;
+mod duplicates;
 mod errors;
+mod export;
+mod history;
 mod list;
 mod queue;
 mod show;
 mod stats;
 
+pub use duplicates::DuplicatesArgs;
 pub use errors::ErrorsArgs;
+pub use export::ExportArgs;
+pub use history::HistoryArgs;
 pub use list::ListArgs;
 pub use queue::QueueArgs;
 pub use show::ShowArgs;
@@ -40,21 +47,40 @@ pub enum QuerySubcommand {
     Queue(QueueArgs),
     /// Show runs with errors
     Errors(ErrorsArgs),
+    /// Show the status history of a run
+    History(HistoryArgs),
+    /// Export runs as CSV, or JSON lines with --format jsonl
+    Export(ExportArgs),
+    /// Show runs whose save was also submitted for another run
+    Duplicates(DuplicatesArgs),
 }
 
-pub async fn handle_query_command(args: QueryArgs) -> Result<()> {
+pub async fn handle_query_command(args: QueryArgs, format: OutputFormat) -> Result<()> {
     let db = Database::new(&args.database).await?;
     let speedrun_client = SpeedrunClient::new().context("Failed to create speedrun client")?;
     let speedrun_ops = SpeedrunOps::new(&speedrun_client).with_db(db.clone());
 
     match args.subcommand {
-        QuerySubcommand::List(list_args) => list::handle_list(&db, &speedrun_ops, list_args).await,
-        QuerySubcommand::Show(show_args) => show::handle_show(&db, &speedrun_ops, show_args).await,
-        QuerySubcommand::Stats(stats_args) => stats::handle_stats(&db, stats_args).await,
-        QuerySubcommand::Queue(queue_args) => queue::handle_queue(&db, queue_args).await,
+        QuerySubcommand::List(list_args) => {
+            list::handle_list(&db, &speedrun_ops, list_args, format).await
+        }
+        QuerySubcommand::Show(show_args) => {
+            show::handle_show(&db, &speedrun_ops, show_args, format).await
+        }
+        QuerySubcommand::Stats(stats_args) => stats::handle_stats(&db, stats_args, format).await,
+        QuerySubcommand::Queue(queue_args) => queue::handle_queue(&db, queue_args, format).await,
         QuerySubcommand::Errors(errors_args) => {
             let filter = errors_args.into_filter_with_error_status().to_filter()?;
-            common::query_and_display_runs(&db, &speedrun_ops, filter).await
+            common::query_and_display_runs(&db, &speedrun_ops, filter, format).await
+        }
+        QuerySubcommand::History(history_args) => {
+            history::handle_history(&db, history_args, format).await
+        }
+        QuerySubcommand::Export(export_args) => {
+            export::handle_export(&db, &speedrun_ops, export_args, format).await
+        }
+        QuerySubcommand::Duplicates(duplicates_args) => {
+            duplicates::handle_duplicates(&db, &speedrun_ops, duplicates_args, format).await
         }
     }
 }
@@ -1,23 +1,131 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use clap::Args;
+use serde::Serialize;
 
 use crate::daemon::database::connection::Database;
-use crate::daemon::database::types::{RunFilter, RunStatus};
+use crate::daemon::database::types::{QueuedRun, ReplayThroughput, RunFilter, RunStatus};
+use crate::daemon::progress::TICKS_PER_SECOND;
+use crate::daemon::speedrun_api::format_run_time;
+use crate::output::{OutputFormat, print_json};
 
 #[derive(Args)]
 pub struct QueueArgs {
-    // No arguments needed
+    /// Runs replayed at the same time, like the daemon's max_concurrent_runs
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+    /// Instance ID of the daemon replaying the queue, to estimate with its replay speed
+    #[arg(long)]
+    pub host: Option<String>,
 }
 
-pub async fn handle_queue(db: &Database, _args: QueueArgs) -> Result<()> {
+#[derive(Serialize)]
+struct QueueOutput {
+    pending: usize,
+    scheduled_retries: usize,
+    next_retry_at: Option<DateTime<Utc>>,
+    throughput: Vec<ThroughputOutput>,
+    estimate: Option<QueueEstimate>,
+}
+
+#[derive(Serialize)]
+struct ThroughputOutput {
+    #[serde(flatten)]
+    throughput: ReplayThroughput,
+    ticks_per_sec: f64,
+}
+
+/// How long the queued runs will take to replay, from their times on speedrun.com and the
+/// recorded replay speed.
+#[derive(Debug, PartialEq, Serialize)]
+struct QueueEstimate {
+    /// Replay speed over all recorded replays.
+    ticks_per_sec: f64,
+    runs: Vec<RunEstimate>,
+    /// Runs without a time on speedrun.com, left out of the estimate.
+    unknown_runs: usize,
+    /// Seconds until the last estimated run is done.
+    total_secs: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct RunEstimate {
+    run_id: String,
+    replay_secs: f64,
+    /// Seconds from now until the run is done, if the runs before it take their estimates.
+    done_in_secs: f64,
+}
+
+/// Replay speed over the recorded replays of `version` on `host`, where given.
+fn ticks_per_sec(
+    throughput: &[ReplayThroughput],
+    version: Option<&str>,
+    host: Option<&str>,
+) -> Option<f64> {
+    let matching = throughput.iter().filter(|t| {
+        version.is_none_or(|version| t.factorio_version == version)
+            && host.is_none_or(|host| t.host == host)
+    });
+    let (ticks, duration_secs) = matching.fold((0, 0.0), |(ticks, secs), t| {
+        (ticks + t.ticks, secs + t.duration_secs)
+    });
+    (ticks > 0 && duration_secs > 0.0).then(|| ticks as f64 / duration_secs)
+}
+
+/// Estimates the remaining replay times of `queued` in order, each taken by the first of
+/// `workers` free. Each run's speed is that recorded for its Factorio version on `host`, if
+/// known from an earlier replay, falling back to fewer of them down to all recorded replays.
+fn estimate_queue(
+    queued: &[QueuedRun],
+    throughput: &[ReplayThroughput],
+    workers: usize,
+    host: Option<&str>,
+) -> Option<QueueEstimate> {
+    let overall = ticks_per_sec(throughput, None, None)?;
+
+    let mut free_at = vec![0.0_f64; workers.max(1)];
+    let mut runs = Vec::new();
+    for run in queued {
+        let Some(run_time_secs) = run.run_time_secs else {
+            continue;
+        };
+        let version = run.factorio_version.as_deref();
+        let speed = [(version, host), (version, None), (None, host)]
+            .into_iter()
+            .filter(|&(v, h)| v.is_some() || h.is_some())
+            .find_map(|(version, host)| ticks_per_sec(throughput, version, host))
+            .unwrap_or(overall);
+        let remaining_ticks =
+            (run_time_secs * TICKS_PER_SECOND - run.replayed_ticks as f64).max(0.0);
+        let replay_secs = remaining_ticks / speed;
+        let worker = free_at
+            .iter_mut()
+            .min_by(|a, b| a.total_cmp(b))
+            .expect("at least one worker");
+        *worker += replay_secs;
+        runs.push(RunEstimate {
+            run_id: run.run_id.clone(),
+            replay_secs,
+            done_in_secs: *worker,
+        });
+    }
+    Some(QueueEstimate {
+        ticks_per_sec: overall,
+        unknown_runs: queued.len() - runs.len(),
+        total_secs: runs.iter().map(|run| run.done_in_secs).fold(0.0, f64::max),
+        runs,
+    })
+}
+
+pub async fn handle_queue(db: &Database, args: QueueArgs, format: OutputFormat) -> Result<()> {
     let discovered_filter = RunFilter {
-        status: Some(RunStatus::Discovered),
+        statuses: vec![RunStatus::Discovered],
         ..Default::default()
     };
     let discovered_runs = db.query_runs(discovered_filter).await?;
 
     let retry_filter = RunFilter {
-        status: Some(RunStatus::Error),
+        statuses: vec![RunStatus::Error],
         ..Default::default()
     };
     let error_runs = db.query_runs(retry_filter).await?;
@@ -26,16 +134,181 @@ pub async fn handle_queue(db: &Database, _args: QueueArgs) -> Result<()> {
         .filter(|r| r.next_retry_at.is_some())
         .collect();
 
+    let next_retry_at = retry_scheduled.iter().filter_map(|r| r.next_retry_at).min();
+
+    let throughput = db.replay_throughput().await?;
+    let estimate = estimate_queue(
+        &db.queued_runs().await?,
+        &throughput,
+        args.workers,
+        args.host.as_deref(),
+    );
+
+    if format.is_json() {
+        return print_json(&QueueOutput {
+            pending: discovered_runs.len(),
+            scheduled_retries: retry_scheduled.len(),
+            next_retry_at,
+            throughput: throughput
+                .into_iter()
+                .map(|throughput| ThroughputOutput {
+                    ticks_per_sec: throughput.ticks_per_sec(),
+                    throughput,
+                })
+                .collect(),
+            estimate,
+        });
+    }
+
     println!("=== Queue ===");
     println!("Pending Runs:      {}", discovered_runs.len());
     println!("Scheduled Retries: {}", retry_scheduled.len());
 
-    if let Some(next_retry) = retry_scheduled.iter().filter_map(|r| r.next_retry_at).min() {
+    if let Some(next_retry) = next_retry_at {
         let local_time = next_retry.with_timezone(&chrono::Local);
         println!(
             "Next Retry At:     {}",
             local_time.format("%Y-%m-%d %H:%M:%S %Z")
         );
     }
+
+    let Some(estimate) = estimate else {
+        println!("\nNo replays recorded yet to estimate the queue from.");
+        return Ok(());
+    };
+    println!("\n=== Estimate ===");
+    println!(
+        "Replay Speed:      {:.0} ticks/s ({:.1}x real time)",
+        estimate.ticks_per_sec,
+        estimate.ticks_per_sec / TICKS_PER_SECOND
+    );
+    println!(
+        "Queue Done In:     {}",
+        format_run_time(estimate.total_secs)
+    );
+    if estimate.unknown_runs > 0 {
+        println!(
+            "                   ({} run(s) without a time left out)",
+            estimate.unknown_runs
+        );
+    }
+    if !estimate.runs.is_empty() {
+        println!("\n{:<12} {:>10} {:>10}", "Run", "Replay", "Done In");
+        for run in &estimate.runs {
+            println!(
+                "{:<12} {:>10} {:>10}",
+                run.run_id,
+                format_run_time(run.replay_secs),
+                format_run_time(run.done_in_secs)
+            );
+        }
+    }
+
+    println!("\n=== Replay Speed by Version ===");
+    for throughput in &throughput {
+        println!(
+            "{:<10} {:<20} {:>8.0} ticks/s over {} replay(s)",
+            throughput.factorio_version,
+            throughput.host,
+            throughput.ticks_per_sec(),
+            throughput.replays
+        );
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(run_id: &str, run_time_secs: Option<f64>) -> QueuedRun {
+        QueuedRun {
+            run_id: run_id.to_string(),
+            run_time_secs,
+            replayed_ticks: 0,
+            factorio_version: None,
+        }
+    }
+
+    fn throughput(ticks: u64, duration_secs: f64) -> ReplayThroughput {
+        host_throughput("2.0.65", "host", ticks, duration_secs)
+    }
+
+    fn host_throughput(
+        version: &str,
+        host: &str,
+        ticks: u64,
+        duration_secs: f64,
+    ) -> ReplayThroughput {
+        ReplayThroughput {
+            factorio_version: version.to_string(),
+            host: host.to_string(),
+            replays: 1,
+            ticks,
+            duration_secs,
+        }
+    }
+
+    #[test]
+    fn test_estimate_queue() {
+        assert_eq!(
+            estimate_queue(&[queued("a", Some(60.0))], &[], 1, None),
+            None
+        );
+
+        // 600 ticks/s overall: ten times real time
+        let throughput = [throughput(36_000, 100.0), throughput(84_000, 100.0)];
+        let queue = [
+            queued("a", Some(600.0)),
+            queued("b", None),
+            queued("c", Some(300.0)),
+            queued("d", Some(100.0)),
+        ];
+
+        let estimate = estimate_queue(&queue, &throughput, 1, None).unwrap();
+        assert_eq!(estimate.ticks_per_sec, 600.0);
+        assert_eq!(estimate.unknown_runs, 1);
+        let done: Vec<_> = estimate.runs.iter().map(|run| run.done_in_secs).collect();
+        assert_eq!(done, [60.0, 90.0, 100.0]);
+        assert_eq!(estimate.total_secs, 100.0);
+
+        let estimate = estimate_queue(&queue, &throughput, 2, None).unwrap();
+        let done: Vec<_> = estimate.runs.iter().map(|run| run.done_in_secs).collect();
+        assert_eq!(done, [60.0, 30.0, 40.0]);
+        assert_eq!(estimate.total_secs, 60.0);
+    }
+
+    #[test]
+    fn test_estimate_queue_by_version_and_host() {
+        let throughput = [
+            host_throughput("2.0.65", "fast", 120_000, 100.0),
+            host_throughput("2.0.65", "slow", 30_000, 100.0),
+            host_throughput("2.0.60", "slow", 60_000, 100.0),
+        ];
+        let replay_secs = |run: QueuedRun, host| {
+            estimate_queue(&[run], &throughput, 1, host).unwrap().runs[0].replay_secs
+        };
+        let known = |version: &str| QueuedRun {
+            factorio_version: Some(version.to_string()),
+            ..queued("a", Some(600.0))
+        };
+
+        // 36000 ticks: overall 700 ticks/s, 2.0.65 at 750, on slow 300, and slow at 450
+        assert_eq!(
+            replay_secs(queued("a", Some(600.0)), None),
+            36_000.0 / 700.0
+        );
+        assert_eq!(replay_secs(known("2.0.65"), None), 48.0);
+        assert_eq!(replay_secs(known("2.0.65"), Some("slow")), 120.0);
+        assert_eq!(replay_secs(queued("a", Some(600.0)), Some("slow")), 80.0);
+        // nothing recorded for the version on the host, so the version's speed
+        assert_eq!(replay_secs(known("2.0.60"), Some("fast")), 60.0);
+
+        // only the rest of a run in progress is left
+        let running = QueuedRun {
+            replayed_ticks: 24_000,
+            ..known("2.0.65")
+        };
+        assert_eq!(replay_secs(running, None), 16.0);
+    }
+}
@@ -1,10 +1,13 @@
 use anyhow::Result;
 use clap::Args;
+use serde::Serialize;
 
 use crate::daemon::database::connection::Database;
-use crate::daemon::speedrun_api::SpeedrunOps;
+use crate::daemon::database::types::{Checkpoint, ReplayResult, Review, RunDetails};
+use crate::daemon::speedrun_api::{SpeedrunOps, format_run_time};
+use crate::output::{OutputFormat, print_json};
 
-use super::common::{format_status, resolve_game_category};
+use super::common::{RunDisplay, format_status, resolve_game_category};
 
 #[derive(Args)]
 pub struct ShowArgs {
@@ -12,7 +15,21 @@ pub struct ShowArgs {
     pub run_id: String,
 }
 
-pub async fn handle_show(db: &Database, ops: &SpeedrunOps, args: ShowArgs) -> Result<()> {
+#[derive(Serialize)]
+struct ShowDisplay<'a> {
+    #[serde(flatten)]
+    run: RunDisplay<'a>,
+    replay_result: Option<ReplayResult>,
+    review: Option<Review>,
+    checkpoint: Option<Checkpoint>,
+}
+
+pub async fn handle_show(
+    db: &Database,
+    ops: &SpeedrunOps,
+    args: ShowArgs,
+    format: OutputFormat,
+) -> Result<()> {
     let run = db
         .get_run(&args.run_id)
         .await?
@@ -20,6 +37,24 @@ pub async fn handle_show(db: &Database, ops: &SpeedrunOps, args: ShowArgs) -> Re
 
     let (game_name, category_name) =
         resolve_game_category(ops, &run.game_id, &run.category_id).await;
+    let replay_result = db.get_replay_result(&run.run_id).await?;
+    let details = db.get_run_details(&run.run_id).await?;
+    let review = db.get_review(&run.run_id).await?;
+    let checkpoint = db.get_checkpoint(&run.run_id).await?;
+
+    if format.is_json() {
+        return print_json(&ShowDisplay {
+            run: RunDisplay {
+                run: &run,
+                game_name,
+                category_name,
+                details,
+            },
+            replay_result,
+            review,
+            checkpoint,
+        });
+    }
 
     println!("Run Details");
     println!("===========");
@@ -27,6 +62,9 @@ pub async fn handle_show(db: &Database, ops: &SpeedrunOps, args: ShowArgs) -> Re
     println!("Run ID:          {}", run.run_id);
     println!("Game:            {} ({})", game_name, run.game_id);
     println!("Category:        {} ({})", category_name, run.category_id);
+    if let Some(details) = &details {
+        print_run_details(details);
+    }
     println!(
         "Submitted:       {}",
         run.submitted_date.format("%Y-%m-%d %H:%M:%S UTC")
@@ -51,6 +89,18 @@ pub async fn handle_show(db: &Database, ops: &SpeedrunOps, args: ShowArgs) -> Re
         println!("{}", error_msg);
     }
 
+    if let Some(result) = &replay_result {
+        print_replay_result(result);
+    }
+
+    if let Some(review) = &review {
+        print_review(review);
+    }
+
+    if let Some(checkpoint) = &checkpoint {
+        print_checkpoint(checkpoint);
+    }
+
     println!();
     println!(
         "Created:         {}",
@@ -65,3 +115,84 @@ pub async fn handle_show(db: &Database, ops: &SpeedrunOps, args: ShowArgs) -> Re
 
     Ok(())
 }
+
+fn print_run_details(details: &RunDetails) {
+    if let Some(players) = &details.player_names {
+        println!("Players:         {}", players);
+    }
+    if let Some(run_time) = details.run_time_secs {
+        println!("Time:            {}", format_run_time(run_time));
+    }
+    if let Some(place) = details.leaderboard_place {
+        println!("Leaderboard:     #{}", place);
+    }
+    if let Some(platform) = &details.platform {
+        println!("Platform:        {}", platform);
+    }
+    for (variable, value) in &details.variable_values {
+        println!("  {:<15}{}", variable, value);
+    }
+}
+
+fn print_review(review: &Review) {
+    println!();
+    println!("Review");
+    println!("------");
+    println!("Decision:        {}", review.decision.as_str());
+    println!("Reviewer:        {}", review.reviewer);
+    if let Some(note) = &review.note {
+        println!("Note:            {}", note);
+    }
+    println!(
+        "Reviewed:        {}",
+        review.reviewed_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+}
+
+fn print_checkpoint(checkpoint: &Checkpoint) {
+    println!();
+    println!("Checkpoint");
+    println!("----------");
+    println!("Save:            {}", checkpoint.save_path);
+    if let Some(tick) = checkpoint.tick {
+        println!("Tick:            {}", tick);
+    }
+    println!(
+        "Kept:            {}",
+        checkpoint.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+}
+
+fn print_replay_result(result: &ReplayResult) {
+    println!();
+    println!("Replay Result");
+    println!("-------------");
+    println!("Highest Level:   {}", result.max_msg_level);
+    println!("Exit Tick:       {}", result.exit_tick);
+    println!(
+        "Win Condition:   {}",
+        if result.win_condition_completed {
+            "completed"
+        } else {
+            "not completed"
+        }
+    );
+    for (rule, count) in &result.rule_counts {
+        println!("  {:<15}{}", rule, count);
+    }
+    if let Some(log_path) = &result.log_path {
+        println!("Log:             {}", log_path);
+    }
+    if let Some(report_path) = &result.report_path {
+        println!("Report:          {}", report_path);
+    }
+    println!(
+        "Completed:       {}",
+        result.completed_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    if let Some(excerpt) = &result.log_excerpt {
+        println!();
+        println!("Log Excerpt:");
+        println!("{}", excerpt);
+    }
+}
@@ -1,10 +1,13 @@
 use anyhow::Result;
 use clap::Args;
+use serde::Serialize;
+use std::collections::BTreeMap;
 
 use crate::daemon::database::connection::Database;
 use crate::daemon::database::types::RunStatus;
+use crate::output::{OutputFormat, print_json};
 
-use super::common::RunFilterArgs;
+use super::common::{RunFilterArgs, format_status};
 
 #[derive(Args)]
 pub struct StatsArgs {
@@ -12,10 +15,21 @@ pub struct StatsArgs {
     pub filter: RunFilterArgs,
 }
 
-pub async fn handle_stats(db: &Database, args: StatsArgs) -> Result<()> {
+#[derive(Serialize)]
+struct StatsOutput {
+    total: usize,
+    by_status: BTreeMap<String, i64>,
+    average_retries: f64,
+    max_retries: u32,
+    error_classes: BTreeMap<String, usize>,
+    reclaimed_bytes: u64,
+}
+
+pub async fn handle_stats(db: &Database, args: StatsArgs, format: OutputFormat) -> Result<()> {
     let filter = args.filter.to_filter()?;
     let all_runs = db.query_runs(filter).await?;
     let counts = db.count_runs_by_status().await?;
+    let reclaimed_bytes = db.total_reclaimed_bytes().await?;
 
     let total = all_runs.len();
     let discovered = counts.get(&RunStatus::Discovered).unwrap_or(&0);
@@ -41,6 +55,20 @@ pub async fn handle_stats(db: &Database, args: StatsArgs) -> Result<()> {
             acc
         });
 
+    if format.is_json() {
+        return print_json(&StatsOutput {
+            total,
+            by_status: counts
+                .iter()
+                .map(|(status, count)| (format_status(status), *count))
+                .collect(),
+            average_retries: avg_retries,
+            max_retries: *max_retries,
+            error_classes: error_counts.into_iter().collect(),
+            reclaimed_bytes,
+        });
+    }
+
     println!("Run Statistics");
     println!("==============");
     println!();
@@ -57,6 +85,11 @@ pub async fn handle_stats(db: &Database, args: StatsArgs) -> Result<()> {
     println!("Retry Statistics:");
     println!("  Average:       {:.2}", avg_retries);
     println!("  Maximum:       {}", max_retries);
+    println!();
+    println!(
+        "Artifacts Reclaimed: {} MB",
+        reclaimed_bytes / (1024 * 1024)
+    );
 
     if !error_counts.is_empty() {
         println!();
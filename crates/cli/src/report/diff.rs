@@ -0,0 +1,371 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use replay_script::log_parser::{ReplayEvent, ReplayLogParser};
+use replay_script::{MsgLevel, ReplayMsg};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::daemon::database::connection::Database;
+use crate::output::{OutputFormat, print_json};
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Run ID, report.json, replay log, or the output directory of a run
+    a: String,
+
+    /// Run ID, report.json, replay log, or the output directory of a run, compared to the
+    /// first
+    b: String,
+
+    /// Also show the messages that are the same in both
+    #[arg(long)]
+    all: bool,
+
+    /// SQLite database file path, to look up run IDs
+    #[arg(long, default_value = "run_verification.db")]
+    database: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Change {
+    Same,
+    /// Only in the first verification
+    Removed,
+    /// Only in the second verification
+    Added,
+    /// Logged by the same rule at the same tick, with a different level or message
+    Changed,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct DiffEntry<'a> {
+    tick: u64,
+    rule: Option<&'a str>,
+    change: Change,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    a: Option<&'a ReplayMsg>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    b: Option<&'a ReplayMsg>,
+}
+
+#[derive(Serialize)]
+struct DiffOutput<'a> {
+    a: &'a str,
+    b: &'a str,
+    entries: Vec<&'a DiffEntry<'a>>,
+    /// Messages per rule in each verification, for the rules where they differ.
+    rule_counts: BTreeMap<&'a str, (usize, usize)>,
+    counts: BTreeMap<&'static str, usize>,
+}
+
+pub async fn handle_diff(args: DiffArgs, format: OutputFormat) -> Result<()> {
+    let a = load_messages(&args.a, &args.database).await?;
+    let b = load_messages(&args.b, &args.database).await?;
+    let entries = diff_messages(&a, &b);
+    let shown: Vec<&DiffEntry> = entries
+        .iter()
+        .filter(|entry| args.all || entry.change != Change::Same)
+        .collect();
+    let rule_counts = rule_count_changes(&a, &b);
+    let counts = BTreeMap::from(
+        [
+            ("same", Change::Same),
+            ("changed", Change::Changed),
+            ("removed", Change::Removed),
+            ("added", Change::Added),
+        ]
+        .map(|(name, change)| {
+            let count = entries.iter().filter(|e| e.change == change).count();
+            (name, count)
+        }),
+    );
+
+    if format.is_json() {
+        return print_json(&DiffOutput {
+            a: &args.a,
+            b: &args.b,
+            entries: shown,
+            rule_counts,
+            counts,
+        });
+    }
+
+    println!("--- {}", args.a);
+    println!("+++ {}", args.b);
+    for entry in shown {
+        let rule = entry.rule.unwrap_or("-");
+        let line = |sign: char, msg: &ReplayMsg| {
+            println!(
+                "{} {:>10} {:<28} [{}] {}",
+                sign, entry.tick, rule, msg.level, msg.message
+            )
+        };
+        match (entry.a, entry.b) {
+            (Some(a), Some(_)) if entry.change == Change::Same => line(' ', a),
+            (Some(a), Some(b)) => {
+                line('-', a);
+                line('+', b);
+            }
+            (Some(a), None) => line('-', a),
+            (None, Some(b)) => line('+', b),
+            (None, None) => {}
+        }
+    }
+    if !rule_counts.is_empty() {
+        println!("\nMessages per rule:");
+        for (rule, (a, b)) in &rule_counts {
+            println!("  {:<28} {} -> {}", rule, a, b);
+        }
+    }
+    println!(
+        "\n{} same, {} changed, {} only in the first, {} only in the second",
+        counts["same"], counts["changed"], counts["removed"], counts["added"]
+    );
+    Ok(())
+}
+
+/// The replay messages of a run, report or log.
+async fn load_messages(source: &str, database: &Path) -> Result<Vec<ReplayMsg>> {
+    let path = Path::new(source);
+    if path.is_dir() {
+        return read_messages(&path.join("report.json"));
+    }
+    if path.is_file() {
+        return read_messages(path);
+    }
+    anyhow::ensure!(
+        database.exists(),
+        "{} is not a file, and there is no database to look it up as a run ID",
+        source
+    );
+    let db = Database::new(database).await?;
+    let result = db
+        .get_replay_result(source)
+        .await?
+        .with_context(|| format!("No replay result for run {}", source))?;
+    let path = result
+        .report_path
+        .filter(|path| Path::new(path).exists())
+        .or(result.log_path)
+        .with_context(|| format!("No report or log kept for run {}", source))?;
+    read_messages(Path::new(&path))
+}
+
+/// Reads the findings of a report.json, or the messages of a replay log or Factorio's
+/// output. Logs don't have the rules of the messages.
+fn read_messages(path: &Path) -> Result<Vec<ReplayMsg>> {
+    #[derive(Deserialize)]
+    struct ReportFindings {
+        findings: Vec<ReplayMsg>,
+    }
+
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        let report: ReportFindings = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse report {}", path.display()))?;
+        return Ok(report.findings);
+    }
+    let mut parser = ReplayLogParser::new();
+    Ok(contents
+        .lines()
+        .filter_map(|line| match parser.feed(line) {
+            Some(ReplayEvent::Message(msg)) => Some(msg),
+            Some(ReplayEvent::Exit(_)) => None,
+            None => parse_log_line(line),
+        })
+        .collect())
+}
+
+/// Parses a message as written to a replay log: `[Level]\ttick\tmessage`.
+fn parse_log_line(line: &str) -> Option<ReplayMsg> {
+    let mut parts = line.splitn(3, '\t');
+    let level = parts.next()?.strip_prefix('[')?.strip_suffix(']')?;
+    Some(ReplayMsg {
+        level: MsgLevel::from_str(level.trim()).ok()?,
+        time: parts.next()?.trim().parse().ok()?,
+        rule: None,
+        message: parts.next()?.to_string(),
+    })
+}
+
+/// Pairs up the messages of `a` and `b` logged by the same rule at the same tick, in tick
+/// order. Messages that are the same in both are matched first, then the rest in order.
+fn diff_messages<'a>(a: &'a [ReplayMsg], b: &'a [ReplayMsg]) -> Vec<DiffEntry<'a>> {
+    type Key<'a> = (u64, Option<&'a str>);
+    fn group(messages: &[ReplayMsg]) -> BTreeMap<Key<'_>, Vec<&ReplayMsg>> {
+        let mut groups: BTreeMap<Key, Vec<&ReplayMsg>> = BTreeMap::new();
+        for msg in messages {
+            groups
+                .entry((msg.time, msg.rule.as_deref()))
+                .or_default()
+                .push(msg);
+        }
+        groups
+    }
+    let mut a_groups = group(a);
+    let mut b_groups = group(b);
+    let keys: BTreeSet<Key> = a_groups.keys().chain(b_groups.keys()).copied().collect();
+
+    let mut entries = Vec::new();
+    for key @ (tick, rule) in keys {
+        let a_msgs = a_groups.remove(&key).unwrap_or_default();
+        let mut b_msgs = b_groups.remove(&key).unwrap_or_default();
+        let entry = |change, a, b| DiffEntry {
+            tick,
+            rule,
+            change,
+            a,
+            b,
+        };
+        let mut a_rest = Vec::new();
+        for a_msg in a_msgs {
+            match b_msgs.iter().position(|b_msg| *b_msg == a_msg) {
+                Some(index) => {
+                    let b_msg = b_msgs.remove(index);
+                    entries.push(entry(Change::Same, Some(a_msg), Some(b_msg)));
+                }
+                None => a_rest.push(a_msg),
+            }
+        }
+        let mut b_rest = b_msgs.into_iter();
+        for a_msg in a_rest {
+            match b_rest.next() {
+                Some(b_msg) => entries.push(entry(Change::Changed, Some(a_msg), Some(b_msg))),
+                None => entries.push(entry(Change::Removed, Some(a_msg), None)),
+            }
+        }
+        entries.extend(b_rest.map(|b_msg| entry(Change::Added, None, Some(b_msg))));
+    }
+    entries
+}
+
+/// Messages per rule in `a` and `b`, for the rules where they differ.
+fn rule_count_changes<'a>(
+    a: &'a [ReplayMsg],
+    b: &'a [ReplayMsg],
+) -> BTreeMap<&'a str, (usize, usize)> {
+    let mut counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for msg in a {
+        if let Some(rule) = &msg.rule {
+            counts.entry(rule).or_default().0 += 1;
+        }
+    }
+    for msg in b {
+        if let Some(rule) = &msg.rule {
+            counts.entry(rule).or_default().1 += 1;
+        }
+    }
+    counts.retain(|_, (a, b)| a != b);
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(time: u64, level: MsgLevel, rule: &str, message: &str) -> ReplayMsg {
+        ReplayMsg {
+            time,
+            level,
+            rule: Some(rule.to_string()),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_diff_messages() {
+        let a = [
+            msg(60, MsgLevel::Info, "log_time", "Time: 0:00:01"),
+            msg(120, MsgLevel::Warn, "max_apm", "APM too high"),
+            msg(
+                120,
+                MsgLevel::Error,
+                "no_map_editor",
+                "player used map editor!",
+            ),
+            msg(
+                300,
+                MsgLevel::Error,
+                "no_map_editor",
+                "player used map editor!",
+            ),
+        ];
+        let b = [
+            msg(60, MsgLevel::Info, "log_time", "Time: 0:00:01"),
+            msg(120, MsgLevel::Error, "max_apm", "APM too high"),
+            msg(
+                120,
+                MsgLevel::Error,
+                "no_map_editor",
+                "player used map editor!",
+            ),
+            msg(200, MsgLevel::Warn, "max_players", "Too many players"),
+        ];
+        let entries = diff_messages(&a, &b);
+        let changes: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.tick, entry.rule.unwrap(), entry.change))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (60, "log_time", Change::Same),
+                (120, "max_apm", Change::Changed),
+                (120, "no_map_editor", Change::Same),
+                (200, "max_players", Change::Added),
+                (300, "no_map_editor", Change::Removed),
+            ]
+        );
+        assert_eq!(entries[1].a.unwrap().level, MsgLevel::Warn);
+        assert_eq!(entries[1].b.unwrap().level, MsgLevel::Error);
+
+        assert_eq!(
+            rule_count_changes(&a, &b),
+            BTreeMap::from([("max_players", (0, 1)), ("no_map_editor", (2, 1))])
+        );
+    }
+
+    #[test]
+    fn test_read_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = msg(
+            120,
+            MsgLevel::Error,
+            "no_map_editor",
+            "player used map editor!",
+        );
+        let second = msg(180, MsgLevel::Warn, "max_apm", "APM too high");
+
+        let report_path = dir.path().join("report.json");
+        let report = serde_json::json!({ "findings": [first, second], "final_tick": 240 });
+        std::fs::write(&report_path, report.to_string()).unwrap();
+        assert_eq!(
+            read_messages(&report_path).unwrap(),
+            [first.clone(), second.clone()]
+        );
+
+        // replay logs leave out the rules
+        let log_path = dir.path().join("output.log");
+        let exit = "Replay exited successfully at tick 240: Rocket launched!";
+        std::fs::write(&log_path, format!("{first}\n{second}\n{exit}\n")).unwrap();
+        let messages = read_messages(&log_path).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].time, 120);
+        assert_eq!(messages[0].level, MsgLevel::Error);
+        assert_eq!(messages[0].rule, None);
+        assert_eq!(messages[1].message, "APM too high");
+
+        let output_path = dir.path().join("factorio-current.log");
+        std::fs::write(
+            &output_path,
+            "   0.500 Info AppManager.cpp:123: Loading map\n\
+             REPLAY_SCRIPT_EVENT:\t120\tError\tno_map_editor\tplayer used map editor!\n",
+        )
+        .unwrap();
+        assert_eq!(read_messages(&output_path).unwrap(), [first]);
+    }
+}
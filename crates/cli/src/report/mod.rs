@@ -0,0 +1,26 @@
+use anyhow::Result;
+use clap::{Args, Subcommand};
+
+use crate::output::OutputFormat;
+
+mod diff;
+
+pub use diff::DiffArgs;
+
+#[derive(Args)]
+pub struct ReportArgs {
+    #[command(subcommand)]
+    pub subcommand: ReportSubcommand,
+}
+
+#[derive(Subcommand)]
+pub enum ReportSubcommand {
+    /// Compare the replay messages of two verifications, by tick and rule
+    Diff(DiffArgs),
+}
+
+pub async fn handle_report_command(args: ReportArgs, format: OutputFormat) -> Result<()> {
+    match args.subcommand {
+        ReportSubcommand::Diff(diff_args) => diff::handle_diff(diff_args, format).await,
+    }
+}
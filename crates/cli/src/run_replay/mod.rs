@@ -0,0 +1,838 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::Duration;
+use std::{fs::File, io::Write, path::Path};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use factorio_manager::error::FactorioError;
+use factorio_manager::factorio_instance::{FactorioInstance, FactorioProcess};
+use factorio_manager::save_analysis::{SaveExpectations, analyze_save};
+use factorio_manager::save_file::SaveFile;
+use factorio_manager::{
+    expected_mods::{ExpectedMods, ModPolicy, check_expected_mods, cross_check_save_mods},
+    factorio_install_dir::{FactorioInstallDir, VersionStr},
+    save_file::WrittenSaveFile,
+};
+use futures::{AsyncBufReadExt, Stream, StreamExt};
+use log::{debug, info, warn};
+use replay_script::log_parser::{ReplayEvent, ReplayLogParser};
+use replay_script::{ExitSignal, MsgLevel, ProgressSnapshot, ReplayMsg, ReplayScripts};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tokio::time::{Instant, sleep};
+use zip_downloader::disk_space::ensure_free_space;
+
+use crate::config::RunRules;
+
+pub mod plugin;
+pub mod report;
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ReplayReport {
+    pub max_msg_level: MsgLevel,
+    pub win_condition_not_completed: bool,
+    /// The replay was stopped at the end of the verification window (`stop_at_tick`), so
+    /// the rest of the run, including any win condition, wasn't checked.
+    pub partial_verification: bool,
+    /// Warning and error messages, in order.
+    pub messages: Vec<String>,
+    pub timeline: Vec<ProgressSnapshot>,
+    /// Every message logged by the replay scripts.
+    pub findings: Vec<ReplayMsg>,
+    /// Number of findings per rule.
+    pub rule_counts: BTreeMap<String, usize>,
+    pub exit: Option<ExitSignal>,
+    /// Last game tick reported by the replay scripts.
+    pub final_tick: u64,
+    /// Wall-clock time spent replaying.
+    pub duration_secs: f64,
+    /// Average updates per second while replaying.
+    pub ups: Option<f64>,
+    /// Version of Factorio the save was made with.
+    pub factorio_version: Option<VersionStr>,
+    /// Version of Factorio the save was replayed on, which the install version policy may
+    /// pick newer than the save's.
+    pub install_version: Option<VersionStr>,
+    /// Reports of each save of a segmented run, which this report combines.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<ReplayReport>,
+    #[serde(skip)]
+    pub log_path: Option<PathBuf>,
+}
+
+/// The parts of a [`ReplayReport`] kept in the database.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReportSummary {
+    pub max_msg_level: MsgLevel,
+    pub win_condition_not_completed: bool,
+    #[serde(default)]
+    pub partial_verification: bool,
+    pub finding_count: usize,
+    pub rule_counts: BTreeMap<String, usize>,
+    pub exit_message: Option<String>,
+    pub final_tick: u64,
+    pub duration_secs: f64,
+    #[serde(default)]
+    pub ups: Option<f64>,
+}
+
+impl ReplayReport {
+    pub fn summary(&self) -> ReportSummary {
+        ReportSummary {
+            max_msg_level: self.max_msg_level,
+            win_condition_not_completed: self.win_condition_not_completed,
+            partial_verification: self.partial_verification,
+            finding_count: self.findings.len(),
+            rule_counts: self.rule_counts.clone(),
+            exit_message: self.exit.as_ref().map(|exit| exit.message.clone()),
+            final_tick: self.final_tick,
+            duration_secs: self.duration_secs,
+            ups: self.ups,
+        }
+    }
+
+    /// Whether the run passes without review.
+    pub fn passed(&self) -> bool {
+        !self.win_condition_not_completed && self.verdict_level() <= MsgLevel::Info
+    }
+
+    /// The message level the verdict goes by: the highest one logged, and at least
+    /// [`MsgLevel::Warn`] for a partial verification, which a moderator has to look at.
+    pub fn verdict_level(&self) -> MsgLevel {
+        if self.partial_verification {
+            self.max_msg_level.max(MsgLevel::Warn)
+        } else {
+            self.max_msg_level
+        }
+    }
+
+    pub fn write_json(&self, path: &Path) -> std::io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
+
+impl ReplayReport {
+    /// Combines the reports of consecutive segments into one for the whole run. The run
+    /// ends where the last segment does.
+    pub fn from_segments(segments: Vec<ReplayReport>) -> Self {
+        let messages = segments
+            .iter()
+            .enumerate()
+            .flat_map(|(i, segment)| {
+                segment
+                    .messages
+                    .iter()
+                    .map(move |message| format!("Segment {}: {}", i + 1, message))
+            })
+            .collect();
+        let findings: Vec<ReplayMsg> = segments
+            .iter()
+            .flat_map(|segment| segment.findings.iter().cloned())
+            .collect();
+        let duration_secs = segments.iter().map(|segment| segment.duration_secs).sum();
+        let replayed_ticks: f64 = segments
+            .iter()
+            .filter_map(|segment| Some(segment.ups? * segment.duration_secs))
+            .sum();
+        let last = segments.last();
+        ReplayReport {
+            max_msg_level: segments
+                .iter()
+                .map(|segment| segment.max_msg_level)
+                .max()
+                .unwrap_or_default(),
+            win_condition_not_completed: last
+                .is_some_and(|segment| segment.win_condition_not_completed),
+            partial_verification: segments.iter().any(|segment| segment.partial_verification),
+            messages,
+            timeline: segments
+                .iter()
+                .flat_map(|segment| segment.timeline.iter().cloned())
+                .collect(),
+            rule_counts: count_by_rule(&findings),
+            findings,
+            exit: last.and_then(|segment| segment.exit.clone()),
+            final_tick: last.map_or(0, |segment| segment.final_tick),
+            duration_secs,
+            ups: (replayed_ticks > 0.0).then(|| replayed_ticks / duration_secs),
+            factorio_version: last.and_then(|segment| segment.factorio_version),
+            install_version: last.and_then(|segment| segment.install_version),
+            log_path: last.and_then(|segment| segment.log_path.clone()),
+            segments,
+        }
+    }
+}
+
+/// Whether the rules have a win condition that a replay ending with `exit` didn't reach. A
+/// replay stopped at `stop_at_tick` ends on purpose before the run is won, so it isn't
+/// failed for it; it's a partial verification instead.
+fn win_condition_not_completed(rules: &RunRules, exit: Option<&ExitSignal>) -> bool {
+    rules.replay_scripts.has_win_condition() && exit.is_none()
+}
+
+fn count_by_rule(findings: &[ReplayMsg]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for msg in findings {
+        let rule = msg.rule.as_deref().unwrap_or("unknown");
+        *counts.entry(rule.to_string()).or_default() += 1;
+    }
+    counts
+}
+
+/// Path of the JSON report written next to a replay log.
+pub fn report_json_path(log_path: &Path) -> PathBuf {
+    log_path.with_file_name("report.json")
+}
+
+/// Path of the checkpoint kept next to a replay log; see [`RunRules::checkpoint_interval_minutes`].
+pub fn checkpoint_path(log_path: &Path) -> PathBuf {
+    log_path.with_file_name("checkpoint.zip")
+}
+
+/// The last `lines` lines of a log file, if it can be read.
+pub fn log_tail(log_path: &Path, lines: usize) -> Option<String> {
+    let contents = std::fs::read_to_string(log_path).ok()?;
+    let all: Vec<&str> = contents.lines().collect();
+    Some(all[all.len().saturating_sub(lines)..].join("\n"))
+}
+
+#[derive(Default)]
+pub struct ReplayOptions<'a> {
+    /// Save files modified after this date are flagged.
+    pub submitted_date: Option<DateTime<Utc>>,
+    /// Also keeps all of Factorio's output here, zstd-compressed.
+    pub full_log_path: Option<&'a Path>,
+    /// Sent the latest tick the replay scripts reported, as the replay runs.
+    pub progress: Option<&'a watch::Sender<u64>>,
+}
+
+pub async fn run_replay(
+    install_dir: &FactorioInstallDir,
+    save: &mut WrittenSaveFile,
+    rules: &RunRules,
+    expected_mods: &ExpectedMods,
+    log_path: &Path,
+    options: ReplayOptions<'_>,
+) -> Result<ReplayReport, FactorioError> {
+    let expectations = SaveExpectations {
+        submitted_date: options.submitted_date,
+        allow_custom_control_lua: rules.allow_custom_control_lua,
+    };
+    let mut pre_run_findings = analyze_save(save, &expectations)?
+        .into_iter()
+        .map(|anomaly| {
+            let level = if anomaly.is_error() {
+                MsgLevel::Error
+            } else {
+                MsgLevel::Warn
+            };
+            pre_run_msg(SAVE_ANALYSIS_RULE, level, anomaly)
+        })
+        .collect::<Vec<_>>();
+
+    let WrittenSaveFile(save_path, save_file) = save;
+    let version = save_file.get_factorio_version()?;
+    info!(
+        "=== Running replay ===\nSave file: {}\nSave version: {}",
+        save_path.display(),
+        version
+    );
+    // the scripts run on the Factorio the policy picks, which may be newer than the save's
+    let install_version = install_dir
+        .version_for_save(version, rules.install_version)
+        .await?;
+    let replay_scripts = rules
+        .replay_scripts
+        .for_version((install_version.0, install_version.1, install_version.2))
+        .map_err(|e| FactorioError::UnsupportedScripts {
+            version: install_version,
+            scripts: e.scripts.iter().map(|script| script.to_string()).collect(),
+        })?;
+
+    let mut instance = install_dir
+        .get_or_download_factorio(install_version)
+        .await?
+        .with_limits(rules.resource_limits.clone());
+    pre_run_findings.extend(
+        do_pre_run_checks(
+            &mut instance,
+            save_path,
+            save_file,
+            expected_mods,
+            &rules.mod_policy,
+        )
+        .await?,
+    );
+    let installed_save_path =
+        install_replay_script(save_path, save_file, &replay_scripts, rules).await?;
+    run_and_log_replay(
+        &instance,
+        install_version,
+        &installed_save_path,
+        save_file,
+        log_path,
+        &options,
+        rules,
+        pre_run_findings,
+    )
+    .await
+}
+
+/// Rule names of findings from checks done before the replay.
+const EXPECTED_MODS_RULE: &str = "expected_mods";
+const SAVE_ANALYSIS_RULE: &str = "save_analysis";
+
+fn pre_run_msg(rule: &str, level: MsgLevel, message: impl std::fmt::Display) -> ReplayMsg {
+    ReplayMsg {
+        time: 0,
+        level,
+        rule: Some(rule.to_string()),
+        message: message.to_string(),
+    }
+}
+
+/// Checks the save's mods, returning findings to include in the report.
+async fn do_pre_run_checks(
+    instance: &mut FactorioInstance,
+    save_path: &Path,
+    save_file: &mut SaveFile<File>,
+    expected_mods: &ExpectedMods,
+    mod_policy: &ModPolicy,
+) -> Result<Vec<ReplayMsg>, FactorioError> {
+    info!("Doing pre-run checks");
+    let actual_mods = instance.get_mod_versions(save_path).await?;
+    let mut findings: Vec<ReplayMsg> =
+        check_expected_mods(expected_mods, mod_policy, &actual_mods)?
+            .into_iter()
+            .map(|finding| {
+                let level = if finding.is_error() {
+                    MsgLevel::Error
+                } else {
+                    MsgLevel::Info
+                };
+                pre_run_msg(EXPECTED_MODS_RULE, level, finding)
+            })
+            .collect();
+    // the save's own mod list can be tampered with, so it only adds warnings
+    match save_file.get_mods() {
+        Ok(save_mods) => findings.extend(
+            cross_check_save_mods(&actual_mods, &save_mods)
+                .into_iter()
+                .map(|difference| pre_run_msg(EXPECTED_MODS_RULE, MsgLevel::Warn, difference)),
+        ),
+        Err(e) => warn!("Could not cross-check the save's mod list: {e}"),
+    }
+    debug!("Pre-run checks passed");
+    Ok(findings)
+}
+
+async fn install_replay_script(
+    save_path: &Path,
+    save_file: &mut SaveFile<File>,
+    replay_script: &ReplayScripts,
+    rules: &RunRules,
+) -> Result<PathBuf, FactorioError> {
+    info!("Installing replay script");
+    debug!("Enabled checks: {:?}", replay_script);
+    let installed_save_path = save_path.with_extension("installed.zip");
+    let save_size = std::fs::metadata(save_path)?.len();
+    let save_dir = save_path.parent().unwrap_or(Path::new("."));
+    let headroom = rules.replay_disk_headroom_mb.saturating_mul(1024 * 1024);
+    ensure_free_space::<FactorioError>(save_dir, save_size.saturating_add(headroom))?;
+    save_file.install_replay_script_to(
+        &mut File::create(&installed_save_path)?,
+        replay_script,
+        rules.script_injection,
+    )?;
+    Ok(installed_save_path)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_and_log_replay(
+    instance: &FactorioInstance,
+    install_version: VersionStr,
+    installed_save_path: &Path,
+    save_file: &mut SaveFile<File>,
+    log_path: &Path,
+    options: &ReplayOptions<'_>,
+    rules: &RunRules,
+    pre_run_findings: Vec<ReplayMsg>,
+) -> Result<ReplayReport, FactorioError> {
+    let mut result = run_and_log_replay_inner(
+        instance,
+        installed_save_path,
+        log_path,
+        options,
+        rules,
+        pre_run_findings,
+    )
+    .await;
+    copy_factorio_log(instance, log_path);
+    if rules.checkpoint_interval_minutes.is_some() {
+        keep_checkpoint(instance, log_path);
+    }
+    if let Ok(report) = &mut result {
+        report.factorio_version = save_file.get_factorio_version().ok();
+        report.install_version = Some(install_version);
+        plugin::registry().run(&rules.plugins, save_file, report);
+        let report_path = report_json_path(log_path);
+        match report.write_json(&report_path) {
+            Ok(()) => debug!("Wrote replay report to: {}", report_path.display()),
+            Err(e) => log::warn!("Failed to write replay report: {e}"),
+        }
+    }
+    result
+}
+
+async fn run_and_log_replay_inner(
+    instance: &FactorioInstance,
+    installed_save_path: &Path,
+    log_path: &Path,
+    options: &ReplayOptions<'_>,
+    rules: &RunRules,
+    pre_run_findings: Vec<ReplayMsg>,
+) -> Result<ReplayReport, FactorioError> {
+    info!("Starting replay. Log file at {}", log_path.display());
+    let mut log_file = File::create(log_path)?;
+    let start = Instant::now();
+    for msg in &pre_run_findings {
+        writeln!(log_file, "{}", msg)?;
+    }
+
+    // finished on drop, so the log is readable even if the replay fails
+    let mut full_log = options
+        .full_log_path
+        .map(|path| {
+            Ok::<_, FactorioError>(zstd::Encoder::new(File::create(path)?, 0)?.auto_finish())
+        })
+        .transpose()?;
+
+    // Phase 1: replay
+    instance.set_autosave_interval(rules.checkpoint_interval_minutes)?;
+    // a checkpoint always belongs to the latest replay
+    let checkpoint = checkpoint_path(log_path);
+    if checkpoint.exists() {
+        std::fs::remove_file(checkpoint)?;
+    }
+    let mut process = instance.spawn_replay(installed_save_path, &rules.factorio_args)?;
+    let output = record_output(
+        &mut process,
+        &mut log_file,
+        full_log.as_mut().map(|log| log as &mut (dyn Write + Send)),
+        rules,
+        options.progress,
+    )
+    .await?;
+    // the last tick the scripts reported; close enough to the end with log_time enabled
+    let replay_ticks = output.last_tick;
+    let replay_secs = start.elapsed().as_secs_f64();
+    let ups = (replay_ticks > 0 && replay_secs > 0.0).then(|| replay_ticks as f64 / replay_secs);
+    if let Some(ups) = ups {
+        info!("Replayed {} ticks at {:.0} UPS", replay_ticks, ups);
+    }
+
+    process.terminate();
+    let exit_status = match tokio::time::timeout(Duration::from_secs(5), process.wait()).await {
+        Ok(result) => result?,
+        Err(_) => {
+            process.kill();
+            process.wait().await?
+        }
+    };
+    if !exit_status.success() && output.exit.is_none() {
+        let detail = extract_error_from_log(&instance.log_file_path());
+        return Err(FactorioError::ProcessExitedUnsuccessfully {
+            exit_code: exit_status.code(),
+            detail,
+        });
+    }
+
+    // Phase 2: run --benchmark 1 tick on the post-replay save to trigger on_load,
+    // which fires afterReplay callbacks (on_init only runs during --run-replay).
+    let mut bench_process = instance.spawn_benchmark(installed_save_path, 1)?;
+    let bench_output = record_output(
+        &mut bench_process,
+        &mut log_file,
+        full_log.as_mut().map(|log| log as &mut (dyn Write + Send)),
+        rules,
+        None,
+    )
+    .await?;
+    terminate_and_wait(&mut bench_process).await;
+
+    let win_condition_not_completed = win_condition_not_completed(rules, output.exit.as_ref());
+    let partial_verification = output.exit.as_ref().is_some_and(ExitSignal::is_window_end);
+
+    let mut timeline = output.timeline;
+    timeline.extend(bench_output.timeline);
+    let mut pre_run = Findings::default();
+    for msg in pre_run_findings {
+        pre_run.add(msg, rules);
+    }
+    let max_msg_level = pre_run
+        .max_level
+        .max(output.findings.max_level)
+        .max(bench_output.findings.max_level);
+    let mut messages = pre_run.messages;
+    messages.extend(output.findings.messages);
+    messages.extend(bench_output.findings.messages);
+    let mut findings = pre_run.findings;
+    findings.extend(output.findings.findings);
+    findings.extend(bench_output.findings.findings);
+    let exit = output.exit;
+    let final_tick = findings
+        .iter()
+        .map(|msg| msg.time)
+        .chain([output.last_tick, bench_output.last_tick])
+        .max()
+        .unwrap_or(0);
+
+    if win_condition_not_completed {
+        let msg = if rules.replay_scripts.win_on_scenario_finished {
+            "win_on_scenario_finished enabled but scenario never completed"
+        } else {
+            "win condition enabled but never reached"
+        };
+        messages.push(msg.to_string());
+        writeln!(log_file, "VERIFICATION FAILED: {msg}")?;
+    }
+    if partial_verification {
+        let msg = format!(
+            "partial verification: stopped at tick {} before the end of the run",
+            final_tick
+        );
+        writeln!(log_file, "NEEDS REVIEW: {msg}")?;
+        messages.push(msg);
+    }
+
+    Ok(ReplayReport {
+        max_msg_level,
+        win_condition_not_completed,
+        partial_verification,
+        messages,
+        timeline,
+        rule_counts: count_by_rule(&findings),
+        findings,
+        exit,
+        final_tick,
+        duration_secs: start.elapsed().as_secs_f64(),
+        ups,
+        factorio_version: None,
+        install_version: None,
+        segments: Vec::new(),
+        log_path: Some(log_path.to_path_buf()),
+    })
+}
+
+async fn terminate_and_wait(process: &mut FactorioProcess) {
+    process.terminate();
+    if tokio::time::timeout(Duration::from_secs(5), process.wait())
+        .await
+        .is_err()
+    {
+        process.kill();
+        process.wait().await.ok();
+    }
+}
+
+fn extract_error_from_log(log_path: &Path) -> Option<String> {
+    use regex::Regex;
+    use std::sync::LazyLock;
+    // Factorio log: "   0.760 Error RunReplay.cpp:27: Error loading replay: ..."
+    static RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^\s*[\d.]+ Error \S+: (.+)").unwrap());
+
+    let content = std::fs::read_to_string(log_path).ok()?;
+    content
+        .lines()
+        .rev()
+        .find_map(|line| RE.captures(line).map(|c| c[1].to_string()))
+}
+
+fn keep_checkpoint(instance: &FactorioInstance, log_path: &Path) {
+    let dest_path = checkpoint_path(log_path);
+    match instance.take_latest_autosave(&dest_path) {
+        Ok(true) => info!("Kept checkpoint at: {}", dest_path.display()),
+        Ok(false) => debug!("Factorio didn't autosave during the replay"),
+        Err(e) => log::warn!("Failed to keep checkpoint: {e}"),
+    }
+}
+
+fn copy_factorio_log(instance: &FactorioInstance, log_path: &Path) {
+    let factorio_log = instance.log_file_path();
+    if !factorio_log.exists() {
+        return;
+    }
+    let dest_path = log_path.parent().unwrap().join("factorio-current.log");
+    match std::fs::copy(&factorio_log, &dest_path) {
+        Ok(_) => debug!("Copied factorio log to: {}", dest_path.display()),
+        Err(e) => log::warn!("Failed to copy factorio log: {e}"),
+    }
+}
+
+/// Findings of a replay. The verdict comes from all of them; the report only lists those
+/// [`RunRules::is_reported`] keeps.
+#[derive(Default)]
+struct Findings {
+    max_level: MsgLevel,
+    messages: Vec<String>,
+    findings: Vec<ReplayMsg>,
+}
+
+impl Findings {
+    fn add(&mut self, msg: ReplayMsg, rules: &RunRules) {
+        self.max_level = self.max_level.max(msg.level);
+        if !rules.is_reported(&msg) {
+            return;
+        }
+        if msg.level >= MsgLevel::Warn {
+            self.messages.push(msg.message.clone());
+        }
+        self.findings.push(msg);
+    }
+}
+
+/// returns when stdout closes.
+struct RecordOutputResult {
+    findings: Findings,
+    exit: Option<ExitSignal>,
+    /// Latest tick of any message, reported or not, or of the exit.
+    last_tick: u64,
+    timeline: Vec<ProgressSnapshot>,
+}
+
+async fn record_output(
+    process: &mut FactorioProcess,
+    log_file: &mut File,
+    mut full_log: Option<&mut (dyn Write + Send)>,
+    rules: &RunRules,
+    progress: Option<&watch::Sender<u64>>,
+) -> Result<RecordOutputResult, FactorioError> {
+    let mut lines = line_stream(process);
+    let mut parser = ReplayLogParser::new();
+
+    let mut timeline = Vec::new();
+    let mut findings = Findings::default();
+    let timeout_duration = Duration::from_secs(60);
+    let mut last_message_time = Instant::now();
+
+    loop {
+        let time_since_last_msg = last_message_time.elapsed();
+        let remaining_time = timeout_duration
+            .checked_sub(time_since_last_msg)
+            .unwrap_or(Duration::ZERO);
+
+        tokio::select! {
+            line = lines.next() => {
+                let Some(line) = line else { break };
+                if let Some(full_log) = full_log.as_mut() {
+                    writeln!(full_log, "{line}")?;
+                }
+                match parser.feed(&line) {
+                    Some(ReplayEvent::Message(msg)) => {
+                        log::debug!("{msg}");
+                        writeln!(log_file, "{}", msg)?;
+                        if let Some(snapshot) = ProgressSnapshot::from_msg(&msg) {
+                            timeline.push(snapshot);
+                        }
+                        // findings before the verification window are only logged
+                        if msg.time >= rules.verify_from_tick || msg.level == MsgLevel::Critical {
+                            findings.add(msg, rules);
+                        }
+                        if let Some(progress) = progress {
+                            progress.send_replace(parser.last_tick());
+                        }
+                        last_message_time = Instant::now();
+                    }
+                    Some(ReplayEvent::Exit(exit)) => {
+                        log::info!("{exit}");
+                        writeln!(log_file, "{}", exit)?;
+                        drop(lines);
+                        process.terminate();
+                        break;
+                    }
+                    None => log::debug!("{line}"),
+                }
+            }
+            _ = sleep(remaining_time), if remaining_time > Duration::ZERO => {
+                drop(lines);
+                process.terminate();
+                return Err(FactorioError::ReplayTimeout);
+            }
+        }
+    }
+
+    if parser.exit().is_some() {
+        info!("Replay finished");
+    }
+
+    Ok(RecordOutputResult {
+        findings,
+        exit: parser.exit().cloned(),
+        last_tick: parser.last_tick(),
+        timeline,
+    })
+}
+
+/// Every line of the process's output, until stdout closes.
+fn line_stream(process: &mut FactorioProcess) -> Pin<Box<dyn Stream<Item = String> + '_>> {
+    let mut reader = process.stdout_reader().unwrap();
+    Box::pin(async_stream::stream! {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match reader.read_line(&mut line).await {
+                Ok(0) => break,
+                Ok(_) => yield line.trim_end().to_string(),
+                Err(_) => continue,
+            }
+        };
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(time: u64, level: MsgLevel, rule: &str) -> ReplayMsg {
+        ReplayMsg {
+            time,
+            level,
+            rule: Some(rule.to_string()),
+            message: format!("{rule} at {time}"),
+        }
+    }
+
+    #[test]
+    fn test_from_segments() {
+        let first = ReplayReport {
+            max_msg_level: MsgLevel::Warn,
+            messages: vec!["too fast".to_string()],
+            findings: vec![msg(60, MsgLevel::Warn, "max_apm")],
+            final_tick: 108000,
+            duration_secs: 12.0,
+            log_path: Some("output.segment1.log".into()),
+            ..Default::default()
+        };
+        let last = ReplayReport {
+            max_msg_level: MsgLevel::Info,
+            win_condition_not_completed: true,
+            messages: vec!["win condition enabled but never reached".to_string()],
+            findings: vec![
+                msg(120, MsgLevel::Info, "log_time"),
+                msg(180, MsgLevel::Warn, "max_apm"),
+            ],
+            exit: Some(ExitSignal {
+                time: 216000,
+                message: "Rocket launched!".to_string(),
+            }),
+            final_tick: 216000,
+            duration_secs: 8.0,
+            log_path: Some("output.segment2.log".into()),
+            ..Default::default()
+        };
+
+        let report = ReplayReport::from_segments(vec![first, last]);
+        assert_eq!(report.max_msg_level, MsgLevel::Warn);
+        assert!(report.win_condition_not_completed);
+        assert_eq!(
+            report.messages,
+            [
+                "Segment 1: too fast",
+                "Segment 2: win condition enabled but never reached"
+            ]
+        );
+        assert_eq!(report.findings.len(), 3);
+        assert_eq!(report.rule_counts["max_apm"], 2);
+        assert_eq!(report.rule_counts["log_time"], 1);
+        assert_eq!(report.exit.unwrap().message, "Rocket launched!");
+        assert_eq!(report.final_tick, 216000);
+        assert_eq!(report.duration_secs, 20.0);
+        assert_eq!(report.log_path, Some("output.segment2.log".into()));
+        assert_eq!(report.segments.len(), 2);
+
+        // only the last segment decides whether the run was won
+        let won = ReplayReport::from_segments(vec![
+            ReplayReport {
+                win_condition_not_completed: true,
+                ..Default::default()
+            },
+            ReplayReport::default(),
+        ]);
+        assert!(!won.win_condition_not_completed);
+    }
+
+    #[test]
+    fn test_win_condition_not_completed() {
+        let rules: RunRules = serde_yaml::from_str("win_on_scenario_finished: true").unwrap();
+        let exit = |message: &str| ExitSignal {
+            time: 216000,
+            message: message.to_string(),
+        };
+        assert!(win_condition_not_completed(&rules, None));
+        assert!(!win_condition_not_completed(
+            &rules,
+            Some(&exit("Scenario finished"))
+        ));
+        assert!(!win_condition_not_completed(
+            &rules,
+            Some(&exit(replay_script::WINDOW_END_MESSAGE))
+        ));
+
+        let rules: RunRules = serde_yaml::from_str("{}").unwrap();
+        assert!(!win_condition_not_completed(&rules, None));
+    }
+
+    #[test]
+    fn test_partial_verification_needs_review() {
+        let report = ReplayReport {
+            max_msg_level: MsgLevel::Info,
+            partial_verification: true,
+            ..Default::default()
+        };
+        assert!(!report.passed());
+        assert_eq!(report.verdict_level(), MsgLevel::Warn);
+
+        let failed = ReplayReport {
+            max_msg_level: MsgLevel::Error,
+            ..report.clone()
+        };
+        assert_eq!(failed.verdict_level(), MsgLevel::Error);
+
+        let segmented = ReplayReport::from_segments(vec![report, ReplayReport::default()]);
+        assert!(segmented.partial_verification);
+        assert!(!segmented.passed());
+        assert_eq!(segmented.verdict_level(), MsgLevel::Warn);
+    }
+
+    #[test]
+    fn test_unreported_findings_still_decide_verdict() {
+        let rules: RunRules = serde_yaml::from_str(
+            "{ min_report_level: Critical, rule_report_levels: { log_all_commands: Warn } }",
+        )
+        .unwrap();
+        let mut findings = Findings::default();
+        findings.add(msg(10, MsgLevel::Error, "no_map_editor"), &rules);
+        findings.add(msg(20, MsgLevel::Info, "log_all_commands"), &rules);
+        findings.add(msg(30, MsgLevel::Warn, "log_all_commands"), &rules);
+
+        assert_eq!(findings.max_level, MsgLevel::Error);
+        assert_eq!(findings.messages, vec!["log_all_commands at 30"]);
+        assert_eq!(
+            findings.findings,
+            vec![msg(30, MsgLevel::Warn, "log_all_commands")]
+        );
+
+        let report = ReplayReport {
+            max_msg_level: findings.max_level,
+            findings: findings.findings,
+            ..Default::default()
+        };
+        assert!(!report.passed());
+        assert_eq!(report.verdict_level(), MsgLevel::Error);
+    }
+}
@@ -0,0 +1,178 @@
+//! Verification checks compiled into the runner, for game-specific rules that don't belong
+//! in the replay scripts. Categories enable them by name in their run rules' `plugins`.
+
+use std::fs::File;
+use std::sync::{Arc, LazyLock};
+
+use factorio_manager::save_file::SaveFile;
+use log::{info, warn};
+use replay_script::{MsgLevel, ReplayMsg};
+
+use super::{ReplayReport, count_by_rule};
+
+/// A check run after each replay, which can add findings to its report.
+pub trait VerificationPlugin: Send + Sync {
+    /// Names the plugin in run rules, and is the rule of its findings.
+    fn name(&self) -> &'static str;
+
+    /// Returns findings to add, given the replay's findings. Failing puts the run up for
+    /// review.
+    fn check(
+        &self,
+        save: &mut SaveFile<File>,
+        findings: &[ReplayMsg],
+    ) -> anyhow::Result<Vec<ReplayMsg>>;
+}
+
+/// Plugins compiled into the runner; add new ones here.
+fn builtin_plugins() -> Vec<Arc<dyn VerificationPlugin>> {
+    Vec::new()
+}
+
+static REGISTRY: LazyLock<PluginRegistry> =
+    LazyLock::new(|| PluginRegistry::new(builtin_plugins()));
+
+/// The plugins run rules can enable.
+pub fn registry() -> &'static PluginRegistry {
+    &REGISTRY
+}
+
+pub struct PluginRegistry {
+    plugins: Vec<Arc<dyn VerificationPlugin>>,
+}
+
+impl PluginRegistry {
+    pub fn new(plugins: Vec<Arc<dyn VerificationPlugin>>) -> Self {
+        Self { plugins }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn VerificationPlugin> {
+        self.plugins
+            .iter()
+            .find(|plugin| plugin.name() == name)
+            .map(|plugin| plugin.as_ref())
+    }
+
+    /// Runs the plugins named in `names` in order, adding their findings to `report`.
+    pub fn run(&self, names: &[String], save: &mut SaveFile<File>, report: &mut ReplayReport) {
+        for name in names {
+            let findings = match self.get(name) {
+                Some(plugin) => {
+                    info!("Running verification plugin {}", name);
+                    plugin
+                        .check(save, &report.findings)
+                        .unwrap_or_else(|e| vec![plugin_failed(name, e)])
+                }
+                None => vec![plugin_failed(
+                    name,
+                    anyhow::anyhow!("no plugin with this name"),
+                )],
+            };
+            for mut finding in findings {
+                finding.rule = Some(name.clone());
+                report.max_msg_level = report.max_msg_level.max(finding.level);
+                if finding.level >= MsgLevel::Warn {
+                    report.messages.push(finding.message.clone());
+                }
+                report.findings.push(finding);
+            }
+        }
+        report.rule_counts = count_by_rule(&report.findings);
+    }
+}
+
+fn plugin_failed(name: &str, error: anyhow::Error) -> ReplayMsg {
+    warn!("Verification plugin {} failed: {:#}", name, error);
+    ReplayMsg {
+        time: 0,
+        level: MsgLevel::Warn,
+        rule: None,
+        message: format!("Plugin {} failed: {:#}", name, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flags saves whose control.lua isn't freeplay's, and counts the replay's warnings.
+    struct TestPlugin;
+
+    impl VerificationPlugin for TestPlugin {
+        fn name(&self) -> &'static str {
+            "test_plugin"
+        }
+
+        fn check(
+            &self,
+            save: &mut SaveFile<File>,
+            findings: &[ReplayMsg],
+        ) -> anyhow::Result<Vec<ReplayMsg>> {
+            let control_lua = save.read_file("control.lua")?;
+            let warnings = findings
+                .iter()
+                .filter(|finding| finding.level == MsgLevel::Warn)
+                .count();
+            Ok(vec![ReplayMsg {
+                time: 60,
+                level: if control_lua.starts_with(b"require('__base__/script/freeplay") {
+                    MsgLevel::Info
+                } else {
+                    MsgLevel::Error
+                },
+                rule: None,
+                message: format!("{} warning(s) before", warnings),
+            }])
+        }
+    }
+
+    struct FailingPlugin;
+
+    impl VerificationPlugin for FailingPlugin {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        fn check(&self, _: &mut SaveFile<File>, _: &[ReplayMsg]) -> anyhow::Result<Vec<ReplayMsg>> {
+            anyhow::bail!("level.dat is unreadable")
+        }
+    }
+
+    #[test]
+    fn test_run_plugins() {
+        let fixtures_dir = test_utils::fixtures_dir();
+        let mut save = SaveFile::new(File::open(fixtures_dir.join("TEST.zip")).unwrap()).unwrap();
+        let registry = PluginRegistry::new(vec![Arc::new(TestPlugin), Arc::new(FailingPlugin)]);
+        let mut report = ReplayReport {
+            findings: vec![ReplayMsg {
+                time: 10,
+                level: MsgLevel::Warn,
+                rule: Some("no_editor".to_string()),
+                message: "opened the editor".to_string(),
+            }],
+            max_msg_level: MsgLevel::Warn,
+            ..Default::default()
+        };
+
+        registry.run(&["test_plugin".to_string()], &mut save, &mut report);
+        assert_eq!(report.max_msg_level, MsgLevel::Warn);
+        assert_eq!(report.findings[1].message, "1 warning(s) before");
+        assert_eq!(report.findings[1].rule.as_deref(), Some("test_plugin"));
+        assert_eq!(report.rule_counts["test_plugin"], 1);
+        assert!(report.messages.is_empty());
+
+        registry.run(
+            &["failing".to_string(), "unknown".to_string()],
+            &mut save,
+            &mut report,
+        );
+        assert_eq!(
+            report.messages,
+            vec![
+                "Plugin failing failed: level.dat is unreadable",
+                "Plugin unknown failed: no plugin with this name",
+            ]
+        );
+        assert_eq!(report.rule_counts["failing"], 1);
+    }
+}
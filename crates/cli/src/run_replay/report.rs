@@ -0,0 +1,354 @@
+//! Human-readable replay reports for moderators.
+
+use itertools::Itertools;
+use replay_script::locale::{RuleDescriptions, format_ticks};
+use replay_script::{MsgLevel, ReplayMsg};
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::path::Path;
+
+use super::ReplayReport;
+
+pub const REPORT_MARKDOWN_FILE: &str = "report.md";
+pub const REPORT_HTML_FILE: &str = "report.html";
+
+/// Labels and links shown alongside a rendered report.
+pub struct ReportContext<'a> {
+    pub title: &'a str,
+    pub save_link: Option<&'a str>,
+    /// Location of the replay log, relative to the report.
+    pub log_link: Option<&'a str>,
+    pub descriptions: &'a RuleDescriptions,
+}
+
+fn verdict(report: &ReplayReport) -> &'static str {
+    if report.win_condition_not_completed {
+        return "Failed (win condition not reached)";
+    }
+    if report.partial_verification && report.max_msg_level <= MsgLevel::Warn {
+        return "Needs review (partial verification)";
+    }
+    match report.max_msg_level {
+        MsgLevel::Debug | MsgLevel::Info => "Passed",
+        MsgLevel::Warn => "Needs review",
+        MsgLevel::Error => "Failed",
+        MsgLevel::Critical => "Failed (critical)",
+    }
+}
+
+/// (label, text, link) rows of the summary section.
+fn summary_rows<'a>(report: &'a ReplayReport, ctx: &ReportContext<'a>) -> Vec<SummaryRow<'a>> {
+    let mut rows = vec![
+        ("Result", verdict(report).to_string(), None),
+        ("Highest level", report.max_msg_level.to_string(), None),
+    ];
+    if let Some(exit) = &report.exit {
+        rows.push((
+            "Ended",
+            format!("{} at {}", exit.message, format_ticks(exit.time)),
+            None,
+        ));
+    }
+    rows.push(("Game time", format_ticks(report.final_tick), None));
+    rows.push((
+        "Verification time",
+        format!("{:.0}s", report.duration_secs),
+        None,
+    ));
+    if let Some(ups) = report.ups {
+        rows.push(("Average UPS", format!("{:.0}", ups), None));
+    }
+    if let Some(link) = ctx.save_link {
+        rows.push(("Save", "download".to_string(), Some(link)));
+    }
+    if let Some(link) = ctx.log_link {
+        rows.push(("Log", "replay log".to_string(), Some(link)));
+    }
+    rows
+}
+
+type SummaryRow<'a> = (&'static str, String, Option<&'a str>);
+
+/// Summary rows of each segment of a segmented run, linking to the segment's own log.
+fn segment_rows<'a>(
+    report: &'a ReplayReport,
+    descriptions: &'a RuleDescriptions,
+) -> Vec<Vec<SummaryRow<'a>>> {
+    report
+        .segments
+        .iter()
+        .map(|segment| {
+            let ctx = ReportContext {
+                title: "",
+                descriptions,
+                save_link: None,
+                log_link: segment
+                    .log_path
+                    .as_deref()
+                    .and_then(Path::file_name)
+                    .and_then(OsStr::to_str),
+            };
+            summary_rows(segment, &ctx)
+        })
+        .collect()
+}
+
+fn write_markdown_rows(out: &mut String, rows: Vec<SummaryRow>) {
+    for (label, text, link) in rows {
+        match link {
+            Some(link) => writeln!(out, "- **{label}:** [{text}]({link})"),
+            None => writeln!(out, "- **{label}:** {text}"),
+        }
+        .unwrap();
+    }
+}
+
+fn write_html_rows(out: &mut String, rows: Vec<SummaryRow>) {
+    writeln!(out, "<ul>").unwrap();
+    for (label, text, link) in rows {
+        let text = escape_html(&text);
+        match link {
+            Some(link) => writeln!(
+                out,
+                "<li><b>{label}:</b> <a href=\"{}\">{text}</a></li>",
+                escape_html(link)
+            ),
+            None => writeln!(out, "<li><b>{label}:</b> {text}</li>"),
+        }
+        .unwrap();
+    }
+    writeln!(out, "</ul>").unwrap();
+}
+
+/// Findings grouped by rule, most severe rules first.
+fn findings_by_rule(report: &ReplayReport) -> Vec<(&str, Vec<&ReplayMsg>)> {
+    report
+        .findings
+        .iter()
+        .into_group_map_by(|msg| msg.rule.as_deref().unwrap_or("unknown"))
+        .into_iter()
+        .sorted_by_key(|(rule, msgs)| {
+            let max_level = msgs.iter().map(|msg| msg.level).max();
+            (std::cmp::Reverse(max_level), *rule)
+        })
+        .collect()
+}
+
+pub fn render_markdown(report: &ReplayReport, ctx: &ReportContext) -> String {
+    let mut out = String::new();
+    writeln!(out, "# {}\n", ctx.title).unwrap();
+
+    writeln!(out, "## Summary\n").unwrap();
+    write_markdown_rows(&mut out, summary_rows(report, ctx));
+    for (i, rows) in segment_rows(report, ctx.descriptions)
+        .into_iter()
+        .enumerate()
+    {
+        writeln!(out, "\n## Segment {}\n", i + 1).unwrap();
+        write_markdown_rows(&mut out, rows);
+    }
+
+    writeln!(out, "\n## Findings\n").unwrap();
+    let groups = findings_by_rule(report);
+    if groups.is_empty() {
+        writeln!(out, "No findings.").unwrap();
+    }
+    for (rule, msgs) in groups {
+        match ctx.descriptions.describe(rule) {
+            description if description == rule => writeln!(out, "### {rule} ({})\n", msgs.len()),
+            description => writeln!(out, "### {description} — `{rule}` ({})\n", msgs.len()),
+        }
+        .unwrap();
+        for msg in msgs {
+            writeln!(
+                out,
+                "- `{}` **{}** {}",
+                format_ticks(msg.time),
+                msg.level,
+                msg.message
+            )
+            .unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn render_html(report: &ReplayReport, ctx: &ReportContext) -> String {
+    let mut out = String::new();
+    let title = escape_html(ctx.title);
+    writeln!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n<body>\n<h1>{title}</h1>"
+    )
+    .unwrap();
+
+    writeln!(out, "<h2>Summary</h2>").unwrap();
+    write_html_rows(&mut out, summary_rows(report, ctx));
+    for (i, rows) in segment_rows(report, ctx.descriptions)
+        .into_iter()
+        .enumerate()
+    {
+        writeln!(out, "<h2>Segment {}</h2>", i + 1).unwrap();
+        write_html_rows(&mut out, rows);
+    }
+    writeln!(out, "<h2>Findings</h2>").unwrap();
+
+    let groups = findings_by_rule(report);
+    if groups.is_empty() {
+        writeln!(out, "<p>No findings.</p>").unwrap();
+    }
+    for (rule, msgs) in groups {
+        let heading = match ctx.descriptions.describe(rule) {
+            description if description == rule => escape_html(rule),
+            description => format!(
+                "{} — <code>{}</code>",
+                escape_html(description),
+                escape_html(rule)
+            ),
+        };
+        writeln!(out, "<h3>{} ({})</h3>\n<ul>", heading, msgs.len()).unwrap();
+        for msg in msgs {
+            writeln!(
+                out,
+                "<li><code>{}</code> <b>{}</b> {}</li>",
+                format_ticks(msg.time),
+                msg.level,
+                escape_html(&msg.message)
+            )
+            .unwrap();
+        }
+        writeln!(out, "</ul>").unwrap();
+    }
+    writeln!(out, "</body>\n</html>").unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use replay_script::ExitSignal;
+
+    fn msg(time: u64, level: MsgLevel, rule: &str, message: &str) -> ReplayMsg {
+        ReplayMsg {
+            time,
+            level,
+            rule: Some(rule.to_string()),
+            message: message.to_string(),
+        }
+    }
+
+    fn sample_report() -> ReplayReport {
+        ReplayReport {
+            max_msg_level: MsgLevel::Error,
+            findings: vec![
+                msg(18000, MsgLevel::Info, "log_time", "00:05:00"),
+                msg(
+                    394,
+                    MsgLevel::Error,
+                    "no_map_editor",
+                    "<player> used map editor!",
+                ),
+            ],
+            exit: Some(ExitSignal {
+                time: 216000,
+                message: "Rocket launched!".to_string(),
+            }),
+            final_tick: 216000,
+            ..Default::default()
+        }
+    }
+
+    fn context(descriptions: &RuleDescriptions) -> ReportContext<'_> {
+        ReportContext {
+            title: "Run abc123",
+            save_link: Some("https://example.com/save.zip"),
+            log_link: Some("output.log"),
+            descriptions,
+        }
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let descriptions = RuleDescriptions::english();
+        let markdown = render_markdown(&sample_report(), &context(&descriptions));
+
+        assert!(markdown.starts_with("# Run abc123\n"));
+        assert!(markdown.contains("- **Result:** Failed\n"));
+        assert!(markdown.contains("- **Ended:** Rocket launched! at 1:00:00.000\n"));
+        assert!(markdown.contains("- **Save:** [download](https://example.com/save.zip)\n"));
+        assert!(markdown.contains("- `0:00:06.566` **Error** <player> used map editor!\n"));
+
+        // most severe rule first
+        let editor = markdown
+            .find("### No map editor — `no_map_editor` (1)")
+            .unwrap();
+        let time = markdown.find("### Game time — `log_time` (1)").unwrap();
+        assert!(editor < time);
+    }
+
+    #[test]
+    fn test_render_custom_descriptions() {
+        let descriptions =
+            RuleDescriptions::with_locale("[rules]\nno_map_editor=Kein Karteneditor\n").unwrap();
+        let mut report = sample_report();
+        report.findings[0].rule = Some("my_plugin".to_string());
+        let markdown = render_markdown(&report, &context(&descriptions));
+        assert!(markdown.contains("### Kein Karteneditor — `no_map_editor` (1)"));
+        assert!(markdown.contains("### my_plugin (1)"));
+    }
+
+    #[test]
+    fn test_render_html_escapes() {
+        let descriptions = RuleDescriptions::english();
+        let html = render_html(&sample_report(), &context(&descriptions));
+        assert!(html.contains("<title>Run abc123</title>"));
+        assert!(html.contains("&lt;player&gt; used map editor!"));
+        assert!(html.contains("<a href=\"https://example.com/save.zip\">download</a>"));
+        assert!(html.contains("<h3>No map editor — <code>no_map_editor</code> (1)</h3>"));
+    }
+
+    #[test]
+    fn test_render_segments() {
+        let first = ReplayReport {
+            max_msg_level: MsgLevel::Warn,
+            messages: vec!["too fast".to_string()],
+            final_tick: 108000,
+            log_path: Some("runs/abc123/output.segment1.log".into()),
+            ..Default::default()
+        };
+        let report = ReplayReport::from_segments(vec![first, sample_report()]);
+        assert_eq!(report.max_msg_level, MsgLevel::Error);
+        assert_eq!(report.messages, ["Segment 1: too fast"]);
+        assert_eq!(report.final_tick, 216000);
+
+        let descriptions = RuleDescriptions::english();
+        let markdown = render_markdown(&report, &context(&descriptions));
+        let segment = markdown.find("## Segment 1\n").unwrap();
+        assert!(markdown[segment..].contains("- **Log:** [replay log](output.segment1.log)\n"));
+        assert!(markdown.contains("## Segment 2\n"));
+    }
+
+    #[test]
+    fn test_render_without_findings() {
+        let report = ReplayReport::default();
+        let descriptions = RuleDescriptions::english();
+        let ctx = ReportContext {
+            title: "Empty",
+            save_link: None,
+            log_link: None,
+            descriptions: &descriptions,
+        };
+        let markdown = render_markdown(&report, &ctx);
+        assert!(markdown.contains("- **Result:** Passed\n"));
+        assert!(markdown.contains("No findings."));
+        assert!(!markdown.contains("**Save:**"));
+    }
+}
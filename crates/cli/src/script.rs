@@ -0,0 +1,101 @@
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use factorio_manager::save_file::ScriptInjection;
+use replay_script::ReplayScripts;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct ScriptArgs {
+    #[command(subcommand)]
+    pub subcommand: ScriptSubcommand,
+}
+
+#[derive(Subcommand)]
+pub enum ScriptSubcommand {
+    /// Print the Lua files written into a save's script directory for a rules file
+    Render(RenderArgs),
+}
+
+#[derive(Args)]
+pub struct RenderArgs {
+    /// RUN Rules (json/yaml)
+    #[arg(long, required_unless_present = "all", conflicts_with = "all")]
+    pub rules: Option<PathBuf>,
+
+    /// Render with every replay script enabled
+    #[arg(long)]
+    pub all: bool,
+
+    /// Save whose control.lua the script is installed into; left out if not given
+    #[arg(long)]
+    pub save: Option<PathBuf>,
+}
+
+pub async fn handle_script_command(args: ScriptArgs) -> Result<()> {
+    match args.subcommand {
+        ScriptSubcommand::Render(render_args) => {
+            let (replay_scripts, injection) = match &render_args.rules {
+                Some(path) => {
+                    let rules = crate::load_run_rules(path).await?;
+                    (rules.replay_scripts, rules.script_injection)
+                }
+                None => (ReplayScripts::all_enabled(), ScriptInjection::default()),
+            };
+            let control_lua = match &render_args.save {
+                Some(path) => crate::load_save(path)
+                    .await?
+                    .1
+                    .get_control_lua_contents()?
+                    .to_string(),
+                None => String::new(),
+            };
+            print!("{}", render(&replay_scripts, injection, &control_lua));
+            Ok(())
+        }
+    }
+}
+
+/// The files installing the script writes, each under a comment naming it.
+fn render(replay_scripts: &ReplayScripts, injection: ScriptInjection, control_lua: &str) -> String {
+    let mut output = String::new();
+    for (name, contents) in injection.files(control_lua, replay_scripts) {
+        writeln!(output, "-- ===== {name} =====").unwrap();
+        output += &contents;
+        if !contents.ends_with('\n') {
+            output.push('\n');
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let scripts = ReplayScripts::default();
+        let script = scripts.to_string();
+
+        let append = render(&scripts, ScriptInjection::Append, "-- freeplay\n");
+        assert!(append.starts_with("-- ===== control.lua =====\n-- freeplay\n"));
+        assert!(append.ends_with(&format!("{script}\n")));
+
+        let require = render(&scripts, ScriptInjection::Require, "-- freeplay\n");
+        let files: Vec<_> = require
+            .lines()
+            .filter(|line| line.starts_with("-- ====="))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                "-- ===== control.lua =====",
+                "-- ===== replay_script.lua =====",
+                "-- ===== original_control.lua ====="
+            ]
+        );
+        assert!(require.contains("require(\"replay_script\")\nrequire(\"original_control\")\n"));
+        assert!(require.ends_with("-- ===== original_control.lua =====\n-- freeplay\n"));
+    }
+}
@@ -0,0 +1,391 @@
+//! Runs replay rules inside a headless Factorio and feeds them scripted events.
+//!
+//! The rules are installed into `EMPTY_SAVE.zip` together with a small driver, which calls the
+//! registered event handlers directly (via `script.get_event_handler`) on the requested ticks.
+//! The save is then run with `--benchmark`, and the rule output is collected from stdout.
+
+use anyhow::{Context, Result};
+use factorio_manager::factorio_install_dir::FactorioInstallDir;
+use factorio_manager::save_file::{SaveFile, ScriptInjection};
+use itertools::Itertools;
+use replay_script::{ExitSignal, MsgLevel, ReplayMsg, ReplayScripts};
+use std::fs::{self, File};
+use std::str::FromStr;
+use tokio::sync::Mutex;
+
+/// Only one Factorio instance can use an install directory at a time.
+static FACTORIO_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// An event passed to the rule's handler, `tick` ticks after the benchmark starts.
+pub struct ScriptedEvent {
+    pub tick: u32,
+    pub event: &'static str,
+    /// Lua table literal with the event data; `name` and `tick` are filled in by the driver.
+    pub data: &'static str,
+}
+
+pub struct HarnessOutput {
+    pub messages: Vec<ReplayMsg>,
+    pub exit: Option<ExitSignal>,
+}
+
+impl HarnessOutput {
+    pub fn has_message(&self, level: MsgLevel, text: &str) -> bool {
+        self.messages
+            .iter()
+            .any(|msg| msg.level == level && msg.message.contains(text))
+    }
+}
+
+fn driver_lua(events: &[ScriptedEvent]) -> String {
+    let events_by_tick = events
+        .iter()
+        .into_group_map_by(|event| event.tick)
+        .into_iter()
+        .map(|(tick, events)| {
+            let events = events
+                .iter()
+                .map(|event| format!("{{\"{}\", {}}}", event.event, event.data))
+                .join(", ");
+            format!("  [{tick}] = {{{events}}},")
+        })
+        .join("\n");
+
+    format!(
+        r#"
+-- Test harness driver
+local harness_events = {{
+{events_by_tick}
+}}
+local harness_start_tick
+addReplayLib({{
+  on_nth_tick = {{
+    [1] = function(event)
+      if storage._replay_script_DATA == nil then
+        storage._replay_script_DATA = {{}}
+      end
+      harness_start_tick = harness_start_tick or event.tick
+      for _, scripted in ipairs(harness_events[event.tick - harness_start_tick] or {{}}) do
+        local id = defines.events[scripted[1]]
+        local data = scripted[2]
+        data.name = id
+        data.tick = event.tick
+        script.get_event_handler(id)(data)
+      end
+    end,
+  }},
+}})
+"#
+    )
+}
+
+/// Runs `scripts` against `events` and returns everything the rules logged.
+pub async fn run_rules(
+    test_name: &str,
+    scripts: &ReplayScripts,
+    events: &[ScriptedEvent],
+) -> Result<HarnessOutput> {
+    run_scripts(test_name, &scripts.to_string(), None, events).await
+}
+
+/// Runs `replay_lua` against `events`, installed with [`ScriptInjection::Require`] into a
+/// save whose own control.lua also runs `save_lua`.
+pub async fn run_with_save_lua(
+    test_name: &str,
+    replay_lua: &str,
+    save_lua: &str,
+    events: &[ScriptedEvent],
+) -> Result<HarnessOutput> {
+    run_scripts(test_name, replay_lua, Some(save_lua), events).await
+}
+
+async fn run_scripts(
+    test_name: &str,
+    replay_lua: &str,
+    save_lua: Option<&str>,
+    events: &[ScriptedEvent],
+) -> Result<HarnessOutput> {
+    let _guard = FACTORIO_LOCK.lock().await;
+
+    let test_dir = test_utils::test_tmp_dir().join("script_harness");
+    fs::create_dir_all(&test_dir)?;
+    let save_path = test_utils::fixtures_dir().join("EMPTY_SAVE.zip");
+    let mut save_file = SaveFile::new(File::open(&save_path)?)?;
+
+    let install_dir = FactorioInstallDir::new_or_create(test_utils::test_factorio_installs_dir())?;
+    let instance = install_dir
+        .get_or_download_factorio(save_file.get_factorio_version()?)
+        .await?;
+
+    let installed_path = test_dir.join(format!("{test_name}.zip"));
+    let script = format!("{replay_lua}\n{}", driver_lua(events));
+    let injection = match save_lua {
+        Some(save_lua) => {
+            // appending to the save's control.lua first makes it part of the save's own script
+            let save_path = test_dir.join(format!("{test_name}_save.zip"));
+            save_file.install_replay_script_to(
+                &mut File::create(&save_path)?,
+                save_lua,
+                ScriptInjection::Append,
+            )?;
+            save_file = SaveFile::new(File::open(&save_path)?)?;
+            ScriptInjection::Require
+        }
+        None => ScriptInjection::Append,
+    };
+    save_file.install_replay_script_to(&mut File::create(&installed_path)?, script, injection)?;
+
+    let ticks = events.iter().map(|event| event.tick).max().unwrap_or(0) + 2;
+    let mut process = instance.spawn_benchmark(&installed_path, ticks)?;
+    let stdout = process
+        .read_all()
+        .await
+        .context("Failed to read Factorio output")?;
+    process.wait().await?;
+
+    let mut messages = Vec::new();
+    let mut exit = None;
+    for line in stdout.lines() {
+        if let Ok(msg) = ReplayMsg::from_str(line) {
+            messages.push(msg);
+        } else if let Ok(signal) = ExitSignal::from_str(line) {
+            exit.get_or_insert(signal);
+        }
+    }
+    Ok(HarnessOutput { messages, exit })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use replay_script::WinCondition;
+
+    #[test]
+    fn test_driver_lua() {
+        let lua = driver_lua(&[
+            ScriptedEvent {
+                tick: 3,
+                event: "on_console_command",
+                data: r#"{command = "c", parameters = ""}"#,
+            },
+            ScriptedEvent {
+                tick: 3,
+                event: "on_console_command",
+                data: r#"{command = "version", parameters = ""}"#,
+            },
+        ]);
+        assert!(lua.contains(
+            r#"[3] = {{"on_console_command", {command = "c", parameters = ""}}, {"on_console_command", {command = "version", parameters = ""}}},"#
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_bad_console_commands() -> Result<()> {
+        let output = run_rules(
+            "bad_console_commands",
+            &ReplayScripts::default(),
+            &[
+                ScriptedEvent {
+                    tick: 1,
+                    event: "on_console_command",
+                    data: r#"{command = "version", parameters = ""}"#,
+                },
+                ScriptedEvent {
+                    tick: 2,
+                    event: "on_console_command",
+                    data: r#"{command = "c", parameters = "game.print(1)"}"#,
+                },
+            ],
+        )
+        .await?;
+
+        assert!(output.has_message(MsgLevel::Info, "server ran: /version"));
+        assert!(output.has_message(MsgLevel::Error, "server ran disallowed command: /c"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_log_all_commands() -> Result<()> {
+        let scripts = ReplayScripts {
+            log_all_commands: true,
+            ..Default::default()
+        };
+        let output = run_rules(
+            "log_all_commands",
+            &scripts,
+            &[ScriptedEvent {
+                tick: 1,
+                event: "on_console_command",
+                data: r#"{command = "editor", parameters = ""}"#,
+            }],
+        )
+        .await?;
+
+        assert!(output.has_message(MsgLevel::Info, "[command] server ran: /editor"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_banned_items() -> Result<()> {
+        let scripts = ReplayScripts {
+            banned_items: Some(vec!["infinity-chest".to_string()]),
+            ..Default::default()
+        };
+        let output = run_rules(
+            "banned_items",
+            &scripts,
+            &[
+                ScriptedEvent {
+                    tick: 1,
+                    event: "on_player_crafted_item",
+                    data: r#"{player_index = 1, item_stack = {name = "iron-chest"}}"#,
+                },
+                ScriptedEvent {
+                    tick: 2,
+                    event: "on_player_crafted_item",
+                    data: r#"{player_index = 1, item_stack = {name = "infinity-chest"}}"#,
+                },
+            ],
+        )
+        .await?;
+
+        assert!(output.has_message(MsgLevel::Error, "crafted banned item \"infinity-chest\""));
+        assert!(!output.has_message(MsgLevel::Error, "iron-chest"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_coop_partner_acting_first() -> Result<()> {
+        let output = run_rules(
+            "coop_partner_acting_first",
+            &ReplayScripts::default(),
+            &[
+                ScriptedEvent {
+                    tick: 1,
+                    event: "on_player_created",
+                    data: "{player_index = 1}",
+                },
+                ScriptedEvent {
+                    tick: 1,
+                    event: "on_player_created",
+                    data: "{player_index = 2}",
+                },
+                ScriptedEvent {
+                    tick: 2,
+                    event: "on_player_dropped_item",
+                    data: "{player_index = 2}",
+                },
+                ScriptedEvent {
+                    tick: 3,
+                    event: "on_player_dropped_item",
+                    data: "{player_index = 1}",
+                },
+            ],
+        )
+        .await?;
+
+        let violations = output
+            .messages
+            .iter()
+            .filter(|msg| {
+                msg.level == MsgLevel::Error && msg.rule.as_deref() == Some("no_coop_actions")
+            })
+            .map(|msg| msg.message.as_str())
+            .collect_vec();
+        assert_eq!(
+            violations,
+            ["player 2 performed actions, but only the runner may play!"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_chained_handlers_with_filters() -> Result<()> {
+        let replay_lua = format!(
+            "{}\n{}",
+            ReplayScripts::default(),
+            r#"
+local log = makeReplayLog("replay_handler")
+addReplayLib({
+  on_built_entity = function(event) log.info("built " .. event.entity.name) end,
+})
+"#
+        );
+        let save_lua = r#"
+local log = makeReplayLog("save_handler")
+script.on_event(defines.events.on_built_entity, function(event)
+  log.info("built " .. event.entity.name)
+end, {{filter = "name", name = "iron-chest"}, {filter = "ghost", mode = "and", invert = true}})
+"#;
+        let output = run_with_save_lua(
+            "chained_handlers_with_filters",
+            &replay_lua,
+            save_lua,
+            &[
+                ScriptedEvent {
+                    tick: 1,
+                    event: "on_built_entity",
+                    data: r#"{entity = {name = "iron-chest", type = "container"}}"#,
+                },
+                ScriptedEvent {
+                    tick: 2,
+                    event: "on_built_entity",
+                    data: r#"{entity = {name = "wooden-chest", type = "container"}}"#,
+                },
+            ],
+        )
+        .await?;
+
+        let built = |rule: &str| {
+            output
+                .messages
+                .iter()
+                .filter(|msg| msg.rule.as_deref() == Some(rule))
+                .map(|msg| msg.message.as_str())
+                .collect_vec()
+        };
+        assert_eq!(
+            built("replay_handler"),
+            ["built iron-chest", "built wooden-chest"]
+        );
+        assert_eq!(built("save_handler"), ["built iron-chest"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_research_win_condition() -> Result<()> {
+        let scripts = ReplayScripts {
+            win_condition: Some(WinCondition::ResearchCompleted {
+                tech: "steel-axe".to_string(),
+            }),
+            ..Default::default()
+        };
+        let output = run_rules(
+            "research_win_condition",
+            &scripts,
+            &[
+                ScriptedEvent {
+                    tick: 1,
+                    event: "on_research_finished",
+                    data: r#"{research = {name = "automation"}}"#,
+                },
+                ScriptedEvent {
+                    tick: 2,
+                    event: "on_research_finished",
+                    data: r#"{research = {name = "steel-axe"}}"#,
+                },
+            ],
+        )
+        .await?;
+
+        let exit = output.exit.expect("win condition should end the replay");
+        assert!(exit.message.contains("steel-axe"));
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+//! A stand-in for the speedrun.com API, serving canned games, categories and runs from a
+//! local wiremock server, so API and polling tests run offline and deterministically.
+
+use serde_json::{Value, json};
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+use crate::daemon::speedrun_api::SpeedrunClient;
+
+/// speedrun.com's page size when a request doesn't give `max`.
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+pub struct SpeedrunMock {
+    server: MockServer,
+}
+
+/// A run as listed by speedrun.com, with embedded players and platform.
+pub fn run_json(run_id: &str, game_id: &str, category_id: &str, submitted: &str) -> Value {
+    json!({
+        "id": run_id,
+        "game": game_id,
+        "category": category_id,
+        "comment": format!("https://example.com/{}.zip", run_id),
+        "submitted": submitted,
+        "times": { "primary_t": 3600.0 },
+        "players": { "data": [{ "names": { "international": "runner" } }] },
+        "platform": { "data": { "id": "pc", "name": "PC" } },
+        "values": {},
+    })
+}
+
+impl SpeedrunMock {
+    pub async fn start() -> Self {
+        Self {
+            server: MockServer::start().await,
+        }
+    }
+
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// A client for this server.
+    pub fn client(&self) -> SpeedrunClient {
+        SpeedrunClient::new().unwrap().with_base_url(self.uri())
+    }
+
+    pub async fn game(&self, game_id: &str, name: &str) {
+        Mock::given(method("GET"))
+            .and(path(format!("/games/{}", game_id)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": { "id": game_id, "names": { "international": name } }
+            })))
+            .mount(&self.server)
+            .await;
+    }
+
+    pub async fn category(&self, category_id: &str, name: &str) {
+        Mock::given(method("GET"))
+            .and(path(format!("/categories/{}", category_id)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": category_json(category_id, name)
+            })))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serves the game's per-game categories, as `(id, name)`.
+    pub async fn game_categories(&self, game_id: &str, categories: &[(&str, &str)]) {
+        let data: Vec<Value> = categories
+            .iter()
+            .map(|(id, name)| category_json(id, name))
+            .collect();
+        Mock::given(method("GET"))
+            .and(path(format!("/games/{}/categories", game_id)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": data })))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serves `run` (see [`run_json`]) by its ID.
+    pub async fn run(&self, run: Value) {
+        let run_id = run["id"].as_str().unwrap().to_string();
+        Mock::given(method("GET"))
+            .and(path(format!("/runs/{}", run_id)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": run })))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serves the category's leaderboard, with `runs` as `(run ID, time in seconds)` in place
+    /// order.
+    pub async fn leaderboard(&self, game_id: &str, category_id: &str, runs: &[(&str, f64)]) {
+        let entries: Vec<Value> = runs
+            .iter()
+            .enumerate()
+            .map(|(i, (run_id, time))| {
+                json!({
+                    "place": i + 1,
+                    "run": { "id": run_id, "times": { "primary_t": time } },
+                })
+            })
+            .collect();
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/leaderboards/{}/category/{}",
+                game_id, category_id
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": { "runs": entries }
+            })))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Lists `runs` of the category in the given order, paged by the request's `offset` and
+    /// `max` like speedrun.com does.
+    pub async fn runs(&self, game_id: &str, category_id: &str, runs: Vec<Value>) {
+        Mock::given(method("GET"))
+            .and(path("/runs"))
+            .and(query_param("game", game_id))
+            .and(query_param("category", category_id))
+            .respond_with(move |request: &Request| {
+                let param = |name: &str| {
+                    request
+                        .url
+                        .query_pairs()
+                        .find(|(key, _)| key == name)
+                        .and_then(|(_, value)| value.parse::<usize>().ok())
+                };
+                let offset = param("offset").unwrap_or(0);
+                let max = param("max").unwrap_or(DEFAULT_PAGE_SIZE);
+                let page: Vec<&Value> = runs.iter().skip(offset).take(max).collect();
+                ResponseTemplate::new(200).set_body_json(json!({
+                    "data": page,
+                    "pagination": { "offset": offset, "max": max, "size": page.len(), "links": [] },
+                }))
+            })
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answers the next `times` requests to `url_path` with 429 and `Retry-After: 0`, before
+    /// the other mocks.
+    pub async fn rate_limit(&self, url_path: &str, times: u64) {
+        Mock::given(method("GET"))
+            .and(path(url_path))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(times)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Requests received for `url_path`, in order.
+    pub async fn requests(&self, url_path: &str) -> Vec<Request> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|request| request.url.path() == url_path)
+            .collect()
+    }
+}
+
+fn category_json(category_id: &str, name: &str) -> Value {
+    json!({ "id": category_id, "name": name, "type": "per-game" })
+}
@@ -0,0 +1,556 @@
+//! Regression tests of the replay scripts: a manifest lists fixture saves and the verdicts
+//! they are expected to get, so edits to the rules that change a verdict are caught.
+//!
+//! ```yaml
+//! # paths are relative to the manifest
+//! rules: rules.yaml
+//! cases:
+//!   - save: good/any_percent.zip
+//!     expect: pass
+//!   - save: bad/map_editor.zip
+//!     expect: fail
+//!     # the rules that logged warnings or errors, exactly
+//!     flagged_by: [no_map_editor]
+//!   - name: lenient_apm
+//!     save: bad/high_apm.zip
+//!     rules: lenient.yaml
+//!     expect: warn
+//! ```
+
+use anyhow::{Context, Result};
+use clap::Args;
+use comfy_table::{Cell, Table};
+use factorio_manager::factorio_install_dir::FactorioInstallDir;
+use log::info;
+use replay_script::MsgLevel;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::daemon::database::types::RunStatus;
+use crate::exit_code::{ConfigError, ExitCode};
+use crate::output::{OutputFormat, print_json};
+use crate::run_replay::{ReplayOptions, ReplayReport, run_replay};
+
+#[derive(Args)]
+pub struct TestRulesArgs {
+    /// Manifest (yaml) of the saves to replay and their expected verdicts
+    manifest: PathBuf,
+
+    /// Factorio installations directory (defaults to ./factorio_installs)
+    #[arg(long, default_value = "./factorio_installs")]
+    install_dir: PathBuf,
+
+    /// Output directory; defaults to results/ next to the manifest
+    /// Logs will be written to {output_dir}/{case_name}/output.log
+    #[arg(short, long)]
+    output_dir: Option<PathBuf>,
+
+    /// JUnit report; defaults to {output_dir}/junit.xml
+    #[arg(long)]
+    junit: Option<PathBuf>,
+
+    /// Only run the cases whose name contains this
+    #[arg(long)]
+    filter: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    /// RUN rules of the cases without their own
+    rules: Option<PathBuf>,
+    cases: Vec<TestCase>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestCase {
+    /// Defaults to the save's file name, without extension
+    name: Option<String>,
+    save: PathBuf,
+    rules: Option<PathBuf>,
+    expect: Verdict,
+    /// The rules that must log warnings or errors; no other rule may
+    flagged_by: Option<BTreeSet<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Verdict {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Verdict {
+    /// As the daemon would judge a run: warnings and partial verifications need review, and
+    /// an unmet win condition fails.
+    fn of(report: &ReplayReport) -> Self {
+        match RunStatus::for_report(report) {
+            RunStatus::Passed => Verdict::Pass,
+            RunStatus::NeedsReview => Verdict::Warn,
+            _ => Verdict::Fail,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CaseResult {
+    name: String,
+    save: PathBuf,
+    expect: Verdict,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    verdict: Option<Verdict>,
+    flagged_by: BTreeSet<String>,
+    /// Expectations the replay didn't meet.
+    failures: Vec<String>,
+    /// The save couldn't be replayed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    duration_secs: f64,
+}
+
+impl CaseResult {
+    fn passed(&self) -> bool {
+        self.failures.is_empty() && self.error.is_none()
+    }
+}
+
+#[derive(Serialize)]
+struct TestRulesOutput<'a> {
+    exit_code: ExitCode,
+    junit: &'a Path,
+    cases: &'a [CaseResult],
+}
+
+pub async fn cli_test_rules(args: TestRulesArgs, format: OutputFormat) -> Result<i32> {
+    let manifest = load_manifest(&args.manifest)?;
+    let base_dir = args.manifest.parent().unwrap_or(Path::new("."));
+    let output_dir = args
+        .output_dir
+        .clone()
+        .unwrap_or_else(|| base_dir.join("results"));
+    let junit_path = args
+        .junit
+        .clone()
+        .unwrap_or_else(|| output_dir.join("junit.xml"));
+    let install_dir = crate::load_install_dir(&args.install_dir).await?;
+
+    let cases: Vec<_> = manifest
+        .cases
+        .iter()
+        .filter(|case| {
+            args.filter
+                .as_ref()
+                .is_none_or(|filter| case_name(case).contains(filter.as_str()))
+        })
+        .collect();
+    info!(
+        "Testing {} save(s) from {}",
+        cases.len(),
+        args.manifest.display()
+    );
+
+    let mut results = Vec::new();
+    for case in cases {
+        let rules = case
+            .rules
+            .as_ref()
+            .or(manifest.rules.as_ref())
+            .context(ConfigError(format!(
+                "case {} has no rules",
+                case_name(case)
+            )))?;
+        results.push(
+            run_case(
+                &install_dir,
+                case,
+                &base_dir.join(rules),
+                base_dir,
+                &output_dir,
+            )
+            .await,
+        );
+    }
+
+    if let Some(parent) = junit_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&junit_path, junit_report(&results))
+        .with_context(|| format!("Failed to write {}", junit_path.display()))?;
+
+    let exit_code = if results.iter().all(CaseResult::passed) {
+        ExitCode::Pass
+    } else {
+        ExitCode::Fail
+    };
+    if format.is_json() {
+        print_json(&TestRulesOutput {
+            exit_code,
+            junit: &junit_path,
+            cases: &results,
+        })?;
+    } else {
+        println!("{}", format_summary(&results));
+        let passed = results.iter().filter(|result| result.passed()).count();
+        println!("{} of {} case(s) passed", passed, results.len());
+    }
+    Ok(exit_code.code())
+}
+
+fn load_manifest(path: &Path) -> Result<Manifest> {
+    let file = std::fs::File::open(path).map_err(anyhow::Error::from);
+    let manifest: Manifest = file
+        .and_then(|file| Ok(serde_yaml::from_reader(file)?))
+        .with_context(|| ConfigError(format!("failed to load manifest {}", path.display())))?;
+    let mut names = HashSet::new();
+    for case in &manifest.cases {
+        let name = case_name(case);
+        if !names.insert(name.clone()) {
+            return Err(anyhow::Error::msg(ConfigError(format!(
+                "duplicate case name {}",
+                name
+            ))));
+        }
+    }
+    Ok(manifest)
+}
+
+fn case_name(case: &TestCase) -> String {
+    case.name.clone().unwrap_or_else(|| {
+        case.save
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    })
+}
+
+async fn run_case(
+    install_dir: &FactorioInstallDir,
+    case: &TestCase,
+    rules_path: &Path,
+    base_dir: &Path,
+    output_dir: &Path,
+) -> CaseResult {
+    let name = case_name(case);
+    let save = base_dir.join(&case.save);
+    let log = output_dir.join(&name).join("output.log");
+    info!("=== Testing {} ===", name);
+    let start = Instant::now();
+    let result = async {
+        std::fs::create_dir_all(output_dir.join(&name))?;
+        let rules = crate::load_run_rules(rules_path).await?;
+        let expected_mods = rules
+            .expected_mods_override
+            .as_ref()
+            .context(ConfigError("rules must list expected_mods".into()))?;
+        let mut save_file = crate::load_save(&save).await?;
+        run_replay(
+            install_dir,
+            &mut save_file,
+            &rules,
+            expected_mods,
+            &log,
+            ReplayOptions::default(),
+        )
+        .await
+        .map_err(anyhow::Error::from)
+    }
+    .await;
+    std::fs::remove_file(save.with_extension("installed.zip")).ok();
+
+    let mut result = match result {
+        Ok(report) => check_case(case, &report),
+        Err(e) => CaseResult {
+            name: String::new(),
+            save: PathBuf::new(),
+            expect: case.expect,
+            verdict: None,
+            flagged_by: BTreeSet::new(),
+            failures: Vec::new(),
+            error: Some(format!("{:#}", e)),
+            duration_secs: 0.0,
+        },
+    };
+    result.name = name;
+    result.save = save;
+    result.duration_secs = start.elapsed().as_secs_f64();
+    result
+}
+
+/// Compares the report of a case with what it expects.
+fn check_case(case: &TestCase, report: &ReplayReport) -> CaseResult {
+    let verdict = Verdict::of(report);
+    let flagged_by: BTreeSet<String> = report
+        .findings
+        .iter()
+        .filter(|finding| finding.level >= MsgLevel::Warn)
+        .filter_map(|finding| finding.rule.clone())
+        .collect();
+
+    let mut failures = Vec::new();
+    if verdict != case.expect {
+        failures.push(format!(
+            "expected verdict {:?}, got {:?}",
+            case.expect, verdict
+        ));
+    }
+    if let Some(expected) = &case.flagged_by {
+        let missing: Vec<_> = expected.difference(&flagged_by).cloned().collect();
+        if !missing.is_empty() {
+            failures.push(format!("not flagged by {}", missing.join(", ")));
+        }
+        let unexpected: Vec<_> = flagged_by.difference(expected).cloned().collect();
+        if !unexpected.is_empty() {
+            failures.push(format!("unexpectedly flagged by {}", unexpected.join(", ")));
+        }
+    }
+    CaseResult {
+        name: case_name(case),
+        save: case.save.clone(),
+        expect: case.expect,
+        verdict: Some(verdict),
+        flagged_by,
+        failures,
+        error: None,
+        duration_secs: report.duration_secs,
+    }
+}
+
+/// A JUnit XML report of the cases, for CI.
+fn junit_report(results: &[CaseResult]) -> String {
+    let failures = results
+        .iter()
+        .filter(|result| result.error.is_none() && !result.failures.is_empty())
+        .count();
+    let errors = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .count();
+    let time: f64 = results.iter().map(|result| result.duration_secs).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuite name=\"test-rules\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">",
+        results.len(),
+        failures,
+        errors,
+        time
+    );
+    for result in results {
+        let _ = write!(
+            xml,
+            "  <testcase classname=\"test-rules\" name=\"{}\" time=\"{:.3}\"",
+            escape_xml(&result.name),
+            result.duration_secs
+        );
+        if let Some(error) = &result.error {
+            let _ = writeln!(
+                xml,
+                ">\n    <error message=\"{}\"/>\n  </testcase>",
+                escape_xml(error)
+            );
+        } else if !result.failures.is_empty() {
+            let message = result.failures.join("; ");
+            let _ = writeln!(
+                xml,
+                ">\n    <failure message=\"{}\"/>\n  </testcase>",
+                escape_xml(&message)
+            );
+        } else {
+            xml.push_str("/>\n");
+        }
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn format_summary(results: &[CaseResult]) -> String {
+    let mut table = Table::new();
+    table.set_header(vec!["Case", "Result", "Expected", "Verdict", "Notes"]);
+    for result in results {
+        let label = match (&result.error, result.passed()) {
+            (Some(_), _) => "Error",
+            (None, true) => "Passed",
+            (None, false) => "Failed",
+        };
+        let verdict = result
+            .verdict
+            .map(|verdict| format!("{:?}", verdict))
+            .unwrap_or_default();
+        let notes = result
+            .error
+            .clone()
+            .unwrap_or_else(|| result.failures.join("; "));
+        table.add_row(vec![
+            Cell::new(&result.name),
+            Cell::new(label),
+            Cell::new(format!("{:?}", result.expect)),
+            Cell::new(verdict),
+            Cell::new(notes),
+        ]);
+    }
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use replay_script::ReplayMsg;
+
+    fn report(findings: &[(MsgLevel, &str)]) -> ReplayReport {
+        ReplayReport {
+            max_msg_level: findings
+                .iter()
+                .map(|(level, _)| *level)
+                .max()
+                .unwrap_or_default(),
+            findings: findings
+                .iter()
+                .map(|(level, rule)| ReplayMsg {
+                    time: 60,
+                    level: *level,
+                    rule: Some(rule.to_string()),
+                    message: "message".to_string(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_load_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("manifest.yaml");
+        std::fs::write(
+            &path,
+            "
+rules: rules.yaml
+cases:
+  - save: good/run.zip
+    expect: pass
+  - name: map_editor
+    save: bad/run.zip
+    rules: other.yaml
+    expect: fail
+    flagged_by: [no_map_editor]
+",
+        )
+        .unwrap();
+        let manifest = load_manifest(&path).unwrap();
+        assert_eq!(manifest.cases.len(), 2);
+        assert_eq!(case_name(&manifest.cases[0]), "run");
+        assert_eq!(case_name(&manifest.cases[1]), "map_editor");
+        assert_eq!(manifest.cases[1].expect, Verdict::Fail);
+
+        std::fs::write(
+            &path,
+            "
+cases:
+  - save: good/run.zip
+    expect: pass
+  - save: bad/run.zip
+    expect: fail
+",
+        )
+        .unwrap();
+        let err = load_manifest(&path).unwrap_err();
+        assert!(err.to_string().contains("duplicate case name run"));
+    }
+
+    #[test]
+    fn test_check_case() {
+        let case = TestCase {
+            name: None,
+            save: PathBuf::from("map_editor.zip"),
+            rules: None,
+            expect: Verdict::Fail,
+            flagged_by: Some(BTreeSet::from(["no_map_editor".to_string()])),
+        };
+
+        let result = check_case(
+            &case,
+            &report(&[
+                (MsgLevel::Info, "log_time"),
+                (MsgLevel::Error, "no_map_editor"),
+            ]),
+        );
+        assert!(result.passed(), "{:?}", result.failures);
+
+        let result = check_case(&case, &report(&[(MsgLevel::Warn, "max_apm")]));
+        assert_eq!(result.verdict, Some(Verdict::Warn));
+        assert_eq!(
+            result.failures,
+            [
+                "expected verdict Fail, got Warn",
+                "not flagged by no_map_editor",
+                "unexpectedly flagged by max_apm",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verdict_like_daemon() {
+        assert_eq!(Verdict::of(&report(&[])), Verdict::Pass);
+        assert_eq!(
+            Verdict::of(&report(&[(MsgLevel::Warn, "max_apm")])),
+            Verdict::Warn
+        );
+        let partial = ReplayReport {
+            partial_verification: true,
+            ..report(&[])
+        };
+        assert_eq!(Verdict::of(&partial), Verdict::Warn);
+        let not_won = ReplayReport {
+            win_condition_not_completed: true,
+            ..report(&[])
+        };
+        assert_eq!(Verdict::of(&not_won), Verdict::Fail);
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let result = |name: &str, failures: &[&str], error: Option<&str>| CaseResult {
+            name: name.to_string(),
+            save: PathBuf::from(format!("{name}.zip")),
+            expect: Verdict::Pass,
+            verdict: Some(Verdict::Pass),
+            flagged_by: BTreeSet::new(),
+            failures: failures.iter().map(|f| f.to_string()).collect(),
+            error: error.map(str::to_string),
+            duration_secs: 1.5,
+        };
+        let xml = junit_report(&[
+            result("good", &[], None),
+            result("bad", &["expected verdict Fail, got Pass"], None),
+            result("broken", &[], Some("can't read <save>")),
+        ]);
+        assert!(xml.contains(
+            "<testsuite name=\"test-rules\" tests=\"3\" failures=\"1\" errors=\"1\" time=\"4.500\">"
+        ));
+        assert!(xml.contains("<testcase classname=\"test-rules\" name=\"good\" time=\"1.500\"/>"));
+        assert!(xml.contains("<failure message=\"expected verdict Fail, got Pass\"/>"));
+        assert!(xml.contains("<error message=\"can&apos;t read &lt;save&gt;\"/>"));
+    }
+}
@@ -1,6 +1,5 @@
 use anyhow::{Context, Result};
 use factorio_manager::error::FactorioError;
-use log::LevelFilter;
 use replay_script::ReplayScripts;
 use std::fs;
 use test_utils::{self, workspace_root};
@@ -11,9 +10,9 @@ const TEST_RUN_ID: &str = "zngelo7m"; // a steelaxe run
 const ALL_RULES_FILE: &str = "all_checks.yaml";
 
 fn init_test_logger() {
-    let _ = env_logger::builder()
-        .is_test(true)
-        .filter_level(LevelFilter::Debug)
+    let _ = tracing_subscriber::fmt()
+        .with_test_writer()
+        .with_env_filter("debug")
         .try_init();
 }
 
@@ -21,6 +20,13 @@ fn write_all_checks() {
     let fixtures_dir = test_utils::fixtures_dir();
     let mut all_scripts = ReplayScripts::all_enabled();
     all_scripts.required_research = vec!["steel-axe".to_string()];
+    // keep the transcript comparable with TEST_expected.txt
+    all_scripts.win_condition = None;
+    all_scripts.snapshot_interval = None;
+    all_scripts.stop_at_tick = None;
+    all_scripts.exit_grace_ticks = None;
+    all_scripts.log_all_commands = false;
+    all_scripts.audit_blueprint_library = false;
     let test_all_rules = RunRules {
         expected_mods_override: Some(
             ["base", "quality", "elevated-rails", "space-age"]
@@ -28,6 +34,20 @@ fn write_all_checks() {
                 .map(|s| s.to_string())
                 .collect(),
         ),
+        mod_policy: Default::default(),
+        allow_custom_control_lua: false,
+        script_injection: Default::default(),
+        install_version: Default::default(),
+        factorio_args: Vec::new(),
+        checkpoint_interval_minutes: None,
+        resource_limits: Default::default(),
+        replay_disk_headroom_mb: 256,
+        security: Default::default(),
+        verify_from_tick: 0,
+        segmented: false,
+        plugins: Vec::new(),
+        min_report_level: Default::default(),
+        rule_report_levels: Default::default(),
         replay_scripts: all_scripts,
     };
 
@@ -125,6 +145,66 @@ async fn test_no_replay_data_gives_detailed_error() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+#[ignore]
+async fn test_replay_segments_checks_win_condition_last() -> Result<()> {
+    use crate::daemon::run_processing::replay_segments;
+    use crate::daemon::speedrun_api::SpeedrunClient;
+
+    init_test_logger();
+    write_all_checks();
+
+    let test_dir = test_utils::test_tmp_dir().join("cli_segments_test");
+    let fixtures_dir = test_utils::fixtures_dir();
+    if test_dir.exists() {
+        fs::remove_dir_all(&test_dir).ok();
+    }
+    fs::create_dir_all(&test_dir)?;
+
+    let install_dir = load_install_dir(&test_utils::test_factorio_installs_dir()).await?;
+    let rules = load_run_rules(&fixtures_dir.join(ALL_RULES_FILE)).await?;
+    assert!(rules.replay_scripts.win_on_scenario_finished);
+    let expected_mods = rules.expected_mods_override.clone().unwrap();
+    let mut saves = Vec::new();
+    let mut log_paths = Vec::new();
+    for segment in 1..=2 {
+        let save_path = test_dir.join(format!("segment{segment}.zip"));
+        fs::copy(fixtures_dir.join("TEST.zip"), &save_path)?;
+        saves.push(load_save(&save_path).await?);
+        log_paths.push(test_dir.join(format!("output.segment{segment}.log")));
+    }
+
+    let client = SpeedrunClient::new()?;
+    let processor = RunProcessor::new(&client, &DownloadThrottles::default());
+    let report = replay_segments(
+        &processor,
+        &mut saves,
+        &log_paths,
+        &rules,
+        &expected_mods,
+        &install_dir,
+        &CancellationToken::new(),
+    )
+    .await?;
+
+    // TEST.zip never finishes the scenario, but only the last segment has to
+    assert!(report.win_condition_not_completed);
+    let unfinished: Vec<_> = report
+        .messages
+        .iter()
+        .filter(|message| message.contains("scenario never completed"))
+        .collect();
+    assert_eq!(
+        unfinished,
+        ["Segment 2: win_on_scenario_finished enabled but scenario never completed"]
+    );
+    assert!(!report.segments[0].win_condition_not_completed);
+    assert!(!fs::read_to_string(&log_paths[0])?.contains("VERIFICATION FAILED"));
+    assert!(fs::read_to_string(&log_paths[1])?.contains("VERIFICATION FAILED"));
+
+    Ok(())
+}
+
 #[tokio::test]
 #[ignore]
 async fn test_cli_run_src() -> Result<()> {
@@ -164,3 +244,40 @@ async fn test_cli_run_src() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_format_flag_is_global() {
+    use clap::Parser;
+    use output::OutputFormat;
+
+    let args = CliArgs::try_parse_from(["cli", "query", "stats", "--format", "json"]).unwrap();
+    assert_eq!(args.format, OutputFormat::Json);
+
+    let args = CliArgs::try_parse_from(["cli", "run", "save.zip", "rules.yaml"]).unwrap();
+    assert_eq!(args.format, OutputFormat::Text);
+}
+
+#[test]
+fn test_export_only_formats() {
+    use clap::Parser;
+    use output::OutputFormat;
+
+    let parse = |args: &[&str]| CliArgs::try_parse_from(args).and_then(CliArgs::check_format);
+    let args = parse(&["cli", "query", "export", "--format", "jsonl"]).unwrap();
+    assert_eq!(args.format, OutputFormat::Jsonl);
+    let args = parse(&["cli", "query", "export", "--format", "csv"]).unwrap();
+    assert_eq!(args.format, OutputFormat::Csv);
+    assert!(parse(&["cli", "query", "stats", "--format", "csv"]).is_err());
+}
+
+#[test]
+fn test_script_render_needs_rules_or_all() {
+    use clap::Parser;
+
+    assert!(CliArgs::try_parse_from(["cli", "script", "render", "--all"]).is_ok());
+    assert!(CliArgs::try_parse_from(["cli", "script", "render", "--rules", "r.yaml"]).is_ok());
+    assert!(CliArgs::try_parse_from(["cli", "script", "render"]).is_err());
+    assert!(
+        CliArgs::try_parse_from(["cli", "script", "render", "--all", "--rules", "r.yaml"]).is_err()
+    );
+}
@@ -0,0 +1,233 @@
+use anyhow::Result;
+use ratatui::crossterm::event::KeyCode;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use crate::daemon::database::connection::Database;
+use crate::daemon::database::types::{Run, RunFilter, RunOrder, RunStatus};
+use crate::daemon::speedrun_api::SpeedrunOps;
+
+const RECENT_LIMIT: u32 = 20;
+const ERRORS_LIMIT: u32 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    Recent,
+    Errors,
+}
+
+#[derive(Default)]
+pub struct Snapshot {
+    pub counts: HashMap<RunStatus, i64>,
+    pub processing: Vec<Run>,
+    pub recent: Vec<Run>,
+    pub errors: Vec<Run>,
+}
+
+pub struct App {
+    pub snapshot: Snapshot,
+    pub panel: Panel,
+    pub selected: usize,
+    /// Run awaiting delete confirmation
+    pub pending_delete: Option<String>,
+    pub status_line: Option<String>,
+    pub should_quit: bool,
+    names: HashMap<(String, String), String>,
+}
+
+impl App {
+    pub fn new() -> Self {
+        Self {
+            snapshot: Snapshot::default(),
+            panel: Panel::Recent,
+            selected: 0,
+            pending_delete: None,
+            status_line: None,
+            should_quit: false,
+            names: HashMap::new(),
+        }
+    }
+
+    pub async fn refresh(&mut self, db: &Database, ops: &SpeedrunOps) -> Result<()> {
+        let recent_filter = RunFilter {
+            statuses: vec![RunStatus::Passed, RunStatus::NeedsReview, RunStatus::Failed],
+            order: RunOrder::Updated,
+            limit: Some(RECENT_LIMIT),
+            ..Default::default()
+        };
+        let errors_filter = RunFilter {
+            statuses: vec![RunStatus::Error],
+            order: RunOrder::Updated,
+            limit: Some(ERRORS_LIMIT),
+            ..Default::default()
+        };
+        self.snapshot = Snapshot {
+            counts: db.count_runs_by_status().await?,
+            processing: db
+                .query_runs(RunFilter {
+                    statuses: vec![RunStatus::Processing],
+                    order: RunOrder::Updated,
+                    ..Default::default()
+                })
+                .await?,
+            recent: db.query_runs(recent_filter).await?,
+            errors: db.query_runs(errors_filter).await?,
+        };
+
+        let snapshot = &self.snapshot;
+        for run in snapshot
+            .processing
+            .iter()
+            .chain(&snapshot.recent)
+            .chain(&snapshot.errors)
+        {
+            let key = (run.game_id.clone(), run.category_id.clone());
+            if let Entry::Vacant(entry) = self.names.entry(key) {
+                entry.insert(
+                    ops.format_game_category(&run.game_id, &run.category_id)
+                        .await,
+                );
+            }
+        }
+
+        self.selected = self.selected.min(self.panel_runs().len().saturating_sub(1));
+        Ok(())
+    }
+
+    pub fn game_category<'a>(&'a self, run: &'a Run) -> &'a str {
+        self.names
+            .get(&(run.game_id.clone(), run.category_id.clone()))
+            .map(String::as_str)
+            .unwrap_or(&run.game_id)
+    }
+
+    pub fn panel_runs(&self) -> &[Run] {
+        match self.panel {
+            Panel::Recent => &self.snapshot.recent,
+            Panel::Errors => &self.snapshot.errors,
+        }
+    }
+
+    pub fn selected_run(&self) -> Option<&Run> {
+        self.panel_runs().get(self.selected)
+    }
+
+    pub async fn handle_key(
+        &mut self,
+        key: KeyCode,
+        db: &Database,
+        ops: &SpeedrunOps,
+    ) -> Result<()> {
+        if let Some(run_id) = self.pending_delete.take() {
+            if matches!(key, KeyCode::Char('y')) {
+                db.delete_runs(std::slice::from_ref(&run_id)).await?;
+                self.status_line = Some(format!("Deleted run {}", run_id));
+                self.refresh(db, ops).await?;
+            } else {
+                self.status_line = Some("Delete cancelled".to_string());
+            }
+            return Ok(());
+        }
+
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.should_quit = true,
+            KeyCode::Tab => {
+                self.panel = match self.panel {
+                    Panel::Recent => Panel::Errors,
+                    Panel::Errors => Panel::Recent,
+                };
+                self.selected = 0;
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.selected + 1 < self.panel_runs().len() {
+                    self.selected += 1;
+                }
+            }
+            KeyCode::Char('r') | KeyCode::Char('s') => {
+                let Some(run_id) = self.selected_run().map(|run| run.run_id.clone()) else {
+                    return Ok(());
+                };
+                let retry = key == KeyCode::Char('r');
+                self.status_line = Some(match db.reset_run(&run_id, retry).await? {
+                    Err(holder) => format!("Run {} is being processed by {}", run_id, holder),
+                    Ok(()) if retry => format!("Queued run {} for retry", run_id),
+                    Ok(()) => format!("Reset run {} to discovered", run_id),
+                });
+                self.refresh(db, ops).await?;
+            }
+            KeyCode::Char('x') => {
+                if let Some(run_id) = self.selected_run().map(|run| run.run_id.clone()) {
+                    self.status_line = Some(format!("Delete run {}? (y/n)", run_id));
+                    self.pending_delete = Some(run_id);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::database::types::NewRun;
+    use crate::daemon::speedrun_api::SpeedrunClient;
+
+    async fn setup() -> (Database, SpeedrunOps) {
+        let db = Database::in_memory().await.unwrap();
+        db.cache_game_name("game1", "Factorio").await.unwrap();
+        db.cache_category_name("cat1", "Any%").await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        for run_id in ["run1", "run2", "run3"] {
+            db.insert_run(NewRun::new(run_id, "game1", "cat1", submitted_date))
+                .await
+                .unwrap();
+        }
+        db.mark_run_passed("run1").await.unwrap();
+        db.mark_run_error("run2", "download failed").await.unwrap();
+        db.mark_run_processing("run3").await.unwrap();
+        let ops = SpeedrunOps::new(&SpeedrunClient::new().unwrap()).with_db(db.clone());
+        (db, ops)
+    }
+
+    #[tokio::test]
+    async fn test_refresh_groups_runs() {
+        let (db, ops) = setup().await;
+        let mut app = App::new();
+        app.refresh(&db, &ops).await.unwrap();
+
+        assert_eq!(app.snapshot.processing[0].run_id, "run3");
+        assert_eq!(app.snapshot.recent[0].run_id, "run1");
+        assert_eq!(app.snapshot.errors[0].run_id, "run2");
+        assert_eq!(app.snapshot.counts[&RunStatus::Error], 1);
+        assert_eq!(
+            app.game_category(&app.snapshot.recent[0]),
+            "Factorio / Any%"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_and_delete_keys() {
+        let (db, ops) = setup().await;
+        let mut app = App::new();
+        app.refresh(&db, &ops).await.unwrap();
+
+        app.handle_key(KeyCode::Tab, &db, &ops).await.unwrap();
+        assert_eq!(app.selected_run().unwrap().run_id, "run2");
+        app.handle_key(KeyCode::Char('r'), &db, &ops).await.unwrap();
+        let run = db.get_run("run2").await.unwrap().unwrap();
+        assert_eq!(run.status, RunStatus::Discovered);
+        assert!(app.snapshot.errors.is_empty());
+
+        app.handle_key(KeyCode::Tab, &db, &ops).await.unwrap();
+        app.handle_key(KeyCode::Char('x'), &db, &ops).await.unwrap();
+        app.handle_key(KeyCode::Char('n'), &db, &ops).await.unwrap();
+        assert!(db.get_run("run1").await.unwrap().is_some());
+
+        app.handle_key(KeyCode::Char('x'), &db, &ops).await.unwrap();
+        app.handle_key(KeyCode::Char('y'), &db, &ops).await.unwrap();
+        assert!(db.get_run("run1").await.unwrap().is_none());
+        assert!(app.snapshot.recent.is_empty());
+    }
+}
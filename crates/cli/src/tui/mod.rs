@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::daemon::database::connection::Database;
+use crate::daemon::speedrun_api::{SpeedrunClient, SpeedrunOps};
+
+mod app;
+mod ui;
+
+use app::App;
+
+#[derive(Args)]
+pub struct TuiArgs {
+    /// SQLite database file path
+    #[arg(long, default_value = "run_verification.db")]
+    pub database: PathBuf,
+
+    /// Connection URL used instead of --database, e.g.
+    /// "postgres://verifier@db.internal/runs"
+    #[arg(long)]
+    pub database_url: Option<String>,
+
+    /// Seconds between refreshes
+    #[arg(long, default_value = "2")]
+    pub refresh_seconds: u64,
+}
+
+pub async fn handle_tui_command(args: TuiArgs, token: CancellationToken) -> Result<()> {
+    let db = Database::open_url_or_path(args.database_url.as_deref(), &args.database)
+        .await?
+        .with_actor("tui");
+    let speedrun_client = SpeedrunClient::new().context("Failed to create speedrun client")?;
+    let speedrun_ops = SpeedrunOps::new(&speedrun_client).with_db(db.clone());
+
+    // log lines would draw over the dashboard
+    log::set_max_level(log::LevelFilter::Off);
+    let mut terminal = ratatui::try_init()?;
+    let result = run_app(
+        &mut terminal,
+        &db,
+        &speedrun_ops,
+        Duration::from_secs(args.refresh_seconds),
+        token,
+    )
+    .await;
+    ratatui::restore();
+    result
+}
+
+async fn run_app(
+    terminal: &mut DefaultTerminal,
+    db: &Database,
+    ops: &SpeedrunOps,
+    refresh_interval: Duration,
+    token: CancellationToken,
+) -> Result<()> {
+    let mut app = App::new();
+    app.refresh(db, ops).await?;
+    let mut last_refresh = Instant::now();
+
+    while !app.should_quit && !token.is_cancelled() {
+        terminal.draw(|frame| ui::render(frame, &app))?;
+
+        let key = tokio::task::spawn_blocking(|| -> std::io::Result<_> {
+            if event::poll(Duration::from_millis(250))?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                return Ok(Some(key));
+            }
+            Ok(None)
+        })
+        .await??;
+
+        if let Some(key) = key {
+            // raw mode swallows SIGINT
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                break;
+            }
+            app.handle_key(key.code, db, ops).await?;
+        }
+        if last_refresh.elapsed() >= refresh_interval {
+            app.refresh(db, ops).await?;
+            last_refresh = Instant::now();
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use chrono::Utc;
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState, Tabs};
+
+use crate::daemon::database::types::{Run, RunStatus};
+use crate::query::common::format_status;
+
+use super::app::{App, Panel};
+
+const KEY_HELP: &str = "Tab switch  ↑/↓ select  r retry  s reset  x delete  q quit";
+
+pub fn render(frame: &mut Frame, app: &App) {
+    let processing_height = app.snapshot.processing.len().max(1) as u16 + 3;
+    let [queue_area, processing_area, panel_area, footer_area] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(processing_height),
+        Constraint::Min(5),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(queue_summary(app), queue_area);
+    frame.render_widget(processing_table(app), processing_area);
+
+    let [tabs_area, list_area] =
+        Layout::vertical([Constraint::Length(1), Constraint::Min(3)]).areas(panel_area);
+    let tabs = Tabs::new(["Recent completions", "Errors"])
+        .select(match app.panel {
+            Panel::Recent => 0,
+            Panel::Errors => 1,
+        })
+        .highlight_style(Style::new().bold().reversed());
+    frame.render_widget(tabs, tabs_area);
+    let mut state = TableState::default().with_selected(Some(app.selected));
+    frame.render_stateful_widget(panel_table(app), list_area, &mut state);
+
+    let footer = app.status_line.as_deref().unwrap_or(KEY_HELP);
+    frame.render_widget(Paragraph::new(footer).dim(), footer_area);
+}
+
+fn queue_summary(app: &App) -> Paragraph<'static> {
+    let count = |status| app.snapshot.counts.get(&status).copied().unwrap_or(0);
+    let summary = [
+        RunStatus::Discovered,
+        RunStatus::Processing,
+        RunStatus::Passed,
+        RunStatus::NeedsReview,
+        RunStatus::Failed,
+        RunStatus::Error,
+    ]
+    .into_iter()
+    .map(|status| format!("{}: {}", format_status(&status), count(status)))
+    .collect::<Vec<_>>()
+    .join("   ");
+    Paragraph::new(Line::from(summary)).block(Block::bordered().title("Queue"))
+}
+
+fn processing_table(app: &App) -> Table<'_> {
+    let now = Utc::now();
+    let rows = app.snapshot.processing.iter().map(|run| {
+        let elapsed = (now - run.updated_at).num_seconds().max(0);
+        Row::new(vec![
+            run.run_id.clone(),
+            app.game_category(run).to_string(),
+            format!("{}m {:02}s", elapsed / 60, elapsed % 60),
+        ])
+    });
+    Table::new(
+        rows,
+        [
+            Constraint::Length(10),
+            Constraint::Fill(1),
+            Constraint::Length(10),
+        ],
+    )
+    .header(Row::new(["Run", "Game / Category", "Elapsed"]).bold())
+    .block(Block::bordered().title("Processing"))
+}
+
+fn panel_table(app: &App) -> Table<'_> {
+    let rows = app.panel_runs().iter().map(|run| run_row(app, run));
+    Table::new(
+        rows,
+        [
+            Constraint::Length(10),
+            Constraint::Length(30),
+            Constraint::Length(13),
+            Constraint::Length(16),
+            Constraint::Fill(1),
+        ],
+    )
+    .header(Row::new(["Run", "Game / Category", "Status", "Updated", "Message"]).bold())
+    .block(Block::bordered())
+    .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+}
+
+fn run_row<'a>(app: &'a App, run: &'a Run) -> Row<'a> {
+    Row::new(vec![
+        run.run_id.clone(),
+        app.game_category(run).to_string(),
+        format_status(&run.status),
+        run.updated_at.format("%Y-%m-%d %H:%M").to_string(),
+        run.error_message.clone().unwrap_or_default(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::database::connection::Database;
+    use crate::daemon::database::types::NewRun;
+    use crate::daemon::speedrun_api::{SpeedrunClient, SpeedrunOps};
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    #[tokio::test]
+    async fn test_render_dashboard() {
+        let db = Database::in_memory().await.unwrap();
+        db.cache_game_name("game1", "Factorio").await.unwrap();
+        db.cache_category_name("cat1", "Any%").await.unwrap();
+        let submitted_date = "2024-01-01T00:00:00Z".parse().unwrap();
+        db.insert_run(NewRun::new("run1", "game1", "cat1", submitted_date))
+            .await
+            .unwrap();
+        db.mark_run_needs_review("run1", Some("used /editor"))
+            .await
+            .unwrap();
+        let ops = SpeedrunOps::new(&SpeedrunClient::new().unwrap()).with_db(db.clone());
+        let mut app = App::new();
+        app.refresh(&db, &ops).await.unwrap();
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 20)).unwrap();
+        terminal.draw(|frame| render(frame, &app)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("needs_review: 1"));
+        assert!(screen.contains("Factorio / Any%"));
+        assert!(screen.contains("used /editor"));
+        assert!(screen.contains(KEY_HELP));
+    }
+}
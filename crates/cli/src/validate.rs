@@ -0,0 +1,394 @@
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand, ValueEnum};
+use replay_script::ReplayScripts;
+use schemars::{Schema, schema_for};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::config::RunRules;
+use crate::daemon::category_sync::{CategoryChange, check_categories};
+use crate::daemon::config::{DaemonConfig, SrcRunRules};
+use crate::daemon::speedrun_api::SpeedrunClient;
+use crate::output::{OutputFormat, print_json};
+
+#[derive(Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub subcommand: ConfigSubcommand,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigSubcommand {
+    /// Check the daemon config and game rules, and print the effective config
+    Validate(ValidateArgs),
+    /// Compare the game rules' categories to speedrun.com's, reporting renamed, deleted and
+    /// unconfigured categories
+    CheckCategories(CheckCategoriesArgs),
+    /// Print the JSON Schema of a config file, for editor completion and validation, e.g.
+    /// with `# yaml-language-server: $schema=daemon.schema.json`
+    Schema(SchemaArgs),
+}
+
+#[derive(Args)]
+pub struct ValidateArgs {
+    /// Daemon configuration (yaml)
+    #[arg(short, long, default_value = "./daemon.yaml")]
+    pub config: PathBuf,
+
+    /// Game rules to check instead of the daemon config's game_rules_file
+    #[arg(long)]
+    pub rules: Option<PathBuf>,
+
+    /// Don't look up games and categories on speedrun.com
+    #[arg(long)]
+    pub offline: bool,
+}
+
+#[derive(Args)]
+pub struct CheckCategoriesArgs {
+    /// Daemon configuration (yaml); its database has the category names renames are found from
+    #[arg(short, long, default_value = "./daemon.yaml")]
+    pub config: PathBuf,
+
+    /// Game rules to check instead of the daemon config's game_rules_file
+    #[arg(long)]
+    pub rules: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct SchemaArgs {
+    #[arg(value_enum)]
+    pub file: ConfigFile,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ConfigFile {
+    /// The daemon config, daemon.yaml
+    Daemon,
+    /// The game rules, speedrun_rules.yaml
+    GameRules,
+    /// RUN rules, as given to `run` and `run-batch`
+    RunRules,
+    /// The replay script settings of RUN rules and categories
+    ReplayScripts,
+}
+
+#[derive(Serialize)]
+struct ValidateOutput<'a> {
+    problems: &'a [String],
+    config: EffectiveConfig<'a>,
+}
+
+#[derive(Serialize)]
+struct EffectiveConfig<'a> {
+    daemon: &'a DaemonConfig,
+    games: BTreeMap<&'a str, EffectiveGame<'a>>,
+}
+
+#[derive(Serialize)]
+struct EffectiveGame<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    categories: BTreeMap<&'a str, EffectiveCategory>,
+}
+
+#[derive(Serialize)]
+struct EffectiveCategory {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// With the game's expected mods filled in.
+    #[serde(flatten)]
+    rules: RunRules,
+}
+
+pub async fn handle_config_command(args: ConfigArgs, format: OutputFormat) -> Result<()> {
+    match args.subcommand {
+        ConfigSubcommand::Validate(validate_args) => handle_validate(validate_args, format).await,
+        ConfigSubcommand::CheckCategories(check_args) => {
+            handle_check_categories(check_args, format).await
+        }
+        ConfigSubcommand::Schema(schema_args) => print_json(&config_schema(schema_args.file)),
+    }
+}
+
+fn load_configs(config: &Path, rules: Option<PathBuf>) -> Result<(DaemonConfig, SrcRunRules)> {
+    let daemon_config = DaemonConfig::load(config)
+        .with_context(|| format!("Invalid daemon config {}", config.display()))?;
+    let rules_path = rules.unwrap_or_else(|| daemon_config.game_rules_file.clone());
+    let rules_file = File::open(&rules_path)
+        .with_context(|| format!("Failed to read game rules {}", rules_path.display()))?;
+    // unknown fields, such as misspelled replay script options, are rejected here
+    let src_rules: SrcRunRules = serde_yaml::from_reader(rules_file)
+        .with_context(|| format!("Invalid game rules {}", rules_path.display()))?;
+    Ok((daemon_config, src_rules))
+}
+
+async fn handle_validate(args: ValidateArgs, format: OutputFormat) -> Result<()> {
+    let (daemon_config, src_rules) = load_configs(&args.config, args.rules)?;
+
+    let mut problems = unknown_scheduling_keys(&daemon_config, &src_rules);
+    problems.extend(invalid_replay_scripts(&src_rules));
+
+    let client = if args.offline {
+        None
+    } else {
+        Some(SpeedrunClient::new()?)
+    };
+    let mut games = BTreeMap::new();
+    for (game_id, game_config) in &src_rules.games {
+        let name = match &client {
+            Some(client) => match client.get_game(game_id).await {
+                Ok(game) => Some(game.names.international),
+                Err(e) => {
+                    problems.push(format!("game {}: {}", game_id, e));
+                    None
+                }
+            },
+            None => None,
+        };
+        let mut categories = BTreeMap::new();
+        for category_id in game_config.categories.keys() {
+            let name = match &client {
+                Some(client) => match client.get_category(category_id).await {
+                    Ok(category) => Some(category.name),
+                    Err(e) => {
+                        problems.push(format!("{}/{}: {}", game_id, category_id, e));
+                        None
+                    }
+                },
+                None => None,
+            };
+            let (run_rules, expected_mods) = src_rules.resolve_rules(game_id, category_id)?;
+            let mut rules = run_rules.clone();
+            rules.expected_mods_override = Some(expected_mods.clone());
+            categories.insert(category_id.as_str(), EffectiveCategory { name, rules });
+        }
+        games.insert(game_id.as_str(), EffectiveGame { name, categories });
+    }
+
+    let config = EffectiveConfig {
+        daemon: &daemon_config,
+        games,
+    };
+    if format.is_json() {
+        print_json(&ValidateOutput {
+            problems: &problems,
+            config,
+        })?;
+    } else {
+        print!("{}", serde_yaml::to_string(&config)?);
+        for problem in &problems {
+            eprintln!("Problem: {}", problem);
+        }
+    }
+
+    if !problems.is_empty() {
+        bail!("Found {} problem(s) in the configuration", problems.len());
+    }
+    Ok(())
+}
+
+async fn handle_check_categories(args: CheckCategoriesArgs, format: OutputFormat) -> Result<()> {
+    let (daemon_config, src_rules) = load_configs(&args.config, args.rules)?;
+    let db = daemon_config
+        .open_database()
+        .await
+        .context("Failed to open database")?;
+    let client = SpeedrunClient::new()?;
+    let changes: Vec<CategoryChange> = check_categories(&client, &db, &src_rules).await?;
+
+    if format.is_json() {
+        print_json(&changes)?;
+    } else if changes.is_empty() {
+        println!("Game rules match speedrun.com's categories");
+    } else {
+        for change in &changes {
+            println!("{}", change);
+        }
+    }
+
+    if !changes.is_empty() {
+        bail!("Found {} out of date categories", changes.len());
+    }
+    Ok(())
+}
+
+fn config_schema(file: ConfigFile) -> Schema {
+    match file {
+        ConfigFile::Daemon => schema_for!(DaemonConfig),
+        ConfigFile::GameRules => schema_for!(SrcRunRules),
+        ConfigFile::RunRules => schema_for!(RunRules),
+        ConfigFile::ReplayScripts => schema_for!(ReplayScripts),
+    }
+}
+
+/// Scheduling policies and poll intervals for games or categories that aren't in the game rules.
+fn unknown_scheduling_keys(daemon_config: &DaemonConfig, src_rules: &SrcRunRules) -> Vec<String> {
+    let is_unknown = |key: &str| {
+        let (game_id, category_id) = match key.split_once('/') {
+            Some((game_id, category_id)) => (game_id, Some(category_id)),
+            None => (key, None),
+        };
+        let Some(game_config) = src_rules.games.get(game_id) else {
+            return true;
+        };
+        category_id.is_some_and(|category_id| !game_config.categories.contains_key(category_id))
+    };
+    let scheduling = daemon_config
+        .scheduling
+        .keys()
+        .filter(|key| *key != "default")
+        .map(|key| ("scheduling", key));
+    let intervals = daemon_config
+        .polling
+        .intervals
+        .keys()
+        .map(|key| ("polling.intervals", key));
+    let mut problems: Vec<String> = scheduling
+        .chain(intervals)
+        .filter(|(_, key)| is_unknown(key))
+        .map(|(section, key)| format!("{}: no game rules for `{}`", section, key))
+        .collect();
+    problems.sort();
+    problems
+}
+
+/// Categories whose replay scripts can't verify their runs, such as a `max_players` of 0.
+fn invalid_replay_scripts(src_rules: &SrcRunRules) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (game_id, game_config) in &src_rules.games {
+        for (category_id, category_config) in &game_config.categories {
+            if let Err(e) = category_config.run_rules.replay_scripts.validate() {
+                problems.push(format!("{}/{}: {}", game_id, category_id, e));
+            }
+        }
+    }
+    problems.sort();
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = "
+games:
+  game1:
+    expected_mods: [base]
+    categories:
+      cat1:
+        max_apm: 400
+";
+
+    #[test]
+    fn test_unknown_scheduling_keys() {
+        let src_rules: SrcRunRules = serde_yaml::from_str(RULES).unwrap();
+        let daemon_config: DaemonConfig = serde_yaml::from_str(
+            "
+scheduling:
+  default: {}
+  game1: {}
+  game1/cat1: {}
+  game1/cat2: {}
+  game2: {}
+polling:
+  intervals:
+    game1/cat1: 60
+    game3: 60
+",
+        )
+        .unwrap();
+        assert_eq!(
+            unknown_scheduling_keys(&daemon_config, &src_rules),
+            [
+                "polling.intervals: no game rules for `game3`",
+                "scheduling: no game rules for `game1/cat2`",
+                "scheduling: no game rules for `game2`",
+            ]
+        );
+    }
+
+    fn schema_errors(file: ConfigFile, yaml: &str) -> Vec<String> {
+        let schema = serde_json::to_value(config_schema(file)).unwrap();
+        let instance: serde_json::Value = serde_yaml::from_str(yaml).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        validator
+            .iter_errors(&instance)
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_config_schema() {
+        let root = test_utils::workspace_root();
+        for (file, path) in [
+            (ConfigFile::Daemon, "daemon.yaml"),
+            (ConfigFile::Daemon, "daemon-dev.yaml"),
+            (ConfigFile::GameRules, "speedrun_rules.yaml"),
+        ] {
+            let yaml = std::fs::read_to_string(root.join(path)).unwrap();
+            assert_eq!(schema_errors(file, &yaml), Vec::<String>::new(), "{path}");
+        }
+        assert!(schema_errors(ConfigFile::GameRules, RULES).is_empty());
+
+        assert!(
+            schema_errors(
+                ConfigFile::RunRules,
+                "{ expected_mods: [base], win_condition: { type: rocket_launched } }"
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            schema_errors(ConfigFile::RunRules, "{ max_player: 2 }").len(),
+            1
+        );
+        assert_eq!(
+            schema_errors(
+                ConfigFile::GameRules,
+                "
+games:
+  game1:
+    expected_mods: [base]
+    categories:
+      cat1:
+        extends: [scenario]
+        install_version: 2.0
+"
+            )
+            .len(),
+            1
+        );
+        assert_eq!(
+            schema_errors(ConfigFile::ReplayScripts, "{ max_players: -1 }").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_invalid_replay_scripts() {
+        let src_rules: SrcRunRules = serde_yaml::from_str(
+            "
+games:
+  game1:
+    expected_mods: [base]
+    categories:
+      cat1:
+        win_on_scenario_finished: true
+      cat2:
+        allowed_surfaces: []
+      cat3:
+        max_players: 0
+",
+        )
+        .unwrap();
+        assert_eq!(
+            invalid_replay_scripts(&src_rules),
+            [
+                "game1/cat2: allowed_surfaces allows nothing; leave it out instead",
+                "game1/cat3: max_players must be at least 1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! Installs laid out as a macOS app bundle, `factorio.app`, which keeps the game data inside
+//! the bundle and by default writes to ~/Library/Application Support/factorio.
+
+use log::warn;
+use std::io;
+use std::path::Path;
+
+pub const APP_BUNDLE: &str = "factorio.app";
+
+/// The Factorio executable inside the bundle, relative to an installation.
+pub const BUNDLE_BINARY: &str = "factorio.app/Contents/MacOS/factorio";
+
+/// Factorio reads this from the bundle's application directory, `Contents`.
+const CONFIG_PATH_CFG: &str = "factorio.app/Contents/config-path.cfg";
+
+const CONFIG_PATH: &str = "\
+config-path=__PATH__executable__/../../../config
+use-system-read-write-data-directories=false
+";
+
+const CONFIG_INI: &str = "\
+[path]
+read-data=__PATH__executable__/../data
+write-data=__PATH__executable__/../../..
+";
+
+pub fn is_app_bundle(install_dir: &Path) -> bool {
+    install_dir.join(BUNDLE_BINARY).is_file()
+}
+
+/// Makes the bundle in `install_dir` run like other installs: Factorio writes its log, mods
+/// and saves to `install_dir`, and Gatekeeper won't block the quarantined binary of a
+/// downloaded bundle. Prepared bundles are left alone.
+pub fn prepare_app_bundle(install_dir: &Path) -> io::Result<()> {
+    let config_path_cfg = install_dir.join(CONFIG_PATH_CFG);
+    if config_path_cfg.exists() {
+        return Ok(());
+    }
+    if cfg!(target_os = "macos") {
+        remove_quarantine(&install_dir.join(APP_BUNDLE));
+    }
+    let config_dir = install_dir.join("config");
+    std::fs::create_dir_all(&config_dir)?;
+    let config_ini = config_dir.join("config.ini");
+    if !config_ini.exists() {
+        std::fs::write(config_ini, CONFIG_INI)?;
+    }
+    std::fs::write(config_path_cfg, CONFIG_PATH)
+}
+
+fn remove_quarantine(bundle: &Path) {
+    let status = std::process::Command::new("xattr")
+        .args(["-dr", "com.apple.quarantine"])
+        .arg(bundle)
+        .status();
+    if let Err(e) = status {
+        warn!(
+            "Failed to clear quarantine of {}; Gatekeeper may block Factorio: {e}",
+            bundle.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::factorio_instance::FactorioInstance;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_prepare_app_bundle() -> io::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let install_dir = temp_dir.path();
+        assert!(!is_app_bundle(install_dir));
+
+        std::fs::create_dir_all(install_dir.join("factorio.app/Contents/MacOS"))?;
+        std::fs::write(install_dir.join(BUNDLE_BINARY), "")?;
+        assert!(is_app_bundle(install_dir));
+        let factorio = FactorioInstance::new(install_dir.to_path_buf()).unwrap();
+        assert_eq!(
+            factorio.binary_path(),
+            factorio.install_dir().join(BUNDLE_BINARY)
+        );
+
+        prepare_app_bundle(install_dir)?;
+        let config_path = std::fs::read_to_string(install_dir.join(CONFIG_PATH_CFG))?;
+        assert!(config_path.contains("use-system-read-write-data-directories=false"));
+        let config_ini = std::fs::read_to_string(install_dir.join("config/config.ini"))?;
+        assert!(config_ini.contains("write-data=__PATH__executable__/../../.."));
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+//! Checkpoints of long replays, from Factorio's autosaves. Factorio writes them to
+//! `saves/_autosave<N>.zip` in an installation's write data, every `autosave-interval`
+//! minutes set in its `config/config.ini`.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const AUTOSAVE_PREFIX: &str = "_autosave";
+const CONFIG_INI: &str = "config/config.ini";
+const OTHER_SECTION: &str = "other";
+const AUTOSAVE_INTERVAL: &str = "autosave-interval";
+
+/// Sets how often Factorio in `install_dir` autosaves, in minutes; None restores the default.
+pub fn set_autosave_interval(install_dir: &Path, minutes: Option<u32>) -> io::Result<()> {
+    let config_path = install_dir.join(CONFIG_INI);
+    let config = match std::fs::read_to_string(&config_path) {
+        Ok(config) => config,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && minutes.is_none() => return Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let value = minutes.map(|minutes| minutes.to_string());
+    let updated = with_ini_value(&config, OTHER_SECTION, AUTOSAVE_INTERVAL, value.as_deref());
+    if updated != config {
+        std::fs::create_dir_all(config_path.parent().unwrap())?;
+        std::fs::write(config_path, updated)?;
+    }
+    Ok(())
+}
+
+/// `ini` with `key` in `section` set to `value`, or removed if None.
+fn with_ini_value(ini: &str, section: &str, key: &str, value: Option<&str>) -> String {
+    let header = format!("[{}]", section);
+    let entry = value.map(|value| format!("{}={}", key, value));
+    let mut lines = Vec::new();
+    let mut in_section = false;
+    let mut written = false;
+    for line in ini.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            if in_section && !written {
+                lines.extend(entry.clone());
+                written = true;
+            }
+            in_section = trimmed == header;
+            lines.push(line.to_string());
+            continue;
+        }
+        let is_key = trimmed
+            .split_once('=')
+            .is_some_and(|(name, _)| name.trim() == key);
+        if in_section && is_key {
+            if !written {
+                lines.extend(entry.clone());
+                written = true;
+            }
+            continue;
+        }
+        lines.push(line.to_string());
+    }
+    if let Some(entry) = entry.filter(|_| !written) {
+        if !in_section {
+            lines.push(header);
+        }
+        lines.push(entry);
+    }
+    let mut ini = lines.join("\n");
+    ini.push('\n');
+    ini
+}
+
+fn autosaves(install_dir: &Path) -> io::Result<Vec<(PathBuf, SystemTime)>> {
+    let saves_dir = install_dir.join("saves");
+    let entries = match std::fs::read_dir(&saves_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut autosaves = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(AUTOSAVE_PREFIX) && name.ends_with(".zip") {
+            autosaves.push((entry.path(), entry.metadata()?.modified()?));
+        }
+    }
+    Ok(autosaves)
+}
+
+/// Removes the autosaves in `install_dir`, so those of an earlier replay aren't mistaken for
+/// checkpoints of the next one.
+pub fn remove_autosaves(install_dir: &Path) -> io::Result<()> {
+    for (path, _) in autosaves(install_dir)? {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Moves the newest autosave in `install_dir` to `dest` and removes the others. Returns
+/// false if there was none.
+pub fn take_latest_autosave(install_dir: &Path, dest: &Path) -> io::Result<bool> {
+    let mut all = autosaves(install_dir)?;
+    all.sort_by_key(|(_, modified)| *modified);
+    let Some((latest, _)) = all.pop() else {
+        return Ok(false);
+    };
+    if std::fs::rename(&latest, dest).is_err() {
+        // across file systems
+        std::fs::copy(&latest, dest)?;
+        std::fs::remove_file(&latest)?;
+    }
+    for (path, _) in all {
+        std::fs::remove_file(path)?;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_with_ini_value() {
+        let config = "[path]\nwrite-data=.\n\n[other]\n; autosave-interval=5\nautosave-slots=3\n";
+        assert_eq!(
+            with_ini_value(config, "other", "autosave-interval", Some("10")),
+            "[path]\nwrite-data=.\n\n[other]\n; autosave-interval=5\nautosave-slots=3\nautosave-interval=10\n"
+        );
+        let set = "[other]\nautosave-interval=10\n[sound]\nvolume=1\n";
+        assert_eq!(
+            with_ini_value(set, "other", "autosave-interval", Some("30")),
+            "[other]\nautosave-interval=30\n[sound]\nvolume=1\n"
+        );
+        assert_eq!(
+            with_ini_value(set, "other", "autosave-interval", None),
+            "[other]\n[sound]\nvolume=1\n"
+        );
+        assert_eq!(
+            with_ini_value(
+                "[path]\nwrite-data=.\n",
+                "other",
+                "autosave-interval",
+                Some("5")
+            ),
+            "[path]\nwrite-data=.\n[other]\nautosave-interval=5\n"
+        );
+    }
+
+    #[test]
+    fn test_set_autosave_interval() -> io::Result<()> {
+        let install_dir = TempDir::new()?;
+        set_autosave_interval(install_dir.path(), None)?;
+        assert!(!install_dir.path().join(CONFIG_INI).exists());
+
+        set_autosave_interval(install_dir.path(), Some(10))?;
+        let config = std::fs::read_to_string(install_dir.path().join(CONFIG_INI))?;
+        assert_eq!(config, "[other]\nautosave-interval=10\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_take_latest_autosave() -> io::Result<()> {
+        let install_dir = TempDir::new()?;
+        let saves = install_dir.path().join("saves");
+        std::fs::create_dir_all(&saves)?;
+        let write_save = |name: &str, contents: &str, age_secs: u64| -> io::Result<()> {
+            std::fs::write(saves.join(name), contents)?;
+            std::fs::File::options()
+                .write(true)
+                .open(saves.join(name))?
+                .set_modified(SystemTime::now() - Duration::from_secs(age_secs))
+        };
+        write_save("_autosave1.zip", "older", 20)?;
+        write_save("_autosave2.zip", "newer", 10)?;
+        write_save("run.zip", "run", 0)?;
+        let dest = TempDir::new()?;
+        let checkpoint = dest.path().join("checkpoint.zip");
+
+        assert!(take_latest_autosave(install_dir.path(), &checkpoint)?);
+        assert_eq!(std::fs::read_to_string(&checkpoint)?, "newer");
+        assert!(!saves.join("_autosave1.zip").exists());
+        assert!(saves.join("run.zip").exists());
+
+        assert!(!take_latest_autosave(install_dir.path(), &checkpoint)?);
+        write_save("_autosave1.zip", "stale", 0)?;
+        remove_autosaves(install_dir.path())?;
+        assert!(!take_latest_autosave(install_dir.path(), &checkpoint)?);
+        Ok(())
+    }
+}
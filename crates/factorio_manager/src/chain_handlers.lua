@@ -0,0 +1,136 @@
+-- Chains event handlers registered by the replay script with those registered by the
+-- save's own scripts, so neither replaces the other's.
+local real_script = script
+local handlers = { replay = {}, save = {} }
+local filters = { replay = {}, save = {} }
+
+-- The core event_handler is one module shared by the replay script and the save, which
+-- registers a single handler dispatching to every lib added to it, so its registrations
+-- already run the save's libs too. Counting them as the replay script's leaves only the
+-- save's direct registrations to chain with. A scenario's own copy of event_handler is a
+-- separate module, so it counts as the save's. Installing replaces any replay_script.lua
+-- at the save's root, so only the replay script is loaded from that file; a file of the same
+-- name in one of the save's folders is the save's.
+local replay_sources = {
+  ["@__core__/lualib/event_handler.lua"] = true,
+  ["@__level__/replay_script.lua"] = true,
+}
+
+local function caller_owner()
+  local source = debug.getinfo(3, "S").source
+  if replay_sources[source] then
+    return "replay"
+  end
+  return "save"
+end
+
+local function matches_any(value, names)
+  if type(names) ~= "table" then
+    return value == names
+  end
+  for _, name in pairs(names) do
+    if value == name then
+      return true
+    end
+  end
+  return false
+end
+
+local entity_filters = {
+  name = function(entity, filter)
+    return matches_any(entity.name, filter.name)
+  end,
+  type = function(entity, filter)
+    return matches_any(entity.type, filter.type)
+  end,
+  ghost = function(entity)
+    return entity.type == "entity-ghost" or entity.type == "tile-ghost"
+  end,
+  ghost_name = function(entity, filter)
+    return entity.type == "entity-ghost" and matches_any(entity.ghost_name, filter.name)
+  end,
+  ghost_type = function(entity, filter)
+    return entity.type == "entity-ghost" and matches_any(entity.ghost_type, filter.type)
+  end,
+}
+
+-- Whether an event passes a handler's filters, combined as Factorio does, with "and" binding
+-- tighter than "or". Filters on anything but the event's entity name and type aren't
+-- evaluated here, and let every event through: a handler may see more events than it asked
+-- for, but never fewer.
+local function passes(event_filters, event)
+  if not event_filters or #event_filters == 0 then
+    return true
+  end
+  local entity = type(event) == "table" and (event.entity or event.created_entity)
+  if not entity then
+    return true
+  end
+  local any, all = false, true
+  for i, filter in ipairs(event_filters) do
+    local check = entity_filters[filter.filter]
+    if not check then
+      return true
+    end
+    if i > 1 and filter.mode ~= "and" then
+      any = any or all
+      all = true
+    end
+    all = all and (check(entity, filter) == not filter.invert)
+  end
+  return any or all
+end
+
+-- The handler to register for `key`, and the filters to register it with. When both
+-- owners have a handler, the combined handler is registered without filters, and applies
+-- each owner's filters itself.
+local function set_handler(owner, key, handler, owner_filters)
+  handlers[owner][key] = handler
+  filters[owner][key] = owner_filters
+  local first, second = handlers.replay[key], handlers.save[key]
+  if not (first and second) then
+    return first or second, filters[first and "replay" or "save"][key]
+  end
+  local first_filters, second_filters = filters.replay[key], filters.save[key]
+  return function(...)
+    if passes(first_filters, ...) then
+      first(...)
+    end
+    if passes(second_filters, ...) then
+      second(...)
+    end
+  end
+end
+
+script = setmetatable({
+  on_init = function(handler)
+    real_script.on_init((set_handler(caller_owner(), "init", handler)))
+  end,
+  on_load = function(handler)
+    real_script.on_load((set_handler(caller_owner(), "load", handler)))
+  end,
+  on_configuration_changed = function(handler)
+    real_script.on_configuration_changed((set_handler(caller_owner(), "configuration_changed", handler)))
+  end,
+  on_event = function(event, handler, event_filters)
+    local owner = caller_owner()
+    for _, e in pairs(type(event) == "table" and event or { event }) do
+      real_script.on_event(e, set_handler(owner, "event_" .. tostring(e), handler, event_filters))
+    end
+  end,
+  on_nth_tick = function(tick, handler)
+    local owner = caller_owner()
+    if tick == nil then
+      for key in pairs(handlers[owner]) do
+        local n = key:match("^nth_tick_(%d+)$")
+        if n then
+          real_script.on_nth_tick(tonumber(n), (set_handler(owner, key, nil)))
+        end
+      end
+      return
+    end
+    for _, t in pairs(type(tick) == "table" and tick or { tick }) do
+      real_script.on_nth_tick(t, (set_handler(owner, "nth_tick_" .. t, handler)))
+    end
+  end,
+}, { __index = real_script })
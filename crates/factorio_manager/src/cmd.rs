@@ -22,12 +22,36 @@ pub async fn try_cmd(cmd: &str, args: &[&str]) -> Result<()> {
     Ok(())
 }
 
+/// Downloads with wget, or with the curl that ships with Windows.
 pub async fn try_download(url: &str, path: &Path) -> Result<()> {
-    try_cmd("wget", &["-O", path.to_str().unwrap(), url])
+    let path_str = path.to_str().unwrap();
+    let result = if cfg!(windows) {
+        try_cmd("curl", &["-fL", "-o", path_str, url]).await
+    } else {
+        try_cmd("wget", &["-O", path_str, url]).await
+    };
+    result.with_context(|| format!("Failed to download from {} to {}", url, path.display()))
+}
+
+/// Downloads like [`try_download`], but as a POST of the form in `form_file`, so its
+/// contents stay out of the command line and the URL.
+pub async fn try_post_download(url: &str, form_file: &Path, path: &Path) -> Result<()> {
+    let path_str = path.to_str().unwrap();
+    let result = if cfg!(windows) {
+        let data = format!("@{}", form_file.to_str().unwrap());
+        try_cmd(
+            "curl",
+            &["-fL", "--data-binary", &data, "-o", path_str, url],
+        )
         .await
-        .with_context(|| format!("Failed to download from {} to {}", url, path.display()))
+    } else {
+        let post_file = format!("--post-file={}", form_file.to_str().unwrap());
+        try_cmd("wget", &[&post_file, "-O", path_str, url]).await
+    };
+    result.with_context(|| format!("Failed to download from {} to {}", url, path.display()))
 }
 
+/// Extracts with `tar`, which also handles zips on Windows, where it is bsdtar.
 pub async fn try_extract(zip_file: &Path, out_path: &Path) -> Result<()> {
     fs::create_dir_all(out_path)
         .with_context(|| format!("Failed to create directory: {}", out_path.display()))?;
@@ -50,3 +74,27 @@ pub async fn try_extract(zip_file: &Path, out_path: &Path) -> Result<()> {
         )
     })
 }
+
+/// Copies the app bundle out of a macOS disk image into `out_path`.
+pub async fn try_extract_dmg(dmg_file: &Path, out_path: &Path) -> Result<()> {
+    fs::create_dir_all(out_path)
+        .with_context(|| format!("Failed to create directory: {}", out_path.display()))?;
+    let mount_dir = tempfile::tempdir()?;
+    let mount_point = mount_dir.path().to_str().unwrap();
+    try_cmd(
+        "hdiutil",
+        &[
+            "attach",
+            "-nobrowse",
+            "-readonly",
+            "-mountpoint",
+            mount_point,
+            dmg_file.to_str().unwrap(),
+        ],
+    )
+    .await?;
+    let bundle = format!("{mount_point}/{}", crate::app_bundle::APP_BUNDLE);
+    let copied = try_cmd("cp", &["-R", &bundle, out_path.to_str().unwrap()]).await;
+    try_cmd("hdiutil", &["detach", mount_point]).await?;
+    copied.with_context(|| format!("Failed to extract {}", dmg_file.display()))
+}
@@ -1,5 +1,6 @@
 use std::io;
 use thiserror::Error;
+use zip_downloader::disk_space::InsufficientDiskSpace;
 
 use crate::factorio_install_dir::VersionStr;
 
@@ -23,6 +24,12 @@ pub enum FactorioError {
     #[error("Failed to inject replay script: {0}")]
     ScriptInjectionFailed(#[source] anyhow::Error),
 
+    #[error("Rules {} don't support Factorio {version}", scripts.join(", "))]
+    UnsupportedScripts {
+        version: VersionStr,
+        scripts: Vec<String>,
+    },
+
     #[error("Failed to download Factorio {version}")]
     FactorioDownloadFailed {
         version: VersionStr,
@@ -30,12 +37,28 @@ pub enum FactorioError {
         source: anyhow::Error,
     },
 
+    #[error("Factorio image {image} is unavailable")]
+    ImageUnavailable {
+        image: String,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    #[error("Failed to list published Factorio versions")]
+    VersionListUnavailable(#[source] anyhow::Error),
+
     #[error("Failed to extract Factorio: {0}")]
     ExtractionFailed(#[source] anyhow::Error),
 
     #[error("Factorio installation not found for version {0}")]
     InstallationNotFound(VersionStr),
 
+    #[error("Save is from Factorio {save_version}, newer than the pinned Factorio {version}")]
+    SaveNewerThanInstall {
+        save_version: VersionStr,
+        version: VersionStr,
+    },
+
     #[error("Install directory error: {0}")]
     InstallDirError(#[source] anyhow::Error),
 
@@ -57,6 +80,9 @@ pub enum FactorioError {
     #[error("Replay timeout: no log messages produced for 5 minutes")]
     ReplayTimeout,
 
+    #[error(transparent)]
+    InsufficientDiskSpace(#[from] InsufficientDiskSpace),
+
     #[error("IO error: {0}")]
     IoError(#[from] io::Error),
 }
@@ -1,44 +1,202 @@
 use crate::error::FactorioError;
+use crate::factorio_install_dir::VersionStr;
 use crate::mod_versions::ModVersions;
-use std::collections::HashSet;
+use crate::save_file::SaveMod;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 pub type ExpectedMods = HashSet<String>;
 
+/// How strictly mod versions in [`ModPolicy::versions`] are compared.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ModStrictness {
+    #[default]
+    Exact,
+    /// A newer patch of the same major.minor version is accepted.
+    AllowNewerPatch,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(default, deny_unknown_fields)]
+pub struct ModPolicy {
+    pub strictness: ModStrictness,
+    /// Expected versions; mods not listed here may have any version.
+    pub versions: HashMap<String, VersionStr>,
+    /// Mods (e.g. QoL mods) that may be enabled on top of the expected mods.
+    pub allowed_extra_mods: HashSet<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModFindingKind {
+    AllowedExtra,
+    NewerPatch {
+        expected: VersionStr,
+        actual: VersionStr,
+    },
+    VersionMismatch {
+        expected: VersionStr,
+        actual: Option<VersionStr>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModFinding {
+    pub mod_name: String,
+    #[serde(flatten)]
+    pub kind: ModFindingKind,
+}
+
+impl ModFinding {
+    pub fn is_error(&self) -> bool {
+        matches!(self.kind, ModFindingKind::VersionMismatch { .. })
+    }
+}
+
+impl fmt::Display for ModFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ModFindingKind::AllowedExtra => write!(f, "Allowed extra mod: {}", self.mod_name),
+            ModFindingKind::NewerPatch { expected, actual } => write!(
+                f,
+                "Mod {} is version {}, newer patch than expected {}",
+                self.mod_name, actual, expected
+            ),
+            ModFindingKind::VersionMismatch {
+                expected,
+                actual: Some(actual),
+            } => write!(
+                f,
+                "Mod {} is version {}, expected {}",
+                self.mod_name, actual, expected
+            ),
+            ModFindingKind::VersionMismatch {
+                expected,
+                actual: None,
+            } => write!(
+                f,
+                "Mod {} has unknown version, expected {}",
+                self.mod_name, expected
+            ),
+        }
+    }
+}
+
+/// Checks mod names against `expected_mods`, then versions against the policy.
+///
+/// Missing mods and extra mods outside `policy.allowed_extra_mods` are an error;
+/// everything else is reported as findings.
 pub fn check_expected_mods(
     expected_mods: &ExpectedMods,
+    policy: &ModPolicy,
     actual_mods: &ModVersions,
-) -> Result<(), FactorioError> {
+) -> Result<Vec<ModFinding>, FactorioError> {
     let actual_mod_list = actual_mods.keys().cloned().collect::<HashSet<String>>();
 
-    if expected_mods != &actual_mod_list {
-        let extra_mods = actual_mod_list
-            .difference(expected_mods)
-            .cloned()
-            .collect::<Vec<String>>();
-        let missing_mods = expected_mods
-            .difference(&actual_mod_list)
-            .cloned()
-            .collect::<Vec<String>>();
+    let (mut allowed_extras, mut extra_mods): (Vec<String>, Vec<String>) = actual_mod_list
+        .difference(expected_mods)
+        .cloned()
+        .partition(|name| policy.allowed_extra_mods.contains(name));
+    let missing_mods = expected_mods
+        .difference(&actual_mod_list)
+        .cloned()
+        .collect::<Vec<String>>();
 
+    if !missing_mods.is_empty() || !extra_mods.is_empty() {
+        extra_mods.sort();
         return Err(FactorioError::ModMismatch {
             missing_mods,
             extra_mods,
         });
     }
-    Ok(())
+
+    allowed_extras.sort();
+    let mut findings = allowed_extras
+        .into_iter()
+        .map(|mod_name| ModFinding {
+            mod_name,
+            kind: ModFindingKind::AllowedExtra,
+        })
+        .collect::<Vec<_>>();
+
+    let mut versioned = policy.versions.iter().collect::<Vec<_>>();
+    versioned.sort();
+    for (mod_name, &expected) in versioned {
+        let Some(&actual) = actual_mods.get(mod_name) else {
+            continue;
+        };
+        let kind = match actual {
+            Some(actual) if actual == expected => continue,
+            Some(actual)
+                if policy.strictness == ModStrictness::AllowNewerPatch
+                    && (actual.0, actual.1) == (expected.0, expected.1)
+                    && actual.2 > expected.2 =>
+            {
+                ModFindingKind::NewerPatch { expected, actual }
+            }
+            actual => ModFindingKind::VersionMismatch { expected, actual },
+        };
+        findings.push(ModFinding {
+            mod_name: mod_name.clone(),
+            kind,
+        });
+    }
+    Ok(findings)
+}
+
+/// Differences between the synced mods and the mod list read from the save's header.
+///
+/// The synced mods are authoritative; the header is only read heuristically, so a
+/// difference is something for a moderator to look at rather than a failure.
+pub fn cross_check_save_mods(actual_mods: &ModVersions, save_mods: &[SaveMod]) -> Vec<String> {
+    let mut differences = Vec::new();
+    for save_mod in save_mods {
+        match actual_mods.get(&save_mod.name) {
+            None => differences.push(format!(
+                "Mod {} is in the save's mod list but was not synced",
+                save_mod.name
+            )),
+            Some(Some(synced)) if *synced != save_mod.version => differences.push(format!(
+                "Mod {} is version {} in the save's mod list, but {} was synced",
+                save_mod.name, save_mod.version, synced
+            )),
+            Some(_) => {}
+        }
+    }
+    let mut unlisted = actual_mods
+        .keys()
+        .filter(|name| !save_mods.iter().any(|save_mod| &save_mod.name == *name))
+        .collect::<Vec<_>>();
+    unlisted.sort();
+    differences.extend(
+        unlisted
+            .into_iter()
+            .map(|name| format!("Mod {} was synced but is not in the save's mod list", name)),
+    );
+    differences
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::expected_mods::{ExpectedMods, check_expected_mods};
-    use std::collections::HashMap;
+    use super::*;
+
+    fn actual_mods(mods: &[(&str, VersionStr)]) -> ModVersions {
+        mods.iter()
+            .map(|(name, version)| (name.to_string(), Some(*version)))
+            .collect()
+    }
 
     #[test]
     fn test_check_expected_mods_match() {
         let expected = ExpectedMods::from(["base".to_string(), "quality".to_string()]);
         let actual = HashMap::from([("base".to_string(), None), ("quality".to_string(), None)]);
 
-        assert!(check_expected_mods(&expected, &actual).is_ok());
+        let findings = check_expected_mods(&expected, &ModPolicy::default(), &actual).unwrap();
+        assert!(findings.is_empty());
     }
 
     #[test]
@@ -46,11 +204,100 @@ mod tests {
         let expected = ExpectedMods::from(["base".to_string(), "quality".to_string()]);
         let actual = HashMap::from([("base".to_string(), None), ("space-age".to_string(), None)]);
 
-        let result = check_expected_mods(&expected, &actual);
+        let result = check_expected_mods(&expected, &ModPolicy::default(), &actual);
         assert!(result.is_err());
         let err = result.unwrap_err();
         let err_msg = err.to_string();
         assert!(err_msg.contains("quality"));
         assert!(err_msg.contains("space-age"));
     }
+
+    #[test]
+    fn test_check_expected_mods_policy() {
+        let expected = ExpectedMods::from(["base".to_string(), "quality".to_string()]);
+        let policy: ModPolicy = serde_yaml::from_str(
+            r#"
+strictness: allow_newer_patch
+versions:
+  base: 2.0.55
+  quality: 2.0.60
+allowed_extra_mods: [even-distribution]
+"#,
+        )
+        .unwrap();
+        let actual = actual_mods(&[
+            ("base", VersionStr::new(2, 0, 57)),
+            ("quality", VersionStr::new(2, 0, 57)),
+            ("even-distribution", VersionStr::new(1, 0, 0)),
+        ]);
+
+        let findings = check_expected_mods(&expected, &policy, &actual).unwrap();
+        assert_eq!(
+            findings,
+            [
+                ModFinding {
+                    mod_name: "even-distribution".to_string(),
+                    kind: ModFindingKind::AllowedExtra,
+                },
+                ModFinding {
+                    mod_name: "base".to_string(),
+                    kind: ModFindingKind::NewerPatch {
+                        expected: VersionStr::new(2, 0, 55),
+                        actual: VersionStr::new(2, 0, 57),
+                    },
+                },
+                ModFinding {
+                    mod_name: "quality".to_string(),
+                    kind: ModFindingKind::VersionMismatch {
+                        expected: VersionStr::new(2, 0, 60),
+                        actual: Some(VersionStr::new(2, 0, 57)),
+                    },
+                },
+            ]
+        );
+        assert!(!findings[1].is_error());
+        assert!(findings[2].is_error());
+
+        let exact = ModPolicy {
+            strictness: ModStrictness::Exact,
+            ..policy
+        };
+        let findings = check_expected_mods(&expected, &exact, &actual).unwrap();
+        assert!(findings[1].is_error());
+    }
+
+    #[test]
+    fn test_cross_check_save_mods() {
+        let save_mod = |name: &str, version| SaveMod {
+            name: name.to_string(),
+            version,
+            crc: 0,
+        };
+        let synced = HashMap::from([
+            ("base".to_string(), Some(VersionStr::new(2, 0, 60))),
+            ("quality".to_string(), None),
+            ("space-age".to_string(), Some(VersionStr::new(2, 0, 60))),
+        ]);
+
+        let save_mods = [
+            save_mod("base", VersionStr::new(2, 0, 60)),
+            save_mod("quality", VersionStr::new(2, 0, 60)),
+            save_mod("space-age", VersionStr::new(2, 0, 60)),
+        ];
+        assert!(cross_check_save_mods(&synced, &save_mods).is_empty());
+
+        let save_mods = [
+            save_mod("base", VersionStr::new(2, 0, 55)),
+            save_mod("quality", VersionStr::new(2, 0, 60)),
+            save_mod("cheat-mod", VersionStr::new(1, 0, 0)),
+        ];
+        assert_eq!(
+            cross_check_save_mods(&synced, &save_mods),
+            [
+                "Mod base is version 2.0.55 in the save's mod list, but 2.0.60 was synced",
+                "Mod cheat-mod is in the save's mod list but was not synced",
+                "Mod space-age was synced but is not in the save's mod list",
+            ]
+        );
+    }
 }
@@ -0,0 +1,273 @@
+use anyhow::{Context, bail};
+use async_process::Command;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+use crate::cmd::try_cmd;
+use crate::error::FactorioError;
+use crate::factorio_install_dir::VersionStr;
+use crate::process_manager::{
+    Container, ContainerRuntime, ResourceLimits, absolute_args, arg_dirs, container_run, push,
+};
+
+/// Builds `factorio-headless:<version>` images from the headless server download.
+const DOCKERFILE: &str = include_str!("../docker/factorio-headless.Dockerfile");
+
+/// Records which image a version's install dir was first run with.
+const PINNED_IMAGE_FILE: &str = "image-id";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PullPolicy {
+    #[default]
+    IfNotPresent,
+    /// Pull every time, ignoring pinned images.
+    Always,
+    /// Only use images already present.
+    Never,
+    /// Build missing images from the bundled Dockerfile instead of pulling.
+    Build,
+}
+
+/// Runs Factorio from a container image per version, `<image>:<version>`, instead of
+/// installs downloaded into the install dir, which then only holds each version's write data.
+/// Defaults to the community `factoriotools/factorio` images.
+///
+/// The first image used for a version is pinned by id, so later replays run the same
+/// Factorio even if the tag moves; configured `digests` take precedence.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(default, deny_unknown_fields)]
+pub struct FactorioImage {
+    pub runtime: ContainerRuntime,
+    pub image: String,
+    /// The Factorio executable inside the image.
+    pub binary: String,
+    pub pull: PullPolicy,
+    /// Repository digests (`sha256:...`) to run for specific versions.
+    pub digests: BTreeMap<VersionStr, String>,
+}
+
+impl Default for FactorioImage {
+    fn default() -> Self {
+        Self {
+            runtime: ContainerRuntime::Docker,
+            image: "factoriotools/factorio".to_string(),
+            binary: "/opt/factorio/bin/x64/factorio".to_string(),
+            pull: PullPolicy::IfNotPresent,
+            digests: BTreeMap::new(),
+        }
+    }
+}
+
+impl FactorioImage {
+    /// The image to run for `version`, pulled or built as the pull policy allows.
+    /// `version_dir` holds the pinned image id.
+    pub async fn resolve(
+        &self,
+        version: VersionStr,
+        version_dir: &Path,
+    ) -> Result<String, FactorioError> {
+        self.try_resolve(version, version_dir)
+            .await
+            .map_err(|source| FactorioError::ImageUnavailable {
+                image: format!("{}:{}", self.image, version),
+                source,
+            })
+    }
+
+    async fn try_resolve(&self, version: VersionStr, version_dir: &Path) -> anyhow::Result<String> {
+        if let Some(digest) = self.digests.get(&version) {
+            let reference = format!("{}@{}", self.image, digest);
+            let present = self.image_id(&reference).await.is_some();
+            if self.pull == PullPolicy::Always || !present {
+                if self.pull == PullPolicy::Never {
+                    bail!("{reference} is not present and pull is never");
+                }
+                self.pull(&reference).await?;
+            }
+            return Ok(reference);
+        }
+
+        let pin_file = version_dir.join(PINNED_IMAGE_FILE);
+        if self.pull != PullPolicy::Always
+            && let Ok(pinned) = std::fs::read_to_string(&pin_file)
+        {
+            let pinned = pinned.trim();
+            if self.image_id(pinned).await.is_some() {
+                return Ok(pinned.to_string());
+            }
+            warn!("Pinned image {pinned} for Factorio {version} is gone; pinning again");
+        }
+
+        let tag = format!("{}:{}", self.image, version);
+        let present = self.image_id(&tag).await.is_some();
+        match self.pull {
+            PullPolicy::Always => self.pull(&tag).await?,
+            PullPolicy::IfNotPresent if !present => self.pull(&tag).await?,
+            PullPolicy::Never if !present => bail!("{tag} is not present and pull is never"),
+            PullPolicy::Build if !present => self.build(&tag, version).await?,
+            _ => {}
+        }
+        let id = self
+            .image_id(&tag)
+            .await
+            .with_context(|| format!("{tag} is missing after pulling"))?;
+        std::fs::create_dir_all(version_dir)?;
+        std::fs::write(&pin_file, &id)?;
+        info!("Pinned Factorio {version} to image {id}");
+        Ok(id)
+    }
+
+    async fn image_id(&self, reference: &str) -> Option<String> {
+        let output = Command::new(self.runtime.program())
+            .args(["image", "inspect", "--format", "{{.Id}}", reference])
+            .output()
+            .await
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    async fn pull(&self, reference: &str) -> anyhow::Result<()> {
+        info!("Pulling {reference}");
+        try_cmd(self.runtime.program(), &["pull", reference]).await
+    }
+
+    async fn build(&self, tag: &str, version: VersionStr) -> anyhow::Result<()> {
+        info!("Building {tag}");
+        let context = tempfile::tempdir()?;
+        let dockerfile = context.path().join("Dockerfile");
+        std::fs::write(&dockerfile, DOCKERFILE)?;
+        try_cmd(
+            self.runtime.program(),
+            &[
+                "build",
+                "--build-arg",
+                &format!("VERSION={version}"),
+                "--tag",
+                tag,
+                context.path().to_str().unwrap(),
+            ],
+        )
+        .await
+    }
+
+    /// A new container to run an image in.
+    pub fn container(&self) -> Container {
+        Container::new(self.runtime)
+    }
+
+    /// The command line running `reference` with `args` in `container`. `write_dir` is mounted
+    /// as Factorio's write data, and directories of files in `args` are mounted read-only.
+    pub fn command_line(
+        &self,
+        reference: &str,
+        container: &Container,
+        write_dir: &Path,
+        limits: &ResourceLimits,
+        args: &[&str],
+    ) -> Vec<OsString> {
+        let args = absolute_args(args);
+        let mut line = container_run(
+            self.runtime,
+            Some(container.name()),
+            limits,
+            write_dir,
+            &arg_dirs(&args, write_dir),
+        );
+        push(&mut line, ["--entrypoint", &self.binary, reference]);
+        push(
+            &mut line,
+            [OsStr::new("--config"), config_path(write_dir).as_os_str()],
+        );
+        line.extend(args);
+        line
+    }
+}
+
+fn config_path(write_dir: &Path) -> std::path::PathBuf {
+    write_dir.join("config/config.ini")
+}
+
+/// Points Factorio in the image at `write_dir` for its log, mods and saves.
+pub fn write_config(write_dir: &Path) -> std::io::Result<()> {
+    let config = config_path(write_dir);
+    std::fs::create_dir_all(config.parent().unwrap())?;
+    std::fs::write(
+        config,
+        format!(
+            "[path]\nread-data=__PATH__executable__/../../data\nwrite-data={}\n",
+            write_dir.display()
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let image: FactorioImage = serde_yaml::from_str(
+            "
+runtime: podman
+pull: build
+digests:
+  2.0.57: sha256:abc
+",
+        )
+        .unwrap();
+        assert_eq!(image.image, "factoriotools/factorio");
+        assert_eq!(image.pull, PullPolicy::Build);
+        assert_eq!(image.digests[&VersionStr(2, 0, 57)], "sha256:abc");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_line() {
+        let saves = tempfile::tempdir().unwrap();
+        let saves_dir = saves.path().canonicalize().unwrap();
+        let save = saves_dir.join("run.zip");
+        std::fs::write(&save, "").unwrap();
+
+        let limits = ResourceLimits {
+            max_memory_mb: Some(4096),
+            ..Default::default()
+        };
+        let image = FactorioImage::default();
+        let container = image.container();
+        let line = image
+            .command_line(
+                "sha256:123",
+                &container,
+                Path::new("/srv/installs/2.0.57/factorio"),
+                &limits,
+                &["--run-replay", save.to_str().unwrap()],
+            )
+            .iter()
+            .map(|part| part.to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert!(line.starts_with(&format!(
+            "docker run --rm --network=none --name {} --user ",
+            container.name()
+        )));
+        assert!(line.contains("--memory=4096m"));
+        assert!(line.contains(
+            "--volume /srv/installs/2.0.57/factorio:/srv/installs/2.0.57/factorio --volume"
+        ));
+        assert!(line.contains(&format!("--volume {0}:{0}:ro", saves_dir.display())));
+        assert!(line.ends_with(&format!(
+            "--entrypoint /opt/factorio/bin/x64/factorio sha256:123 \
+             --config /srv/installs/2.0.57/factorio/config/config.ini --run-replay {}",
+            save.display()
+        )));
+    }
+}
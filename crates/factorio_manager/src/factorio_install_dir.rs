@@ -1,12 +1,24 @@
 use anyhow::Context;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::fmt::Display;
+use std::fs::File;
 use std::path::{Path, PathBuf, absolute};
+use std::time::SystemTime;
+use zip_downloader::disk_space::ensure_free_space;
 
-use crate::cmd::{try_download, try_extract};
+use crate::app_bundle::{is_app_bundle, prepare_app_bundle};
+use crate::cmd::{try_download, try_extract, try_extract_dmg, try_post_download};
 use crate::error::FactorioError;
+use crate::factorio_image::{FactorioImage, write_config};
 use crate::factorio_instance::FactorioInstance;
+use crate::install_version::InstallVersionPolicy;
+use crate::process_manager::Sandbox;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
 pub struct VersionStr(pub u16, pub u16, pub u16);
 
 impl VersionStr {
@@ -14,6 +26,20 @@ impl VersionStr {
         VersionStr(major, minor, patch)
     }
 }
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for VersionStr {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "VersionStr".into()
+    }
+
+    fn json_schema(_generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "string",
+            "pattern": r"^\d+\.\d+\.\d+$",
+        })
+    }
+}
+
 impl TryFrom<&str> for VersionStr {
     type Error = FactorioError;
 
@@ -45,14 +71,83 @@ impl TryFrom<String> for VersionStr {
         VersionStr::try_from(value.as_str())
     }
 }
+impl From<VersionStr> for String {
+    fn from(value: VersionStr) -> Self {
+        value.to_string()
+    }
+}
 impl Display for VersionStr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}.{}.{}", self.0, self.1, self.2)
     }
 }
 
+const SHA256SUMS_URL: &str = "https://factorio.com/download/sha256sums/";
+/// How long the versions listed in the published checksums are reused for.
+const PUBLISHED_VERSIONS_TTL: std::time::Duration = std::time::Duration::from_secs(3600);
+/// Touched whenever an installation is used, for least-recently-used cleanup.
+const LAST_USED_FILE: &str = ".last_used";
+/// Written once an installation is fully unpacked, listing its files to verify it against.
+const MANIFEST_FILE: &str = ".install_manifest.yaml";
+/// Prefix of the directories installations are unpacked in before they are moved into place.
+const STAGING_PREFIX: &str = ".installing-";
+
+/// factorio.com account used for downloads, by default from FACTORIO_USERNAME and
+/// FACTORIO_TOKEN.
+#[derive(Clone, PartialEq, Eq)]
+pub struct FactorioCredentials {
+    pub username: String,
+    pub token: String,
+}
+
+impl FactorioCredentials {
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Some(Self {
+            username: var("FACTORIO_USERNAME")?,
+            token: var("FACTORIO_TOKEN")?,
+        })
+    }
+
+    /// The form posted to factorio.com's download endpoint.
+    fn form_body(&self) -> String {
+        format!(
+            "username={}&token={}",
+            form_encode(&self.username),
+            form_encode(&self.token)
+        )
+    }
+}
+
+impl std::fmt::Debug for FactorioCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FactorioCredentials")
+            .field("username", &self.username)
+            .field("token", &"***")
+            .finish()
+    }
+}
+
+/// Percent-encodes everything but unreserved characters, for an
+/// `application/x-www-form-urlencoded` body.
+fn form_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
 pub struct FactorioInstallDir {
     path: PathBuf,
+    credentials: Option<FactorioCredentials>,
+    quota_bytes: Option<u64>,
+    sandbox: Sandbox,
+    image: Option<FactorioImage>,
 }
 
 impl FactorioInstallDir {
@@ -68,7 +163,41 @@ impl FactorioInstallDir {
                 path.display()
             )));
         }
-        Ok(FactorioInstallDir { path })
+        Ok(FactorioInstallDir {
+            path,
+            credentials: FactorioCredentials::from_env(),
+            quota_bytes: None,
+            sandbox: Sandbox::None,
+            image: None,
+        })
+    }
+
+    /// Once installations take more than `quota_bytes`, the least recently used ones are
+    /// removed after each download.
+    pub fn with_quota(mut self, quota_bytes: Option<u64>) -> Self {
+        self.quota_bytes = quota_bytes;
+        self
+    }
+
+    /// Sandbox for Factorio instances from this directory.
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// Downloads with this factorio.com account instead of the one in FACTORIO_USERNAME and
+    /// FACTORIO_TOKEN, if given.
+    pub fn with_credentials(mut self, credentials: Option<FactorioCredentials>) -> Self {
+        if credentials.is_some() {
+            self.credentials = credentials;
+        }
+        self
+    }
+
+    /// Runs Factorio from container images instead of downloading installs.
+    pub fn with_image(mut self, image: Option<FactorioImage>) -> Self {
+        self.image = image;
+        self
     }
 
     pub fn new_or_create(path: impl AsRef<Path>) -> Result<Self, FactorioError> {
@@ -86,64 +215,632 @@ impl FactorioInstallDir {
     }
 
     async fn download_factorio(&self, version: VersionStr) -> Result<(), FactorioError> {
-        download_factorio(version, &self.path).await
+        download_factorio(version, &self.path, self.credentials.as_ref()).await
     }
 }
 
-async fn download_factorio(version: VersionStr, out_folder: &Path) -> Result<(), FactorioError> {
+/// Room for the headless archive plus its extracted contents.
+const FACTORIO_INSTALL_SPACE: u64 = 1024 * 1024 * 1024; // 1 GB
+
+/// The Factorio build downloaded on this platform.
+struct DownloadBuild {
+    /// Path in `https://factorio.com/get-download/<version>/<path>`.
+    path: &'static str,
+    extension: &'static str,
+    /// How the build's archives are named in the published checksums, if they are there.
+    /// Builds without published checksums can't be verified, so aren't downloaded.
+    checksum_marker: Option<&'static str>,
+    /// Only the headless build can be downloaded without a factorio.com account.
+    needs_credentials: bool,
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+const DOWNLOAD_BUILD: DownloadBuild = DownloadBuild {
+    path: "headless/linux64",
+    extension: "tar.xz",
+    checksum_marker: Some("headless"),
+    needs_credentials: false,
+};
+
+/// There is no headless build for Windows or macOS, so they get the full game.
+#[cfg(windows)]
+const DOWNLOAD_BUILD: DownloadBuild = DownloadBuild {
+    path: "alpha/win64-manual",
+    extension: "zip",
+    checksum_marker: None,
+    needs_credentials: true,
+};
+
+#[cfg(target_os = "macos")]
+const DOWNLOAD_BUILD: DownloadBuild = DownloadBuild {
+    path: "alpha/osx",
+    extension: "dmg",
+    checksum_marker: None,
+    needs_credentials: true,
+};
+
+async fn download_factorio(
+    version: VersionStr,
+    out_folder: &Path,
+    credentials: Option<&FactorioCredentials>,
+) -> Result<(), FactorioError> {
+    ensure_free_space::<FactorioError>(out_folder, FACTORIO_INSTALL_SPACE)?;
+    // only sent where needed, and never in the URL, which shows up in process lists and errors
+    let credentials = match credentials {
+        Some(credentials) if DOWNLOAD_BUILD.needs_credentials => Some(credentials),
+        None if DOWNLOAD_BUILD.needs_credentials => {
+            return Err(FactorioError::FactorioDownloadFailed {
+                version,
+                source: anyhow::anyhow!(
+                    "Downloading Factorio on this platform requires a factorio.com account, \
+                     configured or in FACTORIO_USERNAME and FACTORIO_TOKEN"
+                ),
+            });
+        }
+        _ => None,
+    };
     let url = format!(
-        "https://factorio.com/get-download/{}/headless/linux64",
-        version
+        "https://factorio.com/get-download/{}/{}",
+        version, DOWNLOAD_BUILD.path
     );
     let zip_path =
-        absolute(out_folder.join(format!("factorio-{}.tar.xz", version))).map_err(|e| {
-            FactorioError::FactorioDownloadFailed {
+        absolute(out_folder.join(format!("factorio-{}.{}", version, DOWNLOAD_BUILD.extension)))
+            .map_err(|e| FactorioError::FactorioDownloadFailed {
                 version,
                 source: e.into(),
-            }
-        })?;
+            })?;
     println!("Downloading Factorio {} to {}", version, zip_path.display());
-    try_download(&url, &zip_path)
-        .await
-        .map_err(|e| FactorioError::FactorioDownloadFailed { version, source: e })?;
+    let downloaded = match credentials {
+        Some(credentials) => {
+            download_with_credentials(&url, credentials, out_folder, &zip_path).await
+        }
+        None => try_download(&url, &zip_path).await,
+    };
+    downloaded.map_err(|e| FactorioError::FactorioDownloadFailed { version, source: e })?;
+    let archive_sha256 = match verify_checksum(version, &zip_path).await {
+        Ok(archive_sha256) => archive_sha256,
+        Err(e) => {
+            let _ = std::fs::remove_file(&zip_path);
+            return Err(FactorioError::FactorioDownloadFailed { version, source: e });
+        }
+    };
     let out_path = absolute(out_folder.join(version.to_string()))
         .context("Failed to get extraction path")
         .map_err(FactorioError::ExtractionFailed)?;
+    // unpacked aside, so a failure leaves any existing installation as it was
+    let staging = tempfile::Builder::new()
+        .prefix(STAGING_PREFIX)
+        .tempdir_in(out_folder)?;
     println!(
         "Extracting {} to {}",
         zip_path.display(),
         out_path.display()
     );
-    try_extract(&zip_path, &out_path)
-        .await
-        .map_err(FactorioError::ExtractionFailed)?;
+    let extracted = if DOWNLOAD_BUILD.extension == "dmg" {
+        try_extract_dmg(&zip_path, &staging.path().join("factorio")).await
+    } else {
+        try_extract(&zip_path, staging.path()).await
+    };
     let _ = std::fs::remove_file(&zip_path);
+    extracted
+        .and_then(|()| normalize_layout(staging.path()))
+        .and_then(|()| write_manifest(staging.path(), Some(archive_sha256)))
+        .and_then(|()| replace_dir(staging, &out_path, out_folder))
+        .map_err(FactorioError::ExtractionFailed)
+}
+
+/// Moves the complete installation in `staging` to `out_path`, replacing what was there.
+fn replace_dir(
+    staging: tempfile::TempDir,
+    out_path: &Path,
+    out_folder: &Path,
+) -> anyhow::Result<()> {
+    let replaced = tempfile::Builder::new()
+        .prefix(STAGING_PREFIX)
+        .tempdir_in(out_folder)?;
+    if out_path.exists() {
+        std::fs::rename(out_path, replaced.path().join("replaced"))?;
+    }
+    std::fs::rename(staging.keep(), out_path)?;
     Ok(())
 }
 
+/// Posts the credentials from a file only this user can read, as a form body.
+async fn download_with_credentials(
+    url: &str,
+    credentials: &FactorioCredentials,
+    out_folder: &Path,
+    zip_path: &Path,
+) -> anyhow::Result<()> {
+    let mut form_file = tempfile::NamedTempFile::new_in(out_folder)?;
+    std::io::Write::write_all(&mut form_file, credentials.form_body().as_bytes())?;
+    try_post_download(url, form_file.path(), zip_path).await
+}
+
+/// Checks a downloaded archive against the checksums published on factorio.com, returning
+/// its SHA-256. Archives without a published checksum are rejected.
+async fn verify_checksum(version: VersionStr, archive: &Path) -> anyhow::Result<String> {
+    let actual = sha256_file(archive)?;
+    let Some(marker) = DOWNLOAD_BUILD.checksum_marker else {
+        anyhow::bail!(
+            "No published checksums for this platform's Factorio build, so it can't be verified; \
+             install Factorio {version} manually"
+        );
+    };
+    let sums = fetch_checksums(archive.parent().unwrap_or(Path::new("."))).await?;
+    let Some(expected) = find_checksum(&sums, version, marker, DOWNLOAD_BUILD.extension) else {
+        anyhow::bail!("No published checksum for Factorio {version}");
+    };
+
+    if !actual.eq_ignore_ascii_case(&expected) {
+        anyhow::bail!("Checksum mismatch: expected {expected}, got {actual}");
+    }
+    Ok(actual)
+}
+
+/// The checksums published on factorio.com, downloaded through a temporary file in `dir`.
+async fn fetch_checksums(dir: &Path) -> anyhow::Result<String> {
+    let sums_file = tempfile::Builder::new()
+        .suffix(".sha256sums")
+        .tempfile_in(dir)?;
+    try_download(SHA256SUMS_URL, sums_file.path()).await?;
+    Ok(std::fs::read_to_string(sums_file.path())?)
+}
+
+/// Versions with a published headless archive, which every platform's builds are released with.
+fn published_versions(sums: &str) -> Vec<VersionStr> {
+    let mut versions: Vec<VersionStr> = sums
+        .lines()
+        .filter_map(|line| {
+            let (_, file_name) = line.split_once(char::is_whitespace)?;
+            let file_name = file_name.trim();
+            if !file_name.contains("headless") {
+                return None;
+            }
+            let (_, version) = file_name.strip_suffix(".tar.xz")?.rsplit_once('_')?;
+            VersionStr::try_from(version).ok()
+        })
+        .collect();
+    versions.sort();
+    versions.dedup();
+    versions
+}
+
+fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Lines look like `<sha256>  factorio-headless_linux_2.0.57.tar.xz`.
+fn find_checksum(sums: &str, version: VersionStr, marker: &str, extension: &str) -> Option<String> {
+    let suffix = format!("_{version}.{extension}");
+    sums.lines().find_map(|line| {
+        let (hash, file_name) = line.split_once(char::is_whitespace)?;
+        let file_name = file_name.trim();
+        (file_name.contains(marker) && file_name.ends_with(&suffix)).then(|| hash.to_string())
+    })
+}
+
+/// Renames the extracted top-level directory to `factorio`. The Linux archive already uses
+/// that name; the Windows one is `Factorio_<version>`.
+fn normalize_layout(out_path: &Path) -> anyhow::Result<()> {
+    let factorio_dir = out_path.join("factorio");
+    if factorio_dir.is_dir() {
+        return Ok(());
+    }
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(out_path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    let [dir] = dirs.as_slice() else {
+        anyhow::bail!(
+            "Expected a single directory in {}, found {}",
+            out_path.display(),
+            dirs.len()
+        );
+    };
+    std::fs::rename(dir, &factorio_dir)?;
+    Ok(())
+}
+
+/// What an installation looked like right after it was unpacked.
+#[derive(Debug, Serialize, Deserialize)]
+struct InstallManifest {
+    /// SHA-256 of the archive the installation was unpacked from, unknown for installations
+    /// that predate manifests.
+    #[serde(default)]
+    archive_sha256: Option<String>,
+    /// Every unpacked file, by `/`-separated path relative to the `factorio` directory.
+    files: BTreeMap<String, FileDigest>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct FileDigest {
+    size: u64,
+    sha256: String,
+}
+
+/// Outcome of [`FactorioInstallDir::verify_installation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallCheck {
+    Intact,
+    /// Nothing is installed for the version.
+    Missing,
+    /// Unpacking never finished.
+    Incomplete,
+    /// The installation predates install manifests, so there is nothing to verify it against.
+    Unverified,
+    /// Files were changed or removed since unpacking.
+    Corrupted {
+        files: Vec<String>,
+    },
+}
+
+fn write_manifest(out_path: &Path, archive_sha256: Option<String>) -> anyhow::Result<()> {
+    let mut files = BTreeMap::new();
+    digest_files(&out_path.join("factorio"), "", &mut files)?;
+    let manifest = InstallManifest {
+        archive_sha256,
+        files,
+    };
+    std::fs::write(
+        out_path.join(MANIFEST_FILE),
+        serde_yaml::to_string(&manifest)?,
+    )?;
+    Ok(())
+}
+
+fn digest_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut BTreeMap<String, FileDigest>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{prefix}{}", entry.file_name().to_string_lossy());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            digest_files(&entry.path(), &format!("{name}/"), files)?;
+        } else if file_type.is_file() {
+            let digest = FileDigest {
+                size: entry.metadata()?.len(),
+                sha256: sha256_file(&entry.path())?,
+            };
+            files.insert(name, digest);
+        }
+    }
+    Ok(())
+}
+
+/// Compares the installation in `version_dir` with its manifest. Files Factorio creates
+/// while running (config, logs, saves) aren't in the manifest and are ignored.
+///
+/// Unless `thorough`, only files modified since the manifest was written are hashed; the
+/// others are only checked for their size.
+fn check_installation(version_dir: &Path, thorough: bool) -> std::io::Result<InstallCheck> {
+    if !version_dir.exists() {
+        return Ok(InstallCheck::Missing);
+    }
+    let factorio_dir = version_dir.join("factorio");
+    let manifest_path = version_dir.join(MANIFEST_FILE);
+    let manifest = std::fs::read_to_string(&manifest_path)
+        .ok()
+        .and_then(|manifest| serde_yaml::from_str::<InstallManifest>(&manifest).ok());
+    let Some(manifest) = manifest.filter(|_| factorio_dir.is_dir()) else {
+        let has_binary = FactorioInstance::new(factorio_dir.clone())
+            .is_ok_and(|instance| instance.binary_path().is_file());
+        return Ok(if has_binary && !manifest_path.exists() {
+            InstallCheck::Unverified
+        } else {
+            InstallCheck::Incomplete
+        });
+    };
+    let verified_at = std::fs::metadata(&manifest_path)?.modified()?;
+
+    let mut corrupted = Vec::new();
+    for (name, expected) in manifest.files {
+        let path = factorio_dir.join(&name);
+        let intact = match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() && metadata.len() == expected.size => {
+                (!thorough && metadata.modified()? < verified_at)
+                    || sha256_file(&path)?.eq_ignore_ascii_case(&expected.sha256)
+            }
+            _ => false,
+        };
+        if !intact {
+            corrupted.push(name);
+        }
+    }
+    Ok(if corrupted.is_empty() {
+        InstallCheck::Intact
+    } else {
+        InstallCheck::Corrupted { files: corrupted }
+    })
+}
+
 impl FactorioInstallDir {
     pub fn get_factorio(&self, version: VersionStr) -> Option<FactorioInstance> {
         let path = self.path.join(version.to_string()).join("factorio");
-        path.exists().then(|| FactorioInstance::new(path).unwrap())
+        if is_app_bundle(&path)
+            && let Err(e) = prepare_app_bundle(&path)
+        {
+            warn!("Failed to prepare app bundle of Factorio {version}: {e}");
+        }
+        path.exists().then(|| {
+            FactorioInstance::new(path)
+                .unwrap()
+                .with_sandbox(self.sandbox.clone())
+        })
     }
 
     pub async fn get_or_download_factorio(
         &self,
         version: VersionStr,
     ) -> Result<FactorioInstance, FactorioError> {
-        if let Some(installation) = self.get_factorio(version) {
-            Ok(installation)
+        if let Some(image) = &self.image {
+            return self.get_image_factorio(image, version).await;
+        }
+        let installation = if let Some(installation) = self.get_factorio(version) {
+            installation
         } else {
             self.download_factorio(version).await?;
-            self.get_factorio(version)
-                .ok_or_else(|| FactorioError::InstallationNotFound(version))
+            let installation = self
+                .get_factorio(version)
+                .ok_or_else(|| FactorioError::InstallationNotFound(version))?;
+            if let Some(quota_bytes) = self.quota_bytes
+                && let Err(e) = self.remove_least_recently_used(quota_bytes, version)
+            {
+                warn!("Failed to clean up old Factorio installations: {e}");
+            }
+            installation
+        };
+        if let Err(e) = File::create(self.path.join(version.to_string()).join(LAST_USED_FILE)) {
+            warn!("Failed to record use of Factorio {version}: {e}");
+        }
+        Ok(installation)
+    }
+
+    /// The Factorio `policy` picks for a save from `save_version`, downloading it if needed.
+    pub async fn get_factorio_for_save(
+        &self,
+        save_version: VersionStr,
+        policy: InstallVersionPolicy,
+    ) -> Result<FactorioInstance, FactorioError> {
+        let version = self.version_for_save(save_version, policy).await?;
+        self.get_or_download_factorio(version).await
+    }
+
+    /// The version of Factorio `policy` picks for a save from `save_version`.
+    pub async fn version_for_save(
+        &self,
+        save_version: VersionStr,
+        policy: InstallVersionPolicy,
+    ) -> Result<VersionStr, FactorioError> {
+        let published = if policy.needs_published_versions() {
+            self.published_versions().await?
+        } else {
+            Vec::new()
+        };
+        let version = policy.resolve(save_version, &published)?;
+        if version != save_version {
+            info!("Running save from Factorio {save_version} on Factorio {version}");
+        }
+        Ok(version)
+    }
+
+    /// A Factorio running from `image`, writing to this version's directory.
+    async fn get_image_factorio(
+        &self,
+        image: &FactorioImage,
+        version: VersionStr,
+    ) -> Result<FactorioInstance, FactorioError> {
+        let version_dir = self.path.join(version.to_string());
+        let reference = image.resolve(version, &version_dir).await?;
+        let write_dir = version_dir.join("factorio");
+        write_config(&write_dir)?;
+        File::create(version_dir.join(LAST_USED_FILE))?;
+        Ok(FactorioInstance::new(write_dir)?.with_image(image.clone(), reference))
+    }
+
+    /// Installed versions with their size and last use.
+    fn installations(&self) -> std::io::Result<Vec<(VersionStr, u64, SystemTime)>> {
+        let mut installations = Vec::new();
+        for entry in std::fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            let Some(version) = path
+                .file_name()
+                .and_then(|name| VersionStr::try_from(name.to_string_lossy().as_ref()).ok())
+            else {
+                continue;
+            };
+            if !path.join("factorio").is_dir() {
+                continue;
+            }
+            let last_used = std::fs::metadata(path.join(LAST_USED_FILE))
+                .or_else(|_| std::fs::metadata(&path))?
+                .modified()?;
+            installations.push((version, dir_size(&path)?, last_used));
+        }
+        Ok(installations)
+    }
+
+    /// Removes installations, oldest use first, until the rest fit in `quota_bytes`.
+    /// `keep` is never removed. Returns the removed versions.
+    pub fn remove_least_recently_used(
+        &self,
+        quota_bytes: u64,
+        keep: VersionStr,
+    ) -> Result<Vec<VersionStr>, FactorioError> {
+        let mut installations = self.installations()?;
+        let mut total: u64 = installations.iter().map(|(_, size, _)| size).sum();
+        installations.sort_by_key(|&(_, _, last_used)| last_used);
+
+        let mut removed = Vec::new();
+        for (version, size, _) in installations {
+            if total <= quota_bytes {
+                break;
+            }
+            if version == keep {
+                continue;
+            }
+            info!("Removing least recently used Factorio {version}");
+            std::fs::remove_dir_all(self.path.join(version.to_string()))?;
+            total -= size;
+            removed.push(version);
+        }
+        Ok(removed)
+    }
+}
+
+impl FactorioInstallDir {
+    /// Checks the installation of `version` against the manifest written when it was unpacked,
+    /// hashing every file.
+    pub fn verify_installation(&self, version: VersionStr) -> Result<InstallCheck, FactorioError> {
+        Ok(check_installation(
+            &self.path.join(version.to_string()),
+            true,
+        )?)
+    }
+
+    /// Installs `version` again, keeping its last use. The installation is only replaced once
+    /// the new one is unpacked.
+    pub async fn repair_installation(&self, version: VersionStr) -> Result<(), FactorioError> {
+        let version_dir = self.path.join(version.to_string());
+        let last_used = std::fs::metadata(version_dir.join(LAST_USED_FILE))
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        self.download_factorio(version).await?;
+        if let Some(last_used) = last_used {
+            File::create(version_dir.join(LAST_USED_FILE))?.set_modified(last_used)?;
+        }
+        Ok(())
+    }
+
+    /// Verifies every installation, including partially unpacked ones, and reinstalls those
+    /// that aren't intact. Installations that predate manifests get one from their current
+    /// files instead. Returns the versions that needed repair. A failed repair leaves the
+    /// installation as it was.
+    pub async fn verify_and_repair(&self) -> Result<Vec<VersionStr>, FactorioError> {
+        if self.image.is_some() {
+            return Ok(Vec::new());
+        }
+        self.remove_staging_dirs()?;
+        let mut repaired = Vec::new();
+        for version in self.installed_versions()? {
+            let version_dir = self.path.join(version.to_string());
+            let check = {
+                let version_dir = version_dir.clone();
+                tokio::task::spawn_blocking(move || check_installation(&version_dir, false))
+                    .await
+                    .map_err(|e| FactorioError::InstallDirError(e.into()))??
+            };
+            match check {
+                InstallCheck::Intact | InstallCheck::Missing => continue,
+                InstallCheck::Unverified => {
+                    info!("Recording the files of Factorio {version}, installed before manifests");
+                    tokio::task::spawn_blocking(move || write_manifest(&version_dir, None))
+                        .await
+                        .map_err(|e| FactorioError::InstallDirError(e.into()))?
+                        .map_err(FactorioError::InstallDirError)?;
+                    continue;
+                }
+                InstallCheck::Incomplete => {
+                    warn!("Factorio {version} was not fully installed; reinstalling")
+                }
+                InstallCheck::Corrupted { files } => warn!(
+                    "Factorio {version} has {} changed or missing files (first: {}); reinstalling",
+                    files.len(),
+                    files[0]
+                ),
+            }
+            match self.repair_installation(version).await {
+                Ok(()) => info!("Reinstalled Factorio {version}"),
+                Err(e) => warn!("Failed to reinstall Factorio {version}: {e}"),
+            }
+            repaired.push(version);
+        }
+        Ok(repaired)
+    }
+
+    /// The versions published on factorio.com, fetched at most once per
+    /// [`PUBLISHED_VERSIONS_TTL`] by the whole process.
+    async fn published_versions(&self) -> Result<Vec<VersionStr>, FactorioError> {
+        static CACHE: std::sync::Mutex<Option<(std::time::Instant, Vec<VersionStr>)>> =
+            std::sync::Mutex::new(None);
+        if let Some((fetched_at, versions)) = &*CACHE.lock().unwrap()
+            && fetched_at.elapsed() < PUBLISHED_VERSIONS_TTL
+        {
+            return Ok(versions.clone());
+        }
+        let sums = fetch_checksums(&self.path)
+            .await
+            .map_err(FactorioError::VersionListUnavailable)?;
+        let versions = published_versions(&sums);
+        if versions.is_empty() {
+            return Err(FactorioError::VersionListUnavailable(anyhow::anyhow!(
+                "No versions in {SHA256SUMS_URL}"
+            )));
+        }
+        *CACHE.lock().unwrap() = Some((std::time::Instant::now(), versions.clone()));
+        Ok(versions)
+    }
+
+    /// Removes what interrupted installs left unpacking.
+    fn remove_staging_dirs(&self) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(&self.path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir()
+                && entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(STAGING_PREFIX)
+            {
+                std::fs::remove_dir_all(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Versions with a directory here, whether or not they are fully installed.
+    fn installed_versions(&self) -> std::io::Result<Vec<VersionStr>> {
+        let mut versions = Vec::new();
+        for entry in std::fs::read_dir(&self.path)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if let Ok(version) = VersionStr::try_from(entry.file_name().to_string_lossy().as_ref())
+            {
+                versions.push(version);
+            }
         }
+        versions.sort();
+        Ok(versions)
     }
 }
 
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::factorio_instance::FACTORIO_BINARY;
     use std::fs::{File, create_dir, create_dir_all};
     use tempfile::TempDir;
     use test_utils;
@@ -184,4 +881,185 @@ mod tests {
         drop(temp_dir);
         Ok(())
     }
+
+    #[test]
+    fn test_remove_least_recently_used() -> Result<(), FactorioError> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path();
+        let now = SystemTime::now();
+        let make_installation = |name: &str, size: usize, age_secs: u64| {
+            let dir = path.join(name).join("factorio");
+            create_dir_all(&dir)?;
+            std::fs::write(dir.join("data"), vec![0; size])?;
+            File::create(path.join(name).join(LAST_USED_FILE))?
+                .set_modified(now - std::time::Duration::from_secs(age_secs))
+        };
+        make_installation("1.0.0", 100, 30)?;
+        make_installation("2.0.0", 100, 10)?;
+        make_installation("3.0.0", 100, 20)?;
+        make_installation("4.0.0", 100, 40)?;
+
+        let folder = FactorioInstallDir::new(path)?;
+        let removed = folder.remove_least_recently_used(250, VersionStr(4, 0, 0))?;
+        assert_eq!(removed, [VersionStr(1, 0, 0), VersionStr(3, 0, 0)]);
+        assert!(folder.get_factorio(VersionStr(2, 0, 0)).is_some());
+        assert!(folder.get_factorio(VersionStr(3, 0, 0)).is_none());
+        assert!(folder.get_factorio(VersionStr(4, 0, 0)).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_installation() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let version_dir = temp_dir.path().join("2.0.60");
+        assert_eq!(
+            check_installation(&version_dir, true)?,
+            InstallCheck::Missing
+        );
+
+        let factorio_dir = version_dir.join("factorio");
+        create_dir_all(factorio_dir.join("bin/x64"))?;
+        std::fs::write(factorio_dir.join("config-path.cfg"), b"config")?;
+        assert_eq!(
+            check_installation(&version_dir, true)?,
+            InstallCheck::Incomplete
+        );
+        std::fs::write(factorio_dir.join(FACTORIO_BINARY), b"binary")?;
+        assert_eq!(
+            check_installation(&version_dir, true)?,
+            InstallCheck::Unverified
+        );
+
+        write_manifest(&version_dir, Some("abc".to_string()))?;
+        assert_eq!(
+            check_installation(&version_dir, true)?,
+            InstallCheck::Intact
+        );
+
+        // files created by running Factorio don't count
+        std::fs::write(factorio_dir.join("factorio-current.log"), b"log")?;
+        assert_eq!(
+            check_installation(&version_dir, true)?,
+            InstallCheck::Intact
+        );
+
+        std::fs::write(factorio_dir.join(FACTORIO_BINARY), b"binarY")?;
+        std::fs::remove_file(factorio_dir.join("config-path.cfg"))?;
+        let corrupted = InstallCheck::Corrupted {
+            files: vec![FACTORIO_BINARY.to_string(), "config-path.cfg".to_string()],
+        };
+        assert_eq!(check_installation(&version_dir, true)?, corrupted);
+        // the binary was modified after the manifest, so it is hashed anyway
+        assert_eq!(check_installation(&version_dir, false)?, corrupted);
+
+        std::fs::remove_dir_all(&factorio_dir)?;
+        assert_eq!(
+            check_installation(&version_dir, true)?,
+            InstallCheck::Incomplete
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_and_repair_keeps_legacy_installs() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path();
+        let factorio_dir = path.join("2.0.60").join("factorio");
+        create_dir_all(factorio_dir.join("bin/x64"))?;
+        std::fs::write(factorio_dir.join(FACTORIO_BINARY), b"binary")?;
+        create_dir(path.join(format!("{STAGING_PREFIX}abc")))?;
+
+        let folder = FactorioInstallDir::new(path)?;
+        assert!(folder.verify_and_repair().await?.is_empty());
+        assert!(!path.join(format!("{STAGING_PREFIX}abc")).exists());
+        assert_eq!(
+            folder.verify_installation(VersionStr(2, 0, 60))?,
+            InstallCheck::Intact
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_installed_versions_include_partial_installs() -> Result<(), FactorioError> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path();
+        create_dir_all(path.join("2.0.0").join("factorio"))?;
+        create_dir(path.join("1.0.0"))?;
+        create_dir(path.join("ignored"))?;
+        File::create(path.join("3.0.0"))?;
+
+        let folder = FactorioInstallDir::new(path)?;
+        assert_eq!(
+            folder.installed_versions()?,
+            [VersionStr(1, 0, 0), VersionStr(2, 0, 0)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_checksum() {
+        let sums = "\
+aaa  factorio-space-age_linux_2.0.57.tar.xz
+bbb  factorio-headless_linux_2.0.57.tar.xz
+ccc  factorio-headless_linux_2.0.58.tar.xz
+ddd  factorio_headless_x64_1.1.110.tar.xz
+";
+        let find = |version| find_checksum(sums, version, "headless", "tar.xz");
+        assert_eq!(find(VersionStr(2, 0, 57)).as_deref(), Some("bbb"));
+        assert_eq!(find(VersionStr(1, 1, 110)).as_deref(), Some("ddd"));
+        assert_eq!(find(VersionStr(2, 0, 5)), None);
+    }
+
+    #[test]
+    fn test_published_versions() {
+        let sums = "\
+aaa  factorio-space-age_linux_2.0.59.tar.xz
+bbb  factorio-headless_linux_2.0.58.tar.xz
+ccc  factorio-headless_linux_2.0.57.tar.xz
+ddd  factorio_headless_x64_1.1.110.tar.xz
+eee  factorio-headless_linux_2.0.57.zip
+";
+        assert_eq!(
+            published_versions(sums),
+            [
+                VersionStr(1, 1, 110),
+                VersionStr(2, 0, 57),
+                VersionStr(2, 0, 58)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_credentials_stay_private() {
+        let credentials = FactorioCredentials {
+            username: "some one".to_string(),
+            token: "s3cr&t".to_string(),
+        };
+        assert_eq!(
+            credentials.form_body(),
+            "username=some%20one&token=s3cr%26t"
+        );
+        assert!(!format!("{:?}", credentials).contains("s3cr"));
+    }
+
+    #[test]
+    fn test_normalize_layout() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let windows_layout = temp_dir.path().join("windows");
+        create_dir_all(windows_layout.join("Factorio_2.0.57/bin/x64"))?;
+        normalize_layout(&windows_layout)?;
+        assert!(windows_layout.join("factorio/bin/x64").is_dir());
+        assert!(!windows_layout.join("Factorio_2.0.57").exists());
+
+        let linux_layout = temp_dir.path().join("linux");
+        create_dir_all(linux_layout.join("factorio/bin/x64"))?;
+        normalize_layout(&linux_layout)?;
+        assert!(linux_layout.join("factorio/bin/x64").is_dir());
+
+        let ambiguous = temp_dir.path().join("ambiguous");
+        create_dir_all(ambiguous.join("a"))?;
+        create_dir_all(ambiguous.join("b"))?;
+        assert!(normalize_layout(&ambiguous).is_err());
+        Ok(())
+    }
 }
@@ -1,4 +1,10 @@
+use crate::app_bundle::{BUNDLE_BINARY, is_app_bundle};
+use crate::autosave;
 use crate::error::FactorioError;
+use crate::factorio_image::FactorioImage;
+#[cfg(windows)]
+use crate::job_object::JobObject;
+use crate::process_manager::{Container, ResourceLimits, Sandbox};
 use crate::save_file::SaveFile;
 use async_process::{Child, Command};
 use futures::io::{AsyncReadExt, BufReader};
@@ -10,14 +16,47 @@ use std::{
     path::{Path, PathBuf},
 };
 
+/// The Factorio executable, relative to an installation.
+#[cfg(not(windows))]
+pub const FACTORIO_BINARY: &str = "bin/x64/factorio";
+#[cfg(windows)]
+pub const FACTORIO_BINARY: &str = "bin/x64/factorio.exe";
+
 pub struct FactorioInstance {
     install_dir_abs: PathBuf,
+    sandbox: Sandbox,
+    limits: ResourceLimits,
+    /// The image Factorio runs from, and the resolved reference to run; the install dir
+    /// then only holds write data.
+    image: Option<(FactorioImage, String)>,
 }
 
 impl FactorioInstance {
     pub fn new(install_dir: PathBuf) -> Result<Self, FactorioError> {
         let install_dir_abs = install_dir.canonicalize()?;
-        Ok(FactorioInstance { install_dir_abs })
+        Ok(FactorioInstance {
+            install_dir_abs,
+            sandbox: Sandbox::None,
+            limits: ResourceLimits::default(),
+            image: None,
+        })
+    }
+
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Runs Factorio from `reference`, resolved from `image`, instead of the install dir.
+    /// The image's container replaces the sandbox.
+    pub fn with_image(mut self, image: FactorioImage, reference: String) -> Self {
+        self.image = Some((image, reference));
+        self
     }
 
     pub fn install_dir(&self) -> &Path {
@@ -41,6 +80,20 @@ impl FactorioInstance {
         SaveFile::new(file)
     }
 
+    /// Has Factorio autosave every `minutes`, for checkpoints of long replays, and removes
+    /// the autosaves of earlier replays. None restores the default interval.
+    pub fn set_autosave_interval(&self, minutes: Option<u32>) -> Result<(), FactorioError> {
+        autosave::set_autosave_interval(&self.install_dir_abs, minutes)?;
+        autosave::remove_autosaves(&self.install_dir_abs)?;
+        Ok(())
+    }
+
+    /// Moves the newest autosave to `dest`, as the checkpoint of the replay that just ran.
+    /// Returns false if Factorio didn't autosave.
+    pub fn take_latest_autosave(&self, dest: &Path) -> Result<bool, FactorioError> {
+        Ok(autosave::take_latest_autosave(&self.install_dir_abs, dest)?)
+    }
+
     pub fn delete_saves_dir(&self) -> Result<(), FactorioError> {
         let saves_path = self.install_dir_abs.join("saves");
         if saves_path.exists() {
@@ -49,32 +102,81 @@ impl FactorioInstance {
         Ok(())
     }
 
-    fn new_run_command(&self) -> Command {
-        let factorio_path = self.install_dir_abs.join("bin/x64/factorio");
+    /// The Factorio executable, which is inside the app bundle of macOS installs.
+    pub fn binary_path(&self) -> PathBuf {
+        if is_app_bundle(&self.install_dir_abs) {
+            self.install_dir_abs.join(BUNDLE_BINARY)
+        } else {
+            self.install_dir_abs.join(FACTORIO_BINARY)
+        }
+    }
 
-        std::env::var("FACTORIO_WRAPPER")
-            .ok()
-            .map(|wrapper| {
-                let mut cmd = Command::new(wrapper);
-                cmd.arg(&factorio_path);
-                cmd
-            })
-            .unwrap_or_else(|| Command::new(factorio_path))
+    /// The command running Factorio, and the container it runs in, if any.
+    fn new_run_command(&self, args: &[&str]) -> (Command, Option<Container>) {
+        if let Some((image, reference)) = &self.image {
+            let container = image.container();
+            let mut line = image
+                .command_line(
+                    reference,
+                    &container,
+                    &self.install_dir_abs,
+                    &self.limits,
+                    args,
+                )
+                .into_iter();
+            let mut cmd = Command::new(line.next().expect("command line is never empty"));
+            cmd.args(line);
+            return (cmd, Some(container));
+        }
+        let factorio_path = self.binary_path();
+        let program = std::env::var_os("FACTORIO_WRAPPER")
+            .into_iter()
+            .chain([factorio_path.into_os_string()])
+            .collect();
+        self.sandbox
+            .command(&self.install_dir_abs, &self.limits, program, args)
     }
 
     pub fn spawn(&self, args: &[&str]) -> Result<FactorioProcess, FactorioError> {
-        let mut cmd = self.new_run_command();
-        cmd.stdin(Stdio::null()).stdout(Stdio::piped()).args(args);
+        let (mut cmd, container) = self.new_run_command(args);
+        cmd.stdin(Stdio::null()).stdout(Stdio::piped());
+        #[cfg(windows)]
+        {
+            use async_process::windows::CommandExt;
+            // its own process group, so terminate() can send it Ctrl+Break
+            cmd.creation_flags(windows_sys::Win32::System::Threading::CREATE_NEW_PROCESS_GROUP);
+        }
 
         debug!("Launching: {:?}", cmd);
 
         let child = cmd.spawn().map_err(FactorioError::ProcessSpawnFailed)?;
         debug!("Spawned Factorio process with PID {}", child.id());
-        Ok(FactorioProcess::new(child))
+        #[cfg(windows)]
+        {
+            let job = JobObject::new(&self.limits).map_err(FactorioError::ProcessSpawnFailed)?;
+            job.assign(&child)
+                .map_err(FactorioError::ProcessSpawnFailed)?;
+            Ok(FactorioProcess {
+                child,
+                container,
+                job: Some(job),
+            })
+        }
+        #[cfg(not(windows))]
+        {
+            Ok(FactorioProcess::new(child).with_container(container))
+        }
     }
 
-    pub fn spawn_replay(&self, save_path: &Path) -> Result<FactorioProcess, FactorioError> {
-        self.spawn(&["--run-replay", save_path.to_str().unwrap()])
+    /// `extra_args` are passed to Factorio after the replay arguments.
+    pub fn spawn_replay(
+        &self,
+        save_path: &Path,
+        extra_args: &[String],
+    ) -> Result<FactorioProcess, FactorioError> {
+        let mut args = vec!["--run-replay", save_path.to_str().unwrap()];
+        args.extend(extra_args.iter().map(String::as_str));
+        self.spawn(&args)
     }
 
     pub fn spawn_benchmark(
@@ -91,9 +193,8 @@ impl FactorioInstance {
     }
 
     pub async fn run_and_get_output(&self, args: &[&str]) -> Result<Output, FactorioError> {
-        let mut cmd = self.new_run_command();
-        cmd.args(args);
-        debug!("Running: {:?}", &cmd);
+        let (mut cmd, _container) = self.new_run_command(args);
+        debug!("Running: {:?}", cmd);
         cmd.output()
             .await
             .map_err(FactorioError::ProcessSpawnFailed)
@@ -102,11 +203,35 @@ impl FactorioInstance {
 
 pub struct FactorioProcess {
     child: Child,
+    /// The container Factorio runs in, which outlives a killed `child`.
+    container: Option<Container>,
+    /// Holds the process and its children; closing it kills them.
+    #[cfg(windows)]
+    job: Option<JobObject>,
 }
 
 impl FactorioProcess {
     pub fn new(child: Child) -> Self {
-        FactorioProcess { child }
+        FactorioProcess {
+            child,
+            container: None,
+            #[cfg(windows)]
+            job: None,
+        }
+    }
+
+    pub fn with_container(mut self, container: Option<Container>) -> Self {
+        self.container = container;
+        self
+    }
+
+    /// Kills the container, if Factorio runs in one that may still be running.
+    fn kill_container(&mut self) {
+        if let Some(container) = &self.container
+            && matches!(self.child.try_status(), Ok(None))
+        {
+            container.kill();
+        }
     }
 
     pub fn stdout_reader(
@@ -129,6 +254,7 @@ impl FactorioProcess {
         self.child.status().await
     }
 
+    #[cfg(unix)]
     pub fn terminate(&mut self) {
         let pid = self.child.id();
         unsafe {
@@ -137,16 +263,40 @@ impl FactorioProcess {
         }
     }
 
+    /// Ctrl+Break is the closest Windows has to SIGINT; Factorio exits on either.
+    #[cfg(windows)]
+    pub fn terminate(&mut self) {
+        use windows_sys::Win32::System::Console::{CTRL_BREAK_EVENT, GenerateConsoleCtrlEvent};
+        // SAFETY: the process was spawned in its own group, whose id is its pid
+        unsafe {
+            GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, self.child.id());
+        }
+    }
+
+    #[cfg(unix)]
     pub fn kill(&mut self) {
+        self.kill_container();
         let pid = self.child.id();
         unsafe {
             libc::kill(pid as libc::pid_t, libc::SIGKILL);
         }
     }
+
+    #[cfg(windows)]
+    pub fn kill(&mut self) {
+        self.kill_container();
+        if let Some(job) = &self.job
+            && job.terminate().is_ok()
+        {
+            return;
+        }
+        self.child.kill().ok();
+    }
 }
 
 impl Drop for FactorioProcess {
     fn drop(&mut self) {
+        self.kill_container();
         self.terminate();
         self.child.kill().ok();
     }
@@ -171,7 +321,8 @@ mod tests {
         let factorio = FactorioInstance::test_installation().await;
         let install_dir = factorio.install_dir();
         assert!(install_dir.exists());
-        assert!(install_dir.join("bin/x64/factorio").exists());
+        assert!(install_dir.join(FACTORIO_BINARY).exists());
+        assert_eq!(factorio.binary_path(), install_dir.join(FACTORIO_BINARY));
         Ok(())
     }
 
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::FactorioError;
+use crate::factorio_install_dir::VersionStr;
+
+/// Which Factorio version replays a save, given the version the save is from.
+///
+/// Written as `exact`, `latest_patch` or a version to pin, e.g. `2.0.60`. Factorio loads saves
+/// from older versions but not newer ones, so a save is never run on a version older than its own.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(try_from = "String", into = "String")]
+pub enum InstallVersionPolicy {
+    /// The save's own version.
+    #[default]
+    Exact,
+    /// The newest patch of the save's `major.minor` published on factorio.com, so every
+    /// worker picks the same one whatever it has installed.
+    LatestPatch,
+    /// Always this version, e.g. the one a category's rules were written for.
+    Pinned(VersionStr),
+}
+
+impl InstallVersionPolicy {
+    /// Whether resolving needs the list of published versions.
+    pub fn needs_published_versions(self) -> bool {
+        self == Self::LatestPatch
+    }
+
+    /// The version to run a save from `save_version` on, given the `published` versions.
+    pub fn resolve(
+        self,
+        save_version: VersionStr,
+        published: &[VersionStr],
+    ) -> Result<VersionStr, FactorioError> {
+        match self {
+            Self::Exact => Ok(save_version),
+            Self::LatestPatch => Ok(published
+                .iter()
+                .copied()
+                .filter(|version| {
+                    (version.0, version.1) == (save_version.0, save_version.1)
+                        && *version >= save_version
+                })
+                .max()
+                .unwrap_or(save_version)),
+            Self::Pinned(version) if version < save_version => {
+                Err(FactorioError::SaveNewerThanInstall {
+                    save_version,
+                    version,
+                })
+            }
+            Self::Pinned(version) => Ok(version),
+        }
+    }
+}
+
+impl TryFrom<String> for InstallVersionPolicy {
+    type Error = FactorioError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "exact" => Ok(Self::Exact),
+            "latest_patch" => Ok(Self::LatestPatch),
+            version => VersionStr::try_from(version).map(Self::Pinned),
+        }
+    }
+}
+
+impl From<InstallVersionPolicy> for String {
+    fn from(value: InstallVersionPolicy) -> Self {
+        match value {
+            InstallVersionPolicy::Exact => "exact".to_string(),
+            InstallVersionPolicy::LatestPatch => "latest_patch".to_string(),
+            InstallVersionPolicy::Pinned(version) => version.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLISHED: &[VersionStr] = &[
+        VersionStr(1, 1, 110),
+        VersionStr(2, 0, 55),
+        VersionStr(2, 0, 60),
+        VersionStr(2, 0, 58),
+    ];
+
+    #[test]
+    fn test_resolve() {
+        let resolve = |policy: InstallVersionPolicy, save| policy.resolve(save, PUBLISHED).ok();
+        use InstallVersionPolicy::*;
+
+        assert_eq!(
+            resolve(Exact, VersionStr(2, 0, 57)),
+            Some(VersionStr(2, 0, 57))
+        );
+
+        assert_eq!(
+            resolve(LatestPatch, VersionStr(2, 0, 57)),
+            Some(VersionStr(2, 0, 60))
+        );
+        assert_eq!(
+            resolve(LatestPatch, VersionStr(1, 1, 100)),
+            Some(VersionStr(1, 1, 110))
+        );
+        // nothing published can load it
+        assert_eq!(
+            resolve(LatestPatch, VersionStr(2, 0, 61)),
+            Some(VersionStr(2, 0, 61))
+        );
+        assert_eq!(
+            resolve(LatestPatch, VersionStr(2, 1, 0)),
+            Some(VersionStr(2, 1, 0))
+        );
+
+        let pinned = Pinned(VersionStr(2, 0, 58));
+        assert_eq!(
+            resolve(pinned, VersionStr(2, 0, 55)),
+            Some(VersionStr(2, 0, 58))
+        );
+        assert_eq!(
+            resolve(pinned, VersionStr(2, 0, 58)),
+            Some(VersionStr(2, 0, 58))
+        );
+        assert!(matches!(
+            pinned.resolve(VersionStr(2, 0, 60), PUBLISHED),
+            Err(FactorioError::SaveNewerThanInstall { .. })
+        ));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let parse = |yaml| serde_yaml::from_str::<InstallVersionPolicy>(yaml).unwrap();
+        assert_eq!(parse("exact"), InstallVersionPolicy::Exact);
+        assert_eq!(parse("latest_patch"), InstallVersionPolicy::LatestPatch);
+        let pinned = InstallVersionPolicy::Pinned(VersionStr(2, 0, 60));
+        assert_eq!(parse("2.0.60"), pinned);
+        assert_eq!(serde_yaml::to_string(&pinned).unwrap(), "2.0.60\n");
+        assert!(serde_yaml::from_str::<InstallVersionPolicy>("newest").is_err());
+    }
+}
@@ -0,0 +1,99 @@
+//! Windows Job Objects, which stand in for signals and cgroups: every process Factorio
+//! starts stays in its job, the job enforces [`ResourceLimits`], and closing the job
+//! kills whatever is still running.
+
+use std::io;
+use std::os::windows::io::AsRawHandle;
+
+use async_process::Child;
+use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+use windows_sys::Win32::System::JobObjects::{
+    AssignProcessToJobObject, CreateJobObjectW, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    JOB_OBJECT_LIMIT_PRIORITY_CLASS, JOB_OBJECT_LIMIT_PROCESS_MEMORY,
+    JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JobObjectExtendedLimitInformation,
+    SetInformationJobObject, TerminateJobObject,
+};
+use windows_sys::Win32::System::Threading::{
+    ABOVE_NORMAL_PRIORITY_CLASS, BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS,
+    IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+};
+
+use crate::process_manager::ResourceLimits;
+
+pub struct JobObject(HANDLE);
+
+// SAFETY: job object handles may be used from any thread
+unsafe impl Send for JobObject {}
+unsafe impl Sync for JobObject {}
+
+impl JobObject {
+    /// A job killing its processes when closed, with `limits` applied. `cpu_weight` isn't
+    /// supported on Windows.
+    pub fn new(limits: &ResourceLimits) -> io::Result<Self> {
+        // SAFETY: null attributes and name create an anonymous job with default security
+        let handle = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let job = JobObject(handle);
+
+        let mut info = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        if let Some(mb) = limits.max_memory_mb {
+            info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+            info.ProcessMemoryLimit = (mb * 1024 * 1024) as usize;
+        }
+        if let Some(nice) = limits.nice {
+            info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_PRIORITY_CLASS;
+            info.BasicLimitInformation.PriorityClass = priority_class(nice);
+        }
+        // SAFETY: info is a valid JOBOBJECT_EXTENDED_LIMIT_INFORMATION of the given size
+        let ok = unsafe {
+            SetInformationJobObject(
+                job.0,
+                JobObjectExtendedLimitInformation,
+                (&raw const info).cast(),
+                size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            )
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(job)
+    }
+
+    pub fn assign(&self, child: &Child) -> io::Result<()> {
+        // SAFETY: both handles are open for the duration of the call
+        if unsafe { AssignProcessToJobObject(self.0, child.as_raw_handle()) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Kills every process in the job.
+    pub fn terminate(&self) -> io::Result<()> {
+        // SAFETY: the handle is open until drop
+        if unsafe { TerminateJobObject(self.0, 1) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for JobObject {
+    fn drop(&mut self) {
+        // SAFETY: the handle is closed only here
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+/// The Windows priority class closest to a Unix niceness.
+fn priority_class(nice: i32) -> u32 {
+    match nice {
+        ..=-15 => HIGH_PRIORITY_CLASS,
+        -14..=-1 => ABOVE_NORMAL_PRIORITY_CLASS,
+        0 => NORMAL_PRIORITY_CLASS,
+        1..=14 => BELOW_NORMAL_PRIORITY_CLASS,
+        15.. => IDLE_PRIORITY_CLASS,
+    }
+}
@@ -1,7 +1,15 @@
+pub mod app_bundle;
+pub mod autosave;
 mod cmd;
 pub mod error;
 pub mod expected_mods;
+pub mod factorio_image;
 pub mod factorio_install_dir;
 pub mod factorio_instance;
+pub mod install_version;
+#[cfg(windows)]
+mod job_object;
 pub mod mod_versions;
+pub mod process_manager;
+pub mod save_analysis;
 pub mod save_file;
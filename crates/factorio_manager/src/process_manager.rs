@@ -0,0 +1,620 @@
+use async_process::Command;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::ffi::{OsStr, OsString};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Installation directories mounted read-only inside a sandbox; the rest of the install
+/// dir holds Factorio's write data (logs, mods, saves) and stays writable.
+const READ_ONLY_INSTALL_DIRS: &[&str] = &["bin", "data"];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerRuntime {
+    #[default]
+    Docker,
+    Podman,
+}
+
+impl ContainerRuntime {
+    pub fn program(self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Podman => "podman",
+        }
+    }
+}
+
+/// A container started with a unique name. Killing the runtime's `run` client leaves the
+/// container running, so it is killed by name instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+    runtime: ContainerRuntime,
+    name: String,
+}
+
+impl Container {
+    pub fn new(runtime: ContainerRuntime) -> Self {
+        static SEQ: AtomicU64 = AtomicU64::new(0);
+        let name = format!(
+            "factorio-replay-{}-{}",
+            std::process::id(),
+            SEQ.fetch_add(1, Ordering::Relaxed)
+        );
+        Self { runtime, name }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kill(&self) {
+        let killed = std::process::Command::new(self.runtime.program())
+            .args(["kill", &self.name])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+        if let Err(e) = killed {
+            log::warn!("Failed to kill container {}: {e}", self.name);
+        }
+    }
+}
+
+/// OS-level isolation for Factorio processes, which run untrusted Lua from downloaded saves.
+/// Every sandbox has no network access. Bubblewrap and Firejail are Linux only.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Sandbox {
+    #[default]
+    None,
+    Bubblewrap,
+    Firejail,
+    /// Runs in a container from `image`, which must provide glibc.
+    Container {
+        #[serde(default)]
+        runtime: ContainerRuntime,
+        image: String,
+    },
+}
+
+/// Limits for each Factorio process, so a pathological save can't take down the host.
+///
+/// Memory and CPU limits put the process in its own cgroup with `systemd-run`, or are
+/// passed to the container runtime. Where `systemd-run` can't create cgroups, the memory
+/// limit is set as the process's data size rlimit instead, and `cpu_weight` is ignored. On
+/// Windows, memory and niceness are applied through the process's job object instead, and
+/// `cpu_weight` is ignored.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(default, deny_unknown_fields)]
+pub struct ResourceLimits {
+    /// Maximum resident memory in MB, at least 256; the process is killed past this.
+    #[serde(deserialize_with = "deserialize_max_memory_mb")]
+    pub max_memory_mb: Option<u64>,
+    /// Relative CPU share from 1 to 10000, where other processes have 100.
+    #[serde(deserialize_with = "deserialize_cpu_weight")]
+    pub cpu_weight: Option<u32>,
+    /// Scheduling niceness, from -20 to 19.
+    #[serde(deserialize_with = "deserialize_nice")]
+    pub nice: Option<i32>,
+}
+
+/// Factorio can't load a save in less.
+const MIN_MEMORY_MB: u64 = 256;
+/// Larger limits overflow when converted to bytes.
+const MAX_MEMORY_MB: u64 = u64::MAX / (1024 * 1024);
+
+fn deserialize_in_range<'de, D, T>(deserializer: D, min: T, max: T) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + PartialOrd + Display,
+{
+    let value = Option::<T>::deserialize(deserializer)?;
+    match value {
+        Some(value) if value < min || value > max => Err(serde::de::Error::custom(format!(
+            "{value} is not between {min} and {max}"
+        ))),
+        value => Ok(value),
+    }
+}
+
+fn deserialize_max_memory_mb<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u64>, D::Error> {
+    deserialize_in_range(d, MIN_MEMORY_MB, MAX_MEMORY_MB)
+}
+
+fn deserialize_cpu_weight<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u32>, D::Error> {
+    deserialize_in_range(d, 1, 10000)
+}
+
+fn deserialize_nice<'de, D: Deserializer<'de>>(d: D) -> Result<Option<i32>, D::Error> {
+    deserialize_in_range(d, -20, 19)
+}
+
+/// Whether `systemd-run` can put processes in their own cgroup here; checked once.
+fn cgroups_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        if cfg!(windows) {
+            return false;
+        }
+        let mut cmd = std::process::Command::new("systemd-run");
+        cmd.args(["--scope", "--quiet", "--collect"]);
+        if user_ids().is_some_and(|(uid, _)| uid != 0) {
+            cmd.arg("--user");
+        }
+        let available = cmd
+            .arg("true")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !available {
+            log::warn!(
+                "systemd-run can't create cgroups; limiting Factorio's memory with an rlimit instead"
+            );
+        }
+        available
+    })
+}
+
+impl ResourceLimits {
+    fn needs_cgroup(&self) -> bool {
+        self.max_memory_mb.is_some() || self.cpu_weight.is_some()
+    }
+
+    fn systemd_run(&self) -> Vec<OsString> {
+        if cfg!(windows) {
+            return Vec::new();
+        }
+        let properties = self
+            .max_memory_mb
+            .into_iter()
+            .flat_map(|mb| [format!("MemoryMax={mb}M"), "MemorySwapMax=0".to_string()])
+            .chain(self.cpu_weight.map(|weight| format!("CPUWeight={weight}")))
+            .collect::<Vec<_>>();
+        if properties.is_empty() {
+            return Vec::new();
+        }
+        let mut line = Vec::new();
+        push(
+            &mut line,
+            ["systemd-run", "--scope", "--quiet", "--collect"],
+        );
+        if user_ids().is_some_and(|(uid, _)| uid != 0) {
+            push(&mut line, ["--user"]);
+        }
+        for property in properties {
+            push(&mut line, ["-p".to_string(), property]);
+        }
+        line
+    }
+
+    fn container_flags(&self) -> Vec<OsString> {
+        let mut line = Vec::new();
+        if let Some(mb) = self.max_memory_mb {
+            push(
+                &mut line,
+                [format!("--memory={mb}m"), format!("--memory-swap={mb}m")],
+            );
+        }
+        if let Some(weight) = self.cpu_weight {
+            // container runtimes use cgroup v1 shares, where 1024 is the default
+            push(&mut line, [format!("--cpu-shares={}", weight * 1024 / 100)]);
+        }
+        line
+    }
+
+    fn nice(&self) -> Vec<OsString> {
+        let mut line = Vec::new();
+        if let Some(nice) = self.nice
+            && !cfg!(windows)
+        {
+            push(
+                &mut line,
+                ["nice".to_string(), "-n".to_string(), nice.to_string()],
+            );
+        }
+        line
+    }
+}
+
+impl Sandbox {
+    /// The container the process runs in, for [`Self::command_line`], if this is a container.
+    pub fn container(&self) -> Option<Container> {
+        match self {
+            Sandbox::Container { runtime, .. } => Some(Container::new(*runtime)),
+            _ => None,
+        }
+    }
+
+    /// The full command line running `program` with `args` in this sandbox, under `limits`.
+    /// Container sandboxes are named after `container`.
+    ///
+    /// In a sandbox, arguments naming existing files are made absolute, and their
+    /// directories are mounted read-only.
+    pub fn command_line(
+        &self,
+        install_dir: &Path,
+        limits: &ResourceLimits,
+        container: Option<&Container>,
+        program: Vec<OsString>,
+        args: &[&str],
+    ) -> Vec<OsString> {
+        let cgroups = limits.needs_cgroup() && cgroups_available();
+        self.command_line_with(install_dir, limits, cgroups, container, program, args)
+    }
+
+    /// [`Self::command_line`], putting the process in a cgroup only if `cgroups`.
+    fn command_line_with(
+        &self,
+        install_dir: &Path,
+        limits: &ResourceLimits,
+        cgroups: bool,
+        container: Option<&Container>,
+        program: Vec<OsString>,
+        args: &[&str],
+    ) -> Vec<OsString> {
+        let args = if *self == Sandbox::None {
+            args.iter().map(Into::into).collect::<Vec<OsString>>()
+        } else {
+            absolute_args(args)
+        };
+        let arg_dirs = arg_dirs(&args, install_dir);
+        let read_only_dirs = READ_ONLY_INSTALL_DIRS
+            .iter()
+            .map(|dir| install_dir.join(dir))
+            .collect::<Vec<_>>();
+
+        let mut line = Vec::new();
+        if cgroups && !matches!(self, Sandbox::Container { .. }) {
+            line.extend(limits.systemd_run());
+        }
+        match self {
+            Sandbox::None => {}
+            Sandbox::Bubblewrap => {
+                push(&mut line, ["bwrap", "--unshare-all", "--die-with-parent"]);
+                for dir in ["/usr", "/bin", "/lib", "/lib64"] {
+                    push(&mut line, ["--ro-bind-try", dir, dir]);
+                }
+                push(
+                    &mut line,
+                    ["--dev", "/dev", "--proc", "/proc", "--tmpfs", "/tmp"],
+                );
+                push(
+                    &mut line,
+                    [
+                        OsStr::new("--bind"),
+                        install_dir.as_ref(),
+                        install_dir.as_ref(),
+                    ],
+                );
+                for dir in read_only_dirs.iter().chain(&arg_dirs) {
+                    push(
+                        &mut line,
+                        [OsStr::new("--ro-bind"), dir.as_ref(), dir.as_ref()],
+                    );
+                }
+            }
+            Sandbox::Firejail => {
+                push(
+                    &mut line,
+                    ["firejail", "--quiet", "--net=none", "--private-tmp"],
+                );
+                for dir in &read_only_dirs {
+                    push(&mut line, [format!("--read-only={}", dir.display())]);
+                }
+            }
+            Sandbox::Container { runtime, image } => {
+                let mounts = [read_only_dirs, arg_dirs].concat();
+                line.extend(container_run(
+                    *runtime,
+                    container.map(Container::name),
+                    limits,
+                    install_dir,
+                    &mounts,
+                ));
+                push(&mut line, [image]);
+            }
+        }
+        line.extend(limits.nice());
+        line.extend(program);
+        line.extend(args);
+        line
+    }
+
+    /// The command running `program`, and the container it runs in, if any.
+    pub fn command(
+        &self,
+        install_dir: &Path,
+        limits: &ResourceLimits,
+        program: Vec<OsString>,
+        args: &[&str],
+    ) -> (Command, Option<Container>) {
+        let cgroups = limits.needs_cgroup() && cgroups_available();
+        let container = self.container();
+        let mut line = self
+            .command_line_with(
+                install_dir,
+                limits,
+                cgroups,
+                container.as_ref(),
+                program,
+                args,
+            )
+            .into_iter();
+        let mut cmd = std::process::Command::new(line.next().expect("command line is never empty"));
+        cmd.args(line);
+        #[cfg(unix)]
+        if !cgroups
+            && !matches!(self, Sandbox::Container { .. })
+            && let Some(mb) = limits.max_memory_mb
+        {
+            set_memory_rlimit(&mut cmd, mb);
+        }
+        (cmd.into(), container)
+    }
+}
+
+/// Limits the data segment, which covers the heap and private mappings, of the process
+/// `cmd` starts. It is inherited through the sandbox's own programs.
+#[cfg(unix)]
+fn set_memory_rlimit(cmd: &mut std::process::Command, mb: u64) {
+    use std::os::unix::process::CommandExt;
+
+    let bytes = (mb * 1024 * 1024) as libc::rlim_t;
+    let limit = libc::rlimit {
+        rlim_cur: bytes,
+        rlim_max: bytes,
+    };
+    // SAFETY: setrlimit is async-signal-safe, and `limit` is copied into the closure
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_DATA, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// The start of a `run` command for a container named `name` without network access,
+/// running as the current user under `limits`. `write_dir` is mounted writable, and
+/// `read_only_dirs` read-only, each at the same path as on the host. The image and its
+/// command go after this.
+pub(crate) fn container_run(
+    runtime: ContainerRuntime,
+    name: Option<&str>,
+    limits: &ResourceLimits,
+    write_dir: &Path,
+    read_only_dirs: &[PathBuf],
+) -> Vec<OsString> {
+    let mut line = Vec::new();
+    push(
+        &mut line,
+        [runtime.program(), "run", "--rm", "--network=none"],
+    );
+    if let Some(name) = name {
+        push(&mut line, ["--name", name]);
+    }
+    if let Some((uid, gid)) = user_ids() {
+        push(&mut line, ["--user".to_string(), format!("{uid}:{gid}")]);
+    }
+    line.extend(limits.container_flags());
+    push(
+        &mut line,
+        [
+            "--volume".to_string(),
+            format!("{0}:{0}", write_dir.display()),
+        ],
+    );
+    for dir in read_only_dirs {
+        push(
+            &mut line,
+            ["--volume".to_string(), format!("{0}:{0}:ro", dir.display())],
+        );
+    }
+    line
+}
+
+/// `args`, with the ones naming existing files made absolute.
+pub(crate) fn absolute_args(args: &[&str]) -> Vec<OsString> {
+    args.iter()
+        .map(|arg| {
+            Path::new(arg)
+                .canonicalize()
+                .map(PathBuf::into_os_string)
+                .unwrap_or_else(|_| arg.into())
+        })
+        .collect()
+}
+
+/// Directories outside `install_dir` holding files named by `args`, to mount read-only.
+pub(crate) fn arg_dirs(args: &[OsString], install_dir: &Path) -> Vec<PathBuf> {
+    args.iter()
+        .map(Path::new)
+        .filter(|path| path.is_absolute() && path.exists())
+        .filter_map(Path::parent)
+        .filter(|dir| !dir.starts_with(install_dir))
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+/// The current user and group ids; None on Windows, which has no equivalent.
+pub(crate) fn user_ids() -> Option<(u32, u32)> {
+    #[cfg(unix)]
+    {
+        // SAFETY: getuid and getgid cannot fail
+        Some(unsafe { (libc::getuid(), libc::getgid()) })
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+pub(crate) fn push<S: AsRef<OsStr>>(line: &mut Vec<OsString>, parts: impl IntoIterator<Item = S>) {
+    line.extend(parts.into_iter().map(|part| part.as_ref().to_owned()));
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn command_line(sandbox: &Sandbox, install_dir: &Path, args: &[&str]) -> Vec<String> {
+        limited_command_line(sandbox, &ResourceLimits::default(), install_dir, args)
+    }
+
+    fn limited_command_line(
+        sandbox: &Sandbox,
+        limits: &ResourceLimits,
+        install_dir: &Path,
+        args: &[&str],
+    ) -> Vec<String> {
+        let factorio = install_dir.join("bin/x64/factorio");
+        let container = sandbox.container().map(|container| Container {
+            name: "test".to_string(),
+            ..container
+        });
+        sandbox
+            .command_line_with(
+                install_dir,
+                limits,
+                true,
+                container.as_ref(),
+                vec![factorio.into()],
+                args,
+            )
+            .into_iter()
+            .map(|part| part.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_no_sandbox() {
+        let line = command_line(&Sandbox::None, Path::new("/opt/f"), &["--version"]);
+        assert_eq!(line, ["/opt/f/bin/x64/factorio", "--version"]);
+    }
+
+    #[test]
+    fn test_bubblewrap() {
+        let saves = tempfile::tempdir().unwrap();
+        let saves_dir = saves.path().canonicalize().unwrap();
+        let save = saves_dir.join("run.zip");
+        std::fs::write(&save, "").unwrap();
+
+        let line = command_line(
+            &Sandbox::Bubblewrap,
+            Path::new("/opt/f"),
+            &["--run-replay", save.to_str().unwrap()],
+        )
+        .join(" ");
+        assert!(line.starts_with("bwrap --unshare-all"));
+        assert!(line.contains("--bind /opt/f /opt/f"));
+        assert!(line.contains("--ro-bind /opt/f/bin /opt/f/bin"));
+        assert!(line.contains(&format!("--ro-bind {0} {0}", saves_dir.display())));
+        assert!(line.ends_with(&format!(
+            "/opt/f/bin/x64/factorio --run-replay {}",
+            save.display()
+        )));
+    }
+
+    #[test]
+    fn test_container() {
+        let sandbox: Sandbox =
+            serde_yaml::from_str("{ type: container, runtime: podman, image: debian:12 }").unwrap();
+        let line = command_line(&sandbox, Path::new("/opt/f"), &["--version"]).join(" ");
+        assert!(line.starts_with("podman run --rm --network=none --name test --user "));
+        assert!(line.contains("--volume /opt/f:/opt/f --volume /opt/f/bin:/opt/f/bin:ro"));
+        assert!(line.ends_with("debian:12 /opt/f/bin/x64/factorio --version"));
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        let limits: ResourceLimits =
+            serde_yaml::from_str("{ max_memory_mb: 4096, cpu_weight: 50, nice: 10 }").unwrap();
+        let line =
+            limited_command_line(&Sandbox::None, &limits, Path::new("/opt/f"), &[]).join(" ");
+        assert!(line.starts_with("systemd-run --scope"));
+        assert!(line.contains("-p MemoryMax=4096M -p MemorySwapMax=0 -p CPUWeight=50"));
+        assert!(line.ends_with("nice -n 10 /opt/f/bin/x64/factorio"));
+
+        let sandbox = Sandbox::Container {
+            runtime: ContainerRuntime::Docker,
+            image: "debian:12".to_string(),
+        };
+        let line = limited_command_line(&sandbox, &limits, Path::new("/opt/f"), &[]).join(" ");
+        assert!(line.starts_with("docker run"));
+        assert!(line.contains("--memory=4096m --memory-swap=4096m --cpu-shares=512"));
+        assert!(line.ends_with("debian:12 nice -n 10 /opt/f/bin/x64/factorio"));
+
+        let nice_only = ResourceLimits {
+            nice: Some(5),
+            ..Default::default()
+        };
+        let line = limited_command_line(&Sandbox::None, &nice_only, Path::new("/opt/f"), &[]);
+        assert_eq!(line, ["nice", "-n", "5", "/opt/f/bin/x64/factorio"]);
+
+        // without cgroups the memory limit is an rlimit instead
+        let line = Sandbox::None.command_line_with(
+            Path::new("/opt/f"),
+            &limits,
+            false,
+            None,
+            vec!["/opt/f/bin/x64/factorio".into()],
+            &[],
+        );
+        assert_eq!(line, ["nice", "-n", "10", "/opt/f/bin/x64/factorio"]);
+    }
+
+    #[test]
+    fn test_resource_limits_validated() {
+        let parse = |yaml: &str| serde_yaml::from_str::<ResourceLimits>(yaml);
+        assert!(parse("{ max_memory_mb: 0 }").is_err());
+        assert!(parse("{ max_memory_mb: 100 }").is_err());
+        assert!(parse("{ cpu_weight: 0 }").is_err());
+        assert!(parse("{ cpu_weight: 10001 }").is_err());
+        assert!(parse("{ nice: 20 }").is_err());
+        assert_eq!(parse("{}").unwrap(), ResourceLimits::default());
+        assert_eq!(
+            parse("{ max_memory_mb: 256 }").unwrap().max_memory_mb,
+            Some(256)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_rlimit() {
+        let mut cmd = std::process::Command::new("sh");
+        cmd.args(["-c", "ulimit -d"]);
+        set_memory_rlimit(&mut cmd, 512);
+        let output = Command::from(cmd).output().await.unwrap();
+        // ulimit reports kilobytes
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "524288");
+    }
+}
+
+#[cfg(all(test, windows))]
+mod windows_tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_left_to_job_object() {
+        let limits = ResourceLimits {
+            max_memory_mb: Some(4096),
+            cpu_weight: Some(50),
+            nice: Some(10),
+        };
+        let factorio = OsString::from(r"C:\factorio\bin\x64\factorio.exe");
+        let line = Sandbox::None.command_line(
+            Path::new(r"C:\factorio"),
+            &limits,
+            None,
+            vec![factorio.clone()],
+            &["--version"],
+        );
+        assert_eq!(line, [factorio, "--version".into()]);
+    }
+}
@@ -0,0 +1,331 @@
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use itertools::Itertools;
+use serde::Serialize;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::error::FactorioError;
+use crate::save_file::{REPLAY_SCRIPT_MARKER, SaveFile, WrittenSaveFile};
+
+/// control.lua of saves started from the vanilla freeplay scenario.
+const VANILLA_CONTROL_LUA: &[&str] = &["require('__base__/script/freeplay/control.lua')"];
+
+/// Zip timestamps are in the uploader's local time, so allow for any timezone.
+const TIMESTAMP_TOLERANCE: TimeDelta = TimeDelta::days(1);
+
+#[derive(Debug, Default, Clone)]
+pub struct SaveExpectations {
+    pub submitted_date: Option<DateTime<Utc>>,
+    /// Skip the check that control.lua is vanilla, for scenario categories.
+    pub allow_custom_control_lua: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SaveAnomaly {
+    MissingReplay,
+    ReplayScriptAlreadyInstalled,
+    ModifiedControlLua,
+    DuplicateEntries { names: Vec<String> },
+    ModifiedAfterSubmission { name: String, modified: String },
+}
+
+impl SaveAnomaly {
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            Self::MissingReplay
+                | Self::ReplayScriptAlreadyInstalled
+                | Self::DuplicateEntries { .. }
+        )
+    }
+}
+
+impl fmt::Display for SaveAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingReplay => write!(f, "Save has no replay.dat; no replay was recorded"),
+            Self::ReplayScriptAlreadyInstalled => {
+                write!(f, "control.lua already contains a replay script")
+            }
+            Self::ModifiedControlLua => {
+                write!(f, "control.lua differs from the vanilla freeplay scenario")
+            }
+            Self::DuplicateEntries { names } => {
+                write!(f, "Save has duplicated entries: {}", names.join(", "))
+            }
+            Self::ModifiedAfterSubmission { name, modified } => {
+                write!(f, "{} was modified at {}, after submission", name, modified)
+            }
+        }
+    }
+}
+
+/// Looks for signs that a save was not produced by an unmodified game.
+pub fn analyze_save(
+    WrittenSaveFile(save_path, save_file): &mut WrittenSaveFile,
+    expectations: &SaveExpectations,
+) -> Result<Vec<SaveAnomaly>, FactorioError> {
+    let mut anomalies = Vec::new();
+
+    if !save_file.has_inner_file("replay.dat") {
+        anomalies.push(SaveAnomaly::MissingReplay);
+    }
+
+    let control_lua = save_file.get_control_lua_contents()?;
+    if control_lua.contains(REPLAY_SCRIPT_MARKER) {
+        anomalies.push(SaveAnomaly::ReplayScriptAlreadyInstalled);
+    } else if !expectations.allow_custom_control_lua
+        && !VANILLA_CONTROL_LUA.contains(&control_lua.trim())
+    {
+        anomalies.push(SaveAnomaly::ModifiedControlLua);
+    }
+
+    let names = read_central_directory_names(save_path)
+        .map_err(|e| FactorioError::InvalidSaveFile(e.into()))?;
+    let duplicates = names.into_iter().duplicates().sorted().collect_vec();
+    if !duplicates.is_empty() {
+        anomalies.push(SaveAnomaly::DuplicateEntries { names: duplicates });
+    }
+
+    if let Some(submitted_date) = expectations.submitted_date {
+        anomalies.extend(modified_after(
+            save_file,
+            submitted_date + TIMESTAMP_TOLERANCE,
+        ));
+    }
+
+    Ok(anomalies)
+}
+
+fn modified_after<F: Read + Seek>(
+    save_file: &mut SaveFile<F>,
+    cutoff: DateTime<Utc>,
+) -> Vec<SaveAnomaly> {
+    let zip = &mut save_file.zip;
+    (0..zip.len())
+        .filter_map(|i| {
+            let entry = zip.by_index_raw(i).ok()?;
+            let modified = entry.last_modified()?;
+            // zeroed timestamps (e.g. from zip tools that strip them) are not dates
+            let modified = NaiveDate::from_ymd_opt(
+                modified.year().into(),
+                modified.month().into(),
+                modified.day().into(),
+            )?
+            .and_hms_opt(
+                modified.hour().into(),
+                modified.minute().into(),
+                modified.second().into(),
+            )?
+            .and_utc();
+            (modified > cutoff).then(|| SaveAnomaly::ModifiedAfterSubmission {
+                name: entry.name().to_string(),
+                modified: modified.format("%Y-%m-%d %H:%M").to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Every file name in the zip's central directory, including duplicates, which the zip
+/// reader silently collapses. Empty for zip64 archives.
+fn read_central_directory_names(path: &Path) -> std::io::Result<Vec<String>> {
+    const EOCD_SIGNATURE: &[u8] = b"PK\x05\x06";
+    const EOCD_SIZE: u64 = 22;
+    const ENTRY_SIGNATURE: &[u8] = b"PK\x01\x02";
+    const ENTRY_SIZE: usize = 46;
+
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let tail_len = len.min(EOCD_SIZE + u16::MAX as u64);
+    file.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+
+    let Some(eocd) = tail
+        .windows(EOCD_SIGNATURE.len())
+        .rposition(|window| window == EOCD_SIGNATURE)
+        .map(|pos| &tail[pos..])
+        .filter(|eocd| eocd.len() >= EOCD_SIZE as usize)
+    else {
+        return Ok(Vec::new());
+    };
+    let u16_at = |bytes: &[u8], pos: usize| u16::from_le_bytes([bytes[pos], bytes[pos + 1]]);
+    let u32_at =
+        |bytes: &[u8], pos: usize| u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap());
+
+    let entry_count = u16_at(eocd, 10);
+    let directory_size = u32_at(eocd, 12);
+    let directory_offset = u32_at(eocd, 16);
+    if entry_count == u16::MAX || directory_offset == u32::MAX {
+        return Ok(Vec::new());
+    }
+    // both come from the untrusted record, so check them before allocating
+    if u64::from(directory_offset) + u64::from(directory_size) > len {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Central directory extends past the end of the file",
+        ));
+    }
+
+    let mut directory = vec![0; directory_size as usize];
+    file.seek(SeekFrom::Start(directory_offset.into()))?;
+    file.read_exact(&mut directory)?;
+
+    let mut names = Vec::new();
+    let mut pos = 0;
+    while directory.len() >= pos + ENTRY_SIZE && directory[pos..].starts_with(ENTRY_SIGNATURE) {
+        let name_len = u16_at(&directory, pos + 28) as usize;
+        let extra_len = u16_at(&directory, pos + 30) as usize;
+        let comment_len = u16_at(&directory, pos + 32) as usize;
+        let name_start = pos + ENTRY_SIZE;
+        let Some(name) = directory.get(name_start..name_start + name_len) else {
+            break;
+        };
+        names.push(String::from_utf8_lossy(name).into_owned());
+        pos = name_start + name_len + extra_len + comment_len;
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+    use zip::{DateTime as ZipDateTime, ZipWriter, write::SimpleFileOptions};
+
+    fn analyze(
+        file: &NamedTempFile,
+        expectations: &SaveExpectations,
+    ) -> anyhow::Result<Vec<SaveAnomaly>> {
+        let save_file = SaveFile::new(File::open(file.path())?)?;
+        let mut save = WrittenSaveFile(file.path().to_path_buf(), save_file);
+        Ok(analyze_save(&mut save, expectations)?)
+    }
+
+    fn create_save(
+        files: &[(&str, &str)],
+        modified: Option<ZipDateTime>,
+    ) -> anyhow::Result<NamedTempFile> {
+        let temp_file = NamedTempFile::new()?;
+        let mut zip = ZipWriter::new(temp_file.reopen()?);
+        let mut options = SimpleFileOptions::default();
+        if let Some(modified) = modified {
+            options = options.last_modified_time(modified);
+        }
+        for &(name, content) in files {
+            zip.start_file(name, options)?;
+            zip.write_all(content.as_bytes())?;
+        }
+        zip.finish()?;
+        Ok(temp_file)
+    }
+
+    #[test]
+    fn test_fixture_has_no_anomalies() -> anyhow::Result<()> {
+        let path = test_utils::fixtures_dir().join("TEST.zip");
+        let save_file = SaveFile::new(File::open(&path)?)?;
+        let expectations = SaveExpectations {
+            submitted_date: Some(Utc::now()),
+            allow_custom_control_lua: false,
+        };
+        let anomalies = analyze_save(&mut WrittenSaveFile(path, save_file), &expectations)?;
+        assert_eq!(anomalies, []);
+        Ok(())
+    }
+
+    #[test]
+    fn test_anomalies() -> anyhow::Result<()> {
+        let control_lua = format!("-- custom\n{REPLAY_SCRIPT_MARKER}\n");
+        let file = create_save(
+            &[("save/control.lua", &control_lua), ("save/level.dat0", "")],
+            Some(ZipDateTime::from_date_and_time(2024, 3, 10, 12, 0, 0)?),
+        )?;
+        let expectations = SaveExpectations {
+            submitted_date: Some("2024-03-01T00:00:00Z".parse()?),
+            allow_custom_control_lua: false,
+        };
+        let anomalies = analyze(&file, &expectations)?;
+        assert_eq!(
+            anomalies,
+            [
+                SaveAnomaly::MissingReplay,
+                SaveAnomaly::ReplayScriptAlreadyInstalled,
+                SaveAnomaly::ModifiedAfterSubmission {
+                    name: "save/control.lua".to_string(),
+                    modified: "2024-03-10 12:00".to_string(),
+                },
+                SaveAnomaly::ModifiedAfterSubmission {
+                    name: "save/level.dat0".to_string(),
+                    modified: "2024-03-10 12:00".to_string(),
+                },
+            ]
+        );
+        assert!(anomalies[0].is_error());
+        assert!(!anomalies[2].is_error());
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_control_lua() -> anyhow::Result<()> {
+        let files = [
+            ("save/control.lua", "require('scenario')"),
+            ("save/replay.dat", ""),
+        ];
+        let mut expectations = SaveExpectations::default();
+        let anomalies = analyze(&create_save(&files, None)?, &expectations)?;
+        assert_eq!(anomalies, [SaveAnomaly::ModifiedControlLua]);
+
+        expectations.allow_custom_control_lua = true;
+        let anomalies = analyze(&create_save(&files, None)?, &expectations)?;
+        assert_eq!(anomalies, []);
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_entries() -> anyhow::Result<()> {
+        let file = create_save(
+            &[
+                ("save/control.lua", VANILLA_CONTROL_LUA[0]),
+                ("save/replay.dat", ""),
+                ("save/replay.da2", "hidden"),
+            ],
+            None,
+        )?;
+        // the zip writer refuses duplicate names, so rename the entry in place
+        let mut bytes = std::fs::read(file.path())?;
+        let (from, to) = (b"save/replay.da2", b"save/replay.dat");
+        while let Some(pos) = bytes.windows(from.len()).position(|window| window == from) {
+            bytes[pos..pos + to.len()].copy_from_slice(to);
+        }
+        std::fs::write(file.path(), bytes)?;
+
+        let anomalies = analyze(&file, &SaveExpectations::default())?;
+        assert_eq!(
+            anomalies,
+            [SaveAnomaly::DuplicateEntries {
+                names: vec!["save/replay.dat".to_string()]
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_oversized_central_directory_rejected() -> anyhow::Result<()> {
+        let file = create_save(&[("save/control.lua", VANILLA_CONTROL_LUA[0])], None)?;
+        let mut bytes = std::fs::read(file.path())?;
+        let eocd = bytes
+            .windows(4)
+            .rposition(|window| window == b"PK\x05\x06")
+            .unwrap();
+        bytes[eocd + 12..eocd + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(file.path(), bytes)?;
+
+        let err = read_central_directory_names(file.path()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::Context;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use std::{
     fmt::Display,
     fs::File,
@@ -11,8 +12,57 @@ use zip::{ZipArchive, ZipWriter, read::ZipFile, result::ZipResult, write::Simple
 use crate::error::FactorioError;
 use crate::factorio_install_dir::VersionStr;
 
+/// Line separating a save's control.lua from the installed replay script.
+pub const REPLAY_SCRIPT_MARKER: &str = "-- Begin replay script";
+
+/// Lua installed before the replay script when using [`ScriptInjection::Require`].
+const CHAIN_HANDLERS_LUA: &str = include_str!("chain_handlers.lua");
+const REPLAY_SCRIPT_FILE: &str = "replay_script.lua";
+const ORIGINAL_CONTROL_FILE: &str = "original_control.lua";
+
+/// How the replay script is added to a save's control.lua.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptInjection {
+    /// Appends the script to control.lua; enough for saves using the vanilla event_handler.
+    #[default]
+    Append,
+    /// Moves control.lua and the replay script into separate required files, and chains
+    /// event handlers so the save's scripts can't replace the replay script's. For
+    /// scenarios that return early from control.lua or register events directly.
+    Require,
+}
+
+impl ScriptInjection {
+    /// Files of the save's script directory that installing `replay_script` writes, by name,
+    /// given the save's original `control_lua`.
+    pub fn files(
+        self,
+        control_lua: &str,
+        replay_script: impl Display,
+    ) -> Vec<(&'static str, String)> {
+        match self {
+            ScriptInjection::Append => vec![(
+                "control.lua",
+                format!("{control_lua}\n\n{REPLAY_SCRIPT_MARKER}\n{replay_script}\n"),
+            )],
+            ScriptInjection::Require => vec![
+                (
+                    "control.lua",
+                    format!(
+                        "{REPLAY_SCRIPT_MARKER}\n{CHAIN_HANDLERS_LUA}\nrequire(\"replay_script\")\nrequire(\"original_control\")\n"
+                    ),
+                ),
+                (REPLAY_SCRIPT_FILE, format!("{replay_script}\n")),
+                (ORIGINAL_CONTROL_FILE, control_lua.to_string()),
+            ],
+        }
+    }
+}
+
 pub struct SaveFile<F: Read + Seek> {
-    zip: ZipArchive<F>,
+    pub(crate) zip: ZipArchive<F>,
     save_name: String,
     control_lua_contents: Option<String>,
 }
@@ -37,6 +87,14 @@ impl<F: Read + Seek> SaveFile<F> {
 
 pub struct WrittenSaveFile(pub PathBuf, pub SaveFile<File>);
 
+/// A mod enabled in a save, as recorded in its level-init.dat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveMod {
+    pub name: String,
+    pub version: VersionStr,
+    pub crc: u32,
+}
+
 fn find_save_name<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<String, FactorioError> {
     let save_name = (0..zip.len())
         .filter_map(|i| zip.by_index_raw(i).ok().and_then(|f| f.enclosed_name()))
@@ -88,6 +146,12 @@ impl<F: Read + Seek> SaveFile<F> {
             .map_err(FactorioError::InvalidSaveFile)
     }
 
+    pub(crate) fn has_inner_file(&self, path: impl AsRef<Path>) -> bool {
+        self.zip
+            .index_for_name(&self.inner_file_path(path))
+            .is_some()
+    }
+
     pub fn get_control_lua_contents(&mut self) -> Result<&str, FactorioError> {
         if self.control_lua_contents.is_none() {
             let contents = read_to_new_string(self.get_inner_file("control.lua")?)
@@ -118,15 +182,37 @@ impl<F: Read + Seek> SaveFile<F> {
         Ok(VersionStr::new(major, minor, patch))
     }
 
+    /// The mods the save was made with, in load order.
+    pub fn get_mods(&mut self) -> Result<Vec<SaveMod>, FactorioError> {
+        let mut data = Vec::new();
+        self.get_inner_file("level-init.dat")?
+            .read_to_end(&mut data)
+            .map_err(anyhow::Error::from)
+            .map_err(FactorioError::InvalidSaveFile)?;
+        parse_mod_list(&data).ok_or_else(|| {
+            FactorioError::InvalidSaveFile(anyhow::anyhow!("Mod list not found in level-init.dat"))
+        })
+    }
+
+    /// Contents of a file in the save, by its path relative to the save's root folder.
+    pub fn read_file(&mut self, path: impl AsRef<Path>) -> Result<Vec<u8>, FactorioError> {
+        let mut data = Vec::new();
+        self.get_inner_file(path)?
+            .read_to_end(&mut data)
+            .map_err(anyhow::Error::from)
+            .map_err(FactorioError::InvalidSaveFile)?;
+        Ok(data)
+    }
+
     fn copy_files_except(
         &mut self,
         out: &mut ZipWriter<impl Seek + Write>,
-        exclude_file: &str,
+        exclude_files: &[String],
     ) -> ZipResult<()> {
         let zip = &mut self.zip;
         for i in 0..zip.len() {
             let entry = zip.by_index(i).unwrap();
-            if entry.name() == exclude_file {
+            if exclude_files.iter().any(|name| name == entry.name()) {
                 continue;
             }
             out.raw_copy_file(entry)?;
@@ -138,31 +224,111 @@ impl<F: Read + Seek> SaveFile<F> {
         &mut self,
         out_file: &mut File,
         replay_script: impl Display,
+        injection: ScriptInjection,
     ) -> Result<(), FactorioError> {
-        let ctrl_lua_path = self.inner_file_path("control.lua");
-        let ctrl_lua_contents = self.get_control_lua_contents()?.to_string();
+        let files = injection.files(self.get_control_lua_contents()?, replay_script);
+        let paths = files
+            .iter()
+            .map(|(name, _)| self.inner_file_path(name))
+            .collect_vec();
 
         let mut zip = ZipWriter::new(out_file);
-        self.copy_files_except(&mut zip, &ctrl_lua_path)
+        self.copy_files_except(&mut zip, &paths)
             .context("Failed to copy files")
             .map_err(FactorioError::ScriptInjectionFailed)?;
 
-        zip.start_file(ctrl_lua_path, SimpleFileOptions::default())
-            .context("Failed to start control.lua file in zip")
-            .map_err(FactorioError::ScriptInjectionFailed)?;
-        writeln!(
-            zip,
-            r"{ctrl_lua_contents}
-
--- Begin replay script
-{replay_script}",
-        )
-        .context("Failed to write control.lua contents")
-        .map_err(FactorioError::ScriptInjectionFailed)?;
+        for (path, (_, contents)) in paths.into_iter().zip(files) {
+            zip.start_file(&path, SimpleFileOptions::default())
+                .and_then(|()| Ok(zip.write_all(contents.as_bytes())?))
+                .with_context(|| format!("Failed to write {path}"))
+                .map_err(FactorioError::ScriptInjectionFailed)?;
+        }
         Ok(())
     }
 }
 
+/// Finds the mod list in a level-init.dat header.
+///
+/// The header fields before the list differ between Factorio versions, so rather than
+/// parsing them, this looks for the first position where a complete list starting with
+/// `base` (always the first mod) can be read.
+fn parse_mod_list(data: &[u8]) -> Option<Vec<SaveMod>> {
+    const BASE: &[u8] = b"\x04base";
+    // skip the save version
+    (8..data.len().saturating_sub(BASE.len()))
+        .filter(|&pos| data[pos + 1..].starts_with(BASE))
+        .find_map(|pos| {
+            let mut reader = HeaderReader { data, pos };
+            let count = reader.read_optimized_u32()?;
+            (0..count).map(|_| reader.read_mod()).collect()
+        })
+}
+
+/// Reads the space-optimized encoding used in level-init.dat.
+struct HeaderReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> HeaderReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn read_u8(&mut self) -> Option<u8> {
+        self.read_bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn read_u16(&mut self) -> Option<u16> {
+        self.read_bytes(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        self.read_bytes(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// One byte, or 0xFF followed by the full value.
+    fn read_optimized_u16(&mut self) -> Option<u16> {
+        match self.read_u8()? {
+            0xFF => self.read_u16(),
+            value => Some(value.into()),
+        }
+    }
+
+    fn read_optimized_u32(&mut self) -> Option<u32> {
+        match self.read_u8()? {
+            0xFF => self.read_u32(),
+            value => Some(value.into()),
+        }
+    }
+
+    fn read_mod(&mut self) -> Option<SaveMod> {
+        let len = self.read_optimized_u32()? as usize;
+        let name = std::str::from_utf8(self.read_bytes(len)?).ok()?;
+        let valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ' '));
+        if !valid_name {
+            return None;
+        }
+        let version = VersionStr::new(
+            self.read_optimized_u16()?,
+            self.read_optimized_u16()?,
+            self.read_optimized_u16()?,
+        );
+        Some(SaveMod {
+            name: name.to_string(),
+            version,
+            crc: self.read_u32()?,
+        })
+    }
+}
+
 #[cfg(test)]
 pub(crate) const TEST_VERSION: VersionStr = VersionStr::new(2, 0, 57);
 
@@ -274,6 +440,99 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_file() -> anyhow::Result<()> {
+        let mut save_file = SaveFile::get_test_save_file()?;
+        assert_eq!(
+            save_file.read_file("control.lua")?,
+            b"require('__base__/script/freeplay/control.lua')\n"
+        );
+        assert!(save_file.read_file("missing.dat").is_err());
+        Ok(())
+    }
+
+    fn read_installed(file: &NamedTempFile, name: &str) -> anyhow::Result<String> {
+        let mut zip = ZipArchive::new(File::open(file.path())?)?;
+        Ok(read_to_new_string(zip.by_name(name)?)?)
+    }
+
+    #[test]
+    fn test_install_replay_script() -> anyhow::Result<()> {
+        let mut save_file = SaveFile::get_test_save_file()?;
+
+        let out = NamedTempFile::new()?;
+        save_file.install_replay_script_to(
+            &mut out.reopen()?,
+            "-- the script",
+            ScriptInjection::Append,
+        )?;
+        let control_lua = read_installed(&out, "TEST/control.lua")?;
+        assert!(control_lua.starts_with("require('__base__/script/freeplay/control.lua')"));
+        assert!(control_lua.ends_with(&format!("{REPLAY_SCRIPT_MARKER}\n-- the script\n")));
+
+        let out = NamedTempFile::new()?;
+        save_file.install_replay_script_to(
+            &mut out.reopen()?,
+            "-- the script",
+            ScriptInjection::Require,
+        )?;
+        let control_lua = read_installed(&out, "TEST/control.lua")?;
+        assert!(control_lua.starts_with(REPLAY_SCRIPT_MARKER));
+        assert!(control_lua.contains(CHAIN_HANDLERS_LUA));
+        assert!(
+            control_lua.ends_with("require(\"replay_script\")\nrequire(\"original_control\")\n")
+        );
+        assert_eq!(
+            read_installed(&out, "TEST/replay_script.lua")?,
+            "-- the script\n"
+        );
+        assert_eq!(
+            read_installed(&out, "TEST/original_control.lua")?,
+            "require('__base__/script/freeplay/control.lua')\n"
+        );
+        assert!(read_installed(&out, "TEST/description.json").is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_mods() -> anyhow::Result<()> {
+        let mut save_file = SaveFile::get_test_save_file()?;
+        let mods = save_file.get_mods()?;
+        let names = mods.iter().map(|m| m.name.as_str()).collect_vec();
+        assert_eq!(names, ["base", "elevated-rails", "quality", "space-age"]);
+        assert!(mods.iter().all(|m| m.version == TEST_VERSION));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_mod_list_skips_unknown_header() {
+        let mut data = vec![2, 0, 0, 0, 57, 0, 0, 0];
+        // header strings that mention base but aren't the mod list
+        data.extend(b"\x08freeplay\x04base\x01\x00\x00");
+        data.extend([7, 2]);
+        data.extend(b"\x04base\x02\x00\x39");
+        data.extend(1234u32.to_le_bytes());
+        data.extend(b"\x0amy_QoL-mod\x01\xFF\x2c\x01\x07");
+        data.extend(5678u32.to_le_bytes());
+
+        let mods = parse_mod_list(&data).unwrap();
+        assert_eq!(
+            mods,
+            [
+                SaveMod {
+                    name: "base".to_string(),
+                    version: VersionStr::new(2, 0, 57),
+                    crc: 1234,
+                },
+                SaveMod {
+                    name: "my_QoL-mod".to_string(),
+                    version: VersionStr::new(1, 300, 7),
+                    crc: 5678,
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_get_factorio_version() -> anyhow::Result<()> {
         let mut save_file = SaveFile::get_test_save_file()?;
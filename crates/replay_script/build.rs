@@ -1,6 +1,7 @@
 use glob::glob;
 use itertools::Itertools;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use std::env;
 use std::fs;
 use std::io::Write;
@@ -19,6 +20,14 @@ struct ScriptMetadataParse {
     enable_if: Option<String>,
     #[serde(default)]
     enable_value: Option<String>,
+    #[serde(default)]
+    disable_value: Option<String>,
+    #[serde(default)]
+    min_version: Option<String>,
+    #[serde(default)]
+    max_version: Option<String>,
+    #[serde(default)]
+    optional: bool,
 }
 
 #[derive(Debug)]
@@ -29,6 +38,10 @@ struct ScriptMetadata {
     default_value: String,
     enable_if: String,
     enable_value: String,
+    disable_value: String,
+    min_version: Option<(u16, u16, u16)>,
+    max_version: Option<(u16, u16, u16)>,
+    optional: bool,
 }
 
 impl ScriptMetadata {
@@ -87,6 +100,19 @@ impl ScriptMetadata {
                 ),
             });
 
+        let disable_value = parse
+            .disable_value
+            .unwrap_or_else(|| match enable_if.as_str() {
+                "param" => "false".to_string(),
+                "!param" => "true".to_string(),
+                "param.is_some()" => "None".to_string(),
+                "!param.is_empty()" => "vec![]".to_string(),
+                _ => panic!(
+                    "A value needs to be provided for \"disable\" condition: {}",
+                    enable_if
+                ),
+            });
+
         Self {
             file_name,
             name,
@@ -94,23 +120,143 @@ impl ScriptMetadata {
             default_value,
             enable_if,
             enable_value,
+            disable_value,
+            min_version: parse.min_version.as_deref().map(parse_version),
+            max_version: parse.max_version.as_deref().map(parse_version),
+            optional: parse.optional,
         }
     }
 }
 
+/// Directory of compiled Lua to use instead of compiling `tstl_src`, such as an unpacked
+/// prebuilt bundle.
+const LUA_DIR_ENV: &str = "REPLAY_SCRIPT_LUA_DIR";
+/// Compiled Lua used with the `vendored-lua` feature, so the crate builds without bun.
+/// Written by `vendor-lua.sh`.
+const VENDORED_DIR: &str = "vendored";
+/// Hash of the sources the vendored Lua was compiled from, to warn when it is out of date.
+const SOURCE_HASH_FILE: &str = "SOURCE_HASH";
+
 fn main() {
     println!("cargo:rerun-if-changed=package.json");
     println!("cargo:rerun-if-changed=bun.lock");
     println!("cargo:rerun-if-changed=tsconfig.json");
     println!("cargo:rerun-if-changed=tstl_src/");
+    println!("cargo:rerun-if-env-changed={LUA_DIR_ENV}");
 
     let out_dir = env::var("OUT_DIR").unwrap();
-    run_bun_command("tstl_src", "bun", &["i"]);
-    run_bun_command("tstl_src", "bunx", &["tstl", "--outDir", &out_dir]);
+    if let Some(lua_dir) = env::var_os(LUA_DIR_ENV) {
+        let lua_dir = Path::new(&lua_dir);
+        println!("cargo:rerun-if-changed={}", lua_dir.display());
+        copy_lua(lua_dir, Path::new(&out_dir));
+    } else if env::var_os("CARGO_FEATURE_VENDORED_LUA").is_some() {
+        println!("cargo:rerun-if-changed={VENDORED_DIR}/");
+        use_vendored_lua(Path::new(&out_dir));
+    } else {
+        run_bun_command("tstl_src", "bun", &["i"]);
+        run_bun_command("tstl_src", "bunx", &["tstl", "--outDir", &out_dir]);
+    }
 
     generate_file_list_for_replay_scripts(&out_dir);
 }
 
+fn use_vendored_lua(out_dir: &Path) {
+    let vendored = Path::new(VENDORED_DIR);
+    if !vendored.join("main.lua").exists() {
+        panic!(
+            "No vendored Lua in {}. Generate it with `vendor-lua.sh` (needs bun), \
+             or set {} to a directory of compiled Lua",
+            vendored.display(),
+            LUA_DIR_ENV
+        );
+    }
+    let vendored_hash = fs::read_to_string(vendored.join(SOURCE_HASH_FILE)).unwrap_or_default();
+    if let Some(hash) = source_hash()
+        && vendored_hash.trim() != hash
+    {
+        println!(
+            "cargo:warning=vendored Lua is out of date with tstl_src; regenerate it with `vendor-lua.sh`"
+        );
+    }
+    copy_lua(vendored, out_dir);
+}
+
+/// Copies the `.lua` files of `from` to `to`, checking that every script was compiled.
+fn copy_lua(from: &Path, to: &Path) {
+    let mut expected = vec![from.join("main.lua")];
+    for path in glob("tstl_src/rules/*.ts")
+        .expect("Failed to read glob pattern")
+        .flatten()
+    {
+        let file_name = path.file_stem().unwrap();
+        expected.push(from.join("rules").join(file_name).with_extension("lua"));
+    }
+    let missing = expected.iter().filter(|path| !path.exists()).collect_vec();
+    if !missing.is_empty() {
+        panic!(
+            "Compiled Lua in {:?} is missing scripts: {:?}",
+            from, missing
+        );
+    }
+
+    let pattern = format!("{}/**/*.lua", from.display());
+    for path in glob(&pattern)
+        .expect("Failed to read glob pattern")
+        .flatten()
+    {
+        let dest = to.join(path.strip_prefix(from).unwrap());
+        fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        fs::copy(&path, &dest)
+            .unwrap_or_else(|e| panic!("Failed to copy {:?} to {:?}: {}", path, dest, e));
+    }
+}
+
+/// SHA-256 of the `sha256sum` listing of the sources git tracks that are compiled to Lua,
+/// as `vendor-lua.sh` writes it. None outside of a git checkout.
+fn source_hash() -> Option<String> {
+    let output = Command::new("git")
+        .args([
+            "ls-files",
+            "-z",
+            "--",
+            "package.json",
+            "bun.lock",
+            "tsconfig.json",
+        ])
+        .arg("tstl_src")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let files = String::from_utf8(output.stdout).ok()?;
+    let mut listing = String::new();
+    for file in files.split('\0').filter(|file| !file.is_empty()).sorted() {
+        let content = fs::read(file).ok()?;
+        listing.push_str(&format!("{}  {}\n", hex_digest(&content), file));
+    }
+    Some(hex_digest(listing.as_bytes()))
+}
+
+fn hex_digest(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Parses `major.minor.patch`.
+fn parse_version(version: &str) -> (u16, u16, u16) {
+    let parts = version
+        .split('.')
+        .map(|part| part.parse::<u16>())
+        .collect::<Result<Vec<_>, _>>();
+    match parts.as_deref() {
+        Ok([major, minor, patch]) => (*major, *minor, *patch),
+        _ => panic!("Invalid version, expected major.minor.patch: {}", version),
+    }
+}
+
 fn run_bun_command(working_dir: &str, cmd: &str, args: &[&str]) {
     let mut cmd = Command::new(cmd);
     cmd.current_dir(working_dir);
@@ -164,15 +310,36 @@ fn parse_script_metadata(file_name: &str, file_path: &Path) -> ScriptMetadata {
     ScriptMetadata::from(parse, file_name)
 }
 
-fn formatter_for_type(type_name: &str) -> &str {
+/// Rust expression turning `value` (a reference to `type_name`) into a Lua literal.
+fn formatter_for_type(type_name: &str) -> String {
     match type_name {
-        "bool" => "value.to_string()",
-        "u8" | "u16" | "u32" | "u64" | "i8" | "i16" | "i32" | "i64" => "value.to_string()",
-        "String" => "format!(\"\\\"{{}}\\\"\", value)",
+        "bool" => "value.to_string()".to_string(),
+        "u8" | "u16" | "u32" | "u64" | "i8" | "i16" | "i32" | "i64" => {
+            "value.to_string()".to_string()
+        }
+        "String" => "lua_string(value)".to_string(),
+        "WinCondition" => "value.to_lua()".to_string(),
+        _ if type_name.starts_with("Vec<") && type_name.ends_with('>') => {
+            let inner_formatter = formatter_for_type(&type_name[4..type_name.len() - 1]);
+            format!(
+                r#"format!("{{{{{{}}}}}}", value.iter().map(|value| {inner_formatter}).collect::<Vec<_>>().join(","))"#
+            )
+        }
         _ => panic!("Unsupported param_type: {}", type_name),
     }
 }
 
+fn is_copy_type(type_name: &str) -> bool {
+    let inner = type_name
+        .strip_prefix("Option<")
+        .and_then(|t| t.strip_suffix('>'))
+        .unwrap_or(type_name);
+    matches!(
+        inner,
+        "bool" | "u8" | "u16" | "u32" | "u64" | "i8" | "i16" | "i32" | "i64"
+    )
+}
+
 fn generate_file_list_for_replay_scripts(out_dir: &str) {
     let mut scripts = Vec::new();
 
@@ -224,21 +391,14 @@ fn generate_file_list_for_replay_scripts(out_dir: &str) {
                         let inner_type = &param_type[7..param_type.len() - 1];
                         let inner_formatter = formatter_for_type(inner_type);
                         format!(
-                            "self.{}.map(|value| {}).unwrap_or_else(|| \"undefined\".to_string())",
+                            "self.{}.as_ref().map(|value| {}).unwrap_or_else(|| \"undefined\".to_string())",
                             name, inner_formatter
                         )
-                    } else if param_type.starts_with("Vec<") {
-                        let mut s = String::new();
-                        s.push_str(r#"{ let items: Vec<String> = self."#);
-                        s.push_str(name);
-                        s.push_str(r#".iter().map(|s| format!("\"{}\"", s)).collect(); "#);
-                        s.push_str(r#"format!("{{{}}}", items.join(",")) }"#);
-                        s
                     } else {
-                        formatter_for_type(param_type).replace("value", &format!("self.{}", name))
+                        format!("{{ let value = &self.{}; {} }}", name, formatter_for_type(param_type))
                     };
 
-                let should_borrow = param_type.contains("String") || param_type.starts_with("Vec<");
+                let should_borrow = !is_copy_type(param_type);
                 let borrow_str = if should_borrow { "&" } else { "" };
                 format!(
                     r#"        let param = {borrow_str}self.{name};
@@ -247,7 +407,9 @@ fn generate_file_list_for_replay_scripts(out_dir: &str) {
             let script_content = include_str!(concat!(env!("OUT_DIR"), "/rules/{file_name}.lua"));
             let param_value = {param_formatter};
             let substituted = script_content.replace("PARAM_VALUE", &param_value);
+            writeln!(fmt, "do\nlocal ReplayLog = makeReplayLog(\"{file_name}\")")?;
             fmt.write_str(&substituted)?;
+            writeln!(fmt, "\nend")?;
         }}"#,
                 )
             },
@@ -269,12 +431,76 @@ fn generate_file_list_for_replay_scripts(out_dir: &str) {
         .map(|metadata| format!("\"{}\"", metadata.file_name))
         .join(",");
 
+    let format_version = |version: Option<(u16, u16, u16)>| match version {
+        Some((major, minor, patch)) => format!("Some(({major}, {minor}, {patch}))"),
+        None => "None".to_string(),
+    };
+    let framework = parse_script_metadata("main", Path::new("tstl_src/main.ts"));
+    let framework_versions = format!(
+        "VersionRange {{ min: {}, max: {} }}",
+        format_version(framework.min_version),
+        format_version(framework.max_version)
+    );
+    let script_info = scripts
+        .iter()
+        .map(|metadata| {
+            format!(
+                "        ScriptInfo {{ name: \"{}\", versions: VersionRange {{ min: {}, max: {} }}, optional: {} }},",
+                metadata.file_name,
+                format_version(metadata.min_version),
+                format_version(metadata.max_version),
+                metadata.optional
+            )
+        })
+        .join("\n");
+
+    let enabled_scripts = scripts
+        .iter()
+        .map(|metadata| {
+            let borrow_str = if is_copy_type(&metadata.param_type) {
+                ""
+            } else {
+                "&"
+            };
+            format!(
+                "        {{ let param = {borrow_str}self.{}; if {} {{ enabled.push(\"{}\"); }} }}",
+                metadata.name, metadata.enable_if, metadata.file_name
+            )
+        })
+        .join("\n");
+
+    let disable_arms = scripts
+        .iter()
+        .map(|metadata| {
+            format!(
+                "            \"{}\" => self.{} = {},",
+                metadata.file_name, metadata.name, metadata.disable_value
+            )
+        })
+        .join("\n");
+
+    let builder_setters = scripts
+        .iter()
+        .map(|metadata| {
+            let (param, value) = if metadata.param_type.starts_with("Option<") {
+                (format!("impl Into<{}>", metadata.param_type), "value.into()")
+            } else {
+                (metadata.param_type.clone(), "value")
+            };
+            format!(
+                "    pub fn {}(mut self, value: {}) -> Self {{\n        self.scripts.{} = {};\n        self\n    }}",
+                metadata.name, param, metadata.name, value
+            )
+        })
+        .join("\n\n");
+
     let generated_code = format!(
         r#"// Generated by build.rs
 
 {default_functions}
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct ReplayScripts {{
 {struct_fields}
@@ -298,6 +524,37 @@ impl ReplayScripts {{
     pub fn all_scripts() -> &'static [&'static str] {{
         &[ {all_scripts} ]
     }}
+
+    /// Versions `main`, the framework every script runs in, supports.
+    pub fn framework_versions() -> VersionRange {{
+        {framework_versions}
+    }}
+
+    /// Version support of every script, by file name.
+    pub fn script_info() -> &'static [ScriptInfo] {{
+        &[
+{script_info}
+        ]
+    }}
+
+    /// File names of the scripts these settings enable.
+    pub fn enabled_scripts(&self) -> Vec<&'static str> {{
+        let mut enabled = Vec::new();
+{enabled_scripts}
+        enabled
+    }}
+
+    /// Turns off the script with file name `script`.
+    fn disable(&mut self, script: &str) {{
+        match script {{
+{disable_arms}
+            _ => {{}}
+        }}
+    }}
+}}
+
+impl ReplayScriptsBuilder {{
+{builder_setters}
 }}
 
 impl std::fmt::Display for ReplayScripts {{
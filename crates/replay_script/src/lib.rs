@@ -5,24 +5,303 @@ use std::{
 };
 use strum::{Display, EnumString, VariantArray};
 
+pub mod locale;
+pub mod log_parser;
+
 include!(concat!(env!("OUT_DIR"), "/replay_scripts.rs"));
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, VariantArray, Display, EnumString, PartialOrd, Ord)]
+/// Quoted Lua string literal of `value`. Line breaks are escaped too, as they would end the
+/// literal.
+fn lua_string(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => literal.push_str("\\\\"),
+            '"' => literal.push_str("\\\""),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\0' => literal.push_str("\\000"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Goal that ends a run successfully. The replay fails verification if it is never reached.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum WinCondition {
+    RocketLaunched,
+    ScenarioFinished,
+    ResearchCompleted {
+        tech: String,
+    },
+    /// At least `count` of `item` produced, summed over all surfaces.
+    ItemProduced {
+        item: String,
+        count: u64,
+    },
+}
+
+impl WinCondition {
+    fn to_lua(&self) -> String {
+        match self {
+            Self::RocketLaunched => r#"{type = "rocket_launched"}"#.to_string(),
+            Self::ScenarioFinished => r#"{type = "scenario_finished"}"#.to_string(),
+            Self::ResearchCompleted { tech } => {
+                format!(
+                    r#"{{type = "research_completed", name = {}}}"#,
+                    lua_string(tech)
+                )
+            }
+            Self::ItemProduced { item, count } => format!(
+                r#"{{type = "item_produced", name = {}, count = {}}}"#,
+                lua_string(item),
+                count
+            ),
+        }
+    }
+}
+
+impl ReplayScripts {
+    /// Whether the replay must end through a script-signalled win.
+    pub fn has_win_condition(&self) -> bool {
+        self.win_on_scenario_finished || self.win_condition.is_some()
+    }
+
+    /// These scripts for running on Factorio `version`. Enabled scripts that don't support it
+    /// are left out if they are optional, and are an error otherwise, as is a version the
+    /// framework doesn't support.
+    pub fn for_version(&self, version: GameVersion) -> Result<ReplayScripts, UnsupportedScripts> {
+        self.for_version_of(version, Self::framework_versions(), Self::script_info())
+    }
+
+    fn for_version_of(
+        &self,
+        version: GameVersion,
+        framework_versions: VersionRange,
+        script_info: &[ScriptInfo],
+    ) -> Result<ReplayScripts, UnsupportedScripts> {
+        if !framework_versions.contains(version) {
+            return Err(UnsupportedScripts {
+                version,
+                scripts: vec!["main"],
+            });
+        }
+        let enabled = self.enabled_scripts();
+        let (optional, required): (Vec<&ScriptInfo>, Vec<&ScriptInfo>) = script_info
+            .iter()
+            .filter(|info| enabled.contains(&info.name) && !info.versions.contains(version))
+            .partition(|info| info.optional);
+        if !required.is_empty() {
+            return Err(UnsupportedScripts {
+                version,
+                scripts: required.iter().map(|info| info.name).collect(),
+            });
+        }
+        let mut scripts = self.clone();
+        for info in optional {
+            scripts.disable(info.name);
+        }
+        Ok(scripts)
+    }
+
+    /// Starts a [`ReplayScriptsBuilder`] from the default settings.
+    pub fn builder() -> ReplayScriptsBuilder {
+        ReplayScriptsBuilder::default()
+    }
+
+    /// Checks for settings that can't verify a run, such as a `max_players` of 0.
+    pub fn validate(&self) -> Result<(), InvalidScripts> {
+        let zero = [
+            ("max_players", self.max_players.map(u64::from)),
+            ("max_apm", self.max_apm.map(u64::from)),
+            ("game_speed", self.game_speed.map(u64::from)),
+            ("snapshot_interval", self.snapshot_interval.map(u64::from)),
+            ("stop_at_tick", self.stop_at_tick.map(u64::from)),
+            (
+                "win_condition.count",
+                match &self.win_condition {
+                    Some(WinCondition::ItemProduced { count, .. }) => Some(*count),
+                    _ => None,
+                },
+            ),
+        ]
+        .into_iter()
+        .find(|(_, value)| *value == Some(0));
+        if let Some((setting, _)) = zero {
+            return Err(InvalidScripts::Zero(setting));
+        }
+        let empty = [
+            ("allowed_player_names", &self.allowed_player_names),
+            ("allowed_surfaces", &self.allowed_surfaces),
+        ]
+        .into_iter()
+        .find(|(_, list)| list.as_ref().is_some_and(Vec::is_empty));
+        if let Some((setting, _)) = empty {
+            return Err(InvalidScripts::EmptyList(setting));
+        }
+        if self.win_on_scenario_finished && self.win_condition.is_some() {
+            return Err(InvalidScripts::ConflictingWinConditions);
+        }
+        Ok(())
+    }
+}
+
+/// Builds [`ReplayScripts`], checking the settings when built rather than when a replay
+/// fails. Has a setter for each script, like the fields of [`ReplayScripts`].
+#[derive(Debug, Clone, Default)]
+pub struct ReplayScriptsBuilder {
+    scripts: ReplayScripts,
+    require_win_condition: bool,
+}
+
+impl From<ReplayScripts> for ReplayScriptsBuilder {
+    fn from(scripts: ReplayScripts) -> Self {
+        Self {
+            scripts,
+            require_win_condition: false,
+        }
+    }
+}
+
+impl ReplayScriptsBuilder {
+    /// Fails the build unless the scripts end the replay on a win, for categories that
+    /// are won rather than checked at the end of the replay.
+    pub fn require_win_condition(mut self) -> Self {
+        self.require_win_condition = true;
+        self
+    }
+
+    pub fn build(self) -> Result<ReplayScripts, InvalidScripts> {
+        self.scripts.validate()?;
+        if self.require_win_condition && !self.scripts.has_win_condition() {
+            return Err(InvalidScripts::MissingWinCondition);
+        }
+        Ok(self.scripts)
+    }
+}
+
+/// Settings of [`ReplayScripts`] that can't verify a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidScripts {
+    /// A limit or interval set to 0, by setting name.
+    Zero(&'static str),
+    /// An allow list that allows nothing, by setting name.
+    EmptyList(&'static str),
+    /// Both `win_on_scenario_finished` and `win_condition` are set.
+    ConflictingWinConditions,
+    /// Neither `win_on_scenario_finished` nor `win_condition` is set, but the run must be won.
+    MissingWinCondition,
+}
+
+impl fmt::Display for InvalidScripts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Zero(setting) => write!(f, "{setting} must be at least 1"),
+            Self::EmptyList(setting) => write!(f, "{setting} allows nothing; leave it out instead"),
+            Self::ConflictingWinConditions => {
+                f.write_str("set only one of win_on_scenario_finished and win_condition")
+            }
+            Self::MissingWinCondition => {
+                f.write_str("rules need win_on_scenario_finished or a win_condition")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidScripts {}
+
+/// A Factorio version, as `(major, minor, patch)`.
+pub type GameVersion = (u16, u16, u16);
+
+/// Factorio versions a script runs on, both ends inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionRange {
+    pub min: Option<GameVersion>,
+    pub max: Option<GameVersion>,
+}
+
+impl VersionRange {
+    pub fn contains(&self, version: GameVersion) -> bool {
+        self.min.is_none_or(|min| version >= min) && self.max.is_none_or(|max| version <= max)
+    }
+}
+
+/// Version support of a script, from its `min_version`, `max_version` and `optional`
+/// metadata. Optional scripts only report on the run, so they can be left out on versions
+/// they don't support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptInfo {
+    pub name: &'static str,
+    pub versions: VersionRange,
+    pub optional: bool,
+}
+
+/// Required scripts that don't run on a Factorio version. `main` stands for the framework
+/// every script runs in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedScripts {
+    pub version: GameVersion,
+    pub scripts: Vec<&'static str>,
+}
+
+impl fmt::Display for UnsupportedScripts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (major, minor, patch) = self.version;
+        write!(
+            f,
+            "rules {} don't support Factorio {major}.{minor}.{patch}",
+            self.scripts.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedScripts {}
+
+#[derive(
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Copy,
+    Clone,
+    VariantArray,
+    Display,
+    EnumString,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum MsgLevel {
+    /// Left out of reports unless the rules ask for it
+    Debug,
+    #[default]
     Info,
     Warn,
     Error,
+    /// Fails the run, even outside the verification window or below the reported level
+    Critical,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ReplayMsg {
     pub time: u64,
     pub level: MsgLevel,
+    /// Script that logged the message; `None` for messages without a rule field.
+    pub rule: Option<String>,
     pub message: String,
 }
 
 pub const REPLAY_SCRIPT_EVENT_PREFIX: &str = "REPLAY_SCRIPT_EVENT:";
 pub const REPLAY_EXIT_SUCCESS_PREFIX: &str = "REPLAY_EXIT_SUCCESS:";
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ExitSignal {
     pub time: u64,
     pub message: String,
@@ -43,6 +322,17 @@ impl FromStr for ExitSignal {
     }
 }
 
+/// Exit message of the `stop_at_tick` script.
+pub const WINDOW_END_MESSAGE: &str = "Reached end of verification window";
+
+impl ExitSignal {
+    /// Whether the replay was stopped at the end of the verification window, rather than
+    /// by a win condition.
+    pub fn is_window_end(&self) -> bool {
+        self.message == WINDOW_END_MESSAGE
+    }
+}
+
 impl fmt::Display for ExitSignal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -58,17 +348,53 @@ impl FromStr for ReplayMsg {
 
     fn from_str(value: &str) -> Result<Self, ()> {
         let parts: Vec<&str> = value.split('\t').collect();
-        if parts.len() != 4 || parts[0] != REPLAY_SCRIPT_EVENT_PREFIX {
-            return Err(());
+        let (rule, message) = match parts.as_slice() {
+            [REPLAY_SCRIPT_EVENT_PREFIX, _, _, message] => (None, message),
+            [REPLAY_SCRIPT_EVENT_PREFIX, _, _, rule, message] => (Some(rule.to_string()), message),
+            _ => return Err(()),
         };
         Ok(ReplayMsg {
             time: parts[1].parse().map_err(|_| ())?,
             level: MsgLevel::try_from(parts[2]).map_err(|_| ())?,
-            message: parts[3].to_string(),
+            rule,
+            message: message.to_string(),
         })
     }
 }
 
+pub const PROGRESS_SNAPSHOT_PREFIX: &str = "PROGRESS_SNAPSHOT";
+
+/// Periodic progress report emitted by the `snapshot_interval` script.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ProgressSnapshot {
+    pub tick: u64,
+    pub science_produced: u64,
+    pub rockets_launched: u64,
+    pub players: u32,
+}
+
+impl ProgressSnapshot {
+    pub fn from_msg(msg: &ReplayMsg) -> Option<Self> {
+        let fields = msg.message.strip_prefix(PROGRESS_SNAPSHOT_PREFIX)?;
+        let mut snapshot = ProgressSnapshot {
+            tick: msg.time,
+            science_produced: 0,
+            rockets_launched: 0,
+            players: 0,
+        };
+        for field in fields.split_whitespace() {
+            let (key, value) = field.split_once('=')?;
+            match key {
+                "science" => snapshot.science_produced = value.parse().ok()?,
+                "rockets" => snapshot.rockets_launched = value.parse().ok()?,
+                "players" => snapshot.players = value.parse().ok()?,
+                _ => {}
+            }
+        }
+        Some(snapshot)
+    }
+}
+
 // is NOT the inverse of from_str
 impl fmt::Display for ReplayMsg {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -95,9 +421,25 @@ mod tests {
         assert!(output.contains("no_open_other_player"));
 
         assert!(!output.contains("win_on_scenario_finished"));
+        assert!(!output.contains("win_condition"));
+        assert!(!output.contains("log_all_commands"));
+        assert!(!output.contains("no_coop_actions"));
         assert!(output.contains("local maxPlayers = 1\n"));
     }
 
+    #[test]
+    fn test_lua_string() {
+        assert_eq!(lua_string("iron-plate"), r#""iron-plate""#);
+        assert_eq!(lua_string("a\\b\"c\nd\r\x001"), r#""a\\b\"c\nd\r\0001""#);
+        assert_eq!(
+            WinCondition::ResearchCompleted {
+                tech: "x\"}\nos.exit()--".to_string()
+            }
+            .to_lua(),
+            r#"{type = "research_completed", name = "x\"}\nos.exit()--"}"#
+        );
+    }
+
     #[test]
     fn test_configure_script() {
         let scripts = ReplayScripts {
@@ -113,6 +455,75 @@ mod tests {
         assert!(output.contains("local maxPlayers = 137"));
     }
 
+    #[test]
+    fn test_max_apm() {
+        let scripts: ReplayScripts = serde_yaml::from_str("max_apm: 400").unwrap();
+        assert_eq!(scripts.max_apm, Some(400));
+        assert!(scripts.to_string().contains("local maxApm = 400\n"));
+
+        assert!(!ReplayScripts::default().to_string().contains("maxApm"));
+    }
+
+    #[test]
+    fn test_game_speed() {
+        let scripts: ReplayScripts = serde_yaml::from_str("game_speed: 1000").unwrap();
+        assert_eq!(scripts.game_speed, Some(1000));
+        assert!(scripts.to_string().contains("local gameSpeed = 1000\n"));
+
+        assert!(!ReplayScripts::default().to_string().contains("gameSpeed"));
+    }
+
+    #[test]
+    fn test_list_params() {
+        let scripts = ReplayScripts {
+            required_research: vec!["steel-axe".to_string(), "automation".to_string()],
+            banned_items: Some(vec!["infinity-chest".to_string(), "say \"hi\"".to_string()]),
+            allowed_surfaces: Some(vec!["nauvis".to_string()]),
+            ..Default::default()
+        };
+
+        let output = scripts.to_string();
+        assert!(output.contains("local requiredResearch = {\"steel-axe\",\"automation\"}"));
+        assert!(output.contains(r#"local bannedItems = {"infinity-chest","say \"hi\""}"#));
+        assert!(output.contains("local allowedSurfaces = {\"nauvis\"}"));
+
+        let output = ReplayScripts::default().to_string();
+        assert!(!output.contains("bannedItems"));
+    }
+
+    #[test]
+    fn test_win_condition() {
+        let parse = |yaml: &str| -> WinCondition { serde_yaml::from_str(yaml).unwrap() };
+        assert_eq!(parse("type: rocket_launched"), WinCondition::RocketLaunched);
+        assert_eq!(
+            parse("{ type: research_completed, tech: steel-axe }"),
+            WinCondition::ResearchCompleted {
+                tech: "steel-axe".to_string()
+            }
+        );
+        assert_eq!(
+            parse("{ type: item_produced, item: iron-plate, count: 1000 }"),
+            WinCondition::ItemProduced {
+                item: "iron-plate".to_string(),
+                count: 1000
+            }
+        );
+
+        let scripts = ReplayScripts {
+            win_condition: Some(parse(
+                "{ type: item_produced, item: iron-plate, count: 1000 }",
+            )),
+            ..Default::default()
+        };
+        assert!(scripts.has_win_condition());
+        let output = scripts.to_string();
+        assert!(output.contains(
+            r#"local winCondition = {type = "item_produced", name = "iron-plate", count = 1000}"#
+        ));
+
+        assert!(!ReplayScripts::default().has_win_condition());
+    }
+
     #[test]
     fn test_all_enabled() {
         let output = ReplayScripts::all_enabled().to_string();
@@ -128,6 +539,53 @@ mod tests {
         assert!(!output.contains(pattern));
     }
 
+    #[test]
+    fn test_builder() {
+        let scripts = ReplayScripts::builder()
+            .max_players(2)
+            .max_apm(None)
+            .required_research(vec!["steel-axe".to_string()])
+            .win_on_scenario_finished(true)
+            .require_win_condition()
+            .build()
+            .unwrap();
+        assert_eq!(scripts.max_players, Some(2));
+        assert_eq!(scripts.required_research, ["steel-axe"]);
+        assert!(scripts.win_on_scenario_finished);
+
+        let build = |builder: ReplayScriptsBuilder| builder.build().unwrap_err();
+        assert_eq!(
+            build(ReplayScripts::builder().max_players(0)),
+            InvalidScripts::Zero("max_players")
+        );
+        assert_eq!(
+            build(
+                ReplayScripts::builder().win_condition(WinCondition::ItemProduced {
+                    item: "iron-plate".to_string(),
+                    count: 0,
+                })
+            ),
+            InvalidScripts::Zero("win_condition.count")
+        );
+        assert_eq!(
+            build(ReplayScripts::builder().allowed_surfaces(vec![])),
+            InvalidScripts::EmptyList("allowed_surfaces")
+        );
+        assert_eq!(
+            build(
+                ReplayScripts::builder()
+                    .win_on_scenario_finished(true)
+                    .win_condition(WinCondition::RocketLaunched)
+            ),
+            InvalidScripts::ConflictingWinConditions
+        );
+        assert_eq!(
+            build(ReplayScripts::builder().require_win_condition()),
+            InvalidScripts::MissingWinCondition
+        );
+        assert!(ReplayScripts::builder().build().is_ok());
+    }
+
     #[test]
     fn test_serde_defaults() {
         let scripts: ReplayScripts = serde_yaml::from_str("{}").unwrap();
@@ -135,9 +593,15 @@ mod tests {
         assert_eq!(scripts.max_players, Some(1));
         assert!(!scripts.bad_console_commands);
         assert!(!scripts.blueprint_import);
+        assert!(!scripts.audit_blueprint_library);
         assert!(!scripts.map_editor);
         assert!(!scripts.open_other_player);
         assert!(!scripts.win_on_scenario_finished);
+        assert!(!scripts.log_all_commands);
+        assert!(scripts.coop_actions);
+        assert_eq!(scripts.allowed_player_names, None);
+        assert_eq!(scripts.banned_items, None);
+        assert_eq!(scripts.allowed_surfaces, None);
 
         // Test partial deserialization preserves defaults for missing fields
         let scripts: ReplayScripts =
@@ -146,6 +610,15 @@ mod tests {
         assert_eq!(scripts.max_players, Some(1)); // Should still use configured default
     }
 
+    #[test]
+    fn test_msg_levels() {
+        assert!(MsgLevel::Debug < MsgLevel::default());
+        assert!(MsgLevel::Error < MsgLevel::Critical);
+        let msg = ReplayMsg::from_str("REPLAY_SCRIPT_EVENT:\t5\tCritical\tmain\tdesync").unwrap();
+        assert_eq!(msg.level, MsgLevel::Critical);
+        assert_eq!(msg.rule.as_deref(), Some("main"));
+    }
+
     #[test]
     fn test_parse_msg() {
         let msg = "REPLAY_SCRIPT_EVENT:\t123\tError\tSome message";
@@ -173,6 +646,65 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_progress_snapshot() {
+        let msg = ReplayMsg::from_str(
+            "REPLAY_SCRIPT_EVENT:\t18000\tInfo\tPROGRESS_SNAPSHOT science=120 rockets=1 players=2",
+        )
+        .unwrap();
+        assert_eq!(
+            ProgressSnapshot::from_msg(&msg),
+            Some(ProgressSnapshot {
+                tick: 18000,
+                science_produced: 120,
+                rockets_launched: 1,
+                players: 2,
+            })
+        );
+
+        let msg = ReplayMsg::from_str("REPLAY_SCRIPT_EVENT:\t123\tInfo\t00:05:00").unwrap();
+        assert_eq!(ProgressSnapshot::from_msg(&msg), None);
+
+        let scripts: ReplayScripts = serde_yaml::from_str("snapshot_interval: 3600").unwrap();
+        assert!(
+            scripts
+                .to_string()
+                .contains("local snapshotInterval = 3600\n")
+        );
+    }
+
+    #[test]
+    fn test_window_end() {
+        let exit = ExitSignal::from_str(
+            "REPLAY_EXIT_SUCCESS:\t216000\tReached end of verification window",
+        )
+        .unwrap();
+        assert!(exit.is_window_end());
+        let exit = ExitSignal::from_str("REPLAY_EXIT_SUCCESS:\t216000\tRocket launched!").unwrap();
+        assert!(!exit.is_window_end());
+    }
+
+    #[test]
+    fn test_parse_msg_with_rule() {
+        let msg = ReplayMsg::from_str(
+            "REPLAY_SCRIPT_EVENT:\t394\tError\tno_map_editor\tplayer used map editor!",
+        )
+        .unwrap();
+        assert_eq!(msg.time, 394);
+        assert_eq!(msg.level, MsgLevel::Error);
+        assert_eq!(msg.rule.as_deref(), Some("no_map_editor"));
+        assert_eq!(msg.message, "player used map editor!");
+
+        let msg = ReplayMsg::from_str("REPLAY_SCRIPT_EVENT:\t123\tError\tSome message").unwrap();
+        assert_eq!(msg.rule, None);
+    }
+
+    #[test]
+    fn test_scripts_log_with_rule_name() {
+        let output = ReplayScripts::default().to_string();
+        assert!(output.contains("local ReplayLog = makeReplayLog(\"no_map_editor\")"));
+    }
+
     #[test]
     fn test_parse_exit_signal() {
         let exit = "REPLAY_EXIT_SUCCESS:\t456\tScenario finished";
@@ -188,4 +720,85 @@ mod tests {
         let invalid_format = "REPLAY_EXIT_SUCCESS:\tinvalid\tMessage";
         assert!(ExitSignal::from_str(invalid_format).is_err());
     }
+
+    #[test]
+    fn test_enabled_scripts() {
+        let scripts = ReplayScripts {
+            blueprint_import: true,
+            max_players: None,
+            log_all_commands: true,
+            ..Default::default()
+        };
+        let enabled = scripts.enabled_scripts();
+        assert!(enabled.contains(&"log_all_commands"));
+        assert!(enabled.contains(&"no_map_editor"));
+        assert!(!enabled.contains(&"no_blueprint_import"));
+        assert!(!enabled.contains(&"max_players"));
+    }
+
+    #[test]
+    fn test_for_version() {
+        let scripts = ReplayScripts {
+            audit_blueprint_library: true,
+            snapshot_interval: Some(600),
+            ..Default::default()
+        };
+        assert_eq!(scripts.for_version((2, 0, 60)), Ok(scripts.clone()));
+        // the framework itself needs 2.0
+        let old = scripts.for_version((1, 1, 110)).unwrap_err();
+        assert_eq!(old.scripts, ["main"]);
+        assert_eq!(old.to_string(), "rules main don't support Factorio 1.1.110");
+
+        // optional scripts are left out, and the rest still run
+        let since_2_0 = VersionRange {
+            min: Some((2, 0, 0)),
+            max: None,
+        };
+        let script_info = [
+            "no_blueprint_import",
+            "audit_blueprint_library",
+            "progress_snapshots",
+        ]
+        .map(|name| ScriptInfo {
+            name,
+            versions: since_2_0,
+            optional: name != "no_blueprint_import",
+        });
+        let any_version = VersionRange {
+            min: None,
+            max: None,
+        };
+        let for_old = |scripts: &ReplayScripts| {
+            scripts.for_version_of((1, 1, 110), any_version, &script_info)
+        };
+        let old = for_old(&scripts).unwrap_err();
+        assert_eq!(old.scripts, ["no_blueprint_import"]);
+
+        let without_required = ReplayScripts {
+            blueprint_import: true,
+            ..scripts.clone()
+        };
+        let for_old = for_old(&without_required).unwrap();
+        assert!(!for_old.audit_blueprint_library);
+        assert_eq!(for_old.snapshot_interval, None);
+        assert!(for_old.log_time);
+        assert_eq!(for_old.max_players, Some(1));
+    }
+
+    #[test]
+    fn test_version_range() {
+        let range = VersionRange {
+            min: Some((1, 1, 0)),
+            max: Some((2, 0, 10)),
+        };
+        assert!(range.contains((1, 1, 0)));
+        assert!(range.contains((2, 0, 10)));
+        assert!(!range.contains((1, 0, 99)));
+        assert!(!range.contains((2, 0, 11)));
+        let unbounded = VersionRange {
+            min: None,
+            max: None,
+        };
+        assert!(unbounded.contains((0, 0, 1)));
+    }
 }
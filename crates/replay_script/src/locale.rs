@@ -0,0 +1,109 @@
+//! Presenting replay findings to reviewers: game time instead of ticks, and descriptions
+//! instead of rule identifiers.
+
+use std::collections::HashMap;
+
+pub const TICKS_PER_SECOND: u64 = 60;
+
+/// Formats a tick count as game time, `h:mm:ss.mmm`.
+pub fn format_ticks(tick: u64) -> String {
+    let millis = tick * 1000 / TICKS_PER_SECOND;
+    let seconds = millis / 1000;
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        millis % 1000
+    )
+}
+
+const ENGLISH: &str = include_str!("../locale/en.cfg");
+
+/// Human-readable descriptions of rules, from a locale table in Factorio's `.cfg` format:
+/// `rule=Description` lines under a `[rules]` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleDescriptions {
+    descriptions: HashMap<String, String>,
+}
+
+impl Default for RuleDescriptions {
+    fn default() -> Self {
+        Self::english()
+    }
+}
+
+impl RuleDescriptions {
+    pub fn english() -> Self {
+        Self {
+            descriptions: parse_locale(ENGLISH).expect("built-in locale is valid"),
+        }
+    }
+
+    /// The English descriptions, replaced by those in `locale`.
+    pub fn with_locale(locale: &str) -> Result<Self, String> {
+        let mut descriptions = Self::english();
+        descriptions.descriptions.extend(parse_locale(locale)?);
+        Ok(descriptions)
+    }
+
+    /// The rule's description, or the rule itself if it has none.
+    pub fn describe<'a>(&'a self, rule: &'a str) -> &'a str {
+        self.descriptions.get(rule).map_or(rule, String::as_str)
+    }
+}
+
+fn parse_locale(locale: &str) -> Result<HashMap<String, String>, String> {
+    let mut descriptions = HashMap::new();
+    let mut in_rules = false;
+    for (number, line) in locale.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            in_rules = section == "rules";
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("line {}: expected `rule=Description`", number + 1));
+        };
+        if in_rules {
+            descriptions.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    Ok(descriptions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_ticks() {
+        assert_eq!(format_ticks(0), "0:00:00.000");
+        assert_eq!(format_ticks(394), "0:00:06.566");
+        assert_eq!(format_ticks(216000), "1:00:00.000");
+        assert_eq!(
+            format_ticks(60 * 60 * 60 * 12 + 60 * 61 + 30),
+            "12:01:01.500"
+        );
+    }
+
+    #[test]
+    fn test_rule_descriptions() {
+        let english = RuleDescriptions::english();
+        assert_eq!(english.describe("no_map_editor"), "No map editor");
+        assert_eq!(english.describe("my_plugin"), "my_plugin");
+
+        let german = RuleDescriptions::with_locale(
+            "; Deutsch\n[rules]\nno_map_editor = Kein Karteneditor\n[other]\nmain=ignored\n",
+        )
+        .unwrap();
+        assert_eq!(german.describe("no_map_editor"), "Kein Karteneditor");
+        assert_eq!(german.describe("main"), "Replay script");
+
+        let err = RuleDescriptions::with_locale("[rules]\nno_map_editor\n").unwrap_err();
+        assert_eq!(err, "line 2: expected `rule=Description`");
+    }
+}
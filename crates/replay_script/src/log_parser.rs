@@ -0,0 +1,163 @@
+//! Incremental parsing of the replay scripts' output, for following a verification as it runs.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{ExitSignal, MsgLevel, ProgressSnapshot, ReplayMsg};
+
+/// Something the replay scripts printed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayEvent {
+    Message(ReplayMsg),
+    /// The replay is done; nothing after it is parsed.
+    Exit(ExitSignal),
+}
+
+type EventCallback = Box<dyn FnMut(&ReplayEvent) + Send>;
+
+/// Parses Factorio's output one line at a time, keeping totals of what it has seen so far.
+/// Lines that aren't from the replay scripts are skipped.
+#[derive(Default)]
+pub struct ReplayLogParser {
+    max_level: Option<MsgLevel>,
+    level_counts: BTreeMap<MsgLevel, usize>,
+    rule_counts: BTreeMap<String, usize>,
+    last_tick: u64,
+    latest_snapshot: Option<ProgressSnapshot>,
+    exit: Option<ExitSignal>,
+    on_event: Option<EventCallback>,
+}
+
+impl ReplayLogParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `on_event` with every event, as it is parsed.
+    pub fn on_event(mut self, on_event: impl FnMut(&ReplayEvent) + Send + 'static) -> Self {
+        self.on_event = Some(Box::new(on_event));
+        self
+    }
+
+    /// Parses one line of output, without its line break.
+    pub fn feed(&mut self, line: &str) -> Option<ReplayEvent> {
+        if self.exit.is_some() {
+            return None;
+        }
+        let event = if let Ok(exit) = ExitSignal::from_str(line) {
+            self.last_tick = self.last_tick.max(exit.time);
+            self.exit = Some(exit.clone());
+            ReplayEvent::Exit(exit)
+        } else if let Ok(msg) = ReplayMsg::from_str(line) {
+            self.last_tick = self.last_tick.max(msg.time);
+            self.max_level = self.max_level.max(Some(msg.level));
+            *self.level_counts.entry(msg.level).or_default() += 1;
+            if let Some(rule) = &msg.rule {
+                *self.rule_counts.entry(rule.clone()).or_default() += 1;
+            }
+            if let Some(snapshot) = ProgressSnapshot::from_msg(&msg) {
+                self.latest_snapshot = Some(snapshot);
+            }
+            ReplayEvent::Message(msg)
+        } else {
+            return None;
+        };
+        if let Some(on_event) = &mut self.on_event {
+            on_event(&event);
+        }
+        Some(event)
+    }
+
+    /// Highest level of any message so far.
+    pub fn max_level(&self) -> Option<MsgLevel> {
+        self.max_level
+    }
+
+    /// Number of messages of `level` so far.
+    pub fn count(&self, level: MsgLevel) -> usize {
+        self.level_counts.get(&level).copied().unwrap_or(0)
+    }
+
+    /// Number of messages so far, by the rule that logged them.
+    pub fn rule_counts(&self) -> &BTreeMap<String, usize> {
+        &self.rule_counts
+    }
+
+    /// Latest tick of any message or of the exit.
+    pub fn last_tick(&self) -> u64 {
+        self.last_tick
+    }
+
+    pub fn latest_snapshot(&self) -> Option<&ProgressSnapshot> {
+        self.latest_snapshot.as_ref()
+    }
+
+    pub fn exit(&self) -> Option<&ExitSignal> {
+        self.exit.as_ref()
+    }
+}
+
+impl fmt::Debug for ReplayLogParser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReplayLogParser")
+            .field("max_level", &self.max_level)
+            .field("level_counts", &self.level_counts)
+            .field("rule_counts", &self.rule_counts)
+            .field("last_tick", &self.last_tick)
+            .field("latest_snapshot", &self.latest_snapshot)
+            .field("exit", &self.exit)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    const OUTPUT: &str = "\
+   0.500 Info AppManager.cpp:123: Loading map
+REPLAY_SCRIPT_EVENT:\t60\tInfo\tlog_time\tTime: 0:00:01
+REPLAY_SCRIPT_EVENT:\t120\tError\tno_map_editor\tplayer used map editor!
+REPLAY_SCRIPT_EVENT:\t180\tInfo\tsnapshot_interval\tPROGRESS_SNAPSHOT science=10 rockets=0 players=1
+REPLAY_SCRIPT_EVENT:\t200\tWarn\tSome message without a rule
+REPLAY_EXIT_SUCCESS:\t240\tRocket launched!
+REPLAY_SCRIPT_EVENT:\t300\tCritical\tno_map_editor\tafter the exit
+";
+
+    #[test]
+    fn test_feed() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut parser = ReplayLogParser::new().on_event({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event.clone())
+        });
+        assert_eq!(parser.max_level(), None);
+
+        let returned: Vec<_> = OUTPUT
+            .lines()
+            .filter_map(|line| parser.feed(line))
+            .collect();
+        assert_eq!(returned, *events.lock().unwrap());
+        assert_eq!(returned.len(), 5);
+        assert!(matches!(&returned[1], ReplayEvent::Message(msg) if msg.time == 120));
+        assert!(matches!(&returned[4], ReplayEvent::Exit(exit) if exit.time == 240));
+
+        assert_eq!(parser.max_level(), Some(MsgLevel::Error));
+        assert_eq!(parser.count(MsgLevel::Info), 2);
+        assert_eq!(parser.count(MsgLevel::Warn), 1);
+        assert_eq!(parser.count(MsgLevel::Critical), 0);
+        assert_eq!(parser.rule_counts().get("no_map_editor"), Some(&1));
+        assert_eq!(parser.rule_counts().get("log_time"), Some(&1));
+        assert_eq!(parser.last_tick(), 240);
+        assert_eq!(
+            parser.latest_snapshot().map(|s| s.science_produced),
+            Some(10)
+        );
+        assert_eq!(
+            parser.exit().map(|exit| exit.message.as_str()),
+            Some("Rocket launched!")
+        );
+    }
+}
@@ -1,13 +1,19 @@
+// min_version: 2.0.0
 import { add_lib, type EventLib } from "event_handler"
 import * as util from "util"
 
 declare global {
   // API
-  var ReplayLog: {
+  type ReplayLogger = {
+    critical(...args: string[]): void
     err(...args: string[]): void
     warn(...args: string[]): void
     info(...args: string[]): void
+    debug(...args: string[]): void
   }
+  // Each rule gets its own logger (a local ReplayLog), which tags messages with the rule name.
+  var ReplayLog: ReplayLogger
+  var makeReplayLog: (rule: string) => ReplayLogger
   var addReplayLib: (lib: ReplayLib) => void
   var afterReplay: (fn: () => void) => void
   var exitReplay: (message: string) => void
@@ -25,33 +31,44 @@ declare global {
   }
 
   // Declares
+  // rule state is kept in `storage`, which Factorio 2.0 renamed from `global`
   const storage: {
     _replay_script_DATA: LuaSet<String>
+    // actions per player in the current one-minute window, for max_apm
+    _replay_script_APM_COUNTS?: LuaMap<number, number>
   }
   var util: typeof import("util")
 }
 _G.util = util
 
-type MsgType = "Error" | "Warn" | "Info"
-function logEvent(type: MsgType, ...args: string[]): void {
+type MsgType = "Critical" | "Error" | "Warn" | "Info" | "Debug"
+function logEvent(rule: string, type: MsgType, ...args: string[]): void {
   print(
     "REPLAY_SCRIPT_EVENT:",
     game.ticks_played,
     type,
+    rule,
     table.concat(args, " "),
   )
 }
-ReplayLog = {
+makeReplayLog = (rule: string): ReplayLogger => ({
+  critical(...args: string[]): void {
+    logEvent(rule, "Critical", ...args)
+  },
   err(...args: string[]): void {
-    logEvent("Error", ...args)
+    logEvent(rule, "Error", ...args)
   },
   warn(...args: string[]): void {
-    logEvent("Warn", ...args)
+    logEvent(rule, "Warn", ...args)
   },
   info(...args: string[]): void {
-    logEvent("Info", ...args)
+    logEvent(rule, "Info", ...args)
   },
-}
+  debug(...args: string[]): void {
+    logEvent(rule, "Debug", ...args)
+  },
+})
+ReplayLog = makeReplayLog("main")
 
 addReplayLib = (lib: ReplayLib) => {
   if (!lib.events) lib.events = {}
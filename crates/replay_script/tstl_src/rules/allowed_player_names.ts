@@ -0,0 +1,14 @@
+// param_type: Option<Vec<String>>
+// enable_value: "Some(vec![\"GlassBricks\".to_string()])"
+const allowedPlayerNames: string[] = PARAM_VALUE as any
+const allowed = new LuaSet<string>()
+for (const name of allowedPlayerNames) allowed.add(name)
+
+addReplayLib({
+  on_player_joined_game(event) {
+    const player = game.get_player(event.player_index)!
+    if (!allowed.has(player.name)) {
+      ReplayLog.err(player.name, "joined but is not an allowed player!")
+    }
+  },
+})
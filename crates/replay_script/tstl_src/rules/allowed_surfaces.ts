@@ -0,0 +1,28 @@
+// param_type: Option<Vec<String>>
+// enable_value: "Some(vec![\"nauvis\".to_string()])"
+// min_version: 2.0.0
+const allowedSurfaces: string[] = PARAM_VALUE as any
+const allowed = new LuaSet<string>()
+for (const name of allowedSurfaces) allowed.add(name)
+
+function checkSurface(player: LuaPlayer | undefined, action: string, surface: LuaSurface) {
+  if (allowed.has(surface.name)) return
+  const playerName = player?.name || "unknown"
+  const key = `allowed-surface:${playerName}:${surface.name}`
+  if (storage._replay_script_DATA.has(key)) return
+  storage._replay_script_DATA.add(key)
+  ReplayLog.err(playerName, action, `surface "${surface.name}", which is not allowed!`)
+}
+
+addReplayLib({
+  on_player_changed_surface(event) {
+    const player = game.get_player(event.player_index)
+    if (player) checkSurface(player, "moved to", player.surface)
+  },
+  on_built_entity(event) {
+    checkSurface(game.get_player(event.player_index), "built on", event.entity.surface)
+  },
+  on_player_mined_entity(event) {
+    checkSurface(game.get_player(event.player_index), "mined on", event.entity.surface)
+  },
+})
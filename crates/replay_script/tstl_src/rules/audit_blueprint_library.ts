@@ -0,0 +1,24 @@
+// default: false
+// min_version: 2.0.0
+// optional: true
+function describeRecord(record: LuaRecord): string {
+  const label = record.label ? `"${record.label}"` : "(unnamed)"
+  if (record.type != "blueprint") return `${record.type} ${label}`
+  const tiles = record.get_blueprint_tiles()?.length ?? 0
+  return `blueprint ${label} (${record.get_blueprint_entity_count()} entities, ${tiles} tiles)`
+}
+
+addReplayLib({
+  on_player_cursor_stack_changed(event) {
+    const player = game.get_player(event.player_index)!
+    const record = player.cursor_record
+    // records in the player's library aren't writable; ones in the game's inventory are
+    if (!record || record.valid_for_write) return
+    ReplayLog.warn(
+      player.name,
+      "used",
+      describeRecord(record),
+      "from the blueprint library",
+    )
+  },
+})
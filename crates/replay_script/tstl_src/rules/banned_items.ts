@@ -0,0 +1,33 @@
+// param_type: Option<Vec<String>>
+// enable_value: "Some(vec![\"infinity-chest\".to_string(), \"infinity-pipe\".to_string()])"
+// min_version: 2.0.0
+const bannedItems: string[] = PARAM_VALUE as any
+const banned = new LuaSet<string>()
+for (const name of bannedItems) banned.add(name)
+
+function reportBanned(playerName: string, action: string, name: string) {
+  const key = `banned-item:${playerName}:${action}:${name}`
+  if (storage._replay_script_DATA.has(key)) return
+  storage._replay_script_DATA.add(key)
+  ReplayLog.err(playerName, action, "banned item", `"${name}"!`)
+}
+
+addReplayLib({
+  on_player_crafted_item(event) {
+    const name = event.item_stack.name
+    if (!banned.has(name)) return
+    const player = game.get_player(event.player_index)
+    reportBanned(player?.name || "unknown", "crafted", name)
+  },
+  on_built_entity(event) {
+    const name = event.entity.name
+    if (!banned.has(name)) return
+    const player = game.get_player(event.player_index)
+    reportBanned(player?.name || "unknown", "placed", name)
+  },
+  on_robot_built_entity(event) {
+    const name = event.entity.name
+    if (!banned.has(name)) return
+    reportBanned("robot", "placed", name)
+  },
+})
@@ -0,0 +1,21 @@
+// param_type: Option<u32>
+// enable_value: "Some(3600)"
+const graceTicks: number = PARAM_VALUE
+
+// Keeps replaying for a while after the win condition (or any other exit), so actions
+// just after it are still checked.
+const exitNow = exitReplay
+let pendingExit: { tick: number; message: string } | undefined
+exitReplay = (message: string) => {
+  if (pendingExit) return
+  pendingExit = { tick: game.ticks_played + graceTicks, message }
+}
+
+addReplayLib({
+  on_tick() {
+    if (pendingExit && game.ticks_played >= pendingExit.tick) {
+      exitNow(pendingExit.message)
+      pendingExit = undefined
+    }
+  },
+})
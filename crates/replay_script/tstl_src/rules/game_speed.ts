@@ -0,0 +1,10 @@
+// param_type: Option<u32>
+// enable_value: "Some(100)"
+const gameSpeed: number = PARAM_VALUE
+
+// Headless Factorio runs as fast as the CPU allows at high game speeds.
+addReplayLib({
+  on_init() {
+    game.speed = gameSpeed
+  },
+})
@@ -0,0 +1,17 @@
+// default: false
+// optional: true
+addReplayLib({
+  on_console_command(event) {
+    const player =
+      (event.player_index != undefined &&
+        game.get_player(event.player_index)?.name) ||
+      "server"
+    ReplayLog.info(
+      "[command]",
+      player,
+      "ran:",
+      `/${event.command}`,
+      event.parameters,
+    )
+  },
+})
@@ -1,4 +1,5 @@
 // default: true
+// optional: true
 addReplayLib({
   on_nth_tick: {
     [60 * 15]: () => {
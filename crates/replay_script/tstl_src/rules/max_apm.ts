@@ -0,0 +1,45 @@
+// param_type: Option<u32>
+// enable_value: "Some(600)"
+// min_version: 2.0.0
+const maxApm: number = PARAM_VALUE
+const WINDOW_TICKS = 60 * 60
+
+// kept in storage, like the other rules' state, so the counts are saved with the game
+function actionCounts(): LuaMap<number, number> {
+  if (!storage._replay_script_APM_COUNTS) {
+    storage._replay_script_APM_COUNTS = new LuaMap()
+  }
+  return storage._replay_script_APM_COUNTS
+}
+
+function countAction(event: { player_index: number }) {
+  const counts = actionCounts()
+  counts.set(event.player_index, (counts.get(event.player_index) ?? 0) + 1)
+}
+
+addReplayLib({
+  on_pre_player_crafted_item: countAction,
+  on_built_entity: countAction,
+  on_pre_player_mined_item: countAction,
+  on_player_rotated_entity: countAction,
+  on_player_flipped_entity: countAction,
+  on_player_fast_transferred: countAction,
+  on_player_dropped_item: countAction,
+  on_player_cursor_stack_changed: countAction,
+  on_gui_click: countAction,
+  on_nth_tick: {
+    [WINDOW_TICKS]: () => {
+      const minute = game.ticks_played / WINDOW_TICKS
+      for (const [playerIndex, count] of actionCounts()) {
+        if (count > maxApm) {
+          const player = game.get_player(playerIndex)
+          ReplayLog.warn(
+            player?.name || "unknown",
+            `made ${count} actions in minute ${minute} (max ${maxApm})`,
+          )
+        }
+      }
+      storage._replay_script_APM_COUNTS = new LuaMap()
+    },
+  },
+})
@@ -1,3 +1,4 @@
+// min_version: 2.0.0
 addReplayLib({
   on_player_cursor_stack_changed(event) {
     if ("import-blueprint" in storage._replay_script_DATA) return
@@ -0,0 +1,32 @@
+// default: true
+// min_version: 2.0.0
+function recordRunner(event: { player_index: number }) {
+  // the runner is the first player to join the save, whoever acts first
+  const data = storage._replay_script_DATA
+  if (data.has("runner")) return
+  data.add("runner")
+  data.add(`runner:${event.player_index}`)
+}
+
+function checkActor(event: { player_index: number }) {
+  const data = storage._replay_script_DATA
+  // player indices follow the order players joined in
+  if (!data.has("runner")) recordRunner({ player_index: 1 })
+  if (data.has(`runner:${event.player_index}`)) return
+  if (data.has(`coop-actor:${event.player_index}`)) return
+  data.add(`coop-actor:${event.player_index}`)
+  const name =
+    game.get_player(event.player_index)?.name ||
+    `player ${event.player_index}`
+  ReplayLog.err(name, "performed actions, but only the runner may play!")
+}
+
+addReplayLib({
+  on_player_created: recordRunner,
+  on_built_entity: checkActor,
+  on_pre_player_mined_item: checkActor,
+  on_pre_player_crafted_item: checkActor,
+  on_player_rotated_entity: checkActor,
+  on_player_fast_transferred: checkActor,
+  on_player_dropped_item: checkActor,
+})
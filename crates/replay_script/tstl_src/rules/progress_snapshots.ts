@@ -0,0 +1,28 @@
+// name: snapshot_interval
+// param_type: Option<u32>
+// enable_value: "Some(18000)"
+// min_version: 2.0.0
+// optional: true
+const snapshotInterval: number = PARAM_VALUE
+
+const sciencePacks = prototypes.get_item_filtered([
+  { filter: "type", type: "tool" },
+])
+
+addReplayLib({
+  on_nth_tick: {
+    [snapshotInterval]: () => {
+      const force = game.forces["player"]
+      let science = 0
+      for (const [, surface] of game.surfaces) {
+        const stats = force.get_item_production_statistics(surface)
+        for (const [name] of sciencePacks) {
+          science += stats.get_input_count(name)
+        }
+      }
+      ReplayLog.info(
+        `PROGRESS_SNAPSHOT science=${science} rockets=${force.rockets_launched} players=${game.connected_players.length}`,
+      )
+    },
+  },
+})
@@ -0,0 +1,12 @@
+// param_type: Option<u32>
+// enable_value: "Some(216000)"
+const stopAtTick: number = PARAM_VALUE
+
+// Ends the replay early, to spot-check only the start of a long run.
+addReplayLib({
+  on_nth_tick: {
+    [stopAtTick]: () => {
+      exitReplay("Reached end of verification window")
+    },
+  },
+})
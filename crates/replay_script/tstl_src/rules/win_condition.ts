@@ -0,0 +1,58 @@
+// param_type: Option<WinCondition>
+// enable_value: "Some(WinCondition::RocketLaunched)"
+// min_version: 2.0.0
+interface WinConditionParam {
+  type:
+    | "rocket_launched"
+    | "scenario_finished"
+    | "research_completed"
+    | "item_produced"
+  name: string
+  count: number
+}
+const winCondition: WinConditionParam = PARAM_VALUE as any
+
+function win(message: string) {
+  if (storage._replay_script_DATA.has("win-condition")) return
+  storage._replay_script_DATA.add("win-condition")
+  exitReplay(message)
+}
+
+if (winCondition.type === "rocket_launched") {
+  addReplayLib({
+    on_rocket_launched() {
+      win("Rocket launched!")
+    },
+  })
+} else if (winCondition.type === "scenario_finished") {
+  addReplayLib({
+    on_pre_scenario_finished() {
+      win("Scenario finished!")
+    },
+  })
+} else if (winCondition.type === "research_completed") {
+  addReplayLib({
+    on_research_finished(event) {
+      if (event.research.name === winCondition.name) {
+        win(`Research "${winCondition.name}" completed!`)
+      }
+    },
+  })
+} else if (winCondition.type === "item_produced") {
+  addReplayLib({
+    on_nth_tick: {
+      [60]: () => {
+        const force = game.forces["player"]
+        let produced = 0
+        for (const [, surface] of game.surfaces) {
+          produced += force
+            .get_item_production_statistics(surface)
+            .get_input_count(winCondition.name)
+        }
+        if (produced >= winCondition.count) {
+          win(`Produced ${produced} "${winCondition.name}"!`)
+        }
+      },
+    },
+  })
+}
@@ -0,0 +1,18 @@
+#!/bin/sh
+# Compiles tstl_src to Lua in vendored/, to commit for builds with the vendored-lua feature.
+# Needs bun. SOURCE_HASH records the sources compiled, as build.rs hashes them.
+set -eu
+cd "$(dirname "$0")"
+
+out="$(pwd)/vendored.tmp"
+rm -rf "$out"
+(cd tstl_src && bun i && bunx tstl --outDir "$out")
+
+git ls-files -z -- package.json bun.lock tsconfig.json tstl_src |
+    LC_ALL=C sort -z |
+    xargs -0 sha256sum |
+    sha256sum |
+    cut -d ' ' -f 1 >"$out/SOURCE_HASH"
+
+rm -rf vendored
+mv "$out" vendored
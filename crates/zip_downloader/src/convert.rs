@@ -0,0 +1,471 @@
+//! Saves submitted as 7z or rar archives, which are repacked into zips with the `7z` command
+//! line tool when [`SecurityConfig::convert_archives`] is on and the `archive-conversion`
+//! feature is enabled. The archive's listing has to pass the same limits as a zip before
+//! anything is extracted.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+#[cfg(feature = "archive-conversion")]
+pub use conversion::convert_to_zip;
+
+/// Extensions of the archives that can be converted, with the dot.
+pub const CONVERTIBLE_EXTENSIONS: [&str; 2] = [".7z", ".rar"];
+
+/// Archive format of a file, from its magic number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    SevenZip,
+    Rar,
+    Unknown,
+}
+
+impl ArchiveFormat {
+    pub fn detect(file: &mut File) -> std::io::Result<Self> {
+        let mut magic = Vec::with_capacity(8);
+        file.seek(SeekFrom::Start(0))?;
+        file.by_ref().take(8).read_to_end(&mut magic)?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(Self::from_magic(&magic))
+    }
+
+    fn from_magic(magic: &[u8]) -> Self {
+        if magic.starts_with(b"PK") {
+            ArchiveFormat::Zip
+        } else if magic.starts_with(&[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C]) {
+            ArchiveFormat::SevenZip
+        } else if magic.starts_with(b"Rar!\x1A\x07") {
+            ArchiveFormat::Rar
+        } else {
+            ArchiveFormat::Unknown
+        }
+    }
+
+    /// Whether the format can be converted into a zip.
+    pub fn is_convertible(self) -> bool {
+        matches!(self, ArchiveFormat::SevenZip | ArchiveFormat::Rar)
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::SevenZip => "7z",
+            ArchiveFormat::Rar => "rar",
+            ArchiveFormat::Unknown => "unknown",
+        })
+    }
+}
+
+#[cfg(feature = "archive-conversion")]
+mod conversion {
+    use anyhow::{Context, anyhow, bail, ensure};
+    use std::collections::HashSet;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::{Component, Path, PathBuf};
+    use std::process::Stdio;
+    use tokio::process::Command;
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    use crate::DownloadError;
+    use crate::disk_space::ensure_free_space;
+    use crate::security::SecurityConfig;
+
+    /// The 7-Zip command line tool, which reads both 7z and rar archives.
+    const SEVEN_ZIP: &str = "7z";
+
+    /// A file or folder in the listing of `7z l -slt`.
+    #[derive(Debug, PartialEq)]
+    pub(super) struct ListedEntry {
+        pub path: String,
+        pub size: u64,
+        pub is_dir: bool,
+        pub is_link: bool,
+    }
+
+    /// Replaces the archive at `path` with a zip of the same files, named like it with a
+    /// `.zip` extension, and returns the zip's path.
+    pub async fn convert_to_zip(
+        path: &Path,
+        config: &SecurityConfig,
+    ) -> Result<PathBuf, DownloadError> {
+        let listing = run_7z(&["l", "-slt"], path).await?;
+        let entries = parse_listing(&listing);
+        let extracted_size =
+            validate_entries(&entries, config).map_err(DownloadError::SecurityViolation)?;
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        // the extracted files and the zip of them, at most as large
+        ensure_free_space::<DownloadError>(
+            dir,
+            extracted_size
+                .saturating_mul(2)
+                .saturating_add(config.min_free_space),
+        )?;
+        let extract_dir = tempfile::tempdir_in(dir)?;
+        let out_arg = format!("-o{}", extract_dir.path().display());
+        run_7z(&["x", "-y", "-bd", &out_arg], path).await?;
+
+        let zip_path = path.with_extension("zip");
+        let temp_zip = tempfile::NamedTempFile::new_in(dir)?;
+        let source = extract_dir.path().to_path_buf();
+        let max_size = config.max_extracted_size;
+        let temp_zip = tokio::task::spawn_blocking(move || {
+            zip_dir(&source, temp_zip.as_file(), max_size).map(|()| temp_zip)
+        })
+        .await
+        .map_err(|e| DownloadError::IoError(std::io::Error::other(e)))?
+        .map_err(DownloadError::SecurityViolation)?;
+        temp_zip
+            .persist(&zip_path)
+            .map_err(|e| DownloadError::IoError(e.error))?;
+        if zip_path != path {
+            std::fs::remove_file(path)?;
+        }
+        Ok(zip_path)
+    }
+
+    async fn run_7z(args: &[&str], archive: &Path) -> Result<String, DownloadError> {
+        let output = Command::new(SEVEN_ZIP)
+            .args(args)
+            .arg(archive)
+            // a password prompt reads end of file and fails instead of waiting
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| {
+                DownloadError::IoError(std::io::Error::new(
+                    e.kind(),
+                    format!("Failed to run {SEVEN_ZIP}: {e}"),
+                ))
+            })?;
+        if !output.status.success() {
+            return Err(DownloadError::UnsupportedArchive(anyhow!(
+                "{SEVEN_ZIP} could not read the archive: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Entries of the archive, after the `----------` that ends the archive's own properties.
+    pub(super) fn parse_listing(output: &str) -> Vec<ListedEntry> {
+        let Some((_, entries)) = output.split_once("\n----------\n") else {
+            return Vec::new();
+        };
+        entries
+            .split("\n\n")
+            .filter_map(|block| {
+                let mut entry = ListedEntry {
+                    path: String::new(),
+                    size: 0,
+                    is_dir: false,
+                    is_link: false,
+                };
+                for line in block.lines() {
+                    let Some((key, value)) = line.split_once(" = ") else {
+                        continue;
+                    };
+                    match key {
+                        "Path" => entry.path = value.to_string(),
+                        "Size" => entry.size = value.parse().unwrap_or(0),
+                        "Folder" => entry.is_dir |= value == "+",
+                        "Attributes" => {
+                            entry.is_dir |= value.starts_with('D');
+                            // the unix mode, if any, e.g. `A_ lrwxrwxrwx`
+                            entry.is_link |= value
+                                .split_whitespace()
+                                .any(|part| part.len() == 10 && part.starts_with('l'));
+                        }
+                        "Symbolic Link" | "Hard Link" | "Link" => {
+                            entry.is_link |= !value.is_empty()
+                        }
+                        _ => {}
+                    }
+                }
+                (!entry.path.is_empty()).then_some(entry)
+            })
+            .collect()
+    }
+
+    /// Checks the listing before anything is extracted, and returns the total extracted size.
+    /// Links are rejected, as are entries under a path that isn't a folder, which could be
+    /// a link extracted first.
+    pub(super) fn validate_entries(
+        entries: &[ListedEntry],
+        config: &SecurityConfig,
+    ) -> anyhow::Result<u64> {
+        ensure!(
+            entries.len() <= config.max_zip_entries,
+            "Archive has {} entries, maximum is {}",
+            entries.len(),
+            config.max_zip_entries
+        );
+        for entry in entries {
+            ensure!(
+                Path::new(&entry.path)
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_))),
+                "Unsafe path in archive entry: {}",
+                entry.path
+            );
+            ensure!(!entry.is_link, "Archive contains a link: {}", entry.path);
+        }
+        let files: HashSet<&Path> = entries
+            .iter()
+            .filter(|entry| !entry.is_dir)
+            .map(|entry| Path::new(&entry.path))
+            .collect();
+        for entry in entries {
+            if let Some(file) = Path::new(&entry.path)
+                .ancestors()
+                .skip(1)
+                .find(|ancestor| files.contains(ancestor))
+            {
+                bail!(
+                    "Archive entry {} is under {}, which is not a folder",
+                    entry.path,
+                    file.display()
+                );
+            }
+        }
+        let total = entries
+            .iter()
+            .try_fold(0u64, |total, entry| total.checked_add(entry.size))
+            .context("Total uncompressed size overflows")?;
+        ensure!(
+            total <= config.max_extracted_size,
+            "Total uncompressed size {} exceeds maximum {}",
+            total,
+            config.max_extracted_size
+        );
+        Ok(total)
+    }
+
+    /// Zips the files under `dir`, with paths relative to it. Fails on links, which an
+    /// archive could use to point outside of it, and past `max_size` bytes in total.
+    pub(super) fn zip_dir(dir: &Path, out: &File, max_size: u64) -> anyhow::Result<()> {
+        let mut zip = ZipWriter::new(out);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        let mut total = 0;
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(current) = pending.pop() {
+            let mut children = std::fs::read_dir(&current)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            children.sort();
+            for path in children {
+                let relative = path.strip_prefix(dir)?.to_string_lossy().replace('\\', "/");
+                let file_type = std::fs::symlink_metadata(&path)?.file_type();
+                if file_type.is_symlink() {
+                    bail!("Archive contains a link: {relative}");
+                } else if file_type.is_dir() {
+                    zip.add_directory(relative, options)?;
+                    pending.push(path);
+                } else {
+                    let mut file = File::open(&path)
+                        .with_context(|| format!("Failed to read extracted {relative}"))?;
+                    total += file.metadata()?.len();
+                    ensure!(
+                        total <= max_size,
+                        "Total uncompressed size exceeds maximum {max_size}"
+                    );
+                    zip.start_file(relative, options)?;
+                    std::io::copy(&mut file, &mut zip)?;
+                }
+            }
+        }
+        zip.finish()?.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            ArchiveFormat::from_magic(b"PK\x03\x04rest"),
+            ArchiveFormat::Zip
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic(&[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C, 0, 4]),
+            ArchiveFormat::SevenZip
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic(b"Rar!\x1A\x07\x01\x00"),
+            ArchiveFormat::Rar
+        );
+        assert_eq!(ArchiveFormat::from_magic(b"Rar!"), ArchiveFormat::Unknown);
+        assert_eq!(ArchiveFormat::from_magic(b""), ArchiveFormat::Unknown);
+
+        let mut file = tempfile::tempfile().unwrap();
+        std::io::Write::write_all(&mut file, b"Rar!\x1A\x07\x00more").unwrap();
+        assert_eq!(
+            ArchiveFormat::detect(&mut file).unwrap(),
+            ArchiveFormat::Rar
+        );
+        assert_eq!(file.stream_position().unwrap(), 0);
+    }
+
+    #[cfg(feature = "archive-conversion")]
+    mod conversion {
+        use super::super::conversion::*;
+        use crate::security::{SecurityConfig, validate_factorio_save};
+
+        const LISTING: &str = "\
+7-Zip [64] 16.02 : Copyright (c) 1999-2016 Igor Pavlov : 2016-05-21
+
+Listing archive: save.7z
+
+--
+Path = save.7z
+Type = 7z
+Physical Size = 1234
+
+----------
+Path = save
+Size = 0
+Packed Size = 0
+Attributes = D_ drwxr-xr-x
+
+Path = save/level.dat0
+Size = 1000
+Packed Size = 400
+Attributes = A_ -rw-r--r--
+
+Path = save/control.lua
+Size = 20
+Packed Size = 10
+Folder = -
+";
+
+        #[test]
+        fn test_parse_listing() {
+            let entries = parse_listing(LISTING);
+            assert_eq!(
+                entries,
+                [
+                    ListedEntry {
+                        path: "save".to_string(),
+                        size: 0,
+                        is_dir: true,
+                        is_link: false,
+                    },
+                    ListedEntry {
+                        path: "save/level.dat0".to_string(),
+                        size: 1000,
+                        is_dir: false,
+                        is_link: false,
+                    },
+                    ListedEntry {
+                        path: "save/control.lua".to_string(),
+                        size: 20,
+                        is_dir: false,
+                        is_link: false,
+                    },
+                ]
+            );
+            assert_eq!(
+                validate_entries(&entries, &SecurityConfig::default()).unwrap(),
+                1020
+            );
+
+            let small = SecurityConfig {
+                max_extracted_size: 500,
+                ..Default::default()
+            };
+            assert!(validate_entries(&entries, &small).is_err());
+        }
+
+        #[test]
+        fn test_validate_entries_rejects_unsafe_paths() {
+            for path in ["../evil", "/etc/passwd", "save/../../evil"] {
+                let entries = [ListedEntry {
+                    path: path.to_string(),
+                    size: 1,
+                    is_dir: false,
+                    is_link: false,
+                }];
+                assert!(
+                    validate_entries(&entries, &SecurityConfig::default()).is_err(),
+                    "{path}"
+                );
+            }
+        }
+
+        fn file(path: &str, size: u64) -> ListedEntry {
+            ListedEntry {
+                path: path.to_string(),
+                size,
+                is_dir: false,
+                is_link: false,
+            }
+        }
+
+        #[test]
+        fn test_validate_entries_rejects_links() {
+            let listing = "----------\nPath = save\nSize = 11\nAttributes = A_ lrwxrwxrwx\n\n\
+                           Path = save/level.dat0\nSize = 1\nAttributes = A_ -rw-r--r--\n";
+            let entries = parse_listing(&format!("\n{listing}"));
+            assert!(entries[0].is_link);
+            let err = validate_entries(&entries, &SecurityConfig::default()).unwrap_err();
+            assert!(err.to_string().contains("link"));
+
+            let entries = parse_listing("\n----------\nPath = save\nSymbolic Link = /etc\n");
+            assert!(entries[0].is_link);
+
+            // a file extracted before the entries under it
+            let entries = [file("save", 1), file("save/level.dat0", 1)];
+            let err = validate_entries(&entries, &SecurityConfig::default()).unwrap_err();
+            assert!(err.to_string().contains("not a folder"));
+        }
+
+        #[test]
+        fn test_validate_entries_rejects_overflowing_sizes() {
+            let entries = [file("a", u64::MAX), file("b", 1)];
+            let config = SecurityConfig {
+                max_extracted_size: u64::MAX,
+                ..Default::default()
+            };
+            let err = validate_entries(&entries, &config).unwrap_err();
+            assert!(err.to_string().contains("overflows"));
+        }
+
+        #[test]
+        fn test_zip_dir() {
+            let dir = tempfile::tempdir().unwrap();
+            let save = dir.path().join("save");
+            std::fs::create_dir(&save).unwrap();
+            for name in ["level.dat0", "level-init.dat", "control.lua"] {
+                std::fs::write(save.join(name), name).unwrap();
+            }
+
+            let mut out = tempfile::tempfile().unwrap();
+            zip_dir(dir.path(), &out, 1000).unwrap();
+            validate_factorio_save(&mut out).unwrap();
+
+            let out = tempfile::tempfile().unwrap();
+            assert!(zip_dir(dir.path(), &out, 10).is_err());
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn test_zip_dir_rejects_links() {
+            let dir = tempfile::tempdir().unwrap();
+            std::os::unix::fs::symlink("/etc/passwd", dir.path().join("passwd")).unwrap();
+            let out = tempfile::tempfile().unwrap();
+            let err = zip_dir(dir.path(), &out, 1000).unwrap_err();
+            assert!(err.to_string().contains("link"));
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error(
+    "Not enough disk space in {}: {available} bytes free, {required} needed",
+    path.display()
+)]
+pub struct InsufficientDiskSpace {
+    pub path: PathBuf,
+    pub available: u64,
+    pub required: u64,
+}
+
+/// Fails with [`InsufficientDiskSpace`] if the filesystem containing `path` has less than
+/// `required` bytes free.
+pub fn ensure_free_space<E>(path: &Path, required: u64) -> Result<(), E>
+where
+    E: From<std::io::Error> + From<InsufficientDiskSpace>,
+{
+    let available = fs4::available_space(path)?;
+    if available < required {
+        return Err(InsufficientDiskSpace {
+            path: path.to_path_buf(),
+            available,
+            required,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DownloadError;
+
+    #[test]
+    fn test_ensure_free_space() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(ensure_free_space::<DownloadError>(dir.path(), 1).is_ok());
+        assert!(matches!(
+            ensure_free_space(dir.path(), u64::MAX),
+            Err(DownloadError::InsufficientDiskSpace(_))
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+
+use crate::DownloadError;
+
+/// How a failed download should be handled by callers that retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Retrying will not help.
+    Final,
+    /// Transient failure; retry with backoff.
+    Retryable,
+    /// The service asked us to slow down, possibly saying for how long.
+    RateLimited { retry_after: Option<Duration> },
+    /// The file exists but is private or needs credentials we don't have.
+    AuthRequired,
+}
+
+/// Whether a service can be given credentials to download files that aren't public.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceAccess {
+    /// Only public links can be downloaded; 401 and 403 mean the file is not accessible.
+    Public,
+    /// Configured credentials can grant access; 401 and 403 mean they are missing or lack
+    /// access to the file.
+    Authenticated,
+}
+
+impl DownloadError {
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Self::NoLinkFound => ErrorClass::Final,
+            Self::UnsupportedLink(_) => ErrorClass::Final,
+            Self::FileNotAccessible(_) => ErrorClass::Final,
+            Self::SecurityViolation(_) => ErrorClass::Final,
+            Self::NotAFactorioSave(_) => ErrorClass::Final,
+            Self::UnsupportedArchive(_) => ErrorClass::Final,
+            Self::AuthRequired(_) => ErrorClass::AuthRequired,
+            Self::ServiceError(_) => ErrorClass::Retryable,
+            &Self::RateLimited { retry_after, .. } => ErrorClass::RateLimited { retry_after },
+            Self::IoError(_) => ErrorClass::Retryable,
+            Self::Cancelled => ErrorClass::Retryable,
+            Self::InsufficientDiskSpace(_) => ErrorClass::Retryable,
+        }
+    }
+
+    /// Classifies an unsuccessful HTTP response from `service`.
+    pub fn from_response(
+        response: &reqwest::Response,
+        service: &str,
+        access: ServiceAccess,
+    ) -> Self {
+        Self::from_status(response.status(), response.headers(), service, access)
+    }
+
+    pub fn from_status(
+        status: StatusCode,
+        headers: &HeaderMap,
+        service: &str,
+        access: ServiceAccess,
+    ) -> Self {
+        let error = anyhow::anyhow!("HTTP {} from {}", status, service);
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => match access {
+                ServiceAccess::Public => Self::FileNotAccessible(error),
+                ServiceAccess::Authenticated => Self::AuthRequired(error),
+            },
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => Self::RateLimited {
+                retry_after: parse_retry_after(headers, Utc::now()),
+                message: format!("{} is rate limiting downloads", service),
+                source: error,
+            },
+            StatusCode::REQUEST_TIMEOUT => Self::ServiceError(error),
+            status if status.is_server_error() => Self::ServiceError(error),
+            _ => Self::FileNotAccessible(error),
+        }
+    }
+}
+
+/// Parses a `Retry-After` header, given either as delay seconds or an HTTP date.
+pub fn parse_retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn retry_after(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = "2025-01-01T12:00:00Z".parse().unwrap();
+
+        assert_eq!(
+            parse_retry_after(&retry_after("120"), now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after(&retry_after("Wed, 01 Jan 2025 12:05:00 GMT"), now),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(
+            parse_retry_after(&retry_after("Wed, 01 Jan 2025 11:00:00 GMT"), now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after(&retry_after("soon"), now), None);
+        assert_eq!(parse_retry_after(&HeaderMap::new(), now), None);
+    }
+
+    #[test]
+    fn test_from_status() {
+        let empty = HeaderMap::new();
+        let class = |status: u16, headers: &HeaderMap| {
+            DownloadError::from_status(
+                StatusCode::from_u16(status).unwrap(),
+                headers,
+                "Test",
+                ServiceAccess::Authenticated,
+            )
+            .class()
+        };
+
+        assert_eq!(class(401, &empty), ErrorClass::AuthRequired);
+        assert_eq!(class(403, &empty), ErrorClass::AuthRequired);
+        let public = |status: u16| {
+            DownloadError::from_status(
+                StatusCode::from_u16(status).unwrap(),
+                &empty,
+                "Test",
+                ServiceAccess::Public,
+            )
+            .class()
+        };
+        assert_eq!(public(401), ErrorClass::Final);
+        assert_eq!(public(403), ErrorClass::Final);
+        assert_eq!(class(404, &empty), ErrorClass::Final);
+        assert_eq!(class(410, &empty), ErrorClass::Final);
+        assert_eq!(class(500, &empty), ErrorClass::Retryable);
+        assert_eq!(class(408, &empty), ErrorClass::Retryable);
+        assert_eq!(
+            class(429, &retry_after("30")),
+            ErrorClass::RateLimited {
+                retry_after: Some(Duration::from_secs(30))
+            }
+        );
+        assert_eq!(
+            class(503, &empty),
+            ErrorClass::RateLimited { retry_after: None }
+        );
+    }
+}
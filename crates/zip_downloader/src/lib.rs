@@ -1,22 +1,64 @@
+pub mod convert;
+pub mod disk_space;
+pub mod error_class;
+pub mod naming;
 pub mod security;
 pub mod services;
+pub mod sink;
+pub mod throttle;
 
 use std::{
     fs::File,
     path::{Path, PathBuf},
+    sync::LazyLock,
 };
 
-pub use security::SecurityConfig;
+use convert::ArchiveFormat;
+use disk_space::{InsufficientDiskSpace, ensure_free_space};
+pub use error_class::{ErrorClass, ServiceAccess};
+pub use naming::FileNameTemplate;
+pub use security::{SecurityConfig, SecurityOverrides};
 use services::{FileDownloadHandle, FileServiceDyn};
 pub use services::{FileMeta, FileService};
+pub use sink::DownloadSummary;
 
 use anyhow::Result;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use regex::Regex;
 use tempfile::NamedTempFile;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
 
 pub struct DownloadedFile {
+    /// Sanitized file name, rendered from the downloader's [`FileNameTemplate`].
     pub name: String,
+    /// File name as reported by the service.
+    pub original_name: String,
     pub path: PathBuf,
+    pub size: u64,
+    /// Hex-encoded SHA-256 of the file contents, computed while downloading.
+    pub sha256: String,
+    /// The link the file was downloaded from, when the input had several.
+    pub link: String,
+}
+
+/// Anything that looks like a URL, stopping at brackets and quotes around it.
+static LINK_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"[a-zA-Z][a-zA-Z0-9+.-]*://[^\s<>"'()\[\]{}]+"#).unwrap());
+
+/// A download link found in the input, in order of appearance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedLink {
+    pub service: String,
+    pub link: String,
+    service_index: usize,
+    fragment: String,
+}
+
+impl std::fmt::Display for DetectedLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.link)
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -24,15 +66,27 @@ pub enum DownloadError {
     #[error("No valid download link found in input")]
     NoLinkFound,
 
+    #[error("No service can download {0}")]
+    UnsupportedLink(String),
+
     #[error("File not accessible: {0}")]
     FileNotAccessible(#[source] anyhow::Error),
 
+    #[error("Authentication required: {0}")]
+    AuthRequired(#[source] anyhow::Error),
+
     #[error("Service error: {0}")]
     ServiceError(#[source] anyhow::Error),
 
     #[error("Security violation: {0}")]
     SecurityViolation(#[source] anyhow::Error),
 
+    #[error("Not a Factorio save: {0}")]
+    NotAFactorioSave(#[source] anyhow::Error),
+
+    #[error("Unsupported archive: {0}")]
+    UnsupportedArchive(#[source] anyhow::Error),
+
     #[error("Rate limited: {message}")]
     RateLimited {
         retry_after: Option<std::time::Duration>,
@@ -43,14 +97,23 @@ pub enum DownloadError {
 
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+
+    #[error("Download cancelled")]
+    Cancelled,
+
+    #[error(transparent)]
+    InsufficientDiskSpace(#[from] InsufficientDiskSpace),
 }
 
 impl DownloadError {
     fn with_context(self, context: &str) -> Self {
         match self {
             Self::FileNotAccessible(e) => Self::FileNotAccessible(e.context(context.to_string())),
+            Self::AuthRequired(e) => Self::AuthRequired(e.context(context.to_string())),
             Self::ServiceError(e) => Self::ServiceError(e.context(context.to_string())),
             Self::SecurityViolation(e) => Self::SecurityViolation(e.context(context.to_string())),
+            Self::NotAFactorioSave(e) => Self::NotAFactorioSave(e.context(context.to_string())),
+            Self::UnsupportedArchive(e) => Self::UnsupportedArchive(e.context(context.to_string())),
             Self::RateLimited {
                 retry_after,
                 message,
@@ -70,11 +133,13 @@ type DynFileService = Box<dyn FileServiceDyn>;
 pub struct FileDownloaderBuilder {
     pub services: Vec<DynFileService>,
     pub security_config: SecurityConfig,
+    pub file_name_template: FileNameTemplate,
 }
 
 pub struct FileDownloader {
     services: Vec<DynFileService>,
     security_config: SecurityConfig,
+    file_name_template: FileNameTemplate,
 }
 
 impl FileDownloaderBuilder {
@@ -82,6 +147,7 @@ impl FileDownloaderBuilder {
         Self {
             services: Vec::new(),
             security_config: SecurityConfig::default(),
+            file_name_template: FileNameTemplate::default(),
         }
     }
 
@@ -90,6 +156,11 @@ impl FileDownloaderBuilder {
         self
     }
 
+    pub fn with_file_name_template(mut self, template: FileNameTemplate) -> Self {
+        self.file_name_template = template;
+        self
+    }
+
     pub fn add_service(mut self, service: impl FileService + 'static) -> Self {
         self.services.push(Box::new(service));
         self
@@ -100,6 +171,7 @@ impl FileDownloaderBuilder {
         FileDownloader {
             services: self.services,
             security_config: self.security_config,
+            file_name_template: self.file_name_template,
         }
     }
 }
@@ -117,16 +189,25 @@ impl FileDownloader {
         self.security_config = config;
     }
 
+    pub fn set_file_name_template(&mut self, template: FileNameTemplate) {
+        self.file_name_template = template;
+    }
+
     pub fn service_count(&self) -> usize {
         self.services.len()
     }
 
+    /// Downloads the first working link in `input`.
+    ///
+    /// Each attempt is limited to [`SecurityConfig::download_timeout`]; cancelling `cancel`
+    /// aborts the attempt in progress and removes any partially written file.
     pub async fn download_zip(
         &mut self,
         input: &str,
         out_file_or_path: &Path,
+        cancel: &CancellationToken,
     ) -> Result<DownloadedFile, DownloadError> {
-        let result = self.do_download_zip(input, out_file_or_path).await;
+        let result = self.do_download_zip(input, out_file_or_path, cancel).await;
         match &result {
             Ok(zip) => debug!(
                 "Successfully downloaded {} to {}",
@@ -138,12 +219,55 @@ impl FileDownloader {
         result
     }
 
+    /// Downloads every link in `input` into the directory `out_dir`, in order, for runs
+    /// submitted as several saves. Fails if any link fails.
+    pub async fn download_all_zips(
+        &mut self,
+        input: &str,
+        out_dir: &Path,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<DownloadedFile>, DownloadError> {
+        let links = self.detect_all_links(input);
+        if links.is_empty() {
+            return Err(DownloadError::NoLinkFound);
+        }
+        let mut downloaded = Vec::new();
+        for (i, link) in links.iter().enumerate() {
+            info!("Segment {}/{}: {link}", i + 1, links.len());
+            let result = match Self::get_download_handle(&mut self.services, link) {
+                Some(mut download_handle) => {
+                    Self::download_with_handle(
+                        &mut *download_handle,
+                        out_dir,
+                        &self.security_config,
+                        &self.file_name_template,
+                        cancel,
+                    )
+                    .await
+                }
+                None => Err(DownloadError::UnsupportedLink(link.link.clone())),
+            };
+            match result {
+                Ok(file) => downloaded.push(file),
+                Err(err) => {
+                    error!("Failed to download segment {}: {}", i + 1, err);
+                    for file in &downloaded {
+                        let _ = std::fs::remove_file(&file.path);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(downloaded)
+    }
+
     pub async fn download_zip_to_temp(
         &mut self,
         input: &str,
+        cancel: &CancellationToken,
     ) -> Result<(NamedTempFile, DownloadedFile), DownloadError> {
         let temp_file = NamedTempFile::new()?;
-        let downloaded_file = self.download_zip(input, temp_file.path()).await?;
+        let downloaded_file = self.download_zip(input, temp_file.path(), cancel).await?;
         Ok((temp_file, downloaded_file))
     }
 
@@ -151,25 +275,85 @@ impl FileDownloader {
         &mut self,
         input: &str,
         out_file: &Path,
+        cancel: &CancellationToken,
     ) -> Result<DownloadedFile, DownloadError> {
         debug!("Starting download");
 
-        let mut download_handle = Self::get_download_handle(&mut self.services, input)?;
-        info!("Link: {download_handle}");
+        let links = self.detect_all_links(input);
+        let mut first_error = None;
+        for (i, link) in links.iter().enumerate() {
+            info!("Link {}/{}: {link}", i + 1, links.len());
+            let Some(mut download_handle) = Self::get_download_handle(&mut self.services, link)
+            else {
+                continue;
+            };
+            match Self::download_with_handle(
+                &mut *download_handle,
+                out_file,
+                &self.security_config,
+                &self.file_name_template,
+                cancel,
+            )
+            .await
+            {
+                Ok(downloaded) => return Ok(downloaded),
+                Err(err @ DownloadError::Cancelled) => return Err(err),
+                Err(err) => {
+                    warn!("Download from {link} failed: {err}");
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
 
-        Self::download_with_handle(&mut *download_handle, out_file, &self.security_config).await
+        Err(first_error.unwrap_or(DownloadError::NoLinkFound))
+    }
+
+    /// Finds every supported link in `input`, in order of appearance, without duplicates.
+    /// Links may be wrapped in brackets or Markdown, or followed by punctuation.
+    pub fn detect_all_links(&mut self, input: &str) -> Vec<DetectedLink> {
+        debug!("Input is: {}", input);
+        let mut links: Vec<DetectedLink> = Vec::new();
+        for found in LINK_REGEX.find_iter(input) {
+            // sentence punctuation after a link isn't part of it
+            let fragment = found
+                .as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?', '*']);
+            for (service_index, service) in self.services.iter_mut().enumerate() {
+                let Some(handle) = service.detect_link(fragment) else {
+                    continue;
+                };
+                let link = handle.to_string();
+                if links.iter().all(|existing| existing.link != link) {
+                    links.push(DetectedLink {
+                        service: handle.service_name().to_string(),
+                        link,
+                        service_index,
+                        fragment: fragment.to_string(),
+                    });
+                }
+            }
+        }
+        links
     }
 
     async fn download_with_handle(
         download_handle: &mut dyn FileDownloadHandle,
         out_file: &Path,
         security_config: &SecurityConfig,
+        file_name_template: &FileNameTemplate,
+        cancel: &CancellationToken,
     ) -> Result<DownloadedFile, DownloadError> {
+        let deadline = Instant::now() + security_config.download_timeout;
+
         debug!("Getting file info");
-        let file_info = download_handle
-            .get_file_info(security_config)
-            .await
-            .map_err(|e| e.with_context(&download_handle.to_string()))?;
+        let file_info = guarded(
+            download_handle.get_file_info(security_config),
+            deadline,
+            security_config,
+            cancel,
+        )
+        .await
+        .map_err(|e| e.with_context(&download_handle.to_string()))?;
 
         debug!("File info: {file_info:?}");
         debug!("Running initial checks");
@@ -177,46 +361,174 @@ impl FileDownloader {
             DownloadError::SecurityViolation(e.context(download_handle.to_string()))
         })?;
 
+        let dest_dir = if out_file.is_dir() {
+            out_file
+        } else {
+            out_file.parent().unwrap_or(Path::new("."))
+        };
+        // services that can't tell the size up front may send up to the maximum
+        let expected_size = match file_info.size {
+            0 => security_config.max_file_size,
+            size => size,
+        };
+        ensure_free_space::<DownloadError>(
+            dest_dir,
+            expected_size.saturating_add(security_config.min_free_space),
+        )?;
+
         debug!("Downloading file");
 
+        let mut name = file_name_template.render(&file_info.name);
         let file_path = if out_file.is_dir() {
-            out_file.join(file_info.name.as_str())
+            let path = naming::unique_path(out_file, &name);
+            name = path.file_name().unwrap().to_string_lossy().into_owned();
+            path
         } else {
             out_file.to_path_buf()
         };
 
-        download_handle
-            .download(&file_path, security_config)
-            .await
-            .map_err(|e| e.with_context(&download_handle.to_string()))?;
+        let download = download_handle.download(&file_path, security_config);
+        let summary = match guarded(download, deadline, security_config, cancel).await {
+            Ok(summary) => summary,
+            Err(e) => {
+                if out_file.is_dir() {
+                    let _ = std::fs::remove_file(&file_path);
+                }
+                return Err(e.with_context(&download_handle.to_string()));
+            }
+        };
+        debug!(
+            "Downloaded {} bytes, sha256 {}",
+            summary.size,
+            summary.sha256_hex()
+        );
 
         debug!("Running file checks");
-        let mut reopened_file = File::open(&file_path).map_err(|e| {
-            DownloadError::IoError(std::io::Error::new(
-                e.kind(),
-                format!("{}: {}", download_handle, e),
-            ))
-        })?;
-        security::validate_downloaded_file(&mut reopened_file, &file_info, security_config)
-            .map_err(|e| {
-                DownloadError::SecurityViolation(e.context(download_handle.to_string()))
-            })?;
+        let mut zip_path = None;
+        let checked = async {
+            let reopen = |path: &Path| {
+                File::open(path).map_err(|e| {
+                    DownloadError::IoError(std::io::Error::new(
+                        e.kind(),
+                        format!("{}: {}", download_handle, e),
+                    ))
+                })
+            };
+            let mut reopened_file = reopen(&file_path)?;
+            let format = ArchiveFormat::detect(&mut reopened_file)?;
+            if format.is_convertible() {
+                security::validate_downloaded_size(&reopened_file, &file_info).map_err(|e| {
+                    DownloadError::SecurityViolation(e.context(download_handle.to_string()))
+                })?;
+                drop(reopened_file);
+                let converted = guarded(
+                    convert_archive(&file_path, format, security_config),
+                    deadline,
+                    security_config,
+                    cancel,
+                )
+                .await
+                .inspect_err(|_| {
+                    let _ = std::fs::remove_file(&file_path);
+                })
+                .map_err(|e| e.with_context(&download_handle.to_string()))?;
+                reopened_file = reopen(zip_path.insert(converted))?;
+                security::validate_zip(&mut reopened_file, security_config).map_err(|e| {
+                    DownloadError::SecurityViolation(e.context(download_handle.to_string()))
+                })?;
+            } else {
+                security::validate_downloaded_file(&mut reopened_file, &file_info, security_config)
+                    .map_err(|e| {
+                        DownloadError::SecurityViolation(e.context(download_handle.to_string()))
+                    })?;
+            }
+            if security_config.require_factorio_save {
+                security::validate_factorio_save(&mut reopened_file).map_err(|e| {
+                    DownloadError::NotAFactorioSave(e.context(download_handle.to_string()))
+                })?;
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(e) = checked {
+            // rejected files would pile up as each mirror is tried
+            if out_file.is_dir() {
+                let _ = std::fs::remove_file(&file_path);
+                if let Some(zip_path) = &zip_path {
+                    let _ = std::fs::remove_file(zip_path);
+                }
+            }
+            return Err(e);
+        }
+        let file_path = match zip_path {
+            Some(zip_path) => {
+                name = zip_path.file_name().unwrap().to_string_lossy().into_owned();
+                zip_path
+            }
+            None => file_path,
+        };
 
         Ok(DownloadedFile {
-            name: file_info.name,
+            name,
+            original_name: file_info.name,
             path: file_path,
+            size: summary.size,
+            sha256: summary.sha256_hex(),
+            link: download_handle.to_string(),
         })
     }
 
     fn get_download_handle<'a>(
         services: &'a mut [DynFileService],
-        input: &str,
-    ) -> Result<Box<dyn FileDownloadHandle + 'a>, DownloadError> {
-        debug!("Input is: {}", input);
+        link: &DetectedLink,
+    ) -> Option<Box<dyn FileDownloadHandle + 'a>> {
         services
-            .iter_mut()
-            .find_map(|service| service.detect_link(input))
-            .ok_or(DownloadError::NoLinkFound)
+            .get_mut(link.service_index)?
+            .detect_link(&link.fragment)
+    }
+}
+
+/// Repacks a 7z or rar archive into a zip next to it, if conversion is on and built in.
+async fn convert_archive(
+    path: &Path,
+    format: ArchiveFormat,
+    security_config: &SecurityConfig,
+) -> Result<PathBuf, DownloadError> {
+    if !security_config.convert_archives {
+        return Err(DownloadError::UnsupportedArchive(anyhow::anyhow!(
+            "{format} archives are not accepted; the save has to be a .zip"
+        )));
+    }
+    #[cfg(feature = "archive-conversion")]
+    {
+        info!("Converting {format} archive to zip");
+        convert::convert_to_zip(path, security_config).await
+    }
+    #[cfg(not(feature = "archive-conversion"))]
+    {
+        let _ = path;
+        Err(DownloadError::UnsupportedArchive(anyhow::anyhow!(
+            "{format} archives can't be converted; built without the archive-conversion feature"
+        )))
+    }
+}
+
+/// Runs one step of a download, giving up at `deadline` or when `cancel` fires.
+async fn guarded<T>(
+    step: impl Future<Output = Result<T, DownloadError>>,
+    deadline: Instant,
+    security_config: &SecurityConfig,
+    cancel: &CancellationToken,
+) -> Result<T, DownloadError> {
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => Err(DownloadError::Cancelled),
+        result = tokio::time::timeout_at(deadline, step) => result.unwrap_or_else(|_| {
+            Err(DownloadError::ServiceError(anyhow::anyhow!(
+                "Download timed out after {:?}",
+                security_config.download_timeout
+            )))
+        }),
     }
 }
 
@@ -236,6 +548,63 @@ impl Default for FileDownloader {
 mod tests {
     use super::*;
     use crate::services::test_util::MockService;
+    use async_trait::async_trait;
+
+    /// Detects `flaky://<name>` links; names starting with "dead" fail to download,
+    /// and names starting with "slow" write a partial file and then hang.
+    struct FlakyService;
+
+    #[async_trait]
+    impl FileService for FlakyService {
+        type FileId = String;
+
+        fn service_name() -> &'static str {
+            "flaky"
+        }
+
+        fn detect_link(input: &str) -> Option<Self::FileId> {
+            let start = input.find("flaky://")? + "flaky://".len();
+            let name: String = input[start..]
+                .chars()
+                .take_while(|c| c.is_alphanumeric())
+                .collect();
+            (!name.is_empty()).then_some(name)
+        }
+
+        async fn get_file_info(
+            &mut self,
+            file_id: &Self::FileId,
+            _config: &SecurityConfig,
+        ) -> Result<FileMeta, DownloadError> {
+            if file_id.starts_with("dead") {
+                return Err(DownloadError::FileNotAccessible(anyhow::anyhow!(
+                    "{file_id} is gone"
+                )));
+            }
+            Ok(FileMeta {
+                name: format!("{file_id}.zip"),
+                size: 0,
+            })
+        }
+
+        async fn download(
+            &mut self,
+            file_id: &Self::FileId,
+            dest: &Path,
+            _config: &SecurityConfig,
+        ) -> Result<DownloadSummary, DownloadError> {
+            if file_id.starts_with("slow") {
+                std::fs::write(dest, b"PK")?;
+                std::future::pending::<()>().await;
+            }
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(dest)?);
+            zip.start_file("level.dat0", zip::write::SimpleFileOptions::default())
+                .map_err(|e| DownloadError::ServiceError(e.into()))?;
+            zip.finish()
+                .map_err(|e| DownloadError::ServiceError(e.into()))?;
+            Ok(DownloadSummary::of_file(dest)?)
+        }
+    }
 
     #[test]
     fn test_file_downloader_creation() {
@@ -255,11 +624,254 @@ mod tests {
     #[tokio::test]
     async fn test_no_links_detected() {
         let mut downloader = FileDownloader::builder().add_service(MockService).build();
-        let result = downloader.download_zip_to_temp("no links here").await;
+        let result = downloader
+            .download_zip_to_temp("no links here", &CancellationToken::new())
+            .await;
 
         assert!(matches!(result, Err(DownloadError::NoLinkFound)));
     }
 
+    #[test]
+    fn test_detect_all_links() {
+        let mut downloader = FileDownloader::builder()
+            .add_service(MockService)
+            .add_service(FlakyService)
+            .build();
+
+        let links = downloader.detect_all_links(
+            "mirror: flaky://first, backup (flaky://second) flaky://first mock://x",
+        );
+        let described: Vec<_> = links
+            .iter()
+            .map(|l| (l.service.as_str(), l.link.as_str()))
+            .collect();
+        assert_eq!(
+            described,
+            [
+                ("flaky", "flaky link: first"),
+                ("flaky", "flaky link: second"),
+                ("mock", "mock link: test_id"),
+            ]
+        );
+
+        assert!(downloader.detect_all_links("no links here").is_empty());
+    }
+
+    #[test]
+    fn test_detect_links_around_punctuation() {
+        let mut downloader = FileDownloader::builder()
+            .add_service(services::onedrive::OneDriveService::new())
+            .build();
+        let links: Vec<_> = downloader
+            .detect_all_links(
+                "Save (https://1drv.ms/u/s!first), [mirror](https://1drv.ms/u/s!second). \
+                 <https://1drv.ms/u/s!third> or **https://1drv.ms/u/s!fourth**!",
+            )
+            .into_iter()
+            .map(|l| l.link)
+            .collect();
+        assert_eq!(
+            links,
+            [
+                "onedrive link: https://1drv.ms/u/s!first",
+                "onedrive link: https://1drv.ms/u/s!second",
+                "onedrive link: https://1drv.ms/u/s!third",
+                "onedrive link: https://1drv.ms/u/s!fourth",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_to_next_link() {
+        let mut downloader = FileDownloader::builder().add_service(FlakyService).build();
+        let (_file, downloaded) = downloader
+            .download_zip_to_temp(
+                "flaky://deadlink or flaky://mirror",
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(downloaded.name, "mirror.zip");
+        assert_eq!(downloaded.link, "flaky link: mirror");
+        let summary = DownloadSummary::of_file(&downloaded.path).unwrap();
+        assert_eq!(downloaded.size, summary.size);
+        assert_eq!(downloaded.sha256, summary.sha256_hex());
+    }
+
+    #[tokio::test]
+    async fn test_all_links_failing_returns_first_error() {
+        let mut downloader = FileDownloader::builder().add_service(FlakyService).build();
+        let result = downloader
+            .download_zip_to_temp("flaky://deadone flaky://deadtwo", &CancellationToken::new())
+            .await;
+
+        match result {
+            Err(DownloadError::FileNotAccessible(e)) => {
+                assert!(format!("{e:#}").contains("deadone is gone"))
+            }
+            other => panic!("unexpected result: {:?}", other.map(|(_, f)| f.name)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_all_zips() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut downloader = FileDownloader::builder().add_service(FlakyService).build();
+        let files = downloader
+            .download_all_zips(
+                "part 1: flaky://first part 2: flaky://second",
+                dir.path(),
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+        let names: Vec<_> = files.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(names, ["first.zip", "second.zip"]);
+
+        let other_dir = tempfile::tempdir().unwrap();
+        let result = downloader
+            .download_all_zips(
+                "flaky://third flaky://deadfourth",
+                other_dir.path(),
+                &CancellationToken::new(),
+            )
+            .await;
+        assert!(matches!(result, Err(DownloadError::FileNotAccessible(_))));
+        assert_eq!(std::fs::read_dir(other_dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_download_into_dir_uses_template_and_avoids_collisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut downloader = FileDownloader::builder()
+            .add_service(FlakyService)
+            .with_file_name_template(FileNameTemplate::new("{run_id}_{name}").var("run_id", "r1"))
+            .build();
+
+        let first = downloader
+            .download_zip("flaky://save", dir.path(), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(first.name, "r1_save.zip");
+        assert_eq!(first.original_name, "save.zip");
+        assert_eq!(first.path, dir.path().join("r1_save.zip"));
+
+        let second = downloader
+            .download_zip("flaky://save", dir.path(), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(second.name, "r1_save_1.zip");
+        assert_eq!(second.path, dir.path().join("r1_save_1.zip"));
+        assert!(first.path.exists() && second.path.exists());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_download_times_out() {
+        let mut downloader = FileDownloader::builder()
+            .add_service(FlakyService)
+            .with_security_config(SecurityConfig {
+                download_timeout: std::time::Duration::from_secs(5),
+                ..Default::default()
+            })
+            .build();
+        let result = downloader
+            .download_zip_to_temp("flaky://slow flaky://fast", &CancellationToken::new())
+            .await;
+
+        let (_file, downloaded) = result.unwrap();
+        assert_eq!(downloaded.original_name, "fast.zip");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_aborts_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut downloader = FileDownloader::builder().add_service(FlakyService).build();
+        let cancel = CancellationToken::new();
+
+        let download = downloader.download_zip("flaky://slow flaky://fast", dir.path(), &cancel);
+        let (result, _) = tokio::join!(download, async { cancel.cancel() });
+
+        assert!(matches!(result, Err(DownloadError::Cancelled)));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_insufficient_disk_space() {
+        let mut downloader = FileDownloader::builder()
+            .add_service(FlakyService)
+            .with_security_config(SecurityConfig {
+                min_free_space: u64::MAX / 2,
+                ..Default::default()
+            })
+            .build();
+        let result = downloader
+            .download_zip_to_temp("flaky://save", &CancellationToken::new())
+            .await;
+
+        let err = result.err().unwrap();
+        assert!(matches!(err, DownloadError::InsufficientDiskSpace(_)));
+        assert_eq!(err.class(), ErrorClass::Retryable);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_size_up_to_largest_maximum() {
+        let mut downloader = FileDownloader::builder()
+            .add_service(FlakyService)
+            .with_security_config(SecurityConfig {
+                max_file_size: u64::MAX - 1024 * 1024,
+                ..Default::default()
+            })
+            .build();
+        let result = downloader
+            .download_zip_to_temp("flaky://save", &CancellationToken::new())
+            .await;
+
+        assert!(matches!(
+            result.err().unwrap(),
+            DownloadError::InsufficientDiskSpace(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_require_factorio_save() {
+        let mut downloader = FileDownloader::builder()
+            .add_service(FlakyService)
+            .with_security_config(SecurityConfig {
+                require_factorio_save: true,
+                ..Default::default()
+            })
+            .build();
+        let result = downloader
+            .download_zip_to_temp("flaky://notasave", &CancellationToken::new())
+            .await;
+
+        assert!(matches!(result, Err(DownloadError::NotAFactorioSave(_))));
+        assert_eq!(result.err().unwrap().class(), ErrorClass::Final);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_files_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut downloader = FileDownloader::builder()
+            .add_service(FlakyService)
+            .with_security_config(SecurityConfig {
+                require_factorio_save: true,
+                ..Default::default()
+            })
+            .build();
+        let result = downloader
+            .download_zip(
+                "flaky://notasave or flaky://mirror",
+                dir.path(),
+                &CancellationToken::new(),
+            )
+            .await;
+
+        assert!(matches!(result, Err(DownloadError::NotAFactorioSave(_))));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
     #[test]
     fn test_validate_file_info() {
         let security_config = SecurityConfig::default();
@@ -0,0 +1,283 @@
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+const MAX_FILE_NAME_BYTES: usize = 200;
+const MAX_EXTENSION_BYTES: usize = 16;
+const FALLBACK_STEM: &str = "download";
+/// Device names Windows refuses as a file name, with or without an extension.
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Makes a service-provided file name safe to create on any filesystem.
+///
+/// The name is NFKC-normalized and control characters are dropped. Letters and digits of any
+/// script, `-` and `.` are kept; anything else (whitespace, punctuation, emoji) becomes `_`.
+/// Runs of `_` are collapsed, leading dots are dropped, Windows device names get a `_` suffix
+/// and long names are truncated to a fixed byte length keeping the extension.
+pub fn sanitize_file_name(name: &str) -> String {
+    let sanitized = replace_unsafe_chars(name);
+
+    let (stem, ext) = split_extension(sanitized.trim_end_matches(['.', '_']));
+    let stem = stem.trim_matches(['.', '_']);
+    let stem = if stem.is_empty() { FALLBACK_STEM } else { stem };
+
+    let device = stem.split('.').next().unwrap_or(stem);
+    let reserved = WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| device.eq_ignore_ascii_case(reserved));
+
+    let max_stem = MAX_FILE_NAME_BYTES - ext.len() - usize::from(reserved);
+    let stem = truncate_at_char_boundary(stem, max_stem).trim_end_matches(['.', '_']);
+    if reserved {
+        format!("{}_{}{}", device, &stem[device.len()..], ext)
+    } else {
+        format!("{}{}", stem, ext)
+    }
+}
+
+/// Normalizes `s` and maps every character that isn't safe in a file name to `_`,
+/// collapsing runs of `_`. Combining marks are only kept after a letter or digit.
+fn replace_unsafe_chars(s: &str) -> String {
+    let mut sanitized = String::with_capacity(s.len());
+    for c in s.nfkc() {
+        let c = if c.is_alphanumeric() || matches!(c, '-' | '.') {
+            c
+        } else if is_combining_mark(c) {
+            match sanitized.chars().next_back() {
+                Some(prev) if prev.is_alphanumeric() || is_combining_mark(prev) => c,
+                _ => continue,
+            }
+        } else if c.is_control() && !c.is_whitespace() {
+            continue;
+        } else {
+            '_'
+        };
+        if !(c == '_' && sanitized.ends_with('_')) {
+            sanitized.push(c);
+        }
+    }
+    sanitized
+}
+
+fn truncate_at_char_boundary(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let end = (0..=max_bytes)
+        .rev()
+        .find(|&i| s.is_char_boundary(i))
+        .unwrap_or(0);
+    &s[..end]
+}
+
+/// Splits `name` into stem and extension (with its leading dot).
+/// Only a short, non-empty ASCII alphanumeric suffix counts as an extension, and dotfiles have none.
+fn split_extension(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(i) if i > 0 && name.len() > i + 1 && name.len() - i <= MAX_EXTENSION_BYTES => {
+            let (stem, ext) = name.split_at(i);
+            if ext[1..].chars().all(|c| c.is_ascii_alphanumeric()) {
+                (stem, ext)
+            } else {
+                (name, "")
+            }
+        }
+        _ => (name, ""),
+    }
+}
+
+/// Returns `dir/name`, or `dir/<stem>_<n><ext>` for the first `n` that doesn't exist yet.
+pub fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let (stem, ext) = split_extension(name);
+    (1..)
+        .map(|n| dir.join(format!("{}_{}{}", stem, n, ext)))
+        .find(|path| !path.exists())
+        .expect("ran out of file names")
+}
+
+/// Template for downloaded file names, e.g. `{run_id}_{name}`.
+///
+/// `{name}`, `{stem}` and `{ext}` come from the sanitized service-provided file name; other
+/// placeholders are filled from [`FileNameTemplate::var`]. Each value is sanitized before it is
+/// substituted, so a name that sanitizes to nothing can't change the literal parts of the
+/// template, and the result is sanitized as a whole.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileNameTemplate {
+    template: String,
+    vars: Vec<(String, String)>,
+}
+
+impl FileNameTemplate {
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+            vars: Vec::new(),
+        }
+    }
+
+    pub fn var(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.vars.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn render(&self, name: &str) -> String {
+        let name = sanitize_file_name(name);
+        let (stem, ext) = split_extension(&name);
+        let builtins = [
+            ("name", name.clone()),
+            ("stem", stem.to_string()),
+            ("ext", ext.trim_start_matches('.').to_string()),
+        ];
+        let vars = self
+            .vars
+            .iter()
+            .map(|(k, v)| (k.as_str(), replace_unsafe_chars(v)));
+        let rendered = builtins
+            .iter()
+            .map(|(k, v)| (*k, v.clone()))
+            .chain(vars)
+            .fold(self.template.clone(), |acc, (key, value)| {
+                acc.replace(&format!("{{{}}}", key), &value)
+            });
+        sanitize_file_name(&rendered)
+    }
+}
+
+impl Default for FileNameTemplate {
+    fn default() -> Self {
+        Self::new("{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use unicode_normalization::is_nfkc;
+
+    #[test]
+    fn test_sanitize_file_name() {
+        let test_cases = [
+            ("run.zip", "run.zip"),
+            ("my run (final).zip", "my_run_final.zip"),
+            ("../../etc/passwd", "etc_passwd"),
+            ("..\\windows\\save.zip", "windows_save.zip"),
+            ("ファクトリオ.zip", "ファクトリオ.zip"),
+            ("Über run.zip", "Über_run.zip"),
+            ("U\u{308}ber.zip", "Über.zip"),
+            ("ＲＵＮ１.zip", "RUN1.zip"),
+            ("🚀 speedrun 🏭.zip", "speedrun.zip"),
+            ("🚀.zip", "download.zip"),
+            ("null\u{0}byte\u{7f}.zip", "nullbyte.zip"),
+            ("\u{301}accent.zip", "accent.zip"),
+            ("CON.zip", "CON_.zip"),
+            ("nul", "nul_"),
+            ("com1.tar.zst", "com1_.tar.zst"),
+            ("console.zip", "console.zip"),
+            ("a<b>c:d|e?f*g\"h.zip", "a_b_c_d_e_f_g_h.zip"),
+            ("tab\tand\nnewline.zip", "tab_and_newline.zip"),
+            (".hidden", "hidden"),
+            ("", "download"),
+            ("...", "download"),
+            ("trailing_.zip", "trailing.zip"),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(sanitize_file_name(input), expected, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn test_sanitize_truncates_keeping_extension() {
+        let long = format!("{}.zip", "a".repeat(500));
+        let sanitized = sanitize_file_name(&long);
+        assert_eq!(sanitized.len(), MAX_FILE_NAME_BYTES);
+        assert!(sanitized.ends_with("a.zip"));
+
+        let long = format!("{}.zip", "ファ".repeat(100));
+        let sanitized = sanitize_file_name(&long);
+        assert!(sanitized.len() <= MAX_FILE_NAME_BYTES);
+        assert!(sanitized.ends_with(".zip"));
+    }
+
+    #[test]
+    fn test_template_render() {
+        let template = FileNameTemplate::new("{run_id}_{name}").var("run_id", "abc123");
+        assert_eq!(template.render("my save.zip"), "abc123_my_save.zip");
+
+        let template = FileNameTemplate::new("{stem}-{run_id}.{ext}").var("run_id", "z9");
+        assert_eq!(template.render("run.zip"), "run-z9.zip");
+
+        assert_eq!(FileNameTemplate::default().render("a/b.zip"), "a_b.zip");
+
+        let template = FileNameTemplate::new("{run_id}_{name}").var("run_id", "abc123");
+        assert_eq!(template.render("🏭.zip"), "abc123_download.zip");
+        assert_eq!(template.render("工場.zip"), "abc123_工場.zip");
+    }
+
+    #[test]
+    fn test_unique_path() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            unique_path(dir.path(), "run.zip"),
+            dir.path().join("run.zip")
+        );
+
+        std::fs::write(dir.path().join("run.zip"), b"").unwrap();
+        assert_eq!(
+            unique_path(dir.path(), "run.zip"),
+            dir.path().join("run_1.zip")
+        );
+
+        std::fs::write(dir.path().join("run_1.zip"), b"").unwrap();
+        assert_eq!(
+            unique_path(dir.path(), "run.zip"),
+            dir.path().join("run_2.zip")
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_sanitized_name_is_portable(name in any::<String>()) {
+            let sanitized = sanitize_file_name(&name);
+            prop_assert!(!sanitized.is_empty());
+            prop_assert!(sanitized.len() <= MAX_FILE_NAME_BYTES);
+            prop_assert!(is_nfkc(&sanitized));
+            prop_assert!(!sanitized.starts_with('.'));
+            prop_assert!(!sanitized.ends_with(['.', ' ']));
+            prop_assert!(
+                !sanitized.chars().any(|c| c.is_control() || "<>:\"/\\|?*".contains(c)),
+                "{sanitized:?}"
+            );
+            let device = sanitized.split('.').next().unwrap();
+            prop_assert!(!WINDOWS_RESERVED_NAMES.iter().any(|r| device.eq_ignore_ascii_case(r)));
+        }
+
+        #[test]
+        fn prop_sanitize_is_idempotent(name in any::<String>()) {
+            let sanitized = sanitize_file_name(&name);
+            prop_assert_eq!(sanitize_file_name(&sanitized), sanitized);
+        }
+
+        #[test]
+        fn prop_equivalent_names_sanitize_equally(name in any::<String>()) {
+            let nfd: String = unicode_normalization::UnicodeNormalization::nfd(name.as_str()).collect();
+            prop_assert_eq!(sanitize_file_name(&nfd), sanitize_file_name(&name));
+        }
+
+        #[test]
+        fn prop_template_keeps_prefix(run_id in "[a-z0-9]{1,12}", name in any::<String>()) {
+            let template = FileNameTemplate::new("{run_id}_{name}").var("run_id", run_id.clone());
+            let rendered = template.render(&name);
+            prop_assert!(rendered.starts_with(&format!("{run_id}_")), "{rendered:?}");
+            prop_assert_eq!(sanitize_file_name(&rendered), rendered.clone());
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use anyhow::{Context, Result, bail, ensure};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::io::Read;
 use std::sync::LazyLock;
 use std::{fs::File, path::Path};
 use zip::ZipArchive;
 
+use crate::convert::CONVERTIBLE_EXTENSIONS;
+
 #[derive(Debug, Clone)]
 pub struct SecurityConfig {
     pub max_file_size: u64,
@@ -14,6 +17,14 @@ pub struct SecurityConfig {
     pub connect_timeout: std::time::Duration,
     pub download_timeout: std::time::Duration,
     pub max_redirects: usize,
+    /// Free space that must remain on the destination filesystem after a download.
+    pub min_free_space: u64,
+    /// Reject zips that don't look like a Factorio save (see [`validate_factorio_save`]).
+    pub require_factorio_save: bool,
+    /// Accept 7z and rar archives, repacked into zips; see [`crate::convert`].
+    pub convert_archives: bool,
+    /// Set by [`crate::throttle::Throttled`] for the duration of a download.
+    pub throttle: Option<std::sync::Arc<crate::throttle::DownloadThrottle>>,
 }
 
 impl Default for SecurityConfig {
@@ -26,7 +37,69 @@ impl Default for SecurityConfig {
             connect_timeout: std::time::Duration::from_secs(30),
             download_timeout: std::time::Duration::from_secs(600),
             max_redirects: 10,
+            min_free_space: 256 * 1024 * 1024, // 256 MB
+            require_factorio_save: false,
+            convert_archives: false,
+            throttle: None,
+        }
+    }
+}
+
+/// Limits replacing those of a [`SecurityConfig`], e.g. for a category whose saves are
+/// larger than most.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityOverrides {
+    /// Maximum download size in MB.
+    #[serde(deserialize_with = "deserialize_mb")]
+    pub max_file_size_mb: Option<u64>,
+    /// Maximum total size of a zip's entries once extracted, in MB.
+    #[serde(deserialize_with = "deserialize_mb")]
+    pub max_extracted_size_mb: Option<u64>,
+    /// Extensions accepted, with the dot, e.g. `[".zip"]`.
+    pub allowed_extensions: Option<Vec<String>>,
+    /// Accept 7z and rar archives, repacked into zips.
+    pub convert_archives: Option<bool>,
+}
+
+const MB: u64 = 1024 * 1024;
+
+/// Rejects sizes in MB too large to count in bytes.
+fn deserialize_mb<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<u64>, D::Error> {
+    let mb = Option::<u64>::deserialize(deserializer)?;
+    if let Some(mb) = mb
+        && mb.checked_mul(MB).is_none()
+    {
+        return Err(serde::de::Error::custom(format!(
+            "{mb} MB is more than the maximum of {} MB",
+            u64::MAX / MB
+        )));
+    }
+    Ok(mb)
+}
+
+impl SecurityConfig {
+    /// Sizes too large to count in bytes, only possible if not deserialized, are unlimited.
+    pub fn with_overrides(mut self, overrides: &SecurityOverrides) -> Self {
+        if let Some(mb) = overrides.max_file_size_mb {
+            self.max_file_size = mb.saturating_mul(MB);
+        }
+        if let Some(mb) = overrides.max_extracted_size_mb {
+            self.max_extracted_size = mb.saturating_mul(MB);
+        }
+        if let Some(extensions) = &overrides.allowed_extensions {
+            self.allowed_extensions = extensions
+                .iter()
+                .map(|extension| extension.to_lowercase())
+                .collect();
+        }
+        if let Some(convert_archives) = overrides.convert_archives {
+            self.convert_archives = convert_archives;
         }
+        self
     }
 }
 
@@ -48,7 +121,8 @@ fn validate_file_extension(filename: &str, config: &SecurityConfig) -> Result<()
         .map(|ext| format!(".{}", ext.to_lowercase()));
 
     if let Some(ext) = extension
-        && config.allowed_extensions.contains(&ext)
+        && (config.allowed_extensions.contains(&ext)
+            || config.convert_archives && CONVERTIBLE_EXTENSIONS.contains(&ext.as_str()))
     {
         return Ok(());
     }
@@ -83,7 +157,9 @@ fn validate_zip_file(file: &mut File, config: &SecurityConfig) -> Result<()> {
             entry.name()
         );
 
-        total_uncompressed_size += entry.size();
+        total_uncompressed_size = total_uncompressed_size
+            .checked_add(entry.size())
+            .context("Total uncompressed size overflows")?;
     }
 
     ensure!(
@@ -139,9 +215,18 @@ pub fn validate_downloaded_file(
     file_info: &crate::services::FileMeta,
     config: &SecurityConfig,
 ) -> Result<()> {
+    validate_zip(file, config)?;
+    validate_downloaded_size(file, file_info)
+}
+
+/// Checks that `file` is a zip within the entry and size limits.
+pub fn validate_zip(file: &mut File, config: &SecurityConfig) -> Result<()> {
     validate_zip_magic_number(file)?;
-    validate_zip_file(file, config)?;
+    validate_zip_file(file, config)
+}
 
+/// Checks that the download is as large as the service said it would be.
+pub fn validate_downloaded_size(file: &File, file_info: &crate::services::FileMeta) -> Result<()> {
     let actual_size = file.metadata()?.len();
     // Allow size mismatch when expected size is 0 (unknown size from services that can't get metadata)
     if file_info.size != 0 && actual_size != file_info.size {
@@ -154,6 +239,56 @@ pub fn validate_downloaded_file(
     Ok(())
 }
 
+/// Checks that the zip has a single top-level folder containing `level-init.dat`,
+/// `control.lua` and `level.dat` (or its split `level.datN` parts).
+pub fn validate_factorio_save(file: &mut File) -> Result<()> {
+    let archive = ZipArchive::new(file).with_context(|| "Failed to read zip")?;
+
+    let mut folders = Vec::new();
+    let mut files_in_folder = Vec::new();
+    for path in archive.file_names().map(Path::new) {
+        let mut components = path.components();
+        let Some(folder) = components.next() else {
+            continue;
+        };
+        let folder = folder.as_os_str().to_string_lossy().into_owned();
+        if !folders.contains(&folder) {
+            folders.push(folder);
+        }
+        let rest = components.as_path();
+        if rest.components().count() == 1 {
+            files_in_folder.push(rest.to_string_lossy().into_owned());
+        }
+    }
+
+    match folders.as_slice() {
+        [_] => {}
+        [] => bail!("Zip is empty"),
+        _ => bail!(
+            "Expected a single top-level folder, found: {}",
+            folders.join(", ")
+        ),
+    }
+
+    let has = |name: &str| files_in_folder.iter().any(|f| f == name);
+    let has_level_dat = files_in_folder.iter().any(|f| {
+        f.strip_prefix("level.dat")
+            .is_some_and(|suffix| suffix.chars().all(|c| c.is_ascii_digit()))
+    });
+    let missing: Vec<_> = [
+        ("level.dat", has_level_dat),
+        ("level-init.dat", has("level-init.dat")),
+        ("control.lua", has("control.lua")),
+    ]
+    .into_iter()
+    .filter(|(_, present)| !present)
+    .map(|(name, _)| name)
+    .collect();
+    ensure!(missing.is_empty(), "Missing {}", missing.join(", "));
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::FileMeta;
@@ -172,6 +307,51 @@ mod tests {
         assert_eq!(config.max_zip_entries, 500);
     }
 
+    #[test]
+    fn test_with_overrides() {
+        let config = SecurityConfig {
+            require_factorio_save: true,
+            ..Default::default()
+        };
+        let unchanged = config.clone().with_overrides(&SecurityOverrides::default());
+        assert_eq!(unchanged.max_file_size, config.max_file_size);
+        assert_eq!(unchanged.allowed_extensions, config.allowed_extensions);
+
+        let overrides = SecurityOverrides {
+            max_file_size_mb: Some(2048),
+            max_extracted_size_mb: Some(4096),
+            allowed_extensions: Some(vec![".ZIP".to_string(), ".tar".to_string()]),
+            convert_archives: None,
+        };
+        let config = config.with_overrides(&overrides);
+        assert_eq!(config.max_file_size, 2048 * 1024 * 1024);
+        assert_eq!(config.max_extracted_size, 4096 * 1024 * 1024);
+        assert_eq!(config.allowed_extensions, [".zip", ".tar"]);
+        assert!(config.require_factorio_save);
+        assert!(validate_file_extension("save.tar", &config).is_ok());
+        assert!(validate_file_extension("save.7z", &config).is_err());
+
+        let overrides = SecurityOverrides {
+            convert_archives: Some(true),
+            ..Default::default()
+        };
+        let config = config.with_overrides(&overrides);
+        assert!(validate_file_extension("save.7z", &config).is_ok());
+        assert!(validate_file_extension("save.RAR", &config).is_ok());
+    }
+
+    #[test]
+    fn test_overrides_reject_overflowing_sizes() {
+        let overrides: SecurityOverrides =
+            serde_json::from_str(r#"{"max_file_size_mb": 17592186044415}"#).unwrap();
+        assert_eq!(overrides.max_file_size_mb, Some(u64::MAX / MB));
+        let error = serde_json::from_str::<SecurityOverrides>(
+            r#"{"max_extracted_size_mb": 17592186044416}"#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("17592186044416 MB is more than"));
+    }
+
     #[test]
     fn test_validate_file_size() {
         let config = SecurityConfig::default();
@@ -326,6 +506,51 @@ mod tests {
         );
     }
 
+    fn zip_with_entries(entries: &[&str]) -> NamedTempFile {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        let mut zip = ZipWriter::new(temp_file.as_file_mut());
+        for entry in entries {
+            zip.start_file(*entry, FileOptions::<()>::default())
+                .unwrap();
+        }
+        zip.finish().unwrap();
+        temp_file
+    }
+
+    #[test]
+    fn test_validate_factorio_save() {
+        let validate = |entries: &[&str]| {
+            validate_factorio_save(zip_with_entries(entries).as_file_mut())
+                .map_err(|e| e.to_string())
+        };
+
+        assert!(validate(&["run/level.dat", "run/level-init.dat", "run/control.lua"]).is_ok());
+        assert!(
+            validate(&[
+                "run/level.dat0",
+                "run/level.dat1",
+                "run/level-init.dat",
+                "run/control.lua",
+                "run/script.dat",
+            ])
+            .is_ok()
+        );
+
+        assert_eq!(validate(&[]).unwrap_err(), "Zip is empty");
+        assert_eq!(
+            validate(&["a/level.dat", "b/level-init.dat", "a/control.lua"]).unwrap_err(),
+            "Expected a single top-level folder, found: a, b"
+        );
+        assert_eq!(
+            validate(&["run/level.dat", "run/control.lua"]).unwrap_err(),
+            "Missing level-init.dat"
+        );
+        assert_eq!(
+            validate(&["run/readme.txt", "run/sub/control.lua"]).unwrap_err(),
+            "Missing level.dat, level-init.dat, control.lua"
+        );
+    }
+
     #[test]
     fn test_security_error_types() {
         let config = SecurityConfig {
@@ -1,9 +1,9 @@
-use crate::DownloadError;
 use crate::security::SecurityConfig;
 use crate::services::{FileMeta, FileService};
+use crate::sink::{DownloadSink, DownloadSummary};
+use crate::{DownloadError, ServiceAccess};
 use anyhow::Context;
 use async_trait::async_trait;
-use futures::StreamExt;
 use regex::Regex;
 use std::path::Path;
 use std::sync::LazyLock;
@@ -64,10 +64,11 @@ async fn get_file_info(
         .map_err(DownloadError::ServiceError)?;
 
     if !response.status().is_success() {
-        return Err(DownloadError::FileNotAccessible(anyhow::anyhow!(
-            "HTTP {} from Dropbox",
-            response.status()
-        )));
+        return Err(DownloadError::from_response(
+            &response,
+            "Dropbox",
+            ServiceAccess::Public,
+        ));
     }
 
     let headers = response.headers();
@@ -99,9 +100,7 @@ async fn download_file(
     file_id: &DropboxFileId,
     dest: &Path,
     config: &SecurityConfig,
-) -> Result<(), DownloadError> {
-    use tokio::io::AsyncWriteExt;
-
+) -> Result<DownloadSummary, DownloadError> {
     let client = build_client(config)
         .context("Failed to build HTTP client")
         .map_err(DownloadError::ServiceError)?;
@@ -114,37 +113,17 @@ async fn download_file(
         .map_err(DownloadError::ServiceError)?;
 
     if !response.status().is_success() {
-        return Err(DownloadError::FileNotAccessible(anyhow::anyhow!(
-            "HTTP {} from Dropbox",
-            response.status()
-        )));
+        return Err(DownloadError::from_response(
+            &response,
+            "Dropbox",
+            ServiceAccess::Public,
+        ));
     }
 
-    let mut file = tokio::fs::File::create(dest)
+    DownloadSink::create(dest, config)
+        .await?
+        .write_response(response)
         .await
-        .map_err(DownloadError::IoError)?;
-    let mut stream = response.bytes_stream();
-    let mut total_bytes = 0u64;
-
-    while let Some(chunk) = stream.next().await {
-        let bytes = chunk
-            .context("Failed to read response stream")
-            .map_err(DownloadError::ServiceError)?;
-        total_bytes += bytes.len() as u64;
-        if total_bytes > config.max_file_size {
-            return Err(DownloadError::SecurityViolation(anyhow::anyhow!(
-                "Download exceeded maximum size of {} bytes",
-                config.max_file_size
-            )));
-        }
-        file.write_all(&bytes)
-            .await
-            .map_err(DownloadError::IoError)?;
-    }
-
-    file.flush().await.map_err(DownloadError::IoError)?;
-
-    Ok(())
 }
 
 pub struct DropboxService;
@@ -190,7 +169,7 @@ impl FileService for DropboxService {
         file_id: &Self::FileId,
         dest: &Path,
         config: &SecurityConfig,
-    ) -> Result<(), DownloadError> {
+    ) -> Result<DownloadSummary, DownloadError> {
         download_file(file_id, dest, config).await
     }
 }
@@ -200,6 +179,7 @@ mod tests {
 
     use super::*;
     use crate::FileDownloader;
+    use tokio_util::sync::CancellationToken;
 
     const TEST_URL: &str = "https://www.dropbox.com/scl/fi/aw5ohfvtfoc2nnn4nl2n6/foo.zip?rlkey=1sholbp5uxq15dk0ke5ljtwsz&st=gpkdzloy&dl=0";
 
@@ -270,7 +250,9 @@ mod tests {
         let service = DropboxService::new();
         let mut downloader = FileDownloader::builder().add_service(service).build();
 
-        let (file, info) = downloader.download_zip_to_temp(TEST_URL).await?;
+        let (file, info) = downloader
+            .download_zip_to_temp(TEST_URL, &CancellationToken::new())
+            .await?;
         assert_eq!(info.name, "foo.zip");
         assert!(file.path().exists());
 
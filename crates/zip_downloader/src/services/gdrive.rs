@@ -1,13 +1,14 @@
-use crate::DownloadError;
 use crate::security::SecurityConfig;
+use crate::services::google_auth::ServiceAccountAuthenticator;
 use crate::services::{FileMeta, FileService};
+use crate::sink::{DownloadSink, DownloadSummary};
+use crate::{DownloadError, ServiceAccess};
 use anyhow::Context;
 use async_trait::async_trait;
-use futures::StreamExt;
 use regex::Regex;
-use std::path::Path;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
-use tokio::io::AsyncWriteExt as _;
 
 fn build_client(config: &SecurityConfig) -> reqwest::Result<reqwest::Client> {
     reqwest::Client::builder()
@@ -17,6 +18,8 @@ fn build_client(config: &SecurityConfig) -> reqwest::Result<reqwest::Client> {
         .build()
 }
 
+const DRIVE_FILES_API: &str = "https://www.googleapis.com/drive/v3/files";
+
 fn public_download_url(file_id: &str) -> String {
     format!(
         "https://drive.usercontent.google.com/download?id={}&export=download&confirm=t",
@@ -24,36 +27,44 @@ fn public_download_url(file_id: &str) -> String {
     )
 }
 
-async fn get_file_info(file_id: &str, config: &SecurityConfig) -> Result<FileMeta, DownloadError> {
+/// Fetches a public share link, rejecting the HTML page Drive serves in place of
+/// files that aren't shared publicly.
+async fn fetch_public(
+    url: &str,
+    config: &SecurityConfig,
+) -> Result<reqwest::Response, DownloadError> {
     let client = build_client(config)
         .context("Failed to build HTTP client")
         .map_err(DownloadError::ServiceError)?;
-    let url = public_download_url(file_id);
-
     let response = client
-        .get(&url)
+        .get(url)
         .send()
         .await
         .context("Failed to send request to Google Drive")
         .map_err(DownloadError::ServiceError)?;
 
     if !response.status().is_success() {
-        return Err(DownloadError::FileNotAccessible(anyhow::anyhow!(
-            "HTTP {} from Google Drive",
-            response.status()
-        )));
+        return Err(DownloadError::from_response(
+            &response,
+            "Google Drive",
+            ServiceAccess::Public,
+        ));
     }
 
-    let headers = response.headers();
-
-    if let Some(content_type) = headers.get("content-type")
+    if let Some(content_type) = response.headers().get("content-type")
         && let Ok(ct) = content_type.to_str()
         && ct.contains("text/html")
     {
         return Err(DownloadError::FileNotAccessible(anyhow::anyhow!(
-            "File requires authentication or is not publicly shared"
+            "File is not publicly shared. Please ensure the file is publicly accessible"
         )));
     }
+    Ok(response)
+}
+
+async fn get_file_info(file_id: &str, config: &SecurityConfig) -> Result<FileMeta, DownloadError> {
+    let response = fetch_public(&public_download_url(file_id), config).await?;
+    let headers = response.headers();
     let name = headers
         .get("content-disposition")
         .and_then(|v| v.to_str().ok())
@@ -79,60 +90,94 @@ async fn download_file_streaming(
     file_id: &str,
     dest: &Path,
     config: &SecurityConfig,
-) -> Result<(), DownloadError> {
+) -> Result<DownloadSummary, DownloadError> {
+    let response = fetch_public(&public_download_url(file_id), config).await?;
+    write_response(response, dest, config).await
+}
+
+#[derive(Deserialize)]
+struct DriveFile {
+    name: String,
+    /// int64 fields are strings in Drive API v3
+    size: Option<String>,
+}
+
+async fn get_file_info_with_auth(
+    file_id: &str,
+    auth: &ServiceAccountAuthenticator,
+    config: &SecurityConfig,
+) -> Result<FileMeta, DownloadError> {
     let client = build_client(config)
         .context("Failed to build HTTP client")
         .map_err(DownloadError::ServiceError)?;
-    let url = public_download_url(file_id);
+    let token = auth.token().await?;
     let response = client
-        .get(&url)
+        .get(format!("{}/{}", DRIVE_FILES_API, file_id))
+        .query(&[("fields", "name,size"), ("supportsAllDrives", "true")])
+        .bearer_auth(token)
         .send()
         .await
-        .context("Failed to send request to Google Drive")
+        .context("Failed to send request to Google Drive API")
         .map_err(DownloadError::ServiceError)?;
 
     if !response.status().is_success() {
-        return Err(DownloadError::FileNotAccessible(anyhow::anyhow!(
-            "HTTP {} from Google Drive",
-            response.status()
-        )));
+        return Err(DownloadError::from_response(
+            &response,
+            "Google Drive API",
+            ServiceAccess::Authenticated,
+        ));
     }
 
-    if let Some(content_type) = response.headers().get("content-type")
-        && let Ok(ct) = content_type.to_str()
-        && ct.contains("text/html")
-    {
-        return Err(DownloadError::FileNotAccessible(anyhow::anyhow!(
-            "File requires authentication or is not publicly shared. \
-             Please ensure the file is publicly accessible"
-        )));
-    }
+    let file: DriveFile = response
+        .json()
+        .await
+        .context("Failed to parse Google Drive file metadata")
+        .map_err(DownloadError::ServiceError)?;
+    Ok(FileMeta {
+        name: file.name,
+        size: file.size.and_then(|s| s.parse().ok()).unwrap_or(0),
+    })
+}
 
-    let mut file = tokio::fs::File::create(dest)
+async fn download_file_with_auth(
+    file_id: &str,
+    dest: &Path,
+    auth: &ServiceAccountAuthenticator,
+    config: &SecurityConfig,
+) -> Result<DownloadSummary, DownloadError> {
+    let client = build_client(config)
+        .context("Failed to build HTTP client")
+        .map_err(DownloadError::ServiceError)?;
+    let token = auth.token().await?;
+    let response = client
+        .get(format!("{}/{}", DRIVE_FILES_API, file_id))
+        .query(&[("alt", "media"), ("supportsAllDrives", "true")])
+        .bearer_auth(token)
+        .send()
         .await
-        .map_err(DownloadError::IoError)?;
-    let mut stream = response.bytes_stream();
-    let mut total_bytes = 0u64;
-
-    while let Some(chunk) = stream.next().await {
-        let bytes = chunk
-            .context("Failed to read response stream")
-            .map_err(DownloadError::ServiceError)?;
-        total_bytes += bytes.len() as u64;
-        if total_bytes > config.max_file_size {
-            return Err(DownloadError::SecurityViolation(anyhow::anyhow!(
-                "Download exceeded maximum size of {} bytes",
-                config.max_file_size
-            )));
-        }
-        file.write_all(&bytes)
-            .await
-            .map_err(DownloadError::IoError)?;
+        .context("Failed to send request to Google Drive API")
+        .map_err(DownloadError::ServiceError)?;
+
+    if !response.status().is_success() {
+        return Err(DownloadError::from_response(
+            &response,
+            "Google Drive API",
+            ServiceAccess::Authenticated,
+        ));
     }
 
-    file.flush().await.map_err(DownloadError::IoError)?;
+    write_response(response, dest, config).await
+}
 
-    Ok(())
+async fn write_response(
+    response: reqwest::Response,
+    dest: &Path,
+    config: &SecurityConfig,
+) -> Result<DownloadSummary, DownloadError> {
+    DownloadSink::create(dest, config)
+        .await?
+        .write_response(response)
+        .await
 }
 
 static GOOGLE_DRIVE_URL_PATTERNS: LazyLock<[Regex; 2]> = LazyLock::new(|| {
@@ -142,11 +187,60 @@ static GOOGLE_DRIVE_URL_PATTERNS: LazyLock<[Regex; 2]> = LazyLock::new(|| {
     ]
 });
 
-pub struct GoogleDriveService;
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GoogleDriveConfig {
+    /// Service account JSON key. When set, files are fetched through the Drive API
+    /// instead of public links, so they only need to be shared with the account.
+    pub service_account_key: Option<PathBuf>,
+    /// Workspace user to impersonate via domain-wide delegation, for files shared
+    /// with that user rather than the service account itself.
+    pub subject: Option<String>,
+}
+
+impl GoogleDriveConfig {
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Self {
+            service_account_key: var("GOOGLE_DRIVE_SERVICE_ACCOUNT_KEY").map(PathBuf::from),
+            subject: var("GOOGLE_DRIVE_SUBJECT"),
+        }
+    }
+}
+
+pub struct GoogleDriveService {
+    config: GoogleDriveConfig,
+    authenticator: Option<ServiceAccountAuthenticator>,
+}
 
 impl GoogleDriveService {
     pub fn new() -> Self {
-        Self
+        Self::with_config(GoogleDriveConfig::default())
+    }
+
+    pub fn with_config(config: GoogleDriveConfig) -> Self {
+        Self {
+            config,
+            authenticator: None,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::with_config(GoogleDriveConfig::from_env())
+    }
+
+    async fn authenticator(
+        &mut self,
+    ) -> Result<Option<&ServiceAccountAuthenticator>, DownloadError> {
+        let Some(key_path) = &self.config.service_account_key else {
+            return Ok(None);
+        };
+        if self.authenticator.is_none() {
+            self.authenticator = Some(
+                ServiceAccountAuthenticator::from_file(key_path, self.config.subject.clone())
+                    .await?,
+            );
+        }
+        Ok(self.authenticator.as_ref())
     }
 }
 
@@ -178,7 +272,10 @@ impl FileService for GoogleDriveService {
         file_id: &Self::FileId,
         config: &SecurityConfig,
     ) -> Result<FileMeta, DownloadError> {
-        get_file_info(file_id, config).await
+        match self.authenticator().await? {
+            Some(auth) => get_file_info_with_auth(file_id, auth, config).await,
+            None => get_file_info(file_id, config).await,
+        }
     }
 
     async fn download(
@@ -186,8 +283,11 @@ impl FileService for GoogleDriveService {
         file_id: &Self::FileId,
         dest: &Path,
         config: &SecurityConfig,
-    ) -> Result<(), DownloadError> {
-        download_file_streaming(file_id, dest, config).await
+    ) -> Result<DownloadSummary, DownloadError> {
+        match self.authenticator().await? {
+            Some(auth) => download_file_with_auth(file_id, dest, auth, config).await,
+            None => download_file_streaming(file_id, dest, config).await,
+        }
     }
 }
 
@@ -219,11 +319,40 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_private_link_not_accessible() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/private"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw("<html>Sign in</html>", "text/html"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/forbidden"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&server)
+            .await;
+        let config = SecurityConfig::default();
+
+        for name in ["private", "forbidden"] {
+            let result = fetch_public(&format!("{}/{}", server.uri(), name), &config).await;
+            assert!(
+                matches!(result, Err(DownloadError::FileNotAccessible(_))),
+                "{name}: {result:?}"
+            );
+        }
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_get_file_info() {
         let config = SecurityConfig::default();
-        let mut service = GoogleDriveService;
+        let mut service = GoogleDriveService::new();
         let result = service
             .get_file_info(&TEST_FILE_ID.to_string(), &config)
             .await;
@@ -269,7 +398,7 @@ mod tests {
     #[ignore]
     async fn test_download() {
         let config = SecurityConfig::default();
-        let mut service = GoogleDriveService;
+        let mut service = GoogleDriveService::new();
         let temp_file = NamedTempFile::new().unwrap();
         let result = service
             .download(&TEST_FILE_ID.to_string(), temp_file.path(), &config)
@@ -0,0 +1,613 @@
+use crate::security::SecurityConfig;
+use crate::services::{FileMeta, FileService};
+use crate::sink::{DownloadSink, DownloadSummary};
+use crate::{DownloadError, ServiceAccess};
+use aes::Aes128;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit, KeyIvInit, StreamCipher};
+use anyhow::Context;
+use async_trait::async_trait;
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use futures::StreamExt;
+use regex::Regex;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const API_URL: &str = "https://g.api.mega.co.nz/cs";
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+fn build_client(config: &SecurityConfig) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .connect_timeout(config.connect_timeout)
+        .timeout(config.download_timeout)
+        .redirect(reqwest::redirect::Policy::limited(config.max_redirects))
+        .build()
+}
+
+static MEGA_URL_PATTERNS: LazyLock<[Regex; 2]> = LazyLock::new(|| {
+    [
+        Regex::new(r"https://mega\.(?:nz|co\.nz)/file/([A-Za-z0-9_-]{8})#([A-Za-z0-9_-]{43})")
+            .unwrap(),
+        Regex::new(r"https://mega\.(?:nz|co\.nz)/#!([A-Za-z0-9_-]{8})!([A-Za-z0-9_-]{43})")
+            .unwrap(),
+    ]
+});
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MegaFileId {
+    handle: String,
+    key: String,
+}
+
+impl MegaFileId {
+    pub fn new(handle: String, key: String) -> Self {
+        Self { handle, key }
+    }
+
+    pub fn handle(&self) -> &str {
+        &self.handle
+    }
+
+    fn file_key(&self) -> Result<FileKey, DownloadError> {
+        let raw = URL_SAFE_NO_PAD
+            .decode(&self.key)
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .ok_or_else(|| {
+                DownloadError::FileNotAccessible(anyhow::anyhow!("Invalid Mega file key"))
+            })?;
+        Ok(FileKey::from_raw(&raw))
+    }
+}
+
+impl std::fmt::Display for MegaFileId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the key after '#' decrypts the file, so leave it out of logs
+        write!(f, "https://mega.nz/file/{}", self.handle)
+    }
+}
+
+/// A Mega file key is 256 bits: the AES key is both halves XORed together,
+/// followed by the CTR nonce and the expected meta-MAC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileKey {
+    aes_key: [u8; 16],
+    iv: [u8; 8],
+    meta_mac: [u8; 8],
+}
+
+impl FileKey {
+    fn from_raw(raw: &[u8; 32]) -> Self {
+        let mut aes_key = [0u8; 16];
+        for (i, byte) in aes_key.iter_mut().enumerate() {
+            *byte = raw[i] ^ raw[i + 16];
+        }
+        Self {
+            aes_key,
+            iv: raw[16..24].try_into().unwrap(),
+            meta_mac: raw[24..32].try_into().unwrap(),
+        }
+    }
+
+    fn cipher(&self) -> Aes128 {
+        Aes128::new(&self.aes_key.into())
+    }
+
+    fn content_cipher(&self) -> Aes128Ctr {
+        let mut nonce = [0u8; 16];
+        nonce[..8].copy_from_slice(&self.iv);
+        Aes128Ctr::new(&self.aes_key.into(), &nonce.into())
+    }
+
+    /// Attributes are AES-CBC encrypted with a zero IV: `MEGA{"n":"<name>"}` padded with NULs.
+    fn decrypt_attributes(&self, encrypted: &str) -> Option<String> {
+        let data = URL_SAFE_NO_PAD.decode(encrypted).ok()?;
+        if data.is_empty() || data.len() % 16 != 0 {
+            return None;
+        }
+
+        let cipher = self.cipher();
+        let mut previous = [0u8; 16];
+        let mut plain = Vec::with_capacity(data.len());
+        for block in data.as_chunks::<16>().0 {
+            let mut decrypted = aes::Block::from(*block);
+            cipher.decrypt_block(&mut decrypted);
+            plain.extend(decrypted.iter().zip(previous).map(|(a, b)| a ^ b));
+            previous = *block;
+        }
+
+        let json = std::str::from_utf8(&plain)
+            .ok()?
+            .trim_end_matches('\0')
+            .strip_prefix("MEGA")?;
+
+        #[derive(Deserialize)]
+        struct Attributes {
+            n: String,
+        }
+        serde_json::from_str::<Attributes>(json).ok().map(|a| a.n)
+    }
+}
+
+/// Incremental Mega file MAC: a CBC-MAC per chunk, folded into a CBC-MAC over chunk MACs.
+/// Chunks are 128 KiB, 256 KiB, ... up to 1 MiB, then 1 MiB each.
+struct FileMac {
+    cipher: Aes128,
+    chunk_iv: [u8; 16],
+    file_mac: [u8; 16],
+    chunk_mac: [u8; 16],
+    chunk_index: u64,
+    chunk_remaining: u64,
+    block: [u8; 16],
+    block_len: usize,
+}
+
+impl FileMac {
+    fn new(key: &FileKey) -> Self {
+        let mut chunk_iv = [0u8; 16];
+        chunk_iv[..8].copy_from_slice(&key.iv);
+        chunk_iv[8..].copy_from_slice(&key.iv);
+        Self {
+            cipher: key.cipher(),
+            chunk_iv,
+            file_mac: [0u8; 16],
+            chunk_mac: chunk_iv,
+            chunk_index: 0,
+            chunk_remaining: Self::chunk_size(0),
+            block: [0u8; 16],
+            block_len: 0,
+        }
+    }
+
+    fn chunk_size(index: u64) -> u64 {
+        (index + 1).min(8) * 128 * 1024
+    }
+
+    fn encrypt_xor(&self, state: &mut [u8; 16], data: &[u8; 16]) {
+        for (s, d) in state.iter_mut().zip(data) {
+            *s ^= d;
+        }
+        let mut block = aes::Block::from(*state);
+        self.cipher.encrypt_block(&mut block);
+        state.copy_from_slice(&block);
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = (16 - self.block_len)
+                .min(data.len())
+                .min(self.chunk_remaining as usize);
+            self.block[self.block_len..self.block_len + take].copy_from_slice(&data[..take]);
+            self.block_len += take;
+            self.chunk_remaining -= take as u64;
+            data = &data[take..];
+
+            if self.block_len == 16 {
+                self.flush_block();
+            }
+            if self.chunk_remaining == 0 {
+                self.finish_chunk();
+            }
+        }
+    }
+
+    fn flush_block(&mut self) {
+        let mut block = [0u8; 16];
+        block[..self.block_len].copy_from_slice(&self.block[..self.block_len]);
+        let mut chunk_mac = self.chunk_mac;
+        self.encrypt_xor(&mut chunk_mac, &block);
+        self.chunk_mac = chunk_mac;
+        self.block_len = 0;
+    }
+
+    fn finish_chunk(&mut self) {
+        if self.block_len > 0 {
+            self.flush_block();
+        }
+        let (mut file_mac, chunk_mac) = (self.file_mac, self.chunk_mac);
+        self.encrypt_xor(&mut file_mac, &chunk_mac);
+        self.file_mac = file_mac;
+
+        self.chunk_mac = self.chunk_iv;
+        self.chunk_index += 1;
+        self.chunk_remaining = Self::chunk_size(self.chunk_index);
+    }
+
+    fn finalize(mut self, total_bytes: u64) -> [u8; 8] {
+        // a trailing partial chunk (or an empty file) still contributes one chunk MAC
+        if self.chunk_remaining != Self::chunk_size(self.chunk_index) || total_bytes == 0 {
+            self.finish_chunk();
+        }
+        let m = self.file_mac;
+        let mut condensed = [0u8; 8];
+        for i in 0..4 {
+            condensed[i] = m[i] ^ m[i + 4];
+            condensed[i + 4] = m[i + 8] ^ m[i + 12];
+        }
+        condensed
+    }
+}
+
+#[derive(Deserialize)]
+struct DownloadInfo {
+    /// Size in bytes
+    s: u64,
+    /// Encrypted attributes
+    at: String,
+    /// Temporary download URL
+    g: String,
+}
+
+fn api_error(code: i64) -> DownloadError {
+    let message = match code {
+        -2 => "invalid arguments",
+        -3 => "request failed, retry later",
+        -4 => "too many requests",
+        -9 => "file not found",
+        -11 => "access denied",
+        -16 => "file has been taken down",
+        -17 => "transfer quota exceeded",
+        -18 => "resource temporarily unavailable",
+        _ => "unknown error",
+    };
+    let error = anyhow::anyhow!("Mega API error {}: {}", code, message);
+    match code {
+        -3 | -4 | -17 | -18 => DownloadError::RateLimited {
+            retry_after: None,
+            message: message.to_string(),
+            source: error,
+        },
+        -9 | -11 | -16 => DownloadError::FileNotAccessible(error),
+        _ => DownloadError::ServiceError(error),
+    }
+}
+
+static REQUEST_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+async fn request_download_info(
+    file_id: &MegaFileId,
+    config: &SecurityConfig,
+) -> Result<DownloadInfo, DownloadError> {
+    let client = build_client(config)
+        .context("Failed to build HTTP client")
+        .map_err(DownloadError::ServiceError)?;
+    let sequence = REQUEST_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    let response = client
+        .post(format!("{}?id={}", API_URL, sequence))
+        .json(&serde_json::json!([{ "a": "g", "g": 1, "ssl": 2, "p": file_id.handle }]))
+        .send()
+        .await
+        .context("Failed to send request to Mega")
+        .map_err(DownloadError::ServiceError)?;
+
+    if !response.status().is_success() {
+        return Err(DownloadError::from_response(
+            &response,
+            "Mega API",
+            ServiceAccess::Public,
+        ));
+    }
+
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .context("Failed to parse Mega API response")
+        .map_err(DownloadError::ServiceError)?;
+
+    // errors come back either as a bare number or as the single array element
+    let result = match body {
+        serde_json::Value::Array(mut results) if !results.is_empty() => results.swap_remove(0),
+        other => other,
+    };
+    if let Some(code) = result.as_i64() {
+        return Err(api_error(code));
+    }
+
+    serde_json::from_value(result)
+        .context("Unexpected Mega API response")
+        .map_err(DownloadError::ServiceError)
+}
+
+async fn get_file_info(
+    file_id: &MegaFileId,
+    config: &SecurityConfig,
+) -> Result<FileMeta, DownloadError> {
+    let key = file_id.file_key()?;
+    let info = request_download_info(file_id, config).await?;
+    let name = key.decrypt_attributes(&info.at).ok_or_else(|| {
+        DownloadError::FileNotAccessible(anyhow::anyhow!(
+            "Failed to decrypt Mega file attributes; the link key may be wrong"
+        ))
+    })?;
+
+    Ok(FileMeta { name, size: info.s })
+}
+
+async fn download_file(
+    file_id: &MegaFileId,
+    dest: &Path,
+    config: &SecurityConfig,
+) -> Result<DownloadSummary, DownloadError> {
+    let key = file_id.file_key()?;
+    let info = request_download_info(file_id, config).await?;
+
+    let client = build_client(config)
+        .context("Failed to build HTTP client")
+        .map_err(DownloadError::ServiceError)?;
+    let response = client
+        .get(&info.g)
+        .send()
+        .await
+        .context("Failed to send request to Mega")
+        .map_err(DownloadError::ServiceError)?;
+
+    if response.status().as_u16() == 509 {
+        return Err(DownloadError::RateLimited {
+            retry_after: None,
+            message: "Mega transfer quota exceeded".to_string(),
+            source: anyhow::anyhow!("HTTP 509 from Mega"),
+        });
+    }
+    if !response.status().is_success() {
+        return Err(DownloadError::from_response(
+            &response,
+            "Mega",
+            ServiceAccess::Public,
+        ));
+    }
+
+    DownloadSink::check_content_length(&response, config)?;
+    let mut sink = DownloadSink::create(dest, config).await?;
+    let mut stream = response.bytes_stream();
+    let mut cipher = key.content_cipher();
+    let mut mac = FileMac::new(&key);
+
+    while let Some(chunk) = stream.next().await {
+        let mut bytes = chunk
+            .context("Failed to read response stream")
+            .map_err(DownloadError::ServiceError)?
+            .to_vec();
+        cipher.apply_keystream(&mut bytes);
+        mac.update(&bytes);
+        sink.write(&bytes).await?;
+    }
+
+    let summary = sink.finish().await?;
+    if mac.finalize(summary.size) != key.meta_mac {
+        return Err(DownloadError::SecurityViolation(anyhow::anyhow!(
+            "Mega file MAC mismatch; download is corrupt or the key is wrong"
+        )));
+    }
+
+    Ok(summary)
+}
+
+pub struct MegaService;
+
+impl MegaService {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for MegaService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl FileService for MegaService {
+    type FileId = MegaFileId;
+
+    fn service_name() -> &'static str {
+        "mega"
+    }
+
+    fn detect_link(input: &str) -> Option<Self::FileId> {
+        MEGA_URL_PATTERNS.iter().find_map(|pattern| {
+            pattern
+                .captures(input)
+                .map(|caps| MegaFileId::new(caps[1].to_string(), caps[2].to_string()))
+        })
+    }
+
+    async fn get_file_info(
+        &mut self,
+        file_id: &Self::FileId,
+        config: &SecurityConfig,
+    ) -> Result<FileMeta, DownloadError> {
+        get_file_info(file_id, config).await
+    }
+
+    async fn download(
+        &mut self,
+        file_id: &Self::FileId,
+        dest: &Path,
+        config: &SecurityConfig,
+    ) -> Result<DownloadSummary, DownloadError> {
+        download_file(file_id, dest, config).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HANDLE: &str = "AbCd1234";
+    const KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8";
+
+    fn test_key() -> FileKey {
+        let raw: [u8; 32] = std::array::from_fn(|i| (i * 7 + 3) as u8);
+        FileKey::from_raw(&raw)
+    }
+
+    fn encrypt_attributes(key: &FileKey, name: &str) -> String {
+        let mut plain = format!("MEGA{{\"n\":\"{}\"}}", name).into_bytes();
+        plain.resize(plain.len().div_ceil(16) * 16, 0);
+
+        let cipher = key.cipher();
+        let mut previous = [0u8; 16];
+        let mut encrypted = Vec::new();
+        for block in plain.as_chunks::<16>().0 {
+            let mut state = aes::Block::default();
+            for (i, byte) in state.iter_mut().enumerate() {
+                *byte = block[i] ^ previous[i];
+            }
+            cipher.encrypt_block(&mut state);
+            previous.copy_from_slice(&state);
+            encrypted.extend_from_slice(&state);
+        }
+        URL_SAFE_NO_PAD.encode(encrypted)
+    }
+
+    /// Straightforward whole-buffer MAC, to check the incremental version against.
+    fn reference_mac(key: &FileKey, data: &[u8]) -> [u8; 8] {
+        let cipher = key.cipher();
+        let encrypt_xor = |state: &mut [u8; 16], block: &[u8]| {
+            for (s, b) in state.iter_mut().zip(block) {
+                *s ^= b;
+            }
+            let mut block = aes::Block::from(*state);
+            cipher.encrypt_block(&mut block);
+            state.copy_from_slice(&block);
+        };
+
+        let mut chunks = Vec::new();
+        let (mut offset, mut index) = (0usize, 0u64);
+        while offset < data.len() || chunks.is_empty() {
+            let end = (offset + FileMac::chunk_size(index) as usize).min(data.len());
+            chunks.push(&data[offset..end]);
+            offset = end;
+            index += 1;
+        }
+
+        let mut file_mac = [0u8; 16];
+        for chunk in chunks {
+            let mut chunk_mac = [0u8; 16];
+            chunk_mac[..8].copy_from_slice(&key.iv);
+            chunk_mac[8..].copy_from_slice(&key.iv);
+            for block in chunk.chunks(16) {
+                let mut padded = [0u8; 16];
+                padded[..block.len()].copy_from_slice(block);
+                encrypt_xor(&mut chunk_mac, &padded);
+            }
+            encrypt_xor(&mut file_mac, &chunk_mac);
+        }
+
+        let mut condensed = [0u8; 8];
+        for i in 0..4 {
+            condensed[i] = file_mac[i] ^ file_mac[i + 4];
+            condensed[i + 4] = file_mac[i + 8] ^ file_mac[i + 12];
+        }
+        condensed
+    }
+
+    #[test]
+    fn test_detect_link() {
+        let expected = Some(MegaFileId::new(HANDLE.to_string(), KEY.to_string()));
+        let new_url = format!("https://mega.nz/file/{}#{}", HANDLE, KEY);
+        let old_url = format!("https://mega.nz/#!{}!{}", HANDLE, KEY);
+        let co_nz_url = format!("https://mega.co.nz/#!{}!{}", HANDLE, KEY);
+
+        let test_cases = [
+            (new_url.clone(), expected.clone()),
+            (old_url, expected.clone()),
+            (co_nz_url, expected.clone()),
+            (format!("Save: {} gl", new_url), expected.clone()),
+            (format!("https://mega.nz/file/{}", HANDLE), None),
+            (format!("https://mega.nz/folder/{}#{}", HANDLE, KEY), None),
+            ("https://example.com/not-a-mega-link".to_string(), None),
+            ("just some text".to_string(), None),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(MegaService::detect_link(&input), expected, "input: {input}");
+        }
+    }
+
+    #[test]
+    fn test_display_hides_key() {
+        let file_id = MegaFileId::new(HANDLE.to_string(), KEY.to_string());
+        assert_eq!(file_id.to_string(), "https://mega.nz/file/AbCd1234");
+    }
+
+    #[test]
+    fn test_file_key_from_raw() {
+        let raw: [u8; 32] = std::array::from_fn(|i| i as u8);
+        let key = FileKey::from_raw(&raw);
+        assert_eq!(key.aes_key, [16u8; 16]);
+        assert_eq!(key.iv, [16, 17, 18, 19, 20, 21, 22, 23]);
+        assert_eq!(key.meta_mac, [24, 25, 26, 27, 28, 29, 30, 31]);
+
+        let file_id = MegaFileId::new(HANDLE.to_string(), URL_SAFE_NO_PAD.encode(raw));
+        assert_eq!(file_id.file_key().unwrap(), key);
+
+        let bad = MegaFileId::new(HANDLE.to_string(), "tooshort".to_string());
+        assert!(matches!(
+            bad.file_key(),
+            Err(DownloadError::FileNotAccessible(_))
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_attributes() {
+        let key = test_key();
+        let encrypted = encrypt_attributes(&key, "my run.zip");
+        assert_eq!(
+            key.decrypt_attributes(&encrypted).as_deref(),
+            Some("my run.zip")
+        );
+
+        let wrong_key = FileKey::from_raw(&[1u8; 32]);
+        assert_eq!(wrong_key.decrypt_attributes(&encrypted), None);
+    }
+
+    #[test]
+    fn test_content_cipher_roundtrip_across_chunks() {
+        let key = test_key();
+        let plain: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut encrypted = plain.clone();
+        key.content_cipher().apply_keystream(&mut encrypted);
+        assert_ne!(encrypted, plain);
+
+        let mut cipher = key.content_cipher();
+        let mut decrypted = Vec::new();
+        for chunk in encrypted.chunks(37) {
+            let mut chunk = chunk.to_vec();
+            cipher.apply_keystream(&mut chunk);
+            decrypted.extend(chunk);
+        }
+        assert_eq!(decrypted, plain);
+    }
+
+    #[test]
+    fn test_file_mac_matches_reference() {
+        let key = test_key();
+        let sizes = [0, 5, 16, 131_072, 131_073, 400_000, 3 * 1024 * 1024 + 17];
+
+        for size in sizes {
+            let data: Vec<u8> = (0..size).map(|i| (i % 253) as u8).collect();
+            let mut mac = FileMac::new(&key);
+            for chunk in data.chunks(10_007) {
+                mac.update(chunk);
+            }
+            assert_eq!(
+                mac.finalize(size as u64),
+                reference_mac(&key, &data),
+                "size: {size}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_api_error_classification() {
+        assert!(matches!(api_error(-9), DownloadError::FileNotAccessible(_)));
+        assert!(matches!(api_error(-4), DownloadError::RateLimited { .. }));
+        assert!(matches!(api_error(-2), DownloadError::ServiceError(_)));
+    }
+}
@@ -4,10 +4,15 @@ use std::path::Path;
 
 pub mod dropbox;
 pub mod gdrive;
+pub mod google_auth;
+pub mod mega;
+pub mod onedrive;
+pub mod s3;
 pub mod speedrun;
 
 use crate::DownloadError;
 use crate::security::SecurityConfig;
+use crate::sink::DownloadSummary;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FileMeta {
@@ -33,14 +38,17 @@ pub trait FileService: Send + Sync {
         file_id: &Self::FileId,
         dest: &Path,
         config: &SecurityConfig,
-    ) -> Result<(), DownloadError>;
+    ) -> Result<DownloadSummary, DownloadError>;
 }
 
 #[async_trait]
 pub trait FileDownloadHandle: Send + Sync + Display {
     async fn get_file_info(&mut self, config: &SecurityConfig) -> Result<FileMeta, DownloadError>;
-    async fn download(&mut self, dest: &Path, config: &SecurityConfig)
-    -> Result<(), DownloadError>;
+    async fn download(
+        &mut self,
+        dest: &Path,
+        config: &SecurityConfig,
+    ) -> Result<DownloadSummary, DownloadError>;
     fn service_name(&self) -> &str;
 }
 
@@ -64,7 +72,7 @@ impl<'a, T: FileService> FileDownloadHandle for FileIdWrapper<'a, T> {
         &mut self,
         dest: &Path,
         config: &SecurityConfig,
-    ) -> Result<(), DownloadError> {
+    ) -> Result<DownloadSummary, DownloadError> {
         self.service.download(&self.file_id, dest, config).await
     }
     fn service_name(&self) -> &str {
@@ -112,8 +120,11 @@ pub mod test_util {
             _file_id: &Self::FileId,
             _dest: &Path,
             _config: &SecurityConfig,
-        ) -> Result<(), DownloadError> {
-            Ok(())
+        ) -> Result<DownloadSummary, DownloadError> {
+            Ok(DownloadSummary {
+                size: 0,
+                sha256: [0; 32],
+            })
         }
 
         async fn get_file_info(
@@ -0,0 +1,395 @@
+use crate::security::SecurityConfig;
+use crate::services::{FileMeta, FileService};
+use crate::sink::{DownloadSink, DownloadSummary};
+use crate::{DownloadError, ServiceAccess};
+use anyhow::Context;
+use async_trait::async_trait;
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use regex::Regex;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::LazyLock;
+
+const SHARES_API: &str = "https://api.onedrive.com/v1.0/shares";
+
+fn build_client(config: &SecurityConfig) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .connect_timeout(config.connect_timeout)
+        .timeout(config.download_timeout)
+        .redirect(reqwest::redirect::Policy::limited(config.max_redirects))
+        .build()
+}
+
+static ONEDRIVE_URL_PATTERNS: LazyLock<[Regex; 3]> = LazyLock::new(|| {
+    [
+        Regex::new(r"https://1drv\.ms/[a-z](?:/[a-z])?/[^\s]+").unwrap(),
+        Regex::new(r"https://onedrive\.live\.com/[^\s]*\?[^\s#]+").unwrap(),
+        Regex::new(r"https://[a-z0-9-]+\.sharepoint\.com/:[a-z]:/[^\s]+").unwrap(),
+    ]
+});
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OneDriveFileId {
+    url: String,
+    /// SharePoint links are downloaded directly; other links through the shares API.
+    sharepoint: bool,
+}
+
+impl OneDriveFileId {
+    pub fn new(url: String) -> Self {
+        let sharepoint = url.contains(".sharepoint.com/");
+        Self { url, sharepoint }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Sharing token for the OneDrive shares API: `u!` + unpadded base64url of the link.
+    fn share_token(&self) -> String {
+        format!("u!{}", URL_SAFE_NO_PAD.encode(&self.url))
+    }
+
+    fn metadata_url(&self, shares_api: &str) -> String {
+        format!("{}/{}/root", shares_api, self.share_token())
+    }
+
+    fn to_direct_download_url(&self, shares_api: &str) -> String {
+        if self.sharepoint {
+            let url = self.url.split('#').next().unwrap_or(&self.url);
+            let separator = if url.contains('?') { '&' } else { '?' };
+            format!("{}{}download=1", url, separator)
+        } else {
+            format!("{}/{}/root/content", shares_api, self.share_token())
+        }
+    }
+}
+
+impl std::fmt::Display for OneDriveFileId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+#[derive(Deserialize)]
+struct DriveItem {
+    name: String,
+    size: u64,
+}
+
+async fn send(
+    request: reqwest::RequestBuilder,
+    service: &str,
+) -> Result<reqwest::Response, DownloadError> {
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("Failed to send request to {}", service))
+        .map_err(DownloadError::ServiceError)?;
+
+    if !response.status().is_success() {
+        return Err(DownloadError::from_response(
+            &response,
+            service,
+            ServiceAccess::Public,
+        ));
+    }
+    Ok(response)
+}
+
+async fn get_file_info(
+    file_id: &OneDriveFileId,
+    shares_api: &str,
+    config: &SecurityConfig,
+) -> Result<FileMeta, DownloadError> {
+    let client = build_client(config)
+        .context("Failed to build HTTP client")
+        .map_err(DownloadError::ServiceError)?;
+
+    if !file_id.sharepoint {
+        let item: DriveItem = send(client.get(file_id.metadata_url(shares_api)), "OneDrive")
+            .await?
+            .json()
+            .await
+            .context("Failed to parse OneDrive item metadata")
+            .map_err(DownloadError::ServiceError)?;
+        return Ok(FileMeta {
+            name: item.name,
+            size: item.size,
+        });
+    }
+
+    let response = send(
+        client.head(file_id.to_direct_download_url(shares_api)),
+        "SharePoint",
+    )
+    .await?;
+    let headers = response.headers();
+
+    let name = headers
+        .get("content-disposition")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|disposition| {
+            disposition
+                .split("filename=")
+                .nth(1)
+                .and_then(|s| s.split(';').next())
+                .map(|s| s.trim_matches('"'))
+        })
+        .unwrap_or("unknown.zip")
+        .to_string();
+
+    let size = headers
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+
+    Ok(FileMeta { name, size })
+}
+
+async fn download_file(
+    file_id: &OneDriveFileId,
+    shares_api: &str,
+    dest: &Path,
+    config: &SecurityConfig,
+) -> Result<DownloadSummary, DownloadError> {
+    let client = build_client(config)
+        .context("Failed to build HTTP client")
+        .map_err(DownloadError::ServiceError)?;
+    let response = send(
+        client.get(file_id.to_direct_download_url(shares_api)),
+        "OneDrive",
+    )
+    .await?;
+
+    DownloadSink::create(dest, config)
+        .await?
+        .write_response(response)
+        .await
+}
+
+pub struct OneDriveService {
+    shares_api: String,
+}
+
+impl OneDriveService {
+    pub fn new() -> Self {
+        Self::with_shares_api(SHARES_API)
+    }
+
+    /// Resolves share links through another shares API endpoint.
+    pub fn with_shares_api(shares_api: impl Into<String>) -> Self {
+        Self {
+            shares_api: shares_api.into(),
+        }
+    }
+}
+
+impl Default for OneDriveService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl FileService for OneDriveService {
+    type FileId = OneDriveFileId;
+
+    fn service_name() -> &'static str {
+        "onedrive"
+    }
+
+    fn detect_link(input: &str) -> Option<Self::FileId> {
+        ONEDRIVE_URL_PATTERNS.iter().find_map(|pattern| {
+            pattern
+                .find(input)
+                .map(|m| OneDriveFileId::new(m.as_str().to_string()))
+        })
+    }
+
+    async fn get_file_info(
+        &mut self,
+        file_id: &Self::FileId,
+        config: &SecurityConfig,
+    ) -> Result<FileMeta, DownloadError> {
+        get_file_info(file_id, &self.shares_api, config).await
+    }
+
+    async fn download(
+        &mut self,
+        file_id: &Self::FileId,
+        dest: &Path,
+        config: &SecurityConfig,
+    ) -> Result<DownloadSummary, DownloadError> {
+        download_file(file_id, &self.shares_api, dest, config).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const ONEDRIVE_URL: &str = "https://1drv.ms/u/s!AkVl3ZiQ2lOZgRd4qYJHwoXmEXAMPLE?e=abc123";
+    const SHAREPOINT_URL: &str =
+        "https://contoso-my.sharepoint.com/:u:/g/personal/runner_contoso_com/EaBcDeFgHiJ?e=xYz123";
+
+    #[test]
+    fn test_detect_link() {
+        const LIVE_URL: &str =
+            "https://onedrive.live.com/?authkey=%21ABC&id=1234%21567&cid=89ABCDEF";
+        const NEW_STYLE_URL: &str = "https://1drv.ms/u/c/0123456789abcdef/EXAMPLEabc?e=Q1w2E3";
+        let test_cases = [
+            (
+                ONEDRIVE_URL,
+                Some(OneDriveFileId::new(ONEDRIVE_URL.to_string())),
+            ),
+            (
+                NEW_STYLE_URL,
+                Some(OneDriveFileId::new(NEW_STYLE_URL.to_string())),
+            ),
+            (LIVE_URL, Some(OneDriveFileId::new(LIVE_URL.to_string()))),
+            (
+                SHAREPOINT_URL,
+                Some(OneDriveFileId::new(SHAREPOINT_URL.to_string())),
+            ),
+            (
+                &format!("Save file: {} good luck", ONEDRIVE_URL),
+                Some(OneDriveFileId::new(ONEDRIVE_URL.to_string())),
+            ),
+            ("https://onedrive.live.com/", None),
+            ("https://contoso.sharepoint.com/sites/team", None),
+            ("https://example.com/not-a-onedrive-link", None),
+            ("just some text", None),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(OneDriveService::detect_link(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_direct_download_url() {
+        let onedrive = OneDriveFileId::new("https://1drv.ms/u/s!AbC?e=1".to_string());
+        assert_eq!(
+            onedrive.to_direct_download_url(SHARES_API),
+            "https://api.onedrive.com/v1.0/shares/u!aHR0cHM6Ly8xZHJ2Lm1zL3UvcyFBYkM_ZT0x/root/content"
+        );
+        assert_eq!(
+            onedrive.metadata_url(SHARES_API),
+            "https://api.onedrive.com/v1.0/shares/u!aHR0cHM6Ly8xZHJ2Lm1zL3UvcyFBYkM_ZT0x/root"
+        );
+
+        let sharepoint = OneDriveFileId::new(SHAREPOINT_URL.to_string());
+        assert_eq!(
+            sharepoint.to_direct_download_url(SHARES_API),
+            format!("{}&download=1", SHAREPOINT_URL)
+        );
+
+        let sharepoint_no_query =
+            OneDriveFileId::new("https://contoso.sharepoint.com/:u:/s/team/EaBcDeF".to_string());
+        assert_eq!(
+            sharepoint_no_query.to_direct_download_url(SHARES_API),
+            "https://contoso.sharepoint.com/:u:/s/team/EaBcDeF?download=1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_share_token_resolution() {
+        let server = MockServer::start().await;
+        let file_id = OneDriveFileId::new("https://1drv.ms/u/s!AbC?e=1".to_string());
+        let share = format!("/{}/root", file_id.share_token());
+        Mock::given(method("GET"))
+            .and(path(share.as_str()))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "name": "run.zip", "size": 100 })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("{}/content", share)))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![7u8; 100]))
+            .mount(&server)
+            .await;
+        let mut service = OneDriveService::with_shares_api(server.uri());
+        let config = SecurityConfig::default();
+
+        let info = service.get_file_info(&file_id, &config).await.unwrap();
+        assert_eq!(info.name, "run.zip");
+        assert_eq!(info.size, 100);
+
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("run.zip");
+        let summary = service.download(&file_id, &dest, &config).await.unwrap();
+        assert_eq!(summary.size, 100);
+    }
+
+    #[tokio::test]
+    async fn test_sharepoint_download() {
+        let server = MockServer::start().await;
+        for http_method in ["HEAD", "GET"] {
+            Mock::given(method(http_method))
+                .and(path("/:u:/g/personal/runner/EaBcDeF"))
+                .and(query_param("e", "xYz"))
+                .and(query_param("download", "1"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header(
+                            "content-disposition",
+                            "attachment; filename=\"run.zip\"; filename*=UTF-8''run.zip",
+                        )
+                        .set_body_bytes(vec![7u8; 100]),
+                )
+                .mount(&server)
+                .await;
+        }
+        let file_id = OneDriveFileId {
+            url: format!("{}/:u:/g/personal/runner/EaBcDeF?e=xYz#view", server.uri()),
+            sharepoint: true,
+        };
+        let mut service = OneDriveService::new();
+        let config = SecurityConfig::default();
+
+        let info = service.get_file_info(&file_id, &config).await.unwrap();
+        assert_eq!(info.name, "run.zip");
+
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("run.zip");
+        let summary = service.download(&file_id, &dest, &config).await.unwrap();
+        assert_eq!(summary.size, 100);
+    }
+
+    #[tokio::test]
+    async fn test_error_classification() {
+        let server = MockServer::start().await;
+        let file_id = |name: &str| OneDriveFileId::new(format!("https://1drv.ms/u/s!{}", name));
+        for (name, status) in [
+            ("private", 401),
+            ("blocked", 403),
+            ("deleted", 404),
+            ("busy", 429),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(format!("/{}/root", file_id(name).share_token())))
+                .respond_with(ResponseTemplate::new(status))
+                .mount(&server)
+                .await;
+        }
+        let mut service = OneDriveService::with_shares_api(server.uri());
+        let config = SecurityConfig::default();
+
+        for name in ["private", "blocked", "deleted"] {
+            let result = service.get_file_info(&file_id(name), &config).await;
+            assert!(
+                matches!(result, Err(DownloadError::FileNotAccessible(_))),
+                "{name}: {result:?}"
+            );
+        }
+        let result = service.get_file_info(&file_id("busy"), &config).await;
+        assert!(matches!(result, Err(DownloadError::RateLimited { .. })));
+    }
+}
@@ -0,0 +1,200 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+use crate::DownloadError;
+use crate::security::SecurityConfig;
+
+/// Size and SHA-256 of a completed download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadSummary {
+    pub size: u64,
+    pub sha256: [u8; 32],
+}
+
+impl DownloadSummary {
+    pub fn sha256_hex(&self) -> String {
+        hex::encode(self.sha256)
+    }
+
+    /// Summarizes a file that was written without a [`DownloadSink`].
+    pub fn of_file(path: &Path) -> std::io::Result<Self> {
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = [0u8; 64 * 1024];
+        let mut size = 0u64;
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            size += read as u64;
+        }
+        Ok(Self {
+            size,
+            sha256: hasher.finalize().into(),
+        })
+    }
+}
+
+pub(crate) fn too_large(config: &SecurityConfig) -> DownloadError {
+    DownloadError::SecurityViolation(anyhow::anyhow!(
+        "Download exceeded maximum size of {} bytes",
+        config.max_file_size
+    ))
+}
+
+/// Destination of a download. Enforces [`SecurityConfig::max_file_size`] and the download
+/// throttle as bytes arrive, and hashes them on the way to disk.
+///
+/// If the limit is exceeded the partial file is removed and nothing more is written.
+pub struct DownloadSink<'a> {
+    file: tokio::fs::File,
+    dest: PathBuf,
+    config: &'a SecurityConfig,
+    size: u64,
+    hasher: Sha256,
+}
+
+impl<'a> DownloadSink<'a> {
+    pub async fn create(dest: &Path, config: &'a SecurityConfig) -> Result<Self, DownloadError> {
+        let file = tokio::fs::File::create(dest)
+            .await
+            .map_err(DownloadError::IoError)?;
+        Ok(Self {
+            file,
+            dest: dest.to_path_buf(),
+            config,
+            size: 0,
+            hasher: Sha256::new(),
+        })
+    }
+
+    /// Fails early if the response declares a body larger than allowed.
+    pub fn check_content_length(
+        response: &reqwest::Response,
+        config: &SecurityConfig,
+    ) -> Result<(), DownloadError> {
+        match response.content_length() {
+            Some(length) if length > config.max_file_size => Err(too_large(config)),
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn write(&mut self, bytes: &[u8]) -> Result<(), DownloadError> {
+        let size = self.size + bytes.len() as u64;
+        if size > self.config.max_file_size {
+            return Err(self.abort().await);
+        }
+        self.config.throttle(bytes.len()).await;
+        self.file
+            .write_all(bytes)
+            .await
+            .map_err(DownloadError::IoError)?;
+        self.hasher.update(bytes);
+        self.size = size;
+        Ok(())
+    }
+
+    /// Streams the whole response body into the sink.
+    pub async fn write_response(
+        mut self,
+        response: reqwest::Response,
+    ) -> Result<DownloadSummary, DownloadError> {
+        if Self::check_content_length(&response, self.config).is_err() {
+            return Err(self.abort().await);
+        }
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let bytes = chunk
+                .context("Failed to read response stream")
+                .map_err(DownloadError::ServiceError)?;
+            self.write(&bytes).await?;
+        }
+        self.finish().await
+    }
+
+    async fn abort(&mut self) -> DownloadError {
+        let _ = tokio::fs::remove_file(&self.dest).await;
+        too_large(self.config)
+    }
+
+    pub async fn finish(mut self) -> Result<DownloadSummary, DownloadError> {
+        self.file.flush().await.map_err(DownloadError::IoError)?;
+        Ok(DownloadSummary {
+            size: self.size,
+            sha256: self.hasher.finalize().into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_SHA256: &str = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+    #[tokio::test]
+    async fn test_sink_hashes_and_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("out.zip");
+        let config = SecurityConfig::default();
+
+        let mut sink = DownloadSink::create(&dest, &config).await.unwrap();
+        sink.write(b"hello ").await.unwrap();
+        sink.write(b"world").await.unwrap();
+        let summary = sink.finish().await.unwrap();
+
+        assert_eq!(summary.size, 11);
+        assert_eq!(summary.sha256_hex(), HELLO_SHA256);
+        assert_eq!(std::fs::read(&dest).unwrap(), b"hello world");
+        assert_eq!(DownloadSummary::of_file(&dest).unwrap(), summary);
+    }
+
+    #[tokio::test]
+    async fn test_sink_aborts_over_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("out.zip");
+        let config = SecurityConfig {
+            max_file_size: 8,
+            ..Default::default()
+        };
+
+        let mut sink = DownloadSink::create(&dest, &config).await.unwrap();
+        sink.write(b"12345").await.unwrap();
+        let result = sink.write(b"6789").await;
+
+        assert!(matches!(result, Err(DownloadError::SecurityViolation(_))));
+        assert!(!dest.exists());
+    }
+
+    #[tokio::test]
+    async fn test_write_response_checks_content_length() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![0u8; 100]))
+            .mount(&server)
+            .await;
+        let response = reqwest::get(server.uri()).await.unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("out.zip");
+        let config = SecurityConfig {
+            max_file_size: 50,
+            ..Default::default()
+        };
+        let sink = DownloadSink::create(&dest, &config).await.unwrap();
+
+        let result = sink.write_response(response).await;
+        assert!(matches!(result, Err(DownloadError::SecurityViolation(_))));
+        assert!(!dest.exists());
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+use crate::DownloadError;
+use crate::security::SecurityConfig;
+use crate::services::{FileMeta, FileService};
+use crate::sink::DownloadSummary;
+
+/// Key in [`DownloadThrottles`] for services without their own entry.
+pub const DEFAULT_THROTTLE_KEY: &str = "default";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct ThrottleConfig {
+    #[serde(default)]
+    pub max_bytes_per_second: Option<u64>,
+    #[serde(default)]
+    pub max_concurrent_downloads: Option<usize>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token bucket and download slots shared by every download through the same throttle.
+#[derive(Debug)]
+pub struct DownloadThrottle {
+    max_bytes_per_second: Option<u64>,
+    bucket: Mutex<Bucket>,
+    slots: Option<Arc<Semaphore>>,
+}
+
+impl DownloadThrottle {
+    pub fn new(config: &ThrottleConfig) -> Self {
+        let rate = config.max_bytes_per_second.filter(|&rate| rate > 0);
+        Self {
+            max_bytes_per_second: rate,
+            bucket: Mutex::new(Bucket {
+                tokens: rate.unwrap_or(0) as f64,
+                last_refill: Instant::now(),
+            }),
+            slots: config
+                .max_concurrent_downloads
+                .map(|n| Arc::new(Semaphore::new(n.max(1)))),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(&ThrottleConfig::default())
+    }
+
+    pub fn max_bytes_per_second(&self) -> Option<u64> {
+        self.max_bytes_per_second
+    }
+
+    async fn acquire_slot(&self) -> Option<OwnedSemaphorePermit> {
+        let slots = self.slots.clone()?;
+        slots.acquire_owned().await.ok()
+    }
+
+    /// Waits until `bytes` more bytes may be written. Allows a burst of up to one second's worth.
+    pub async fn consume(&self, bytes: usize) {
+        let Some(rate) = self.max_bytes_per_second else {
+            return;
+        };
+        let rate = rate as f64;
+
+        // holding the lock while sleeping makes concurrent downloads queue up for bandwidth
+        let mut bucket = self.bucket.lock().await;
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(rate) - bytes as f64;
+        bucket.last_refill = now;
+
+        if bucket.tokens < 0.0 {
+            let wait = Duration::from_secs_f64(-bucket.tokens / rate);
+            tokio::time::sleep(wait).await;
+            bucket.tokens = 0.0;
+            bucket.last_refill = Instant::now();
+        }
+    }
+}
+
+impl SecurityConfig {
+    pub(crate) async fn throttle(&self, bytes: usize) {
+        if let Some(throttle) = &self.throttle {
+            throttle.consume(bytes).await;
+        }
+    }
+}
+
+/// A [`FileService`] whose downloads go through a [`DownloadThrottle`].
+pub struct Throttled<S> {
+    inner: S,
+    throttle: Arc<DownloadThrottle>,
+}
+
+impl<S: FileService> Throttled<S> {
+    pub fn new(inner: S, throttle: Arc<DownloadThrottle>) -> Self {
+        Self { inner, throttle }
+    }
+}
+
+#[async_trait]
+impl<S: FileService> FileService for Throttled<S> {
+    type FileId = S::FileId;
+
+    fn service_name() -> &'static str {
+        S::service_name()
+    }
+
+    fn detect_link(input: &str) -> Option<Self::FileId> {
+        S::detect_link(input)
+    }
+
+    async fn get_file_info(
+        &mut self,
+        file_id: &Self::FileId,
+        config: &SecurityConfig,
+    ) -> Result<FileMeta, DownloadError> {
+        self.inner.get_file_info(file_id, config).await
+    }
+
+    async fn download(
+        &mut self,
+        file_id: &Self::FileId,
+        dest: &Path,
+        config: &SecurityConfig,
+    ) -> Result<DownloadSummary, DownloadError> {
+        let _slot = self.throttle.acquire_slot().await;
+        let config = SecurityConfig {
+            throttle: Some(self.throttle.clone()),
+            ..config.clone()
+        };
+        self.inner.download(file_id, dest, &config).await
+    }
+}
+
+/// Per-service throttles, keyed by [`FileService::service_name`].
+///
+/// Services without an entry share the [`DEFAULT_THROTTLE_KEY`] throttle, if configured.
+/// Clones share the same limits, so one instance can be handed to every downloader.
+#[derive(Debug, Clone, Default)]
+pub struct DownloadThrottles {
+    throttles: HashMap<String, Arc<DownloadThrottle>>,
+}
+
+impl DownloadThrottles {
+    pub fn new(configs: &HashMap<String, ThrottleConfig>) -> Self {
+        Self {
+            throttles: configs
+                .iter()
+                .map(|(service, config)| (service.clone(), Arc::new(DownloadThrottle::new(config))))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, service_name: &str) -> Arc<DownloadThrottle> {
+        self.throttles
+            .get(service_name)
+            .or_else(|| self.throttles.get(DEFAULT_THROTTLE_KEY))
+            .cloned()
+            .unwrap_or_else(|| Arc::new(DownloadThrottle::unlimited()))
+    }
+
+    pub fn wrap<S: FileService>(&self, service: S) -> Throttled<S> {
+        Throttled::new(service, self.get(S::service_name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_util::MockService;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_consume_limits_rate() {
+        let throttle = DownloadThrottle::new(&ThrottleConfig {
+            max_bytes_per_second: Some(1000),
+            max_concurrent_downloads: None,
+        });
+
+        let start = Instant::now();
+        // the first second's worth is allowed as a burst
+        throttle.consume(1000).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        throttle.consume(500).await;
+        throttle.consume(1500).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unlimited_never_waits() {
+        let throttle = DownloadThrottle::unlimited();
+        let start = Instant::now();
+        throttle.consume(usize::MAX).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert!(throttle.acquire_slot().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_cap() {
+        let throttle = DownloadThrottle::new(&ThrottleConfig {
+            max_bytes_per_second: None,
+            max_concurrent_downloads: Some(1),
+        });
+
+        let first = throttle.acquire_slot().await;
+        assert!(first.is_some());
+        let blocked = tokio::time::timeout(Duration::from_millis(10), throttle.acquire_slot());
+        assert!(blocked.await.is_err());
+
+        drop(first);
+        assert!(throttle.acquire_slot().await.is_some());
+    }
+
+    #[test]
+    fn test_throttles_lookup() {
+        let configs = HashMap::from([
+            (
+                "mock".to_string(),
+                ThrottleConfig {
+                    max_bytes_per_second: Some(10),
+                    ..Default::default()
+                },
+            ),
+            (
+                DEFAULT_THROTTLE_KEY.to_string(),
+                ThrottleConfig {
+                    max_bytes_per_second: Some(99),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let throttles = DownloadThrottles::new(&configs);
+
+        assert_eq!(throttles.get("mock").max_bytes_per_second(), Some(10));
+        assert_eq!(throttles.get("dropbox").max_bytes_per_second(), Some(99));
+        assert!(Arc::ptr_eq(&throttles.get("s3"), &throttles.get("gdrive")));
+
+        let wrapped = throttles.wrap(MockService);
+        assert_eq!(wrapped.throttle.max_bytes_per_second(), Some(10));
+        assert_eq!(
+            DownloadThrottles::default()
+                .get("mock")
+                .max_bytes_per_second(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_throttled_service_delegates() {
+        let mut service = DownloadThrottles::default().wrap(MockService);
+        assert_eq!(
+            Throttled::<MockService>::detect_link("mock://x"),
+            Some("test_id".to_string())
+        );
+
+        let config = SecurityConfig::default();
+        let info = service
+            .get_file_info(&"test_id".to_string(), &config)
+            .await
+            .unwrap();
+        assert_eq!(info.name, "test.zip");
+    }
+}